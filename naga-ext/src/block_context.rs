@@ -1,4 +1,4 @@
-use crate::{into_literal::IntoLiteral, LocalsExt};
+use crate::{into_literal::IntoLiteral, BlockExt, LocalsExt};
 
 /// A module and context into which an expression can be built using the [`naga_expr`] macro in this library.
 pub struct BlockContext<'a> {
@@ -156,6 +156,227 @@ impl<'a> BlockContext<'a> {
         self.append_expr(naga::Expression::GlobalVariable(global))
     }
 
+    /// Resolves the type of an expression in this function using naga's type resolution. Since this context
+    /// only has access to part of the module, returns `None` if the type depends on a global variable, function
+    /// argument or call result.
+    ///
+    /// Every expression up to the one given is resolved on each call, so prefer threading types through where
+    /// they are already known.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use naga_ext::*;
+    /// let mut module = naga::Module::default();
+    /// let u32_ty = module.types.insert_u32();
+    /// let vec2_ty = module.types.insert_anonymous(naga::TypeInner::Vector {
+    ///     size: naga::VectorSize::Bi,
+    ///     scalar: naga::Scalar::U32,
+    /// });
+    /// let (function,) = naga_ext::declare_function! {&mut module =>
+    ///     fn foo()
+    /// };
+    /// let mut ctx = naga_ext::BlockContext::from((&mut module, function));
+    /// let local1 = ctx.new_local("my_local", u32_ty, None);
+    /// let local_ptr = ctx.local_expr(local1);
+    /// let value = naga_expr!(&mut ctx => Load(local_ptr) + U32(1));
+    /// let vector = naga_expr!(&mut ctx => vec2_ty(value, value));
+    ///
+    /// assert!(matches!(ctx.expr_type(local_ptr), Some(naga::TypeInner::Pointer { .. })));
+    /// assert_eq!(ctx.expr_type(value), Some(naga::TypeInner::Scalar(naga::Scalar::U32)));
+    /// assert!(matches!(ctx.expr_type(vector), Some(naga::TypeInner::Vector { .. })));
+    /// ```
+    pub fn expr_type(&self, expr: naga::Handle<naga::Expression>) -> Option<naga::TypeInner> {
+        let special_types = naga::SpecialTypes::default();
+        let global_vars = naga::Arena::new();
+        let functions = naga::Arena::new();
+
+        let mut resolutions: Vec<Option<naga::proc::TypeResolution>> = Vec::new();
+        for (handle, expression) in self.expressions.iter() {
+            let resolution = match expression {
+                naga::Expression::GlobalVariable(_)
+                | naga::Expression::FunctionArgument(_)
+                | naga::Expression::CallResult(_)
+                | naga::Expression::RayQueryProceedResult
+                | naga::Expression::RayQueryGetIntersection { .. } => None,
+                _ => {
+                    let resolve_ctx = naga::proc::ResolveContext {
+                        constants: &*self.constants,
+                        types: &*self.types,
+                        special_types: &special_types,
+                        global_vars: &global_vars,
+                        local_vars: &*self.locals,
+                        functions: &functions,
+                        arguments: &[],
+                    };
+                    resolve_ctx
+                        .resolve(expression, |past| {
+                            resolutions[past.index()].as_ref().ok_or_else(|| {
+                                naga::proc::ResolveError::IncompatibleOperands(format!(
+                                    "{:?} depends on an object outside of this context",
+                                    past
+                                ))
+                            })
+                        })
+                        .ok()
+                }
+            };
+            resolutions.push(resolution);
+
+            if handle == expr {
+                break;
+            }
+        }
+
+        let resolution = resolutions.get(expr.index())?.as_ref()?;
+        Some(resolution.inner_with(&*self.types).clone())
+    }
+
+    /// Builds a swizzle of the given vector from a pattern such as `"x"`, `"zyx"` or `"xyzw"`. Single component
+    /// patterns produce a [`naga::Expression::AccessIndex`], while longer patterns produce a [`naga::Expression::Swizzle`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern is empty, longer than 4 components, or contains a character other than `x`, `y`, `z` or `w`.
+    /// Swizzles written with [`naga_expr`] are checked with [`is_swizzle_pattern`] when compiling instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use naga_ext::*;
+    /// let mut module = naga::Module::default();
+    /// let vec4_ty = module.types.insert_anonymous(naga::TypeInner::Vector {
+    ///     size: naga::VectorSize::Quad,
+    ///     scalar: naga::Scalar::U32,
+    /// });
+    /// let vec3_ty = module.types.insert_anonymous(naga::TypeInner::Vector {
+    ///     size: naga::VectorSize::Tri,
+    ///     scalar: naga::Scalar::U32,
+    /// });
+    /// let (function, arg1) = naga_ext::declare_function! {&mut module =>
+    ///     fn foo(arg1: vec4_ty) -> vec3_ty
+    /// };
+    /// let mut ctx = naga_ext::BlockContext::from((&mut module, function));
+    /// let reversed = ctx.swizzle_expr(arg1, "zyx");
+    /// ctx.result(reversed);
+    /// # naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty()).validate(&mut module).unwrap();
+    /// ```
+    ///
+    /// The above code results in the following shader:
+    ///
+    /// ```wgsl
+    /// fn foo(arg1: vec4<u32>) -> vec3<u32> {
+    ///     return arg1.zyx;
+    /// }
+    /// ```
+    pub fn swizzle_expr(
+        &mut self,
+        vector: naga::Handle<naga::Expression>,
+        pattern: &str,
+    ) -> naga::Handle<naga::Expression> {
+        let components = pattern
+            .chars()
+            .map(|c| match c {
+                'x' => naga::SwizzleComponent::X,
+                'y' => naga::SwizzleComponent::Y,
+                'z' => naga::SwizzleComponent::Z,
+                'w' => naga::SwizzleComponent::W,
+                _ => panic!("invalid swizzle component `{}` in pattern `{}`", c, pattern),
+            })
+            .collect::<Vec<_>>();
+
+        let size = match components.len() {
+            1 => {
+                return self.append_expr(naga::Expression::AccessIndex {
+                    base: vector,
+                    index: components[0].index(),
+                })
+            }
+            2 => naga::VectorSize::Bi,
+            3 => naga::VectorSize::Tri,
+            4 => naga::VectorSize::Quad,
+            _ => panic!(
+                "swizzle pattern `{}` must have between 1 and 4 components",
+                pattern
+            ),
+        };
+
+        let mut swizzle_pattern = [naga::SwizzleComponent::X; 4];
+        swizzle_pattern[..components.len()].copy_from_slice(&components);
+
+        self.append_expr(naga::Expression::Swizzle {
+            size,
+            vector,
+            pattern: swizzle_pattern,
+        })
+    }
+
+    /// Builds an array from the given components, inserting an array type with one element per component. The
+    /// element type is `element_ty` if given, otherwise it is resolved from the first component as with
+    /// [`BlockContext::expr_type`]. Components may themselves be arrays or vectors, giving nested arrays.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no components, or if no element type is given and the type of the first component
+    /// can't be resolved.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use naga_ext::*;
+    /// let mut module = naga::Module::default();
+    /// let (function,) = naga_ext::declare_function! {&mut module =>
+    ///     fn foo()
+    /// };
+    /// let mut ctx = naga_ext::BlockContext::from((&mut module, function));
+    /// let first = ctx.literal_expr_from(1i32);
+    /// let second = ctx.literal_expr_from(2i32);
+    /// let array = ctx.array_expr(None, vec![first, second]);
+    ///
+    /// assert!(matches!(ctx.expr_type(array), Some(naga::TypeInner::Array { .. })));
+    /// # naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty()).validate(&mut module).unwrap();
+    /// ```
+    pub fn array_expr(
+        &mut self,
+        element_ty: Option<naga::Handle<naga::Type>>,
+        components: Vec<naga::Handle<naga::Expression>>,
+    ) -> naga::Handle<naga::Expression> {
+        let len = u32::try_from(components.len())
+            .ok()
+            .and_then(std::num::NonZeroU32::new)
+            .expect("arrays must have at least one element");
+        let element_ty = element_ty.unwrap_or_else(|| {
+            let inner = self
+                .expr_type(components[0])
+                .expect("the element type of an array must be given if it can't be resolved");
+            self.types
+                .insert(naga::Type { name: None, inner }, naga::Span::UNDEFINED)
+        });
+
+        let mut layouter = naga::proc::Layouter::default();
+        layouter
+            .update(naga::proc::GlobalCtx {
+                types: &*self.types,
+                constants: &*self.constants,
+                const_expressions: &*self.const_expressions,
+            })
+            .expect("array element types must have a known layout");
+        let stride = layouter[element_ty].to_stride();
+
+        let ty = self.types.insert(
+            naga::Type {
+                name: None,
+                inner: naga::TypeInner::Array {
+                    base: element_ty,
+                    size: naga::ArraySize::Constant(len),
+                    stride,
+                },
+            },
+            naga::Span::UNDEFINED,
+        );
+        self.append_expr(naga::Expression::Compose { ty, components })
+    }
+
     /// Builds a [`naga::Statement::If`] using the given condition.
     ///
     /// # Example
@@ -188,6 +409,61 @@ impl<'a> BlockContext<'a> {
         }
     }
 
+    /// Builds a [`naga::Statement::Switch`] on the given selector. Cases are added with [`Switch::case`], and the
+    /// statement is completed by a call to [`Switch::default`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use naga_ext::*;
+    /// let mut module = naga::Module::default();
+    /// let u32_ty = module.types.insert_u32();
+    /// let (function, arg1) = naga_ext::declare_function! {&mut module =>
+    ///     fn foo(arg1: u32_ty) -> u32_ty
+    /// };
+    /// let mut ctx = naga_ext::BlockContext::from((&mut module, function));
+    /// ctx.switch(arg1)
+    ///     .case(0u32, |mut ctx| {
+    ///         let const_10u32 = ctx.literal_expr_from(10u32);
+    ///         ctx.result(const_10u32)
+    ///     })
+    ///     .case(1u32, |mut ctx| {
+    ///         let const_20u32 = ctx.literal_expr_from(20u32);
+    ///         ctx.result(const_20u32)
+    ///     })
+    ///     .default(|mut ctx| {
+    ///         let const_0u32 = ctx.literal_expr_from(0u32);
+    ///         ctx.result(const_0u32)
+    ///     });
+    /// # naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty()).validate(&mut module).unwrap();
+    /// ```
+    ///
+    /// The above code results in the following shader:
+    ///
+    /// ```wgsl
+    /// fn foo(arg1: u32) -> u32 {
+    ///     switch arg1 {
+    ///         case 0u: {
+    ///             return 10u;
+    ///         }
+    ///         case 1u: {
+    ///             return 20u;
+    ///         }
+    ///         default: {
+    ///             return 0u;
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    #[inline(always)]
+    pub fn switch<'b>(&'b mut self, selector: naga::Handle<naga::Expression>) -> Switch<'b> {
+        Switch {
+            ctx: self.into(),
+            selector,
+            cases: Vec::new(),
+        }
+    }
+
     /// Builds a [`naga::Statement::Store`] using the given pointer and value.
     ///
     /// # Example
@@ -216,6 +492,44 @@ impl<'a> BlockContext<'a> {
             naga::Span::UNDEFINED,
         )
     }
+    /// Adds `by` to the value behind `pointer` with a load, an add and a store, giving the incremented value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use naga_ext::*;
+    /// let mut module = naga::Module::default();
+    /// let u32_ty = module.types.insert_u32();
+    /// let (function,) = naga_ext::declare_function! {&mut module =>
+    ///     fn foo() -> u32_ty
+    /// };
+    /// let mut ctx = naga_ext::BlockContext::from((&mut module, function));
+    /// let counter = ctx.new_local("counter", u32_ty, None);
+    /// let counter = ctx.local_expr(counter);
+    /// let one = ctx.literal_expr_from(1u32);
+    /// let res = ctx.increment(counter, one);
+    /// ctx.result(res);
+    /// # naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty()).validate(&mut module).unwrap();
+    /// ```
+    ///
+    /// The above code results in the following shader:
+    ///
+    /// ```wgsl
+    /// fn foo() -> u32 {
+    ///     var counter: u32;
+    ///     let incremented = counter + 1u;
+    ///     counter = incremented;
+    ///     return incremented;
+    /// }
+    /// ```
+    #[inline(always)]
+    pub fn increment(
+        &mut self,
+        pointer: naga::Handle<naga::Expression>,
+        by: naga::Handle<naga::Expression>,
+    ) -> naga::Handle<naga::Expression> {
+        self.block.push_increment(pointer, self.expressions, by)
+    }
     /// Builds a [`naga::Statement::Loop`] without a continuing or break if block.
     ///
     /// # Example
@@ -263,6 +577,79 @@ impl<'a> BlockContext<'a> {
         return res;
     }
 
+    /// Builds a [`naga::Statement::Loop`] which exits at the end of any iteration where the condition is true.
+    /// The condition is built into the loop's continuing block, so is evaluated after the body and on every
+    /// `continue`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use naga_ext::*;
+    /// let mut module = naga::Module::default();
+    /// let u32_ty = module.types.insert_u32();
+    /// let (function, arg1) = naga_ext::declare_function! {&mut module =>
+    ///     fn foo(arg1: u32_ty)
+    /// };
+    /// let mut ctx = naga_ext::BlockContext::from((&mut module, function));
+    /// let local1 = ctx.new_local("my_local", u32_ty, None);
+    /// let local_ptr = ctx.local_expr(local1);
+    /// ctx.store(local_ptr, arg1);
+    /// ctx.loop_with_break_if(
+    ///     |ctx| naga_expr!(ctx => Load(local_ptr) == U32(0)),
+    ///     |mut ctx| {
+    ///         let decremented = naga_expr!(&mut ctx => Load(local_ptr) - U32(1));
+    ///         ctx.store(local_ptr, decremented);
+    ///     },
+    /// );
+    /// # naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty()).validate(&mut module).unwrap();
+    /// ```
+    ///
+    /// The above code results in the following shader:
+    ///
+    /// ```wgsl
+    /// fn foo(arg1: u32) {
+    ///     var my_local: u32;
+    ///     my_local = arg1;
+    ///     loop {
+    ///         my_local = (my_local - 1u);
+    ///         continuing {
+    ///             break if (my_local == 0u);
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    #[inline(always)]
+    pub fn loop_with_break_if<R>(
+        &mut self,
+        make_cond: impl FnOnce(&mut BlockContext<'_>) -> naga::Handle<naga::Expression>,
+        body: impl FnOnce(BlockContext<'_>) -> R,
+    ) -> R {
+        let mut body_block = naga::Block::new();
+        let body_ctx = BlockContext {
+            block: &mut body_block,
+            ..self.into()
+        };
+        let res = body(body_ctx);
+
+        let mut continuing = naga::Block::new();
+        let mut continuing_ctx = BlockContext {
+            block: &mut continuing,
+            ..self.into()
+        };
+        let break_if = make_cond(&mut continuing_ctx);
+
+        self.block.push(
+            naga::Statement::Loop {
+                body: body_block,
+                continuing,
+                break_if: Some(break_if),
+            },
+            naga::Span::UNDEFINED,
+        );
+
+        return res;
+    }
+
     /// Calls a function with [`naga::Statement::Call`], placing the result in an expression which is returned.
     ///
     /// # Example
@@ -550,3 +937,155 @@ impl<'a> TestThen<'a> {
         }
     }
 }
+
+/// Whether the pattern can be given to [`BlockContext::swizzle_expr`], i.e. whether it is between 1 and 4 of the
+/// components `x`, `y`, `z` and `w`. This is a `const fn` so that [`naga_expr`] can reject invalid swizzles when
+/// compiling.
+///
+/// # Example
+///
+/// ```
+/// # use naga_ext::*;
+/// let mut module = naga::Module::default();
+/// let vec4_ty = module.types.insert_vecn(naga::Scalar::U32, naga::VectorSize::Quad);
+/// let (function, value) = naga_ext::declare_function! {&mut module =>
+///     fn foo(value: vec4_ty) -> vec4_ty
+/// };
+/// let mut ctx = naga_ext::BlockContext::from((&mut module, function));
+/// let reversed = naga_expr!(&mut ctx => value.wzyx);
+/// ctx.result(reversed);
+/// # naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty()).validate(&mut module).unwrap();
+/// ```
+///
+/// Components other than `x`, `y`, `z` and `w` fail to compile:
+///
+/// ```compile_fail
+/// # use naga_ext::*;
+/// # let mut module = naga::Module::default();
+/// # let vec4_ty = module.types.insert_vecn(naga::Scalar::U32, naga::VectorSize::Quad);
+/// # let (function, value) = naga_ext::declare_function! {&mut module =>
+/// #     fn foo(value: vec4_ty) -> vec4_ty
+/// # };
+/// # let mut ctx = naga_ext::BlockContext::from((&mut module, function));
+/// let reversed = naga_expr!(&mut ctx => value.wzyq);
+/// # ctx.result(reversed);
+/// ```
+///
+/// As do swizzles of more than 4 components:
+///
+/// ```compile_fail
+/// # use naga_ext::*;
+/// # let mut module = naga::Module::default();
+/// # let vec4_ty = module.types.insert_vecn(naga::Scalar::U32, naga::VectorSize::Quad);
+/// # let (function, value) = naga_ext::declare_function! {&mut module =>
+/// #     fn foo(value: vec4_ty) -> vec4_ty
+/// # };
+/// # let mut ctx = naga_ext::BlockContext::from((&mut module, function));
+/// let reversed = naga_expr!(&mut ctx => value.wzyxw);
+/// # ctx.result(reversed);
+/// ```
+pub const fn is_swizzle_pattern(pattern: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    if pattern.is_empty() || pattern.len() > 4 {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < pattern.len() {
+        if !matches!(pattern[i], b'x' | b'y' | b'z' | b'w') {
+            return false;
+        }
+        i += 1;
+    }
+
+    true
+}
+
+/// A value which can be used to label a case within a [`naga::Statement::Switch`].
+pub trait SwitchCaseValue {
+    fn into_switch_value(self) -> naga::SwitchValue;
+}
+
+impl SwitchCaseValue for i32 {
+    fn into_switch_value(self) -> naga::SwitchValue {
+        naga::SwitchValue::I32(self)
+    }
+}
+
+impl SwitchCaseValue for u32 {
+    fn into_switch_value(self) -> naga::SwitchValue {
+        naga::SwitchValue::U32(self)
+    }
+}
+
+impl SwitchCaseValue for naga::SwitchValue {
+    fn into_switch_value(self) -> naga::SwitchValue {
+        self
+    }
+}
+
+/// Built by a call to [`BlockContext::switch`], and must be consumed by a call to [`Switch::default`].
+#[must_use]
+pub struct Switch<'a> {
+    ctx: BlockContext<'a>,
+    selector: naga::Handle<naga::Expression>,
+    cases: Vec<naga::SwitchCase>,
+}
+
+impl<'a> Switch<'a> {
+    /// Populates a block to be run if the selector is equal to the given value.
+    #[inline]
+    pub fn case(self, value: impl SwitchCaseValue, f: impl FnOnce(BlockContext<'_>)) -> Self {
+        self.try_case::<!>(value, |ctx| Ok(f(ctx))).into_ok()
+    }
+
+    /// Tries to populate a block to be run if the selector is equal to the given value.
+    #[inline]
+    pub fn try_case<E>(
+        mut self,
+        value: impl SwitchCaseValue,
+        f: impl FnOnce(BlockContext<'_>) -> Result<(), E>,
+    ) -> Result<Self, E> {
+        let mut body = naga::Block::new();
+        let case_ctx = BlockContext {
+            block: &mut body,
+            ..(&mut self.ctx).into()
+        };
+        f(case_ctx)?;
+
+        self.cases.push(naga::SwitchCase {
+            value: value.into_switch_value(),
+            body,
+            fall_through: false,
+        });
+
+        Ok(self)
+    }
+
+    /// Populates a block to be run if the selector matched none of the cases, and pushes the completed switch
+    /// statement.
+    #[inline]
+    pub fn default<R>(mut self, f: impl FnOnce(BlockContext<'_>) -> R) -> R {
+        let mut body = naga::Block::new();
+        let default_ctx = BlockContext {
+            block: &mut body,
+            ..(&mut self.ctx).into()
+        };
+        let res = f(default_ctx);
+
+        self.cases.push(naga::SwitchCase {
+            value: naga::SwitchValue::Default,
+            body,
+            fall_through: false,
+        });
+        self.ctx.block.push(
+            naga::Statement::Switch {
+                selector: self.selector,
+                cases: self.cases,
+            },
+            naga::Span::UNDEFINED,
+        );
+
+        return res;
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+
+use naga::Handle;
+
+/// Maps handles into a module that was merged into another with [`crate::ModuleExt::merge`] to handles to the same
+/// objects in the module that it was merged into.
+#[derive(Debug, Clone, Default)]
+pub struct HandleRemap {
+    types: HashMap<Handle<naga::Type>, Handle<naga::Type>>,
+    constants: HashMap<Handle<naga::Constant>, Handle<naga::Constant>>,
+    const_expressions: HashMap<Handle<naga::Expression>, Handle<naga::Expression>>,
+    globals: HashMap<Handle<naga::GlobalVariable>, Handle<naga::GlobalVariable>>,
+    functions: HashMap<Handle<naga::Function>, Handle<naga::Function>>,
+}
+
+impl HandleRemap {
+    /// Gets the new handle of a type from the merged module.
+    ///
+    /// # Panics
+    /// Panics if the handle does not refer to a type in the merged module.
+    pub fn ty(&self, old: Handle<naga::Type>) -> Handle<naga::Type> {
+        self.types[&old]
+    }
+
+    /// Gets the new handle of a constant from the merged module.
+    ///
+    /// # Panics
+    /// Panics if the handle does not refer to a constant in the merged module.
+    pub fn constant(&self, old: Handle<naga::Constant>) -> Handle<naga::Constant> {
+        self.constants[&old]
+    }
+
+    /// Gets the new handle of a global variable from the merged module.
+    ///
+    /// # Panics
+    /// Panics if the handle does not refer to a global variable in the merged module.
+    pub fn global(&self, old: Handle<naga::GlobalVariable>) -> Handle<naga::GlobalVariable> {
+        self.globals[&old]
+    }
+
+    /// Gets the new handle of a function from the merged module.
+    ///
+    /// # Panics
+    /// Panics if the handle does not refer to a function in the merged module.
+    pub fn function(&self, old: Handle<naga::Function>) -> Handle<naga::Function> {
+        self.functions[&old]
+    }
+
+    fn const_expression(&self, old: Handle<naga::Expression>) -> Handle<naga::Expression> {
+        self.const_expressions[&old]
+    }
+
+    /// Appends everything in `other` to `module`. Every object in `other` only refers to objects declared before
+    /// it, other than constant initialisers, so most arenas can be appended in a single pass.
+    pub(crate) fn merge(module: &mut naga::Module, other: naga::Module) -> Self {
+        let mut remap = Self::default();
+
+        for (old, ty) in other.types.iter() {
+            let ty = naga::Type {
+                name: ty.name.clone(),
+                inner: remap.type_inner(&ty.inner),
+            };
+            let new = module.types.insert(ty, other.types.get_span(old));
+            remap.types.insert(old, new);
+        }
+
+        // Constant expressions may refer to constants, so constants are appended first and their initialisers are
+        // fixed up once the constant expressions have been appended
+        for (old, constant) in other.constants.iter() {
+            let mut constant = constant.clone();
+            constant.ty = remap.ty(constant.ty);
+            let new = module
+                .constants
+                .append(constant, other.constants.get_span(old));
+            remap.constants.insert(old, new);
+        }
+        for (old, expression) in other.const_expressions.iter() {
+            let mut expression = expression.clone();
+            remap.expression(&mut expression);
+            remap.const_subexpressions(&mut expression);
+            let new = module
+                .const_expressions
+                .append(expression, other.const_expressions.get_span(old));
+            remap.const_expressions.insert(old, new);
+        }
+        for new in remap.constants.values() {
+            let constant = module.constants.get_mut(*new);
+            constant.init = remap.const_expression(constant.init);
+        }
+
+        for (old, global) in other.global_variables.iter() {
+            let mut global = global.clone();
+            global.ty = remap.ty(global.ty);
+            global.init = global.init.map(|init| remap.const_expression(init));
+            let new = module
+                .global_variables
+                .append(global, other.global_variables.get_span(old));
+            remap.globals.insert(old, new);
+        }
+
+        // Functions are all declared before any bodies are remapped, so that calls can be redirected regardless of
+        // the order that functions were declared in
+        for (old, function) in other.functions.iter() {
+            let new = module
+                .functions
+                .append(function.clone(), other.functions.get_span(old));
+            remap.functions.insert(old, new);
+        }
+        for new in remap.functions.values() {
+            remap.function_body(module.functions.get_mut(*new));
+        }
+
+        for mut entry_point in other.entry_points {
+            remap.function_body(&mut entry_point.function);
+            module.entry_points.push(entry_point);
+        }
+
+        return remap;
+    }
+
+    fn type_inner(&self, inner: &naga::TypeInner) -> naga::TypeInner {
+        let mut inner = inner.clone();
+        match &mut inner {
+            naga::TypeInner::Pointer { base, .. }
+            | naga::TypeInner::Array { base, .. }
+            | naga::TypeInner::BindingArray { base, .. } => *base = self.ty(*base),
+            naga::TypeInner::Struct { members, .. } => {
+                for member in members {
+                    member.ty = self.ty(member.ty);
+                }
+            }
+            _ => {}
+        }
+        return inner;
+    }
+
+    /// Remaps the module level objects referred to by a function. Expression handles index the function's own
+    /// arena, which was moved wholesale, so only handles into module level arenas need updating.
+    fn function_body(&self, function: &mut naga::Function) {
+        for argument in function.arguments.iter_mut() {
+            argument.ty = self.ty(argument.ty);
+        }
+        if let Some(result) = &mut function.result {
+            result.ty = self.ty(result.ty);
+        }
+        for (_, local) in function.local_variables.iter_mut() {
+            local.ty = self.ty(local.ty);
+        }
+        for (_, expression) in function.expressions.iter_mut() {
+            self.expression(expression);
+        }
+        self.block(&mut function.body);
+    }
+
+    /// Remaps the module level objects referred to by an expression in any arena.
+    fn expression(&self, expression: &mut naga::Expression) {
+        match expression {
+            naga::Expression::Constant(constant) => *constant = self.constant(*constant),
+            naga::Expression::GlobalVariable(global) => *global = self.global(*global),
+            naga::Expression::CallResult(function) => *function = self.function(*function),
+            naga::Expression::ZeroValue(ty)
+            | naga::Expression::Compose { ty, .. }
+            | naga::Expression::AtomicResult { ty, .. }
+            | naga::Expression::WorkGroupUniformLoadResult { ty } => *ty = self.ty(*ty),
+            naga::Expression::ImageSample {
+                offset: Some(offset),
+                ..
+            } => *offset = self.const_expression(*offset),
+            _ => {}
+        }
+    }
+
+    /// Remaps the other constant expressions referred to by a constant expression.
+    fn const_subexpressions(&self, expression: &mut naga::Expression) {
+        let remap =
+            |handle: &mut Handle<naga::Expression>| *handle = self.const_expression(*handle);
+        match expression {
+            naga::Expression::Compose { components, .. } => components.iter_mut().for_each(remap),
+            naga::Expression::Splat { value, .. } => remap(value),
+            naga::Expression::Swizzle { vector, .. } => remap(vector),
+            naga::Expression::Access { base, index } => {
+                remap(base);
+                remap(index);
+            }
+            naga::Expression::AccessIndex { base, .. } => remap(base),
+            naga::Expression::Unary { expr, .. } | naga::Expression::As { expr, .. } => remap(expr),
+            naga::Expression::Binary { left, right, .. } => {
+                remap(left);
+                remap(right);
+            }
+            naga::Expression::Select {
+                condition,
+                accept,
+                reject,
+            } => {
+                remap(condition);
+                remap(accept);
+                remap(reject);
+            }
+            naga::Expression::Relational { argument, .. } => remap(argument),
+            naga::Expression::Math {
+                arg,
+                arg1,
+                arg2,
+                arg3,
+                ..
+            } => {
+                remap(arg);
+                for arg in [arg1, arg2, arg3].into_iter().flatten() {
+                    remap(arg);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn block(&self, block: &mut naga::Block) {
+        for statement in block.iter_mut() {
+            match statement {
+                naga::Statement::Call { function, .. } => *function = self.function(*function),
+                naga::Statement::Block(block) => self.block(block),
+                naga::Statement::If { accept, reject, .. } => {
+                    self.block(accept);
+                    self.block(reject);
+                }
+                naga::Statement::Switch { cases, .. } => {
+                    for case in cases {
+                        self.block(&mut case.body);
+                    }
+                }
+                naga::Statement::Loop {
+                    body, continuing, ..
+                } => {
+                    self.block(body);
+                    self.block(continuing);
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -20,6 +20,13 @@ impl IntoLiteral for u32 {
     }
 }
 
+#[sealed]
+impl IntoLiteral for i64 {
+    fn into_literal(self) -> naga::Literal {
+        naga::Literal::I64(self)
+    }
+}
+
 #[sealed]
 impl IntoLiteral for f32 {
     fn into_literal(self) -> naga::Literal {
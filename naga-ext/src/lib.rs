@@ -3,9 +3,13 @@
 
 //! Provide a collection of shorthand and opinionated methods extending base naga objects.
 pub mod block_context;
+mod handle_remap;
 pub mod into_literal;
+pub mod prelude;
 pub use block_context::BlockContext;
+pub use handle_remap::HandleRemap;
 
+use into_literal::IntoLiteral;
 use sealed::sealed;
 
 #[sealed]
@@ -16,6 +20,10 @@ pub trait ModuleExt {
     fn new_function(&mut self, definition: FunctionSignature) -> naga::Handle<naga::Function>;
     /// Shorthand for `module.functions.get_mut(handle)`
     fn fn_mut(&mut self, handle: naga::Handle<naga::Function>) -> &mut naga::Function;
+    /// Appends the types, constants, global variables, functions and entry points of another module to this one.
+    /// Handles into `other` are no longer valid once it has been merged, so the returned map gives the handle of
+    /// each merged object within this module.
+    fn merge(&mut self, other: naga::Module) -> HandleRemap;
 }
 
 #[sealed]
@@ -29,12 +37,19 @@ impl ModuleExt for naga::Module {
     fn fn_mut(&mut self, handle: naga::Handle<naga::Function>) -> &mut naga::Function {
         self.functions.get_mut(handle)
     }
+    fn merge(&mut self, other: naga::Module) -> HandleRemap {
+        HandleRemap::merge(self, other)
+    }
 }
 
 #[sealed]
 pub trait FunctionsExt {
     fn new_empty_function(&mut self, name: String) -> naga::Handle<naga::Function>;
     fn new_function(&mut self, definition: FunctionSignature) -> naga::Handle<naga::Function>;
+    /// Finds the first function with the given name, if any.
+    fn get_by_name(&self, name: &str) -> Option<naga::Handle<naga::Function>>;
+    /// Iterates over every function that has a name, in the order that they were declared.
+    fn iter_named(&self) -> impl Iterator<Item = (naga::Handle<naga::Function>, &str)>;
 }
 
 #[sealed]
@@ -66,6 +81,15 @@ impl FunctionsExt for naga::Arena<naga::Function> {
         let handle = self.append(new_function, naga::Span::UNDEFINED);
         return handle;
     }
+    fn get_by_name(&self, name: &str) -> Option<naga::Handle<naga::Function>> {
+        self.iter_named()
+            .find(|(_, function_name)| *function_name == name)
+            .map(|(handle, _)| handle)
+    }
+    fn iter_named(&self) -> impl Iterator<Item = (naga::Handle<naga::Function>, &str)> {
+        self.iter()
+            .filter_map(|(handle, function)| Some((handle, function.name.as_deref()?)))
+    }
 }
 
 #[sealed]
@@ -75,6 +99,20 @@ pub trait ConstantsExt {
         ty: naga::Handle<naga::Type>,
         init: naga::Handle<naga::Expression>,
     ) -> naga::Handle<naga::Constant>;
+    fn append_named(
+        &mut self,
+        name: impl Into<String>,
+        ty: naga::Handle<naga::Type>,
+        init: naga::Handle<naga::Expression>,
+    ) -> naga::Handle<naga::Constant>;
+    /// Finds an anonymous constant of the given type initialised to the given literal, appending one if none
+    /// exists yet. Floats are compared bitwise, so `-0.0` and `0.0` are kept separate.
+    fn get_or_append_scalar(
+        &mut self,
+        const_expressions: &mut naga::Arena<naga::Expression>,
+        ty: naga::Handle<naga::Type>,
+        init: impl IntoLiteral,
+    ) -> naga::Handle<naga::Constant>;
 }
 
 #[sealed]
@@ -94,6 +132,57 @@ impl ConstantsExt for naga::Arena<naga::Constant> {
             naga::Span::UNDEFINED,
         )
     }
+    fn append_named(
+        &mut self,
+        name: impl Into<String>,
+        ty: naga::Handle<naga::Type>,
+        init: naga::Handle<naga::Expression>,
+    ) -> naga::Handle<naga::Constant> {
+        self.append(
+            naga::Constant {
+                name: Some(name.into()),
+                ty,
+                r#override: naga::Override::None,
+                init,
+            },
+            naga::Span::UNDEFINED,
+        )
+    }
+    fn get_or_append_scalar(
+        &mut self,
+        const_expressions: &mut naga::Arena<naga::Expression>,
+        ty: naga::Handle<naga::Type>,
+        init: impl IntoLiteral,
+    ) -> naga::Handle<naga::Constant> {
+        let literal = init.into_literal();
+        let existing = self.iter().find(|(_, constant)| {
+            constant.name.is_none()
+                && matches!(constant.r#override, naga::Override::None)
+                && constant.ty == ty
+                && matches!(
+                    &const_expressions[constant.init],
+                    naga::Expression::Literal(existing) if same_literal(existing, &literal)
+                )
+        });
+        if let Some((handle, _)) = existing {
+            return handle;
+        }
+
+        let init = const_expressions.append_literal(literal);
+        self.append_anonymous(ty, init)
+    }
+}
+
+/// Compares literals by their bit patterns, rather than by their numeric values.
+fn same_literal(lhs: &naga::Literal, rhs: &naga::Literal) -> bool {
+    match (lhs, rhs) {
+        (naga::Literal::F32(lhs), naga::Literal::F32(rhs)) => lhs.to_bits() == rhs.to_bits(),
+        (naga::Literal::F64(lhs), naga::Literal::F64(rhs))
+        | (naga::Literal::AbstractFloat(lhs), naga::Literal::AbstractFloat(rhs)) => {
+            lhs.to_bits() == rhs.to_bits()
+        }
+        _ => lhs == rhs,
+    }
 }
 
 #[sealed]
@@ -101,6 +190,11 @@ pub trait TypesExt {
     fn insert_anonymous(&mut self, ty: naga::TypeInner) -> naga::Handle<naga::Type>;
 
     fn insert_scalar(&mut self, scalar: naga::Scalar) -> naga::Handle<naga::Type>;
+    fn insert_vecn(
+        &mut self,
+        scalar: naga::Scalar,
+        size: naga::VectorSize,
+    ) -> naga::Handle<naga::Type>;
 
     fn insert_i32(&mut self) -> naga::Handle<naga::Type>;
     fn insert_i64(&mut self) -> naga::Handle<naga::Type>;
@@ -109,6 +203,11 @@ pub trait TypesExt {
     fn insert_f64(&mut self) -> naga::Handle<naga::Type>;
 
     fn insert_bool(&mut self) -> naga::Handle<naga::Type>;
+
+    fn insert_atomic(&mut self, scalar: naga::Scalar) -> naga::Handle<naga::Type>;
+
+    fn insert_atomic_i32(&mut self) -> naga::Handle<naga::Type>;
+    fn insert_atomic_u32(&mut self) -> naga::Handle<naga::Type>;
 }
 
 #[sealed]
@@ -126,6 +225,13 @@ impl TypesExt for naga::UniqueArena<naga::Type> {
     fn insert_scalar(&mut self, scalar: naga::Scalar) -> naga::Handle<naga::Type> {
         self.insert_anonymous(naga::TypeInner::Scalar(scalar))
     }
+    fn insert_vecn(
+        &mut self,
+        scalar: naga::Scalar,
+        size: naga::VectorSize,
+    ) -> naga::Handle<naga::Type> {
+        self.insert_anonymous(naga::TypeInner::Vector { size, scalar })
+    }
 
     fn insert_i32(&mut self) -> naga::Handle<naga::Type> {
         self.insert_scalar(naga::Scalar::I32)
@@ -146,6 +252,17 @@ impl TypesExt for naga::UniqueArena<naga::Type> {
     fn insert_bool(&mut self) -> naga::Handle<naga::Type> {
         self.insert_scalar(naga::Scalar::BOOL)
     }
+
+    fn insert_atomic(&mut self, scalar: naga::Scalar) -> naga::Handle<naga::Type> {
+        self.insert_anonymous(naga::TypeInner::Atomic(scalar))
+    }
+
+    fn insert_atomic_i32(&mut self) -> naga::Handle<naga::Type> {
+        self.insert_atomic(naga::Scalar::I32)
+    }
+    fn insert_atomic_u32(&mut self) -> naga::Handle<naga::Type> {
+        self.insert_atomic(naga::Scalar::U32)
+    }
 }
 
 #[sealed]
@@ -184,6 +301,42 @@ impl LocalsExt for naga::Arena<naga::LocalVariable> {
     }
 }
 
+#[sealed]
+pub trait GlobalsExt {
+    // Shorthand handle generation
+    fn append_global_var(
+        &mut self,
+        name: impl Into<String>,
+        space: naga::AddressSpace,
+        binding: Option<naga::ResourceBinding>,
+        ty: naga::Handle<naga::Type>,
+        init: Option<naga::Handle<naga::Expression>>,
+    ) -> naga::Handle<naga::GlobalVariable>;
+}
+
+#[sealed]
+impl GlobalsExt for naga::Arena<naga::GlobalVariable> {
+    fn append_global_var(
+        &mut self,
+        name: impl Into<String>,
+        space: naga::AddressSpace,
+        binding: Option<naga::ResourceBinding>,
+        ty: naga::Handle<naga::Type>,
+        init: Option<naga::Handle<naga::Expression>>,
+    ) -> naga::Handle<naga::GlobalVariable> {
+        self.append(
+            naga::GlobalVariable {
+                name: Some(name.into()),
+                space,
+                binding,
+                ty,
+                init,
+            },
+            naga::Span::UNDEFINED,
+        )
+    }
+}
+
 #[sealed]
 pub trait ExpressionsExt {
     // Shorthand expression generation
@@ -210,6 +363,9 @@ pub trait ExpressionsExt {
         pointer: naga::Handle<naga::Expression>,
     ) -> naga::Handle<naga::Expression>;
     fn append_literal(&mut self, literal: naga::Literal) -> naga::Handle<naga::Expression>;
+    /// Appends the zero value of any constructible type, without needing to build it from literals.
+    fn append_zero_value(&mut self, ty: naga::Handle<naga::Type>)
+        -> naga::Handle<naga::Expression>;
 
     fn append_u32(&mut self, value: u32) -> naga::Handle<naga::Expression>;
     fn append_i32(&mut self, value: i32) -> naga::Handle<naga::Expression>;
@@ -217,6 +373,21 @@ pub trait ExpressionsExt {
     fn append_f32(&mut self, value: f32) -> naga::Handle<naga::Expression>;
     fn append_f64(&mut self, value: f64) -> naga::Handle<naga::Expression>;
     fn append_bool(&mut self, value: bool) -> naga::Handle<naga::Expression>;
+    fn append_abstract_int(&mut self, value: i64) -> naga::Handle<naga::Expression>;
+    fn append_abstract_float(&mut self, value: f64) -> naga::Handle<naga::Expression>;
+
+    /// Composes a `vec4<u32>` of the given type from four words, with the least significant word first.
+    fn append_v128(
+        &mut self,
+        ty: naga::Handle<naga::Type>,
+        words: [u32; 4],
+    ) -> naga::Handle<naga::Expression>;
+    /// Composes a `vec4<u32>` of the given type from the little-endian words of a `u128`.
+    fn append_u128(
+        &mut self,
+        ty: naga::Handle<naga::Type>,
+        value: u128,
+    ) -> naga::Handle<naga::Expression>;
 }
 
 #[sealed]
@@ -271,6 +442,12 @@ impl ExpressionsExt for naga::Arena<naga::Expression> {
     fn append_literal(&mut self, literal: naga::Literal) -> naga::Handle<naga::Expression> {
         self.append(naga::Expression::Literal(literal), naga::Span::UNDEFINED)
     }
+    fn append_zero_value(
+        &mut self,
+        ty: naga::Handle<naga::Type>,
+    ) -> naga::Handle<naga::Expression> {
+        self.append(naga::Expression::ZeroValue(ty), naga::Span::UNDEFINED)
+    }
     fn append_u32(&mut self, value: u32) -> naga::Handle<naga::Expression> {
         self.append_literal(naga::Literal::U32(value))
     }
@@ -289,6 +466,29 @@ impl ExpressionsExt for naga::Arena<naga::Expression> {
     fn append_bool(&mut self, value: bool) -> naga::Handle<naga::Expression> {
         self.append_literal(naga::Literal::Bool(value))
     }
+    fn append_abstract_int(&mut self, value: i64) -> naga::Handle<naga::Expression> {
+        self.append_literal(naga::Literal::AbstractInt(value))
+    }
+    fn append_abstract_float(&mut self, value: f64) -> naga::Handle<naga::Expression> {
+        self.append_literal(naga::Literal::AbstractFloat(value))
+    }
+
+    fn append_v128(
+        &mut self,
+        ty: naga::Handle<naga::Type>,
+        words: [u32; 4],
+    ) -> naga::Handle<naga::Expression> {
+        let components = words.map(|word| self.append_u32(word)).to_vec();
+        self.append_compose(ty, components)
+    }
+    fn append_u128(
+        &mut self,
+        ty: naga::Handle<naga::Type>,
+        value: u128,
+    ) -> naga::Handle<naga::Expression> {
+        let words = [0, 1, 2, 3].map(|i| (value >> (i * 32)) as u32);
+        self.append_v128(ty, words)
+    }
 }
 
 #[sealed]
@@ -309,6 +509,28 @@ pub trait BlockExt {
         reject: naga::Block,
     );
     fn push_kill(&mut self);
+
+    // Shorthand read-modify-write
+    /// Stores `lhs + rhs` to `pointer`, emitting the sum, which is returned.
+    fn push_store_add(
+        &mut self,
+        pointer: naga::Handle<naga::Expression>,
+        expressions: &mut naga::Arena<naga::Expression>,
+        lhs: naga::Handle<naga::Expression>,
+        rhs: naga::Handle<naga::Expression>,
+    ) -> naga::Handle<naga::Expression>;
+    /// Loads the value at `pointer`, adds `by` and stores the result back, emitting the load and the sum. Gives
+    /// the incremented value.
+    fn push_increment(
+        &mut self,
+        pointer: naga::Handle<naga::Expression>,
+        expressions: &mut naga::Arena<naga::Expression>,
+        by: naga::Handle<naga::Expression>,
+    ) -> naga::Handle<naga::Expression>;
+
+    // Debug info
+    /// Gives every statement from index `start` onwards that doesn't yet have a span the given span.
+    fn fill_spans_from(&mut self, start: usize, span: naga::Span);
 }
 
 #[sealed]
@@ -361,6 +583,44 @@ impl BlockExt for naga::Block {
     fn push_kill(&mut self) {
         self.push(naga::Statement::Kill, naga::Span::UNDEFINED);
     }
+
+    fn push_store_add(
+        &mut self,
+        pointer: naga::Handle<naga::Expression>,
+        expressions: &mut naga::Arena<naga::Expression>,
+        lhs: naga::Handle<naga::Expression>,
+        rhs: naga::Handle<naga::Expression>,
+    ) -> naga::Handle<naga::Expression> {
+        let sum = expressions.append(
+            naga::Expression::Binary {
+                op: naga::BinaryOperator::Add,
+                left: lhs,
+                right: rhs,
+            },
+            naga::Span::UNDEFINED,
+        );
+        self.push_emit(sum);
+        self.push_store(pointer, sum);
+        return sum;
+    }
+    fn push_increment(
+        &mut self,
+        pointer: naga::Handle<naga::Expression>,
+        expressions: &mut naga::Arena<naga::Expression>,
+        by: naga::Handle<naga::Expression>,
+    ) -> naga::Handle<naga::Expression> {
+        let value = expressions.append_load(pointer);
+        self.push_emit(value);
+        self.push_store_add(pointer, expressions, value, by)
+    }
+
+    fn fill_spans_from(&mut self, start: usize, span: naga::Span) {
+        for (_, statement_span) in self.span_iter_mut().skip(start) {
+            if let Some(statement_span) = statement_span.filter(|span| !span.is_defined()) {
+                *statement_span = span;
+            }
+        }
+    }
 }
 
 pub struct FunctionSignature {
@@ -425,6 +685,70 @@ macro_rules! declare_function {
     }};
 }
 
+/// Provides an inline way of defining compute shader entry points and getting their arguments as expressions.
+/// Every argument of an entry point must be bound to a builtin. Gives the index of the new entry point within
+/// `module.entry_points`, followed by the argument expressions.
+///
+/// # Usage
+///
+/// ```
+/// # use naga_ext::*;
+/// let mut module = naga::Module::default();
+/// let uvec3_ty = module.types.insert_anonymous(naga::TypeInner::Vector {
+///     size: naga::VectorSize::Tri,
+///     scalar: naga::Scalar::U32,
+/// });
+/// let (index, global_id) = naga_ext::declare_entry_point! {&mut module =>
+///     fn main(global_id: uvec3_ty @builtin(GlobalInvocationId)) @workgroup_size(64, 1, 1)
+/// };
+/// assert_eq!(module.entry_points[index].workgroup_size, [64, 1, 1]);
+/// # naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty()).validate(&mut module).unwrap();
+/// ```
+///
+/// The above code results in the following shader:
+///
+/// ```wgsl
+/// @compute @workgroup_size(64, 1, 1)
+/// fn main(@builtin(global_invocation_id) global_id: vec3<u32>) { }
+/// ```
+#[macro_export]
+macro_rules! declare_entry_point {
+    ($module:expr => fn $fn_name:tt ( $($arg_name:ident : $arg_ty:tt @builtin($builtin:ident)),* $(,)? ) @workgroup_size($x:expr, $y:expr, $z:expr)) => {{
+        let mut function = naga::Function::default();
+        $(
+            function.arguments.push(naga::FunctionArgument {
+                name: Some(stringify!{$arg_name}.to_owned()),
+                ty: $arg_ty,
+                binding: Some(naga::Binding::BuiltIn(naga::BuiltIn::$builtin)),
+            });
+        )*
+
+        let module: &mut naga::Module = $module;
+        let index = module.entry_points.len();
+        module.entry_points.push(naga::EntryPoint {
+            name: $crate::declare_function!(@match_fn_name $fn_name),
+            stage: naga::ShaderStage::Compute,
+            early_depth_test: None,
+            workgroup_size: [$x, $y, $z],
+            function,
+        });
+
+        #[allow(unused_variables)]
+        let function = &mut module.entry_points[index].function;
+
+        #[allow(unused_mut, unused_variables)]
+        let mut i = 0;
+        (index, $(
+            function.expressions.append(naga::Expression::FunctionArgument({
+                let _ = stringify!{$arg_name};
+                let v = i;
+                i += 1;
+                v
+            }), naga::Span::UNDEFINED),
+        )*)
+    }};
+}
+
 #[macro_export]
 macro_rules! naga_expr {
     ($ctx:expr => $($expression:tt)*) => {{
@@ -579,6 +903,17 @@ macro_rules! naga_expr {
         $crate::naga_expr!(@inner $ctx => handle $($others)*)
     }};
 
+    // Vector Ops
+    (@inner $ctx:expr => $base:tt . $components:ident $($others:tt)*) => {{
+        const _: () = assert!(
+            $crate::block_context::is_swizzle_pattern(stringify!($components)),
+            concat!("`", stringify!($components), "` is not a swizzle of 1 to 4 of `x`, `y`, `z` and `w`"),
+        );
+        let base = $crate::naga_expr!(@inner $ctx => $base);
+        let handle = $ctx.swizzle_expr(base, stringify!($components));
+        $crate::naga_expr!(@inner $ctx => handle $($others)*)
+    }};
+
     // Array Ops
     (@inner $ctx:expr => $base:tt [ $($index:tt)* ] $($others:tt)*) => {{
         let base = $crate::naga_expr!(@inner $ctx => $base);
@@ -640,6 +975,7 @@ macro_rules! naga_expr {
         let handle = $ctx.append_expr(naga::Expression::Math { fun: naga::MathFunction::Exp2, arg, arg1: None, arg2: None, arg3: None });
         $crate::naga_expr!(@inner $ctx => handle $($others)*)
     }};
+    // Abs is defined for floats and for both signed and unsigned integers, where it leaves unsigned values unchanged
     (@inner $ctx:expr => abs($($arg:tt)*) $($others:tt)*) => {{
         let arg = $crate::naga_expr!(@inner $ctx => $($arg)*);
         let handle = $ctx.append_expr(naga::Expression::Math { fun: naga::MathFunction::Abs, arg, arg1: None, arg2: None, arg3: None });
@@ -675,6 +1011,73 @@ macro_rules! naga_expr {
         let handle = $ctx.append_expr(naga::Expression::Math { fun: naga::MathFunction::Sign, arg, arg1: None, arg2: None, arg3: None });
         $crate::naga_expr!(@inner $ctx => handle $($others)*)
     }};
+    // -1, 0 or 1 in the type of the operand, which must be a float or a signed integer
+    (@inner $ctx:expr => signum($($arg:tt)*) $($others:tt)*) => {{
+        $crate::naga_expr!(@inner $ctx => sign($($arg)*) $($others)*)
+    }};
+    (@inner $ctx:expr => sin($($arg:tt)*) $($others:tt)*) => {{
+        let arg = $crate::naga_expr!(@inner $ctx => $($arg)*);
+        let handle = $ctx.append_expr(naga::Expression::Math { fun: naga::MathFunction::Sin, arg, arg1: None, arg2: None, arg3: None });
+        $crate::naga_expr!(@inner $ctx => handle $($others)*)
+    }};
+    (@inner $ctx:expr => cos($($arg:tt)*) $($others:tt)*) => {{
+        let arg = $crate::naga_expr!(@inner $ctx => $($arg)*);
+        let handle = $ctx.append_expr(naga::Expression::Math { fun: naga::MathFunction::Cos, arg, arg1: None, arg2: None, arg3: None });
+        $crate::naga_expr!(@inner $ctx => handle $($others)*)
+    }};
+    (@inner $ctx:expr => tan($($arg:tt)*) $($others:tt)*) => {{
+        let arg = $crate::naga_expr!(@inner $ctx => $($arg)*);
+        let handle = $ctx.append_expr(naga::Expression::Math { fun: naga::MathFunction::Tan, arg, arg1: None, arg2: None, arg3: None });
+        $crate::naga_expr!(@inner $ctx => handle $($others)*)
+    }};
+    (@inner $ctx:expr => log2($($arg:tt)*) $($others:tt)*) => {{
+        let arg = $crate::naga_expr!(@inner $ctx => $($arg)*);
+        let handle = $ctx.append_expr(naga::Expression::Math { fun: naga::MathFunction::Log2, arg, arg1: None, arg2: None, arg3: None });
+        $crate::naga_expr!(@inner $ctx => handle $($others)*)
+    }};
+    (@inner $ctx:expr => pow($($args:tt)*) $($others:tt)*) => {{
+        let mut components = Vec::new();
+        $crate::naga_expr!{@innerconstructor $ctx, components => $($args)* }
+        let arg = components[0];
+        let arg1 = components[1];
+        let handle = $ctx.append_expr(naga::Expression::Math { fun: naga::MathFunction::Pow, arg, arg1: Some(arg1), arg2: None, arg3: None });
+        $crate::naga_expr!(@inner $ctx => handle $($others)*)
+    }};
+    (@inner $ctx:expr => atan2($($args:tt)*) $($others:tt)*) => {{
+        let mut components = Vec::new();
+        $crate::naga_expr!{@innerconstructor $ctx, components => $($args)* }
+        let arg = components[0];
+        let arg1 = components[1];
+        let handle = $ctx.append_expr(naga::Expression::Math { fun: naga::MathFunction::Atan2, arg, arg1: Some(arg1), arg2: None, arg3: None });
+        $crate::naga_expr!(@inner $ctx => handle $($others)*)
+    }};
+    (@inner $ctx:expr => fma($($args:tt)*) $($others:tt)*) => {{
+        let mut components = Vec::new();
+        $crate::naga_expr!{@innerconstructor $ctx, components => $($args)* }
+        let arg = components[0];
+        let arg1 = components[1];
+        let arg2 = components[2];
+        let handle = $ctx.append_expr(naga::Expression::Math { fun: naga::MathFunction::Fma, arg, arg1: Some(arg1), arg2: Some(arg2), arg3: None });
+        $crate::naga_expr!(@inner $ctx => handle $($others)*)
+    }};
+    (@inner $ctx:expr => clamp($($args:tt)*) $($others:tt)*) => {{
+        let mut components = Vec::new();
+        $crate::naga_expr!{@innerconstructor $ctx, components => $($args)* }
+        let arg = components[0];
+        let arg1 = components[1];
+        let arg2 = components[2];
+        let handle = $ctx.append_expr(naga::Expression::Math { fun: naga::MathFunction::Clamp, arg, arg1: Some(arg1), arg2: Some(arg2), arg3: None });
+        $crate::naga_expr!(@inner $ctx => handle $($others)*)
+    }};
+    (@inner $ctx:expr => mix($($args:tt)*) $($others:tt)*) => {{
+        let mut components = Vec::new();
+        $crate::naga_expr!{@innerconstructor $ctx, components => $($args)* }
+        let arg = components[0];
+        let arg1 = components[1];
+        let arg2 = components[2];
+        let handle = $ctx.append_expr(naga::Expression::Math { fun: naga::MathFunction::Mix, arg, arg1: Some(arg1), arg2: Some(arg2), arg3: None });
+        $crate::naga_expr!(@inner $ctx => handle $($others)*)
+    }};
     (@inner $ctx:expr => min($($args:tt)*) $($others:tt)*) => {{
         let mut components = Vec::new();
         $crate::naga_expr!{@innerconstructor $ctx, components => $($args)* }
@@ -700,6 +1103,115 @@ macro_rules! naga_expr {
         $ctx.append_expr(naga::Expression::Select { condition, accept, reject })
     }};
 
+    // Switch, where each case body is given a context for its own block, built with `BlockContext::switch`
+    (@inner $ctx:expr => switch ( $($selector:tt)* ) {
+        $( case $kind:ident ( $value:expr ) => |$case_ctx:ident| $case_body:block , )*
+        default => |$default_ctx:ident| $default_body:block $(,)?
+    } ) => {{
+        let selector = $crate::naga_expr!(@inner $ctx => $($selector)* );
+        $ctx.switch(selector)
+            $(
+                .case(naga::SwitchValue::$kind($value), |#[allow(unused_mut, unused_variables)] mut $case_ctx| {
+                    $case_body;
+                })
+            )*
+            .default(|#[allow(unused_mut, unused_variables)] mut $default_ctx| {
+                $default_body;
+            })
+    }};
+
+    // Loops, where the condition is re-evaluated at the start of every iteration
+    (@inner $ctx:expr => while ( $($condition:tt)* ) |$body_ctx:ident| $body:block ) => {{
+        let mut body = naga::Block::new();
+        {
+            let mut loop_ctx = $crate::BlockContext {
+                block: &mut body,
+                ..$ctx.reborrow()
+            };
+            let should_stop = $crate::naga_expr!(@inner loop_ctx => !($($condition)*));
+            loop_ctx.test(should_stop).then(|ctx| ctx.stop_loop());
+
+            #[allow(unused_mut, unused_variables)]
+            let mut $body_ctx = loop_ctx;
+            $body;
+        }
+        $ctx.block.push(naga::Statement::Loop { body, continuing: naga::Block::new(), break_if: None }, naga::Span::UNDEFINED);
+    }};
+    (@inner $ctx:expr => for $var:ident in ( $($start:tt)* ) .. ( $($end:tt)* ) |$body_ctx:ident| $body:block ) => {{
+        #[allow(unused_imports)]
+        use $crate::TypesExt as _;
+
+        let counter_ty = $ctx.types.insert_u32();
+        let counter = $ctx.new_local(concat!("loop_", stringify!($var)), counter_ty, None);
+        let counter_ptr = $ctx.local_expr(counter);
+        let start = $crate::naga_expr!(@inner $ctx => $($start)*);
+        $ctx.store(counter_ptr, start);
+        let end = $crate::naga_expr!(@inner $ctx => $($end)*);
+
+        let mut body = naga::Block::new();
+        {
+            let mut loop_ctx = $crate::BlockContext {
+                block: &mut body,
+                ..$ctx.reborrow()
+            };
+            let $var = $crate::naga_expr!(@inner loop_ctx => Load(counter_ptr));
+            let should_stop = $crate::naga_expr!(@inner loop_ctx => $var >= end);
+            loop_ctx.test(should_stop).then(|ctx| ctx.stop_loop());
+
+            #[allow(unused_mut, unused_variables)]
+            let mut $body_ctx = loop_ctx;
+            $body;
+        }
+
+        let mut continuing = naga::Block::new();
+        {
+            let mut step_ctx = $crate::BlockContext {
+                block: &mut continuing,
+                ..$ctx.reborrow()
+            };
+            let one = $crate::naga_expr!(@inner step_ctx => U32(1));
+            step_ctx.increment(counter_ptr, one);
+        }
+
+        $ctx.block.push(naga::Statement::Loop { body, continuing, break_if: None }, naga::Span::UNDEFINED);
+    }};
+    // C-style loops, where `$var` is a local of type `$ty` holding the initial value. Within the condition, the body
+    // and the step, `$var` is the value of the local at the start of the iteration, and the step gives its next value
+    (@inner $ctx:expr => for ( $var:ident : $ty:tt = ( $($init:tt)* ) ; ( $($condition:tt)* ) ; ( $($step:tt)* ) ) |$body_ctx:ident| $body:block ) => {{
+        let init = $crate::naga_expr!(@inner $ctx => $($init)*);
+        let counter = $ctx.new_local(concat!("loop_", stringify!($var)), $ty, None);
+        let counter_ptr = $ctx.local_expr(counter);
+        $ctx.store(counter_ptr, init);
+
+        let mut body = naga::Block::new();
+        {
+            let mut loop_ctx = $crate::BlockContext {
+                block: &mut body,
+                ..$ctx.reborrow()
+            };
+            let $var = $crate::naga_expr!(@inner loop_ctx => Load(counter_ptr));
+            let should_stop = $crate::naga_expr!(@inner loop_ctx => !($($condition)*));
+            loop_ctx.test(should_stop).then(|ctx| ctx.stop_loop());
+
+            #[allow(unused_mut, unused_variables)]
+            let mut $body_ctx = loop_ctx;
+            $body;
+        }
+
+        let mut continuing = naga::Block::new();
+        {
+            let mut step_ctx = $crate::BlockContext {
+                block: &mut continuing,
+                ..$ctx.reborrow()
+            };
+            let $var = $crate::naga_expr!(@inner step_ctx => Load(counter_ptr));
+            let next = $crate::naga_expr!(@inner step_ctx => $($step)*);
+            step_ctx.store(counter_ptr, next);
+        }
+
+        $ctx.block.push(naga::Statement::Loop { body, continuing, break_if: None }, naga::Span::UNDEFINED);
+    }};
+
     // Constructors
     (@innerconstructor $ctx:expr, $components:expr => $e1:tt $(, $($others:tt)*)?) => {{
         $components.push(naga_expr!(@inner $ctx => $e1));
@@ -727,6 +1239,19 @@ macro_rules! naga_expr {
         let handle = $ctx.append_expr(naga::Expression::Compose {ty: $ty, components});
         $crate::naga_expr!(@inner $ctx => handle $($others)*)
     }};
+    // Arrays, either `[elem_ty; a, b, c]` or `[a, b, c]` to take the element type from the first component
+    (@inner $ctx:expr => [ $elem_ty:tt ; $($args:tt)* ] $($others:tt)*) => {{
+        let mut components = Vec::new();
+        $crate::naga_expr!{@innerconstructor $ctx, components => $($args)* }
+        let handle = $ctx.array_expr(Some($elem_ty), components);
+        $crate::naga_expr!(@inner $ctx => handle $($others)*)
+    }};
+    (@inner $ctx:expr => [ $($args:tt)* ] $($others:tt)*) => {{
+        let mut components = Vec::new();
+        $crate::naga_expr!{@innerconstructor $ctx, components => $($args)* }
+        let handle = $ctx.array_expr(None, components);
+        $crate::naga_expr!(@inner $ctx => handle $($others)*)
+    }};
 
     // Step braces
     (@inner $ctx:expr => ($($expression:tt)*) $($others:tt)*) => {{
@@ -738,3 +1263,863 @@ macro_rules! naga_expr {
     (@inner $ctx:expr => {$term:expr}) => { $term };
     (@inner $ctx:expr => $term:expr) => { $term };
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::block_context::is_swizzle_pattern;
+    use crate::{
+        declare_function, into_literal::IntoLiteral, naga_expr, BlockContext, BlockExt,
+        ConstantsExt, ExpressionsExt, FunctionsExt, ModuleExt, TypesExt,
+    };
+
+    fn validate(module: &naga::Module) {
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(module)
+        .unwrap();
+    }
+
+    /// Declares a function returning the zero value of the given type
+    fn validate_zero_value(mut module: naga::Module, ty: naga::Handle<naga::Type>) {
+        let (function,) = declare_function! {&mut module =>
+            fn zero() -> ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let zero = ctx.expressions.append_zero_value(ty);
+        assert!(matches!(
+            ctx.expressions[zero],
+            naga::Expression::ZeroValue(zero_ty) if zero_ty == ty
+        ));
+        ctx.result(zero);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn zero_value_of_scalar() {
+        let mut module = naga::Module::default();
+        let f32_ty = module.types.insert_f32();
+        validate_zero_value(module, f32_ty);
+    }
+
+    #[test]
+    fn zero_value_of_vector() {
+        let mut module = naga::Module::default();
+        let vec4_ty = module
+            .types
+            .insert_vecn(naga::Scalar::U32, naga::VectorSize::Quad);
+        validate_zero_value(module, vec4_ty);
+    }
+
+    #[test]
+    fn zero_value_of_struct() {
+        let mut module = naga::Module::default();
+        let u32_ty = module.types.insert_u32();
+        let vec2_ty = module
+            .types
+            .insert_vecn(naga::Scalar::F32, naga::VectorSize::Bi);
+        let struct_ty = module.types.insert_anonymous(naga::TypeInner::Struct {
+            members: vec![
+                naga::StructMember {
+                    name: Some("word".to_owned()),
+                    ty: u32_ty,
+                    binding: None,
+                    offset: 0,
+                },
+                naga::StructMember {
+                    name: Some("pair".to_owned()),
+                    ty: vec2_ty,
+                    binding: None,
+                    offset: 8,
+                },
+            ],
+            span: 16,
+        });
+        validate_zero_value(module, struct_ty);
+    }
+
+    fn get_or_append_scalar(
+        module: &mut naga::Module,
+        ty: naga::Handle<naga::Type>,
+        init: impl IntoLiteral,
+    ) -> naga::Handle<naga::Constant> {
+        module
+            .constants
+            .get_or_append_scalar(&mut module.const_expressions, ty, init)
+    }
+
+    #[test]
+    fn scalar_constants_are_deduplicated() {
+        let mut module = naga::Module::default();
+        let u32_ty = module.types.insert_u32();
+        let f32_ty = module.types.insert_f32();
+
+        let zero = get_or_append_scalar(&mut module, u32_ty, 0u32);
+        let zero_again = get_or_append_scalar(&mut module, u32_ty, 0u32);
+        assert_eq!(zero, zero_again);
+        assert_eq!(module.constants.len(), 1);
+        assert_eq!(module.const_expressions.len(), 1);
+
+        let one = get_or_append_scalar(&mut module, u32_ty, 1u32);
+        assert_ne!(zero, one);
+
+        // Named constants are never reused
+        let init = module.const_expressions.append_u32(2);
+        let named = module.constants.append_named("TWO", u32_ty, init);
+        let two = get_or_append_scalar(&mut module, u32_ty, 2u32);
+        assert_ne!(named, two);
+        assert_eq!(module.constants[named].name.as_deref(), Some("TWO"));
+
+        let positive_zero = get_or_append_scalar(&mut module, f32_ty, 0.0f32);
+        let negative_zero = get_or_append_scalar(&mut module, f32_ty, -0.0f32);
+        assert_ne!(positive_zero, negative_zero);
+    }
+
+    #[test]
+    fn fill_spans_from_keeps_earlier_and_defined_spans() {
+        let mut module = naga::Module::default();
+        let u32_ty = module.types.insert_u32();
+        let (function,) = declare_function! {&mut module =>
+            fn spanned() -> u32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let zero = naga_expr!(&mut ctx => U32(0));
+        let counter = ctx.new_local("counter", u32_ty, Some(zero));
+        let counter = ctx.local_expr(counter);
+        let one = naga_expr!(&mut ctx => U32(1));
+
+        ctx.block.push_store(counter, one);
+        let start = ctx.block.len();
+        ctx.block.push_store(counter, zero);
+        ctx.block.push(
+            naga::Statement::Store {
+                pointer: counter,
+                value: one,
+            },
+            naga::Span::new(1, 2),
+        );
+
+        ctx.block.fill_spans_from(start, naga::Span::new(10, 20));
+
+        let spans = ctx
+            .block
+            .span_iter()
+            .map(|(_, span)| *span)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            vec![
+                naga::Span::UNDEFINED,
+                naga::Span::new(10, 20),
+                naga::Span::new(1, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn increment_loads_adds_and_stores() {
+        let mut module = naga::Module::default();
+        let u32_ty = module.types.insert_u32();
+        let (function,) = declare_function! {&mut module =>
+            fn count() -> u32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let zero = naga_expr!(&mut ctx => U32(0));
+        let counter = ctx.new_local("counter", u32_ty, Some(zero));
+        let counter = ctx.local_expr(counter);
+        let by = naga_expr!(&mut ctx => U32(3));
+
+        let statements_before = ctx.block.len();
+        let incremented = ctx.increment(counter, by);
+
+        let emitted = |statement: &naga::Statement| match statement {
+            naga::Statement::Emit(range) => range.clone().collect::<Vec<_>>(),
+            statement => panic!("expected an emit but got {:?}", statement),
+        };
+        let statements = &ctx.block[statements_before..];
+        assert_eq!(statements.len(), 3);
+        let [load] = emitted(&statements[0])[..] else {
+            panic!("expected only the load to be emitted");
+        };
+        assert_eq!(emitted(&statements[1]), vec![incremented]);
+        assert!(matches!(
+            statements[2],
+            naga::Statement::Store { pointer, value } if pointer == counter && value == incremented
+        ));
+
+        assert!(matches!(
+            ctx.expressions[load],
+            naga::Expression::Load { pointer } if pointer == counter
+        ));
+        assert!(matches!(
+            ctx.expressions[incremented],
+            naga::Expression::Binary {
+                op: naga::BinaryOperator::Add,
+                left,
+                right,
+            } if left == load && right == by
+        ));
+
+        ctx.result(incremented);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn functions_resolved_by_name() {
+        let mut module = naga::Module::default();
+        let u32_ty = module.types.insert_u32();
+        let (first,) = declare_function! {&mut module =>
+            fn first() -> u32_ty
+        };
+        let unnamed = module
+            .functions
+            .append(naga::Function::default(), naga::Span::UNDEFINED);
+        let (second, _) = declare_function! {&mut module =>
+            fn second(value: u32_ty)
+        };
+
+        assert_eq!(module.functions.get_by_name("first"), Some(first));
+        assert_eq!(module.functions.get_by_name("second"), Some(second));
+        assert_eq!(module.functions.get_by_name("third"), None);
+
+        let named = module.functions.iter_named().collect::<Vec<_>>();
+        assert_eq!(named, vec![(first, "first"), (second, "second")]);
+        assert!(named.iter().all(|(handle, _)| *handle != unnamed));
+    }
+
+    #[test]
+    fn merge_two_function_module() {
+        let mut other = naga::Module::default();
+        let other_i32_ty = other.types.insert_i32();
+        let two = other.const_expressions.append(
+            naga::Expression::Literal(naga::Literal::I32(2)),
+            naga::Span::UNDEFINED,
+        );
+        let two = other.constants.append_anonymous(other_i32_ty, two);
+        let (double, value) = declare_function! {&mut other =>
+            fn double(value: other_i32_ty) -> other_i32_ty
+        };
+        let mut ctx = BlockContext::from((&mut other, double));
+        let two = ctx.constant_expr(two);
+        let res = naga_expr!(&mut ctx => value * two);
+        ctx.result(res);
+        let (quadruple, value) = declare_function! {&mut other =>
+            fn quadruple(value: other_i32_ty) -> other_i32_ty
+        };
+        let mut ctx = BlockContext::from((&mut other, quadruple));
+        let doubled = ctx.call_get_return(double, vec![value]);
+        let res = ctx.call_get_return(double, vec![doubled]);
+        ctx.result(res);
+        validate(&other);
+
+        // Objects already in the module shift the handles of everything merged into it
+        let mut module = naga::Module::default();
+        let f32_ty = module.types.insert_f32();
+        let i32_ty = module.types.insert_i32();
+        let (unrelated,) = declare_function! {&mut module =>
+            fn unrelated() -> f32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, unrelated));
+        let res = naga_expr!(&mut ctx => F32(1.0));
+        ctx.result(res);
+
+        let remap = module.merge(other);
+        assert_eq!(remap.ty(other_i32_ty), i32_ty);
+        assert_eq!(module.functions.len(), 3);
+        assert_eq!(
+            module.functions[remap.function(quadruple)].name.as_deref(),
+            Some("quadruple")
+        );
+
+        let (caller,) = declare_function! {&mut module =>
+            fn caller() -> i32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, caller));
+        let value = naga_expr!(&mut ctx => I32(3));
+        let res = ctx.call_get_return(remap.function(quadruple), vec![value]);
+        ctx.result(res);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn array_of_inferred_literals() {
+        let mut module = naga::Module::default();
+        let i32_ty = module.types.insert_i32();
+        let (function,) = declare_function! {&mut module =>
+            fn third() -> i32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let array = naga_expr!(&mut ctx => [I32(1), I32(2), I32(3)]);
+        let res = naga_expr!(&mut ctx => array[const 2]);
+
+        assert_eq!(
+            ctx.expr_type(array),
+            Some(naga::TypeInner::Array {
+                base: i32_ty,
+                size: naga::ArraySize::Constant(std::num::NonZeroU32::new(3).unwrap()),
+                stride: 4,
+            })
+        );
+        ctx.result(res);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn array_with_given_element_type() {
+        let mut module = naga::Module::default();
+        let u32_ty = module.types.insert_u32();
+        let (function, value) = declare_function! {&mut module =>
+            fn second(value: u32_ty) -> u32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        // Function arguments can't be resolved by the context, so the element type must be given
+        let array = naga_expr!(&mut ctx => [u32_ty; value, value + U32(1)]);
+        let res = naga_expr!(&mut ctx => array[const 1]);
+        ctx.result(res);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn nested_arrays_of_vectors() {
+        let mut module = naga::Module::default();
+        let vec3_ty = module
+            .types
+            .insert_vecn(naga::Scalar::F32, naga::VectorSize::Tri);
+        let f32_ty = module.types.insert_f32();
+        let (function,) = declare_function! {&mut module =>
+            fn last() -> f32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let first = naga_expr!(&mut ctx => vec3_ty(F32(1.0), F32(2.0), F32(3.0)));
+        let second = naga_expr!(&mut ctx => vec3_ty(F32(4.0), F32(5.0), F32(6.0)));
+        let nested = naga_expr!(&mut ctx => [[first, second], [second, first]]);
+        let res = naga_expr!(&mut ctx => nested[const 1][const 1][const 2]);
+
+        // Three component vectors are aligned to 16 bytes, so the inner arrays are 32 bytes
+        match ctx.expr_type(nested) {
+            Some(naga::TypeInner::Array { base, size, stride }) => {
+                assert_eq!(
+                    size,
+                    naga::ArraySize::Constant(std::num::NonZeroU32::new(2).unwrap())
+                );
+                assert_eq!(stride, 32);
+                assert!(matches!(
+                    ctx.types[base].inner,
+                    naga::TypeInner::Array { stride: 16, .. }
+                ));
+            }
+            ty => panic!("expected a nested array but got {:?}", ty),
+        }
+        ctx.result(res);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn abs_of_signed_integer() {
+        let mut module = naga::Module::default();
+        let i32_ty = module.types.insert_i32();
+        let (function, value) = declare_function! {&mut module =>
+            fn i32_abs(value: i32_ty) -> i32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let res = naga_expr!(&mut ctx => abs(value) + I32(1));
+        ctx.result(res);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn abs_of_unsigned_integer() {
+        let mut module = naga::Module::default();
+        let u32_ty = module.types.insert_u32();
+        let (function, value) = declare_function! {&mut module =>
+            fn u32_abs(value: u32_ty) -> u32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let res = naga_expr!(&mut ctx => abs(value));
+        ctx.result(res);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn abs_of_integer_vector() {
+        let mut module = naga::Module::default();
+        let vec2_ty = module
+            .types
+            .insert_vecn(naga::Scalar::I32, naga::VectorSize::Bi);
+        let (function, value) = declare_function! {&mut module =>
+            fn vec2_abs(value: vec2_ty) -> vec2_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let res = naga_expr!(&mut ctx => abs(value));
+        ctx.result(res);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn signum_of_signed_integer() {
+        let mut module = naga::Module::default();
+        let i32_ty = module.types.insert_i32();
+        let (function, value) = declare_function! {&mut module =>
+            fn i32_signum(value: i32_ty) -> i32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let res = naga_expr!(&mut ctx => signum(value) * I32(-1));
+        ctx.result(res);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn signum_of_float() {
+        let mut module = naga::Module::default();
+        let f32_ty = module.types.insert_f32();
+        let (function, value) = declare_function! {&mut module =>
+            fn f32_signum(value: f32_ty) -> f32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let res = naga_expr!(&mut ctx => signum(value));
+        ctx.result(res);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn switch_builds_a_block_for_each_case() {
+        let mut module = naga::Module::default();
+        let u32_ty = module.types.insert_u32();
+        let (function, selector) = declare_function! {&mut module =>
+            fn select(selector: u32_ty) -> u32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        naga_expr!(&mut ctx => switch (selector) {
+            case U32(0) => |ctx| {
+                let res = naga_expr!(&mut ctx => U32(10));
+                ctx.result(res);
+            },
+            case U32(1) => |ctx| {
+                let res = naga_expr!(&mut ctx => selector + U32(20));
+                ctx.result(res);
+            },
+            default => |ctx| {
+                let res = naga_expr!(&mut ctx => U32(0));
+                ctx.result(res);
+            },
+        });
+
+        let [.., naga::Statement::Switch {
+            selector: switched,
+            cases,
+        }] = &ctx.block[..]
+        else {
+            panic!("expected the switch to be the last statement");
+        };
+        assert_eq!(*switched, selector);
+        assert_eq!(
+            cases.iter().map(|case| case.value).collect::<Vec<_>>(),
+            vec![
+                naga::SwitchValue::U32(0),
+                naga::SwitchValue::U32(1),
+                naga::SwitchValue::Default,
+            ]
+        );
+        for case in cases {
+            assert!(!case.fall_through);
+            assert!(matches!(
+                case.body.last(),
+                Some(naga::Statement::Return { value: Some(_) })
+            ));
+        }
+
+        validate(&module);
+    }
+
+    /// Gives the body, the stopping condition and the continuing block of the loop that was the last statement
+    /// pushed, after checking that its body starts by breaking if the stopping condition is true
+    fn last_loop<'a>(
+        ctx: &'a BlockContext<'_>,
+    ) -> (
+        &'a naga::Block,
+        naga::Handle<naga::Expression>,
+        &'a naga::Block,
+    ) {
+        let [.., naga::Statement::Loop {
+            body,
+            continuing,
+            break_if: None,
+        }] = &ctx.block[..]
+        else {
+            panic!("expected a loop to be the last statement");
+        };
+        let Some(naga::Statement::If {
+            condition: should_stop,
+            accept,
+            reject,
+        }) = body
+            .iter()
+            .find(|statement| !matches!(statement, naga::Statement::Emit(_)))
+        else {
+            panic!("expected the loop body to start with a test");
+        };
+        assert!(matches!(accept[..], [naga::Statement::Break]));
+        assert!(reject.is_empty());
+
+        (body, *should_stop, continuing)
+    }
+
+    /// Gives the condition that the given expression is the negation of
+    fn negated<'a>(
+        ctx: &'a BlockContext<'_>,
+        expr: naga::Handle<naga::Expression>,
+    ) -> &'a naga::Expression {
+        let naga::Expression::Unary {
+            op: naga::UnaryOperator::LogicalNot,
+            expr,
+        } = ctx.expressions[expr]
+        else {
+            panic!("expected a logical not but got {:?}", ctx.expressions[expr]);
+        };
+        &ctx.expressions[expr]
+    }
+
+    #[test]
+    fn while_tests_condition_before_body() {
+        let mut module = naga::Module::default();
+        let u32_ty = module.types.insert_u32();
+        let (function, value) = declare_function! {&mut module =>
+            fn halve_until_small(value: u32_ty) -> u32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let current = ctx.new_local("current", u32_ty, Some(value));
+        let current = ctx.local_expr(current);
+        naga_expr!(&mut ctx => while (Load(current) > U32(10)) |ctx| {
+            let halved = naga_expr!(&mut ctx => Load(current) >> U32(1));
+            ctx.store(current, halved);
+        });
+
+        let (body, should_stop, continuing) = last_loop(&ctx);
+        assert!(matches!(
+            negated(&ctx, should_stop),
+            naga::Expression::Binary {
+                op: naga::BinaryOperator::Greater,
+                ..
+            }
+        ));
+        assert!(matches!(
+            body.last(),
+            Some(naga::Statement::Store { pointer, .. }) if *pointer == current
+        ));
+        assert!(continuing.is_empty());
+
+        let res = naga_expr!(&mut ctx => Load(current));
+        ctx.result(res);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn for_range_counts_up_to_end() {
+        let mut module = naga::Module::default();
+        let u32_ty = module.types.insert_u32();
+        let (function, count) = declare_function! {&mut module =>
+            fn sum_below(count: u32_ty) -> u32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let zero = naga_expr!(&mut ctx => U32(0));
+        let sum = ctx.new_local("sum", u32_ty, Some(zero));
+        let sum = ctx.local_expr(sum);
+        naga_expr!(&mut ctx => for i in (U32(0))..(count) |ctx| {
+            ctx.increment(sum, i);
+        });
+
+        let (_, should_stop, continuing) = last_loop(&ctx);
+        assert!(matches!(
+            ctx.expressions[should_stop],
+            naga::Expression::Binary {
+                op: naga::BinaryOperator::GreaterEqual,
+                right,
+                ..
+            } if right == count
+        ));
+        assert!(matches!(
+            continuing.last(),
+            Some(naga::Statement::Store { .. })
+        ));
+
+        let res = naga_expr!(&mut ctx => Load(sum));
+        ctx.result(res);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn for_with_init_condition_and_step() {
+        let mut module = naga::Module::default();
+        let i32_ty = module.types.insert_i32();
+        let (function, start) = declare_function! {&mut module =>
+            fn sum_odd_down_from(start: i32_ty) -> i32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let zero = naga_expr!(&mut ctx => I32(0));
+        let sum = ctx.new_local("sum", i32_ty, Some(zero));
+        let sum = ctx.local_expr(sum);
+        naga_expr!(&mut ctx => for (i: i32_ty = (start | I32(1)); (i > I32(0)); (i - I32(2))) |ctx| {
+            ctx.increment(sum, i);
+        });
+
+        let (_, should_stop, continuing) = last_loop(&ctx);
+        assert!(matches!(
+            negated(&ctx, should_stop),
+            naga::Expression::Binary {
+                op: naga::BinaryOperator::Greater,
+                ..
+            }
+        ));
+        let Some(naga::Statement::Store { value: next, .. }) = continuing.last() else {
+            panic!("expected the step to store the next value");
+        };
+        let naga::Expression::Binary {
+            op: naga::BinaryOperator::Subtract,
+            left,
+            ..
+        } = ctx.expressions[*next]
+        else {
+            panic!("expected the step to subtract from the loaded value");
+        };
+        assert!(matches!(
+            ctx.expressions[left],
+            naga::Expression::Load { .. }
+        ));
+
+        let res = naga_expr!(&mut ctx => Load(sum));
+        ctx.result(res);
+
+        validate(&module);
+    }
+
+    /// Checks that the expression calls the given math function, giving its arguments
+    fn math_args(
+        ctx: &BlockContext<'_>,
+        expr: naga::Handle<naga::Expression>,
+        expected_fun: naga::MathFunction,
+    ) -> Vec<naga::Handle<naga::Expression>> {
+        let naga::Expression::Math {
+            fun,
+            arg,
+            arg1,
+            arg2,
+            arg3,
+        } = ctx.expressions[expr]
+        else {
+            panic!(
+                "expected {:?} but got {:?}",
+                expected_fun, ctx.expressions[expr]
+            );
+        };
+        assert_eq!(fun, expected_fun);
+        [Some(arg), arg1, arg2, arg3]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    #[test]
+    fn clamp_of_nested_expression() {
+        let mut module = naga::Module::default();
+        let i32_ty = module.types.insert_i32();
+        let (function, a, b, c, d) = declare_function! {&mut module =>
+            fn clamp_sum(a: i32_ty, b: i32_ty, c: i32_ty, d: i32_ty) -> i32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let res = naga_expr!(&mut ctx => clamp(a + b, c, d));
+
+        let args = math_args(&ctx, res, naga::MathFunction::Clamp);
+        assert_eq!(args[1..], [c, d]);
+        assert!(matches!(
+            ctx.expressions[args[0]],
+            naga::Expression::Binary {
+                op: naga::BinaryOperator::Add,
+                left,
+                right,
+            } if left == a && right == b
+        ));
+        ctx.result(res);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn mix_and_fma_of_floats() {
+        let mut module = naga::Module::default();
+        let f32_ty = module.types.insert_f32();
+        let (function, a, b, t) = declare_function! {&mut module =>
+            fn blend(a: f32_ty, b: f32_ty, t: f32_ty) -> f32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let mixed = naga_expr!(&mut ctx => mix(a, b, t));
+        assert_eq!(math_args(&ctx, mixed, naga::MathFunction::Mix), [a, b, t]);
+        let fused = naga_expr!(&mut ctx => fma(mixed, t, b));
+        assert_eq!(
+            math_args(&ctx, fused, naga::MathFunction::Fma),
+            [mixed, t, b]
+        );
+        ctx.result(fused);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn pow_and_atan2_of_floats() {
+        let mut module = naga::Module::default();
+        let f32_ty = module.types.insert_f32();
+        let (function, y, x) = declare_function! {&mut module =>
+            fn angle_power(y: f32_ty, x: f32_ty) -> f32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let angle = naga_expr!(&mut ctx => atan2(y, x));
+        assert_eq!(math_args(&ctx, angle, naga::MathFunction::Atan2), [y, x]);
+        let res = naga_expr!(&mut ctx => pow(angle, F32(2.0)));
+
+        let args = math_args(&ctx, res, naga::MathFunction::Pow);
+        assert_eq!(args.len(), 2);
+        assert_eq!(args[0], angle);
+        assert!(matches!(
+            ctx.expressions[args[1]],
+            naga::Expression::Literal(naga::Literal::F32(exponent)) if exponent == 2.0
+        ));
+        ctx.result(res);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn trig_and_log2_of_float() {
+        let mut module = naga::Module::default();
+        let f32_ty = module.types.insert_f32();
+        let (function, value) = declare_function! {&mut module =>
+            fn transcendental(value: f32_ty) -> f32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let sin = naga_expr!(&mut ctx => sin(value));
+        assert_eq!(math_args(&ctx, sin, naga::MathFunction::Sin), [value]);
+        let cos = naga_expr!(&mut ctx => cos(value));
+        assert_eq!(math_args(&ctx, cos, naga::MathFunction::Cos), [value]);
+        let tan = naga_expr!(&mut ctx => tan(sin));
+        assert_eq!(math_args(&ctx, tan, naga::MathFunction::Tan), [sin]);
+
+        // Arguments may be expressions themselves
+        let res = naga_expr!(&mut ctx => log2(tan + cos));
+        let [sum] = math_args(&ctx, res, naga::MathFunction::Log2)[..] else {
+            panic!("expected log2 to take one argument");
+        };
+        assert!(matches!(
+            ctx.expressions[sum],
+            naga::Expression::Binary {
+                op: naga::BinaryOperator::Add,
+                left,
+                right,
+            } if left == tan && right == cos
+        ));
+        ctx.result(res);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn swizzle_patterns_are_checked() {
+        for pattern in ["x", "w", "zyx", "xyzw", "wwww"] {
+            assert!(is_swizzle_pattern(pattern), "{}", pattern);
+        }
+        for pattern in ["", "xyzwx", "xq", "rgb", "X"] {
+            assert!(!is_swizzle_pattern(pattern), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn swizzle_of_one_component_is_access_index() {
+        let mut module = naga::Module::default();
+        let u32_ty = module.types.insert_u32();
+        let vec4_ty = module
+            .types
+            .insert_vecn(naga::Scalar::U32, naga::VectorSize::Quad);
+        let (function, value) = declare_function! {&mut module =>
+            fn third(value: vec4_ty) -> u32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let res = naga_expr!(&mut ctx => value.z);
+        assert!(matches!(
+            ctx.expressions[res],
+            naga::Expression::AccessIndex { base, index: 2 } if base == value
+        ));
+        ctx.result(res);
+
+        validate(&module);
+    }
+
+    #[test]
+    fn swizzles_keep_component_order() {
+        let mut module = naga::Module::default();
+        let u32_ty = module.types.insert_u32();
+        let vec4_ty = module
+            .types
+            .insert_vecn(naga::Scalar::U32, naga::VectorSize::Quad);
+        let (function, value) = declare_function! {&mut module =>
+            fn shuffle(value: vec4_ty) -> u32_ty
+        };
+        let mut ctx = BlockContext::from((&mut module, function));
+        let swizzled = |ctx: &BlockContext<'_>, expr: naga::Handle<naga::Expression>| {
+            let naga::Expression::Swizzle {
+                size,
+                vector,
+                pattern,
+            } = ctx.expressions[expr]
+            else {
+                panic!("expected a swizzle but got {:?}", ctx.expressions[expr]);
+            };
+            assert_eq!(vector, value);
+            (size, pattern)
+        };
+        use naga::SwizzleComponent::{W, X, Y, Z};
+
+        let pair = naga_expr!(&mut ctx => value.xy);
+        let (size, pattern) = swizzled(&ctx, pair);
+        assert_eq!((size, &pattern[..2]), (naga::VectorSize::Bi, &[X, Y][..]));
+
+        let reversed = naga_expr!(&mut ctx => value.zyx);
+        let (size, pattern) = swizzled(&ctx, reversed);
+        assert_eq!(
+            (size, &pattern[..3]),
+            (naga::VectorSize::Tri, &[Z, Y, X][..])
+        );
+
+        let all = naga_expr!(&mut ctx => value.wzyx);
+        let (size, pattern) = swizzled(&ctx, all);
+        assert_eq!((size, pattern), (naga::VectorSize::Quad, [W, Z, Y, X]));
+
+        // Swizzles can be chained, and used within larger expressions
+        let res = naga_expr!(&mut ctx => value.wzyx.x + (value.zyx).y);
+        let naga::Expression::Binary { left, right, .. } = ctx.expressions[res] else {
+            panic!("expected a sum but got {:?}", ctx.expressions[res]);
+        };
+        assert!(matches!(
+            ctx.expressions[left],
+            naga::Expression::AccessIndex { index: 0, .. }
+        ));
+        assert!(matches!(
+            ctx.expressions[right],
+            naga::Expression::AccessIndex { index: 1, .. }
+        ));
+        ctx.result(res);
+
+        validate(&module);
+    }
+}
@@ -0,0 +1,21 @@
+//! Re-exports the extension traits, macros and types needed to build naga modules with this library, so that they
+//! can all be brought into scope with `use naga_ext::prelude::*`.
+//!
+//! ```
+//! use naga_ext::prelude::*;
+//!
+//! let mut module = naga::Module::default();
+//! let u32_ty = module.types.insert_u32();
+//! let (function, value) = declare_function! {&mut module =>
+//!     fn increment(value: u32_ty) -> u32_ty
+//! };
+//! let mut ctx = BlockContext::from((&mut module, function));
+//! let res = naga_expr!(&mut ctx => value + U32(1));
+//! ctx.result(res);
+//! # naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty()).validate(&module).unwrap();
+//! ```
+pub use crate::{declare_entry_point, declare_function, naga_expr};
+pub use crate::{
+    BlockContext, BlockExt, ConstantsExt, ExpressionsExt, FunctionExt, FunctionSignature,
+    FunctionsExt, GlobalsExt, LocalsExt, ModuleExt, TypesExt,
+};
@@ -0,0 +1,69 @@
+use crate::parity::{gpu, ParityOutputType, WgpuState};
+
+/// Instantiates a module `invocations` times, then calls each of the named parameterless functions in turn on every
+/// instance, giving the results of each call. Since the store set is reused between calls, later functions observe
+/// the effects of earlier ones, which allows testing behaviour that can't be compared against a single wasmtime
+/// instance, such as invocations communicating through shared memory.
+pub async fn call_all_in_turn<Output: ParityOutputType>(
+    wasm: &str,
+    target_names: &[&str],
+    invocations: usize,
+    features: wasm_gpu::WasmFeatures,
+    tuneables: wasm_gpu::Tuneables,
+) -> Vec<Vec<Result<Output, wasmtime::Trap>>> {
+    let WgpuState {
+        memory_system,
+        queue,
+    } = gpu();
+
+    let module =
+        wasm_gpu::Module::new(&features, wasm.as_bytes(), "main_module".to_owned()).unwrap();
+
+    let mut store_builder =
+        wasm_gpu::MappedStoreSetBuilder::new(&memory_system, "in_turn_test_storeset", tuneables);
+
+    let instances = store_builder
+        .instantiate_module(&queue, &module, wasm_gpu::imports! {})
+        .await
+        .expect("could not instantiate all modules");
+
+    let targets: Vec<_> = target_names
+        .iter()
+        .map(|target_name| {
+            instances
+                .get_func(target_name)
+                .unwrap()
+                .try_typed::<(), Output>()
+                .unwrap()
+        })
+        .collect();
+
+    let store_source = store_builder
+        .complete(&queue)
+        .await
+        .expect("could not complete store builder");
+    let mut stores = store_source
+        .build(&memory_system, &queue, invocations)
+        .await
+        .expect("could not build stores");
+
+    let mut results = Vec::new();
+    for target in targets {
+        let got_results = target
+            .call_all(&memory_system, &queue, &mut stores, vec![(); invocations])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+
+        assert_eq!(got_results.len(), invocations);
+        results.push(
+            got_results
+                .into_iter()
+                .map(|got_result| got_result.map_err(|trap_code| wasmtime::Trap::from(trap_code)))
+                .collect(),
+        );
+    }
+
+    results
+}
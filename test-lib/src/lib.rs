@@ -1,4 +1,9 @@
+mod invocations;
 mod parity;
 
+pub use invocations::call_all_in_turn;
+pub use parity::shared_backend;
 pub use parity::test_parity;
 pub use parity::test_parity_set;
+pub use parity::test_parity_set_with_tuneables;
+pub use parity::test_parity_with_tuneables;
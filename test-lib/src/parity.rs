@@ -65,16 +65,21 @@ async fn get_backend() -> (MemorySystem, wgpu_async::AsyncQueue) {
     return (memory_system, queue);
 }
 
-struct WgpuState {
-    memory_system: MemorySystem,
-    queue: wgpu_async::AsyncQueue,
+pub(crate) struct WgpuState {
+    pub(crate) memory_system: MemorySystem,
+    pub(crate) queue: wgpu_async::AsyncQueue,
 }
 
 static GPU_STATE: OnceCell<WgpuState> = OnceCell::new();
-fn gpu<'a>() -> &'a WgpuState {
+pub(crate) fn gpu<'a>() -> &'a WgpuState {
     GPU_STATE.get_or_init(WgpuState::new)
 }
 
+/// The device used by the parity tests, for tests and benchmarks that drive `wasm-gpu` directly.
+pub fn shared_backend<'a>() -> (&'a MemorySystem, &'a wgpu_async::AsyncQueue) {
+    (&gpu().memory_system, &gpu().queue)
+}
+
 impl WgpuState {
     fn new() -> Self {
         let (memory_system, queue) = pollster::block_on(get_backend());
@@ -89,6 +94,22 @@ pub async fn test_parity<Input: ParityInputType, Output: ParityOutputType>(
     wasm: &str,
     target_name: &str,
     input: Input,
+) {
+    test_parity_with_tuneables::<Input, Output>(
+        wasm,
+        target_name,
+        input,
+        wasm_gpu::Tuneables::default(),
+    )
+    .await
+}
+
+/// As with [`test_parity`], but building the store set with non-default tuneables
+pub async fn test_parity_with_tuneables<Input: ParityInputType, Output: ParityOutputType>(
+    wasm: &str,
+    target_name: &str,
+    input: Input,
+    tuneables: wasm_gpu::Tuneables,
 ) {
     // Evaluate with wasmtime
     let engine = wasmtime::Engine::default();
@@ -112,11 +133,8 @@ pub async fn test_parity<Input: ParityInputType, Output: ParityOutputType>(
     )
     .unwrap();
 
-    let mut store_builder = wasm_gpu::MappedStoreSetBuilder::new(
-        &memory_system,
-        "parity_test_storeset",
-        wasm_gpu::Tuneables::default(),
-    );
+    let mut store_builder =
+        wasm_gpu::MappedStoreSetBuilder::new(&memory_system, "parity_test_storeset", tuneables);
 
     let instances = store_builder
         .instantiate_module(&queue, &module, wasm_gpu::imports! {})
@@ -170,6 +188,22 @@ pub async fn test_parity_set<Input: ParityInputType, Output: ParityOutputType>(
     wasm: &str,
     target_name: &str,
     inputs: Vec<Input>,
+) {
+    test_parity_set_with_tuneables::<Input, Output>(
+        wasm,
+        target_name,
+        inputs,
+        wasm_gpu::Tuneables::default(),
+    )
+    .await
+}
+
+/// As with [`test_parity_set`], but building the store set with non-default tuneables
+pub async fn test_parity_set_with_tuneables<Input: ParityInputType, Output: ParityOutputType>(
+    wasm: &str,
+    target_name: &str,
+    inputs: Vec<Input>,
+    tuneables: wasm_gpu::Tuneables,
 ) {
     // Evaluate with wasmtime
     let engine = wasmtime::Engine::default();
@@ -197,11 +231,8 @@ pub async fn test_parity_set<Input: ParityInputType, Output: ParityOutputType>(
     )
     .unwrap();
 
-    let mut store_builder = wasm_gpu::MappedStoreSetBuilder::new(
-        &memory_system,
-        "parity_test_storeset",
-        wasm_gpu::Tuneables::default(),
-    );
+    let mut store_builder =
+        wasm_gpu::MappedStoreSetBuilder::new(&memory_system, "parity_test_storeset", tuneables);
 
     let instances = store_builder
         .instantiate_module(&queue, &module, wasm_gpu::imports! {})
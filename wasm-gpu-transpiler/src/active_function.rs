@@ -3,21 +3,24 @@ mod arguments;
 mod locals;
 mod results;
 
-use self::active_block::{ActiveBlock, BlockType, BodyData};
+pub use self::active_block::unlowered_proposal;
+use self::active_block::{ActiveBlock, BlockLocals, BlockType, BodyData};
 use self::results::WasmFnResTy;
 use self::{
-    arguments::{EntryArguments, WasmFnArgs},
+    arguments::{EntryArguments, FnArg, WasmFnArgs},
     locals::FnLocals,
 };
 use crate::typed::FuncRef;
 use naga::Handle;
-use naga_ext::{naga_expr, BlockContext, BlockExt, ExpressionsExt, ModuleExt};
+use naga_ext::prelude::*;
 use sealed::sealed;
+use wasmparser::FuncType;
 
 use crate::active_function::active_block::EndInstruction;
-use crate::{build, get_entry_name, std_objects::StdObjects, FuncUnit};
+use crate::{build, get_entry_name, std_objects::StdObjects, FuncUnit, IoLayout, Tuneables};
 
 use crate::active_module::ActiveModule;
+use crate::function_lookup::FunctionLookup;
 
 /// A set of handles to a function that can be 'activated' given a mutable reference to a module
 pub(crate) trait InactiveFunction {
@@ -42,6 +45,16 @@ pub(crate) trait ActiveFunction<'f>: 'f {
         'f: 'b;
 }
 
+/// The naga function that a direct call to a wasm function is lowered to.
+#[derive(Debug, Clone)]
+pub(crate) struct CallTarget {
+    pub(crate) handle: Handle<naga::Function>,
+    pub(crate) argument_count: usize,
+    pub(crate) result_count: usize,
+    /// The wasm type of the function, used to find candidates for indirect calls
+    pub(crate) ty: FuncType,
+}
+
 pub(crate) struct InternalFunction {
     handle: Handle<naga::Function>,
     wasm_arguments: WasmFnArgs,
@@ -56,8 +69,12 @@ impl InternalFunction {
         name_suffix: &str,
         ptr: FuncRef,
         function_definition: &FuncUnit,
+        preserve_names: bool,
     ) -> build::Result<Self> {
-        let name = get_entry_name(ptr) + name_suffix;
+        let name = match &function_definition.data.name {
+            Some(wasm_name) if preserve_names => wasm_name.clone(),
+            _ => get_entry_name(ptr),
+        } + name_suffix;
 
         let wasm_results =
             WasmFnResTy::make_type(module, std_objects, function_definition.data.ty.results());
@@ -77,6 +94,7 @@ impl InternalFunction {
             std_objects,
             &function_definition.data.locals,
             &wasm_arguments,
+            preserve_names.then_some(&function_definition.data.local_names),
         )?;
 
         Ok(Self {
@@ -88,6 +106,88 @@ impl InternalFunction {
     }
 }
 
+impl InternalFunction {
+    /// Populates the body of a base function within the given context, which must be the root context of the
+    /// function. Only the module level types, constants and constant expressions in the context are modified
+    /// outside of the function itself, so bodies can be populated against copies of those arenas.
+    pub(crate) fn populate_base_body(
+        &self,
+        mut ctx: BlockContext<'_>,
+        func_data: &FuncUnit,
+        call_targets: &FunctionLookup<CallTarget>,
+        std_objects: &StdObjects,
+        tuneables: &Tuneables,
+    ) -> build::Result<()> {
+        // Decompose this into the parts needed for the blocks
+        let accessible = &func_data.accessible;
+        let module_data = func_data.data.module_data.as_ref();
+        let return_type = &self.wasm_results;
+        let block_type =
+            BlockType::from_return_type(return_type.as_ref().map(|ty| ty.components().clone()));
+
+        let body_data = BodyData::new(
+            self.handle,
+            accessible,
+            module_data,
+            return_type,
+            &self.locals,
+            call_targets,
+            &mut ctx,
+            std_objects,
+            tuneables,
+        );
+
+        // Define base block
+        let locals = BlockLocals::append_to("body", &mut ctx, std_objects, block_type);
+        let mut base_block = ActiveBlock::new((&mut ctx).into(), locals, &body_data, None);
+
+        // Parse instructions, alongside the spans that the statements generated for them are given
+        let spans = func_data
+            .data
+            .operator_ranges
+            .iter()
+            .map(|range| match tuneables.emit_debug_spans {
+                true => naga::Span::new(
+                    u32::try_from(range.start).expect("module binaries are smaller than 4GiB"),
+                    u32::try_from(range.end).expect("module binaries are smaller than 4GiB"),
+                ),
+                false => naga::Span::UNDEFINED,
+            })
+            .chain(std::iter::repeat(naga::Span::UNDEFINED));
+        let mut instructions = func_data.data.operators.iter().zip(spans).peekable();
+
+        // Populate recursively
+        let end = base_block.populate_straight(&mut instructions)?;
+        assert_eq!(end, EndInstruction::End);
+        let (results, control_flow_state) = base_block.finish();
+
+        debug_assert!(instructions.next().is_none(), "validation ensures that all instructions are within the body and that blocks are balanced");
+
+        // Return results if there's a chance control flow exits the end of the block of the body
+        if control_flow_state.lower_unconditional_depth.is_none() {
+            body_data.push_final_return(ctx, results)
+        }
+
+        return Ok(());
+    }
+
+    pub(crate) fn handle(&self) -> Handle<naga::Function> {
+        self.handle
+    }
+
+    pub(crate) fn call_target(&self, ty: FuncType) -> CallTarget {
+        CallTarget {
+            handle: self.handle,
+            argument_count: self.wasm_arguments.len(),
+            result_count: self
+                .wasm_results
+                .as_ref()
+                .map_or(0, |results| results.components().len()),
+            ty,
+        }
+    }
+}
+
 impl InactiveFunction for InternalFunction {
     type Active<'f, 'm: 'f> = ActiveInternalFunction<'f, 'm>;
 
@@ -112,56 +212,23 @@ impl<'f, 'm: 'f> ActiveInternalFunction<'f, 'm> {
         self.data.handle
     }
 
-    /// Populates the body of a base function that doesn't use the stack.
-    pub(crate) fn populate_base_function(&mut self, func_data: &FuncUnit) -> build::Result<()> {
-        let Self {
-            working_module,
-            data,
-        } = self;
-
-        // Decompose this into the parts needed for the blocks
-        let accessible = &func_data.accessible;
-        let module_data = func_data.data.module_data.as_ref();
-        let return_type = &data.wasm_results;
-        let block_type =
-            BlockType::from_return_type(return_type.as_ref().map(|ty| ty.components().clone()));
+    /// Populates the body of a base function that doesn't use the stack. Direct calls are lowered to the
+    /// base functions given in `call_targets`.
+    pub(crate) fn populate_base_function(
+        &mut self,
+        func_data: &FuncUnit,
+        call_targets: &FunctionLookup<CallTarget>,
+    ) -> build::Result<()> {
         let ActiveModule {
             module,
             std_objects,
-            ..
-        } = working_module;
+            tuneables,
+        } = self.working_module;
 
-        let mut ctx = BlockContext::from((&mut **module, data.handle));
-
-        let body_data = BodyData::new(
-            accessible,
-            module_data,
-            return_type,
-            &data.locals,
-            &mut ctx,
-            &std_objects,
-            working_module.tuneables,
-        );
+        let ctx = BlockContext::from((&mut **module, self.data.handle));
 
-        // Define base block
-        let mut base_block = ActiveBlock::new((&mut ctx).into(), block_type, &body_data, None);
-
-        // Parse instructions
-        let mut instructions = func_data.data.operators.iter().peekable();
-
-        // Populate recursively
-        let end = base_block.populate_straight(&mut instructions)?;
-        assert_eq!(end, EndInstruction::End);
-        let (results, control_flow_state) = base_block.finish();
-
-        debug_assert!(instructions.next().is_none(), "validation ensures that all instructions are within the body and that blocks are balanced");
-
-        // Return results if there's a chance control flow exits the end of the block of the body
-        if control_flow_state.lower_unconditional_depth.is_none() {
-            body_data.push_final_return(ctx, results)
-        }
-
-        return Ok(());
+        self.data
+            .populate_base_body(ctx, func_data, call_targets, std_objects, tuneables)
     }
 
     pub(crate) fn get_arg_tys(&self) -> &WasmFnArgs {
@@ -208,6 +275,55 @@ impl<'f, 'm: 'f> ActiveFunction<'f> for ActiveInternalFunction<'f, 'm> {
     }
 }
 
+/// The start of each region of the packed I/O buffer used by an entry function, in words.
+#[derive(Copy, Clone)]
+struct PackedIoOffsets {
+    flags: naga::Handle<naga::Expression>,
+    input: naga::Handle<naga::Expression>,
+    output: naga::Handle<naga::Expression>,
+}
+
+/// Where the inputs or outputs of the invocation being executed by an entry function are found within an I/O buffer.
+#[derive(Copy, Clone)]
+pub(crate) struct IoLocation {
+    /// The first word of the region holding the values of every invocation, or `None` if the region starts at the
+    /// start of its buffer
+    region_offset: Option<naga::Handle<naga::Expression>>,
+    instance_index: naga::Handle<naga::Expression>,
+    invocations_count: naga::Handle<naga::Expression>,
+    layout: IoLayout,
+    /// The number of words taken by the values of each invocation, see `IoLayout::invocation_words`
+    invocation_words: u32,
+}
+
+impl IoLocation {
+    /// Gives the index of the first word of one of this invocation's values, which starts `value_offset` words into
+    /// the values of an invocation and takes `value_words` words.
+    pub(crate) fn value_index(
+        &self,
+        mut ctx: BlockContext<'_>,
+        value_offset: u32,
+        value_words: u32,
+    ) -> naga::Handle<naga::Expression> {
+        let instance_index = self.instance_index;
+        let invocations_count = self.invocations_count;
+        let index = match self.layout {
+            IoLayout::Packed => {
+                let invocation_words = self.invocation_words;
+                naga_expr!(&mut ctx => (U32(invocation_words) * instance_index) + U32(value_offset))
+            }
+            IoLayout::StructOfArrays => {
+                naga_expr!(&mut ctx => (U32(value_offset) * invocations_count) + (U32(value_words) * instance_index))
+            }
+        };
+
+        match self.region_offset {
+            Some(region_offset) => naga_expr!(&mut ctx => region_offset + index),
+            None => index,
+        }
+    }
+}
+
 pub(crate) struct EntryFunction {
     index: usize,
     args: EntryArguments,
@@ -218,21 +334,22 @@ impl EntryFunction {
         module: &mut naga::Module,
         std_objects: &StdObjects,
         ptr: FuncRef,
+        workgroup_size: u32,
     ) -> Self {
         let name = get_entry_name(ptr);
 
-        let mut function = naga::Function::default();
-
-        let args = EntryArguments::append_to(&mut function, std_objects);
+        let uvec3_ty = std_objects.preamble.uvec3_ty;
+        let (index, global_id) = naga_ext::declare_entry_point! {module =>
+            fn {name}(global_id: uvec3_ty @builtin(GlobalInvocationId))
+                @workgroup_size(workgroup_size, 1, 1)
+        };
 
-        let index = module.entry_points.len();
-        module.entry_points.push(naga::EntryPoint {
-            name,
-            stage: naga::ShaderStage::Compute,
-            early_depth_test: None,
-            workgroup_size: [crate::WORKGROUP_SIZE, 1, 1],
-            function,
-        });
+        let args = EntryArguments {
+            global_id: FnArg {
+                type_handle: uvec3_ty,
+                expression_handle: global_id,
+            },
+        };
 
         Self { index, args }
     }
@@ -263,33 +380,103 @@ impl<'f, 'm: 'f> ActiveEntryFunction<'f, 'm> {
         naga_expr! {self.ctx() => global_id[const 0]}
     }
 
-    fn io_base_index(
-        &mut self,
-        io_word_alignment: u32,
+    fn io_location(
+        &self,
+        invocation_words: u32,
         instance_index: naga::Handle<naga::Expression>,
+        invocations_count: naga::Handle<naga::Expression>,
+        region_offset: Option<naga::Handle<naga::Expression>>,
+    ) -> IoLocation {
+        IoLocation {
+            region_offset,
+            instance_index,
+            invocations_count,
+            layout: self.working_module.tuneables.io_layout,
+            invocation_words,
+        }
+    }
+
+    /// When the per-dispatch buffers are packed into a single binding, calculates where each region starts.
+    /// Mirrors the layout given by `crate::PackedIoLayout`.
+    fn packed_io_offsets(
+        &mut self,
+        arguments: &WasmFnArgs,
+        results_ty: &Option<WasmFnResTy>,
+        invocations_count: naga::Handle<naga::Expression>,
+    ) -> PackedIoOffsets {
+        let tuneables = *self.working_module.tuneables;
+        let input_words = arguments.word_alignment(&tuneables);
+        let output_words = results_ty
+            .as_ref()
+            .map(|results_ty| results_ty.word_alignment(&tuneables))
+            .unwrap_or(0);
+
+        let flags = naga_expr!(self.ctx() => U32(crate::CONSTANTS_LEN_BYTES / 4));
+        let input =
+            naga_expr!(self.ctx() => flags + (invocations_count * U32(crate::FLAGS_LEN_BYTES / 4)));
+        let output = naga_expr!(self.ctx() => input + (invocations_count * U32(input_words)));
+
+        PackedIoOffsets {
+            flags,
+            input,
+            output,
+        }
+    }
+
+    /// Gets a pointer to one of the words in the flags of the given invocation.
+    fn flag_ptr(
+        &mut self,
+        invocation_id: naga::Handle<naga::Expression>,
+        packed_offsets: Option<PackedIoOffsets>,
+        flag_index: u32,
     ) -> naga::Handle<naga::Expression> {
-        naga_expr! {self.ctx() => U32(io_word_alignment) * instance_index}
+        let flags_buffer = self.working_module.std_objects.preamble.bindings.flags;
+        match packed_offsets {
+            Some(PackedIoOffsets { flags, .. }) => {
+                let flag_index = naga_expr!(self.ctx() => flags + ((invocation_id * U32(crate::FLAGS_LEN_BYTES / 4)) + U32(flag_index)));
+                naga_expr!(self.ctx() => Global(flags_buffer)[flag_index])
+            }
+            None => {
+                naga_expr!(self.ctx() => Global(flags_buffer)[invocation_id][const flag_index])
+            }
+        }
     }
 
     fn read_entry_inputs(
         &mut self,
         arguments: &WasmFnArgs,
         instance_index: naga::Handle<naga::Expression>,
+        invocations_count: naga::Handle<naga::Expression>,
+        region_offset: Option<naga::Handle<naga::Expression>>,
     ) -> Vec<naga::Handle<naga::Expression>> {
-        let base_index = self.io_base_index(arguments.word_alignment(), instance_index);
+        let tuneables = *self.working_module.tuneables;
+        let location = self.io_location(
+            arguments.word_alignment(&tuneables),
+            instance_index,
+            invocations_count,
+            region_offset,
+        );
 
-        return arguments.append_read_at(self, base_index);
+        return arguments.append_read_at(self, location, &tuneables);
     }
 
     fn store_output(
         &mut self,
         ty: &WasmFnResTy,
         instance_index: naga::Handle<naga::Expression>,
+        invocations_count: naga::Handle<naga::Expression>,
         value: naga::Handle<naga::Expression>,
+        region_offset: Option<naga::Handle<naga::Expression>>,
     ) -> build::Result<()> {
-        let base_index = self.io_base_index(ty.word_alignment(), instance_index);
+        let tuneables = *self.working_module.tuneables;
+        let location = self.io_location(
+            ty.word_alignment(&tuneables),
+            instance_index,
+            invocations_count,
+            region_offset,
+        );
 
-        ty.append_store_at(self, base_index, value)
+        ty.append_store_at(self, location, value, &tuneables)
     }
 
     /// Generates function that extracts arguments from buffer, calls base function,
@@ -319,8 +506,14 @@ impl<'f, 'm: 'f> ActiveEntryFunction<'f, 'm> {
             .body
             .push_store(invocations_count_ptr, invocations_count);
 
+        let packed_offsets = if self.working_module.tuneables.pack_io_bindings {
+            Some(self.packed_io_offsets(arguments, results_ty, invocations_count))
+        } else {
+            None
+        };
+
         // Don't execute if we're beyond the invocation count
-        let is_not_being_executed = naga_expr!(self.ctx() => invocation_id > invocations_count);
+        let is_not_being_executed = naga_expr!(self.ctx() => invocation_id >= invocations_count);
         let mut if_not_being_executed = naga::Block::default();
         if_not_being_executed.push_bare_return();
         self.fn_mut().body.push_if(
@@ -329,8 +522,55 @@ impl<'f, 'm: 'f> ActiveEntryFunction<'f, 'm> {
             naga::Block::default(),
         );
 
+        // Read memory size, which may be changed by the invocation
+        let memory_pages = self.working_module.std_objects.preamble.memory_pages;
+        let memory_pages_ptr = naga_expr!(self.ctx() => Global(memory_pages));
+        let memory_pages_flag = self.flag_ptr(
+            invocation_id,
+            packed_offsets,
+            crate::MEMORY_PAGES_FLAG_INDEX,
+        );
+        let initial_memory_pages = naga_expr!(self.ctx() => Load(memory_pages_flag));
+        self.fn_mut()
+            .body
+            .push_store(memory_pages_ptr, initial_memory_pages);
+
+        // Read dropped data segments, which may also be changed by the invocation
+        let data_dropped = self.working_module.std_objects.preamble.data_dropped;
+        let mut dropped_words = Vec::new();
+        for i in 0..crate::DATA_DROPPED_FLAG_WORDS {
+            let word_ptr = naga_expr!(self.ctx() => Global(data_dropped)[const i]);
+            let word_flag = self.flag_ptr(
+                invocation_id,
+                packed_offsets,
+                crate::DATA_DROPPED_FLAG_INDEX + i,
+            );
+            let initial_word = naga_expr!(self.ctx() => Load(word_flag));
+            self.fn_mut().body.push_store(word_ptr, initial_word);
+            dropped_words.push((word_ptr, word_flag));
+        }
+
+        // And dropped element segments
+        let element_dropped = self.working_module.std_objects.preamble.element_dropped;
+        for i in 0..crate::ELEMENT_DROPPED_FLAG_WORDS {
+            let word_ptr = naga_expr!(self.ctx() => Global(element_dropped)[const i]);
+            let word_flag = self.flag_ptr(
+                invocation_id,
+                packed_offsets,
+                crate::ELEMENT_DROPPED_FLAG_INDEX + i,
+            );
+            let initial_word = naga_expr!(self.ctx() => Load(word_flag));
+            self.fn_mut().body.push_store(word_ptr, initial_word);
+            dropped_words.push((word_ptr, word_flag));
+        }
+
         // Call fn
-        let arguments = self.read_entry_inputs(arguments, invocation_id);
+        let arguments = self.read_entry_inputs(
+            arguments,
+            invocation_id,
+            invocations_count,
+            packed_offsets.map(|offsets| offsets.input),
+        );
         let results: Option<(&WasmFnResTy, Handle<naga::Expression>)> =
             results_ty.as_ref().map(|results_ty| {
                 (
@@ -352,16 +592,33 @@ impl<'f, 'm: 'f> ActiveEntryFunction<'f, 'm> {
 
         // Write outputs
         if let Some((results_ty, results_expr)) = results {
-            self.store_output(results_ty, invocation_id, results_expr)?;
+            self.store_output(
+                results_ty,
+                invocation_id,
+                invocations_count,
+                results_expr,
+                packed_offsets.map(|offsets| offsets.output),
+            )?;
         }
 
         // Write trap status
         let flag_state = self.working_module.std_objects.preamble.trap_state;
-        let flags_buffer = self.working_module.std_objects.preamble.bindings.flags;
         let flag_state = naga_expr!(self.ctx() => Load(Global(flag_state)));
-        let write_word_loc = naga_expr!(self.ctx() => Global(flags_buffer)[invocation_id][const crate::TRAP_FLAG_INDEX]);
+        let write_word_loc = self.flag_ptr(invocation_id, packed_offsets, crate::TRAP_FLAG_INDEX);
         self.fn_mut().body.push_store(write_word_loc, flag_state);
 
+        // Write back memory size
+        let final_memory_pages = naga_expr!(self.ctx() => Load(memory_pages_ptr));
+        self.fn_mut()
+            .body
+            .push_store(memory_pages_flag, final_memory_pages);
+
+        // Write back dropped data and element segments
+        for (word_ptr, word_flag) in dropped_words {
+            let final_word = naga_expr!(self.ctx() => Load(word_ptr));
+            self.fn_mut().body.push_store(word_flag, final_word);
+        }
+
         return Ok(());
     }
 }
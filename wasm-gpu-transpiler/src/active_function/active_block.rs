@@ -1,14 +1,21 @@
 use std::iter::Peekable;
 
 use itertools::Itertools;
-use naga_ext::{naga_expr, BlockContext, ConstantsExt};
-use wasm_opcodes::{proposals::ControlFlowOperator, OperatorByProposal};
-use wasmparser::ValType;
-use wasmtime_environ::Trap;
+use naga_ext::prelude::*;
+use wasm_opcodes::{
+    proposals::{ControlFlowOperator, MVPOperator},
+    OperatorByProposal,
+};
+use wasmparser::{FuncType, ValType};
+use wasmtime_environ::{Trap, WASM_PAGE_SIZE};
 
 use crate::{
-    build, linked_stack::LinkedStack, std_objects::StdObjects, typed::Val, BuildError,
-    ExceededComponent, FuncAccessible, FunctionModuleData, Tuneables, MEMORY_STRIDE_WORDS,
+    build,
+    function_lookup::FunctionLookup,
+    linked_stack::LinkedStack,
+    std_objects::StdObjects,
+    typed::{FuncRef, Val},
+    BoundsCheckMode, BuildError, ExceededComponent, FuncAccessible, FunctionModuleData, Tuneables,
 };
 
 use self::block_label::{BlockLabel, BlockLabelGen};
@@ -16,14 +23,33 @@ use self::block_label::{BlockLabel, BlockLabelGen};
 use super::{
     locals::{FnLocal, FnLocals},
     results::WasmFnResTy,
+    CallTarget,
 };
 
 mod block_label;
+mod bulk_memory;
 mod mvp;
+mod reference_types;
+mod saturating_float_to_int;
 mod sign_extension;
 mod simd;
 mod threads;
 
+/// Gives the proposal that an instruction comes from if the instruction can't be lowered yet, so that modules using
+/// it can be rejected before any lowering is attempted.
+pub fn unlowered_proposal(operation: &OperatorByProposal) -> Option<&'static str> {
+    match operation {
+        OperatorByProposal::SIMD(simd_op) if !simd::is_lowered(simd_op) => Some("simd"),
+        OperatorByProposal::Exceptions(_) => Some("exceptions"),
+        OperatorByProposal::TailCall(_) => Some("tail_call"),
+        OperatorByProposal::RelaxedSIMD(_) => Some("relaxed_simd"),
+        OperatorByProposal::FunctionReferences(_) => Some("function_references"),
+        OperatorByProposal::MemoryControl(_) => Some("memory_control"),
+        OperatorByProposal::GC(_) => Some("gc"),
+        _ => None,
+    }
+}
+
 /// Blocks have parameters that they take off the stack, and results that they put back
 #[derive(Clone, Debug)]
 pub(super) struct BlockType {
@@ -66,11 +92,60 @@ impl BlockType {
     }
 }
 
+/// Blocks take arguments off of the stack and push back results, like inline functions. To manage these
+/// values in a control-flow independent way (e.g. for looping or branching blocks) we take the parameters
+/// as locals and pass them back as locals to be read. Both arms of an if share one set of locals, so that
+/// whichever arm is taken leaves its results in the same place.
+#[derive(Clone)]
+pub(super) struct BlockLocals {
+    arguments: Vec<FnLocal>,
+    results: Vec<FnLocal>,
+}
+
+impl BlockLocals {
+    pub(super) fn append_to(
+        name_prefix: &str,
+        ctx: &mut BlockContext<'_>,
+        std_objects: &StdObjects,
+        block_type: BlockType,
+    ) -> Self {
+        let arguments = FnLocal::append_all_wasm_to(
+            format!("{}_arguments", name_prefix),
+            ctx,
+            std_objects,
+            block_type.arguments,
+        );
+        let results = FnLocal::append_all_wasm_to(
+            format!("{}_results", name_prefix),
+            ctx,
+            std_objects,
+            block_type.results,
+        );
+
+        Self { arguments, results }
+    }
+
+    /// Stores the values taken off of the parent's stack, before the block is entered
+    fn assign_arguments(
+        &self,
+        ctx: &mut BlockContext<'_>,
+        values: Vec<naga::Handle<naga::Expression>>,
+    ) {
+        for (value, local) in values.into_iter().zip_eq(&self.arguments) {
+            ctx.store(local.expression, value);
+        }
+    }
+}
+
 /// Data shared across an entire function body, shared by many blocks and mutable references are built into as blocks are populated.
 pub(crate) struct BodyData<'a> {
     std_objects: &'a StdObjects,
     tuneables: &'a Tuneables,
 
+    /// The function being populated, and the functions that it can directly call.
+    handle: naga::Handle<naga::Function>,
+    call_targets: &'a FunctionLookup<CallTarget>,
+
     // Taken from the definition of the function
     accessible: &'a FuncAccessible,
     module_data: &'a FunctionModuleData,
@@ -82,14 +157,18 @@ pub(crate) struct BodyData<'a> {
 
     /// To avoid checking if we have trapped too often, this counter is used and incremented on each loop iteration
     trap_check_counter: naga::Handle<naga::Expression>,
+    /// The trap state as of the last time it was read by a loop, which loops exit on while it is non-zero
+    observed_trap_state: naga::Handle<naga::Expression>,
 }
 
 impl<'a> BodyData<'a> {
     pub(crate) fn new(
+        handle: naga::Handle<naga::Function>,
         accessible: &'a FuncAccessible,
         module_data: &'a FunctionModuleData,
         return_type: &'a Option<WasmFnResTy>,
         locals: &'a FnLocals,
+        call_targets: &'a FunctionLookup<CallTarget>,
         ctx: &mut BlockContext<'_>,
         std_objects: &'a StdObjects,
         tuneables: &'a Tuneables,
@@ -101,21 +180,103 @@ impl<'a> BodyData<'a> {
             Some(zero),
         );
         let trap_check_counter = ctx.local_expr(trap_check_counter);
+        let observed_trap_state = ctx.new_local(
+            "observed_trap_state",
+            std_objects.preamble.word_ty,
+            Some(zero),
+        );
+        let observed_trap_state = ctx.local_expr(observed_trap_state);
 
         let block_label_set = BlockLabelGen::new(ctx);
 
         Self {
             tuneables,
+            handle,
+            call_targets,
             accessible,
             module_data,
             return_type,
             locals,
             std_objects,
             trap_check_counter,
+            observed_trap_state,
             block_label_set,
         }
     }
 
+    /// Finds the function to call for a `call` instruction with the given function index.
+    fn call_target(&self, function_index: u32) -> build::Result<CallTarget> {
+        let ptr = self
+            .accessible
+            .func_index_lookup
+            .get(usize::try_from(function_index).expect("module must fit in memory"))
+            .expect("an OoB function reference should be caught by validation");
+        let target = self.call_targets.lookup(ptr).clone();
+
+        // Naga requires that callees are declared before their callers, which only fails to hold for recursion
+        if target.handle.index() >= self.handle.index() {
+            return Err(BuildError::UnsupportedRecursion { callee: *ptr });
+        }
+
+        Ok(target)
+    }
+
+    /// Finds every function that a `call_indirect` instruction with the given type could call, sorted by funcref.
+    /// Naga requires that callees are declared before their callers, so functions not declared before this one
+    /// (including this function itself) could only be reached by recursion, and are given separately.
+    fn indirect_call_targets(&self, ty: &FuncType) -> (Vec<(FuncRef, CallTarget)>, Vec<FuncRef>) {
+        let (targets, recursive_targets): (Vec<_>, Vec<_>) = self
+            .call_targets
+            .iter()
+            .filter(|(_, target)| target.ty == *ty)
+            .map(|(ptr, target)| (*ptr, target.clone()))
+            .sorted_by_key(|(ptr, _)| ptr.as_u32())
+            .partition(|(_, target)| target.handle.index() < self.handle.index());
+        let recursive_targets = recursive_targets
+            .into_iter()
+            .map(|(ptr, _)| ptr)
+            .collect_vec();
+
+        (targets, recursive_targets)
+    }
+
+    /// Finds the word offset of a table within the tables buffer, along with the number of entries in the table and
+    /// the type of those entries.
+    fn table(&self, table_index: u32) -> (u32, u32, ValType) {
+        let table_index = usize::try_from(table_index).expect("module must fit in memory");
+        let table_word = **self
+            .accessible
+            .table_index_lookup
+            .get(table_index)
+            .expect("an OoB table reference should be caught by validation");
+        let table_size = self.accessible.table_size_lookup[table_index];
+        let table_ty = self.accessible.table_type_lookup[table_index];
+
+        (table_word, table_size, table_ty)
+    }
+
+    /// Gets a pointer to an entry of a table in the tables buffer. The element index is not bounds checked.
+    fn table_entry_ptr(
+        &self,
+        ctx: &mut BlockContext<'_>,
+        table_word: u32,
+        element_index: naga::Handle<naga::Expression>,
+    ) -> naga::Handle<naga::Expression> {
+        let tables = self.std_objects.preamble.bindings.tables;
+
+        let mut word = naga_expr!(ctx => U32(table_word) + element_index);
+        if self.tuneables.disjoint_memory {
+            // Tables are interleaved between instances one word at a time
+            let invocations_count_global = self.std_objects.preamble.invocations_count;
+            let instance_id_global = self.std_objects.preamble.instance_id;
+            let invocations_count = naga_expr!(ctx => Load(Global(invocations_count_global)));
+            let instance_id = naga_expr!(ctx => Load(Global(instance_id_global)));
+            word = naga_expr!(ctx => (word * invocations_count) + instance_id);
+        }
+
+        naga_expr!(ctx => Global(tables)[word])
+    }
+
     /// Return results by popping from the current stack, or just returns if the function has no such return values.
     fn push_return(&self, ctx: BlockContext<'_>, stack: &mut Vec<naga::Handle<naga::Expression>>) {
         if let Some(return_type) = &self.return_type {
@@ -131,6 +292,24 @@ impl<'a> BodyData<'a> {
         }
     }
 
+    /// Returns default values, or just returns if the function has no return values. Used to leave a function
+    /// once it has trapped, since whatever is returned is discarded.
+    fn push_default_return(&self, mut ctx: BlockContext<'_>) {
+        if let Some(return_type) = &self.return_type {
+            let results = return_type
+                .components()
+                .iter()
+                .map(|ty| {
+                    let default = self.std_objects.get_default_value(*ty);
+                    naga_expr!(&mut ctx => Constant(default))
+                })
+                .collect_vec();
+            return_type.push_return(ctx, results);
+        } else {
+            ctx.void_return()
+        }
+    }
+
     /// Used in the outer function scope once the final block has been completed to emit the final return
     pub(crate) fn push_final_return(&self, mut ctx: BlockContext<'_>, results: Vec<FnLocal>) {
         let mut result_expressions = Vec::new();
@@ -145,6 +324,14 @@ impl<'a> BodyData<'a> {
         self.push_return(ctx, &mut result_expressions);
     }
 }
+/// The instruction that ended a basic block.
+#[derive(Copy, Clone, Debug)]
+enum BasicBlockEnd<'c> {
+    ControlFlow(&'c ControlFlowOperator, naga::Span),
+    /// An `unreachable` instruction, after which the instance has trapped and nothing more is executed.
+    Unreachable(naga::Span),
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub(crate) enum EndInstruction {
     End,
@@ -161,6 +348,15 @@ pub(crate) struct ControlFlowState {
     pub(crate) lower_conditional_depth: Option<u32>,
 }
 impl ControlFlowState {
+    /// The state after unconditionally branching to the given depth.
+    fn branch(relative_depth: u32) -> ControlFlowState {
+        ControlFlowState {
+            lower_unconditional_depth: Some(relative_depth),
+            lower_conditional_depth: Some(relative_depth),
+            upper_conditional_depth: Some(relative_depth),
+        }
+    }
+
     /// Finds the state given that control flow may have come from the left or right branch.
     fn union(lhs: ControlFlowState, rhs: ControlFlowState) -> ControlFlowState {
         ControlFlowState {
@@ -243,7 +439,11 @@ use binary;
 
 macro_rules! mem_load {
     ($state:ident, $memarg:ident, $ty:ident::$fn:ident) => {
-        $state.pop_one_push_call_mem_func($memarg, $state.std_objects().$ty.$fn)
+        $state.pop_one_push_call_mem_func(
+            $memarg,
+            $state.std_objects().$ty.$fn,
+            $state.std_objects().$ty.default,
+        )
     };
 }
 use mem_load;
@@ -267,9 +467,7 @@ pub(crate) struct ActiveBlock<'b> {
 
     labels: LinkedStack<'b, BlockLabel>,
 
-    /// Blocks take arguments off of the stack and push back results, like inline functions. To manage these
-    /// values in a control-flow independent way (e.g. for looping or branching blocks) we take the parameters
-    /// as locals and pass them back as locals to be read.
+    /// See [`BlockLocals`]
     arguments: Vec<FnLocal>,
     results: Vec<FnLocal>,
 
@@ -279,27 +477,17 @@ pub(crate) struct ActiveBlock<'b> {
 }
 
 impl<'b> ActiveBlock<'b> {
+    /// Enters a block, reading its arguments from locals that must have been assigned before the block
+    /// was entered.
     pub(super) fn new(
         mut ctx: BlockContext<'b>,
-        block_type: BlockType,
+        locals: BlockLocals,
         body_data: &'b BodyData<'b>,
         parents: Option<&'b LinkedStack<BlockLabel>>,
     ) -> Self {
         // Set up block-level data
         let own_label = body_data.block_label_set.get_label(&mut ctx);
-
-        let arguments = FnLocal::append_all_wasm_to(
-            format!("block_{}_arguments", own_label.id()),
-            &mut ctx,
-            &body_data.std_objects,
-            block_type.arguments,
-        );
-        let results = FnLocal::append_all_wasm_to(
-            format!("block_{}_results", own_label.id()),
-            &mut ctx,
-            &body_data.std_objects,
-            block_type.results,
-        );
+        let BlockLocals { arguments, results } = locals;
 
         let labels = match parents {
             Some(parents) => parents.push(own_label),
@@ -324,20 +512,14 @@ impl<'b> ActiveBlock<'b> {
         }
     }
 
-    pub(crate) fn assign_arguments(&mut self, values: Vec<naga::Handle<naga::Expression>>) {
-        for (value, local) in values.into_iter().zip_eq(&self.arguments) {
-            self.ctx.store(local.expression, value);
-        }
-    }
-
     /// Webassembly allows breaks/returns/jumps mid-block, while naga doesn't. This is a sink method used
     /// after an unconditional branch when we need to discard everything left in a function. It eats up to,
-    /// but not including, the next *balanced* end instruction
+    /// but not including, the next *balanced* end or else instruction
     fn eat_to_end<'a: 'c, 'c>(
-        instructions: &mut Peekable<impl Iterator<Item = &'c OperatorByProposal<'a>>>,
+        instructions: &mut Peekable<impl Iterator<Item = (&'c OperatorByProposal<'a>, naga::Span)>>,
     ) {
         let mut depth = 0;
-        while let Some(instruction) = instructions.peek() {
+        while let Some((instruction, _)) = instructions.peek() {
             match instruction {
                 OperatorByProposal::ControlFlow(cfo) => match cfo {
                     ControlFlowOperator::End => {
@@ -346,6 +528,8 @@ impl<'b> ActiveBlock<'b> {
                         }
                         depth -= 1
                     }
+                    // The else of the if that we are within ends the current arm
+                    ControlFlowOperator::Else if depth == 0 => return,
                     ControlFlowOperator::Block { .. }
                     | ControlFlowOperator::If { .. }
                     | ControlFlowOperator::Loop { .. } => depth += 1,
@@ -365,6 +549,13 @@ impl<'b> ActiveBlock<'b> {
         }
     }
 
+    /// Peeks the top n items and stores them in the n argument variables, for branching back to the start of a loop
+    fn push_store_stack_in_arguments(&mut self) {
+        for (value, local) in self.stack.iter().rev().zip(self.arguments.iter().rev()) {
+            self.ctx.store(local.expression, *value);
+        }
+    }
+
     /// Stores true in the variables giving whether the top n parents should break
     fn push_store_break_top_n_parents(
         ctx: &mut BlockContext<'_>,
@@ -380,11 +571,7 @@ impl<'b> ActiveBlock<'b> {
 
     fn do_br(&mut self, relative_depth: u32) -> ControlFlowState {
         Self::push_store_break_top_n_parents(&mut self.ctx, &self.labels, relative_depth);
-        ControlFlowState {
-            lower_unconditional_depth: Some(relative_depth),
-            lower_conditional_depth: Some(relative_depth),
-            upper_conditional_depth: Some(relative_depth),
-        }
+        ControlFlowState::branch(relative_depth)
     }
 
     fn do_br_if(&mut self, relative_depth: u32) -> ControlFlowState {
@@ -404,10 +591,42 @@ impl<'b> ActiveBlock<'b> {
         }
     }
 
+    fn do_br_table(&mut self, targets: &wasmparser::BrTable<'_>) -> ControlFlowState {
+        let value = self.pop();
+        let selector = naga_expr!(self => bitcast<u32>(value));
+
+        let depths: Vec<u32> = targets
+            .targets()
+            .collect::<Result<_, _>>()
+            .expect("br_table targets were read when the module was parsed");
+        let default_depth = targets.default();
+
+        // Out of range selectors, including negative ones, take the default branch
+        let mut switch = self.ctx.switch(selector);
+        for (index, relative_depth) in depths.iter().enumerate() {
+            let index =
+                u32::try_from(index).expect("br_table cannot have more than u32::MAX targets");
+            switch = switch.case(index, |mut ctx| {
+                Self::push_store_break_top_n_parents(&mut ctx, &self.labels, *relative_depth);
+            });
+        }
+        switch.default(|mut ctx| {
+            Self::push_store_break_top_n_parents(&mut ctx, &self.labels, default_depth);
+        });
+
+        // We definitely branch, but to which depth depends on the selector
+        depths.into_iter().fold(
+            ControlFlowState::branch(default_depth),
+            |state, relative_depth| {
+                ControlFlowState::union(state, ControlFlowState::branch(relative_depth))
+            },
+        )
+    }
+
     fn do_block<'a: 'c, 'c>(
         &mut self,
         blockty: wasmparser::BlockType,
-        instructions: &mut Peekable<impl Iterator<Item = &'c OperatorByProposal<'a>>>,
+        instructions: &mut Peekable<impl Iterator<Item = (&'c OperatorByProposal<'a>, naga::Span)>>,
     ) -> build::Result<ControlFlowState> {
         let block_type = BlockType::from_parsed(blockty, &self.body_data.module_data.types);
 
@@ -418,17 +637,13 @@ impl<'b> ActiveBlock<'b> {
         }
         args.reverse();
 
-        // Make new block, temporarily moving out of this
-        let mut inner_block = naga::Block::default();
-        let mut inner_active_block = ActiveBlock::new(
-            (&mut self.ctx).into(),
-            block_type,
-            self.body_data,
-            Some(&self.labels),
-        );
+        let locals =
+            BlockLocals::append_to("block", &mut self.ctx, &self.body_data.std_objects, block_type);
+        locals.assign_arguments(&mut self.ctx, args);
 
-        // Write args
-        inner_active_block.assign_arguments(args);
+        // Make new block, temporarily moving out of this
+        let mut inner_active_block =
+            ActiveBlock::new((&mut self.ctx).into(), locals, self.body_data, Some(&self.labels));
 
         let end = inner_active_block.populate_straight(instructions)?;
         debug_assert_eq!(end, EndInstruction::End);
@@ -446,7 +661,7 @@ impl<'b> ActiveBlock<'b> {
     fn do_if<'a: 'c, 'c>(
         &mut self,
         blockty: wasmparser::BlockType,
-        instructions: &mut Peekable<impl Iterator<Item = &'c OperatorByProposal<'a>>>,
+        instructions: &mut Peekable<impl Iterator<Item = (&'c OperatorByProposal<'a>, naga::Span)>>,
     ) -> build::Result<ControlFlowState> {
         let value = self.pop();
         let wasm_false = self.body_data.std_objects.preamble.wasm_bool.const_false;
@@ -461,20 +676,21 @@ impl<'b> ActiveBlock<'b> {
         }
         args.reverse();
 
+        // Both arms share locals, so the results are unified regardless of which arm is taken
+        let locals =
+            BlockLocals::append_to("if", &mut self.ctx, &self.body_data.std_objects, block_type);
+        locals.assign_arguments(&mut self.ctx, args.clone());
+
         let mut end = EndInstruction::End;
-        let mut results = vec![];
         let mut exit_state = ControlFlowState::default();
         let test = self.ctx.test(condition).try_then(|ctx| {
             // Make new accept block
             let mut accept_block =
-                ActiveBlock::new(ctx, block_type.clone(), self.body_data, Some(&self.labels));
-
-            // Write args
-            accept_block.assign_arguments(args.clone());
+                ActiveBlock::new(ctx, locals.clone(), self.body_data, Some(&self.labels));
 
             // Perform body
             end = accept_block.populate_straight(instructions)?;
-            (results, exit_state) = accept_block.finish();
+            (_, exit_state) = accept_block.finish();
 
             Ok(())
         })?;
@@ -483,12 +699,8 @@ impl<'b> ActiveBlock<'b> {
             test.otherwise(|ctx| {
                 // Make new reject block
                 let mut reject_block =
-                    ActiveBlock::new(ctx, block_type, self.body_data, Some(&self.labels));
+                    ActiveBlock::new(ctx, locals.clone(), self.body_data, Some(&self.labels));
 
-                // Write args
-                reject_block.assign_arguments(args);
-                // Overwrite results to unify if statement results
-                reject_block.results = results.clone();
                 let end = reject_block.populate_straight(instructions)?;
                 debug_assert_eq!(end, EndInstruction::End);
                 let (_, reject_exit_state) = reject_block.finish();
@@ -499,15 +711,16 @@ impl<'b> ActiveBlock<'b> {
             })?;
         } else {
             test.otherwise(|mut ctx| {
-                // To match if branch, write popped args to results
-                for (arg, result) in args.into_iter().zip_eq(results.iter()) {
+                // Validation requires an if without an else to have the same arguments as results, so
+                // to match the if branch, write popped args to results
+                for (arg, result) in args.into_iter().zip_eq(locals.results.iter()) {
                     ctx.store(result.expression, arg)
                 }
             });
         };
 
         // Extract unified results
-        for result in results {
+        for result in locals.results {
             let expr = naga_expr!(self => Load(result.expression));
             self.stack.push(expr);
         }
@@ -518,7 +731,7 @@ impl<'b> ActiveBlock<'b> {
     fn do_loop<'a: 'c, 'c>(
         &mut self,
         blockty: wasmparser::BlockType,
-        instructions: &mut Peekable<impl Iterator<Item = &'c OperatorByProposal<'a>>>,
+        instructions: &mut Peekable<impl Iterator<Item = (&'c OperatorByProposal<'a>, naga::Span)>>,
     ) -> build::Result<ControlFlowState> {
         let trap_state = self.body_data.std_objects.preamble.trap_state;
         let trap_state = naga_expr!(self => Global(trap_state));
@@ -532,35 +745,49 @@ impl<'b> ActiveBlock<'b> {
         }
         args.reverse();
 
-        // Make loop block
-        let (results, exit_state) = self.ctx.cycle(|mut ctx| {
-            let mut loop_body = ActiveBlock::new(
-                ctx.reborrow(),
-                block_type,
-                self.body_data,
-                Some(&self.labels),
-            );
-
-            // To avoid infinite loops on trapped modules, periodically check if we have trapped
-            let trapped_condition = naga_expr!(&mut loop_body => Load(trap_state) != U32(0));
-            loop_body
-                .ctx
-                .test(trapped_condition)
-                .then(|ctx| ctx.stop_loop());
+        // Arguments are assigned before the loop, and re-assigned each time the loop is branched back to
+        let locals =
+            BlockLocals::append_to("loop", &mut self.ctx, &self.body_data.std_objects, block_type);
+        locals.assign_arguments(&mut self.ctx, args);
+
+        // Make loop block. To avoid infinite loops on trapped modules, check if we have trapped every
+        // `trap_check_interval` iterations
+        let trap_check_interval = self.body_data.tuneables.trap_check_interval;
+        let trap_check_counter = self.body_data.trap_check_counter;
+        let observed_trap_state = self.body_data.observed_trap_state;
+        let (results, exit_state) = self.ctx.loop_with_break_if(
+            |ctx| {
+                if trap_check_interval == 1 {
+                    return naga_expr!(ctx => Load(trap_state) != U32(0));
+                }
 
-            // Write args
-            loop_body.assign_arguments(args);
+                let one = naga_expr!(ctx => U32(1));
+                let count = ctx.increment(trap_check_counter, one);
+                let is_due = naga_expr!(ctx => count >= U32(trap_check_interval));
+                ctx.test(is_due).then(|mut ctx| {
+                    let zero = naga_expr!(&mut ctx => U32(0));
+                    ctx.store(trap_check_counter, zero);
+                    let state = naga_expr!(&mut ctx => Load(trap_state));
+                    ctx.store(observed_trap_state, state);
+                });
+
+                naga_expr!(ctx => Load(observed_trap_state) != U32(0))
+            },
+            |mut ctx| {
+                let mut loop_body =
+                    ActiveBlock::new(ctx.reborrow(), locals, self.body_data, Some(&self.labels));
 
-            // Do loop body
-            let end = loop_body.populate_looping(instructions)?;
-            debug_assert_eq!(end, EndInstruction::End);
-            let res = loop_body.finish();
+                // Do loop body
+                let end = loop_body.populate_looping(instructions)?;
+                debug_assert_eq!(end, EndInstruction::End);
+                let res = loop_body.finish();
 
-            // Loops exit if they don't continue
-            ctx.stop_loop();
+                // Loops exit if they don't continue
+                ctx.stop_loop();
 
-            Ok(res)
-        })?;
+                Ok(res)
+            },
+        )?;
 
         // Extract results
         for result in results {
@@ -571,6 +798,124 @@ impl<'b> ActiveBlock<'b> {
         Ok(exit_state)
     }
 
+    fn do_call(&mut self, function_index: u32) -> build::Result<ControlFlowState> {
+        let target = self.body_data.call_target(function_index)?;
+
+        let mut arguments = Vec::new();
+        for _ in 0..target.argument_count {
+            arguments.push(self.pop());
+        }
+        arguments.reverse();
+
+        // Traps within the callee are recorded in the shared trap state, so there is nothing to propagate here
+        if target.result_count == 0 {
+            self.ctx.call_void(target.handle, arguments);
+        } else {
+            let results = self.ctx.call_get_return(target.handle, arguments);
+            for i_res in 0..target.result_count {
+                let i_res = u32::try_from(i_res)
+                    .map_err(|_| BuildError::BoundsExceeded(ExceededComponent::ReturnType))?;
+                let result = naga_expr!(self => results[const i_res]);
+                self.stack.push(result);
+            }
+        }
+
+        Ok(ControlFlowState::default())
+    }
+
+    /// Naga has no function pointers, so indirect calls are lowered to a switch over every function with the expected type.
+    fn do_call_indirect(
+        &mut self,
+        type_index: u32,
+        table_index: u32,
+    ) -> build::Result<ControlFlowState> {
+        let body_data = self.body_data;
+        let std_objects = body_data.std_objects;
+        let ty = body_data
+            .module_data
+            .types
+            .get(usize::try_from(type_index).expect("module must fit in memory"))
+            .expect("an OoB type reference should be caught by validation");
+        let (targets, recursive_targets) = body_data.indirect_call_targets(ty);
+
+        let (table_word, table_size, _) = body_data.table(table_index);
+
+        let element_index = self.pop();
+        let mut arguments = Vec::new();
+        for _ in 0..ty.params().len() {
+            arguments.push(self.pop());
+        }
+        arguments.reverse();
+
+        let results = FnLocal::append_all_wasm_to(
+            "call_indirect_results".to_owned(),
+            &mut self.ctx,
+            std_objects,
+            ty.results().to_vec(),
+        );
+
+        let trap_values = &std_objects.preamble.trap_values;
+        let trap_state = std_objects.preamble.trap_state;
+
+        let element_index = naga_expr!(self => bitcast<u32>(element_index));
+        let in_bounds = naga_expr!(self => element_index < U32(table_size));
+        self.ctx
+            .test(in_bounds)
+            .then(|mut ctx| {
+                let entry_ptr = body_data.table_entry_ptr(&mut ctx, table_word, element_index);
+                let func_ref = naga_expr!(&mut ctx => Load(entry_ptr));
+
+                let mut switch = ctx.switch(func_ref);
+                for (ptr, target) in &targets {
+                    let value = ptr.as_u32().expect("call targets are never null");
+                    switch = switch.case(value, |mut ctx| {
+                        if target.result_count == 0 {
+                            ctx.call_void(target.handle, arguments.clone());
+                        } else {
+                            let returned = ctx.call_get_return(target.handle, arguments.clone());
+                            for (i_res, result) in results.iter().enumerate() {
+                                let i_res = i_res as u32;
+                                let value = naga_expr!(&mut ctx => returned[const i_res]);
+                                ctx.store(result.expression, value);
+                            }
+                        }
+                    });
+                }
+                // Recursion isn't supported yet, so a call that would recurse behaves as if the stack were exhausted
+                for ptr in &recursive_targets {
+                    let value = ptr.as_u32().expect("call targets are never null");
+                    switch = switch.case(value, |mut ctx| {
+                        trap_values.emit_set_trap(&mut ctx, Trap::StackOverflow, trap_state)
+                    });
+                }
+                switch.default(|mut ctx| {
+                    let is_null = naga_expr!(&mut ctx => func_ref == U32(u32::MAX));
+                    ctx.test(is_null)
+                        .then(|mut ctx| {
+                            trap_values.emit_set_trap(
+                                &mut ctx,
+                                Trap::IndirectCallToNull,
+                                trap_state,
+                            )
+                        })
+                        .otherwise(|mut ctx| {
+                            trap_values.emit_set_trap(&mut ctx, Trap::BadSignature, trap_state)
+                        });
+                });
+            })
+            .otherwise(|mut ctx| {
+                trap_values.emit_set_trap(&mut ctx, Trap::TableOutOfBounds, trap_state)
+            });
+
+        // Extract results, which keep their default values if we trapped
+        for result in results {
+            let expr = naga_expr!(self => Load(result.expression));
+            self.stack.push(expr);
+        }
+
+        Ok(ControlFlowState::default())
+    }
+
     fn do_return(&mut self) -> ControlFlowState {
         self.body_data
             .push_return((&mut self.ctx).into(), &mut self.stack);
@@ -582,17 +927,41 @@ impl<'b> ActiveBlock<'b> {
         }
     }
 
+    /// Sets the unreachable trap and leaves the function. Callers observe the trap the next time they check
+    /// the trap state, so the instance halts rather than continuing past the `unreachable`.
+    fn do_unreachable(&mut self) -> build::Result<ControlFlowState> {
+        self.append_trap(Trap::UnreachableCodeReached)?;
+        self.body_data.push_default_return((&mut self.ctx).into());
+
+        Ok(ControlFlowState {
+            lower_unconditional_depth: Some(u32::MAX), // Gone
+            upper_conditional_depth: Some(u32::MAX),
+            lower_conditional_depth: Some(u32::MAX),
+        })
+    }
+
     /// Populates a block using the callbacks provided
     pub(crate) fn populate<'a: 'c, 'c>(
         &mut self,
-        instructions: &mut Peekable<impl Iterator<Item = &'c OperatorByProposal<'a>>>,
+        instructions: &mut Peekable<impl Iterator<Item = (&'c OperatorByProposal<'a>, naga::Span)>>,
         // What to do when we're branching with relative distance 0
         on_r0_branching: impl Fn(&mut ActiveBlock<'_>),
         // What to do when we're branching with relative distance >0
         on_rp_branching: impl Fn(&mut ActiveBlock<'_>),
     ) -> build::Result<EndInstruction> {
         let end_instruction = loop {
-            let operation = self.eat_basic_block(instructions)?;
+            let (operation, span) = match self.eat_basic_block(instructions)? {
+                BasicBlockEnd::ControlFlow(operation, span) => (operation, span),
+                BasicBlockEnd::Unreachable(span) => {
+                    let statements_before = self.ctx.block.len();
+                    let state = self.do_unreachable()?;
+                    self.ctx.block.fill_spans_from(statements_before, span);
+                    self.exit_state = ControlFlowState::concat(self.exit_state, state.decrement());
+                    Self::eat_to_end(instructions);
+                    continue;
+                }
+            };
+            let statements_before = self.ctx.block.len();
             let state = match operation {
                 ControlFlowOperator::End => {
                     break EndInstruction::End;
@@ -607,14 +976,16 @@ impl<'b> ActiveBlock<'b> {
                 ControlFlowOperator::If { blockty } => self.do_if(*blockty, instructions)?,
                 ControlFlowOperator::Loop { blockty } => self.do_loop(*blockty, instructions)?,
                 ControlFlowOperator::Return => self.do_return(),
-                ControlFlowOperator::BrTable { targets } => unimplemented!(),
-                ControlFlowOperator::Call { function_index } => unimplemented!(),
+                ControlFlowOperator::BrTable { targets } => self.do_br_table(targets),
+                ControlFlowOperator::Call { function_index } => self.do_call(*function_index)?,
                 ControlFlowOperator::CallIndirect {
                     type_index,
                     table_index,
-                    table_byte,
-                } => unimplemented!(),
+                    ..
+                } => self.do_call_indirect(*type_index, *table_index)?,
             };
+            // Statements within nested blocks were given the spans of their own instructions
+            self.ctx.block.fill_spans_from(statements_before, span);
 
             self.exit_state = ControlFlowState::concat(self.exit_state, state.decrement());
 
@@ -693,7 +1064,7 @@ impl<'b> ActiveBlock<'b> {
     /// Populates a non-looping (straight) block
     pub(crate) fn populate_straight<'a: 'c, 'c>(
         &mut self,
-        instructions: &mut Peekable<impl Iterator<Item = &'c OperatorByProposal<'a>>>,
+        instructions: &mut Peekable<impl Iterator<Item = (&'c OperatorByProposal<'a>, naga::Span)>>,
     ) -> build::Result<EndInstruction> {
         self.populate(instructions, |_| {}, |_| {})
     }
@@ -701,21 +1072,13 @@ impl<'b> ActiveBlock<'b> {
     /// Populates a looping block
     pub(crate) fn populate_looping<'a: 'c, 'c>(
         &mut self,
-        instructions: &mut Peekable<impl Iterator<Item = &'c OperatorByProposal<'a>>>,
+        instructions: &mut Peekable<impl Iterator<Item = (&'c OperatorByProposal<'a>, naga::Span)>>,
     ) -> build::Result<EndInstruction> {
         self.populate(
             instructions,
             |active| {
-                // Read results back to arguments
-                for (dst, src) in active
-                    .arguments
-                    .clone()
-                    .into_iter()
-                    .zip(active.results.clone().into_iter())
-                {
-                    let value = naga_expr!(active => Load(src.expression));
-                    active.ctx.store(dst.expression, value);
-                }
+                // Branching to a loop passes the top of the stack back in as the loop's arguments
+                active.push_store_stack_in_arguments();
                 active.ctx.resume_loop();
             },
             |active| {
@@ -745,15 +1108,30 @@ impl<'b> ActiveBlock<'b> {
     /// Fills instructions until some control flow instruction
     fn eat_basic_block<'a: 'c, 'c, 's>(
         &'s mut self,
-        instructions: &mut impl Iterator<Item = &'c OperatorByProposal<'a>>,
-    ) -> build::Result<&'c ControlFlowOperator> {
+        instructions: &mut Peekable<impl Iterator<Item = (&'c OperatorByProposal<'a>, naga::Span)>>,
+    ) -> build::Result<BasicBlockEnd<'c>> {
         let mut last_op = None;
-        while let Some(operation) = instructions.next() {
+        while let Some((operation, span)) = instructions.next() {
+            let statements_before = self.ctx.block.len();
             match operation {
                 OperatorByProposal::ControlFlow(found_last_op) => {
-                    last_op = Some(found_last_op);
+                    last_op = Some(BasicBlockEnd::ControlFlow(found_last_op, span));
+                    break;
+                }
+                OperatorByProposal::MVP(MVPOperator::Unreachable) => {
+                    last_op = Some(BasicBlockEnd::Unreachable(span));
                     break;
                 }
+                // Extending an i32 and immediately wrapping it back is the identity, so skip building the i64
+                OperatorByProposal::MVP(
+                    MVPOperator::I64ExtendI32S | MVPOperator::I64ExtendI32U,
+                ) if matches!(
+                    instructions.peek(),
+                    Some((OperatorByProposal::MVP(MVPOperator::I32WrapI64), _))
+                ) =>
+                {
+                    instructions.next();
+                }
                 OperatorByProposal::MVP(mvp_op) => mvp::eat_mvp_operator(self, mvp_op)?,
                 OperatorByProposal::SignExtension(sign_ext_op) => {
                     sign_extension::eat_sign_extension_operator(self, sign_ext_op)?
@@ -762,11 +1140,20 @@ impl<'b> ActiveBlock<'b> {
                 OperatorByProposal::Threads(threads_op) => {
                     threads::eat_threads_operator(self, threads_op)?
                 }
+                OperatorByProposal::BulkMemory(bulk_memory_op) => {
+                    bulk_memory::eat_bulk_memory_operator(self, bulk_memory_op)?
+                }
+                OperatorByProposal::ReferenceTypes(reference_types_op) => {
+                    reference_types::eat_reference_types_operator(self, reference_types_op)?
+                }
+                OperatorByProposal::SaturatingFloatToInt(saturating_float_to_int_op) => {
+                    saturating_float_to_int::eat_saturating_float_to_int_operator(
+                        self,
+                        saturating_float_to_int_op,
+                    )?
+                }
                 OperatorByProposal::Exceptions(_)
                 | OperatorByProposal::TailCall(_)
-                | OperatorByProposal::ReferenceTypes(_)
-                | OperatorByProposal::SaturatingFloatToInt(_)
-                | OperatorByProposal::BulkMemory(_)
                 | OperatorByProposal::RelaxedSIMD(_)
                 | OperatorByProposal::FunctionReferences(_)
                 | OperatorByProposal::MemoryControl(_)
@@ -776,6 +1163,7 @@ impl<'b> ActiveBlock<'b> {
                     })
                 }
             };
+            self.ctx.block.fill_spans_from(statements_before, span);
         }
 
         return Ok(last_op.expect("blocks should be balanced"));
@@ -812,6 +1200,151 @@ impl<'b> ActiveBlock<'b> {
             .clone()
     }
 
+    /// The byte offset of a memory within the memory buffer, which holds each memory one after another.
+    pub(super) fn memory_base(&self, mem: u32) -> u32 {
+        **self
+            .body_data
+            .accessible
+            .memory_index_lookup
+            .get(usize::try_from(mem).expect("module must fit in memory"))
+            .expect("an OoB memory reference should be caught by validation")
+    }
+
+    /// The current size of a memory, in pages. Only the memory at the start of the memory buffer can grow, with its
+    /// size tracked in the `memory_pages` global, so every other memory keeps its initial size.
+    fn memory_pages(&mut self, mem: u32) -> naga::Handle<naga::Expression> {
+        if self.memory_base(mem) == 0 {
+            let memory_pages = self.std_objects().preamble.memory_pages;
+            return naga_expr!(self => Load(Global(memory_pages)));
+        }
+
+        let pages = *self
+            .body_data
+            .accessible
+            .memory_pages_lookup
+            .get(usize::try_from(mem).expect("module must fit in memory"))
+            .expect("an OoB memory reference should be caught by validation");
+        naga_expr!(self => U32(pages))
+    }
+
+    /// The number of pages that a memory may grow to, as allocated when it was instantiated.
+    fn memory_capacity(&self, mem: u32) -> u32 {
+        *self
+            .body_data
+            .accessible
+            .memory_capacity_lookup
+            .get(usize::try_from(mem).expect("module must fit in memory"))
+            .expect("an OoB memory reference should be caught by validation")
+    }
+
+    fn do_table_get(&mut self, table_index: u32) -> build::Result<()> {
+        let body_data = self.body_data;
+        let std_objects = body_data.std_objects;
+        let (table_word, table_size, table_ty) = body_data.table(table_index);
+
+        let element_index = self.pop();
+        let element_index = naga_expr!(self => bitcast<u32>(element_index));
+
+        // Null if we trap
+        let null = std_objects.get_default_value(table_ty);
+        let null = self.ctx.constant_expr(null);
+        let result = self.ctx.new_local(
+            "table_get_result",
+            std_objects.get_val_type(table_ty),
+            Some(null),
+        );
+        let result = self.ctx.local_expr(result);
+
+        let trap_values = &std_objects.preamble.trap_values;
+        let trap_state = std_objects.preamble.trap_state;
+
+        let in_bounds = naga_expr!(self => element_index < U32(table_size));
+        self.ctx
+            .test(in_bounds)
+            .then(|mut ctx| {
+                let entry_ptr = body_data.table_entry_ptr(&mut ctx, table_word, element_index);
+                let value = naga_expr!(&mut ctx => Load(entry_ptr));
+                ctx.store(result, value);
+            })
+            .otherwise(|mut ctx| {
+                trap_values.emit_set_trap(&mut ctx, Trap::TableOutOfBounds, trap_state)
+            });
+
+        let result = naga_expr!(self => Load(result));
+        self.stack.push(result);
+
+        Ok(())
+    }
+
+    fn do_table_set(&mut self, table_index: u32) -> build::Result<()> {
+        let body_data = self.body_data;
+        let std_objects = body_data.std_objects;
+        let (table_word, table_size, _) = body_data.table(table_index);
+
+        let value = self.pop();
+        let element_index = self.pop();
+        let element_index = naga_expr!(self => bitcast<u32>(element_index));
+
+        let trap_values = &std_objects.preamble.trap_values;
+        let trap_state = std_objects.preamble.trap_state;
+
+        let in_bounds = naga_expr!(self => element_index < U32(table_size));
+        self.ctx
+            .test(in_bounds)
+            .then(|mut ctx| {
+                // If we have trapped, don't store
+                let is_trapped = naga_expr!(&mut ctx => Load(Global(trap_state)) != U32(0));
+                ctx.test(is_trapped).otherwise(|mut ctx| {
+                    let entry_ptr = body_data.table_entry_ptr(&mut ctx, table_word, element_index);
+                    ctx.store(entry_ptr, value);
+                });
+            })
+            .otherwise(|mut ctx| {
+                trap_values.emit_set_trap(&mut ctx, Trap::TableOutOfBounds, trap_state)
+            });
+
+        Ok(())
+    }
+
+    fn do_memory_size(&mut self, mem: u32) -> build::Result<()> {
+        let pages = self.memory_pages(mem);
+        let pages = naga_expr!(self => bitcast<i32>(pages));
+        self.stack.push(pages);
+
+        Ok(())
+    }
+
+    fn do_memory_grow(&mut self, mem: u32) -> build::Result<()> {
+        let capacity = self.memory_capacity(mem);
+
+        let delta = self.pop();
+        let delta = naga_expr!(self => bitcast<u32>(delta));
+
+        // Memories other than the first can't grow, but growing by nothing always succeeds
+        if self.memory_base(mem) != 0 {
+            let pages = self.memory_pages(mem);
+            let is_empty = naga_expr!(self => delta == U32(0));
+            let result = naga_expr!(self => if (is_empty) {bitcast<i32>(pages)} else {I32(-1)});
+            self.stack.push(result);
+
+            return Ok(());
+        }
+
+        let memory_pages = self.std_objects().preamble.memory_pages;
+        let memory_pages_ptr = naga_expr!(self => Global(memory_pages));
+        let old_pages = naga_expr!(self => Load(memory_pages_ptr));
+
+        // Growing fails if it would go beyond the space reserved when the memory was instantiated
+        let can_grow = naga_expr!(self => delta <= (U32(capacity) - old_pages));
+        let new_pages = naga_expr!(self => if (can_grow) {old_pages + delta} else {old_pages});
+        self.ctx.store(memory_pages_ptr, new_pages);
+
+        let result = naga_expr!(self => if (can_grow) {bitcast<i32>(old_pages)} else {I32(-1)});
+        self.stack.push(result);
+
+        Ok(())
+    }
+
     /// Calls trap, recording the given flag
     fn append_trap(&mut self, trap_id: Trap) -> build::Result<()> {
         let mut ctx = self.into();
@@ -852,20 +1385,16 @@ impl<'b> ActiveBlock<'b> {
     }
 
     /// Takes a byte address in shared memory space and calculates the address in disjoint memory space. I.e. calculates
-    /// `(address / STRIDE) * invocations_count + STRIDE * instance_id + (address % STRIDE)`
+    /// `(address / STRIDE) * STRIDE * invocations_count + STRIDE * instance_id + (address % STRIDE)`
     fn disjoint_memory_address(
         &mut self,
         shared_address: naga::Handle<naga::Expression>,
     ) -> naga::Handle<naga::Expression> {
-        let stride_bytes = naga_expr!(self => U32(MEMORY_STRIDE_WORDS * 4));
-
-        let invocations_count_global = self.std_objects().preamble.invocations_count;
-        let invocations_count = naga_expr!(self => Load(Global(invocations_count_global)));
-
-        let instance_id_global = self.std_objects().preamble.instance_id;
-        let instance_id = naga_expr!(self => Load(Global(instance_id_global)));
-
-        naga_expr!(self => ((shared_address / stride_bytes) * {invocations_count}) + (stride_bytes * instance_id) + (shared_address % stride_bytes))
+        let mut ctx = self.into();
+        self.body_data
+            .std_objects
+            .preamble
+            .disjoint_memory_address(&mut ctx, shared_address)
     }
 
     /// Calls a function and pushes the result of the call onto the stack
@@ -889,68 +1418,154 @@ impl<'b> ActiveBlock<'b> {
         self.push_call(function, vec![value])
     }
 
-    /// Used when calling a memory function, by popping the address, adding the memory arg as constants and pushing a call to the memory function
-    fn pop_one_push_call_mem_func(
+    /// Checks that an access of `size_bytes` bytes at the given address lies within the memory, as configured by
+    /// `Tuneables::bounds_checks`, trapping with `MemoryOutOfBounds` if it doesn't. The address has already had the
+    /// memarg offset added, so an address smaller than the offset has wrapped.
+    ///
+    /// Returns the shared address to access, which is redirected to the start of the memory if out of bounds,
+    /// and an expression which is true if the access is in bounds, if checks are enabled.
+    pub(super) fn emit_memory_bounds_check(
+        &mut self,
+        mem: u32,
+        address: naga::Handle<naga::Expression>,
+        offset: u32,
+        size_bytes: u32,
+    ) -> (
+        naga::Handle<naga::Expression>,
+        Option<naga::Handle<naga::Expression>>,
+    ) {
+        let (address, in_bounds) = if self.body_data.tuneables.bounds_checks.is_checked() {
+            let (address, in_bounds) = self.emit_checked_address(mem, address, offset, size_bytes);
+            (address, Some(in_bounds))
+        } else {
+            (address, None)
+        };
+
+        // Translate from the memory's address space to the shared address space of all memories
+        let base = self.memory_base(mem);
+        if base == 0 {
+            return (address, in_bounds);
+        }
+        let address = naga_expr!(self => address + U32(base));
+
+        (address, in_bounds)
+    }
+
+    /// Performs the checks for `emit_memory_bounds_check`, giving the address to access within the memory and
+    /// whether the access is in bounds.
+    fn emit_checked_address(
+        &mut self,
+        mem: u32,
+        address: naga::Handle<naga::Expression>,
+        offset: u32,
+        size_bytes: u32,
+    ) -> (
+        naga::Handle<naga::Expression>,
+        naga::Handle<naga::Expression>,
+    ) {
+        let std_objects = self.body_data.std_objects;
+        let trap_values = &std_objects.preamble.trap_values;
+        let trap_state = std_objects.preamble.trap_state;
+
+        let memory_pages = self.memory_pages(mem);
+        let memory_bytes = naga_expr!(self => memory_pages * U32(WASM_PAGE_SIZE));
+        let not_wrapped = naga_expr!(self => address >= U32(offset));
+        let fits = naga_expr!(self => U32(size_bytes) <= memory_bytes);
+        let before_end = naga_expr!(self => address <= (memory_bytes - U32(size_bytes)));
+        let in_bounds = naga_expr!(self => (not_wrapped & fits) & before_end);
+        let out_of_bounds = naga_expr!(self => !in_bounds);
+        self.ctx.test(out_of_bounds).then(|mut ctx| {
+            trap_values.emit_set_trap(&mut ctx, Trap::MemoryOutOfBounds, trap_state)
+        });
+
+        let address = naga_expr!(self => if (in_bounds) {address} else {U32(0)});
+
+        (address, in_bounds)
+    }
+
+    /// Pops an address and adds the memarg offset to it, checking the access and translating it into the shared
+    /// address space of all memories, then into disjoint memory if required. Returns the address to access and, if
+    /// bounds checks are enabled, whether the access is in bounds.
+    fn pop_memory_access(
         &mut self,
         memarg: &wasmparser::MemArg,
-        memory_function: naga::Handle<naga::Function>,
-    ) -> Result<(), BuildError> {
+    ) -> build::Result<(
+        naga::Handle<naga::Expression>,
+        Option<naga::Handle<naga::Expression>>,
+    )> {
         let wasmparser::MemArg {
             offset,
             memory,
             // Alignment has no semantic influence, it is a performance hint
             align: _,
-            max_align: _,
+            max_align,
         } = memarg;
 
         let offset = u32::try_from(*offset)
             .map_err(|_| BuildError::BoundsExceeded(ExceededComponent::MemArgOffset))?;
 
-        let memory = naga_expr!(self => U32(*memory));
-
         let address = self.pop();
-        let mut address = naga_expr!(self => address + U32(offset));
+        let address = naga_expr!(self => (bitcast<u32>(address)) + U32(offset));
+
+        // The maximum alignment of an access is its natural alignment, which is the number of bytes accessed
+        let (mut address, in_bounds) =
+            self.emit_memory_bounds_check(*memory, address, offset, 1 << *max_align);
 
         if self.body_data.tuneables.disjoint_memory {
             address = self.disjoint_memory_address(address);
         }
 
-        self.push_call(memory_function, vec![memory, address])
+        Ok((address, in_bounds))
+    }
+
+    /// Used when calling a memory function, by popping the address, adding the memory arg as constants and pushing a call
+    /// to the memory function. If bounds checks read zero when out of bounds, the default value is pushed instead.
+    fn pop_one_push_call_mem_func(
+        &mut self,
+        memarg: &wasmparser::MemArg,
+        memory_function: naga::Handle<naga::Function>,
+        default: naga::Handle<naga::Constant>,
+    ) -> Result<(), BuildError> {
+        let (address, in_bounds) = self.pop_memory_access(memarg)?;
+
+        let mut result = self.ctx.call_get_return(memory_function, vec![address]);
+
+        if let Some(in_bounds) = in_bounds {
+            if self.body_data.tuneables.bounds_checks == BoundsCheckMode::ReadZeroSkipWrite {
+                result = naga_expr!(self => if (in_bounds) {result} else {Constant(default)});
+            }
+        }
+
+        self.stack.push(result);
+
+        Ok(())
     }
 
     /// Used when calling a memory function, by popping the address and operand, adding the memory arg as constants
     /// and calling the memory function (discarding the return value). This method also optionally incorporates the
-    /// invocation ID to the memory operation, if `disjoint_memory` is enabled.
+    /// invocation ID to the memory operation, if `disjoint_memory` is enabled. If bounds checks skip writes when out
+    /// of bounds, the call is only made when the access is in bounds.
     fn pop_two_call_mem_func(
         &mut self,
         memarg: &wasmparser::MemArg,
         memory_function: naga::Handle<naga::Function>,
     ) -> Result<(), BuildError> {
-        let wasmparser::MemArg {
-            offset,
-            memory,
-            // Alignment has no semantic influence, it is a performance hint
-            align: _,
-            max_align: _,
-        } = memarg;
-
-        let offset = u32::try_from(*offset)
-            .map_err(|_| BuildError::BoundsExceeded(ExceededComponent::MemArgOffset))?;
-
         let value = self.pop();
 
-        let memory = naga_expr!(self => U32(*memory));
-
-        let address = self.pop();
-        let mut address = naga_expr!(self => address + U32(offset));
+        let (address, in_bounds) = self.pop_memory_access(memarg)?;
+        let arguments = vec![address, value];
 
-        if self.body_data.tuneables.disjoint_memory {
-            address = self.disjoint_memory_address(address);
+        match in_bounds {
+            Some(in_bounds)
+                if self.body_data.tuneables.bounds_checks == BoundsCheckMode::ReadZeroSkipWrite =>
+            {
+                self.ctx.test(in_bounds).then(|mut ctx| {
+                    ctx.call_void(memory_function, arguments);
+                });
+            }
+            _ => self.ctx.call_void(memory_function, arguments),
         }
 
-        self.ctx
-            .call_void(memory_function, vec![memory, address, value]);
-
         Ok(())
     }
 
@@ -1,6 +1,6 @@
 use std::sync::atomic::AtomicUsize;
 
-use naga_ext::{block_context::Test, naga_expr, BlockContext, TypesExt};
+use naga_ext::{block_context::Test, prelude::*};
 
 use crate::active_function::locals::FnLocal;
 
@@ -0,0 +1,204 @@
+use naga_ext::naga_expr;
+use wasm_opcodes::proposals::BulkMemoryOperator;
+use wasmtime_environ::Trap;
+
+use crate::{
+    build, BuildError, ExceededComponent, MAX_DROPPABLE_DATA_SEGMENTS,
+    MAX_DROPPABLE_ELEMENT_SEGMENTS,
+};
+
+use super::ActiveBlock;
+
+/// The location of a data segment in the data buffer, its length in bytes, and the position of its dropped flag.
+fn data_segment(state: &ActiveBlock<'_>, data_index: u32) -> build::Result<(u32, u32, u32)> {
+    let accessible = &state.body_data.accessible;
+    let data_index = usize::try_from(data_index).expect("module must fit in memory");
+
+    let data = accessible
+        .data_index_lookup
+        .get(data_index)
+        .expect("an OoB data reference should be caught by validation");
+    let length = accessible.data_size_lookup[data_index];
+    let segment = accessible.data_segment_lookup[data_index];
+
+    if segment >= MAX_DROPPABLE_DATA_SEGMENTS {
+        return Err(BuildError::BoundsExceeded(ExceededComponent::DataSegments));
+    }
+
+    Ok((**data, length, segment))
+}
+
+/// The word offset of an element segment in the elements buffer, its length in references, and the position of its
+/// dropped flag.
+fn element_segment(state: &ActiveBlock<'_>, elem_index: u32) -> build::Result<(u32, u32, u32)> {
+    let accessible = &state.body_data.accessible;
+    let elem_index = usize::try_from(elem_index).expect("module must fit in memory");
+
+    let element = accessible
+        .element_index_lookup
+        .get(elem_index)
+        .expect("an OoB element reference should be caught by validation");
+    let length = accessible.element_size_lookup[elem_index];
+    let segment = accessible.element_segment_lookup[elem_index];
+
+    if segment >= MAX_DROPPABLE_ELEMENT_SEGMENTS {
+        return Err(BuildError::BoundsExceeded(
+            ExceededComponent::ElementSegments,
+        ));
+    }
+
+    // References are one word each
+    Ok((**element / 4, length, segment))
+}
+
+pub(super) fn eat_bulk_memory_operator(
+    state: &mut ActiveBlock<'_>,
+    operator: &BulkMemoryOperator,
+) -> build::Result<()> {
+    // The bulk memory functions check bounds against the size of the first memory
+    // TODO: Support other memories
+    let memories = match operator {
+        BulkMemoryOperator::MemoryCopy { dst_mem, src_mem } => vec![*dst_mem, *src_mem],
+        BulkMemoryOperator::MemoryFill { mem } | BulkMemoryOperator::MemoryInit { mem, .. } => {
+            vec![*mem]
+        }
+        _ => vec![],
+    };
+    if memories.into_iter().any(|mem| state.memory_base(mem) != 0) {
+        return Err(BuildError::UnsupportedInstructionError {
+            instruction_opcode: operator.opcode(),
+        });
+    }
+
+    match operator {
+        BulkMemoryOperator::MemoryCopy { dst_mem, src_mem } => {
+            let length = state.pop();
+            let src = state.pop();
+            let dst = state.pop();
+
+            let dst_mem = naga_expr!(state => U32(*dst_mem));
+            let src_mem = naga_expr!(state => U32(*src_mem));
+            let dst = naga_expr!(state => bitcast<u32>(dst));
+            let src = naga_expr!(state => bitcast<u32>(src));
+            let length = naga_expr!(state => bitcast<u32>(length));
+
+            let copy = state.std_objects().bulk_memory.copy;
+            state
+                .ctx
+                .call_void(copy, vec![dst_mem, src_mem, dst, src, length]);
+
+            Ok(())
+        }
+        BulkMemoryOperator::MemoryFill { mem } => {
+            let length = state.pop();
+            let value = state.pop();
+            let address = state.pop();
+
+            let mem = naga_expr!(state => U32(*mem));
+            let address = naga_expr!(state => bitcast<u32>(address));
+            let value = naga_expr!(state => bitcast<u32>(value));
+            let length = naga_expr!(state => bitcast<u32>(length));
+
+            let fill = state.std_objects().bulk_memory.fill;
+            state.ctx.call_void(fill, vec![mem, address, value, length]);
+
+            Ok(())
+        }
+        BulkMemoryOperator::MemoryInit { data_index, mem } => {
+            let (data, data_length, segment) = data_segment(state, *data_index)?;
+
+            let length = state.pop();
+            let src = state.pop();
+            let dst = state.pop();
+
+            let mem = naga_expr!(state => U32(*mem));
+            let dst = naga_expr!(state => bitcast<u32>(dst));
+            let src = naga_expr!(state => bitcast<u32>(src));
+            let length = naga_expr!(state => bitcast<u32>(length));
+
+            // A dropped segment acts as if it were empty
+            let data_dropped = state.std_objects().preamble.data_dropped;
+            let is_dropped = naga_expr!(state => ((Load(Global(data_dropped)[const segment / 32]) >> U32(segment % 32)) & U32(1)) != U32(0));
+            let data_length = naga_expr!(state => if (is_dropped) {U32(0)} else {U32(data_length)});
+            let data = naga_expr!(state => U32(data));
+
+            let init = state.std_objects().bulk_memory.init;
+            state
+                .ctx
+                .call_void(init, vec![mem, data, data_length, dst, src, length]);
+
+            Ok(())
+        }
+        BulkMemoryOperator::DataDrop { data_index } => {
+            let (_, _, segment) = data_segment(state, *data_index)?;
+
+            let data_dropped = state.std_objects().preamble.data_dropped;
+            let word_ptr = naga_expr!(state => Global(data_dropped)[const segment / 32]);
+            let word = naga_expr!(state => Load(word_ptr) | U32(1 << (segment % 32)));
+            state.ctx.store(word_ptr, word);
+
+            Ok(())
+        }
+        BulkMemoryOperator::TableInit { elem_index, table } => {
+            let (element_word, element_length, segment) = element_segment(state, *elem_index)?;
+            let body_data = state.body_data;
+            let preamble = &body_data.std_objects.preamble;
+            let (table_word, table_size, _) = body_data.table(*table);
+
+            let length = state.pop();
+            let src = state.pop();
+            let dst = state.pop();
+
+            let dst = naga_expr!(state => bitcast<u32>(dst));
+            let src = naga_expr!(state => bitcast<u32>(src));
+            let length = naga_expr!(state => bitcast<u32>(length));
+
+            // A dropped segment acts as if it were empty
+            let element_dropped = preamble.element_dropped;
+            let is_dropped = naga_expr!(state => ((Load(Global(element_dropped)[const segment / 32]) >> U32(segment % 32)) & U32(1)) != U32(0));
+            let element_length =
+                naga_expr!(state => if (is_dropped) {U32(0)} else {U32(element_length)});
+            let table_size = naga_expr!(state => U32(table_size));
+
+            // The whole range is checked before anything is written, so that a trapping operation has no effect
+            let is_out_of_bounds = naga_expr!(state => (length > element_length) | (src > (element_length - length)) | (length > table_size) | (dst > (table_size - length)));
+
+            let trap_values = &preamble.trap_values;
+            let trap_state = preamble.trap_state;
+            let elements = preamble.bindings.elements;
+            state
+                .ctx
+                .test(is_out_of_bounds)
+                .then(|mut ctx| {
+                    trap_values.emit_set_trap(&mut ctx, Trap::TableOutOfBounds, trap_state)
+                })
+                .otherwise(|mut ctx| {
+                    // If we have trapped, don't store
+                    let is_trapped = naga_expr!(&mut ctx => Load(Global(trap_state)) != U32(0));
+                    ctx.test(is_trapped).otherwise(|mut ctx| {
+                        naga_expr!(&mut ctx => for i in (U32(0))..(length) |ctx| {
+                            let reference = naga_expr!(&mut ctx => Load(Global(elements)[U32(element_word) + (src + i)]));
+                            let entry_index = naga_expr!(&mut ctx => dst + i);
+                            let entry_ptr = body_data.table_entry_ptr(&mut ctx, table_word, entry_index);
+                            ctx.store(entry_ptr, reference);
+                        });
+                    });
+                });
+
+            Ok(())
+        }
+        BulkMemoryOperator::ElemDrop { elem_index } => {
+            let (_, _, segment) = element_segment(state, *elem_index)?;
+
+            let element_dropped = state.std_objects().preamble.element_dropped;
+            let word_ptr = naga_expr!(state => Global(element_dropped)[const segment / 32]);
+            let word = naga_expr!(state => Load(word_ptr) | U32(1 << (segment % 32)));
+            state.ctx.store(word_ptr, word);
+
+            Ok(())
+        }
+        BulkMemoryOperator::TableCopy { .. } => Err(BuildError::UnsupportedInstructionError {
+            instruction_opcode: operator.opcode(),
+        }),
+    }
+}
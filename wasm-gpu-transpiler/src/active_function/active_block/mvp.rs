@@ -1,7 +1,6 @@
 use super::{binary, mem_load, mem_store, unary, ActiveBlock};
 use crate::{build, typed::Val};
 use wasm_opcodes::proposals::MVPOperator;
-use wasmtime_environ::Trap;
 
 pub(super) fn eat_mvp_operator(
     state: &mut ActiveBlock<'_>,
@@ -20,7 +19,9 @@ pub(super) fn eat_mvp_operator(
         MVPOperator::F64Const { value } => {
             state.push_const_val(Val::F64(f64::from_bits(value.bits())))
         }
-        MVPOperator::Unreachable => state.append_trap(Trap::UnreachableCodeReached),
+        MVPOperator::Unreachable => {
+            unreachable!("unreachable ends a basic block, so is handled by the enclosing block")
+        }
         MVPOperator::Drop => {
             state.pop(); // And do nothing
             Ok(())
@@ -68,8 +69,8 @@ pub(super) fn eat_mvp_operator(
         MVPOperator::I64Store8 { memarg } => mem_store!(state, memarg, i64::store_8),
         MVPOperator::I64Store16 { memarg } => mem_store!(state, memarg, i64::store_16),
         MVPOperator::I64Store32 { memarg } => mem_store!(state, memarg, i64::store_32),
-        MVPOperator::MemorySize { mem, mem_byte } => unimplemented!(),
-        MVPOperator::MemoryGrow { mem, mem_byte } => unimplemented!(),
+        MVPOperator::MemorySize { mem, .. } => state.do_memory_size(*mem),
+        MVPOperator::MemoryGrow { mem, .. } => state.do_memory_grow(*mem),
         MVPOperator::I32Eqz => unary!(state, i32::eqz),
         MVPOperator::I32Eq => binary!(state, i32::eq),
         MVPOperator::I32Ne => binary!(state, i32::ne),
@@ -169,29 +170,29 @@ pub(super) fn eat_mvp_operator(
         MVPOperator::F64Max => binary!(state, f64::max),
         MVPOperator::F64Copysign => binary!(state, f64::copy_sign),
         MVPOperator::I32WrapI64 => unimplemented!(),
-        MVPOperator::I32TruncF32S => unimplemented!(),
-        MVPOperator::I32TruncF32U => unimplemented!(),
-        MVPOperator::I32TruncF64S => unimplemented!(),
-        MVPOperator::I32TruncF64U => unimplemented!(),
+        MVPOperator::I32TruncF32S => unary!(state, f32::trunc_i32_s),
+        MVPOperator::I32TruncF32U => unary!(state, f32::trunc_i32_u),
+        MVPOperator::I32TruncF64S => unary!(state, f64::trunc_i32_s),
+        MVPOperator::I32TruncF64U => unary!(state, f64::trunc_i32_u),
         MVPOperator::I64ExtendI32S => unimplemented!(),
         MVPOperator::I64ExtendI32U => unimplemented!(),
-        MVPOperator::I64TruncF32S => unimplemented!(),
-        MVPOperator::I64TruncF32U => unimplemented!(),
-        MVPOperator::I64TruncF64S => unimplemented!(),
-        MVPOperator::I64TruncF64U => unimplemented!(),
+        MVPOperator::I64TruncF32S => unary!(state, f32::trunc_i64_s),
+        MVPOperator::I64TruncF32U => unary!(state, f32::trunc_i64_u),
+        MVPOperator::I64TruncF64S => unary!(state, f64::trunc_i64_s),
+        MVPOperator::I64TruncF64U => unary!(state, f64::trunc_i64_u),
         MVPOperator::F32ConvertI32S => unary!(state, f32::convert_i32_s),
         MVPOperator::F32ConvertI32U => unary!(state, f32::convert_i32_u),
         MVPOperator::F32ConvertI64S => unimplemented!(),
         MVPOperator::F32ConvertI64U => unimplemented!(),
-        MVPOperator::F32DemoteF64 => unimplemented!(),
-        MVPOperator::F64ConvertI32S => unimplemented!(),
-        MVPOperator::F64ConvertI32U => unimplemented!(),
-        MVPOperator::F64ConvertI64S => unimplemented!(),
-        MVPOperator::F64ConvertI64U => unimplemented!(),
-        MVPOperator::F64PromoteF32 => unimplemented!(),
-        MVPOperator::I32ReinterpretF32 => unimplemented!(),
-        MVPOperator::I64ReinterpretF64 => unimplemented!(),
-        MVPOperator::F32ReinterpretI32 => unimplemented!(),
-        MVPOperator::F64ReinterpretI64 => unimplemented!(),
+        MVPOperator::F32DemoteF64 => unary!(state, f64::demote_f32),
+        MVPOperator::F64ConvertI32S => unary!(state, f64::convert_i32_s),
+        MVPOperator::F64ConvertI32U => unary!(state, f64::convert_i32_u),
+        MVPOperator::F64ConvertI64S => unary!(state, f64::convert_i64_s),
+        MVPOperator::F64ConvertI64U => unary!(state, f64::convert_i64_u),
+        MVPOperator::F64PromoteF32 => unary!(state, f64::promote_f32),
+        MVPOperator::I32ReinterpretF32 => unary!(state, f32::reinterpret_to_i32),
+        MVPOperator::I64ReinterpretF64 => unary!(state, f64::reinterpret_to_i64),
+        MVPOperator::F32ReinterpretI32 => unary!(state, f32::reinterpret_from_i32),
+        MVPOperator::F64ReinterpretI64 => unary!(state, f64::reinterpret_from_i64),
     }
 }
@@ -0,0 +1,91 @@
+use naga_ext::naga_expr;
+use wasm_opcodes::proposals::ReferenceTypesOperator;
+use wasmparser::{HeapType, RefType, ValType};
+
+use crate::{build, BuildError};
+
+use super::ActiveBlock;
+
+pub(super) fn eat_reference_types_operator(
+    state: &mut ActiveBlock<'_>,
+    operator: &ReferenceTypesOperator,
+) -> build::Result<()> {
+    match operator {
+        ReferenceTypesOperator::TableGet { table } => state.do_table_get(*table),
+        ReferenceTypesOperator::TableSet { table } => state.do_table_set(*table),
+        ReferenceTypesOperator::RefNull { hty } => {
+            let ty = match hty {
+                HeapType::Func => RefType::FUNCREF,
+                HeapType::Extern => RefType::EXTERNREF,
+                _ => {
+                    return Err(BuildError::UnsupportedInstructionError {
+                        instruction_opcode: operator.opcode(),
+                    })
+                }
+            };
+
+            // Nulls are the default values of the reference polyfills
+            let null = state.std_objects().get_default_value(ValType::Ref(ty));
+            let null = naga_expr!(state => Constant(null));
+            state.stack.push(null);
+
+            Ok(())
+        }
+        ReferenceTypesOperator::RefIsNull => {
+            // Both reference polyfills use u32::MAX as their null sentinel
+            let value = state.pop();
+            let wasm_bool = &state.std_objects().preamble.wasm_bool;
+            let (t, f) = (wasm_bool.const_true, wasm_bool.const_false);
+            let is_null = naga_expr!(state => value == U32(u32::MAX));
+            let is_null = naga_expr!(state => if (is_null) {Constant(t)} else {Constant(f)});
+            state.stack.push(is_null);
+
+            Ok(())
+        }
+        ReferenceTypesOperator::RefFunc { function_index } => {
+            let function_index =
+                usize::try_from(*function_index).expect("module must fit in memory");
+            let ptr = state
+                .body_data
+                .accessible
+                .func_index_lookup
+                .get(function_index)
+                .expect("an OoB function reference should be caught by validation");
+            let func_ref = ptr.as_u32().expect("function pointers are never null");
+            let func_ref = naga_expr!(state => U32(func_ref));
+            state.stack.push(func_ref);
+
+            Ok(())
+        }
+        ReferenceTypesOperator::TypedSelect { ty } => {
+            let condition = state.pop();
+            let rejected = state.pop();
+            let accepted = state.pop();
+
+            let wasm_false = state.std_objects().preamble.wasm_bool.const_false;
+            let condition = naga_expr!(state => condition != Constant(wasm_false));
+
+            // Not every wasm type is a naga scalar or vector, which `Select` expressions are restricted to, so branch
+            let naga_ty = state.std_objects().get_val_type(*ty);
+            let selected = state.ctx.new_local("select_result", naga_ty, None);
+            let selected = state.ctx.local_expr(selected);
+            state
+                .ctx
+                .test(condition)
+                .then(|mut ctx| ctx.store(selected, accepted))
+                .otherwise(|mut ctx| ctx.store(selected, rejected));
+
+            let selected = naga_expr!(state => Load(selected));
+            state.stack.push(selected);
+
+            Ok(())
+        }
+        ReferenceTypesOperator::TableFill { .. }
+        | ReferenceTypesOperator::TableGrow { .. }
+        | ReferenceTypesOperator::TableSize { .. } => {
+            Err(BuildError::UnsupportedInstructionError {
+                instruction_opcode: operator.opcode(),
+            })
+        }
+    }
+}
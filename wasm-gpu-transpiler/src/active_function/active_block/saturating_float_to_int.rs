@@ -0,0 +1,26 @@
+use wasm_opcodes::proposals::SaturatingFloatToIntOperator;
+
+use crate::{build, BuildError};
+
+use super::{unary, ActiveBlock};
+
+pub(super) fn eat_saturating_float_to_int_operator(
+    state: &mut ActiveBlock<'_>,
+    operator: &SaturatingFloatToIntOperator,
+) -> build::Result<()> {
+    match operator {
+        SaturatingFloatToIntOperator::I32TruncSatF32S => unary!(state, f32::trunc_sat_i32_s),
+        SaturatingFloatToIntOperator::I32TruncSatF32U => unary!(state, f32::trunc_sat_i32_u),
+        SaturatingFloatToIntOperator::I64TruncSatF32S => unary!(state, f32::trunc_sat_i64_s),
+        SaturatingFloatToIntOperator::I64TruncSatF32U => unary!(state, f32::trunc_sat_i64_u),
+        // f64s are polyfilled, and don't yet support conversions
+        SaturatingFloatToIntOperator::I32TruncSatF64S
+        | SaturatingFloatToIntOperator::I32TruncSatF64U
+        | SaturatingFloatToIntOperator::I64TruncSatF64S
+        | SaturatingFloatToIntOperator::I64TruncSatF64U => {
+            Err(BuildError::UnsupportedInstructionError {
+                instruction_opcode: operator.opcode(),
+            })
+        }
+    }
+}
@@ -1,13 +1,109 @@
+use naga_ext::prelude::*;
 use wasm_opcodes::proposals::SIMDOperator;
 
-use crate::build;
+use crate::typed::{Val, V128};
+use crate::{build, BoundsCheckMode, BuildError, ExceededComponent};
+
+use super::{binary, unary, ActiveBlock};
+
+/// Pops an address and calculates the address of each of the four words making up a v128 starting at that
+/// address, so that vectors can be read and written through the existing 32-bit memory functions. Each word is
+/// translated separately, since a vector may straddle a boundary in disjoint memory. The whole vector is bounds
+/// checked at once, giving whether it is in bounds if checks are enabled.
+fn v128_word_addresses(
+    state: &mut ActiveBlock<'_>,
+    memarg: &wasmparser::MemArg,
+) -> build::Result<(
+    [naga::Handle<naga::Expression>; 4],
+    Option<naga::Handle<naga::Expression>>,
+)> {
+    let wasmparser::MemArg {
+        offset,
+        memory,
+        // Alignment has no semantic influence, it is a performance hint
+        align: _,
+        max_align: _,
+    } = memarg;
+
+    let offset = u32::try_from(*offset)
+        .map_err(|_| BuildError::BoundsExceeded(ExceededComponent::MemArgOffset))?;
+
+    let address = state.pop();
+    let address = naga_expr!(state => (bitcast<u32>(address)) + U32(offset));
+    let (address, in_bounds) = state.emit_memory_bounds_check(*memory, address, offset, 16);
+
+    let addresses = [0, 4, 8, 12].map(|word_offset| {
+        let word_address = naga_expr!(state => address + U32(word_offset));
+        if state.body_data.tuneables.disjoint_memory {
+            state.disjoint_memory_address(word_address)
+        } else {
+            word_address
+        }
+    });
+
+    Ok((addresses, in_bounds))
+}
+
+/// Whether `eat_simd_operator` can lower the operator. Must be kept in sync with the operators implemented below.
+pub(crate) fn is_lowered(simd_op: &SIMDOperator) -> bool {
+    matches!(
+        simd_op,
+        SIMDOperator::V128Load { .. }
+            | SIMDOperator::V128Store { .. }
+            | SIMDOperator::V128Const { .. }
+            | SIMDOperator::I32x4ExtractLane { .. }
+            | SIMDOperator::I32x4ReplaceLane { .. }
+            | SIMDOperator::I32x4Splat
+            | SIMDOperator::V128Not
+            | SIMDOperator::V128And
+            | SIMDOperator::V128AndNot
+            | SIMDOperator::V128Or
+            | SIMDOperator::V128Xor
+            | SIMDOperator::I8x16Add
+            | SIMDOperator::I8x16Sub
+            | SIMDOperator::I16x8Add
+            | SIMDOperator::I16x8Sub
+            | SIMDOperator::I16x8Mul
+            | SIMDOperator::I32x4Add
+            | SIMDOperator::I32x4Sub
+            | SIMDOperator::I32x4Mul
+            | SIMDOperator::I64x2Add
+            | SIMDOperator::I64x2Sub
+            | SIMDOperator::F32x4Add
+            | SIMDOperator::F32x4Sub
+            | SIMDOperator::F32x4Mul
+            | SIMDOperator::F32x4Div
+    )
+}
 
 pub(crate) fn eat_simd_operator(
-    state: &mut super::ActiveBlock,
+    state: &mut ActiveBlock,
     simd_op: &SIMDOperator,
 ) -> build::Result<()> {
     match simd_op {
-        SIMDOperator::V128Load { memarg } => unimplemented!(),
+        SIMDOperator::V128Load { memarg } => {
+            let (addresses, in_bounds) = v128_word_addresses(state, memarg)?;
+
+            let load = state.std_objects().i32.load;
+            let words = addresses.map(|address| {
+                let word = state.ctx.call_get_return(load, vec![address]);
+                naga_expr!(state => bitcast<u32>(word))
+            });
+
+            let [x, y, z, w] = words;
+            let v128_ty = state.std_objects().v128.ty;
+            let mut value = naga_expr!(state => v128_ty(x, y, z, w));
+
+            if let Some(in_bounds) = in_bounds {
+                if state.body_data.tuneables.bounds_checks == BoundsCheckMode::ReadZeroSkipWrite {
+                    let default = state.std_objects().v128.default;
+                    value = naga_expr!(state => if (in_bounds) {value} else {Constant(default)});
+                }
+            }
+
+            state.stack.push(value);
+            Ok(())
+        }
         SIMDOperator::V128Load8x8S { memarg } => unimplemented!(),
         SIMDOperator::V128Load8x8U { memarg } => unimplemented!(),
         SIMDOperator::V128Load16x4S { memarg } => unimplemented!(),
@@ -20,7 +116,34 @@ pub(crate) fn eat_simd_operator(
         SIMDOperator::V128Load64Splat { memarg } => unimplemented!(),
         SIMDOperator::V128Load32Zero { memarg } => unimplemented!(),
         SIMDOperator::V128Load64Zero { memarg } => unimplemented!(),
-        SIMDOperator::V128Store { memarg } => unimplemented!(),
+        SIMDOperator::V128Store { memarg } => {
+            let value = state.pop();
+            let (addresses, in_bounds) = v128_word_addresses(state, memarg)?;
+
+            let store = state.std_objects().i32.store;
+            let words = [0u32, 1, 2, 3].map(|i| naga_expr!(state => bitcast<i32>(value[const i])));
+            let emit_stores = |ctx: &mut BlockContext<'_>| {
+                // Write the highest word first, so that an out of bounds store traps before any other words are
+                // written, and the remaining stores are skipped
+                for (word, address) in words.into_iter().zip(addresses).rev() {
+                    ctx.call_void(store, vec![address, word]);
+                }
+            };
+
+            match in_bounds {
+                Some(in_bounds)
+                    if state.body_data.tuneables.bounds_checks
+                        == BoundsCheckMode::ReadZeroSkipWrite =>
+                {
+                    state
+                        .ctx
+                        .test(in_bounds)
+                        .then(|mut ctx| emit_stores(&mut ctx));
+                }
+                _ => emit_stores(&mut state.ctx),
+            }
+            Ok(())
+        }
         SIMDOperator::V128Load8Lane { memarg, lane: u8 } => unimplemented!(),
         SIMDOperator::V128Load16Lane { memarg, lane: u8 } => unimplemented!(),
         SIMDOperator::V128Load32Lane { memarg, lane: u8 } => unimplemented!(),
@@ -29,7 +152,7 @@ pub(crate) fn eat_simd_operator(
         SIMDOperator::V128Store16Lane { memarg, lane: u8 } => unimplemented!(),
         SIMDOperator::V128Store32Lane { memarg, lane: u8 } => unimplemented!(),
         SIMDOperator::V128Store64Lane { memarg, lane: u8 } => unimplemented!(),
-        SIMDOperator::V128Const { value } => unimplemented!(),
+        SIMDOperator::V128Const { value } => state.push_const_val(Val::V128(V128::from(*value))),
         SIMDOperator::I8x16Shuffle { lanes } => unimplemented!(),
         SIMDOperator::I8x16ExtractLaneS { lane: u8 } => unimplemented!(),
         SIMDOperator::I8x16ExtractLaneU { lane: u8 } => unimplemented!(),
@@ -37,8 +160,31 @@ pub(crate) fn eat_simd_operator(
         SIMDOperator::I16x8ExtractLaneS { lane: u8 } => unimplemented!(),
         SIMDOperator::I16x8ExtractLaneU { lane: u8 } => unimplemented!(),
         SIMDOperator::I16x8ReplaceLane { lane: u8 } => unimplemented!(),
-        SIMDOperator::I32x4ExtractLane { lane: u8 } => unimplemented!(),
-        SIMDOperator::I32x4ReplaceLane { lane: u8 } => unimplemented!(),
+        SIMDOperator::I32x4ExtractLane { lane } => {
+            let value = state.pop();
+            let lane = naga_expr!(state => bitcast<i32>(value[const u32::from(*lane)]));
+            state.stack.push(lane);
+            Ok(())
+        }
+        SIMDOperator::I32x4ReplaceLane { lane } => {
+            let replacement = state.pop();
+            let value = state.pop();
+
+            let replacement = naga_expr!(state => bitcast<u32>(replacement));
+            let words = [0u32, 1, 2, 3].map(|i| {
+                if i == u32::from(*lane) {
+                    replacement
+                } else {
+                    naga_expr!(state => value[const i])
+                }
+            });
+
+            let [x, y, z, w] = words;
+            let v128_ty = state.std_objects().v128.ty;
+            let value = naga_expr!(state => v128_ty(x, y, z, w));
+            state.stack.push(value);
+            Ok(())
+        }
         SIMDOperator::I64x2ExtractLane { lane: u8 } => unimplemented!(),
         SIMDOperator::I64x2ReplaceLane { lane: u8 } => unimplemented!(),
         SIMDOperator::F32x4ExtractLane { lane: u8 } => unimplemented!(),
@@ -48,7 +194,16 @@ pub(crate) fn eat_simd_operator(
         SIMDOperator::I8x16Swizzle => unimplemented!(),
         SIMDOperator::I8x16Splat => unimplemented!(),
         SIMDOperator::I16x8Splat => unimplemented!(),
-        SIMDOperator::I32x4Splat => unimplemented!(),
+        SIMDOperator::I32x4Splat => {
+            let value = state.pop();
+            let value = naga_expr!(state => bitcast<u32>(value));
+            let value = state.ctx.append_expr(naga::Expression::Splat {
+                size: naga::VectorSize::Quad,
+                value,
+            });
+            state.stack.push(value);
+            Ok(())
+        }
         SIMDOperator::I64x2Splat => unimplemented!(),
         SIMDOperator::F32x4Splat => unimplemented!(),
         SIMDOperator::F64x2Splat => unimplemented!(),
@@ -100,11 +255,11 @@ pub(crate) fn eat_simd_operator(
         SIMDOperator::F64x2Gt => unimplemented!(),
         SIMDOperator::F64x2Le => unimplemented!(),
         SIMDOperator::F64x2Ge => unimplemented!(),
-        SIMDOperator::V128Not => unimplemented!(),
-        SIMDOperator::V128And => unimplemented!(),
-        SIMDOperator::V128AndNot => unimplemented!(),
-        SIMDOperator::V128Or => unimplemented!(),
-        SIMDOperator::V128Xor => unimplemented!(),
+        SIMDOperator::V128Not => unary!(state, v128::not),
+        SIMDOperator::V128And => binary!(state, v128::and),
+        SIMDOperator::V128AndNot => binary!(state, v128::and_not),
+        SIMDOperator::V128Or => binary!(state, v128::or),
+        SIMDOperator::V128Xor => binary!(state, v128::xor),
         SIMDOperator::V128Bitselect => unimplemented!(),
         SIMDOperator::V128AnyTrue => unimplemented!(),
         SIMDOperator::I8x16Abs => unimplemented!(),
@@ -117,10 +272,10 @@ pub(crate) fn eat_simd_operator(
         SIMDOperator::I8x16Shl => unimplemented!(),
         SIMDOperator::I8x16ShrS => unimplemented!(),
         SIMDOperator::I8x16ShrU => unimplemented!(),
-        SIMDOperator::I8x16Add => unimplemented!(),
+        SIMDOperator::I8x16Add => binary!(state, v128::i8x16_add),
         SIMDOperator::I8x16AddSatS => unimplemented!(),
         SIMDOperator::I8x16AddSatU => unimplemented!(),
-        SIMDOperator::I8x16Sub => unimplemented!(),
+        SIMDOperator::I8x16Sub => binary!(state, v128::i8x16_sub),
         SIMDOperator::I8x16SubSatS => unimplemented!(),
         SIMDOperator::I8x16SubSatU => unimplemented!(),
         SIMDOperator::I8x16MinS => unimplemented!(),
@@ -144,13 +299,13 @@ pub(crate) fn eat_simd_operator(
         SIMDOperator::I16x8Shl => unimplemented!(),
         SIMDOperator::I16x8ShrS => unimplemented!(),
         SIMDOperator::I16x8ShrU => unimplemented!(),
-        SIMDOperator::I16x8Add => unimplemented!(),
+        SIMDOperator::I16x8Add => binary!(state, v128::i16x8_add),
         SIMDOperator::I16x8AddSatS => unimplemented!(),
         SIMDOperator::I16x8AddSatU => unimplemented!(),
-        SIMDOperator::I16x8Sub => unimplemented!(),
+        SIMDOperator::I16x8Sub => binary!(state, v128::i16x8_sub),
         SIMDOperator::I16x8SubSatS => unimplemented!(),
         SIMDOperator::I16x8SubSatU => unimplemented!(),
-        SIMDOperator::I16x8Mul => unimplemented!(),
+        SIMDOperator::I16x8Mul => binary!(state, v128::i16x8_mul),
         SIMDOperator::I16x8MinS => unimplemented!(),
         SIMDOperator::I16x8MinU => unimplemented!(),
         SIMDOperator::I16x8MaxS => unimplemented!(),
@@ -173,9 +328,9 @@ pub(crate) fn eat_simd_operator(
         SIMDOperator::I32x4Shl => unimplemented!(),
         SIMDOperator::I32x4ShrS => unimplemented!(),
         SIMDOperator::I32x4ShrU => unimplemented!(),
-        SIMDOperator::I32x4Add => unimplemented!(),
-        SIMDOperator::I32x4Sub => unimplemented!(),
-        SIMDOperator::I32x4Mul => unimplemented!(),
+        SIMDOperator::I32x4Add => binary!(state, v128::i32x4_add),
+        SIMDOperator::I32x4Sub => binary!(state, v128::i32x4_sub),
+        SIMDOperator::I32x4Mul => binary!(state, v128::i32x4_mul),
         SIMDOperator::I32x4MinS => unimplemented!(),
         SIMDOperator::I32x4MinU => unimplemented!(),
         SIMDOperator::I32x4MaxS => unimplemented!(),
@@ -196,8 +351,8 @@ pub(crate) fn eat_simd_operator(
         SIMDOperator::I64x2Shl => unimplemented!(),
         SIMDOperator::I64x2ShrS => unimplemented!(),
         SIMDOperator::I64x2ShrU => unimplemented!(),
-        SIMDOperator::I64x2Add => unimplemented!(),
-        SIMDOperator::I64x2Sub => unimplemented!(),
+        SIMDOperator::I64x2Add => binary!(state, v128::i64x2_add),
+        SIMDOperator::I64x2Sub => binary!(state, v128::i64x2_sub),
         SIMDOperator::I64x2Mul => unimplemented!(),
         SIMDOperator::I64x2ExtMulLowI32x4S => unimplemented!(),
         SIMDOperator::I64x2ExtMulHighI32x4S => unimplemented!(),
@@ -210,10 +365,10 @@ pub(crate) fn eat_simd_operator(
         SIMDOperator::F32x4Abs => unimplemented!(),
         SIMDOperator::F32x4Neg => unimplemented!(),
         SIMDOperator::F32x4Sqrt => unimplemented!(),
-        SIMDOperator::F32x4Add => unimplemented!(),
-        SIMDOperator::F32x4Sub => unimplemented!(),
-        SIMDOperator::F32x4Mul => unimplemented!(),
-        SIMDOperator::F32x4Div => unimplemented!(),
+        SIMDOperator::F32x4Add => binary!(state, v128::f32x4_add),
+        SIMDOperator::F32x4Sub => binary!(state, v128::f32x4_sub),
+        SIMDOperator::F32x4Mul => binary!(state, v128::f32x4_mul),
+        SIMDOperator::F32x4Div => binary!(state, v128::f32x4_div),
         SIMDOperator::F32x4Min => unimplemented!(),
         SIMDOperator::F32x4Max => unimplemented!(),
         SIMDOperator::F32x4PMin => unimplemented!(),
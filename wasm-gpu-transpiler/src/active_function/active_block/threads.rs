@@ -1,124 +1,81 @@
+use naga_ext::naga_expr;
 use wasm_opcodes::proposals::ThreadsOperator;
 
-use crate::{build, BuildError};
+use crate::{build, BuildError, ExceededComponent};
 
 use super::ActiveBlock;
 
-macro_rules! impl_op {
-    (match $operator:ident {
-        $state:ident {
-        $(
-            $state_function:ident {
-                $($ty:ident :: $fn:ident),* $(,)*
-            }
-        ),* $(,)?
-        }
-        $($others:tt)*
-    }) => {
-        paste::paste!{
-            match $operator {
-                $($(
-                    ThreadsOperator:: [< $ty:camel $fn:camel >] { memarg } => $state.$state_function(memarg, $state.std_objects().$ty.$fn),
-                )*)*
-                $($others)*
-            }
-
-        }
-    };
-}
-
-pub(super) fn eat_threads_operator(
+/// Pops an address and some operands, then calls an atomic function and pushes its result.
+/// Unlike other memory functions, atomic functions take the shared address, since they check its alignment before
+/// translating it into disjoint memory.
+fn pop_push_call_atomic_func(
     state: &mut ActiveBlock<'_>,
-    operator: &ThreadsOperator,
+    memarg: &wasmparser::MemArg,
+    operand_count: usize,
+    atomic_function: naga::Handle<naga::Function>,
 ) -> build::Result<()> {
-    let err = Err(BuildError::UnsupportedInstructionError {
-        instruction_opcode: operator.opcode(),
-    });
-    return err;
-    /*impl_op! {
-        match operator {
-            state {
-                pop_one_push_call_mem_func {
-                    i32::atomic_load,
-                    i32::atomic_load_8_u,
-                    i32::atomic_load_16_u,
-                    i64::atomic_load,
-                    i64::atomic_load_8_u,
-                    i64::atomic_load_16_u,
-                    i64::atomic_load_32_u,
-                },
+    let wasmparser::MemArg {
+        offset,
+        memory,
+        // Alignment is validated to be natural for atomics, and misaligned addresses trap at runtime
+        align: _,
+        max_align: _,
+    } = memarg;
 
-                pop_two_call_mem_func {
-                    i32::atomic_store,
-                    i32::atomic_store_8,
-                    i32::atomic_store_16,
-                    i64::atomic_store,
-                    i64::atomic_store_8,
-                    i64::atomic_store_16,
-                    i64::atomic_store_32,
+    let offset = u32::try_from(*offset)
+        .map_err(|_| BuildError::BoundsExceeded(ExceededComponent::MemArgOffset))?;
 
-                    i32::atomic_rmw_add,
-                    i32::atomic_rmw_8_add_u,
-                    i32::atomic_rmw_16_add_u,
-                    i64::atomic_rmw_add,
-                    i64::atomic_rmw_8_add_u,
-                    i64::atomic_rmw_16_add_u,
-                    i64::atomic_rmw_32_add_u,
+    let mut operands: Vec<_> = (0..operand_count).map(|_| state.pop()).collect();
+    operands.reverse();
 
-                    i32::atomic_rmw_sub,
-                    i32::atomic_rmw_8_sub_u,
-                    i32::atomic_rmw_16_sub_u,
-                    i64::atomic_rmw_sub,
-                    i64::atomic_rmw_8_sub_u,
-                    i64::atomic_rmw_16_sub_u,
-                    i64::atomic_rmw_32_sub_u,
+    let memory = naga_expr!(state => U32(*memory));
 
-                    i32::atomic_rmw_and,
-                    i32::atomic_rmw_8_and_u,
-                    i32::atomic_rmw_16_and_u,
-                    i64::atomic_rmw_and,
-                    i64::atomic_rmw_8_and_u,
-                    i64::atomic_rmw_16_and_u,
-                    i64::atomic_rmw_32_and_u,
+    let address = state.pop();
+    let address = naga_expr!(state => (bitcast<u32>(address)) + U32(offset));
 
-                    i32::atomic_rmw_or,
-                    i32::atomic_rmw_8_or_u,
-                    i32::atomic_rmw_16_or_u,
-                    i64::atomic_rmw_or,
-                    i64::atomic_rmw_8_or_u,
-                    i64::atomic_rmw_16_or_u,
-                    i64::atomic_rmw_32_or_u,
-
-                    i32::atomic_rmw_xor,
-                    i32::atomic_rmw_8_xor_u,
-                    i32::atomic_rmw_16_xor_u,
-                    i64::atomic_rmw_xor,
-                    i64::atomic_rmw_8_xor_u,
-                    i64::atomic_rmw_16_xor_u,
-                    i64::atomic_rmw_32_xor_u,
+    let mut arguments = vec![memory, address];
+    arguments.extend(operands);
+    state.push_call(atomic_function, arguments)
+}
 
-                    i32::atomic_rmw_xchg,
-                    i32::atomic_rmw_8_xchg_u,
-                    i32::atomic_rmw_16_xchg_u,
-                    i64::atomic_rmw_xchg,
-                    i64::atomic_rmw_8_xchg_u,
-                    i64::atomic_rmw_16_xchg_u,
-                    i64::atomic_rmw_32_xchg_u,
+pub(super) fn eat_threads_operator(
+    state: &mut ActiveBlock<'_>,
+    operator: &ThreadsOperator,
+) -> build::Result<()> {
+    let permit_nonblocking_atomics = state.body_data.tuneables.permit_nonblocking_atomics;
+    let atomics = &state.std_objects().atomics;
+    let (memarg, operand_count, atomic_function) = match operator {
+        ThreadsOperator::I32AtomicRmwAdd { memarg } => (memarg, 1, atomics.i32_rmw_add),
+        ThreadsOperator::I32AtomicRmwSub { memarg } => (memarg, 1, atomics.i32_rmw_sub),
+        ThreadsOperator::I32AtomicRmwAnd { memarg } => (memarg, 1, atomics.i32_rmw_and),
+        ThreadsOperator::I32AtomicRmwOr { memarg } => (memarg, 1, atomics.i32_rmw_or),
+        ThreadsOperator::I32AtomicRmwXor { memarg } => (memarg, 1, atomics.i32_rmw_xor),
+        ThreadsOperator::I32AtomicRmwXchg { memarg } => (memarg, 1, atomics.i32_rmw_xchg),
+        ThreadsOperator::I32AtomicRmwCmpxchg { memarg } => (memarg, 2, atomics.i32_rmw_cmpxchg),
+        ThreadsOperator::MemoryAtomicWait32 { memarg } if permit_nonblocking_atomics => {
+            (memarg, 2, atomics.wait32)
+        }
+        ThreadsOperator::MemoryAtomicWait64 { memarg } if permit_nonblocking_atomics => {
+            (memarg, 2, atomics.wait64)
+        }
+        ThreadsOperator::MemoryAtomicNotify { memarg } if permit_nonblocking_atomics => {
+            (memarg, 1, atomics.notify)
+        }
+        // TODO: Sub-word and 64-bit atomics, which can't be directly represented by `atomic<u32>`
+        _ => {
+            return Err(BuildError::UnsupportedInstructionError {
+                instruction_opcode: operator.opcode(),
+            })
+        }
+    };
 
-                    i32::atomic_rmw_cmpxchg,
-                    i32::atomic_rmw_8_cmpxchg_u,
-                    i32::atomic_rmw_16_cmpxchg_u,
-                    i64::atomic_rmw_cmpxchg,
-                    i64::atomic_rmw_8_cmpxchg_u,
-                    i64::atomic_rmw_16_cmpxchg_u,
-                    i64::atomic_rmw_32_cmpxchg_u,
-                }
-            }
+    // Atomic functions check bounds against the size of the first memory themselves
+    // TODO: Atomics on other memories
+    if state.memory_base(memarg.memory) != 0 {
+        return Err(BuildError::UnsupportedInstructionError {
+            instruction_opcode: operator.opcode(),
+        });
+    }
 
-            ThreadsOperator::MemoryAtomicNotify { memarg } => err,
-            ThreadsOperator::MemoryAtomicWait32 { memarg } => err,
-            ThreadsOperator::MemoryAtomicWait64 { memarg } => err,
-            ThreadsOperator::AtomicFence => err,
-        }
-    }*/
+    pop_push_call_atomic_func(state, memarg, operand_count, atomic_function)
 }
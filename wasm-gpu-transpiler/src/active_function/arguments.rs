@@ -1,12 +1,9 @@
-use naga_ext::{naga_expr, ExpressionsExt};
+use naga_ext::prelude::*;
 use wasmparser::ValType;
 
-use crate::{
-    std_objects::StdObjects, typed::ValTypeByteCount, IO_ARGUMENT_ALIGNMENT_WORDS,
-    IO_INVOCATION_ALIGNMENT_WORDS,
-};
+use crate::{std_objects::StdObjects, typed::ValTypeByteCount, Tuneables};
 
-use super::ActiveFunction;
+use super::{ActiveFunction, IoLocation};
 
 /// An argument in a function
 #[derive(Debug, Copy, Clone)]
@@ -37,26 +34,6 @@ impl FnArg {
         }
     }
 
-    pub(crate) fn append_bound_to(
-        function: &mut naga::Function,
-        type_handle: naga::Handle<naga::Type>,
-        binding: naga::Binding,
-    ) -> Self {
-        let i_param = function.arguments.len();
-        function.arguments.push(naga::FunctionArgument {
-            name: Some(format!("bound_arg_{}", i_param)),
-            ty: type_handle,
-            binding: Some(binding),
-        });
-
-        let expression_handle = function.expressions.append_fn_argument(i_param as u32);
-
-        Self {
-            type_handle,
-            expression_handle,
-        }
-    }
-
     pub(crate) fn expression(&self) -> naga::Handle<naga::Expression> {
         self.expression_handle.clone()
     }
@@ -119,33 +96,35 @@ impl WasmFnArgs {
 
     /// Calculates the word alignment in memory (i.e. buffer bindings) that these arguments must occupy.
     /// Useful when loading the arguments of an entry function from an input buffer.
-    pub(crate) fn word_alignment(&self) -> u32 {
+    pub(crate) fn word_alignment(&self, tuneables: &Tuneables) -> u32 {
         let mut word_offset = 0;
         for arg in &self.args {
             word_offset += u32::from(arg.ty.byte_count())
-                .next_multiple_of(IO_ARGUMENT_ALIGNMENT_WORDS * 4)
+                .next_multiple_of(tuneables.io_argument_alignment_words * 4)
                 / 4;
         }
-        let word_alignment = word_offset.next_multiple_of(IO_INVOCATION_ALIGNMENT_WORDS);
+        let word_alignment = tuneables.io_layout.invocation_words(word_offset, tuneables);
         return word_alignment;
     }
 
     pub(crate) fn append_read_at<'f>(
         &self,
         function: &mut impl ActiveFunction<'f>,
-        location: naga::Handle<naga::Expression>,
+        location: IoLocation,
+        tuneables: &Tuneables,
     ) -> Vec<naga::Handle<naga::Expression>> {
         let mut arg_results = Vec::new();
 
         let mut offset = 0u32;
         for arg in &self.args {
-            let location = naga_expr!(function.ctx() => location + U32(offset));
+            let words = u32::from(arg.ty.byte_count())
+                .next_multiple_of(tuneables.io_argument_alignment_words * 4)
+                / 4;
+            let location = location.value_index(function.ctx(), offset, words);
 
             arg_results.push(arg.append_read_at(function, location));
 
-            offset += u32::from(arg.ty.byte_count())
-                .next_multiple_of(IO_ARGUMENT_ALIGNMENT_WORDS * 4)
-                / 4;
+            offset += words;
         }
 
         return arg_results;
@@ -164,15 +143,3 @@ impl WasmFnArgs {
 pub(crate) struct EntryArguments {
     pub(crate) global_id: FnArg,
 }
-
-impl EntryArguments {
-    pub(crate) fn append_to(function: &mut naga::Function, std_objects: &StdObjects) -> Self {
-        let global_id = FnArg::append_bound_to(
-            function,
-            std_objects.preamble.uvec3_ty,
-            naga::Binding::BuiltIn(naga::BuiltIn::GlobalInvocationId),
-        );
-
-        Self { global_id }
-    }
-}
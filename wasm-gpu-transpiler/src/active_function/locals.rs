@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use crate::{build, std_objects::StdObjects, BuildError, ExceededComponent};
-use naga_ext::{BlockContext, ExpressionsExt, LocalsExt};
+use naga_ext::prelude::*;
 use wasmparser::ValType;
 
 use super::arguments::WasmFnArgs;
@@ -65,9 +65,18 @@ impl FnLocals {
         std_objects: &StdObjects,
         parsed_locals: &Vec<(u32, ValType)>,
         parameters: &WasmFnArgs,
+        local_names: Option<&HashMap<u32, String>>,
     ) -> build::Result<Self> {
         let mut locals = HashMap::new();
 
+        // Names from the wasm name section take precedence over our own, if they are being preserved
+        let name_local = |i_local: u32, generated_name: String| {
+            local_names
+                .and_then(|local_names| local_names.get(&i_local))
+                .cloned()
+                .unwrap_or(generated_name)
+        };
+
         // First insert actual wasm locals
         let mut i_local = parameters.len() as u32;
         for (local_count, local_ty) in parsed_locals {
@@ -75,7 +84,7 @@ impl FnLocals {
                 locals.insert(
                     i_local,
                     FnLocal::append_wasm_to(
-                        format!("wasm_defined_local_{}", i_local),
+                        name_local(i_local, format!("wasm_defined_local_{}", i_local)),
                         ctx,
                         std_objects,
                         *local_ty,
@@ -90,7 +99,7 @@ impl FnLocals {
             let i_param = u32::try_from(i_param)
                 .map_err(|_| BuildError::BoundsExceeded(ExceededComponent::ParameterCount))?;
             let local = FnLocal::append_wasm_to(
-                format!("parameter_{}_as_local", i_param),
+                name_local(i_param, format!("parameter_{}_as_local", i_param)),
                 ctx,
                 std_objects,
                 parameter.ty,
@@ -1,12 +1,12 @@
 use crate::typed::ValTypeByteCount;
 use crate::{active_function::ActiveFunction, build};
 use crate::{BuildError, ExceededComponent};
-use naga_ext::{naga_expr, BlockContext, BlockExt, ExpressionsExt};
+use naga_ext::prelude::*;
 use wasmparser::ValType;
 
-use crate::{std_objects::StdObjects, IO_ARGUMENT_ALIGNMENT_WORDS, IO_INVOCATION_ALIGNMENT_WORDS};
+use crate::{std_objects::StdObjects, Tuneables};
 
-use super::ActiveEntryFunction;
+use super::{ActiveEntryFunction, IoLocation};
 
 /// A return type for a wasm-originated function
 #[derive(Debug, Clone)]
@@ -65,14 +65,15 @@ impl WasmFnResTy {
 
     /// Calculates the word alignment in memory (i.e. buffer bindings) that these results must occupy.
     /// Useful when saving the arguments of an entry function to an output buffer.
-    pub(crate) fn word_alignment(&self) -> u32 {
+    pub(crate) fn word_alignment(&self, tuneables: &Tuneables) -> u32 {
         let mut word_offset = 0;
         for ty in &self.wasm_ty {
-            word_offset +=
-                u32::from(ty.byte_count()).next_multiple_of(IO_ARGUMENT_ALIGNMENT_WORDS * 4) / 4;
+            word_offset += u32::from(ty.byte_count())
+                .next_multiple_of(tuneables.io_argument_alignment_words * 4)
+                / 4;
         }
 
-        return word_offset.next_multiple_of(IO_INVOCATION_ALIGNMENT_WORDS);
+        return tuneables.io_layout.invocation_words(word_offset, tuneables);
     }
 
     /// Builds a struct of the return type and pushes it as a return expression at the end of the function's
@@ -92,12 +93,16 @@ impl WasmFnResTy {
     pub(crate) fn append_store_at(
         &self,
         function: &mut ActiveEntryFunction<'_, '_>,
-        location: naga::Handle<naga::Expression>,
+        location: IoLocation,
         value: naga::Handle<naga::Expression>,
+        tuneables: &Tuneables,
     ) -> build::Result<()> {
         let mut word_offset = 0;
         for (i_res, val_ty) in self.wasm_ty.iter().enumerate() {
-            let location = naga_expr! {function.ctx() => location + (U32(word_offset))};
+            let words = u32::from(val_ty.byte_count())
+                .next_multiple_of(tuneables.io_argument_alignment_words * 4)
+                / 4;
+            let location = location.value_index(function.ctx(), word_offset, words);
 
             let i_res = u32::try_from(i_res)
                 .map_err(|_| BuildError::BoundsExceeded(ExceededComponent::ReturnType))?;
@@ -114,9 +119,7 @@ impl WasmFnResTy {
                 naga::Span::UNDEFINED,
             );
 
-            word_offset += u32::from(val_ty.byte_count())
-                .next_multiple_of(IO_ARGUMENT_ALIGNMENT_WORDS * 4)
-                / 4;
+            word_offset += words;
         }
 
         Ok(())
@@ -47,12 +47,18 @@ impl<'a> ActiveModule<'a> {
             "_base_impl",
             ptr,
             function_data,
+            self.tuneables.preserve_names,
         )
     }
 
     /// Forward declare a shader entry function
     pub(crate) fn declare_entry_function(&mut self, ptr: FuncRef) -> EntryFunction {
-        EntryFunction::append_declaration_to(&mut self.module, &self.std_objects, ptr)
+        EntryFunction::append_declaration_to(
+            &mut self.module,
+            &self.std_objects,
+            ptr,
+            self.tuneables.workgroup_size,
+        )
     }
 
     /// Forward declare a stack function
@@ -67,6 +73,7 @@ impl<'a> ActiveModule<'a> {
             "_stack_impl",
             ptr,
             function_data,
+            self.tuneables.preserve_names,
         )
     }
 
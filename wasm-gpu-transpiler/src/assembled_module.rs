@@ -1,10 +1,21 @@
+#[cfg(feature = "cache")]
+mod caching;
 mod call_graph;
+mod deduplication;
+mod function_merging;
+mod function_stats;
+#[cfg(feature = "parallel")]
+mod parallel_codegen;
+mod pruning;
 
 use self::call_graph::CallGraph;
+use self::deduplication::Deduplication;
+pub use self::function_stats::FunctionStat;
 use crate::active_module::ActiveModule;
 use crate::function_lookup::FunctionLookup;
 use crate::wasm_front::FuncsInstance;
 use crate::{build, BuildError, ExternalValidationError, Tuneables, ValidationError};
+use std::fmt::Write;
 
 /// All of the functions and trampolines for a module, in wgpu objects ready to be called.
 pub struct AssembledModule<'a> {
@@ -13,6 +24,8 @@ pub struct AssembledModule<'a> {
 
     // Used as debug info
     functions: FuncsInstance<'a>,
+    /// The base function that each wasm function was lowered to, indexed by function index
+    base_functions: Vec<naga::Handle<naga::Function>>,
     tuneables: Tuneables,
     capabilities: naga::valid::Capabilities,
 }
@@ -23,13 +36,21 @@ impl<'a> AssembledModule<'a> {
         if module.entry_points.is_empty() {
             // Shaders must do something, even if our module doesn't. Introduce a dud function
             // that does nothing and that isn't exposed to the outside world
-            module.entry_points.push(naga::EntryPoint {
-                name: "dud_entry".to_owned(),
-                stage: naga::ShaderStage::Compute,
-                early_depth_test: None,
-                workgroup_size: [1, 1, 1],
-                function: naga::Function::default(),
-            })
+            naga_ext::declare_entry_point! {module =>
+                fn dud_entry() @workgroup_size(1, 1, 1)
+            };
+        }
+    }
+
+    fn capabilities(tuneables: &Tuneables) -> naga::valid::Capabilities {
+        if let Some(capabilities) = tuneables.capabilities {
+            return capabilities;
+        }
+
+        if !tuneables.fp_options.emulate_f64 {
+            naga::valid::Capabilities::FLOAT64
+        } else {
+            naga::valid::Capabilities::empty()
         }
     }
 
@@ -67,6 +88,8 @@ impl<'a> AssembledModule<'a> {
 
     /// Converts wasm functions to a validated naga module
     pub fn assemble(functions: FuncsInstance<'a>, tuneables: &Tuneables) -> build::Result<Self> {
+        tuneables.validate()?;
+
         let mut module = naga::Module::default();
 
         let mut base_functions = FunctionLookup::empty();
@@ -80,13 +103,22 @@ impl<'a> AssembledModule<'a> {
         let call_graph = CallGraph::calculate(&functions);
         let call_order = call_graph.to_call_order();
 
-        // Declare base and entry functions first
-        for ptr in call_order.get_in_order() {
-            let function_data = functions
-                .get(*ptr)
-                .expect("call order doesn't invent functions");
-            let base_function = active_module.declare_base_function(*ptr, function_data)?;
-            base_functions.insert(*ptr, base_function);
+        // Find functions that are identical, so that we only lower each distinct body once
+        let deduplication = Deduplication::calculate(&functions);
+
+        // Declare base and entry functions first. Naga requires callees to be declared before their callers, and
+        // calls to a duplicate are made to its canonical function, so each canonical function is declared at the
+        // position of whichever function in its group comes first
+        for ptr in call_order.get_in_order().iter().rev() {
+            let canonical_ptr = deduplication.canonical(*ptr);
+            if !base_functions.contains(&canonical_ptr) {
+                let function_data = functions
+                    .get(canonical_ptr)
+                    .expect("call order doesn't invent functions");
+                let base_function =
+                    active_module.declare_base_function(canonical_ptr, function_data)?;
+                base_functions.insert(canonical_ptr, base_function);
+            }
             let entry_function = active_module.declare_entry_function(*ptr);
             entry_functions.insert(*ptr, entry_function);
         }
@@ -96,6 +128,9 @@ impl<'a> AssembledModule<'a> {
 
         // Declare recursive functions after brain
         for ptr in call_order.get_in_order() {
+            if !deduplication.is_canonical(*ptr) {
+                continue;
+            }
             let function_data = functions
                 .get(*ptr)
                 .expect("call order doesn't invent functions");
@@ -103,11 +138,44 @@ impl<'a> AssembledModule<'a> {
             stack_functions.insert(*ptr, stack_function);
         }
 
+        // Direct calls to duplicate functions are made to the base function of their canonical function
+        let mut call_targets = FunctionLookup::empty();
+        for (ptr, function_data) in functions.all_items() {
+            let canonical_ptr = deduplication.canonical(ptr);
+            let call_target = base_functions
+                .lookup(&canonical_ptr)
+                .call_target(function_data.data.ty.clone());
+            call_targets.insert(ptr, call_target);
+        }
+
+        // Base function bodies are independent of each other, so they can be populated up front on a thread pool
+        let populate_in_parallel = cfg!(feature = "parallel") && tuneables.parallel_codegen;
+        #[cfg(feature = "parallel")]
+        if populate_in_parallel {
+            let to_populate = functions
+                .all_items()
+                .into_iter()
+                .filter(|(ptr, _)| deduplication.is_canonical(*ptr))
+                .map(|(ptr, function_data)| (base_functions.lookup(&ptr), function_data))
+                .collect();
+            parallel_codegen::populate_base_functions(
+                &mut active_module,
+                to_populate,
+                &call_targets,
+            )?;
+        }
+
         // Populate functions
+        let mut base_function_handles = Vec::new();
         for (ptr, function_data) in functions.all_items() {
+            let canonical_ptr = deduplication.canonical(ptr);
             let (base_handle, base_args, base_res) = {
-                let mut base_function = base_functions.lookup_mut(&mut active_module, &ptr);
-                base_function.populate_base_function(function_data)?;
+                let mut base_function =
+                    base_functions.lookup_mut(&mut active_module, &canonical_ptr);
+                // Duplicates share the body of their canonical function, which only needs populating once
+                if canonical_ptr == ptr && !populate_in_parallel {
+                    base_function.populate_base_function(function_data, &call_targets)?;
+                }
 
                 let handle = base_function.handle().clone();
                 let args = base_function.get_arg_tys().clone();
@@ -115,6 +183,7 @@ impl<'a> AssembledModule<'a> {
 
                 (handle, args, res)
             };
+            base_function_handles.push(base_handle);
 
             //let stack_function = stack_functions.lookup_mut(&mut active_module, &ptr);
             //populate_stack_function(&mut module, function_data, &call_order, stack_functions.lookup(&ptr.to_func_ref()))?;
@@ -130,11 +199,14 @@ impl<'a> AssembledModule<'a> {
 
         Self::appease_drivers(&mut module);
 
-        let capabilities = if !tuneables.fp_options.emulate_f64 {
-            naga::valid::Capabilities::FLOAT64
-        } else {
-            naga::valid::Capabilities::empty()
-        };
+        if tuneables.deduplicate_functions {
+            function_merging::merge_identical_functions(&mut module, &mut base_function_handles);
+        }
+        if tuneables.prune_unused {
+            pruning::prune_unused(&mut module, &mut base_function_handles);
+        }
+
+        let capabilities = Self::capabilities(tuneables);
 
         let module_info = Self::validate(&module, tuneables, capabilities, true)
             .map_err(BuildError::ValidationError)?;
@@ -144,6 +216,7 @@ impl<'a> AssembledModule<'a> {
             module,
             tuneables: tuneables.clone(),
             functions,
+            base_functions: base_function_handles,
             capabilities,
         };
 
@@ -164,6 +237,7 @@ impl<'a> AssembledModule<'a> {
             module,
             module_info: _, // Throw away old derived info
             functions,
+            base_functions,
             tuneables,
             capabilities,
         } = self;
@@ -182,23 +256,669 @@ impl<'a> AssembledModule<'a> {
             module_info,
             module,
             functions,
+            base_functions,
             tuneables,
             capabilities,
         })
     }
 
+    /// Gives size statistics for the code generated for each wasm function, in function index order, along with
+    /// the functions that each may call. Useful to find the functions responsible for an oversized shader, e.g.
+    /// to decide whether the floating point emulation options are worth their cost for a build.
+    pub fn function_stats(&self) -> Vec<FunctionStat> {
+        let call_graph = CallGraph::calculate(&self.functions);
+
+        self.base_functions
+            .iter()
+            .enumerate()
+            .map(|(function_index, handle)| {
+                FunctionStat::calculate(
+                    function_index,
+                    &self.module.functions[*handle],
+                    call_graph.callees(function_index),
+                )
+            })
+            .collect()
+    }
+
     /// Converts our internal representation to HLSL and passes it back as a string of source code.
     ///
     /// This method is intended for debugging; the outputted source is intended to be as close as possible
     /// to the shader module that will be run, but no guarantee is made that compiling this source will give
-    /// the same shader module as will be executed.
+    /// the same shader module as will be executed. The source targets the shader model given by
+    /// `Tuneables::hlsl_shader_model`.
     pub fn generate_hlsl_source(&self) -> String {
         let mut output_shader = String::new();
 
-        let hlsl_options = &crate::HLSL_OUT_OPTIONS;
-        let mut writer = naga::back::hlsl::Writer::new(&mut output_shader, hlsl_options);
+        let hlsl_options = self.hlsl_options();
+        let mut writer = naga::back::hlsl::Writer::new(&mut output_shader, &hlsl_options);
         writer.write(&self.module, &self.module_info).unwrap();
 
         return output_shader;
     }
+
+    /// The options that [`AssembledModule::generate_hlsl_source`] writes HLSL with, as given by the tuneables that
+    /// this module was built with.
+    pub fn hlsl_options(&self) -> naga::back::hlsl::Options {
+        naga::back::hlsl::Options {
+            shader_model: self.tuneables.hlsl_shader_model.to_naga(),
+            zero_initialize_workgroup_memory: self.tuneables.zero_init_workgroup_memory,
+            ..crate::HLSL_OUT_OPTIONS
+        }
+    }
+
+    /// Pretty-prints our internal representation as naga IR, listing the module's types, constants and globals,
+    /// followed by the arguments, locals, expressions and statements of every function and entry point.
+    ///
+    /// This method is intended for debugging, and unlike the module held by [`ExternalValidationError`] it is
+    /// available in release builds, so that bug reports can be filed without a debug build of the whole crate.
+    pub fn dump_naga_ir(&self) -> String {
+        let mut output = String::new();
+        self.write_naga_ir(&mut output)
+            .expect("writing to a string cannot fail");
+
+        return output;
+    }
+
+    fn write_naga_ir(&self, output: &mut impl std::fmt::Write) -> std::fmt::Result {
+        writeln!(output, "types:")?;
+        for (handle, ty) in self.module.types.iter() {
+            writeln!(output, "    {:?}: {:?}", handle, ty)?;
+        }
+
+        writeln!(output, "const expressions:")?;
+        for (handle, expression) in self.module.const_expressions.iter() {
+            writeln!(output, "    {:?}: {:?}", handle, expression)?;
+        }
+
+        writeln!(output, "constants:")?;
+        for (handle, constant) in self.module.constants.iter() {
+            writeln!(output, "    {:?}: {:?}", handle, constant)?;
+        }
+
+        writeln!(output, "globals:")?;
+        for (handle, global) in self.module.global_variables.iter() {
+            writeln!(output, "    {:?}: {:?}", handle, global)?;
+        }
+
+        for (handle, function) in self.module.functions.iter() {
+            writeln!(output, "function {:?}:", handle)?;
+            Self::write_function_ir(output, function)?;
+        }
+
+        for entry_point in &self.module.entry_points {
+            writeln!(
+                output,
+                "entry point {:?} (stage {:?}, workgroup size {:?}):",
+                entry_point.name, entry_point.stage, entry_point.workgroup_size
+            )?;
+            Self::write_function_ir(output, &entry_point.function)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_function_ir(
+        output: &mut impl std::fmt::Write,
+        function: &naga::Function,
+    ) -> std::fmt::Result {
+        writeln!(output, "    name: {:?}", function.name)?;
+        writeln!(output, "    arguments: {:?}", function.arguments)?;
+        writeln!(output, "    result: {:?}", function.result)?;
+
+        writeln!(output, "    locals:")?;
+        for (handle, local) in function.local_variables.iter() {
+            writeln!(output, "        {:?}: {:?}", handle, local)?;
+        }
+
+        writeln!(output, "    expressions:")?;
+        for (handle, expression) in function.expressions.iter() {
+            writeln!(output, "        {:?}: {:?}", handle, expression)?;
+        }
+
+        writeln!(output, "    body: {:#?}", function.body)
+    }
+
+    /// Converts our internal representation to WGSL and passes it back as a string of source code.
+    ///
+    /// WGSL is usually the most readable format to attach to a naga bug report, and is the source given to wgpu
+    /// when targeting WebGPU. Any failure to write the module is reported as an error, which describes why the
+    /// module couldn't be written.
+    pub fn generate_wgsl_source(&self) -> build::Result<String> {
+        naga::back::wgsl::write_string(
+            &self.module,
+            &self.module_info,
+            naga::back::wgsl::WriterFlags::EXPLICIT_TYPES,
+        )
+        .map_err(|source| BuildError::ValidationError(ValidationError::WgslWriterError(source)))
+    }
+
+    /// Converts our internal representation to SPIR-V, giving the binary as 32-bit words. Buffer accesses are
+    /// checked according to `Tuneables::bounds_checks`, and the version of SPIR-V written is taken from
+    /// `Tuneables::spirv`.
+    ///
+    /// This method is intended for offline inspection, e.g. with `spirv-dis`. As with
+    /// [`AssembledModule::generate_hlsl_source`], no guarantee is made that this is exactly the shader that will be
+    /// executed, since the module is passed to wgpu as naga IR.
+    ///
+    /// If `Tuneables::emit_debug_spans` is set, each statement is preceded by an `OpLine`. The binary that spans
+    /// refer to isn't kept, so lines are given against a blank stand-in for it: every `OpLine` is on line 1, at the
+    /// column one past the wasm offset of its instruction.
+    pub fn get_spirv_binary(&self) -> build::Result<Vec<u32>> {
+        let debug_source;
+        let mut spv_options = self.spirv_options();
+        if self.tuneables.emit_debug_spans {
+            let binary_len = self
+                .functions
+                .all_items()
+                .into_iter()
+                .filter_map(|(_, function)| function.data.operator_ranges.last())
+                .map(|range| range.end)
+                .max()
+                .unwrap_or(0);
+            debug_source = " ".repeat(binary_len);
+            spv_options.flags |= naga::back::spv::WriterFlags::DEBUG;
+            spv_options.debug_info = Some(naga::back::spv::DebugInfo {
+                source_code: &debug_source,
+                file_name: std::path::Path::new("module.wasm"),
+            });
+        }
+
+        naga::back::spv::write_vec(&self.module, &self.module_info, &spv_options, None)
+            .map_err(|source| BuildError::ValidationError(ValidationError::SpvWriterError(source)))
+    }
+
+    /// The options that [`AssembledModule::get_spirv_binary`] writes SPIR-V with, as given by the tuneables that
+    /// this module was built with.
+    pub fn spirv_options(&self) -> naga::back::spv::Options<'static> {
+        let zero_initialize_workgroup_memory = match self.tuneables.zero_init_workgroup_memory {
+            true => naga::back::spv::ZeroInitializeWorkgroupMemoryMode::Polyfill,
+            false => naga::back::spv::ZeroInitializeWorkgroupMemoryMode::None,
+        };
+        naga::back::spv::Options {
+            lang_version: self.tuneables.spirv.lang_version,
+            bounds_check_policies: self.tuneables.bounds_checks.policies(),
+            zero_initialize_workgroup_memory,
+            ..crate::SPV_OUT_OPTIONS
+        }
+    }
+
+    /// As with [`AssembledModule::get_spirv_binary`], but giving the little-endian bytes of the binary, ready to be
+    /// written to a `.spv` file.
+    pub fn get_spirv_bytes(&self) -> Vec<u8> {
+        self.get_spirv_binary()
+            .expect("validated module should always be writable as spir-v")
+            .into_iter()
+            .flat_map(u32::to_le_bytes)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AssembledModule;
+    use crate::typed::FuncRef;
+    use crate::wasm_front::{
+        FuncAccessible, FuncData, FuncUnit, FuncsInstance, FunctionModuleData, StableHasher,
+    };
+    use crate::{build, BuildError, SpirvOptions, SpirvTargetEnv, Tuneables};
+    use naga_ext::{BlockExt, ExpressionsExt, GlobalsExt, TypesExt};
+    use std::collections::HashMap;
+    use std::hash::Hasher;
+    use std::sync::Arc;
+    use wasm_opcodes::OperatorByProposal;
+    use wasmparser::{Name, NameSectionReader, Payload, Type};
+
+    /// Parses a module from the text format and assembles its functions. The module mustn't import anything, and
+    /// its functions may call each other directly but mustn't access any other module objects. The binary is leaked so that the
+    /// assembled module can borrow the operators read from it.
+    fn assemble_wat(wat: &str, tuneables: &Tuneables) -> build::Result<AssembledModule<'static>> {
+        let buffer = wast::parser::ParseBuffer::new(wat).expect("test module should be lexable");
+        let mut wat = wast::parser::parse::<wast::Wat>(&buffer).expect("test module should parse");
+        let binary: &'static [u8] = Vec::leak(wat.encode().expect("test module should encode"));
+
+        let mut module_data = Arc::new(FunctionModuleData { types: Vec::new() });
+        let mut type_ids = Vec::new();
+        let mut functions = Vec::new();
+        for payload in wasmparser::Parser::new(0).parse_all(binary) {
+            match payload.expect("test module should be well formed") {
+                Payload::TypeSection(types) => {
+                    let types = types
+                        .into_iter()
+                        .map(|ty| {
+                            let Type::Func(ty) = ty.unwrap();
+                            ty
+                        })
+                        .collect();
+                    module_data = Arc::new(FunctionModuleData { types });
+                }
+                Payload::FunctionSection(entries) => {
+                    type_ids = entries.into_iter().collect::<Result<_, _>>().unwrap();
+                }
+                Payload::CodeSectionEntry(body) => {
+                    let type_id = type_ids[functions.len()];
+                    let mut hasher = StableHasher::default();
+                    hasher.write(&binary[body.range()]);
+
+                    let reader = body.get_operators_reader().unwrap();
+                    let mut operators = Vec::new();
+                    let mut offsets = Vec::new();
+                    for operator in reader.into_iter_with_offsets() {
+                        let (operator, offset) = operator.unwrap();
+                        operators.push(OperatorByProposal::from_operator(operator).unwrap());
+                        offsets.push(offset);
+                    }
+                    offsets.push(body.range().end);
+
+                    functions.push(FuncData {
+                        ty: module_data.types[type_id as usize].clone(),
+                        locals: body
+                            .get_locals_reader()
+                            .unwrap()
+                            .into_iter()
+                            .collect::<Result<_, _>>()
+                            .unwrap(),
+                        operators,
+                        operator_ranges: offsets
+                            .windows(2)
+                            .map(|window| window[0]..window[1])
+                            .collect(),
+                        body_hash: hasher.finish(),
+                        module_data: Arc::clone(&module_data),
+                        name: None,
+                        local_names: HashMap::new(),
+                    });
+                }
+                Payload::CustomSection(section) if section.name() == "name" => {
+                    let names = NameSectionReader::new(section.data(), section.data_offset());
+                    for name in names {
+                        match name.unwrap() {
+                            Name::Function(names) => {
+                                for naming in names {
+                                    let naming = naming.unwrap();
+                                    functions[naming.index as usize].name =
+                                        Some(naming.name.to_owned());
+                                }
+                            }
+                            Name::Local(names) => {
+                                for indirect_naming in names {
+                                    let indirect_naming = indirect_naming.unwrap();
+                                    for naming in indirect_naming.names {
+                                        let naming = naming.unwrap();
+                                        functions[indirect_naming.index as usize]
+                                            .local_names
+                                            .insert(naming.index, naming.name.to_owned());
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let accessible = Arc::new(FuncAccessible {
+            func_index_lookup: (0..functions.len())
+                .map(|index| FuncRef::try_from(index as u32).unwrap())
+                .collect(),
+            ..FuncAccessible::empty()
+        });
+        let functions = FuncsInstance {
+            wasm_functions: functions
+                .into_iter()
+                .map(|data| FuncUnit {
+                    data,
+                    accessible: Arc::clone(&accessible),
+                })
+                .collect(),
+        };
+
+        AssembledModule::assemble(functions, tuneables)
+    }
+
+    /// The generated module doesn't declare any workgroup memory, so this adds an entry point that stores to a
+    /// workgroup variable, and revalidates the module so that it can be written.
+    fn add_workgroup_memory(assembled: &mut AssembledModule) {
+        let module = &mut assembled.module;
+        let u32_ty = module.types.insert_u32();
+        let scratch = module.global_variables.append_global_var(
+            "scratch",
+            naga::AddressSpace::WorkGroup,
+            None,
+            u32_ty,
+            None,
+        );
+        let (index,) = naga_ext::declare_entry_point! {module =>
+            fn uses_scratch() @workgroup_size(1, 1, 1)
+        };
+        let function = &mut module.entry_points[index].function;
+        let pointer = function.expressions.append_global(scratch);
+        let value = function.expressions.append_u32(1);
+        function.body.push_store(pointer, value);
+
+        assembled.module_info = AssembledModule::validate(
+            &assembled.module,
+            &assembled.tuneables,
+            assembled.capabilities,
+            false,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn spirv_header_holds_configured_version() {
+        let wat = r#"
+            (module
+                (func $f (param i32) (result i32)
+                    (i32.add (local.get 0) (i32.const 5))
+                )
+            )
+        "#;
+
+        for (target_env, lang_version) in [
+            (SpirvTargetEnv::Vulkan1_0, (1, 0)),
+            (SpirvTargetEnv::Vulkan1_1, (1, 3)),
+            (SpirvTargetEnv::Vulkan1_3, (1, 6)),
+        ] {
+            let tuneables = Tuneables {
+                spirv: SpirvOptions {
+                    target_env,
+                    lang_version,
+                },
+                ..Default::default()
+            };
+            let binary = assemble_wat(wat, &tuneables)
+                .unwrap()
+                .get_spirv_binary()
+                .unwrap();
+
+            // The second word of the header holds the version as 0x00MMmm00
+            let (major, minor) = lang_version;
+            assert_eq!(
+                binary[1],
+                (u32::from(major) << 16) | (u32::from(minor) << 8)
+            );
+        }
+
+        // Versions newer than the environment accepts are rejected
+        let tuneables = Tuneables {
+            spirv: SpirvOptions {
+                target_env: SpirvTargetEnv::Vulkan1_0,
+                lang_version: (1, 3),
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            assemble_wat(wat, &tuneables),
+            Err(BuildError::IncompatibleSpirvVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn names_are_preserved() {
+        let wat = r#"
+            (module
+                (func $add_five (param $augend i32) (result i32)
+                    (local $five i32)
+                    (local.set $five (i32.const 5))
+                    (i32.add (local.get $augend) (local.get $five))
+                )
+            )
+        "#;
+
+        for preserve_names in [true, false] {
+            let tuneables = Tuneables {
+                preserve_names,
+                ..Default::default()
+            };
+            let assembled = assemble_wat(wat, &tuneables).unwrap();
+
+            let function = &assembled.module.functions[assembled.base_functions[0]];
+            let function_name = function.name.as_deref().unwrap_or_default();
+            assert_eq!(
+                function_name.starts_with("add_five"),
+                preserve_names,
+                "{}",
+                function_name
+            );
+            let local_names = function
+                .local_variables
+                .iter()
+                .filter_map(|(_, local)| local.name.as_deref())
+                .collect::<Vec<_>>();
+            for name in ["augend", "five"] {
+                assert_eq!(
+                    local_names.contains(&name),
+                    preserve_names,
+                    "{} in {:?}",
+                    name,
+                    local_names
+                );
+            }
+        }
+    }
+
+    /// Splits a SPIR-V binary into the opcodes of its instructions.
+    fn spirv_opcodes(binary: &[u32]) -> Vec<u32> {
+        // Each instruction after the 5 word header starts with its word count and opcode
+        let mut instructions = &binary[5..];
+        let mut opcodes = Vec::new();
+        while let Some(first_word) = instructions.first() {
+            opcodes.push(first_word & 0xffff);
+            instructions = &instructions[(first_word >> 16) as usize..];
+        }
+        opcodes
+    }
+
+    #[test]
+    fn debug_spans_cover_the_instructions_of_statements() {
+        let wat = r#"
+            (module
+                (func $f (param i32) (result i32)
+                    (local i32)
+                    (local.set 1 (local.get 0))
+                    (local.get 1)
+                )
+            )
+        "#;
+
+        for emit_debug_spans in [true, false] {
+            let tuneables = Tuneables {
+                emit_debug_spans,
+                ..Default::default()
+            };
+            let assembled = assemble_wat(wat, &tuneables).unwrap();
+
+            let function = &assembled.module.functions[assembled.base_functions[0]];
+            let spanned = function
+                .body
+                .span_iter()
+                .filter_map(|(statement, span)| Some((statement, span.to_range()?)))
+                .collect::<Vec<_>>();
+
+            // Spans are written to SPIR-V as `OpLine`s
+            const OP_LINE: u32 = 8;
+            let opcodes = spirv_opcodes(&assembled.get_spirv_binary().unwrap());
+            assert_eq!(opcodes.contains(&OP_LINE), emit_debug_spans);
+
+            if !emit_debug_spans {
+                assert!(spanned.is_empty(), "{:?}", spanned);
+                continue;
+            }
+
+            // The operators are `local.get 0`, `local.set 1`, `local.get 1` and `end`
+            let ranges = &assembled.functions.wasm_functions[0].data.operator_ranges;
+            assert_eq!(ranges.len(), 4);
+            assert!(
+                spanned.iter().any(|(statement, range)| {
+                    matches!(statement, naga::Statement::Store { .. }) && range == &ranges[1]
+                }),
+                "no store spanning `local.set` at {:?} in {:?}",
+                ranges[1],
+                spanned
+            );
+            for (statement, range) in &spanned {
+                assert!(
+                    ranges.contains(range),
+                    "{:?} spans {:?}, which isn't an instruction",
+                    statement,
+                    range
+                );
+            }
+
+            // Copying the parameter into its local happens before any instruction
+            let first_store = function
+                .body
+                .span_iter()
+                .find(|(statement, _)| matches!(statement, naga::Statement::Store { .. }))
+                .unwrap();
+            assert!(!first_store.1.is_defined());
+        }
+    }
+
+    #[test]
+    fn workgroup_memory_is_zeroed_when_enabled() {
+        let wat = r#"
+            (module
+                (func $f (result i32)
+                    (i32.const 5)
+                )
+            )
+        "#;
+
+        for zero_init_workgroup_memory in [true, false] {
+            let tuneables = Tuneables {
+                zero_init_workgroup_memory,
+                ..Default::default()
+            };
+            let mut assembled = assemble_wat(wat, &tuneables).unwrap();
+            add_workgroup_memory(&mut assembled);
+
+            // The generated module has no barriers of its own, so any barrier is from zeroing the variable before
+            // the invocations of a workgroup read it
+            let hlsl = assembled.generate_hlsl_source();
+            assert_eq!(
+                hlsl.contains("scratch = (uint)0;"),
+                zero_init_workgroup_memory,
+                "{}",
+                hlsl
+            );
+            assert_eq!(
+                hlsl.contains("GroupMemoryBarrierWithGroupSync()"),
+                zero_init_workgroup_memory,
+                "{}",
+                hlsl
+            );
+
+            const OP_CONTROL_BARRIER: u32 = 224;
+            let opcodes = spirv_opcodes(&assembled.get_spirv_binary().unwrap());
+            assert_eq!(
+                opcodes.contains(&OP_CONTROL_BARRIER),
+                zero_init_workgroup_memory
+            );
+        }
+    }
+
+    #[test]
+    fn wgsl_source_is_valid() {
+        let wat = r#"
+            (module
+                (func $f (param i32) (result i32)
+                    (i32.add (local.get 0) (i32.const 5))
+                )
+            )
+        "#;
+        let assembled = assemble_wat(wat, &Tuneables::default()).unwrap();
+        let source = assembled.generate_wgsl_source().unwrap();
+
+        // Round-trip through naga's own frontend and validator
+        let reparsed = naga::front::wgsl::parse_str(&source).unwrap();
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            AssembledModule::capabilities(&assembled.tuneables),
+        )
+        .validate(&reparsed)
+        .unwrap();
+    }
+
+    #[test]
+    fn duplicates_are_declared_before_callers_of_any_function_in_their_group() {
+        // f0 and f2 are identical, so the call from f1 to f2 is made to f0, which must be declared before f1 even
+        // though f0 itself isn't called
+        let assembled = assemble_wat(
+            r#"(module
+                (func $f0 (result i32) i32.const 7)
+                (func $f1 (result i32) call $f2)
+                (func $f2 (result i32) i32.const 7)
+                (func $f3 (result i32) call $f1))"#,
+            &Tuneables::default(),
+        )
+        .expect("calls to duplicate functions aren't recursive");
+
+        assert_eq!(assembled.base_functions[0], assembled.base_functions[2]);
+    }
+
+    #[test]
+    fn identical_functions_share_a_base_function() {
+        let assembled = assemble_wat(
+            r#"(module
+                (func $f1 (param i32) (result i32) (i32.mul (local.get 0) (i32.const 7)))
+                (func $f2 (param i32) (result i32) (i32.mul (local.get 0) (i32.const 7)))
+                (func $f3 (param i32) (result i32) (i32.mul (local.get 0) (i32.const 7)))
+                (func $f4 (param i32) (result i32) (i32.mul (local.get 0) (i32.const 8))))"#,
+            &Tuneables::default(),
+        )
+        .unwrap();
+
+        let base_functions = &assembled.base_functions;
+        assert_eq!(base_functions[0], base_functions[1]);
+        assert_eq!(base_functions[0], base_functions[2]);
+        assert_ne!(base_functions[0], base_functions[3]);
+    }
+
+    #[test]
+    fn invocations_from_the_invocation_count_onwards_return_early() {
+        // The invocation whose index equals the invocation count is one past the last instance, so it mustn't
+        // read inputs or write outputs and flags, which would overrun into the next region of a packed binding
+        let assembled = assemble_wat(
+            r#"(module (func $f (result i32) (i32.const 5)))"#,
+            &Tuneables::default(),
+        )
+        .unwrap();
+
+        let entry_name = crate::get_entry_name(FuncRef::try_from(0).unwrap());
+        let function = &assembled
+            .module
+            .entry_points
+            .iter()
+            .find(|entry_point| entry_point.name == entry_name)
+            .unwrap()
+            .function;
+        let condition = function
+            .body
+            .iter()
+            .find_map(|statement| match statement {
+                naga::Statement::If {
+                    condition, accept, ..
+                } => {
+                    let returns = matches!(accept.first(), Some(naga::Statement::Return { .. }));
+                    returns.then_some(*condition)
+                }
+                _ => None,
+            })
+            .expect("entry point should return early for invocations beyond the last instance");
+        assert!(
+            matches!(
+                function.expressions[condition],
+                naga::Expression::Binary {
+                    op: naga::BinaryOperator::GreaterEqual,
+                    ..
+                }
+            ),
+            "{:?}",
+            function.expressions[condition]
+        );
+    }
 }
@@ -0,0 +1,122 @@
+use std::hash::{Hash, Hasher};
+
+use super::AssembledModule;
+use crate::wasm_front::{FuncsInstance, StableHasher};
+use crate::{build, BuildError, Tuneables};
+
+/// Identifies the format of cached modules. Generated modules change between versions of this crate, so caches saved
+/// by any other version are rejected.
+const CACHE_VERSION: &str = concat!(env!("CARGO_PKG_NAME"), "-", env!("CARGO_PKG_VERSION"), "-2");
+
+/// Everything that a cached module must match to be reused, saved ahead of the module so that it can be checked
+/// without reading a module whose format may differ.
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+struct CacheHeader {
+    version: String,
+    tuneables: Vec<u8>,
+    functions: u64,
+}
+
+impl CacheHeader {
+    fn new(functions: &FuncsInstance, tuneables: &Tuneables) -> Self {
+        Self {
+            version: CACHE_VERSION.to_owned(),
+            tuneables: bincode::serialize(tuneables).expect("tuneables are always serializable"),
+            functions: functions_hash(functions),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct CachedModuleRef<'m> {
+    header: CacheHeader,
+    module: &'m naga::Module,
+}
+
+#[derive(serde::Deserialize)]
+struct CachedModule {
+    header: CacheHeader,
+    module: naga::Module,
+}
+
+/// The generated module depends on the body of every wasm function and on the objects that each can access, so a
+/// cached module can only be reused for the same functions, instantiated in the same order.
+fn functions_hash(functions: &FuncsInstance) -> u64 {
+    let mut hasher = StableHasher::default();
+    (functions.wasm_functions.len() as u64).hash(&mut hasher);
+    for function in &functions.wasm_functions {
+        function.data.body_hash.hash(&mut hasher);
+        function.data.ty.hash(&mut hasher);
+        function.data.module_data.types.hash(&mut hasher);
+        function.accessible.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn invalid_cache(err: impl ToString) -> BuildError {
+    BuildError::InvalidCache {
+        reason: err.to_string(),
+    }
+}
+
+impl<'a> AssembledModule<'a> {
+    /// Saves the generated module, so that it can be reloaded with [`AssembledModule::deserialize`] rather than
+    /// assembled again, e.g. to avoid regenerating all of the polyfills on every program start.
+    ///
+    /// The saved module is tagged with the version of this crate, the tuneables that it was assembled with and a hash
+    /// of the wasm functions that it was assembled from.
+    pub fn serialize(&self) -> Vec<u8> {
+        bincode::serialize(&CachedModuleRef {
+            header: CacheHeader::new(&self.functions, &self.tuneables),
+            module: &self.module,
+        })
+        .expect("naga modules are always serializable")
+    }
+
+    /// Reloads a module saved with [`AssembledModule::serialize`], rejecting it if it was saved by a different version
+    /// of this crate, assembled with different tuneables or assembled from different wasm functions. The module is
+    /// validated again after loading.
+    ///
+    /// The wasm functions that the module was assembled from aren't saved, so the reloaded module has no
+    /// [`AssembledModule::function_stats`].
+    pub fn deserialize(
+        bytes: &[u8],
+        functions: &FuncsInstance,
+        tuneables: &Tuneables,
+    ) -> build::Result<Self> {
+        // Check the header before the module, since the module format may differ between versions
+        let header: CacheHeader = bincode::deserialize(bytes).map_err(invalid_cache)?;
+        let expected = CacheHeader::new(functions, tuneables);
+        if header.version != expected.version {
+            return Err(invalid_cache(format!(
+                "module was cached by {} but this is {}",
+                header.version, expected.version
+            )));
+        }
+        if header.tuneables != expected.tuneables {
+            return Err(invalid_cache("module was cached with different tuneables"));
+        }
+        if header.functions != expected.functions {
+            return Err(invalid_cache(
+                "module was cached from different wasm functions",
+            ));
+        }
+
+        let CachedModule { module, .. } = bincode::deserialize(bytes).map_err(invalid_cache)?;
+
+        let capabilities = Self::capabilities(tuneables);
+        let module_info = Self::validate(&module, tuneables, capabilities, false)
+            .map_err(BuildError::ValidationError)?;
+
+        Ok(Self {
+            module,
+            module_info,
+            functions: FuncsInstance {
+                wasm_functions: Vec::new(),
+            },
+            base_functions: Vec::new(),
+            tuneables: tuneables.clone(),
+            capabilities,
+        })
+    }
+}
@@ -4,6 +4,7 @@ use std::collections::HashMap;
 use wasm_opcodes::{
     proposals::ControlFlowOperator, proposals::TailCallOperator, OperatorByProposal,
 };
+use wasmparser::FuncType;
 
 use crate::{
     typed::FuncRef,
@@ -18,6 +19,7 @@ impl CallGraph {
     fn add_local_function(
         calls: &mut Graph<FuncRef, ()>,
         nodes: &HashMap<FuncRef, NodeIndex>,
+        nodes_by_type: &HashMap<FuncType, Vec<NodeIndex>>,
         src_node: &NodeIndex,
         function: &FuncUnit,
     ) {
@@ -32,6 +34,22 @@ impl CallGraph {
                         .expect("an OoB function reference should be caught by validation")
                         .clone()
                 }
+                // An indirect call may reach any function with the right type
+                OperatorByProposal::ControlFlow(ControlFlowOperator::CallIndirect {
+                    type_index,
+                    ..
+                }) => {
+                    let ty = function
+                        .data
+                        .module_data
+                        .types
+                        .get(usize::try_from(*type_index).expect("module must fit in memory"))
+                        .expect("an OoB type reference should be caught by validation");
+                    for dest_node in nodes_by_type.get(ty).into_iter().flatten() {
+                        calls.add_edge(src_node.clone(), dest_node.clone(), ());
+                    }
+                    continue;
+                }
                 _ => continue, // Not a call
             };
 
@@ -47,13 +65,22 @@ impl CallGraph {
 
         // Add all nodes
         let mut nodes = HashMap::new();
+        let mut nodes_by_type: HashMap<FuncType, Vec<NodeIndex>> = HashMap::new();
         let all_ptrs = functions.all_funcrefs();
         for function_ptr in &all_ptrs {
             let node = calls.add_node(*function_ptr);
             nodes.insert(*function_ptr, node);
+
+            let function = functions
+                .get(*function_ptr)
+                .expect("funcref originated from this set, so is not None or OoB");
+            nodes_by_type
+                .entry(function.data.ty.clone())
+                .or_default()
+                .push(node);
         }
 
-        // Add direct calls
+        // Add calls
         for function_ptr in &all_ptrs {
             let src_node = nodes
                 .get(function_ptr)
@@ -62,15 +89,23 @@ impl CallGraph {
             let function = functions
                 .get(*function_ptr)
                 .expect("funcref originated from this set, so is not None or OoB");
-            Self::add_local_function(&mut calls, &nodes, src_node, function);
+            Self::add_local_function(&mut calls, &nodes, &nodes_by_type, src_node, function);
         }
 
-        // We don't need to add indirect calls since indirection is implemented by dropping down into the brain method anyway,
-        // which prevents cycles in the call graph.
-
         Self { calls }
     }
 
+    /// The indices of the functions that the function at the given index may call, in ascending order. Nodes are
+    /// added in function order, so node indices are function indices.
+    pub(super) fn callees(&self, function_index: usize) -> Vec<usize> {
+        self.calls
+            .neighbors(NodeIndex::new(function_index))
+            .map(|node| node.index())
+            .sorted()
+            .dedup()
+            .collect_vec()
+    }
+
     fn get_externals(calls: &Graph<FuncRef, ()>) -> Vec<NodeIndex> {
         calls
             .externals(petgraph::Direction::Incoming)
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use wasmparser::{FuncType, ValType};
+
+use crate::{
+    typed::FuncRef,
+    wasm_front::{FuncUnit, FuncsInstance},
+};
+
+/// Some toolchains (especially those which make heavy use of macros or generics) emit many byte-identical
+/// functions. Lowering each of these separately bloats the generated shader, so instead we detect functions
+/// with identical types, locals, bodies and accessible objects and lower them once.
+pub(super) struct Deduplication {
+    // Maps every function to the first function found that is identical to it, which may be itself
+    canonical: HashMap<FuncRef, FuncRef>,
+}
+
+impl Deduplication {
+    /// Two functions are only identical if their bodies refer to the same objects, since a body calling
+    /// `func 0` in one module has different semantics to a body calling `func 0` in another.
+    fn is_identical(lhs: &FuncUnit, rhs: &FuncUnit) -> bool {
+        lhs.data.ty == rhs.data.ty
+            && lhs.data.locals == rhs.data.locals
+            && lhs.data.operators == rhs.data.operators
+            && (Arc::ptr_eq(&lhs.accessible, &rhs.accessible) || lhs.accessible == rhs.accessible)
+    }
+
+    pub(super) fn calculate(functions: &FuncsInstance) -> Self {
+        // Bucket by cheaply hashable properties first, to avoid comparing every pair of function bodies
+        let mut buckets: HashMap<(FuncType, Vec<(u32, ValType)>, usize), Vec<FuncRef>> =
+            HashMap::new();
+        let mut canonical = HashMap::new();
+
+        for (ptr, function) in functions.all_items() {
+            let key = (
+                function.data.ty.clone(),
+                function.data.locals.clone(),
+                function.data.operators.len(),
+            );
+            let candidates = buckets.entry(key).or_default();
+
+            let existing = candidates.iter().find(|candidate| {
+                let candidate_function = functions
+                    .get(**candidate)
+                    .expect("funcref originated from this set, so is not None or OoB");
+                Self::is_identical(candidate_function, function)
+            });
+
+            match existing {
+                Some(existing) => {
+                    canonical.insert(ptr, *existing);
+                }
+                None => {
+                    candidates.push(ptr);
+                    canonical.insert(ptr, ptr);
+                }
+            }
+        }
+
+        Self { canonical }
+    }
+
+    /// Gets the function whose lowered body should be used for the given function.
+    pub(super) fn canonical(&self, ptr: FuncRef) -> FuncRef {
+        *self
+            .canonical
+            .get(&ptr)
+            .expect("every function was inserted on calculation")
+    }
+
+    /// True if the given function should have its body lowered, false if it shares the body of another function.
+    pub(super) fn is_canonical(&self, ptr: FuncRef) -> bool {
+        self.canonical(ptr) == ptr
+    }
+}
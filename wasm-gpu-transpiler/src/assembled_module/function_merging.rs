@@ -0,0 +1,59 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use super::pruning::remap_calls;
+
+/// Many of the helpers generated for the standard objects, and the polyfills that they call, end up with identical
+/// bodies. This keeps only the first of each set of structurally identical functions and redirects calls to the
+/// others to it.
+///
+/// Handles within a function body index into the function's own arenas, so two identically generated bodies have
+/// identical handles and can be compared directly. Functions are declared before their callers, so by the time a
+/// function is compared its calls have already been redirected to canonical functions, and callers of merged
+/// functions are merged in turn.
+///
+/// Handles to functions in `kept_handles` are remapped in place to their canonical functions.
+pub(super) fn merge_identical_functions(
+    module: &mut naga::Module,
+    kept_handles: &mut [naga::Handle<naga::Function>],
+) {
+    let mut functions = naga::Arena::new();
+    let mut remapping = HashMap::new();
+    let mut canonical_by_body = HashMap::new();
+    for (handle, function) in module.functions.iter() {
+        let mut function = function.clone();
+        remap_calls(&mut function, &remapping);
+
+        let new_handle = match canonical_by_body.entry(body_key(&function)) {
+            Entry::Occupied(canonical) => *canonical.get(),
+            Entry::Vacant(vacant) => {
+                let new_handle = functions.append(function, module.functions.get_span(handle));
+                *vacant.insert(new_handle)
+            }
+        };
+        remapping.insert(handle, new_handle);
+    }
+
+    for entry_point in module.entry_points.iter_mut() {
+        remap_calls(&mut entry_point.function, &remapping);
+    }
+    for handle in kept_handles.iter_mut() {
+        *handle = *remapping
+            .get(handle)
+            .expect("every function was given a canonical function");
+    }
+
+    module.functions = functions;
+}
+
+/// naga functions can't be compared or hashed, but their debug representations can. Names don't change
+/// semantics, so are excluded.
+fn body_key(function: &naga::Function) -> String {
+    let mut function = function.clone();
+    function.name = None;
+    for (_, local) in function.local_variables.iter_mut() {
+        local.name = None;
+    }
+
+    return format!("{:?}", function);
+}
@@ -0,0 +1,69 @@
+/// Size statistics for the shader code generated for a single wasm function, used to find the functions
+/// responsible for oversized shaders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionStat {
+    /// The index of the wasm function in the set of functions that the module was assembled from.
+    pub function_index: usize,
+    /// The name of the naga function that the body of the wasm function was lowered to. Identical wasm functions
+    /// are lowered once, so duplicates share a name and statistics.
+    pub name: Option<String>,
+    /// The number of naga expressions in the lowered body.
+    pub expression_count: usize,
+    /// The number of naga statements in the lowered body, including those nested in blocks.
+    pub statement_count: usize,
+    /// The number of local variables in the lowered body.
+    pub local_count: usize,
+    /// A rough estimate of the number of SPIR-V instructions that the lowered body compiles to, counting one
+    /// instruction for each expression, statement, local and argument.
+    pub estimated_spirv_instructions: usize,
+    /// The indices of the wasm functions that this function may call, either directly or through a table.
+    pub callees: Vec<usize>,
+}
+
+impl FunctionStat {
+    pub(super) fn calculate(
+        function_index: usize,
+        function: &naga::Function,
+        callees: Vec<usize>,
+    ) -> Self {
+        let expression_count = function.expressions.len();
+        let statement_count = count_statements(&function.body);
+        let local_count = function.local_variables.len();
+
+        // OpFunction and OpFunctionEnd, plus a label for the entry block
+        let overhead = 3;
+        let estimated_spirv_instructions =
+            expression_count + statement_count + local_count + function.arguments.len() + overhead;
+
+        Self {
+            function_index,
+            name: function.name.clone(),
+            expression_count,
+            statement_count,
+            local_count,
+            estimated_spirv_instructions,
+            callees,
+        }
+    }
+}
+
+fn count_statements(block: &naga::Block) -> usize {
+    block
+        .iter()
+        .map(|statement| {
+            1 + match statement {
+                naga::Statement::Block(block) => count_statements(block),
+                naga::Statement::If { accept, reject, .. } => {
+                    count_statements(accept) + count_statements(reject)
+                }
+                naga::Statement::Switch { cases, .. } => {
+                    cases.iter().map(|case| count_statements(&case.body)).sum()
+                }
+                naga::Statement::Loop {
+                    body, continuing, ..
+                } => count_statements(body) + count_statements(continuing),
+                _ => 0,
+            }
+        })
+        .sum()
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use naga::{Arena, Handle, UniqueArena};
+use naga_ext::BlockContext;
+use rayon::prelude::*;
+
+use crate::active_function::{CallTarget, InternalFunction};
+use crate::active_module::ActiveModule;
+use crate::function_lookup::FunctionLookup;
+use crate::std_objects::StdObjects;
+use crate::{build, FuncUnit, Tuneables};
+
+/// A base function whose body was populated on a worker thread, along with the copies of the module level arenas
+/// that it was populated against.
+struct PopulatedFunction {
+    handle: Handle<naga::Function>,
+    function: naga::Function,
+    types: UniqueArena<naga::Type>,
+    constants: Arena<naga::Constant>,
+    const_expressions: Arena<naga::Expression>,
+}
+
+/// Populates the bodies of the given base functions on a thread pool. Each body is generated against its own copy
+/// of the module level arenas, since naga handles are only meaningful within the arena that they index. Anything
+/// appended to those copies is then merged back into the module in the order that the functions were given, and
+/// the handles within each body are remapped to point to the merged objects.
+pub(super) fn populate_base_functions(
+    active_module: &mut ActiveModule<'_>,
+    to_populate: Vec<(&InternalFunction, &FuncUnit)>,
+    call_targets: &FunctionLookup<CallTarget>,
+) -> build::Result<()> {
+    let ActiveModule {
+        module,
+        std_objects,
+        tuneables,
+    } = active_module;
+    let std_objects: &StdObjects = std_objects;
+    let tuneables: &Tuneables = tuneables;
+
+    // Take the declared functions out of the module so that each worker owns the function that it populates
+    let jobs = to_populate
+        .into_iter()
+        .map(|(declaration, func_data)| {
+            let function = std::mem::take(module.functions.get_mut(declaration.handle()));
+            (declaration, func_data, function)
+        })
+        .collect::<Vec<_>>();
+
+    let shared: &naga::Module = module;
+    let populated = jobs
+        .into_par_iter()
+        .map(|(declaration, func_data, mut function)| {
+            let mut types = shared.types.clone();
+            let mut constants = shared.constants.clone();
+            let mut const_expressions = shared.const_expressions.clone();
+
+            let ctx = BlockContext {
+                types: &mut types,
+                constants: &mut constants,
+                const_expressions: &mut const_expressions,
+                expressions: &mut function.expressions,
+                locals: &mut function.local_variables,
+                block: &mut function.body,
+            };
+            declaration.populate_base_body(ctx, func_data, call_targets, std_objects, tuneables)?;
+
+            Ok(PopulatedFunction {
+                handle: declaration.handle(),
+                function,
+                types,
+                constants,
+                const_expressions,
+            })
+        })
+        .collect::<build::Result<Vec<_>>>()?;
+
+    let shared_types = module.types.len();
+    let shared_constants = module.constants.len();
+    let shared_const_expressions = module.const_expressions.len();
+    for populated in populated {
+        merge(
+            module,
+            populated,
+            shared_types,
+            shared_constants,
+            shared_const_expressions,
+        );
+    }
+
+    Ok(())
+}
+
+/// Appends the objects created while populating a function to the module, and puts the function back in its slot.
+fn merge(
+    module: &mut naga::Module,
+    populated: PopulatedFunction,
+    shared_types: usize,
+    shared_constants: usize,
+    shared_const_expressions: usize,
+) {
+    let PopulatedFunction {
+        handle,
+        mut function,
+        types,
+        constants,
+        const_expressions,
+    } = populated;
+
+    let mut remapping = Remapping::default();
+
+    // Types only refer to types declared before them
+    for (local_handle, ty) in types.iter().skip(shared_types) {
+        let ty = naga::Type {
+            name: ty.name.clone(),
+            inner: remapping.type_inner(&ty.inner),
+        };
+        let new_handle = module.types.insert(ty, types.get_span(local_handle));
+        remapping.types.insert(local_handle, new_handle);
+    }
+
+    // Constant expressions may refer to constants, so constants are appended first and their initialisers are
+    // fixed up once the constant expressions have been appended
+    for (local_handle, constant) in constants.iter().skip(shared_constants) {
+        let mut constant = constant.clone();
+        constant.ty = remapping.ty(constant.ty);
+        let new_handle = module
+            .constants
+            .append(constant, constants.get_span(local_handle));
+        remapping.constants.insert(local_handle, new_handle);
+    }
+    for (local_handle, expression) in const_expressions.iter().skip(shared_const_expressions) {
+        let mut expression = expression.clone();
+        remapping.expression(&mut expression);
+        remapping.const_subexpressions(&mut expression);
+        let new_handle = module
+            .const_expressions
+            .append(expression, const_expressions.get_span(local_handle));
+        remapping.const_expressions.insert(local_handle, new_handle);
+    }
+    for new_handle in remapping.constants.values() {
+        let constant = module.constants.get_mut(*new_handle);
+        constant.init = remapping.const_expression(constant.init);
+    }
+
+    for (_, expression) in function.expressions.iter_mut() {
+        remapping.expression(expression);
+    }
+    for (_, local) in function.local_variables.iter_mut() {
+        local.ty = remapping.ty(local.ty);
+    }
+
+    *module.functions.get_mut(handle) = function;
+}
+
+/// Maps handles into a worker's copy of a module level arena to handles into the module. Objects that existed
+/// before the function was populated keep their handles.
+#[derive(Default)]
+struct Remapping {
+    types: HashMap<Handle<naga::Type>, Handle<naga::Type>>,
+    constants: HashMap<Handle<naga::Constant>, Handle<naga::Constant>>,
+    const_expressions: HashMap<Handle<naga::Expression>, Handle<naga::Expression>>,
+}
+
+impl Remapping {
+    fn ty(&self, handle: Handle<naga::Type>) -> Handle<naga::Type> {
+        *self.types.get(&handle).unwrap_or(&handle)
+    }
+
+    fn constant(&self, handle: Handle<naga::Constant>) -> Handle<naga::Constant> {
+        *self.constants.get(&handle).unwrap_or(&handle)
+    }
+
+    fn const_expression(&self, handle: Handle<naga::Expression>) -> Handle<naga::Expression> {
+        *self.const_expressions.get(&handle).unwrap_or(&handle)
+    }
+
+    fn type_inner(&self, inner: &naga::TypeInner) -> naga::TypeInner {
+        let mut inner = inner.clone();
+        match &mut inner {
+            naga::TypeInner::Pointer { base, .. }
+            | naga::TypeInner::Array { base, .. }
+            | naga::TypeInner::BindingArray { base, .. } => *base = self.ty(*base),
+            naga::TypeInner::Struct { members, .. } => {
+                for member in members {
+                    member.ty = self.ty(member.ty);
+                }
+            }
+            _ => {}
+        }
+        return inner;
+    }
+
+    /// Remaps the types and constants referred to by an expression in any arena.
+    fn expression(&self, expression: &mut naga::Expression) {
+        match expression {
+            naga::Expression::Constant(constant) => *constant = self.constant(*constant),
+            naga::Expression::ZeroValue(ty)
+            | naga::Expression::Compose { ty, .. }
+            | naga::Expression::AtomicResult { ty, .. }
+            | naga::Expression::WorkGroupUniformLoadResult { ty } => *ty = self.ty(*ty),
+            _ => {}
+        }
+    }
+
+    /// Remaps the other constant expressions referred to by a constant expression.
+    fn const_subexpressions(&self, expression: &mut naga::Expression) {
+        let remap =
+            |handle: &mut Handle<naga::Expression>| *handle = self.const_expression(*handle);
+        match expression {
+            naga::Expression::Compose { components, .. } => components.iter_mut().for_each(remap),
+            naga::Expression::Splat { value, .. } => remap(value),
+            naga::Expression::Swizzle { vector, .. } => remap(vector),
+            naga::Expression::Access { base, index } => {
+                remap(base);
+                remap(index);
+            }
+            naga::Expression::AccessIndex { base, .. } => remap(base),
+            naga::Expression::Unary { expr, .. } | naga::Expression::As { expr, .. } => remap(expr),
+            naga::Expression::Binary { left, right, .. } => {
+                remap(left);
+                remap(right);
+            }
+            naga::Expression::Select {
+                condition,
+                accept,
+                reject,
+            } => {
+                remap(condition);
+                remap(accept);
+                remap(reject);
+            }
+            naga::Expression::Relational { argument, .. } => remap(argument),
+            naga::Expression::Math {
+                arg,
+                arg1,
+                arg2,
+                arg3,
+                ..
+            } => {
+                remap(arg);
+                for arg in [arg1, arg2, arg3].into_iter().flatten() {
+                    remap(arg);
+                }
+            }
+            _ => {}
+        }
+    }
+}
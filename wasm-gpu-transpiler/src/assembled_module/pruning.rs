@@ -0,0 +1,127 @@
+use std::collections::{HashMap, HashSet};
+
+/// The standard objects for every wasm type are generated whether or not a module uses them, so most of the
+/// functions in an assembled module are never called. This removes every function that isn't reachable from an
+/// entry point, then compacts the module to remove the types and constants that only those functions used.
+///
+/// Handles to the functions that are kept are remapped in place in `kept_handles`, which must only contain
+/// handles to reachable functions.
+pub(super) fn prune_unused(
+    module: &mut naga::Module,
+    kept_handles: &mut [naga::Handle<naga::Function>],
+) {
+    let reachable = find_reachable(module);
+
+    // Functions are declared before their callers, so keeping the arena order keeps that invariant
+    let mut functions = naga::Arena::new();
+    let mut remapping = HashMap::new();
+    for (handle, function) in module.functions.iter() {
+        if !reachable.contains(&handle) {
+            continue;
+        }
+
+        let mut function = function.clone();
+        remap_calls(&mut function, &remapping);
+        let new_handle = functions.append(function, module.functions.get_span(handle));
+        remapping.insert(handle, new_handle);
+    }
+
+    for entry_point in module.entry_points.iter_mut() {
+        remap_calls(&mut entry_point.function, &remapping);
+    }
+    for handle in kept_handles.iter_mut() {
+        *handle = *remapping
+            .get(handle)
+            .expect("kept functions must be reachable from an entry point");
+    }
+
+    module.functions = functions;
+
+    naga::compact::compact(module);
+}
+
+fn find_reachable(module: &naga::Module) -> HashSet<naga::Handle<naga::Function>> {
+    let mut reachable = HashSet::new();
+    let mut to_visit = Vec::new();
+    for entry_point in &module.entry_points {
+        collect_calls(&entry_point.function.body, &mut to_visit);
+    }
+
+    while let Some(handle) = to_visit.pop() {
+        if reachable.insert(handle) {
+            collect_calls(&module.functions[handle].body, &mut to_visit);
+        }
+    }
+
+    return reachable;
+}
+
+fn collect_calls(block: &naga::Block, calls: &mut Vec<naga::Handle<naga::Function>>) {
+    for statement in block.iter() {
+        match statement {
+            naga::Statement::Call { function, .. } => calls.push(*function),
+            naga::Statement::Block(block) => collect_calls(block, calls),
+            naga::Statement::If { accept, reject, .. } => {
+                collect_calls(accept, calls);
+                collect_calls(reject, calls);
+            }
+            naga::Statement::Switch { cases, .. } => {
+                for case in cases {
+                    collect_calls(&case.body, calls);
+                }
+            }
+            naga::Statement::Loop {
+                body, continuing, ..
+            } => {
+                collect_calls(body, calls);
+                collect_calls(continuing, calls);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Redirects every call made by a function to the function given by the remapping.
+pub(super) fn remap_calls(
+    function: &mut naga::Function,
+    remapping: &HashMap<naga::Handle<naga::Function>, naga::Handle<naga::Function>>,
+) {
+    let remap = |handle: &mut naga::Handle<naga::Function>| {
+        *handle = *remapping
+            .get(handle)
+            .expect("callees are declared before their callers");
+    };
+
+    for (_, expression) in function.expressions.iter_mut() {
+        if let naga::Expression::CallResult(callee) = expression {
+            remap(callee);
+        }
+    }
+
+    remap_block_calls(&mut function.body, &remap);
+}
+
+fn remap_block_calls(block: &mut naga::Block, remap: &impl Fn(&mut naga::Handle<naga::Function>)) {
+    for statement in block.iter_mut() {
+        match statement {
+            naga::Statement::Call { function, .. } => remap(function),
+            naga::Statement::Block(block) => remap_block_calls(block, remap),
+            naga::Statement::If { accept, reject, .. } => {
+                remap_block_calls(accept, remap);
+                remap_block_calls(reject, remap);
+            }
+            naga::Statement::Switch { cases, .. } => {
+                for case in cases {
+                    remap_block_calls(&mut case.body, remap);
+                }
+            }
+            naga::Statement::Loop {
+                body, continuing, ..
+            } => {
+                remap_block_calls(body, remap);
+                remap_block_calls(continuing, remap);
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{active_function::InternalFunction, function_lookup::FunctionLookup};
 
-use naga_ext::{FunctionSignature, ModuleExt};
+use naga_ext::prelude::*;
 
 use crate::active_module::ActiveModule;
 
@@ -24,6 +24,14 @@ impl<F> FunctionLookup<F> {
             .get(ptr)
             .expect("all pointers are present from constructor")
     }
+
+    pub(crate) fn contains(&self, ptr: &FuncRef) -> bool {
+        self.lookup.contains_key(ptr)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&FuncRef, &F)> {
+        self.lookup.iter()
+    }
 }
 
 impl<F: InactiveFunction> FunctionLookup<F> {
@@ -3,6 +3,7 @@
 #![feature(int_roundings)]
 #![recursion_limit = "4096"]
 
+/// The default number of invocations in each workgroup, see `Tuneables::workgroup_size`
 pub const WORKGROUP_SIZE: u32 = 256;
 
 pub const MEMORY_BINDING_INDEX: u32 = 0;
@@ -48,12 +49,47 @@ pub const BINDING_TUPLES: [(u32, bool); 11] = [
     (CONSTANTS_BINDING_INDEX, CONSTANTS_BINDING_READ_ONLY),
 ];
 
-// Stack size is only used for recursive or co-recursive calls, and is currently fixed (and split across all instances)
+// When a device can't provide a storage buffer per binding above, the per-dispatch buffers (flags, constants,
+// input, output & stack) are packed into one read-write buffer. See `PackedIoLayout`.
+pub const PACKED_IO_BINDING_INDEX: u32 = INPUT_BINDING_INDEX;
+pub const PACKED_IO_BINDING_READ_ONLY: bool = false;
+
+pub const PACKED_BINDING_TUPLES: [(u32, bool); 7] = [
+    (MEMORY_BINDING_INDEX, MEMORY_BINDING_READ_ONLY),
+    (
+        MUTABLE_GLOBALS_BINDING_INDEX,
+        MUTABLE_GLOBALS_BINDING_READ_ONLY,
+    ),
+    (
+        IMMUTABLE_GLOBALS_BINDING_INDEX,
+        IMMUTABLE_GLOBALS_BINDING_READ_ONLY,
+    ),
+    (PACKED_IO_BINDING_INDEX, PACKED_IO_BINDING_READ_ONLY),
+    (TABLES_BINDING_INDEX, TABLES_BINDING_READ_ONLY),
+    (DATA_BINDING_INDEX, DATA_BINDING_READ_ONLY),
+    (ELEMENTS_BINDING_INDEX, ELEMENTS_BINDING_READ_ONLY),
+];
+
+// Stack size is only used for recursive or co-recursive calls, and is currently fixed (and split across all instances).
+// Recursion isn't lowered yet (see "Implement recursion using brain function" in the roadmap), so nothing pushes to
+// the stack, and the size can only become adjustable once stack frames exist to overflow it
 pub const STACK_LEN_BYTES: u32 = 128; //268435456; // 256MB
 
-// Flags are 32-bits wide
-pub const FLAGS_LEN_BYTES: u32 = 4;
+// Flags are six 32-bit words
+pub const FLAGS_LEN_BYTES: u32 = 24;
 pub const TRAP_FLAG_INDEX: u32 = 0;
+// The number of pages in the memory, read before and written back after each invocation
+pub const MEMORY_PAGES_FLAG_INDEX: u32 = 1;
+// A bit for each data segment in the store set, set once the segment is dropped. Read before and written back after each invocation
+pub const DATA_DROPPED_FLAG_INDEX: u32 = 2;
+pub const DATA_DROPPED_FLAG_WORDS: u32 = 2;
+// Only data segments with a dropped bit can be used by `memory.init` and `data.drop`
+pub const MAX_DROPPABLE_DATA_SEGMENTS: u32 = DATA_DROPPED_FLAG_WORDS * 32;
+// A bit for each element segment in the store set, set once the segment is dropped. Read before and written back after each invocation
+pub const ELEMENT_DROPPED_FLAG_INDEX: u32 = 4;
+pub const ELEMENT_DROPPED_FLAG_WORDS: u32 = 2;
+// Only element segments with a dropped bit can be used by `table.init` and `elem.drop`
+pub const MAX_DROPPABLE_ELEMENT_SEGMENTS: u32 = ELEMENT_DROPPED_FLAG_WORDS * 32;
 
 // Constants are 32-bits wide
 pub const CONSTANTS_LEN_BYTES: u32 = 4;
@@ -62,12 +98,27 @@ pub const TOTAL_INVOCATIONS_CONSTANT_INDEX: u32 = 0;
 // Strides in 4-byte words
 pub const MEMORY_STRIDE_WORDS: u32 = 4;
 
-// Alignment between single WASM value arguments when doing I/O in 4-byte words
-pub const IO_ARGUMENT_ALIGNMENT_WORDS: u32 = 1;
-// Alignment between sets of WASM value arguments fro each invocation when doing I/O in 4-byte words
-pub const IO_INVOCATION_ALIGNMENT_WORDS: u32 = 1;
-
+// The default for `SpirvOptions::lang_version`
 const LANG_VERSION: (u8, u8) = (1, 0);
+// Bounds check policies, the language version and workgroup memory initialisation are taken from the tuneables when
+// writing, see `AssembledModule::spirv_options`
+const SPV_OUT_OPTIONS: naga::back::spv::Options = naga::back::spv::Options {
+    lang_version: LANG_VERSION,
+    flags: naga::back::spv::WriterFlags::empty(),
+    binding_map: naga::back::spv::BindingMap::new(),
+    capabilities: None,
+    bounds_check_policies: naga::proc::BoundsCheckPolicies {
+        index: naga::proc::BoundsCheckPolicy::Unchecked,
+        buffer: naga::proc::BoundsCheckPolicy::Unchecked,
+        image_load: naga::proc::BoundsCheckPolicy::Unchecked,
+        image_store: naga::proc::BoundsCheckPolicy::Unchecked,
+        binding_array: naga::proc::BoundsCheckPolicy::Unchecked,
+    },
+    zero_initialize_workgroup_memory: naga::back::spv::ZeroInitializeWorkgroupMemoryMode::None,
+    debug_info: None,
+};
+// The shader model and workgroup memory initialisation are taken from the tuneables when writing, see
+// `AssembledModule::hlsl_options`
 const HLSL_OUT_OPTIONS: naga::back::hlsl::Options = naga::back::hlsl::Options {
     shader_model: naga::back::hlsl::ShaderModel::V6_0,
     binding_map: naga::back::hlsl::BindingMap::new(),
@@ -91,7 +142,10 @@ mod wasm_front;
 use std::error::Error;
 use std::fmt::Debug;
 
+pub use active_function::unlowered_proposal;
 pub use assembled_module::AssembledModule;
+pub use assembled_module::FunctionStat;
+pub use traps::trap_message;
 pub use traps::trap_to_u32;
 pub use traps::u32_to_trap;
 pub use wasm_front::DataIndex;
@@ -105,9 +159,11 @@ pub use wasm_front::GlobalImmutableIndex;
 pub use wasm_front::GlobalIndex;
 pub use wasm_front::GlobalMutableIndex;
 pub use wasm_front::MemoryIndex;
+pub use wasm_front::StableHasher;
 pub use wasm_front::TableIndex;
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize))]
 pub struct Tuneables {
     /// If this is true, each parallel instance is executed in its own environment
     /// and cannot see the values stored in the memories of its peers. If this is
@@ -118,9 +174,247 @@ pub struct Tuneables {
     /// Which extra things to do when performing floating point operations to ensure
     /// adherance to the specification
     pub fp_options: FloatingPointOptions,
+    /// Alignment between single WASM value arguments when doing I/O, in 4-byte words. Must be a power of two.
+    pub io_argument_alignment_words: u32,
+    /// Alignment between sets of WASM value arguments for each invocation when doing I/O, in 4-byte words.
+    /// Must be a power of two. Increase this to read results directly into aligned host structures. Has no effect
+    /// when `io_layout` is `IoLayout::StructOfArrays`.
+    pub io_invocation_alignment_words: u32,
+    /// How the arguments and results of every invocation in a call are arranged in the input and output buffers.
+    /// Defaults to `IoLayout::Packed`.
+    pub io_layout: IoLayout,
+    /// If this is true, the flags, constants, input, output and stack buffers are packed into a single
+    /// binding and addressed by offset, requiring `PACKED_BINDING_TUPLES.len()` storage buffers per shader
+    /// stage rather than `BINDING_TUPLES.len()`. Many mobile and WebGL adapters need this.
+    pub pack_io_bindings: bool,
+    /// If this is true, lane-wise `v128` operations are lowered to native vector instructions on a `vec4<u32>`,
+    /// rather than being polyfilled one 32-bit word at a time. Float lanes still use the polyfill when any of the
+    /// emulation options in `fp_options` would apply to them.
+    pub native_v128: bool,
+    /// GPUs can't block an invocation until it is notified, so `memory.atomic.wait32`, `memory.atomic.wait64` and
+    /// `memory.atomic.notify` are rejected by default. If this is true, they are instead accepted with relaxed
+    /// semantics: waits compare the value in memory and then return immediately as if they had timed out, and
+    /// notifies never wake any waiters.
+    pub permit_nonblocking_atomics: bool,
+    /// How accesses to linear memory are checked against the size of the memory. Defaults to
+    /// `BoundsCheckMode::Unchecked`, which is fastest but allows a module to read and write memory belonging to
+    /// other instances. Use a checked mode when running untrusted modules.
+    pub bounds_checks: BoundsCheckMode,
+    /// The number of invocations in each workgroup, used as the x component of the `@workgroup_size` of every
+    /// generated entry point. Must be non-zero, and must be within the device's `max_compute_workgroup_size_x`
+    /// and `max_compute_invocations_per_workgroup` limits. Defaults to `WORKGROUP_SIZE`.
+    pub workgroup_size: u32,
+    /// If this is true, functions that can't be reached from any entry point are removed from the generated module,
+    /// along with the types and constants that only they used. The standard objects for every wasm type are
+    /// generated whether or not a module uses them, so this greatly reduces the size of the shader for most modules.
+    /// Defaults to true.
+    pub prune_unused: bool,
+    /// If this is true, generated functions with identical bodies are merged into a single function, and calls to
+    /// the duplicates are redirected to it. Helpers and polyfills are often generated several times with the same
+    /// body, so this reduces the size of the shader for numeric-heavy modules. Defaults to true.
+    pub deduplicate_functions: bool,
+    /// If this is true, the bodies of wasm functions are generated on a thread pool rather than one after another,
+    /// which reduces the time taken to build modules with many functions. Has no effect unless the `parallel`
+    /// feature is enabled. The generated module behaves identically whether or not this is set. Defaults to true.
+    pub parallel_codegen: bool,
+    /// How many iterations of a loop run between each check of whether the invocation has trapped. Checking
+    /// requires a load from the trap state buffer, so larger intervals increase the throughput of tight loops, at
+    /// the cost of a trapped invocation running up to this many extra iterations before it notices. Must be
+    /// non-zero. Defaults to 1, checking on every iteration.
+    pub trap_check_interval: u32,
+    /// The environment and version of SPIR-V that shaders are written for by `AssembledModule::get_spirv_binary`.
+    /// Defaults to SPIR-V 1.0 for Vulkan 1.0.
+    pub spirv: SpirvOptions,
+    /// The shader model that HLSL is written for by `AssembledModule::generate_hlsl_source`. Newer shader models
+    /// are needed to use features such as wave intrinsics (6.0+) or 64-bit atomics (6.6+) when targeting DX12.
+    /// Defaults to `HlslShaderModel::V6_0`.
+    pub hlsl_shader_model: HlslShaderModel,
+    /// If this is true, the functions and locals generated for each wasm function are named after the names given
+    /// in the module's name section, so that the original identifiers appear in `generate_wgsl_source` and as
+    /// SPIR-V `OpName`s. Functions and locals without a name keep their generated names. Defaults to false.
+    pub preserve_names: bool,
+    /// If this is true, the statements generated for each wasm instruction are given a `naga::Span` covering the
+    /// bytes of the module's binary that the instruction was read from, so that GPU debuggers can step through a
+    /// dispatched shader by wasm offset. Instructions from modules without a binary, and statements added around the
+    /// instructions of a function, keep `naga::Span::UNDEFINED`. Defaults to false.
+    pub emit_debug_spans: bool,
+    /// If this is true, the HLSL and SPIR-V writers zero any workgroup-shared memory at the start of each entry
+    /// point, as some drivers leave it holding whatever was last written to it. SPIR-V uses a polyfill, which works
+    /// with every `SpirvOptions::lang_version`. The generated module doesn't currently declare any workgroup
+    /// memory, so this only has an effect on modules that do. Defaults to false.
+    pub zero_init_workgroup_memory: bool,
+    /// If set, the capabilities that the generated module is validated against, in place of those derived from
+    /// `fp_options`. Use this to forbid capabilities that a device lacks, or to allow ones that it has. The SPIR-V
+    /// writer only declares the capabilities that the validated module uses, so it is restricted by this too.
+    /// Modules that need a capability missing from this set fail to build with
+    /// `ValidationError::NagaValidationError`. Defaults to `None`.
+    pub capabilities: Option<naga::valid::Capabilities>,
+}
+
+/// How the values passed to and returned from invocations are arranged in I/O buffers, see `Tuneables::io_layout`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize))]
+pub enum IoLayout {
+    /// The values of each invocation are stored together, one invocation after another, each value aligned to
+    /// `io_argument_alignment_words` and each invocation aligned to `io_invocation_alignment_words`.
+    #[default]
+    Packed,
+    /// The first value of every invocation is stored, one invocation after another, followed by the second value
+    /// of every invocation, and so on. Each value is aligned to `io_argument_alignment_words`. Neighbouring
+    /// invocations read and write neighbouring words, which GPUs can coalesce into fewer memory transactions.
+    StructOfArrays,
+}
+
+impl IoLayout {
+    /// The number of words taken by the values of a single invocation, given the number of words taken by each of
+    /// its values once aligned to `io_argument_alignment_words`.
+    pub fn invocation_words(&self, value_words: u32, tuneables: &Tuneables) -> u32 {
+        match self {
+            Self::Packed => value_words.next_multiple_of(tuneables.io_invocation_alignment_words),
+            Self::StructOfArrays => value_words,
+        }
+    }
+}
+
+/// How out of bounds accesses to linear memory are handled, see `Tuneables::bounds_checks`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize))]
+pub enum BoundsCheckMode {
+    /// No checks are emitted for memory accesses, so out of bounds accesses give unspecified results. Shaders are
+    /// still created with the device's own runtime checks, which keep accesses within the bound buffers.
+    #[default]
+    Unchecked,
+    /// Out of bounds accesses trap with `MemoryOutOfBounds`, and are redirected to the start of the memory so
+    /// that they can't touch anything outside of the instance's own memory. Shader backends clamp indices into
+    /// buffers, as with `naga::proc::BoundsCheckPolicy::Restrict`.
+    Restrict,
+    /// Out of bounds accesses trap with `MemoryOutOfBounds`, out of bounds loads give zero and out of bounds
+    /// stores are skipped. Shader backends do the same for buffers, as with
+    /// `naga::proc::BoundsCheckPolicy::ReadZeroSkipWrite`.
+    ReadZeroSkipWrite,
+}
+
+impl BoundsCheckMode {
+    /// Whether any checks are emitted at all.
+    pub fn is_checked(&self) -> bool {
+        *self != Self::Unchecked
+    }
+
+    /// The policies that naga backends should use when writing shaders built with this mode.
+    pub fn policies(&self) -> naga::proc::BoundsCheckPolicies {
+        let policy = match self {
+            Self::Unchecked => naga::proc::BoundsCheckPolicy::Unchecked,
+            Self::Restrict => naga::proc::BoundsCheckPolicy::Restrict,
+            Self::ReadZeroSkipWrite => naga::proc::BoundsCheckPolicy::ReadZeroSkipWrite,
+        };
+        naga::proc::BoundsCheckPolicies {
+            index: policy,
+            buffer: policy,
+            image_load: policy,
+            image_store: policy,
+            binding_array: policy,
+        }
+    }
+}
+
+/// The Vulkan environment that written SPIR-V is consumed by, see `Tuneables::spirv`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize))]
+pub enum SpirvTargetEnv {
+    #[default]
+    Vulkan1_0,
+    Vulkan1_1,
+    /// Vulkan 1.1 with the `VK_KHR_spirv_1_4` extension
+    Vulkan1_1Spirv1_4,
+    Vulkan1_2,
+    Vulkan1_3,
+}
+
+impl SpirvTargetEnv {
+    /// The newest version of SPIR-V that the environment is required to accept.
+    pub fn max_lang_version(&self) -> (u8, u8) {
+        match self {
+            Self::Vulkan1_0 => (1, 0),
+            Self::Vulkan1_1 => (1, 3),
+            Self::Vulkan1_1Spirv1_4 => (1, 4),
+            Self::Vulkan1_2 => (1, 5),
+            Self::Vulkan1_3 => (1, 6),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize))]
+pub struct SpirvOptions {
+    /// The environment that the SPIR-V is run in, which limits the versions that can be used.
+    pub target_env: SpirvTargetEnv,
+    /// The `(major, minor)` version of SPIR-V to write. Newer versions allow some backends to use the subgroup
+    /// and atomic features that threads rely on more efficiently, but must be supported by `target_env`.
+    pub lang_version: (u8, u8),
+}
+
+impl Default for SpirvOptions {
+    fn default() -> Self {
+        Self {
+            target_env: SpirvTargetEnv::default(),
+            lang_version: LANG_VERSION,
+        }
+    }
+}
+
+impl SpirvOptions {
+    /// Checks that the version of SPIR-V requested is accepted by the target environment.
+    pub fn validate(&self) -> build::Result<()> {
+        let max_lang_version = self.target_env.max_lang_version();
+        if self.lang_version < (1, 0) || self.lang_version > max_lang_version {
+            return Err(BuildError::IncompatibleSpirvVersion {
+                lang_version: self.lang_version,
+                target_env: self.target_env,
+                max_lang_version,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// The HLSL shader model that written HLSL targets, see `Tuneables::hlsl_shader_model`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize))]
+pub enum HlslShaderModel {
+    V5_0,
+    V5_1,
+    #[default]
+    V6_0,
+    V6_1,
+    V6_2,
+    V6_3,
+    V6_4,
+    V6_5,
+    V6_6,
+    V6_7,
+}
+
+impl HlslShaderModel {
+    /// The equivalent shader model for the naga HLSL backend.
+    pub fn to_naga(&self) -> naga::back::hlsl::ShaderModel {
+        match self {
+            Self::V5_0 => naga::back::hlsl::ShaderModel::V5_0,
+            Self::V5_1 => naga::back::hlsl::ShaderModel::V5_1,
+            Self::V6_0 => naga::back::hlsl::ShaderModel::V6_0,
+            Self::V6_1 => naga::back::hlsl::ShaderModel::V6_1,
+            Self::V6_2 => naga::back::hlsl::ShaderModel::V6_2,
+            Self::V6_3 => naga::back::hlsl::ShaderModel::V6_3,
+            Self::V6_4 => naga::back::hlsl::ShaderModel::V6_4,
+            Self::V6_5 => naga::back::hlsl::ShaderModel::V6_5,
+            Self::V6_6 => naga::back::hlsl::ShaderModel::V6_6,
+            Self::V6_7 => naga::back::hlsl::ShaderModel::V6_7,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize))]
 pub struct FloatingPointOptions {
     /// Most GPUs support very fast 32-bit floating point operations, but only for some subset of 'normal' floats.
     /// WebAssembly requires SubNormals to be supported for an engine to be specification compliant. Only set this
@@ -137,6 +431,18 @@ pub struct FloatingPointOptions {
     /// to other options, this should only be set to `false` if you are sure that your GPU supports 64 bit floats,
     /// however incorrect setting of this flag will result in a crash, rather than incorrect behaviour.
     pub emulate_f64: bool,
+    /// GPUs may produce NaNs with any payload from arithmetic, whereas WebAssembly requires that arithmetic on
+    /// canonical NaNs produces a canonical NaN. If this is true, any NaN produced by addition, subtraction,
+    /// multiplication or division is replaced with the canonical NaN, `0x7fc00000` for f32 or `0x7ff8000000000000`
+    /// for f64, at the cost of an extra check after each f32 operation.
+    pub canonicalize_nans: bool,
+    /// WGSL only requires f32 addition, subtraction and multiplication to be rounded to one of the two nearest
+    /// values, and division to be within 2.5 ULP, whereas WebAssembly requires rounding to nearest with ties going to
+    /// even. If this is true, these operations are performed with integer arithmetic on the bits of their operands,
+    /// which gives the exact result that WebAssembly requires at a large cost to throughput. The integer arithmetic
+    /// handles subnormals and the full range of finite values itself, so `emulate_subnormals` and
+    /// `emulate_div_beyond_max` have no effect on these operations when this is set.
+    pub round_to_nearest_even: bool,
 }
 
 impl Default for Tuneables {
@@ -144,7 +450,77 @@ impl Default for Tuneables {
         Self {
             disjoint_memory: true,
             fp_options: FloatingPointOptions::default(),
+            io_argument_alignment_words: 1,
+            io_invocation_alignment_words: 1,
+            io_layout: IoLayout::default(),
+            pack_io_bindings: false,
+            native_v128: false,
+            permit_nonblocking_atomics: false,
+            bounds_checks: BoundsCheckMode::default(),
+            workgroup_size: WORKGROUP_SIZE,
+            prune_unused: true,
+            deduplicate_functions: true,
+            parallel_codegen: true,
+            trap_check_interval: 1,
+            spirv: SpirvOptions::default(),
+            hlsl_shader_model: HlslShaderModel::default(),
+            preserve_names: false,
+            emit_debug_spans: false,
+            zero_init_workgroup_memory: false,
+            capabilities: None,
+        }
+    }
+}
+
+impl Tuneables {
+    /// Tuneables under which f32 arithmetic gives results bit-for-bit identical to IEEE 754 binary32 arithmetic on a
+    /// CPU, for comparing GPU execution against a CPU reference.
+    ///
+    /// With these options, `f32.add`, `f32.sub`, `f32.mul` and `f32.div` are rounded to nearest with ties going to
+    /// even, including results that are subnormal or beyond `2^126`, and subnormal operands are never flushed to
+    /// zero. Every NaN produced by these operations is the canonical NaN `0x7fc00000`, which WebAssembly allows but
+    /// which may differ in sign or payload from the NaN a particular CPU produces. The sign-manipulating operations
+    /// `f32.neg`, `f32.abs` and `f32.copysign` only change the sign bit. Other f32 operations, such as `f32.sqrt`,
+    /// are not covered by this guarantee. All other tuneables take their default values.
+    pub fn deterministic_f32() -> Self {
+        Self {
+            fp_options: FloatingPointOptions {
+                emulate_subnormals: true,
+                emulate_div_beyond_max: true,
+                canonicalize_nans: true,
+                round_to_nearest_even: true,
+                ..FloatingPointOptions::default()
+            },
+            ..Self::default()
+        }
+    }
+
+    /// Checks that the values given are usable to generate a module.
+    pub fn validate(&self) -> build::Result<()> {
+        for (name, value) in [
+            ("io_argument_alignment_words", self.io_argument_alignment_words),
+            (
+                "io_invocation_alignment_words",
+                self.io_invocation_alignment_words,
+            ),
+        ] {
+            if !value.is_power_of_two() {
+                return Err(BuildError::InvalidTuneable { name, value });
+            }
         }
+
+        for (name, value) in [
+            ("workgroup_size", self.workgroup_size),
+            ("trap_check_interval", self.trap_check_interval),
+        ] {
+            if value == 0 {
+                return Err(BuildError::ZeroTuneable { name });
+            }
+        }
+
+        self.spirv.validate()?;
+
+        Ok(())
     }
 }
 
@@ -155,6 +531,8 @@ impl FloatingPointOptions {
             emulate_subnormals: false,
             emulate_div_beyond_max: false,
             emulate_f64: false,
+            canonicalize_nans: false,
+            round_to_nearest_even: false,
         }
     }
 }
@@ -165,6 +543,41 @@ impl Default for FloatingPointOptions {
             emulate_subnormals: true,
             emulate_div_beyond_max: true,
             emulate_f64: true,
+            canonicalize_nans: false,
+            round_to_nearest_even: false,
+        }
+    }
+}
+
+/// The location of each logical buffer, in 4-byte words, within the single buffer bound when
+/// `Tuneables::pack_io_bindings` is set. Constants come first so that they can be found without
+/// knowing the number of invocations, and the remaining regions are laid out in order after them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PackedIoLayout {
+    pub constants_offset_words: u32,
+    pub flags_offset_words: u32,
+    pub input_offset_words: u32,
+    pub output_offset_words: u32,
+    pub stack_offset_words: u32,
+    pub len_words: u32,
+}
+
+impl PackedIoLayout {
+    pub fn new(invocations_count: u32, input_words: u32, output_words: u32) -> Self {
+        let constants_offset_words = 0;
+        let flags_offset_words = constants_offset_words + CONSTANTS_LEN_BYTES / 4;
+        let input_offset_words = flags_offset_words + invocations_count * (FLAGS_LEN_BYTES / 4);
+        let output_offset_words = input_offset_words + invocations_count * input_words;
+        let stack_offset_words = output_offset_words + invocations_count * output_words;
+        let len_words = stack_offset_words + STACK_LEN_BYTES / 4;
+
+        Self {
+            constants_offset_words,
+            flags_offset_words,
+            input_offset_words,
+            output_offset_words,
+            stack_offset_words,
+            len_words,
         }
     }
 }
@@ -188,6 +601,24 @@ pub enum BuildError {
     BoundsExceeded(ExceededComponent),
     #[error("one of our validation checks didn't hold. This is a bug in the wasm-gpu-funcgen crate: {0:?}")]
     ValidationError(ValidationError),
+    #[error("tuneable {name} must be a power of two, but was {value}")]
+    InvalidTuneable { name: &'static str, value: u32 },
+    #[error("tuneable {name} must be non-zero")]
+    ZeroTuneable { name: &'static str },
+    #[error("spir-v version {lang_version:?} is not supported by {target_env:?}, which accepts versions from (1, 0) up to {max_lang_version:?}")]
+    IncompatibleSpirvVersion {
+        lang_version: (u8, u8),
+        target_env: SpirvTargetEnv,
+        max_lang_version: (u8, u8),
+    },
+    #[error("wasm used the {proposal} proposal, which is not supported")]
+    UnsupportedProposal { proposal: &'static str },
+    #[error("wasm used instructions from the {} proposals, which can't be lowered yet", .proposals.join(", "))]
+    UnloweredProposals { proposals: Vec<&'static str> },
+    #[error("wasm contained a recursive call to {callee:?}, which is not supported")]
+    UnsupportedRecursion { callee: crate::typed::FuncRef },
+    #[error("cached module was rejected: {reason}")]
+    InvalidCache { reason: String },
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -203,6 +634,10 @@ pub enum ValidationError {
         observed_buffer_type: naga::Type,
         required_buffer_type: naga::Type,
     },
+    #[error("naga failed to write the validated module as wgsl {0:?}")]
+    WgslWriterError(naga::back::wgsl::Error),
+    #[error("naga failed to write the validated module as spir-v {0:?}")]
+    SpvWriterError(naga::back::spv::Error),
 }
 
 #[derive(Clone)]
@@ -295,6 +730,10 @@ pub enum ExceededComponent {
     ParameterCount,
     #[error("memory operation argument offset size")]
     MemArgOffset,
+    #[error("number of data segments referenced by bulk memory operations")]
+    DataSegments,
+    #[error("number of element segments referenced by bulk table operations")]
+    ElementSegments,
 }
 
 pub(crate) mod build {
@@ -1,25 +1,31 @@
+mod atomics;
 mod bindings;
+mod bulk_memory;
 mod flags;
 mod wasm_tys;
 
 use std::marker::PhantomData;
 
 use crate::typed::Val;
-use naga_ext::{ConstantsExt, ExpressionsExt, TypesExt};
+use naga_ext::prelude::*;
 use wasmparser::ValType;
 
 use crate::{
-    build, FloatingPointOptions, Tuneables, CONSTANTS_LEN_BYTES, FLAGS_LEN_BYTES,
+    build, FloatingPointOptions, Tuneables, CONSTANTS_LEN_BYTES, DATA_DROPPED_FLAG_INDEX,
+    DATA_DROPPED_FLAG_WORDS, ELEMENT_DROPPED_FLAG_INDEX, ELEMENT_DROPPED_FLAG_WORDS,
+    FLAGS_LEN_BYTES, MEMORY_PAGES_FLAG_INDEX, MEMORY_STRIDE_WORDS,
     TOTAL_INVOCATIONS_CONSTANT_INDEX, TRAP_FLAG_INDEX,
 };
 
 use self::{
+    atomics::AtomicsInstance,
     bindings::StdBindings,
+    bulk_memory::BulkMemoryInstance,
     flags::TrapValuesInstance,
     wasm_tys::{
         native_f32::NativeF32, native_i32::NativeI32, pollyfill_extern_ref::PolyfillExternRef,
         pollyfill_func_ref::PolyfillFuncRef, polyfill_f64::PolyfillF64, polyfill_i64::PolyfillI64,
-        polyfill_v128::PolyfillV128,
+        native_v128::NativeV128, polyfill_v128::PolyfillV128,
     },
 };
 
@@ -171,21 +177,27 @@ impl GenWasmBool for WasmBoolInstance {
         module: &mut naga::Module,
         requirements: wasm_bool_instance_gen::ConstFalseRequirements,
     ) -> build::Result<wasm_bool_instance_gen::ConstFalse> {
-        let init = module.const_expressions.append_u32(0);
-        Ok(module.constants.append_anonymous(*requirements.ty, init))
+        Ok(module.constants.get_or_append_scalar(
+            &mut module.const_expressions,
+            *requirements.ty,
+            0u32,
+        ))
     }
 
     fn gen_const_true(
         module: &mut naga::Module,
         requirements: wasm_bool_instance_gen::ConstTrueRequirements,
     ) -> build::Result<wasm_bool_instance_gen::ConstTrue> {
-        let init = module.const_expressions.append_u32(1);
-        Ok(module.constants.append_anonymous(*requirements.ty, init))
+        Ok(module.constants.get_or_append_scalar(
+            &mut module.const_expressions,
+            *requirements.ty,
+            1u32,
+        ))
     }
 }
 
 generator_struct! {
-    pub(crate) struct PreambleObjects (fp_options: crate::FloatingPointOptions)
+    pub(crate) struct PreambleObjects (fp_options: crate::FloatingPointOptions, pack_io_bindings: bool, disjoint_memory: bool)
     {
         word_ty: naga::Handle<naga::Type>,
         word_max: |word_ty| naga::Handle<naga::Constant>, // Used for overflow calculations
@@ -197,31 +209,56 @@ generator_struct! {
         uvec3_ty: naga::Handle<naga::Type>,
 
         word_array_buffer_ty:   |word_ty| naga::Handle<naga::Type>,
+        memory_array_buffer_ty: |word_ty| naga::Handle<naga::Type>,
         constants_buffer_ty:    |word_ty| naga::Handle<naga::Type>,
         flags_ty:               |word_ty| naga::Handle<naga::Type>,
         flags_array_buffer_ty:  |flags_ty| naga::Handle<naga::Type>,
 
-        bindings: |constants_buffer_ty, word_array_buffer_ty, flags_array_buffer_ty| StdBindings,
+        bindings: |constants_buffer_ty, word_array_buffer_ty, memory_array_buffer_ty, flags_array_buffer_ty| StdBindings,
 
         trap_values: TrapValuesInstance,
         trap_state: |word_ty| naga::Handle<naga::GlobalVariable>,
+        memory_pages: |word_ty| naga::Handle<naga::GlobalVariable>,
+        data_dropped: |word_ty| naga::Handle<naga::GlobalVariable>,
+        element_dropped: |word_ty| naga::Handle<naga::GlobalVariable>,
 
         wasm_bool: WasmBoolInstance,
     } with trait PreambleObjectsGen;
 }
 
+impl PreambleObjects {
+    /// Takes a byte address in shared memory space and calculates the address in disjoint memory space. I.e. calculates
+    /// `(address / STRIDE) * STRIDE * invocations_count + STRIDE * instance_id + (address % STRIDE)`
+    pub(crate) fn disjoint_memory_address(
+        &self,
+        ctx: &mut BlockContext<'_>,
+        shared_address: naga::Handle<naga::Expression>,
+    ) -> naga::Handle<naga::Expression> {
+        let stride_bytes = naga_expr!(ctx => U32(MEMORY_STRIDE_WORDS * 4));
+
+        let invocations_count = naga_expr!(ctx => Load(Global(self.invocations_count)));
+        let instance_id = naga_expr!(ctx => Load(Global(self.instance_id)));
+
+        // Each instance's memory is interleaved with its peers' one stride at a time
+        naga_expr!(ctx => ((shared_address / stride_bytes) * (stride_bytes * invocations_count)) + (stride_bytes * instance_id) + (shared_address % stride_bytes))
+    }
+}
+
 generator_struct! {
-    pub(crate) struct StdObjects (fp_options: crate::FloatingPointOptions)
+    pub(crate) struct StdObjects (fp_options: crate::FloatingPointOptions, pack_io_bindings: bool, disjoint_memory: bool)
     {
         preamble: PreambleObjects,
 
         i32: |preamble| wasm_tys::I32Instance,
         i64: |preamble| wasm_tys::I64Instance,
-        f32: |preamble, i32| wasm_tys::F32Instance,
-        f64: |preamble| wasm_tys::F64Instance,
-        v128: |preamble| wasm_tys::V128Instance,
+        f32: |preamble, i32, i64| wasm_tys::F32Instance,
+        f64: |preamble, i32, i64, f32| wasm_tys::F64Instance,
+        v128: |preamble, f32| wasm_tys::V128Instance,
         func_ref: |preamble| wasm_tys::FuncRefInstance,
         extern_ref: |preamble| wasm_tys::ExternRefInstance,
+
+        bulk_memory: |preamble, i32| BulkMemoryInstance,
+        atomics: |preamble, i32, i64| AtomicsInstance,
     } with trait GenStdObjects;
 }
 
@@ -269,15 +306,9 @@ impl<Ps: GenerationParameters> PreambleObjectsGen for PreambleObjectsGenerator<P
         requirements: preamble_objects_gen::WordMaxRequirements,
     ) -> build::Result<preamble_objects_gen::WordMax> {
         let init = module.const_expressions.append_u32(u32::MAX);
-        Ok(module.constants.append(
-            naga::Constant {
-                name: Some("MAX_WORD".to_owned()),
-                r#override: naga::Override::None,
-                ty: *requirements.word_ty,
-                init,
-            },
-            naga::Span::UNDEFINED,
-        ))
+        Ok(module
+            .constants
+            .append_named("MAX_WORD", *requirements.word_ty, init))
     }
 
     fn gen_uvec3_ty(
@@ -315,6 +346,32 @@ impl<Ps: GenerationParameters> PreambleObjectsGen for PreambleObjectsGenerator<P
         Ok(word_array_ty)
     }
 
+    fn gen_memory_array_buffer_ty(
+        module: &mut naga::Module,
+        requirements: preamble_objects_gen::MemoryArrayBufferTyRequirements,
+    ) -> build::Result<preamble_objects_gen::MemoryArrayBufferTy> {
+        // Shared memory is accessed by every invocation at once, so atomic operations must be atomic on the GPU
+        let base = if *requirements.disjoint_memory {
+            *requirements.word_ty
+        } else {
+            module.types.insert_atomic_u32()
+        };
+
+        let memory_array_ty = module.types.insert(
+            naga::Type {
+                name: None,
+                inner: naga::TypeInner::Array {
+                    base,
+                    size: naga::ArraySize::Dynamic,
+                    stride: 4,
+                },
+            },
+            naga::Span::UNDEFINED,
+        );
+
+        Ok(memory_array_ty)
+    }
+
     fn gen_constants_buffer_ty(
         module: &mut naga::Module,
         requirements: preamble_objects_gen::ConstantsBufferTyRequirements,
@@ -343,12 +400,37 @@ impl<Ps: GenerationParameters> PreambleObjectsGen for PreambleObjectsGenerator<P
         module: &mut naga::Module,
         requirements: preamble_objects_gen::FlagsTyRequirements,
     ) -> build::Result<preamble_objects_gen::FlagsTy> {
-        let flag_members = vec![naga::StructMember {
-            name: Some("trap_flag".to_owned()),
-            ty: *requirements.word_ty,
-            binding: None,
-            offset: TRAP_FLAG_INDEX * 4,
-        }];
+        let mut flag_members = vec![
+            naga::StructMember {
+                name: Some("trap_flag".to_owned()),
+                ty: *requirements.word_ty,
+                binding: None,
+                offset: TRAP_FLAG_INDEX * 4,
+            },
+            naga::StructMember {
+                name: Some("memory_pages".to_owned()),
+                ty: *requirements.word_ty,
+                binding: None,
+                offset: MEMORY_PAGES_FLAG_INDEX * 4,
+            },
+        ];
+        // Each word is its own member, so that member indices and word indices agree
+        for i in 0..DATA_DROPPED_FLAG_WORDS {
+            flag_members.push(naga::StructMember {
+                name: Some(format!("data_dropped_{}", i)),
+                ty: *requirements.word_ty,
+                binding: None,
+                offset: (DATA_DROPPED_FLAG_INDEX + i) * 4,
+            });
+        }
+        for i in 0..ELEMENT_DROPPED_FLAG_WORDS {
+            flag_members.push(naga::StructMember {
+                name: Some(format!("element_dropped_{}", i)),
+                ty: *requirements.word_ty,
+                binding: None,
+                offset: (ELEMENT_DROPPED_FLAG_INDEX + i) * 4,
+            });
+        }
         let flags_ty = module.types.insert(
             naga::Type {
                 name: Some("wasm_flags".to_owned()),
@@ -390,7 +472,9 @@ impl<Ps: GenerationParameters> PreambleObjectsGen for PreambleObjectsGenerator<P
             module,
             *requirements.constants_buffer_ty,
             *requirements.word_array_buffer_ty,
+            *requirements.memory_array_buffer_ty,
             *requirements.flags_array_buffer_ty,
+            *requirements.pack_io_bindings,
         )
     }
 
@@ -408,15 +492,66 @@ impl<Ps: GenerationParameters> PreambleObjectsGen for PreambleObjectsGenerator<P
             .const_expressions
             .append_literal(naga::Literal::U32(0));
 
-        Ok(module.global_variables.append(
-            naga::GlobalVariable {
-                name: Some("trap_state".to_owned()),
-                space: naga::AddressSpace::Private,
-                binding: None,
-                ty: *requirements.word_ty,
-                init: Some(zero),
-            },
-            naga::Span::UNDEFINED,
+        Ok(module.global_variables.append_global_var(
+            "trap_state",
+            naga::AddressSpace::Private,
+            None,
+            *requirements.word_ty,
+            Some(zero),
+        ))
+    }
+    fn gen_memory_pages(
+        module: &mut naga::Module,
+        requirements: preamble_objects_gen::MemoryPagesRequirements,
+    ) -> build::Result<preamble_objects_gen::MemoryPages> {
+        Ok(module.global_variables.append_global_var(
+            "memory_pages",
+            naga::AddressSpace::Private,
+            None,
+            *requirements.word_ty,
+            None,
+        ))
+    }
+    fn gen_data_dropped(
+        module: &mut naga::Module,
+        requirements: preamble_objects_gen::DataDroppedRequirements,
+    ) -> build::Result<preamble_objects_gen::DataDropped> {
+        let data_dropped_ty = module.types.insert_anonymous(naga::TypeInner::Array {
+            base: *requirements.word_ty,
+            size: naga::ArraySize::Constant(
+                std::num::NonZeroU32::new(DATA_DROPPED_FLAG_WORDS)
+                    .expect("there is at least one data dropped flag"),
+            ),
+            stride: 4,
+        });
+
+        Ok(module.global_variables.append_global_var(
+            "data_dropped",
+            naga::AddressSpace::Private,
+            None,
+            data_dropped_ty,
+            None,
+        ))
+    }
+    fn gen_element_dropped(
+        module: &mut naga::Module,
+        requirements: preamble_objects_gen::ElementDroppedRequirements,
+    ) -> build::Result<preamble_objects_gen::ElementDropped> {
+        let element_dropped_ty = module.types.insert_anonymous(naga::TypeInner::Array {
+            base: *requirements.word_ty,
+            size: naga::ArraySize::Constant(
+                std::num::NonZeroU32::new(ELEMENT_DROPPED_FLAG_WORDS)
+                    .expect("there is at least one element dropped flag"),
+            ),
+            stride: 4,
+        });
+
+        Ok(module.global_variables.append_global_var(
+            "element_dropped",
+            naga::AddressSpace::Private,
+            None,
+            element_dropped_ty,
+            None,
         ))
     }
     fn gen_wasm_bool(
@@ -429,30 +564,24 @@ impl<Ps: GenerationParameters> PreambleObjectsGen for PreambleObjectsGenerator<P
         module: &mut naga::Module,
         requirements: preamble_objects_gen::InstanceIdRequirements,
     ) -> build::Result<preamble_objects_gen::InstanceId> {
-        Ok(module.global_variables.append(
-            naga::GlobalVariable {
-                name: Some("invocation_id".to_owned()),
-                space: naga::AddressSpace::Private,
-                binding: None,
-                ty: *requirements.word_ty,
-                init: None,
-            },
-            naga::Span::UNDEFINED,
+        Ok(module.global_variables.append_global_var(
+            "invocation_id",
+            naga::AddressSpace::Private,
+            None,
+            *requirements.word_ty,
+            None,
         ))
     }
     fn gen_invocations_count(
         module: &mut naga::Module,
         requirements: preamble_objects_gen::InvocationsCountRequirements,
     ) -> build::Result<preamble_objects_gen::InvocationsCount> {
-        Ok(module.global_variables.append(
-            naga::GlobalVariable {
-                name: Some("invocations_count".to_owned()),
-                space: naga::AddressSpace::Private,
-                binding: None,
-                ty: *requirements.word_ty,
-                init: None,
-            },
-            naga::Span::UNDEFINED,
+        Ok(module.global_variables.append_global_var(
+            "invocations_count",
+            naga::AddressSpace::Private,
+            None,
+            *requirements.word_ty,
+            None,
         ))
     }
 
@@ -473,6 +602,8 @@ impl<Ps: GenerationParameters> GenStdObjects for StdObjectsGenerator<Ps> {
         std_objects_gen::Preamble::gen_from::<PreambleObjectsGenerator<Ps>>(
             module,
             requirements.fp_options,
+            requirements.pack_io_bindings,
+            requirements.disjoint_memory,
         )
     }
 
@@ -487,12 +618,60 @@ impl<Ps: GenerationParameters> GenStdObjects for StdObjectsGenerator<Ps> {
             requirements.preamble,
             requirements.fp_options,
             &requirements.i32.ty,
+            &requirements.i64.ty,
+        )
+    }
+    fn gen_f64(
+        module: &mut naga::Module,
+        requirements: std_objects_gen::F64Requirements,
+    ) -> build::Result<std_objects_gen::F64> {
+        std_objects_gen::F64::gen_from::<Ps::F64>(
+            module,
+            requirements.preamble,
+            requirements.fp_options,
+            &requirements.i32.ty,
+            &requirements.i64.ty,
+            &requirements.f32.ty,
+        )
+    }
+    fn gen_v128(
+        module: &mut naga::Module,
+        requirements: std_objects_gen::V128Requirements,
+    ) -> build::Result<std_objects_gen::V128> {
+        std_objects_gen::V128::gen_from::<Ps::V128>(
+            module,
+            requirements.preamble,
+            requirements.fp_options,
+            requirements.f32,
         )
     }
-    impl_gen_wasm! {f64}
-    impl_gen_wasm! {v128}
     impl_gen_wasm! {func_ref}
     impl_gen_wasm! {extern_ref}
+
+    fn gen_bulk_memory(
+        module: &mut naga::Module,
+        requirements: std_objects_gen::BulkMemoryRequirements,
+    ) -> build::Result<std_objects_gen::BulkMemory> {
+        std_objects_gen::BulkMemory::gen_from::<BulkMemoryInstance>(
+            module,
+            requirements.preamble,
+            requirements.i32,
+            requirements.disjoint_memory,
+        )
+    }
+
+    fn gen_atomics(
+        module: &mut naga::Module,
+        requirements: std_objects_gen::AtomicsRequirements,
+    ) -> build::Result<std_objects_gen::Atomics> {
+        std_objects_gen::Atomics::gen_from::<AtomicsInstance>(
+            module,
+            requirements.preamble,
+            requirements.i32,
+            requirements.i64,
+            requirements.disjoint_memory,
+        )
+    }
 }
 
 macro_rules! extract_type_field {
@@ -516,8 +695,15 @@ impl StdObjects {
     pub(crate) fn new<Ps: GenerationParameters>(
         module: &mut naga::Module,
         fp_options: &FloatingPointOptions,
+        pack_io_bindings: bool,
+        disjoint_memory: bool,
     ) -> build::Result<Self> {
-        StdObjects::gen_from::<StdObjectsGenerator<Ps>>(module, fp_options)
+        StdObjects::gen_from::<StdObjectsGenerator<Ps>>(
+            module,
+            fp_options,
+            &pack_io_bindings,
+            &disjoint_memory,
+        )
     }
 
     pub(crate) fn from_tuneables(
@@ -525,7 +711,21 @@ impl StdObjects {
         tuneables: &Tuneables,
     ) -> build::Result<StdObjects> {
         // TODO: Support native f64 and i64
-        StdObjects::new::<FullPolyfill>(module, &tuneables.fp_options)
+        if tuneables.native_v128 {
+            StdObjects::new::<NativeVectors>(
+                module,
+                &tuneables.fp_options,
+                tuneables.pack_io_bindings,
+                tuneables.disjoint_memory,
+            )
+        } else {
+            StdObjects::new::<FullPolyfill>(
+                module,
+                &tuneables.fp_options,
+                tuneables.pack_io_bindings,
+                tuneables.disjoint_memory,
+            )
+        }
     }
 
     /// Get's a WASM val type's naga type
@@ -587,3 +787,15 @@ impl GenerationParameters for FullPolyfill {
     type FuncRef = PolyfillFuncRef;
     type ExternRef = PolyfillExternRef;
 }
+
+/// As with [`FullPolyfill`], but with vector operations lowered to native vector instructions
+pub(crate) struct NativeVectors;
+impl GenerationParameters for NativeVectors {
+    type I32 = NativeI32;
+    type I64 = PolyfillI64;
+    type F32 = NativeF32;
+    type F64 = PolyfillF64;
+    type V128 = NativeV128;
+    type FuncRef = PolyfillFuncRef;
+    type ExternRef = PolyfillExternRef;
+}
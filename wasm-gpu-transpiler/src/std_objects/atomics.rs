@@ -0,0 +1,338 @@
+use naga_ext::prelude::*;
+use wasmtime_environ::Trap;
+
+use crate::build;
+
+use super::{bulk_memory::memory_address, generator_struct, PreambleObjects};
+
+generator_struct! {
+    pub(crate) struct AtomicsInstance (
+        preamble: crate::std_objects::PreambleObjects,
+        i32: crate::std_objects::wasm_tys::I32Instance,
+        i64: crate::std_objects::wasm_tys::I64Instance,
+        disjoint_memory: bool,
+    )
+    {
+        i32_rmw_add: naga::Handle<naga::Function>,
+        i32_rmw_sub: naga::Handle<naga::Function>,
+        i32_rmw_and: naga::Handle<naga::Function>,
+        i32_rmw_or: naga::Handle<naga::Function>,
+        i32_rmw_xor: naga::Handle<naga::Function>,
+        i32_rmw_xchg: naga::Handle<naga::Function>,
+        i32_rmw_cmpxchg: naga::Handle<naga::Function>,
+
+        wait32: naga::Handle<naga::Function>,
+        wait64: naga::Handle<naga::Function>,
+        notify: naga::Handle<naga::Function>,
+    } with trait GenAtomics;
+}
+
+/// The return value of a wait when the value in memory isn't the expected value
+const WAIT_NOT_EQUAL: i32 = 1;
+/// The return value of a wait when no notify arrives before the timeout
+const WAIT_TIMED_OUT: i32 = 2;
+
+/// Gives a pointer to the word of memory at a shared byte address
+fn word_ptr(
+    ctx: &mut BlockContext<'_>,
+    preamble: &PreambleObjects,
+    disjoint_memory: bool,
+    address: naga::Handle<naga::Expression>,
+) -> naga::Handle<naga::Expression> {
+    let address = memory_address(ctx, preamble, disjoint_memory, address);
+    let memory = preamble.bindings.memory;
+    naga_expr!(ctx => Global(memory)[address >> U32(2)])
+}
+
+/// Emits the checks shared by all atomic accesses, returning early if we have already trapped or if the address
+/// isn't aligned to `size_bytes`, and otherwise gives a pointer to the first accessed word of memory
+fn emit_word_ptr(
+    ctx: &mut BlockContext<'_>,
+    preamble: &PreambleObjects,
+    i32: &crate::std_objects::wasm_tys::I32Instance,
+    disjoint_memory: bool,
+    address: naga::Handle<naga::Expression>,
+    size_bytes: u32,
+) -> naga::Handle<naga::Expression> {
+    // If we have trapped, memory must not be modified
+    let default = naga_expr!(ctx => Constant(i32.default));
+    let trap_state = preamble.trap_state;
+    let is_trapped = naga_expr!(ctx => Load(Global(trap_state)) != U32(0));
+    ctx.test(is_trapped).then(|ctx| {
+        ctx.result(default);
+    });
+
+    // Atomic accesses must be naturally aligned
+    let is_misaligned = naga_expr!(ctx => (address & U32(size_bytes - 1)) != U32(0));
+    ctx.test(is_misaligned).then(|mut ctx| {
+        preamble
+            .trap_values
+            .emit_set_trap(&mut ctx, Trap::HeapMisaligned, preamble.trap_state);
+        ctx.result(default);
+    });
+
+    word_ptr(ctx, preamble, disjoint_memory, address)
+}
+
+/// Declares `fn(memory: u32, address: u32, value: i32) -> i32`, which atomically applies `fun` to the aligned word at
+/// `address` and returns the word's previous value.
+///
+/// Disjoint memory is only ever accessed by a single invocation, so is modified with a plain load and store. Shared
+/// memory is bound as an array of atomics, so is modified with an atomic statement.
+fn gen_i32_rmw(
+    module: &mut naga::Module,
+    preamble: &PreambleObjects,
+    i32: &crate::std_objects::wasm_tys::I32Instance,
+    disjoint_memory: bool,
+    name: &str,
+    fun: naga::AtomicFunction,
+) -> build::Result<naga::Handle<naga::Function>> {
+    let word_ty = preamble.word_ty;
+    let (function_handle, memory, address, value) = declare_function! {
+        module => fn {format!("i32_atomic_rmw_{}", name)}(memory: word_ty, address: word_ty, value: i32.ty) -> i32.ty
+    };
+    let u32_ty = module.types.insert_u32();
+    let mut ctx = BlockContext::from((module, function_handle));
+
+    // TODO: Support other memories
+    drop(memory);
+
+    let word_ptr = emit_word_ptr(&mut ctx, preamble, i32, disjoint_memory, address, 4);
+    let value = naga_expr!(&mut ctx => bitcast<u32>(value));
+
+    if disjoint_memory {
+        let previous = naga_expr!(&mut ctx => Load(word_ptr));
+        let new_value = match fun {
+            naga::AtomicFunction::Add => naga_expr!(&mut ctx => previous + value),
+            naga::AtomicFunction::Subtract => naga_expr!(&mut ctx => previous - value),
+            naga::AtomicFunction::And => naga_expr!(&mut ctx => previous & value),
+            naga::AtomicFunction::InclusiveOr => naga_expr!(&mut ctx => previous | value),
+            naga::AtomicFunction::ExclusiveOr => naga_expr!(&mut ctx => previous ^ value),
+            naga::AtomicFunction::Exchange { compare: None } => value,
+            _ => unreachable!("only read-modify-write functions are generated"),
+        };
+        ctx.store(word_ptr, new_value);
+
+        let res = naga_expr!(&mut ctx => bitcast<i32>(previous));
+        ctx.result(res);
+    } else {
+        let previous = ctx.append_expr(naga::Expression::AtomicResult {
+            ty: u32_ty,
+            comparison: false,
+        });
+        ctx.block.push(
+            naga::Statement::Atomic {
+                pointer: word_ptr,
+                fun,
+                value,
+                result: previous,
+            },
+            naga::Span::UNDEFINED,
+        );
+
+        let res = naga_expr!(&mut ctx => bitcast<i32>(previous));
+        ctx.result(res);
+    }
+
+    Ok(function_handle)
+}
+
+macro_rules! impl_i32_rmw {
+    ($($op_name:ident => $fun:expr),* $(,)?) => {
+        paste::paste! {
+            $(
+                // fn(memory: u32, address: u32, value: i32) -> i32
+                fn [< gen_i32_rmw_ $op_name >](
+                    module: &mut naga::Module,
+                    requirements: atomics_instance_gen::[< I32Rmw $op_name:camel Requirements >],
+                ) -> build::Result<atomics_instance_gen::[< I32Rmw $op_name:camel >]> {
+                    gen_i32_rmw(
+                        module,
+                        requirements.preamble,
+                        requirements.i32,
+                        *requirements.disjoint_memory,
+                        stringify!($op_name),
+                        $fun,
+                    )
+                }
+            )*
+        }
+    };
+}
+
+impl GenAtomics for AtomicsInstance {
+    // fn(memory: u32, address: u32, expected: i32, replacement: i32) -> i32
+    fn gen_i32_rmw_cmpxchg(
+        module: &mut naga::Module,
+        requirements: atomics_instance_gen::I32RmwCmpxchgRequirements,
+    ) -> build::Result<atomics_instance_gen::I32RmwCmpxchg> {
+        let preamble = requirements.preamble;
+        let i32 = requirements.i32;
+        let disjoint_memory = *requirements.disjoint_memory;
+
+        let word_ty = preamble.word_ty;
+        let (function_handle, memory, address, expected, replacement) = declare_function! {
+            module => fn i32_atomic_rmw_cmpxchg(memory: word_ty, address: word_ty, expected: i32.ty, replacement: i32.ty) -> i32.ty
+        };
+        let result_ty = module.generate_predeclared_type(
+            naga::PredeclaredType::AtomicCompareExchangeWeakResult(naga::Scalar::U32),
+        );
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        // TODO: Support other memories
+        drop(memory);
+
+        let word_ptr = emit_word_ptr(&mut ctx, preamble, i32, disjoint_memory, address, 4);
+        let expected = naga_expr!(&mut ctx => bitcast<u32>(expected));
+        let replacement = naga_expr!(&mut ctx => bitcast<u32>(replacement));
+
+        if disjoint_memory {
+            let previous = naga_expr!(&mut ctx => Load(word_ptr));
+            let is_expected = naga_expr!(&mut ctx => previous == expected);
+            ctx.test(is_expected).then(|mut ctx| {
+                ctx.store(word_ptr, replacement);
+            });
+
+            let res = naga_expr!(&mut ctx => bitcast<i32>(previous));
+            ctx.result(res);
+        } else {
+            // naga only exposes a weak compare-exchange, which may fail even if the word held the expected value, but
+            // wasm requires a strong one. So retry until we either exchange or see an unexpected value.
+            ctx.cycle(|mut ctx| {
+                let result = ctx.append_expr(naga::Expression::AtomicResult {
+                    ty: result_ty,
+                    comparison: true,
+                });
+                ctx.block.push(
+                    naga::Statement::Atomic {
+                        pointer: word_ptr,
+                        fun: naga::AtomicFunction::Exchange {
+                            compare: Some(expected),
+                        },
+                        value: replacement,
+                        result,
+                    },
+                    naga::Span::UNDEFINED,
+                );
+
+                let previous = naga_expr!(&mut ctx => result[const 0]);
+                let exchanged = naga_expr!(&mut ctx => result[const 1]);
+                let is_done = naga_expr!(&mut ctx => exchanged | (previous != expected));
+                ctx.test(is_done).then(|mut ctx| {
+                    let res = naga_expr!(&mut ctx => bitcast<i32>(previous));
+                    ctx.result(res);
+                });
+            });
+        }
+
+        Ok(function_handle)
+    }
+
+    impl_i32_rmw! {
+        add => naga::AtomicFunction::Add,
+        sub => naga::AtomicFunction::Subtract,
+        and => naga::AtomicFunction::And,
+        or => naga::AtomicFunction::InclusiveOr,
+        xor => naga::AtomicFunction::ExclusiveOr,
+        xchg => naga::AtomicFunction::Exchange { compare: None },
+    }
+
+    // fn(memory: u32, address: u32, expected: i32, timeout: i64) -> i32
+    fn gen_wait32(
+        module: &mut naga::Module,
+        requirements: atomics_instance_gen::Wait32Requirements,
+    ) -> build::Result<atomics_instance_gen::Wait32> {
+        let preamble = requirements.preamble;
+        let i32 = requirements.i32;
+
+        let word_ty = preamble.word_ty;
+        let (function_handle, memory, address, expected, _timeout) = declare_function! {
+            module => fn memory_atomic_wait32(memory: word_ty, address: word_ty, expected: i32.ty, timeout: requirements.i64.ty) -> i32.ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        // TODO: Support other memories
+        drop(memory);
+
+        // We never block, so every wait with the expected value times out immediately
+        let word_ptr = emit_word_ptr(
+            &mut ctx,
+            preamble,
+            i32,
+            *requirements.disjoint_memory,
+            address,
+            4,
+        );
+        let is_equal = naga_expr!(&mut ctx => Load(word_ptr) == bitcast<u32>(expected));
+        let res =
+            naga_expr!(&mut ctx => if (is_equal) {I32(WAIT_TIMED_OUT)} else {I32(WAIT_NOT_EQUAL)});
+        ctx.result(res);
+
+        Ok(function_handle)
+    }
+
+    // fn(memory: u32, address: u32, expected: i64, timeout: i64) -> i32
+    fn gen_wait64(
+        module: &mut naga::Module,
+        requirements: atomics_instance_gen::Wait64Requirements,
+    ) -> build::Result<atomics_instance_gen::Wait64> {
+        let preamble = requirements.preamble;
+        let i32 = requirements.i32;
+        let i64_ty = requirements.i64.ty;
+        let disjoint_memory = *requirements.disjoint_memory;
+
+        let word_ty = preamble.word_ty;
+        let (function_handle, memory, address, expected, _timeout) = declare_function! {
+            module => fn memory_atomic_wait64(memory: word_ty, address: word_ty, expected: i64_ty, timeout: i64_ty) -> i32.ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        // TODO: Support other memories
+        drop(memory);
+
+        // We never block, so every wait with the expected value times out immediately.
+        // Words are stored in little-endian order, matching memory
+        let low_ptr = emit_word_ptr(&mut ctx, preamble, i32, disjoint_memory, address, 8);
+        let high_address = naga_expr!(&mut ctx => address + U32(4));
+        let high_ptr = word_ptr(&mut ctx, preamble, disjoint_memory, high_address);
+
+        let is_equal = naga_expr!(&mut ctx => (Load(low_ptr) == expected[const 0]) & (Load(high_ptr) == expected[const 1]));
+        let res =
+            naga_expr!(&mut ctx => if (is_equal) {I32(WAIT_TIMED_OUT)} else {I32(WAIT_NOT_EQUAL)});
+        ctx.result(res);
+
+        Ok(function_handle)
+    }
+
+    // fn(memory: u32, address: u32, count: i32) -> i32
+    fn gen_notify(
+        module: &mut naga::Module,
+        requirements: atomics_instance_gen::NotifyRequirements,
+    ) -> build::Result<atomics_instance_gen::Notify> {
+        let preamble = requirements.preamble;
+        let i32 = requirements.i32;
+
+        let word_ty = preamble.word_ty;
+        let (function_handle, memory, address, _count) = declare_function! {
+            module => fn memory_atomic_notify(memory: word_ty, address: word_ty, count: i32.ty) -> i32.ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        // TODO: Support other memories
+        drop(memory);
+
+        // Nothing ever waits, so there is never anything to wake. Notifies still trap on misaligned addresses though
+        emit_word_ptr(
+            &mut ctx,
+            preamble,
+            i32,
+            *requirements.disjoint_memory,
+            address,
+            4,
+        );
+        let res = naga_expr!(&mut ctx => I32(0));
+        ctx.result(res);
+
+        Ok(function_handle)
+    }
+}
@@ -1,4 +1,5 @@
 use crate::build;
+use naga_ext::GlobalsExt;
 
 use super::preamble_objects_gen;
 
@@ -12,35 +13,36 @@ fn access(read_only: bool) -> naga::StorageAccess {
 
 fn make_word_binding(
     module: &mut naga::Module,
-    word_array_ty: preamble_objects_gen::WordArrayBufferTy,
+    word_array_ty: naga::Handle<naga::Type>,
     name: &str,
     read_only: bool,
     binding: u32,
 ) -> build::Result<naga::Handle<naga::GlobalVariable>> {
-    Ok(module.global_variables.append(
-        naga::GlobalVariable {
-            name: Some(name.to_owned()),
-            space: naga::AddressSpace::Storage {
-                access: access(read_only),
-            },
-            binding: Some(naga::ResourceBinding { group: 0, binding }),
-            ty: word_array_ty,
-            init: None,
+    Ok(module.global_variables.append_global_var(
+        name,
+        naga::AddressSpace::Storage {
+            access: access(read_only),
         },
-        naga::Span::UNDEFINED,
+        Some(naga::ResourceBinding { group: 0, binding }),
+        word_array_ty,
+        None,
     ))
 }
 
 macro_rules! word_bindings {
-    (struct $gen_struct_name:ident { flags, constants, $($name:ident),* $(,)? }) => {
+    (struct $gen_struct_name:ident { flags, constants, memory, $($name:ident),* $(,)? } packed { $($packed_name:ident),* $(,)? }) => {
         paste::paste!{
             #[perfect_derive::perfect_derive(Copy, Clone)]
             pub(crate) struct $gen_struct_name {
                 pub(crate) flags: naga::Handle<naga::GlobalVariable>,
                 pub(crate) constants: naga::Handle<naga::GlobalVariable>,
+                pub(crate) memory: naga::Handle<naga::GlobalVariable>,
                 $(
                     pub(crate) $name: naga::Handle<naga::GlobalVariable>,
                 )*
+                $(
+                    pub(crate) $packed_name: naga::Handle<naga::GlobalVariable>,
+                )*
             }
 
             impl $gen_struct_name {
@@ -48,38 +50,17 @@ macro_rules! word_bindings {
                     module: &mut naga::Module,
                     constants_ty: preamble_objects_gen::WordArrayBufferTy,
                     word_array_ty: preamble_objects_gen::WordArrayBufferTy,
+                    memory_array_ty: preamble_objects_gen::MemoryArrayBufferTy,
                     flags_array_ty: preamble_objects_gen::FlagsArrayBufferTy,
+                    pack_io_bindings: bool,
                 ) -> crate::build::Result<Self> {
-                    let flags = module.global_variables.append(
-                        naga::GlobalVariable {
-                            name: Some("wasm_exec_flags".to_owned()),
-                            space: naga::AddressSpace::Storage {
-                                access: access(crate::FLAGS_BINDING_READ_ONLY),
-                            },
-                            binding: Some(naga::ResourceBinding {
-                                group: 0,
-                                binding: crate::FLAGS_BINDING_INDEX,
-                            }),
-                            ty: flags_array_ty,
-                            init: None,
-                        },
-                        naga::Span::UNDEFINED,
-                    );
-                    let constants = module.global_variables.append(
-                        naga::GlobalVariable {
-                            name: Some("wasm_exec_constants".to_owned()),
-                            space: naga::AddressSpace::Storage {
-                                access: access(crate::CONSTANTS_BINDING_READ_ONLY),
-                            },
-                            binding: Some(naga::ResourceBinding {
-                                group: 0,
-                                binding: crate::CONSTANTS_BINDING_INDEX,
-                            }),
-                            ty: constants_ty,
-                            init: None,
-                        },
-                        naga::Span::UNDEFINED,
-                    );
+                    let memory = make_word_binding(
+                        module,
+                        memory_array_ty,
+                        "wasm_memory",
+                        crate::MEMORY_BINDING_READ_ONLY,
+                        crate::MEMORY_BINDING_INDEX,
+                    )?;
                     $(
                         let $name = make_word_binding(
                             module,
@@ -90,10 +71,65 @@ macro_rules! word_bindings {
                         )?;
                     )*
 
+                    if pack_io_bindings {
+                        // Every per-dispatch buffer is the same binding, addressed by offset
+                        let packed = make_word_binding(
+                            module,
+                            word_array_ty,
+                            "wasm_packed_io",
+                            crate::PACKED_IO_BINDING_READ_ONLY,
+                            crate::PACKED_IO_BINDING_INDEX,
+                        )?;
+
+                        return Ok(Self {
+                            flags: packed,
+                            constants: packed,
+                            memory,
+                            $($name,)*
+                            $($packed_name: packed,)*
+                        });
+                    }
+
+                    let flags = module.global_variables.append_global_var(
+                        "wasm_exec_flags",
+                        naga::AddressSpace::Storage {
+                            access: access(crate::FLAGS_BINDING_READ_ONLY),
+                        },
+                        Some(naga::ResourceBinding {
+                            group: 0,
+                            binding: crate::FLAGS_BINDING_INDEX,
+                        }),
+                        flags_array_ty,
+                        None,
+                    );
+                    let constants = module.global_variables.append_global_var(
+                        "wasm_exec_constants",
+                        naga::AddressSpace::Storage {
+                            access: access(crate::CONSTANTS_BINDING_READ_ONLY),
+                        },
+                        Some(naga::ResourceBinding {
+                            group: 0,
+                            binding: crate::CONSTANTS_BINDING_INDEX,
+                        }),
+                        constants_ty,
+                        None,
+                    );
+                    $(
+                        let $packed_name = make_word_binding(
+                            module,
+                            word_array_ty,
+                            concat!("wasm_", stringify!($packed_name)),
+                            crate::[< $packed_name:upper _BINDING_READ_ONLY >],
+                            crate::[< $packed_name:upper _BINDING_INDEX >],
+                        )?;
+                    )*
+
                     Ok(Self {
                         flags,
                         constants,
-                        $($name),*
+                        memory,
+                        $($name,)*
+                        $($packed_name,)*
                     })
                 }
             }
@@ -103,6 +139,8 @@ macro_rules! word_bindings {
 
 word_bindings! {
     struct StdBindings {
-        flags, constants, memory, mutable_globals, immutable_globals, tables, data, elements, input, output, stack
+        flags, constants, memory, mutable_globals, immutable_globals, tables, data, elements
+    } packed {
+        input, output, stack
     }
 }
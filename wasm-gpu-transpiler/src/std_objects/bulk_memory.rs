@@ -0,0 +1,267 @@
+use naga_ext::prelude::*;
+use wasmtime_environ::Trap;
+
+use crate::build;
+
+use super::{generator_struct, PreambleObjects};
+
+/// The number of bytes in a wasm page
+const PAGE_SIZE_BYTES: u32 = 65536;
+
+generator_struct! {
+    pub(crate) struct BulkMemoryInstance (
+        preamble: crate::std_objects::PreambleObjects,
+        i32: crate::std_objects::wasm_tys::I32Instance,
+        disjoint_memory: bool,
+    )
+    {
+        read_byte: naga::Handle<naga::Function>,
+        write_byte: naga::Handle<naga::Function>,
+        read_data_byte: naga::Handle<naga::Function>,
+
+        fill: |write_byte| naga::Handle<naga::Function>,
+        copy: |read_byte, write_byte| naga::Handle<naga::Function>,
+        init: |read_data_byte, write_byte| naga::Handle<naga::Function>,
+    } with trait GenBulkMemory;
+}
+
+/// Bulk memory operations act on a shared memory address, but the memory buffer may be laid out disjointly
+pub(super) fn memory_address(
+    ctx: &mut BlockContext<'_>,
+    preamble: &PreambleObjects,
+    disjoint_memory: bool,
+    shared_address: naga::Handle<naga::Expression>,
+) -> naga::Handle<naga::Expression> {
+    if disjoint_memory {
+        preamble.disjoint_memory_address(ctx, shared_address)
+    } else {
+        shared_address
+    }
+}
+
+/// The current size of the memory, in bytes
+fn memory_bytes(
+    ctx: &mut BlockContext<'_>,
+    preamble: &PreambleObjects,
+) -> naga::Handle<naga::Expression> {
+    let memory_pages = preamble.memory_pages;
+    naga_expr!(ctx => Load(Global(memory_pages)) * U32(PAGE_SIZE_BYTES))
+}
+
+/// Bulk memory operations check the whole range before modifying anything, so that a trapping operation has no effect
+fn emit_bounds_check(
+    ctx: &mut BlockContext<'_>,
+    preamble: &PreambleObjects,
+    address: naga::Handle<naga::Expression>,
+    length: naga::Handle<naga::Expression>,
+    size_bytes: naga::Handle<naga::Expression>,
+) {
+    let is_out_of_bounds =
+        naga_expr!(ctx => (length > size_bytes) | (address > (size_bytes - length)));
+    ctx.test(is_out_of_bounds).then(|mut ctx| {
+        preamble
+            .trap_values
+            .emit_set_trap(&mut ctx, Trap::MemoryOutOfBounds, preamble.trap_state);
+        ctx.void_return();
+    });
+}
+
+/// If we have trapped, memory must not be modified
+fn emit_return_if_trapped(ctx: &mut BlockContext<'_>, preamble: &PreambleObjects) {
+    let trap_state = preamble.trap_state;
+    let is_trapped = naga_expr!(ctx => Load(Global(trap_state)) != U32(0));
+    ctx.test(is_trapped).then(|ctx| {
+        ctx.void_return();
+    });
+}
+
+impl GenBulkMemory for BulkMemoryInstance {
+    // fn(address: u32) -> u32
+    fn gen_read_byte(
+        module: &mut naga::Module,
+        requirements: bulk_memory_instance_gen::ReadByteRequirements,
+    ) -> build::Result<bulk_memory_instance_gen::ReadByte> {
+        let word_ty = requirements.preamble.word_ty;
+        let (function_handle, address) = declare_function! {
+            module => fn read_byte_from_memory(address: word_ty) -> word_ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        let address = memory_address(
+            &mut ctx,
+            requirements.preamble,
+            *requirements.disjoint_memory,
+            address,
+        );
+
+        let word_address = naga_expr!(&mut ctx => address >> U32(2));
+        let word = ctx.call_get_return(requirements.i32.read_memory, vec![word_address]);
+        let shift = naga_expr!(&mut ctx => (address & U32(3)) * U32(8));
+        let byte = naga_expr!(&mut ctx => (bitcast<u32>(word) >> shift) & U32(0xFF));
+        ctx.result(byte);
+
+        Ok(function_handle)
+    }
+
+    // fn(address: u32, value: u32)
+    fn gen_write_byte(
+        module: &mut naga::Module,
+        requirements: bulk_memory_instance_gen::WriteByteRequirements,
+    ) -> build::Result<bulk_memory_instance_gen::WriteByte> {
+        let word_ty = requirements.preamble.word_ty;
+        let (function_handle, address, value) = declare_function! {
+            module => fn write_byte_to_memory(address: word_ty, value: word_ty)
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        let address = memory_address(
+            &mut ctx,
+            requirements.preamble,
+            *requirements.disjoint_memory,
+            address,
+        );
+
+        // Memory is made of words, so merge the byte into the word containing it
+        let word_address = naga_expr!(&mut ctx => address >> U32(2));
+        let word = ctx.call_get_return(requirements.i32.read_memory, vec![word_address]);
+        let shift = naga_expr!(&mut ctx => (address & U32(3)) * U32(8));
+        let mask = naga_expr!(&mut ctx => ~(U32(0xFF) << shift));
+        let byte = naga_expr!(&mut ctx => (value & U32(0xFF)) << shift);
+        let new_word = naga_expr!(&mut ctx => bitcast<i32>((bitcast<u32>(word) & mask) | byte));
+        ctx.call_void(requirements.i32.write_memory, vec![word_address, new_word]);
+
+        Ok(function_handle)
+    }
+
+    // fn(address: u32) -> u32
+    fn gen_read_data_byte(
+        module: &mut naga::Module,
+        requirements: bulk_memory_instance_gen::ReadDataByteRequirements,
+    ) -> build::Result<bulk_memory_instance_gen::ReadDataByte> {
+        let word_ty = requirements.preamble.word_ty;
+        let (function_handle, address) = declare_function! {
+            module => fn read_byte_from_data(address: word_ty) -> word_ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        // Data segments are shared by all instances, so are never disjoint
+        let data = requirements.preamble.bindings.data;
+        let word = naga_expr!(&mut ctx => Load(Global(data)[address >> U32(2)]));
+        let shift = naga_expr!(&mut ctx => (address & U32(3)) * U32(8));
+        let byte = naga_expr!(&mut ctx => (word >> shift) & U32(0xFF));
+        ctx.result(byte);
+
+        Ok(function_handle)
+    }
+
+    // fn(memory: u32, address: u32, value: u32, length: u32)
+    fn gen_fill(
+        module: &mut naga::Module,
+        requirements: bulk_memory_instance_gen::FillRequirements,
+    ) -> build::Result<bulk_memory_instance_gen::Fill> {
+        let word_ty = requirements.preamble.word_ty;
+        let (function_handle, memory, address, value, length) = declare_function! {
+            module => fn memory_fill(memory: word_ty, address: word_ty, value: word_ty, length: word_ty)
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        // TODO: Support other memories
+        drop(memory);
+
+        emit_return_if_trapped(&mut ctx, requirements.preamble);
+        let memory_bytes = memory_bytes(&mut ctx, requirements.preamble);
+        emit_bounds_check(
+            &mut ctx,
+            requirements.preamble,
+            address,
+            length,
+            memory_bytes,
+        );
+
+        let write_byte = *requirements.write_byte;
+        naga_expr!(&mut ctx => for i in (U32(0))..(length) |ctx| {
+            let byte_address = naga_expr!(&mut ctx => address + i);
+            ctx.call_void(write_byte, vec![byte_address, value]);
+        });
+
+        Ok(function_handle)
+    }
+
+    // fn(dst_memory: u32, src_memory: u32, dst: u32, src: u32, length: u32)
+    fn gen_copy(
+        module: &mut naga::Module,
+        requirements: bulk_memory_instance_gen::CopyRequirements,
+    ) -> build::Result<bulk_memory_instance_gen::Copy> {
+        let word_ty = requirements.preamble.word_ty;
+        let (function_handle, dst_memory, src_memory, dst, src, length) = declare_function! {
+            module => fn memory_copy(dst_memory: word_ty, src_memory: word_ty, dst: word_ty, src: word_ty, length: word_ty)
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        // TODO: Support other memories
+        drop(dst_memory);
+        drop(src_memory);
+
+        emit_return_if_trapped(&mut ctx, requirements.preamble);
+        let memory_bytes = memory_bytes(&mut ctx, requirements.preamble);
+        emit_bounds_check(&mut ctx, requirements.preamble, src, length, memory_bytes);
+        emit_bounds_check(&mut ctx, requirements.preamble, dst, length, memory_bytes);
+
+        let read_byte = *requirements.read_byte;
+        let write_byte = *requirements.write_byte;
+
+        // Overlapping ranges must be copied in the direction that reads each byte before it is overwritten
+        let is_forward = naga_expr!(&mut ctx => dst <= src);
+        ctx.test(is_forward)
+            .then(|mut ctx| {
+                naga_expr!(&mut ctx => for i in (U32(0))..(length) |ctx| {
+                    let src_address = naga_expr!(&mut ctx => src + i);
+                    let dst_address = naga_expr!(&mut ctx => dst + i);
+                    let byte = ctx.call_get_return(read_byte, vec![src_address]);
+                    ctx.call_void(write_byte, vec![dst_address, byte]);
+                });
+            })
+            .otherwise(|mut ctx| {
+                naga_expr!(&mut ctx => for i in (U32(0))..(length) |ctx| {
+                    let offset = naga_expr!(&mut ctx => length - (i + U32(1)));
+                    let src_address = naga_expr!(&mut ctx => src + offset);
+                    let dst_address = naga_expr!(&mut ctx => dst + offset);
+                    let byte = ctx.call_get_return(read_byte, vec![src_address]);
+                    ctx.call_void(write_byte, vec![dst_address, byte]);
+                });
+            });
+
+        Ok(function_handle)
+    }
+
+    // fn(memory: u32, data: u32, data_length: u32, dst: u32, src: u32, length: u32)
+    fn gen_init(
+        module: &mut naga::Module,
+        requirements: bulk_memory_instance_gen::InitRequirements,
+    ) -> build::Result<bulk_memory_instance_gen::Init> {
+        let word_ty = requirements.preamble.word_ty;
+        let (function_handle, memory, data, data_length, dst, src, length) = declare_function! {
+            module => fn memory_init(memory: word_ty, data: word_ty, data_length: word_ty, dst: word_ty, src: word_ty, length: word_ty)
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        // TODO: Support other memories
+        drop(memory);
+
+        emit_return_if_trapped(&mut ctx, requirements.preamble);
+        emit_bounds_check(&mut ctx, requirements.preamble, src, length, data_length);
+        let memory_bytes = memory_bytes(&mut ctx, requirements.preamble);
+        emit_bounds_check(&mut ctx, requirements.preamble, dst, length, memory_bytes);
+
+        let read_data_byte = *requirements.read_data_byte;
+        let write_byte = *requirements.write_byte;
+        naga_expr!(&mut ctx => for i in (U32(0))..(length) |ctx| {
+            let src_address = naga_expr!(&mut ctx => data + (src + i));
+            let dst_address = naga_expr!(&mut ctx => dst + i);
+            let byte = ctx.call_get_return(read_data_byte, vec![src_address]);
+            ctx.call_void(write_byte, vec![dst_address, byte]);
+        });
+
+        Ok(function_handle)
+    }
+}
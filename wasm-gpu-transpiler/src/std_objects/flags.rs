@@ -1,4 +1,4 @@
-use naga_ext::{naga_expr, BlockContext, ExpressionsExt, TypesExt};
+use naga_ext::prelude::*;
 use wasmtime_environ::Trap;
 
 use crate::trap_to_u32;
@@ -16,15 +16,9 @@ fn make_trap_constant(
 
     let ty = module.types.insert_u32();
     let init = module.const_expressions.append_u32(trap_id);
-    module.constants.append(
-        naga::Constant {
-            name: Some(format!("TRAP_{:?}", name)),
-            r#override: naga::Override::None,
-            ty,
-            init,
-        },
-        naga::Span::UNDEFINED,
-    )
+    module
+        .constants
+        .append_named(format!("TRAP_{:?}", name), ty, init)
 }
 
 #[derive(Clone)]
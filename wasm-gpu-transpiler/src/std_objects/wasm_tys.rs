@@ -3,6 +3,7 @@ use crate::typed::{ExternRef, FuncRef, V128};
 
 pub(crate) mod native_f32;
 pub(crate) mod native_i32;
+pub(crate) mod native_v128;
 pub(crate) mod pollyfill_extern_ref;
 pub(crate) mod pollyfill_func_ref;
 pub(crate) mod polyfill_f64;
@@ -10,7 +11,9 @@ pub(crate) mod polyfill_i64;
 pub(crate) mod polyfill_v128;
 
 type MakeConstFn<Ty> = Box<
-    dyn Fn(&mut naga::Arena<naga::Expression>, Ty) -> build::Result<naga::Handle<naga::Expression>>,
+    dyn Fn(&mut naga::Arena<naga::Expression>, Ty) -> build::Result<naga::Handle<naga::Expression>>
+        + Send
+        + Sync,
 >;
 
 macro_rules! wasm_ty_generator {
@@ -190,15 +193,87 @@ macro_rules! wasm_ty_generator {
 
             convert_i32_s: |ty| naga::Handle<naga::Function>,
             convert_i32_u: |ty| naga::Handle<naga::Function>,
-        }; ($($extra_params)* i32_ty: naga::Handle<naga::Type>,)}
+
+            // Trapping conversions
+            trunc_i32_s: |ty| naga::Handle<naga::Function>,
+            trunc_i32_u: |ty| naga::Handle<naga::Function>,
+            trunc_i64_s: |ty| naga::Handle<naga::Function>,
+            trunc_i64_u: |ty| naga::Handle<naga::Function>,
+
+            // Saturating conversions (from non-trapping float-to-int proposal)
+            trunc_sat_i32_s: |ty| naga::Handle<naga::Function>,
+            trunc_sat_i32_u: |ty| naga::Handle<naga::Function>,
+            trunc_sat_i64_s: |ty| naga::Handle<naga::Function>,
+            trunc_sat_i64_u: |ty| naga::Handle<naga::Function>,
+
+            // Bitcasts, `f32.reinterpret_i32` and `i32.reinterpret_f32`
+            reinterpret_from_i32: |ty| naga::Handle<naga::Function>,
+            reinterpret_to_i32: |ty| naga::Handle<naga::Function>,
+        }; ($($extra_params)* i32_ty: naga::Handle<naga::Type>, i64_ty: naga::Handle<naga::Type>,)}
+    };
+    // Just f64
+    (struct $struct_name:ident; trait $trait_name:ident; $wasm_ty:ty; [f64 $(, $parts:tt)*]; {$($impl:tt)*}; ($($extra_params:tt)*)) => {
+        wasm_ty_generator!{struct $struct_name; trait $trait_name; $wasm_ty; [$($parts),*]; {
+            $($impl)*
+
+            convert_i32_s: |ty| naga::Handle<naga::Function>,
+            convert_i32_u: |ty| naga::Handle<naga::Function>,
+            convert_i64_s: |ty| naga::Handle<naga::Function>,
+            convert_i64_u: |ty| naga::Handle<naga::Function>,
+
+            promote_f32: |ty| naga::Handle<naga::Function>,
+            demote_f32: |ty| naga::Handle<naga::Function>,
+
+            // Trapping conversions
+            trunc_i32_s: |ty| naga::Handle<naga::Function>,
+            trunc_i32_u: |ty| naga::Handle<naga::Function>,
+            trunc_i64_s: |ty| naga::Handle<naga::Function>,
+            trunc_i64_u: |ty| naga::Handle<naga::Function>,
+
+            // Bitcasts, `f64.reinterpret_i64` and `i64.reinterpret_f64`
+            reinterpret_from_i64: |ty| naga::Handle<naga::Function>,
+            reinterpret_to_i64: |ty| naga::Handle<naga::Function>,
+        }; ($($extra_params)* i32_ty: naga::Handle<naga::Type>, i64_ty: naga::Handle<naga::Type>, f32_ty: naga::Handle<naga::Type>,)}
+    };
+    // Just v128
+    // See https://webassembly.github.io/spec/core/syntax/instructions.html#vector-instructions
+    (struct $struct_name:ident; trait $trait_name:ident; $wasm_ty:ty; [v128 $(, $parts:tt)*]; {$($impl:tt)*}; ($($extra_params:tt)*)) => {
+        wasm_ty_generator!{struct $struct_name; trait $trait_name; $wasm_ty; [$($parts),*]; {
+            $($impl)*
+
+            // Bitwise
+            not: |ty| naga::Handle<naga::Function>,
+            and: |ty| naga::Handle<naga::Function>,
+            and_not: |ty| naga::Handle<naga::Function>,
+            or: |ty| naga::Handle<naga::Function>,
+            xor: |ty| naga::Handle<naga::Function>,
+
+            // Lane-wise integer arithmetic
+            i8x16_add: |ty| naga::Handle<naga::Function>,
+            i8x16_sub: |ty| naga::Handle<naga::Function>,
+            i16x8_add: |ty| naga::Handle<naga::Function>,
+            i16x8_sub: |ty| naga::Handle<naga::Function>,
+            i16x8_mul: |ty| naga::Handle<naga::Function>,
+            i32x4_add: |ty| naga::Handle<naga::Function>,
+            i32x4_sub: |ty| naga::Handle<naga::Function>,
+            i32x4_mul: |ty| naga::Handle<naga::Function>,
+            i64x2_add: |ty| naga::Handle<naga::Function>,
+            i64x2_sub: |ty| naga::Handle<naga::Function>,
+
+            // Lane-wise float arithmetic
+            f32x4_add: |ty| naga::Handle<naga::Function>,
+            f32x4_sub: |ty| naga::Handle<naga::Function>,
+            f32x4_mul: |ty| naga::Handle<naga::Function>,
+            f32x4_div: |ty| naga::Handle<naga::Function>,
+        }; ($($extra_params)* f32: crate::std_objects::wasm_tys::F32Instance,)}
     };
 }
 
 wasm_ty_generator!(struct I32Instance; trait I32Gen; i32; [numeric, integer]);
 wasm_ty_generator!(struct I64Instance; trait I64Gen; i64; [numeric, integer, i64]);
 wasm_ty_generator!(struct F32Instance; trait F32Gen; f32; [numeric, floating, f32]);
-wasm_ty_generator!(struct F64Instance; trait F64Gen; f64; [numeric, floating]);
-wasm_ty_generator!(struct V128Instance; trait V128Gen; V128; []);
+wasm_ty_generator!(struct F64Instance; trait F64Gen; f64; [numeric, floating, f64]);
+wasm_ty_generator!(struct V128Instance; trait V128Gen; V128; [v128]);
 wasm_ty_generator!(struct FuncRefInstance; trait FuncRefGen; FuncRef; []);
 wasm_ty_generator!(struct ExternRefInstance; trait ExternRefGen; ExternRef; []);
 
@@ -443,14 +518,11 @@ macro_rules! impl_load_and_store {
                 module: &mut naga::Module,
                 requirements: $instance_gen::LoadRequirements,
             ) -> build::Result<$instance_gen::Load> {
-                let (function_handle, memory, address) = declare_function! {
-                    module => fn [< $name _load >](memory: requirements.preamble.word_ty, address: requirements.preamble.word_ty) -> *requirements.ty
+                let (function_handle, address) = declare_function! {
+                    module => fn [< $name _load >](address: requirements.preamble.word_ty) -> *requirements.ty
                 };
                 let mut ctx = BlockContext::from((module, function_handle));
 
-                // TODO: Support other memories
-                drop(memory);
-
                 // Variable to unify aligned and unaligned loads
                 let loaded_value_local = ctx.new_local(
                     "loaded_value".to_owned(),
@@ -489,14 +561,11 @@ macro_rules! impl_load_and_store {
                 module: &mut naga::Module,
                 requirements: $instance_gen::StoreRequirements,
             ) -> build::Result<$instance_gen::Store> {
-                let (function_handle, memory, address, value) = declare_function! {
-                    module => fn [< $name _store >](memory: requirements.preamble.word_ty, address: requirements.preamble.word_ty, value: *requirements.ty)
+                let (function_handle, address, value) = declare_function! {
+                    module => fn [< $name _store >](address: requirements.preamble.word_ty, value: *requirements.ty)
                 };
                 let mut ctx = BlockContext::from((module, function_handle));
 
-                // TODO: Support other memories
-                drop(memory);
-
                 // If we have trapped, don't store
                 let trap_state = requirements.preamble.trap_state;
                 let is_trapped = naga_expr!(&mut ctx => Load(Global(trap_state)) != U32(0));
@@ -541,7 +610,7 @@ macro_rules! impl_dud_integer_load {
                 requirements: $instance_gen::[< $fn:camel Requirements >],
             ) -> build::Result<$instance_gen::[< $fn:camel >]> {
                 let (function_handle, ..) = declare_function! {
-                    module => fn [< $name _ $fn >](memory: requirements.preamble.word_ty, address: requirements.preamble.word_ty) -> *requirements.ty
+                    module => fn [< $name _ $fn >](address: requirements.preamble.word_ty) -> *requirements.ty
                 };
                 let mut ctx = BlockContext::from((module, function_handle));
 
@@ -563,7 +632,7 @@ macro_rules! impl_dud_integer_store {
                 requirements: $instance_gen::[< $fn:camel Requirements >],
             ) -> build::Result<$instance_gen::[< $fn:camel >]> {
                 let (function_handle, ..) = declare_function! {
-                    module => fn [< $name _ $fn >](memory: requirements.preamble.word_ty, address: requirements.preamble.word_ty, value: *requirements.ty)
+                    module => fn [< $name _ $fn >](address: requirements.preamble.word_ty, value: *requirements.ty)
                 };
 
                 Ok(function_handle)
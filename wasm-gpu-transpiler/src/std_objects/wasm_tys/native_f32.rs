@@ -1,10 +1,9 @@
 use crate::{
     build,
-    std_objects::{preamble_objects_gen, wasm_tys::impl_native_bool_binexp},
-};
-use naga_ext::{
-    declare_function, naga_expr, BlockContext, ConstantsExt, ExpressionsExt, TypesExt,
+    std_objects::{preamble_objects_gen, wasm_tys::impl_native_bool_binexp, PreambleObjects},
 };
+use naga_ext::prelude::*;
+use wasmtime_environ::Trap;
 
 use super::{f32_instance_gen, F32Gen};
 
@@ -82,6 +81,32 @@ fn scale_down_float(
     )
 }
 
+/// Generates a boolean expression that is true if either of the given floats is a NaN
+fn is_either_nan(
+    ctx: &mut BlockContext<'_>,
+    lhs: naga::Handle<naga::Expression>,
+    rhs: naga::Handle<naga::Expression>,
+) -> naga::Handle<naga::Expression> {
+    naga_expr!(ctx =>
+        ((bitcast<u32>(lhs) & U32(0x7FFFFFFF)) > U32(0x7f800000)) | ((bitcast<u32>(rhs) & U32(0x7FFFFFFF)) > U32(0x7f800000))
+    )
+}
+
+/// Replaces the value with the canonical NaN if it is any NaN, if requested by the floating point options
+fn maybe_canonicalize_nan(
+    ctx: &mut BlockContext<'_>,
+    fp_options: &crate::FloatingPointOptions,
+    value: naga::Handle<naga::Expression>,
+) -> naga::Handle<naga::Expression> {
+    if !fp_options.canonicalize_nans {
+        return value;
+    }
+
+    naga_expr!(ctx =>
+        if ((bitcast<u32>(value) & U32(0x7FFFFFFF)) > U32(0x7f800000)) {bitcast<f32>(U32(0x7fc00000))} else {value}
+    )
+}
+
 fn subnormal_add(
     ctx: &mut BlockContext<'_>,
     ty: naga::Handle<naga::Type>,
@@ -95,42 +120,50 @@ fn subnormal_add(
     let res_ptr = ctx.local_expr(res_var);
     ctx.store(res_ptr, res);
 
-    let is_subnormal = naga_expr!(ctx => 
-        (res >= F32(-9.861e-32)) & (res <= F32(9.861e-32))
-    );
-    ctx.test(is_subnormal).then(|mut ctx| {
-        // Short-circuit if we're adding 0 to 0 (ignoring sign bit)
-        let is_adding_zeros = naga_expr!{&mut ctx => 
-            (bitcast<u32>(lhs) | bitcast<u32>(rhs) | U32(0x80000000)) == U32(0x80000000)
-        };
-
-        ctx.test(is_adding_zeros).then(|mut ctx| {
-            let new_res = naga_expr!{&mut ctx => 
-                bitcast<f32>(bitcast<u32>(lhs) & bitcast<u32>(rhs))
+    // Short-circuit if either operand is NaN, since the bit manipulation below could otherwise
+    // produce a non-NaN result from NaN inputs
+    let is_nan = is_either_nan(ctx, lhs, rhs);
+    ctx.test(is_nan).then(|mut ctx| {
+        let nan = naga_expr!(&mut ctx => bitcast<f32>(U32(0x7fc00000)));
+        ctx.store(res_ptr, nan);
+    }).otherwise(|mut ctx| {
+        let is_subnormal = naga_expr!(&mut ctx => 
+            (res >= F32(-9.861e-32)) & (res <= F32(9.861e-32))
+        );
+        ctx.test(is_subnormal).then(|mut ctx| {
+            // Short-circuit if we're adding 0 to 0 (ignoring sign bit)
+            let is_adding_zeros = naga_expr!{&mut ctx => 
+                (bitcast<u32>(lhs) | bitcast<u32>(rhs) | U32(0x80000000)) == U32(0x80000000)
             };
 
-            ctx.store(res_ptr, new_res);
-        }).otherwise(|mut ctx| {
-            // Short-circuit if we're adding n to -n
-            let is_adding_n_to_minus_n = naga_expr!{&mut ctx => 
-                (bitcast<u32>(lhs) ^ bitcast<u32>(rhs)) == U32(0x80000000)
-            };
+            ctx.test(is_adding_zeros).then(|mut ctx| {
+                let new_res = naga_expr!{&mut ctx => 
+                    bitcast<f32>(bitcast<u32>(lhs) & bitcast<u32>(rhs))
+                };
 
-            ctx.test(is_adding_n_to_minus_n).then(|mut ctx| {
-                let const_zero = ctx.literal_expr_from(0.0f32);
-                ctx.store(res_ptr, const_zero);
+                ctx.store(res_ptr, new_res);
             }).otherwise(|mut ctx| {
-                // Scale both floats up
-                let lhs_scaled = scale_up_float(&mut ctx, lhs, 64);
-                let rhs_scaled = scale_up_float(&mut ctx, rhs, 64);
-
-                let scaled_new_res = naga_expr!{&mut ctx => 
-                    lhs_scaled + rhs_scaled
+                // Short-circuit if we're adding n to -n
+                let is_adding_n_to_minus_n = naga_expr!{&mut ctx => 
+                    (bitcast<u32>(lhs) ^ bitcast<u32>(rhs)) == U32(0x80000000)
                 };
 
-                // Scale back down, possibly into subnormal range
-                let new_res = scale_down_float(&mut ctx, scaled_new_res, 64);
-                ctx.store(res_ptr, new_res);
+                ctx.test(is_adding_n_to_minus_n).then(|mut ctx| {
+                    let const_zero = ctx.literal_expr_from(0.0f32);
+                    ctx.store(res_ptr, const_zero);
+                }).otherwise(|mut ctx| {
+                    // Scale both floats up
+                    let lhs_scaled = scale_up_float(&mut ctx, lhs, 64);
+                    let rhs_scaled = scale_up_float(&mut ctx, rhs, 64);
+
+                    let scaled_new_res = naga_expr!{&mut ctx => 
+                        lhs_scaled + rhs_scaled
+                    };
+
+                    // Scale back down, possibly into subnormal range
+                    let new_res = scale_down_float(&mut ctx, scaled_new_res, 64);
+                    ctx.store(res_ptr, new_res);
+                });
             });
         });
     });
@@ -153,40 +186,48 @@ fn subnormal_sub(
     let res_ptr = ctx.local_expr(res_var);
     ctx.store(res_ptr, res);
 
-    let is_subnormal = naga_expr!{ctx => 
-        (res >= F32(-9.861e-32)) & (res <= F32(9.861e-32))
-    };
-    ctx.test(is_subnormal).then(|mut ctx| {
-        // Short-circuit if we're subbing 0 from 0 (ignoring sign bit)
-        let is_subbing_zeros = naga_expr!{&mut ctx => 
-            (bitcast<u32>(lhs) | bitcast<u32>(rhs) | U32(0x80000000)) == U32(0x80000000)
+    // Short-circuit if either operand is NaN, since the bit manipulation below could otherwise
+    // produce a non-NaN result from NaN inputs
+    let is_nan = is_either_nan(ctx, lhs, rhs);
+    ctx.test(is_nan).then(|mut ctx| {
+        let nan = naga_expr!(&mut ctx => bitcast<f32>(U32(0x7fc00000)));
+        ctx.store(res_ptr, nan);
+    }).otherwise(|mut ctx| {
+        let is_subnormal = naga_expr!{&mut ctx => 
+            (res >= F32(-9.861e-32)) & (res <= F32(9.861e-32))
         };
-        ctx.test(is_subbing_zeros).then(|mut ctx| {
-            let new_res = naga_expr!{&mut ctx => 
-                bitcast<f32>(bitcast<u32>(lhs) & (bitcast<u32>(rhs) ^ U32(0x80000000)))
-            };
-            ctx.store(res_ptr, new_res);
-        }).otherwise(|mut ctx| {
-            // Short-circuit if we're subbing n from n
-            let is_subbing_n_from_n = naga_expr!{&mut ctx => 
-                bitcast<u32>(lhs) == bitcast<u32>(rhs)
+        ctx.test(is_subnormal).then(|mut ctx| {
+            // Short-circuit if we're subbing 0 from 0 (ignoring sign bit)
+            let is_subbing_zeros = naga_expr!{&mut ctx => 
+                (bitcast<u32>(lhs) | bitcast<u32>(rhs) | U32(0x80000000)) == U32(0x80000000)
             };
-
-            ctx.test(is_subbing_n_from_n).then(|mut ctx| {
-                let zero = naga_expr!(&mut ctx => F32(0.0));
-                ctx.store(res_ptr, zero);
+            ctx.test(is_subbing_zeros).then(|mut ctx| {
+                let new_res = naga_expr!{&mut ctx => 
+                    bitcast<f32>(bitcast<u32>(lhs) & (bitcast<u32>(rhs) ^ U32(0x80000000)))
+                };
+                ctx.store(res_ptr, new_res);
             }).otherwise(|mut ctx| {
-                // Scale both floats up
-                let lhs_scaled = scale_up_float(&mut ctx, lhs, 64);
-                let rhs_scaled = scale_up_float(&mut ctx, rhs, 64);
-
-                let scaled_new_res = naga_expr!{&mut ctx => 
-                    lhs_scaled - rhs_scaled
+                // Short-circuit if we're subbing n from n
+                let is_subbing_n_from_n = naga_expr!{&mut ctx => 
+                    bitcast<u32>(lhs) == bitcast<u32>(rhs)
                 };
 
-                // Scale back down, possibly into subnormal range
-                let new_res = scale_down_float(&mut ctx, scaled_new_res, 64);
-                ctx.store(res_ptr, new_res);
+                ctx.test(is_subbing_n_from_n).then(|mut ctx| {
+                    let zero = naga_expr!(&mut ctx => F32(0.0));
+                    ctx.store(res_ptr, zero);
+                }).otherwise(|mut ctx| {
+                    // Scale both floats up
+                    let lhs_scaled = scale_up_float(&mut ctx, lhs, 64);
+                    let rhs_scaled = scale_up_float(&mut ctx, rhs, 64);
+
+                    let scaled_new_res = naga_expr!{&mut ctx => 
+                        lhs_scaled - rhs_scaled
+                    };
+
+                    // Scale back down, possibly into subnormal range
+                    let new_res = scale_down_float(&mut ctx, scaled_new_res, 64);
+                    ctx.store(res_ptr, new_res);
+                });
             });
         });
     });
@@ -246,8 +287,20 @@ fn subnormal_mult(
             (lhs_exp != U32(0)) & (rhs_exp != U32(0)) & ((lhs_exp + rhs_exp) > U32(127))
         );
         ctx.test(can_just_mult).then(|mut ctx| {
-            let res = naga_expr!(&mut ctx => lhs * rhs);
-            ctx.store(res_ptr, res);
+            // Products with a biased exponent of at least 255 aren't representable, and must overflow to a
+            // correctly signed infinity rather than whatever the GPU gives for an out-of-range result
+            let will_overflow = naga_expr!(&mut ctx =>
+                (lhs_exp + rhs_exp) >= U32(127 + 255)
+            );
+            ctx.test(will_overflow).then(|mut ctx| {
+                let res = naga_expr!(&mut ctx =>
+                    bitcast<f32>(((lhs_u32 ^ rhs_u32) & U32(0x80000000)) | U32(0x7F800000))
+                );
+                ctx.store(res_ptr, res);
+            }).otherwise(|mut ctx| {
+                let res = naga_expr!(&mut ctx => lhs * rhs);
+                ctx.store(res_ptr, res);
+            });
         }).otherwise(|mut ctx| {
             let is_lhs_smaller = naga_expr!(&mut ctx =>
                 lhs_exp <= rhs_exp
@@ -479,6 +532,300 @@ fn subnormal_max(
     return naga_expr!(ctx => Load(res_ptr));
 }
 
+/// The parts of a finite, non-zero f32, with subnormals normalized so that the significand always has its leading
+/// bit at bit 23. The exponent is biased, and is below 1 for subnormals.
+struct UnpackedF32 {
+    sign: naga::Handle<naga::Expression>,
+    exponent: naga::Handle<naga::Expression>,
+    significand: naga::Handle<naga::Expression>,
+}
+
+impl UnpackedF32 {
+    fn from_bits(ctx: &mut BlockContext<'_>, bits: naga::Handle<naga::Expression>) -> Self {
+        let sign = naga_expr!(ctx => bits >> U32(31));
+        let exponent_bits = naga_expr!(ctx => (bits >> U32(23)) & U32(0xFF));
+        let fraction = naga_expr!(ctx => bits & U32(0x007FFFFF));
+
+        // Subnormals are shifted up until their leading bit is where the implicit bit of a normal would be
+        let is_subnormal = naga_expr!(ctx => exponent_bits == U32(0));
+        let shift = naga_expr!(ctx => (countLeadingZeros(fraction)) - U32(8));
+        let significand = naga_expr!(ctx =>
+            if (is_subnormal) {fraction << shift} else {fraction | U32(0x00800000)}
+        );
+        let exponent = naga_expr!(ctx =>
+            if (is_subnormal) {I32(1) - (bitcast<i32>(shift))} else {bitcast<i32>(exponent_bits)}
+        );
+
+        Self {
+            sign,
+            exponent,
+            significand,
+        }
+    }
+}
+
+/// Rounds a significand to the nearest representable f32, with ties going to even, and gives the bits of the
+/// result. The significand has its leading bit at bit 30, followed by the 23 bits that are kept and then 7 bits
+/// used for rounding. `exponent` is one less than the biased exponent of a normal result, since the leading bit is
+/// added into the exponent when packing. Results too small to be normal are shifted down to be subnormal, and
+/// results too large to be finite become infinities.
+pub(super) fn gen_round_pack_bits(
+    ctx: &mut BlockContext<'_>,
+    sign: naga::Handle<naga::Expression>,
+    exponent: naga::Handle<naga::Expression>,
+    significand: naga::Handle<naga::Expression>,
+) -> naga::Handle<naga::Expression> {
+    let is_tiny = naga_expr!(ctx => exponent < I32(0));
+    let subnormal_shift = naga_expr!(ctx => bitcast<u32>(-exponent));
+    let clamped_shift = naga_expr!(ctx => subnormal_shift & U32(31));
+    let shifted = naga_expr!(ctx => significand >> clamped_shift);
+    let lost_bits = naga_expr!(ctx => (shifted << clamped_shift) != significand);
+    let jam = naga_expr!(ctx => if (lost_bits) {U32(1)} else {U32(0)});
+    let jammed = naga_expr!(ctx => shifted | jam);
+    let in_range = naga_expr!(ctx => subnormal_shift < U32(31));
+    let subnormal_significand = naga_expr!(ctx => if (in_range) {jammed} else {U32(1)});
+    let significand = naga_expr!(ctx => if (is_tiny) {subnormal_significand} else {significand});
+    let exponent = naga_expr!(ctx => if (is_tiny) {I32(0)} else {exponent});
+
+    let round_bits = naga_expr!(ctx => significand & U32(0x7F));
+    let incremented = naga_expr!(ctx => significand + U32(0x40));
+    let overflows = naga_expr!(ctx =>
+        (exponent > I32(0xFD)) | ((exponent == I32(0xFD)) & (incremented >= U32(0x80000000)))
+    );
+    let rounded = naga_expr!(ctx => incremented >> U32(7));
+    let is_tie = naga_expr!(ctx => round_bits == U32(0x40));
+    let rounded = naga_expr!(ctx => if (is_tie) {rounded & U32(0xFFFFFFFE)} else {rounded});
+    let exponent = naga_expr!(ctx => if (rounded == U32(0)) {U32(0)} else {bitcast<u32>(exponent)});
+    let finite = naga_expr!(ctx => ((sign << U32(31)) | (exponent << U32(23))) + rounded);
+
+    let infinite = naga_expr!(ctx => (sign << U32(31)) | U32(0x7F800000));
+    naga_expr!(ctx => if (overflows) {infinite} else {finite})
+}
+
+/// The bits of the NaN given by an operation with a NaN operand, or by an invalid operation such as `inf - inf`.
+/// The first NaN operand is made quiet, or the canonical NaN is used if neither operand is a NaN.
+fn gen_nan_result_bits(
+    ctx: &mut BlockContext<'_>,
+    lhs_bits: naga::Handle<naga::Expression>,
+    rhs_bits: naga::Handle<naga::Expression>,
+) -> naga::Handle<naga::Expression> {
+    let lhs_is_nan = naga_expr!(ctx => (lhs_bits & U32(0x7FFFFFFF)) > U32(0x7F800000));
+    let rhs_is_nan = naga_expr!(ctx => (rhs_bits & U32(0x7FFFFFFF)) > U32(0x7F800000));
+    let rhs_or_canonical = naga_expr!(ctx =>
+        if (rhs_is_nan) {rhs_bits | U32(0x00400000)} else {U32(0x7FC00000)}
+    );
+    naga_expr!(ctx => if (lhs_is_nan) {lhs_bits | U32(0x00400000)} else {rhs_or_canonical})
+}
+
+/// Adds two floats using integer arithmetic, so that the result is rounded to nearest with ties going to even
+/// rather than in whichever direction the GPU chooses. Subnormals are handled without any scaling.
+fn rounded_add(
+    ctx: &mut BlockContext<'_>,
+    lhs: naga::Handle<naga::Expression>,
+    rhs: naga::Handle<naga::Expression>,
+) -> naga::Handle<naga::Expression> {
+    let lhs_bits = naga_expr!(ctx => bitcast<u32>(lhs));
+    let rhs_bits = naga_expr!(ctx => bitcast<u32>(rhs));
+    rounded_add_bits(ctx, lhs_bits, rhs_bits)
+}
+
+fn rounded_sub(
+    ctx: &mut BlockContext<'_>,
+    lhs: naga::Handle<naga::Expression>,
+    rhs: naga::Handle<naga::Expression>,
+) -> naga::Handle<naga::Expression> {
+    let lhs_bits = naga_expr!(ctx => bitcast<u32>(lhs));
+    let rhs_bits = naga_expr!(ctx => (bitcast<u32>(rhs)) ^ U32(0x80000000));
+    rounded_add_bits(ctx, lhs_bits, rhs_bits)
+}
+
+fn rounded_add_bits(
+    ctx: &mut BlockContext<'_>,
+    lhs_bits: naga::Handle<naga::Expression>,
+    rhs_bits: naga::Handle<naga::Expression>,
+) -> naga::Handle<naga::Expression> {
+    // Order the operands by magnitude, so that subtracting significands never goes negative
+    let lhs_magnitude = naga_expr!(ctx => lhs_bits & U32(0x7FFFFFFF));
+    let rhs_magnitude = naga_expr!(ctx => rhs_bits & U32(0x7FFFFFFF));
+    let swap = naga_expr!(ctx => rhs_magnitude > lhs_magnitude);
+    let big_bits = naga_expr!(ctx => if (swap) {rhs_bits} else {lhs_bits});
+    let small_bits = naga_expr!(ctx => if (swap) {lhs_bits} else {rhs_bits});
+    let big = UnpackedF32::from_bits(ctx, big_bits);
+    let small = UnpackedF32::from_bits(ctx, small_bits);
+
+    // Leading bits go at bit 29, leaving room for a carry into bit 30 and 6 bits below the kept bits for rounding.
+    // Bits shifted out of the smaller operand are collected into its lowest bit.
+    let big_significand = naga_expr!(ctx => {big.significand} << U32(6));
+    let small_significand = naga_expr!(ctx => {small.significand} << U32(6));
+    let distance = naga_expr!(ctx => bitcast<u32>({big.exponent} - {small.exponent}));
+    let shift = naga_expr!(ctx => min(distance, U32(31)));
+    let shifted = naga_expr!(ctx => small_significand >> shift);
+    let lost_bits = naga_expr!(ctx => (shifted << shift) != small_significand);
+    let aligned = naga_expr!(ctx => shifted | (if (lost_bits) {U32(1)} else {U32(0)}));
+    let is_subtraction = naga_expr!(ctx => {big.sign} != {small.sign});
+    let sum = naga_expr!(ctx =>
+        if (is_subtraction) {big_significand - aligned} else {big_significand + aligned}
+    );
+
+    // Move the leading bit to bit 30. Many bits can only cancel when the exponents are close enough that no bits
+    // were lost while aligning, so shifting left never moves the collected bit into the kept bits
+    let leading_zeros = naga_expr!(ctx => countLeadingZeros(sum));
+    let normalized = naga_expr!(ctx => sum << (leading_zeros - U32(1)));
+    let exponent = naga_expr!(ctx => ({big.exponent} + I32(1)) - (bitcast<i32>(leading_zeros)));
+    let finite = gen_round_pack_bits(ctx, big.sign, exponent, normalized);
+    // Exact cancellation gives a positive zero when rounding to nearest
+    let res = naga_expr!(ctx => if (sum == U32(0)) {U32(0)} else {finite});
+
+    // Special values
+    let lhs_is_zero = naga_expr!(ctx => lhs_magnitude == U32(0));
+    let rhs_is_zero = naga_expr!(ctx => rhs_magnitude == U32(0));
+    let lhs_is_inf = naga_expr!(ctx => lhs_magnitude == U32(0x7F800000));
+    let rhs_is_inf = naga_expr!(ctx => rhs_magnitude == U32(0x7F800000));
+    let res = naga_expr!(ctx => if (rhs_is_zero) {lhs_bits} else {res});
+    let res = naga_expr!(ctx => if (lhs_is_zero) {rhs_bits} else {res});
+    // The sum of two zeros is only negative if both are
+    let res = naga_expr!(ctx => if (lhs_is_zero & rhs_is_zero) {lhs_bits & rhs_bits} else {res});
+    let res = naga_expr!(ctx => if (rhs_is_inf) {rhs_bits} else {res});
+    let res = naga_expr!(ctx => if (lhs_is_inf) {lhs_bits} else {res});
+    let is_nan = naga_expr!(ctx =>
+        ((lhs_magnitude > U32(0x7F800000)) | (rhs_magnitude > U32(0x7F800000)))
+            | ((lhs_is_inf & rhs_is_inf) & (((lhs_bits ^ rhs_bits) >> U32(31)) == U32(1)))
+    );
+    let nan = gen_nan_result_bits(ctx, lhs_bits, rhs_bits);
+    let res = naga_expr!(ctx => if (is_nan) {nan} else {res});
+
+    naga_expr!(ctx => bitcast<f32>(res))
+}
+
+/// Multiplies two floats using integer arithmetic, so that the result is rounded to nearest with ties going to
+/// even rather than in whichever direction the GPU chooses. Subnormals are handled without any scaling.
+fn rounded_mul(
+    ctx: &mut BlockContext<'_>,
+    lhs: naga::Handle<naga::Expression>,
+    rhs: naga::Handle<naga::Expression>,
+) -> naga::Handle<naga::Expression> {
+    let lhs_bits = naga_expr!(ctx => bitcast<u32>(lhs));
+    let rhs_bits = naga_expr!(ctx => bitcast<u32>(rhs));
+    let sign = naga_expr!(ctx => (lhs_bits ^ rhs_bits) >> U32(31));
+    let lhs_parts = UnpackedF32::from_bits(ctx, lhs_bits);
+    let rhs_parts = UnpackedF32::from_bits(ctx, rhs_bits);
+
+    // Multiply the 24-bit significands as 8-bit and 16-bit halves, to get all 48 bits of the product
+    let lhs_high = naga_expr!(ctx => {lhs_parts.significand} >> U32(16));
+    let lhs_low = naga_expr!(ctx => {lhs_parts.significand} & U32(0xFFFF));
+    let rhs_high = naga_expr!(ctx => {rhs_parts.significand} >> U32(16));
+    let rhs_low = naga_expr!(ctx => {rhs_parts.significand} & U32(0xFFFF));
+    let low = naga_expr!(ctx => lhs_low * rhs_low);
+    let middle = naga_expr!(ctx => (lhs_high * rhs_low) + (lhs_low * rhs_high));
+    let product_low = naga_expr!(ctx => low + ((middle & U32(0xFFFF)) << U32(16)));
+    let carry = naga_expr!(ctx => if (product_low < low) {U32(1)} else {U32(0)});
+    let product_high = naga_expr!(ctx => ((lhs_high * rhs_high) + (middle >> U32(16))) + carry);
+
+    // The product has its leading bit at bit 46 or 47, so keep the top 32 bits with the rest collected into the
+    // lowest bit, then move the leading bit to bit 30
+    let top = naga_expr!(ctx => (product_high << U32(16)) | (product_low >> U32(16)));
+    let lost_bits = naga_expr!(ctx => (product_low & U32(0xFFFF)) != U32(0));
+    let top = naga_expr!(ctx => top | (if (lost_bits) {U32(1)} else {U32(0)}));
+    let is_carried = naga_expr!(ctx => top >= U32(0x80000000));
+    let significand = naga_expr!(ctx =>
+        if (is_carried) {(top >> U32(1)) | (top & U32(1))} else {top}
+    );
+    let exponent = naga_expr!(ctx =>
+        (({lhs_parts.exponent} + {rhs_parts.exponent}) - I32(128)) + (if (is_carried) {I32(1)} else {I32(0)})
+    );
+    let res = gen_round_pack_bits(ctx, sign, exponent, significand);
+
+    // Special values
+    let lhs_magnitude = naga_expr!(ctx => lhs_bits & U32(0x7FFFFFFF));
+    let rhs_magnitude = naga_expr!(ctx => rhs_bits & U32(0x7FFFFFFF));
+    let is_zero = naga_expr!(ctx => (lhs_magnitude == U32(0)) | (rhs_magnitude == U32(0)));
+    let is_inf =
+        naga_expr!(ctx => (lhs_magnitude == U32(0x7F800000)) | (rhs_magnitude == U32(0x7F800000)));
+    let res = naga_expr!(ctx => if (is_zero) {sign << U32(31)} else {res});
+    let res = naga_expr!(ctx => if (is_inf) {(sign << U32(31)) | U32(0x7F800000)} else {res});
+    let is_nan = naga_expr!(ctx =>
+        ((lhs_magnitude > U32(0x7F800000)) | (rhs_magnitude > U32(0x7F800000))) | (is_zero & is_inf)
+    );
+    let nan = gen_nan_result_bits(ctx, lhs_bits, rhs_bits);
+    let res = naga_expr!(ctx => if (is_nan) {nan} else {res});
+
+    naga_expr!(ctx => bitcast<f32>(res))
+}
+
+/// Divides two floats using long division, one quotient bit at a time, so that the result is rounded to nearest
+/// with ties going to even rather than only being accurate to within a few ULP. Subnormals and operands beyond
+/// `2^126` are handled without any scaling.
+fn rounded_div(
+    ctx: &mut BlockContext<'_>,
+    lhs: naga::Handle<naga::Expression>,
+    rhs: naga::Handle<naga::Expression>,
+) -> naga::Handle<naga::Expression> {
+    let lhs_bits = naga_expr!(ctx => bitcast<u32>(lhs));
+    let rhs_bits = naga_expr!(ctx => bitcast<u32>(rhs));
+    let sign = naga_expr!(ctx => (lhs_bits ^ rhs_bits) >> U32(31));
+    let lhs_parts = UnpackedF32::from_bits(ctx, lhs_bits);
+    let rhs_parts = UnpackedF32::from_bits(ctx, rhs_bits);
+
+    // Make the dividend at least the divisor, so that the first quotient bit is set
+    let lhs_is_smaller = naga_expr!(ctx => {lhs_parts.significand} < {rhs_parts.significand});
+    let dividend = naga_expr!(ctx =>
+        if (lhs_is_smaller) {{lhs_parts.significand} << U32(1)} else {{lhs_parts.significand}}
+    );
+    let exponent = naga_expr!(ctx =>
+        (({lhs_parts.exponent} - {rhs_parts.exponent}) + I32(126)) - (if (lhs_is_smaller) {I32(1)} else {I32(0)})
+    );
+
+    let word_ty = ctx.types.insert_u32();
+    let remainder = ctx.new_local("remainder", word_ty, None);
+    let remainder = ctx.local_expr(remainder);
+    ctx.store(remainder, dividend);
+    let quotient = ctx.new_local("quotient", word_ty, None);
+    let quotient = ctx.local_expr(quotient);
+    let zero = naga_expr!(ctx => U32(0));
+    ctx.store(quotient, zero);
+
+    // 31 quotient bits puts the leading bit at bit 30. The remainder is always less than twice the divisor, so
+    // never overflows when doubled
+    naga_expr!(ctx => for i in (U32(0))..(U32(31)) |ctx| {
+        let current = naga_expr!(&mut ctx => Load(remainder));
+        let fits = naga_expr!(&mut ctx => current >= {rhs_parts.significand});
+        let subtracted = naga_expr!(&mut ctx =>
+            if (fits) {current - {rhs_parts.significand}} else {current}
+        );
+        let doubled = naga_expr!(&mut ctx => subtracted << U32(1));
+        ctx.store(remainder, doubled);
+
+        let quotient_bit = naga_expr!(&mut ctx => if (fits) {U32(1)} else {U32(0)});
+        let new_quotient = naga_expr!(&mut ctx => ((Load(quotient)) << U32(1)) | quotient_bit);
+        ctx.store(quotient, new_quotient);
+    });
+
+    let is_exact = naga_expr!(ctx => (Load(remainder)) == U32(0));
+    let jam = naga_expr!(ctx => if (is_exact) {U32(0)} else {U32(1)});
+    let significand = naga_expr!(ctx => (Load(quotient)) | jam);
+    let res = gen_round_pack_bits(ctx, sign, exponent, significand);
+
+    // Special values
+    let lhs_magnitude = naga_expr!(ctx => lhs_bits & U32(0x7FFFFFFF));
+    let rhs_magnitude = naga_expr!(ctx => rhs_bits & U32(0x7FFFFFFF));
+    let lhs_is_zero = naga_expr!(ctx => lhs_magnitude == U32(0));
+    let rhs_is_zero = naga_expr!(ctx => rhs_magnitude == U32(0));
+    let lhs_is_inf = naga_expr!(ctx => lhs_magnitude == U32(0x7F800000));
+    let rhs_is_inf = naga_expr!(ctx => rhs_magnitude == U32(0x7F800000));
+    let res = naga_expr!(ctx => if (lhs_is_zero | rhs_is_inf) {sign << U32(31)} else {res});
+    let res = naga_expr!(ctx =>
+        if (lhs_is_inf | rhs_is_zero) {(sign << U32(31)) | U32(0x7F800000)} else {res}
+    );
+    let is_nan = naga_expr!(ctx =>
+        (((lhs_magnitude > U32(0x7F800000)) | (rhs_magnitude > U32(0x7F800000)))
+            | (lhs_is_zero & rhs_is_zero)) | (lhs_is_inf & rhs_is_inf)
+    );
+    let nan = gen_nan_result_bits(ctx, lhs_bits, rhs_bits);
+    let res = naga_expr!(ctx => if (is_nan) {nan} else {res});
+
+    naga_expr!(ctx => bitcast<f32>(res))
+}
+
 /// An implementation of f32s using the GPU's native f32 type
 pub(crate) struct NativeF32;
 impl F32Gen for NativeF32 {
@@ -493,9 +840,11 @@ impl F32Gen for NativeF32 {
         module: &mut naga::Module,
         requirements: super::f32_instance_gen::DefaultRequirements,
     ) -> build::Result<super::f32_instance_gen::Default> {
-        let expr = module.const_expressions.append_f32(0.0);
-        let res = module.constants.append_anonymous(*requirements.ty, expr);
-        Ok(res)
+        Ok(module.constants.get_or_append_scalar(
+            &mut module.const_expressions,
+            *requirements.ty,
+            0.0f32,
+        ))
     }
 
     fn gen_size_bytes(
@@ -785,7 +1134,8 @@ impl F32Gen for NativeF32 {
         };
         let mut ctx = BlockContext::from((module, function_handle));
 
-        let res = naga_expr!(&mut ctx => sign(lhs) * sign(rhs) * lhs);
+        // Only the sign bit is copied, so this must also work for zeros and NaNs
+        let res = naga_expr!(&mut ctx => bitcast<f32>((bitcast<u32>(lhs) & U32(0x7FFFFFFF)) | (bitcast<u32>(rhs) & U32(0x80000000))));
         ctx.result(res);
 
         Ok(function_handle)
@@ -800,11 +1150,14 @@ impl F32Gen for NativeF32 {
         };
         let mut ctx = BlockContext::from((module, function_handle));
 
-        let res = if requirements.fp_options.emulate_subnormals {
+        let res = if requirements.fp_options.round_to_nearest_even {
+            rounded_add(&mut ctx, lhs, rhs)
+        } else if requirements.fp_options.emulate_subnormals {
             subnormal_add(&mut ctx, *requirements.ty, lhs, rhs)
         } else {
             naga_expr!(&mut ctx => lhs + rhs)
         };
+        let res = maybe_canonicalize_nan(&mut ctx, requirements.fp_options, res);
         ctx.result(res);
 
         Ok(function_handle)
@@ -818,11 +1171,14 @@ impl F32Gen for NativeF32 {
         };
         let mut ctx = BlockContext::from((module, function_handle));
 
-        let res = if requirements.fp_options.emulate_subnormals {
+        let res = if requirements.fp_options.round_to_nearest_even {
+            rounded_sub(&mut ctx, lhs, rhs)
+        } else if requirements.fp_options.emulate_subnormals {
             subnormal_sub(&mut ctx, *requirements.ty, lhs, rhs)
         } else {
             naga_expr!(&mut ctx => lhs - rhs)
         };
+        let res = maybe_canonicalize_nan(&mut ctx, requirements.fp_options, res);
         ctx.result(res);
 
         Ok(function_handle)
@@ -836,11 +1192,14 @@ impl F32Gen for NativeF32 {
         };
         let mut ctx = BlockContext::from((module, function_handle));
 
-        let res = if requirements.fp_options.emulate_subnormals {
+        let res = if requirements.fp_options.round_to_nearest_even {
+            rounded_mul(&mut ctx, lhs, rhs)
+        } else if requirements.fp_options.emulate_subnormals {
             subnormal_mult(&mut ctx, *requirements.ty, lhs, rhs)
         } else {
             naga_expr!(&mut ctx => lhs * rhs)
         };
+        let res = maybe_canonicalize_nan(&mut ctx, requirements.fp_options, res);
         ctx.result(res);
 
         Ok(function_handle)
@@ -858,6 +1217,14 @@ impl F32Gen for NativeF32 {
         };
         let mut ctx = BlockContext::from((module, function_handle));
 
+        if requirements.fp_options.round_to_nearest_even {
+            let res = rounded_div(&mut ctx, lhs, rhs);
+            let res = maybe_canonicalize_nan(&mut ctx, requirements.fp_options, res);
+            ctx.result(res);
+
+            return Ok(function_handle);
+        }
+
         if requirements.fp_options.emulate_div_beyond_max {
             let lhs_exp = naga_expr!(&mut ctx => (bitcast<u32>(lhs) >> U32(23)) & U32(0xFF));
             let rhs_exp = naga_expr!(&mut ctx => (bitcast<u32>(rhs) >> U32(23)) & U32(0xFF));
@@ -869,6 +1236,7 @@ impl F32Gen for NativeF32 {
                 let lhs_scaled = scale_down_float(&mut ctx, lhs, 32);
                 let rhs_scaled = scale_down_float(&mut ctx, rhs, 32);
                 let res = naga_expr!(&mut ctx => lhs_scaled / rhs_scaled);
+                let res = maybe_canonicalize_nan(&mut ctx, requirements.fp_options, res);
                 ctx.result(res);
             });
 
@@ -876,6 +1244,7 @@ impl F32Gen for NativeF32 {
                 let lhs_scaled = scale_down_float(&mut ctx, lhs, 32);
                 let res_scaled = naga_expr!(&mut ctx => lhs_scaled / rhs);
                 let res = scale_up_float(&mut ctx, res_scaled, 32);
+                let res = maybe_canonicalize_nan(&mut ctx, requirements.fp_options, res);
                 ctx.result(res);
             });
 
@@ -883,6 +1252,7 @@ impl F32Gen for NativeF32 {
                 let rhs_scaled = scale_down_float(&mut ctx, rhs, 32);
                 let res_scaled = naga_expr!(&mut ctx => lhs / rhs_scaled);
                 let res = scale_up_float(&mut ctx, res_scaled, 32);
+                let res = maybe_canonicalize_nan(&mut ctx, requirements.fp_options, res);
                 ctx.result(res);
             });
         }
@@ -892,6 +1262,7 @@ impl F32Gen for NativeF32 {
         } else {
             naga_expr!(&mut ctx => lhs / rhs)
         };
+        let res = maybe_canonicalize_nan(&mut ctx, requirements.fp_options, res);
         ctx.result(res);
 
         Ok(function_handle)
@@ -931,12 +1302,275 @@ impl F32Gen for NativeF32 {
         Ok(function_handle)
     }
 
+    fn gen_reinterpret_from_i32(
+        module: &mut naga::Module,
+        requirements: f32_instance_gen::ReinterpretFromI32Requirements,
+    ) -> build::Result<f32_instance_gen::ReinterpretFromI32> {
+        let (function_handle, value) = declare_function! {
+            module => fn f32_reinterpret_i32(value: *requirements.i32_ty) -> *requirements.ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        let res = naga_expr!(&mut ctx => bitcast<f32>(value));
+        ctx.result(res);
+
+        Ok(function_handle)
+    }
+
+    fn gen_reinterpret_to_i32(
+        module: &mut naga::Module,
+        requirements: f32_instance_gen::ReinterpretToI32Requirements,
+    ) -> build::Result<f32_instance_gen::ReinterpretToI32> {
+        let (function_handle, value) = declare_function! {
+            module => fn i32_reinterpret_f32(value: *requirements.ty) -> *requirements.i32_ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        let res = naga_expr!(&mut ctx => bitcast<i32>(value));
+        ctx.result(res);
+
+        Ok(function_handle)
+    }
+
+    fn gen_trunc_i32_s(
+        module: &mut naga::Module,
+        requirements: f32_instance_gen::TruncI32SRequirements,
+    ) -> build::Result<f32_instance_gen::TruncI32S> {
+        let (function_handle, value) = declare_function! {
+            module => fn i32_trunc_f32_s(value: *requirements.ty) -> *requirements.i32_ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        let fits = naga_expr!(&mut ctx => (value >= F32(-2147483648.0)) & (value < F32(2147483648.0)));
+        let poison = naga_expr!(&mut ctx => I32(0));
+        emit_trunc_traps(&mut ctx, requirements.preamble, value, fits, poison);
+
+        let res = naga_expr!(&mut ctx => i32(value));
+        ctx.result(res);
+
+        Ok(function_handle)
+    }
+
+    fn gen_trunc_i32_u(
+        module: &mut naga::Module,
+        requirements: f32_instance_gen::TruncI32URequirements,
+    ) -> build::Result<f32_instance_gen::TruncI32U> {
+        let (function_handle, value) = declare_function! {
+            module => fn i32_trunc_f32_u(value: *requirements.ty) -> *requirements.i32_ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        // Anything above -1 truncates to 0 or above
+        let fits = naga_expr!(&mut ctx => (value > F32(-1.0)) & (value < F32(4294967296.0)));
+        let poison = naga_expr!(&mut ctx => I32(0));
+        emit_trunc_traps(&mut ctx, requirements.preamble, value, fits, poison);
+
+        // Negative values that fit are fractional, and casting them natively is undefined
+        let res = naga_expr!(&mut ctx => bitcast<i32>(u32(abs(value))));
+        ctx.result(res);
+
+        Ok(function_handle)
+    }
+
+    fn gen_trunc_i64_s(
+        module: &mut naga::Module,
+        requirements: f32_instance_gen::TruncI64SRequirements,
+    ) -> build::Result<f32_instance_gen::TruncI64S> {
+        let i64_ty = *requirements.i64_ty;
+        let (function_handle, value) = declare_function! {
+            module => fn i64_trunc_f32_s(value: *requirements.ty) -> i64_ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        let fits = naga_expr!(&mut ctx => (value >= F32(-9223372036854775808.0)) & (value < F32(9223372036854775808.0)));
+        let poison = naga_expr!(&mut ctx => i64_ty(U32(0), U32(0)));
+        emit_trunc_traps(&mut ctx, requirements.preamble, value, fits, poison);
+
+        let magnitude = naga_expr!(&mut ctx => abs(value));
+        let (high, low) = split_magnitude(&mut ctx, magnitude);
+        // Two's complement negation across both words
+        let neg_low = naga_expr!(&mut ctx => (~low) + U32(1));
+        let neg_high = naga_expr!(&mut ctx => (~high) + if (low == U32(0)) {U32(1)} else {U32(0)});
+        let is_negative = naga_expr!(&mut ctx => value < F32(0.0));
+        let high = naga_expr!(&mut ctx => if (is_negative) {neg_high} else {high});
+        let low = naga_expr!(&mut ctx => if (is_negative) {neg_low} else {low});
+        // Words are stored in little-endian order, matching memory
+        let res = naga_expr!(&mut ctx => i64_ty(low, high));
+        ctx.result(res);
+
+        Ok(function_handle)
+    }
+
+    fn gen_trunc_i64_u(
+        module: &mut naga::Module,
+        requirements: f32_instance_gen::TruncI64URequirements,
+    ) -> build::Result<f32_instance_gen::TruncI64U> {
+        let i64_ty = *requirements.i64_ty;
+        let (function_handle, value) = declare_function! {
+            module => fn i64_trunc_f32_u(value: *requirements.ty) -> i64_ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        let fits = naga_expr!(&mut ctx => (value > F32(-1.0)) & (value < F32(18446744073709551616.0)));
+        let poison = naga_expr!(&mut ctx => i64_ty(U32(0), U32(0)));
+        emit_trunc_traps(&mut ctx, requirements.preamble, value, fits, poison);
+
+        // Negative values that fit are fractional, so have a magnitude of 0 once truncated
+        let magnitude = naga_expr!(&mut ctx => abs(value));
+        let (high, low) = split_magnitude(&mut ctx, magnitude);
+        let res = naga_expr!(&mut ctx => i64_ty(low, high));
+        ctx.result(res);
+
+        Ok(function_handle)
+    }
+
+    fn gen_trunc_sat_i32_s(
+        module: &mut naga::Module,
+        requirements: f32_instance_gen::TruncSatI32SRequirements,
+    ) -> build::Result<f32_instance_gen::TruncSatI32S> {
+        let (function_handle, value) = declare_function! {
+            module => fn i32_trunc_sat_f32_s(value: *requirements.ty) -> *requirements.i32_ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        // Native casts are undefined out of range, so clamp first. NaN fails every comparison so becomes 0
+        let in_range = naga_expr!(&mut ctx => if (value < F32(2147483648.0)) {i32(value)} else {I32(i32::MAX)});
+        let res = naga_expr!(&mut ctx => if (value >= F32(-2147483648.0)) {in_range} else {
+            if (value < F32(0.0)) {I32(i32::MIN)} else {I32(0)}
+        });
+        ctx.result(res);
+
+        Ok(function_handle)
+    }
+
+    fn gen_trunc_sat_i32_u(
+        module: &mut naga::Module,
+        requirements: f32_instance_gen::TruncSatI32URequirements,
+    ) -> build::Result<f32_instance_gen::TruncSatI32U> {
+        let (function_handle, value) = declare_function! {
+            module => fn i32_trunc_sat_f32_u(value: *requirements.ty) -> *requirements.i32_ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        // Anything below 1 truncates to 0, including negatives and NaN
+        let in_range = naga_expr!(&mut ctx => if (value < F32(4294967296.0)) {u32(value)} else {U32(u32::MAX)});
+        let res = naga_expr!(&mut ctx => if (value >= F32(1.0)) {in_range} else {U32(0)});
+        let res = naga_expr!(&mut ctx => bitcast<i32>(res));
+        ctx.result(res);
+
+        Ok(function_handle)
+    }
+
+    fn gen_trunc_sat_i64_s(
+        module: &mut naga::Module,
+        requirements: f32_instance_gen::TruncSatI64SRequirements,
+    ) -> build::Result<f32_instance_gen::TruncSatI64S> {
+        let i64_ty = *requirements.i64_ty;
+        let (function_handle, value) = declare_function! {
+            module => fn i64_trunc_sat_f32_s(value: *requirements.ty) -> i64_ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        let magnitude = naga_expr!(&mut ctx => abs(value));
+        let (high, low) = split_magnitude(&mut ctx, magnitude);
+        // Two's complement negation across both words
+        let neg_low = naga_expr!(&mut ctx => (~low) + U32(1));
+        let neg_high = naga_expr!(&mut ctx => (~high) + if (low == U32(0)) {U32(1)} else {U32(0)});
+        let is_negative = naga_expr!(&mut ctx => value < F32(0.0));
+        let high = naga_expr!(&mut ctx => if (is_negative) {neg_high} else {high});
+        let low = naga_expr!(&mut ctx => if (is_negative) {neg_low} else {low});
+
+        // Clamp to [i64::MIN, i64::MAX], with NaN failing every comparison so becoming 0
+        let in_range = naga_expr!(&mut ctx => (value >= F32(-9223372036854775808.0)) & (value < F32(9223372036854775808.0)));
+        let high = naga_expr!(&mut ctx => if (in_range) {high} else {
+            if (value > F32(0.0)) {U32(0x7FFFFFFF)} else {
+                if (value < F32(0.0)) {U32(0x80000000)} else {U32(0)}
+            }
+        });
+        let low = naga_expr!(&mut ctx => if (in_range) {low} else {
+            if (value > F32(0.0)) {U32(0xFFFFFFFF)} else {U32(0)}
+        });
+        // Words are stored in little-endian order, matching memory
+        let res = naga_expr!(&mut ctx => i64_ty(low, high));
+        ctx.result(res);
+
+        Ok(function_handle)
+    }
+
+    fn gen_trunc_sat_i64_u(
+        module: &mut naga::Module,
+        requirements: f32_instance_gen::TruncSatI64URequirements,
+    ) -> build::Result<f32_instance_gen::TruncSatI64U> {
+        let i64_ty = *requirements.i64_ty;
+        let (function_handle, value) = declare_function! {
+            module => fn i64_trunc_sat_f32_u(value: *requirements.ty) -> i64_ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        let (high, low) = split_magnitude(&mut ctx, value);
+
+        // Anything below 1 truncates to 0, including negatives and NaN
+        let is_positive = naga_expr!(&mut ctx => value >= F32(1.0));
+        let is_overflow = naga_expr!(&mut ctx => value >= F32(18446744073709551616.0));
+        let high = naga_expr!(&mut ctx => if (is_overflow) {U32(u32::MAX)} else {high});
+        let low = naga_expr!(&mut ctx => if (is_overflow) {U32(u32::MAX)} else {low});
+        let high = naga_expr!(&mut ctx => if (is_positive) {high} else {U32(0)});
+        let low = naga_expr!(&mut ctx => if (is_positive) {low} else {U32(0)});
+        // Words are stored in little-endian order, matching memory
+        let res = naga_expr!(&mut ctx => i64_ty(low, high));
+        ctx.result(res);
+
+        Ok(function_handle)
+    }
+
     impl_native_bool_binexp! { f32_instance_gen, f32, lt; < }
     impl_native_bool_binexp! { f32_instance_gen, f32, le; <= }
     impl_native_bool_binexp! { f32_instance_gen, f32, gt; > }
     impl_native_bool_binexp! { f32_instance_gen, f32, ge; >= }
 }
 
+/// Traps with `BadConversionToInteger` if the value is NaN, or `IntegerOverflow` if its truncation doesn't fit in the
+/// target integer, returning the poison value early in either case so the native cast is never reached
+fn emit_trunc_traps(
+    ctx: &mut BlockContext<'_>,
+    preamble: &PreambleObjects,
+    value: naga::Handle<naga::Expression>,
+    fits: naga::Handle<naga::Expression>,
+    poison: naga::Handle<naga::Expression>,
+) {
+    let is_nan = naga_expr!(ctx => (bitcast<u32>(value) & U32(0x7FFFFFFF)) > U32(0x7f800000));
+    ctx.test(is_nan).then(|mut ctx| {
+        preamble.trap_values.emit_set_trap(
+            &mut ctx,
+            Trap::BadConversionToInteger,
+            preamble.trap_state,
+        );
+        ctx.result(poison);
+    });
+
+    let is_overflowing = naga_expr!(ctx => !fits);
+    ctx.test(is_overflowing).then(|mut ctx| {
+        preamble
+            .trap_values
+            .emit_set_trap(&mut ctx, Trap::IntegerOverflow, preamble.trap_state);
+        ctx.result(poison);
+    });
+}
+
+/// Splits the integer part of a non-negative float below 2^64 into its high and low words. Every float this
+/// large is an integer, and dividing by a power of two is exact, so no rounding occurs.
+fn split_magnitude(
+    ctx: &mut BlockContext<'_>,
+    magnitude: naga::Handle<naga::Expression>,
+) -> (naga::Handle<naga::Expression>, naga::Handle<naga::Expression>) {
+    let high = naga_expr!(ctx => trunc(magnitude * F32(f32::powi(2.0, -32))));
+    let low = naga_expr!(ctx => trunc(magnitude - (high * F32(f32::powi(2.0, 32)))));
+    let high = naga_expr!(ctx => u32(high));
+    let low = naga_expr!(ctx => u32(low));
+
+    (high, low)
+}
+
 // fn<buffer>(word_address: u32) -> f32
 fn gen_read(
     module: &mut naga::Module,
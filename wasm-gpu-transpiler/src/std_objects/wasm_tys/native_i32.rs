@@ -1,6 +1,5 @@
 use crate::{build, std_objects::preamble_objects_gen};
-use naga_ext::BlockContext;
-use naga_ext::{declare_function, naga_expr, ConstantsExt, ExpressionsExt, TypesExt};
+use naga_ext::prelude::*;
 use wasmtime_environ::Trap;
 
 use super::{i32_instance_gen, I32Gen};
@@ -19,8 +18,11 @@ impl I32Gen for NativeI32 {
         module: &mut naga::Module,
         requirements: super::i32_instance_gen::DefaultRequirements,
     ) -> build::Result<super::i32_instance_gen::Default> {
-        let expr = module.const_expressions.append_i32(0);
-        Ok(module.constants.append_anonymous(*requirements.ty, expr))
+        Ok(module.constants.get_or_append_scalar(
+            &mut module.const_expressions,
+            *requirements.ty,
+            0i32,
+        ))
     }
 
     fn gen_size_bytes(
@@ -192,6 +194,9 @@ impl I32Gen for NativeI32 {
                 Trap::IntegerDivisionByZero,
                 requirements.preamble.trap_state,
             );
+            // Dividing by zero natively is undefined, so bail out with a poison value
+            let poison = naga_expr!(&mut ctx => I32(0));
+            ctx.result(poison);
         });
 
         // Overflow test
@@ -202,6 +207,8 @@ impl I32Gen for NativeI32 {
                 Trap::IntegerOverflow,
                 requirements.preamble.trap_state,
             );
+            let poison = naga_expr!(&mut ctx => I32(0));
+            ctx.result(poison);
         });
 
         let res = naga_expr!(&mut ctx => lhs/rhs);
@@ -227,6 +234,9 @@ impl I32Gen for NativeI32 {
                 Trap::IntegerDivisionByZero,
                 requirements.preamble.trap_state,
             );
+            // Dividing by zero natively is undefined, so bail out with a poison value
+            let poison = naga_expr!(&mut ctx => I32(0));
+            ctx.result(poison);
         });
 
         let res = naga_expr!(&mut ctx => ((lhs as Uint)/(rhs as Uint))as Sint);
@@ -252,6 +262,16 @@ impl I32Gen for NativeI32 {
                 Trap::IntegerDivisionByZero,
                 requirements.preamble.trap_state,
             );
+            // Dividing by zero natively is undefined, so bail out with a poison value
+            let poison = naga_expr!(&mut ctx => I32(0));
+            ctx.result(poison);
+        });
+
+        // `i32::MIN % -1` overflows natively, but wasm defines the remainder of any division by -1 to be 0
+        let is_negative_one = naga_expr!(&mut ctx => rhs == I32(-1));
+        ctx.test(is_negative_one).then(|mut ctx| {
+            let zero = naga_expr!(&mut ctx => I32(0));
+            ctx.result(zero);
         });
 
         let res = naga_expr!(&mut ctx => lhs%rhs);
@@ -277,6 +297,9 @@ impl I32Gen for NativeI32 {
                 Trap::IntegerDivisionByZero,
                 requirements.preamble.trap_state,
             );
+            // Dividing by zero natively is undefined, so bail out with a poison value
+            let poison = naga_expr!(&mut ctx => I32(0));
+            ctx.result(poison);
         });
 
         let res = naga_expr!(&mut ctx => ((lhs as Uint)%(rhs as Uint))as Sint);
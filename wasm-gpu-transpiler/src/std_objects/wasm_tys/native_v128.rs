@@ -0,0 +1,263 @@
+use crate::build;
+use naga_ext::prelude::*;
+
+use super::polyfill_v128::{packed_add, packed_mul_16, packed_sub, LaneWords, PolyfillV128};
+use super::{v128_instance_gen, V128Gen};
+
+/// Splats a constant across every lane of a vector, so that it can be combined with whole vectors
+fn splat(
+    ctx: &mut BlockContext<'_>,
+    size: naga::VectorSize,
+    value: u32,
+) -> naga::Handle<naga::Expression> {
+    let value = naga_expr!(ctx => U32(value));
+    ctx.append_expr(naga::Expression::Splat { size, value })
+}
+
+/// Declares `fn(lhs: v128, rhs: v128) -> v128`, built by operating on both vectors at once
+fn gen_vector_binary(
+    module: &mut naga::Module,
+    v128_ty: naga::Handle<naga::Type>,
+    name: &str,
+    make: impl FnOnce(
+        &mut BlockContext<'_>,
+        naga::Handle<naga::Expression>,
+        naga::Handle<naga::Expression>,
+    ) -> naga::Handle<naga::Expression>,
+) -> build::Result<naga::Handle<naga::Function>> {
+    let (function_handle, lhs, rhs) = declare_function! {
+        module => fn {format!("v128_{}", name)}(lhs: v128_ty, rhs: v128_ty) -> v128_ty
+    };
+    let mut ctx = BlockContext::from((module, function_handle));
+
+    let res = make(&mut ctx, lhs, rhs);
+    ctx.result(res);
+
+    Ok(function_handle)
+}
+
+/// Declares `fn(lhs: v128, rhs: v128) -> v128`, operating on the pairs of 64-bit lanes as two `vec2<u32>`s of low
+/// words and high words
+fn gen_i64_vector_binary(
+    module: &mut naga::Module,
+    v128_ty: naga::Handle<naga::Type>,
+    name: &str,
+    make: impl FnOnce(&mut BlockContext<'_>, LaneWords, LaneWords) -> LaneWords,
+) -> build::Result<naga::Handle<naga::Function>> {
+    gen_vector_binary(module, v128_ty, name, |ctx, lhs, rhs| {
+        // Lanes are little-endian, so the low word comes first
+        let lhs_words = (naga_expr!(ctx => lhs.xz), naga_expr!(ctx => lhs.yw));
+        let rhs_words = (naga_expr!(ctx => rhs.xz), naga_expr!(ctx => rhs.yw));
+        let (low, high) = make(ctx, lhs_words, rhs_words);
+        naga_expr!(ctx => v128_ty((low[const 0]), (high[const 0]), (low[const 1]), (high[const 1])))
+    })
+}
+
+macro_rules! impl_vector_binexp {
+    ($op_name:ident; $op:tt) => {
+        paste::paste! {
+            fn [< gen_ $op_name >](
+                module: &mut naga::Module,
+                requirements: v128_instance_gen::[< $op_name:camel Requirements >],
+            ) -> build::Result<v128_instance_gen::[< $op_name:camel >]> {
+                gen_vector_binary(module, *requirements.ty, stringify!($op_name), |ctx, lhs, rhs| {
+                    naga_expr!(ctx => lhs $op rhs)
+                })
+            }
+        }
+    };
+}
+
+macro_rules! impl_packed_vector_binexp {
+    ($op_name:ident; $packed_fn:ident; $high_bits:expr) => {
+        paste::paste! {
+            fn [< gen_ $op_name >](
+                module: &mut naga::Module,
+                requirements: v128_instance_gen::[< $op_name:camel Requirements >],
+            ) -> build::Result<v128_instance_gen::[< $op_name:camel >]> {
+                gen_vector_binary(module, *requirements.ty, stringify!($op_name), |ctx, lhs, rhs| {
+                    $packed_fn(ctx, lhs, rhs, $high_bits, &|ctx, value| splat(ctx, naga::VectorSize::Quad, value))
+                })
+            }
+        }
+    };
+}
+
+macro_rules! impl_f32_vector_binexp {
+    ($op_name:ident; $op:tt; $($emulate_option:ident)|*) => {
+        paste::paste! {
+            fn [< gen_ $op_name >](
+                module: &mut naga::Module,
+                requirements: v128_instance_gen::[< $op_name:camel Requirements >],
+            ) -> build::Result<v128_instance_gen::[< $op_name:camel >]> {
+                // Native float vectors don't give the guarantees we need if we are emulating, so go lane by lane
+                if $(requirements.fp_options.$emulate_option)||* {
+                    return PolyfillV128::[< gen_ $op_name >](module, requirements);
+                }
+
+                gen_vector_binary(module, *requirements.ty, stringify!($op_name), |ctx, lhs, rhs| {
+                    naga_expr!(ctx => bitcast<u32>((bitcast<f32>(lhs)) $op (bitcast<f32>(rhs))))
+                })
+            }
+        }
+    };
+}
+
+/// An implementation of v128s as a native `vec4<u32>`, where lane-wise operations are performed on whole vectors
+/// rather than word by word. Float lanes still fall back to [`PolyfillV128`] when emulating floating point
+/// behaviour or canonicalizing NaNs, since native vector float operations can't be corrected lane by lane.
+pub(crate) struct NativeV128;
+impl V128Gen for NativeV128 {
+    fn gen_ty(
+        module: &mut naga::Module,
+        _requirements: v128_instance_gen::TyRequirements,
+    ) -> build::Result<v128_instance_gen::Ty> {
+        Ok(module
+            .types
+            .insert_vecn(naga::Scalar::U32, naga::VectorSize::Quad))
+    }
+
+    fn gen_default(
+        module: &mut naga::Module,
+        requirements: v128_instance_gen::DefaultRequirements,
+    ) -> build::Result<v128_instance_gen::Default> {
+        PolyfillV128::gen_default(module, requirements)
+    }
+
+    fn gen_size_bytes(
+        module: &mut naga::Module,
+        requirements: v128_instance_gen::SizeBytesRequirements,
+    ) -> build::Result<v128_instance_gen::SizeBytes> {
+        PolyfillV128::gen_size_bytes(module, requirements)
+    }
+
+    fn gen_make_const(
+        module: &mut naga::Module,
+        requirements: v128_instance_gen::MakeConstRequirements,
+    ) -> build::Result<v128_instance_gen::MakeConst> {
+        PolyfillV128::gen_make_const(module, requirements)
+    }
+
+    fn gen_read_input(
+        module: &mut naga::Module,
+        requirements: v128_instance_gen::ReadInputRequirements,
+    ) -> build::Result<v128_instance_gen::ReadInput> {
+        PolyfillV128::gen_read_input(module, requirements)
+    }
+
+    fn gen_write_output(
+        module: &mut naga::Module,
+        requirements: v128_instance_gen::WriteOutputRequirements,
+    ) -> build::Result<v128_instance_gen::WriteOutput> {
+        PolyfillV128::gen_write_output(module, requirements)
+    }
+
+    fn gen_read_memory(
+        module: &mut naga::Module,
+        requirements: v128_instance_gen::ReadMemoryRequirements,
+    ) -> build::Result<v128_instance_gen::ReadMemory> {
+        PolyfillV128::gen_read_memory(module, requirements)
+    }
+
+    fn gen_write_memory(
+        module: &mut naga::Module,
+        requirements: v128_instance_gen::WriteMemoryRequirements,
+    ) -> build::Result<v128_instance_gen::WriteMemory> {
+        PolyfillV128::gen_write_memory(module, requirements)
+    }
+
+    fn gen_not(
+        module: &mut naga::Module,
+        requirements: v128_instance_gen::NotRequirements,
+    ) -> build::Result<v128_instance_gen::Not> {
+        let v128_ty = *requirements.ty;
+        let (function_handle, value) = declare_function! {
+            module => fn v128_not(value: v128_ty) -> v128_ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        let res = naga_expr!(&mut ctx => ~value);
+        ctx.result(res);
+
+        Ok(function_handle)
+    }
+
+    impl_vector_binexp! { and; & }
+    impl_vector_binexp! { or; | }
+    impl_vector_binexp! { xor; ^ }
+
+    fn gen_and_not(
+        module: &mut naga::Module,
+        requirements: v128_instance_gen::AndNotRequirements,
+    ) -> build::Result<v128_instance_gen::AndNot> {
+        gen_vector_binary(
+            module,
+            *requirements.ty,
+            "and_not",
+            |ctx, lhs, rhs| naga_expr!(ctx => lhs & (~rhs)),
+        )
+    }
+
+    impl_packed_vector_binexp! { i8x16_add; packed_add; 0x80808080 }
+    impl_packed_vector_binexp! { i8x16_sub; packed_sub; 0x80808080 }
+    impl_packed_vector_binexp! { i16x8_add; packed_add; 0x80008000 }
+    impl_packed_vector_binexp! { i16x8_sub; packed_sub; 0x80008000 }
+
+    fn gen_i16x8_mul(
+        module: &mut naga::Module,
+        requirements: v128_instance_gen::I16x8MulRequirements,
+    ) -> build::Result<v128_instance_gen::I16x8Mul> {
+        gen_vector_binary(module, *requirements.ty, "i16x8_mul", |ctx, lhs, rhs| {
+            packed_mul_16(ctx, lhs, rhs, &|ctx, value| {
+                splat(ctx, naga::VectorSize::Quad, value)
+            })
+        })
+    }
+
+    impl_vector_binexp! { i32x4_add; + }
+    impl_vector_binexp! { i32x4_sub; - }
+    impl_vector_binexp! { i32x4_mul; * }
+
+    fn gen_i64x2_add(
+        module: &mut naga::Module,
+        requirements: v128_instance_gen::I64x2AddRequirements,
+    ) -> build::Result<v128_instance_gen::I64x2Add> {
+        gen_i64_vector_binary(
+            module,
+            *requirements.ty,
+            "i64x2_add",
+            |ctx, (lhs_low, lhs_high), (rhs_low, rhs_high)| {
+                let one = splat(ctx, naga::VectorSize::Bi, 1);
+                let zero = splat(ctx, naga::VectorSize::Bi, 0);
+                let low = naga_expr!(ctx => lhs_low + rhs_low);
+                let carry = naga_expr!(ctx => if (low < lhs_low) {one} else {zero});
+                let high = naga_expr!(ctx => lhs_high + (rhs_high + carry));
+                (low, high)
+            },
+        )
+    }
+
+    fn gen_i64x2_sub(
+        module: &mut naga::Module,
+        requirements: v128_instance_gen::I64x2SubRequirements,
+    ) -> build::Result<v128_instance_gen::I64x2Sub> {
+        gen_i64_vector_binary(
+            module,
+            *requirements.ty,
+            "i64x2_sub",
+            |ctx, (lhs_low, lhs_high), (rhs_low, rhs_high)| {
+                let one = splat(ctx, naga::VectorSize::Bi, 1);
+                let zero = splat(ctx, naga::VectorSize::Bi, 0);
+                let low = naga_expr!(ctx => lhs_low - rhs_low);
+                let borrow = naga_expr!(ctx => if (lhs_low < rhs_low) {one} else {zero});
+                let high = naga_expr!(ctx => lhs_high - (rhs_high + borrow));
+                (low, high)
+            },
+        )
+    }
+
+    impl_f32_vector_binexp! { f32x4_add; +; emulate_subnormals | canonicalize_nans | round_to_nearest_even }
+    impl_f32_vector_binexp! { f32x4_sub; -; emulate_subnormals | canonicalize_nans | round_to_nearest_even }
+    impl_f32_vector_binexp! { f32x4_mul; *; emulate_subnormals | canonicalize_nans | round_to_nearest_even }
+    impl_f32_vector_binexp! { f32x4_div; /; emulate_subnormals | emulate_div_beyond_max | canonicalize_nans | round_to_nearest_even }
+}
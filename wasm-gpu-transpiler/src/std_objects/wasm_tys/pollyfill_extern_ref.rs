@@ -1,6 +1,5 @@
 use crate::{build, std_objects::preamble_objects_gen};
-use naga_ext::BlockContext;
-use naga_ext::{declare_function, naga_expr, ConstantsExt, ExpressionsExt};
+use naga_ext::prelude::*;
 
 use super::{extern_ref_instance_gen, ExternRefGen};
 
@@ -26,8 +25,11 @@ impl ExternRefGen for PolyfillExternRef {
         module: &mut naga::Module,
         requirements: super::extern_ref_instance_gen::DefaultRequirements,
     ) -> build::Result<super::extern_ref_instance_gen::Default> {
-        let expr = module.const_expressions.append_u32(u32::MAX);
-        Ok(module.constants.append_anonymous(*requirements.ty, expr))
+        Ok(module.constants.get_or_append_scalar(
+            &mut module.const_expressions,
+            *requirements.ty,
+            u32::MAX,
+        ))
     }
 
     fn gen_size_bytes(
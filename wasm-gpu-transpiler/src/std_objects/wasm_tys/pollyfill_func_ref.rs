@@ -1,6 +1,5 @@
 use crate::{build, std_objects::preamble_objects_gen};
-use naga_ext::BlockContext;
-use naga_ext::{declare_function, naga_expr, ConstantsExt, ExpressionsExt, TypesExt};
+use naga_ext::prelude::*;
 
 use super::{func_ref_instance_gen, FuncRefGen};
 
@@ -18,8 +17,11 @@ impl FuncRefGen for PolyfillFuncRef {
         module: &mut naga::Module,
         requirements: super::func_ref_instance_gen::DefaultRequirements,
     ) -> build::Result<super::func_ref_instance_gen::Default> {
-        let expr = module.const_expressions.append_u32(u32::MAX);
-        Ok(module.constants.append_anonymous(*requirements.ty, expr))
+        Ok(module.constants.get_or_append_scalar(
+            &mut module.const_expressions,
+            *requirements.ty,
+            u32::MAX,
+        ))
     }
 
     fn gen_size_bytes(
@@ -1,6 +1,10 @@
-use super::{f64_instance_gen, F64Gen};
-use crate::{build, std_objects::preamble_objects_gen};
-use naga_ext::{declare_function, naga_expr, BlockContext, ConstantsExt, TypesExt};
+use super::{f64_instance_gen, native_f32::gen_round_pack_bits, F64Gen};
+use crate::{
+    build,
+    std_objects::{preamble_objects_gen, PreambleObjects},
+};
+use naga_ext::prelude::*;
+use wasmtime_environ::Trap;
 
 #[derive(Clone)]
 struct FrexpParts {
@@ -12,13 +16,14 @@ struct FrexpParts {
 
 impl FrexpParts {
     fn from_uvec2(ctx: &mut BlockContext<'_>, value: naga::Handle<naga::Expression>) -> Self {
-        let word1 = naga_expr!(ctx => value[const 0]);
-        let word2 = naga_expr!(ctx => value[const 1]);
+        // Words are stored little-endian, as they are in memory
+        let low_word = naga_expr!(ctx => value[const 0]);
+        let high_word = naga_expr!(ctx => value[const 1]);
 
-        let sign = naga_expr!(ctx => word1 >> U32(31));
-        let exponent = naga_expr!(ctx => (word1 >> U32(20)) & U32((1 << 11) - 1));
-        let upper_magnitude = naga_expr!(ctx => word1 & U32((1 << 20) - 1));
-        let lower_magnitude = naga_expr!(ctx => word2);
+        let sign = naga_expr!(ctx => high_word >> U32(31));
+        let exponent = naga_expr!(ctx => (high_word >> U32(20)) & U32((1 << 11) - 1));
+        let upper_magnitude = naga_expr!(ctx => high_word & U32((1 << 20) - 1));
+        let lower_magnitude = naga_expr!(ctx => low_word);
 
         Self {
             sign,
@@ -37,24 +42,69 @@ impl FrexpParts {
         todo!()
     }
 
-    /// Generates code that adds two floats
-    fn gen_add(self, rhs_frexp: FrexpParts, ctx: &mut BlockContext<'_>) -> FrexpParts {
-        self // TODO: This
+    /// Chooses between the parts of two floats
+    fn select(
+        ctx: &mut BlockContext<'_>,
+        condition: naga::Handle<naga::Expression>,
+        accept: &FrexpParts,
+        reject: &FrexpParts,
+    ) -> Self {
+        let mut select = |accept, reject| naga_expr!(ctx => if (condition) {accept} else {reject});
+        Self {
+            sign: select(accept.sign, reject.sign),
+            exponent: select(accept.exponent, reject.exponent),
+            upper_magnitude: select(accept.upper_magnitude, reject.upper_magnitude),
+            lower_magnitude: select(accept.lower_magnitude, reject.lower_magnitude),
+        }
     }
 
-    /// Generates code that subtracts two floats
-    fn gen_sub(self, rhs_frexp: FrexpParts, ctx: &mut BlockContext<'_>) -> FrexpParts {
-        self // TODO: This
+    fn gen_is_magnitude_zero(&self, ctx: &mut BlockContext<'_>) -> naga::Handle<naga::Expression> {
+        naga_expr!(ctx => ({self.upper_magnitude} == U32(0)) & ({self.lower_magnitude} == U32(0)))
     }
 
-    /// Generates code that multiplies two floats
-    fn gen_mul(self, rhs_frexp: FrexpParts, ctx: &mut BlockContext<'_>) -> FrexpParts {
-        self // TODO: This
+    fn gen_is_nan(&self, ctx: &mut BlockContext<'_>) -> naga::Handle<naga::Expression> {
+        let is_magnitude_zero = self.gen_is_magnitude_zero(ctx);
+        naga_expr!(ctx => ({self.exponent} == U32(0x7FF)) & (!is_magnitude_zero))
     }
 
-    /// Generates code that divides two floats
-    fn gen_div(self, rhs_frexp: FrexpParts, ctx: &mut BlockContext<'_>) -> FrexpParts {
-        self // TODO: This
+    fn gen_is_inf(&self, ctx: &mut BlockContext<'_>) -> naga::Handle<naga::Expression> {
+        let is_magnitude_zero = self.gen_is_magnitude_zero(ctx);
+        naga_expr!(ctx => ({self.exponent} == U32(0x7FF)) & is_magnitude_zero)
+    }
+
+    fn gen_is_zero(&self, ctx: &mut BlockContext<'_>) -> naga::Handle<naga::Expression> {
+        let is_magnitude_zero = self.gen_is_magnitude_zero(ctx);
+        naga_expr!(ctx => ({self.exponent} == U32(0)) & is_magnitude_zero)
+    }
+
+    /// Gives the significand, including the implicit leading bit of normal values, and the biased exponent that it
+    /// is scaled by. Subnormals have no implicit bit and are scaled by the smallest normal exponent, 1.
+    fn gen_significand(
+        &self,
+        ctx: &mut BlockContext<'_>,
+    ) -> (Wide, naga::Handle<naga::Expression>) {
+        let is_subnormal = naga_expr!(ctx => {self.exponent} == U32(0));
+        let implicit_bit = naga_expr!(ctx => if (is_subnormal) {U32(0)} else {U32(1 << 20)});
+        let high = naga_expr!(ctx => {self.upper_magnitude} | implicit_bit);
+        let exponent = naga_expr!(ctx => if (is_subnormal) {U32(1)} else {{self.exponent}});
+
+        (Wide::new(high, self.lower_magnitude), exponent)
+    }
+
+    /// Gives the significand shifted so that its leading bit is bit 52, even for subnormals, and the signed biased
+    /// exponent that it is then scaled by.
+    fn gen_normalized_significand(
+        &self,
+        ctx: &mut BlockContext<'_>,
+    ) -> (Wide, naga::Handle<naga::Expression>) {
+        let (significand, exponent) = self.gen_significand(ctx);
+
+        let leading_zeros = significand.gen_leading_zeros(ctx);
+        let shift = naga_expr!(ctx => leading_zeros - U32(11));
+        let significand = significand.gen_shl(shift, ctx);
+        let exponent = naga_expr!(ctx => (bitcast<i32>(exponent)) - (bitcast<i32>(shift)));
+
+        (significand, exponent)
     }
 
     /// Generates code that finds the min of two floats
@@ -100,12 +150,74 @@ impl FrexpParts {
         self // TODO: This
     }
 
+    /// The bits of the float other than the sign, which order the same way as the magnitudes of non-NaN values
+    fn gen_magnitude(&self, ctx: &mut BlockContext<'_>) -> Wide {
+        let high = naga_expr!(ctx => ({self.exponent} << U32(20)) | {self.upper_magnitude});
+        Wide::new(high, self.lower_magnitude)
+    }
+
+    /// Gives whether neither float is NaN, since any comparison with a NaN is false other than `ne`
+    fn gen_is_ordered(
+        &self,
+        rhs_frexp: &FrexpParts,
+        ctx: &mut BlockContext<'_>,
+    ) -> naga::Handle<naga::Expression> {
+        let lhs_is_nan = self.gen_is_nan(ctx);
+        let rhs_is_nan = rhs_frexp.gen_is_nan(ctx);
+        naga_expr!(ctx => !(lhs_is_nan | rhs_is_nan))
+    }
+
+    fn gen_eq(
+        self,
+        rhs_frexp: FrexpParts,
+        ctx: &mut BlockContext<'_>,
+    ) -> naga::Handle<naga::Expression> {
+        let is_ordered = self.gen_is_ordered(&rhs_frexp, ctx);
+        let lhs_magnitude = self.gen_magnitude(ctx);
+        let rhs_magnitude = rhs_frexp.gen_magnitude(ctx);
+
+        // Positive and negative zero are equal
+        let lhs_is_zero = lhs_magnitude.gen_is_zero(ctx);
+        let rhs_is_zero = rhs_magnitude.gen_is_zero(ctx);
+        let identical = naga_expr!(ctx =>
+            ({self.sign} == {rhs_frexp.sign})
+                & (({lhs_magnitude.high} == {rhs_magnitude.high}) & ({lhs_magnitude.low} == {rhs_magnitude.low}))
+        );
+        naga_expr!(ctx => is_ordered & (identical | (lhs_is_zero & rhs_is_zero)))
+    }
+
+    fn gen_ne(
+        self,
+        rhs_frexp: FrexpParts,
+        ctx: &mut BlockContext<'_>,
+    ) -> naga::Handle<naga::Expression> {
+        let is_eq = self.gen_eq(rhs_frexp, ctx);
+        naga_expr!(ctx => !is_eq)
+    }
+
     fn gen_lt(
         self,
         rhs_frexp: FrexpParts,
         ctx: &mut BlockContext<'_>,
     ) -> naga::Handle<naga::Expression> {
-        ctx.literal_expr_from(0) // TODO: This
+        let is_ordered = self.gen_is_ordered(&rhs_frexp, ctx);
+        let lhs_magnitude = self.gen_magnitude(ctx);
+        let rhs_magnitude = rhs_frexp.gen_magnitude(ctx);
+        let lhs_is_zero = lhs_magnitude.gen_is_zero(ctx);
+        let rhs_is_zero = rhs_magnitude.gen_is_zero(ctx);
+
+        // Negative values order by decreasing magnitude, and are less than all positive values other than zero
+        let lhs_is_negative = naga_expr!(ctx => {self.sign} == U32(1));
+        let rhs_is_negative = naga_expr!(ctx => {rhs_frexp.sign} == U32(1));
+        let smaller_magnitude = lhs_magnitude.gen_lt(rhs_magnitude, ctx);
+        let larger_magnitude = rhs_magnitude.gen_lt(lhs_magnitude, ctx);
+        let both_zero = naga_expr!(ctx => lhs_is_zero & rhs_is_zero);
+        let differing_signs = naga_expr!(ctx => lhs_is_negative & (!(rhs_is_negative | both_zero)));
+        let both_positive =
+            naga_expr!(ctx => (!lhs_is_negative) & ((!rhs_is_negative) & smaller_magnitude));
+        let both_negative =
+            naga_expr!(ctx => lhs_is_negative & (rhs_is_negative & larger_magnitude));
+        naga_expr!(ctx => is_ordered & (differing_signs | (both_positive | both_negative)))
     }
 
     fn gen_le(
@@ -113,7 +225,9 @@ impl FrexpParts {
         rhs_frexp: FrexpParts,
         ctx: &mut BlockContext<'_>,
     ) -> naga::Handle<naga::Expression> {
-        ctx.literal_expr_from(0) // TODO: This
+        let is_lt = self.clone().gen_lt(rhs_frexp.clone(), ctx);
+        let is_eq = self.gen_eq(rhs_frexp, ctx);
+        naga_expr!(ctx => is_lt | is_eq)
     }
 
     fn gen_gt(
@@ -121,7 +235,7 @@ impl FrexpParts {
         rhs_frexp: FrexpParts,
         ctx: &mut BlockContext<'_>,
     ) -> naga::Handle<naga::Expression> {
-        ctx.literal_expr_from(0) // TODO: This
+        rhs_frexp.gen_lt(self, ctx)
     }
 
     fn gen_ge(
@@ -129,7 +243,7 @@ impl FrexpParts {
         rhs_frexp: FrexpParts,
         ctx: &mut BlockContext<'_>,
     ) -> naga::Handle<naga::Expression> {
-        ctx.literal_expr_from(0) // TODO: This
+        rhs_frexp.gen_le(self, ctx)
     }
 
     /// Combines all of the component expressions into a 64 bit float, possibly losing subnormals and handling inf/nans badly
@@ -159,8 +273,267 @@ impl FrexpParts {
     ) -> naga::Handle<naga::Expression> {
         let res_high = naga_expr!(ctx => ({self.sign} << U32(31)) | ({self.exponent} << U32(20)) | {self.upper_magnitude});
         let res_low = naga_expr!(ctx => {self.lower_magnitude});
-        naga_expr!(ctx => uvec2_ty(res_high, res_low))
+        naga_expr!(ctx => uvec2_ty(res_low, res_high))
+    }
+}
+
+/// A 64 bit unsigned integer held as its high and low words, used for the significands of emulated f64s while
+/// they are operated on
+#[derive(Clone, Copy)]
+struct Wide {
+    high: naga::Handle<naga::Expression>,
+    low: naga::Handle<naga::Expression>,
+}
+
+impl Wide {
+    fn new(high: naga::Handle<naga::Expression>, low: naga::Handle<naga::Expression>) -> Self {
+        Self { high, low }
+    }
+
+    fn constant(ctx: &mut BlockContext<'_>, value: u64) -> Self {
+        let high = naga_expr!(ctx => U32((value >> 32) as u32));
+        let low = naga_expr!(ctx => U32(value as u32));
+        Self { high, low }
+    }
+
+    fn select(
+        ctx: &mut BlockContext<'_>,
+        condition: naga::Handle<naga::Expression>,
+        accept: Wide,
+        reject: Wide,
+    ) -> Self {
+        let high = naga_expr!(ctx => if (condition) {{accept.high}} else {{reject.high}});
+        let low = naga_expr!(ctx => if (condition) {{accept.low}} else {{reject.low}});
+        Self { high, low }
+    }
+
+    fn gen_is_zero(self, ctx: &mut BlockContext<'_>) -> naga::Handle<naga::Expression> {
+        naga_expr!(ctx => ({self.high} == U32(0)) & ({self.low} == U32(0)))
+    }
+
+    fn gen_lt(self, rhs: Wide, ctx: &mut BlockContext<'_>) -> naga::Handle<naga::Expression> {
+        naga_expr!(ctx =>
+            ({self.high} < {rhs.high}) | (({self.high} == {rhs.high}) & ({self.low} < {rhs.low}))
+        )
+    }
+
+    fn gen_add(self, rhs: Wide, ctx: &mut BlockContext<'_>) -> Self {
+        let low = naga_expr!(ctx => {self.low} + {rhs.low});
+        let carry = naga_expr!(ctx => if (low < {self.low}) {U32(1)} else {U32(0)});
+        let high = naga_expr!(ctx => ({self.high} + {rhs.high}) + carry);
+        Self { high, low }
+    }
+
+    fn gen_sub(self, rhs: Wide, ctx: &mut BlockContext<'_>) -> Self {
+        let borrow = naga_expr!(ctx => if ({self.low} < {rhs.low}) {U32(1)} else {U32(0)});
+        let low = naga_expr!(ctx => {self.low} - {rhs.low});
+        let high = naga_expr!(ctx => ({self.high} - {rhs.high}) - borrow);
+        Self { high, low }
+    }
+
+    /// Shifts left by an amount less than 64. Shifting a word by 32 or more is undefined, so the words are moved
+    /// with selects instead.
+    fn gen_shl(self, amount: naga::Handle<naga::Expression>, ctx: &mut BlockContext<'_>) -> Self {
+        let Self { high, low } = self;
+        let moves_word = naga_expr!(ctx => amount >= U32(32));
+        let shift = naga_expr!(ctx => amount & U32(31));
+        let carried_shift = naga_expr!(ctx => (U32(32) - shift) & U32(31));
+        let carried = naga_expr!(ctx => if (shift == U32(0)) {U32(0)} else {low >> carried_shift});
+        let shifted_high = naga_expr!(ctx => (high << shift) | carried);
+        let shifted_low = naga_expr!(ctx => low << shift);
+
+        let high = naga_expr!(ctx => if (moves_word) {shifted_low} else {shifted_high});
+        let low = naga_expr!(ctx => if (moves_word) {U32(0)} else {shifted_low});
+        Self { high, low }
+    }
+
+    /// Shifts right by an amount less than 64
+    fn gen_shr(self, amount: naga::Handle<naga::Expression>, ctx: &mut BlockContext<'_>) -> Self {
+        let Self { high, low } = self;
+        let moves_word = naga_expr!(ctx => amount >= U32(32));
+        let shift = naga_expr!(ctx => amount & U32(31));
+        let carried_shift = naga_expr!(ctx => (U32(32) - shift) & U32(31));
+        let carried = naga_expr!(ctx => if (shift == U32(0)) {U32(0)} else {high << carried_shift});
+        let shifted_high = naga_expr!(ctx => high >> shift);
+        let shifted_low = naga_expr!(ctx => (low >> shift) | carried);
+
+        let high = naga_expr!(ctx => if (moves_word) {U32(0)} else {shifted_high});
+        let low = naga_expr!(ctx => if (moves_word) {shifted_high} else {shifted_low});
+        Self { high, low }
+    }
+
+    /// Shifts right by any amount, setting the lowest bit of the result if any set bits were shifted out so that
+    /// the result still rounds in the right direction
+    fn gen_shr_jam(
+        self,
+        amount: naga::Handle<naga::Expression>,
+        ctx: &mut BlockContext<'_>,
+    ) -> Self {
+        let in_range = naga_expr!(ctx => amount < U32(64));
+        let clamped_amount = naga_expr!(ctx => amount & U32(63));
+        let shifted = self.gen_shr(clamped_amount, ctx);
+        let restored = shifted.gen_shl(clamped_amount, ctx);
+        let lost_bits = naga_expr!(ctx =>
+            ({restored.high} != {self.high}) | ({restored.low} != {self.low})
+        );
+        let jam = naga_expr!(ctx => if (lost_bits) {U32(1)} else {U32(0)});
+        let jammed_low = naga_expr!(ctx => {shifted.low} | jam);
+
+        // Everything is shifted out if shifting by 64 or more
+        let is_zero = self.gen_is_zero(ctx);
+        let all_lost_low = naga_expr!(ctx => if (is_zero) {U32(0)} else {U32(1)});
+
+        let high = naga_expr!(ctx => if (in_range) {{shifted.high}} else {U32(0)});
+        let low = naga_expr!(ctx => if (in_range) {jammed_low} else {all_lost_low});
+        Self { high, low }
+    }
+
+    fn gen_leading_zeros(self, ctx: &mut BlockContext<'_>) -> naga::Handle<naga::Expression> {
+        let high_zeros = naga_expr!(ctx => countLeadingZeros({self.high}));
+        let low_zeros = naga_expr!(ctx => countLeadingZeros({self.low}));
+        naga_expr!(ctx => if ({self.high} == U32(0)) {U32(32) + low_zeros} else {high_zeros})
+    }
+
+    /// Multiplies two words to give their full 64 bit product, by splitting each word into 16 bit halves so that
+    /// no partial product overflows
+    fn gen_mul_words(
+        lhs: naga::Handle<naga::Expression>,
+        rhs: naga::Handle<naga::Expression>,
+        ctx: &mut BlockContext<'_>,
+    ) -> Self {
+        let lhs_low = naga_expr!(ctx => lhs & U32(0xFFFF));
+        let lhs_high = naga_expr!(ctx => lhs >> U32(16));
+        let rhs_low = naga_expr!(ctx => rhs & U32(0xFFFF));
+        let rhs_high = naga_expr!(ctx => rhs >> U32(16));
+
+        let low_low = naga_expr!(ctx => lhs_low * rhs_low);
+        let low_high = naga_expr!(ctx => lhs_low * rhs_high);
+        let high_low = naga_expr!(ctx => lhs_high * rhs_low);
+        let high_high = naga_expr!(ctx => lhs_high * rhs_high);
+
+        let middle = naga_expr!(ctx =>
+            ((low_low >> U32(16)) + (low_high & U32(0xFFFF))) + (high_low & U32(0xFFFF))
+        );
+        let low = naga_expr!(ctx => (low_low & U32(0xFFFF)) | (middle << U32(16)));
+        let high = naga_expr!(ctx =>
+            ((high_high + (low_high >> U32(16))) + (high_low >> U32(16))) + (middle >> U32(16))
+        );
+        Self { high, low }
     }
+
+    /// Gives the high and low 64 bits of the 128 bit product
+    fn gen_mul(self, rhs: Wide, ctx: &mut BlockContext<'_>) -> (Self, Self) {
+        let low_low = Self::gen_mul_words(self.low, rhs.low, ctx);
+        let low_high = Self::gen_mul_words(self.low, rhs.high, ctx);
+        let high_low = Self::gen_mul_words(self.high, rhs.low, ctx);
+        let high_high = Self::gen_mul_words(self.high, rhs.high, ctx);
+
+        let zero = naga_expr!(ctx => U32(0));
+        let word = |value| Self::new(zero, value);
+
+        // The second word of the product, with its carry into the third word held in the high word
+        let middle = word(low_low.high)
+            .gen_add(word(low_high.low), ctx)
+            .gen_add(word(high_low.low), ctx);
+        let upper = high_high
+            .gen_add(word(low_high.high), ctx)
+            .gen_add(word(high_low.high), ctx)
+            .gen_add(word(middle.high), ctx);
+
+        (upper, Self::new(middle.low, low_low.low))
+    }
+}
+
+/// Builds an f64 with a zero low word, such as a zero or an infinity
+fn gen_f64_from_high_word(
+    ctx: &mut BlockContext<'_>,
+    f64_ty: naga::Handle<naga::Type>,
+    sign: naga::Handle<naga::Expression>,
+    high_magnitude: u32,
+) -> naga::Handle<naga::Expression> {
+    naga_expr!(ctx => f64_ty(U32(0), ((sign << U32(31)) | U32(high_magnitude))))
+}
+
+/// Gives the result of an arithmetic operation that produces a NaN. NaN operands are propagated, made quiet, with
+/// `lhs` taking priority. Otherwise the operation was invalid and gives the canonical NaN. If NaNs are canonicalized
+/// by the floating point options then every NaN result is the canonical NaN.
+fn gen_nan_result(
+    ctx: &mut BlockContext<'_>,
+    fp_options: &crate::FloatingPointOptions,
+    f64_ty: naga::Handle<naga::Type>,
+    lhs: naga::Handle<naga::Expression>,
+    lhs_is_nan: naga::Handle<naga::Expression>,
+    rhs: naga::Handle<naga::Expression>,
+    rhs_is_nan: naga::Handle<naga::Expression>,
+) -> naga::Handle<naga::Expression> {
+    let canonical = naga_expr!(ctx => f64_ty(U32(0), U32(0x7FF80000)));
+    if fp_options.canonicalize_nans {
+        return canonical;
+    }
+
+    let quiet_lhs = naga_expr!(ctx => f64_ty((lhs[const 0]), ((lhs[const 1]) | U32(1 << 19))));
+    let quiet_rhs = naga_expr!(ctx => f64_ty((rhs[const 0]), ((rhs[const 1]) | U32(1 << 19))));
+
+    let rhs_or_canonical = naga_expr!(ctx => if (rhs_is_nan) {quiet_rhs} else {canonical});
+    naga_expr!(ctx => if (lhs_is_nan) {quiet_lhs} else {rhs_or_canonical})
+}
+
+/// Rounds a significand to the nearest representable value, with ties going to even, and packs it into an f64.
+/// The significand has its leading bit at bit 62 for normal results, followed by the 52 bits that are kept and then
+/// 10 bits used for rounding. `exponent` is one less than the biased exponent of a normal result, since the
+/// leading bit is added into the exponent when packing. Results too small to be normal are shifted down to be
+/// subnormal, and results too large to be finite become infinities.
+fn gen_round_pack(
+    ctx: &mut BlockContext<'_>,
+    f64_ty: naga::Handle<naga::Type>,
+    sign: naga::Handle<naga::Expression>,
+    exponent: naga::Handle<naga::Expression>,
+    significand: Wide,
+) -> naga::Handle<naga::Expression> {
+    let is_tiny = naga_expr!(ctx => exponent < I32(0));
+    let subnormal_shift = naga_expr!(ctx => bitcast<u32>(-exponent));
+    let subnormal_significand = significand.gen_shr_jam(subnormal_shift, ctx);
+    let significand = Wide::select(ctx, is_tiny, subnormal_significand, significand);
+    let exponent = naga_expr!(ctx => if (is_tiny) {I32(0)} else {exponent});
+
+    let round_bits = naga_expr!(ctx => {significand.low} & U32(0x3FF));
+    let half = Wide::constant(ctx, 0x200);
+    let incremented = significand.gen_add(half, ctx);
+    let overflows = naga_expr!(ctx =>
+        (exponent > I32(0x7FD)) | ((exponent == I32(0x7FD)) & ({incremented.high} >= U32(0x80000000)))
+    );
+
+    let ten = naga_expr!(ctx => U32(10));
+    let rounded = incremented.gen_shr(ten, ctx);
+    let is_tie = naga_expr!(ctx => round_bits == U32(0x200));
+    let rounded_low =
+        naga_expr!(ctx => if (is_tie) {{rounded.low} & U32(0xFFFFFFFE)} else {{rounded.low}});
+    let rounded = Wide::new(rounded.high, rounded_low);
+    let is_zero = rounded.gen_is_zero(ctx);
+    let exponent = naga_expr!(ctx => if (is_zero) {U32(0)} else {bitcast<u32>(exponent)});
+
+    // The leading bit of the significand carries into the exponent
+    let high = naga_expr!(ctx => ((sign << U32(31)) | (exponent << U32(20))) + {rounded.high});
+    let finite = naga_expr!(ctx => f64_ty({rounded.low}, high));
+    let infinite = gen_f64_from_high_word(ctx, f64_ty, sign, 0x7FF00000);
+    naga_expr!(ctx => if (overflows) {infinite} else {finite})
+}
+
+/// Shifts a significand with bit 63 clear so that its leading bit is bit 62, then rounds and packs it as in
+/// `gen_round_pack`
+fn gen_normalize_round_pack(
+    ctx: &mut BlockContext<'_>,
+    f64_ty: naga::Handle<naga::Type>,
+    sign: naga::Handle<naga::Expression>,
+    exponent: naga::Handle<naga::Expression>,
+    significand: Wide,
+) -> naga::Handle<naga::Expression> {
+    let leading_zeros = significand.gen_leading_zeros(ctx);
+    let shift = naga_expr!(ctx => leading_zeros - U32(1));
+    let significand = significand.gen_shl(shift, ctx);
+    let exponent = naga_expr!(ctx => exponent - (bitcast<i32>(shift)));
+
+    gen_round_pack(ctx, f64_ty, sign, exponent, significand)
 }
 
 macro_rules! impl_mono_using_frexp {
@@ -232,7 +605,10 @@ macro_rules! impl_bool_binary_using_frexp {
                 let lhs_frexp = FrexpParts::from_uvec2(&mut ctx, lhs);
                 let rhs_frexp = FrexpParts::from_uvec2(&mut ctx, rhs);
 
-                let res = lhs_frexp.[< gen_ $fn >](rhs_frexp, &mut ctx);
+                let t = naga_expr!(&mut ctx => Constant(requirements.preamble.wasm_bool.const_true));
+                let f = naga_expr!(&mut ctx => Constant(requirements.preamble.wasm_bool.const_false));
+                let is_true = lhs_frexp.[< gen_ $fn >](rhs_frexp, &mut ctx);
+                let res = naga_expr!(&mut ctx => if (is_true) {t} else {f});
                 ctx.result(res);
 
                 Ok(function_handle)
@@ -340,18 +716,214 @@ impl F64Gen for PolyfillF64 {
         )
     }
 
-    impl_binary_using_frexp! {f64_instance_gen, add}
-    impl_binary_using_frexp! {f64_instance_gen, sub}
-    impl_binary_using_frexp! {f64_instance_gen, mul}
-    impl_binary_using_frexp! {f64_instance_gen, div}
+    fn gen_add(
+        module: &mut naga::Module,
+        requirements: f64_instance_gen::AddRequirements,
+    ) -> build::Result<f64_instance_gen::Add> {
+        Ok(gen_add_or_sub(
+            module,
+            requirements.fp_options,
+            *requirements.ty,
+            "add",
+            false,
+        ))
+    }
+
+    fn gen_sub(
+        module: &mut naga::Module,
+        requirements: f64_instance_gen::SubRequirements,
+    ) -> build::Result<f64_instance_gen::Sub> {
+        Ok(gen_add_or_sub(
+            module,
+            requirements.fp_options,
+            *requirements.ty,
+            "sub",
+            true,
+        ))
+    }
+
+    fn gen_mul(
+        module: &mut naga::Module,
+        requirements: f64_instance_gen::MulRequirements,
+    ) -> build::Result<f64_instance_gen::Mul> {
+        Ok(gen_mul(module, requirements.fp_options, *requirements.ty))
+    }
+
+    fn gen_div(
+        module: &mut naga::Module,
+        requirements: f64_instance_gen::DivRequirements,
+    ) -> build::Result<f64_instance_gen::Div> {
+        Ok(gen_div(module, requirements.fp_options, *requirements.ty))
+    }
     impl_binary_using_frexp! {f64_instance_gen, min}
     impl_binary_using_frexp! {f64_instance_gen, max}
     impl_binary_using_frexp! {f64_instance_gen, copy_sign}
 
-    super::impl_bitwise_2vec32_numeric_ops! {f64_instance_gen, f64}
-
     super::impl_load_and_store! {f64_instance_gen, f64}
 
+    fn gen_convert_i32_s(
+        module: &mut naga::Module,
+        requirements: f64_instance_gen::ConvertI32SRequirements,
+    ) -> build::Result<f64_instance_gen::ConvertI32S> {
+        Ok(gen_convert_from_integer(
+            module,
+            *requirements.ty,
+            *requirements.i32_ty,
+            "convert_i32_s",
+            false,
+            true,
+        ))
+    }
+
+    fn gen_convert_i32_u(
+        module: &mut naga::Module,
+        requirements: f64_instance_gen::ConvertI32URequirements,
+    ) -> build::Result<f64_instance_gen::ConvertI32U> {
+        Ok(gen_convert_from_integer(
+            module,
+            *requirements.ty,
+            *requirements.i32_ty,
+            "convert_i32_u",
+            false,
+            false,
+        ))
+    }
+
+    fn gen_convert_i64_s(
+        module: &mut naga::Module,
+        requirements: f64_instance_gen::ConvertI64SRequirements,
+    ) -> build::Result<f64_instance_gen::ConvertI64S> {
+        Ok(gen_convert_from_integer(
+            module,
+            *requirements.ty,
+            *requirements.i64_ty,
+            "convert_i64_s",
+            true,
+            true,
+        ))
+    }
+
+    fn gen_convert_i64_u(
+        module: &mut naga::Module,
+        requirements: f64_instance_gen::ConvertI64URequirements,
+    ) -> build::Result<f64_instance_gen::ConvertI64U> {
+        Ok(gen_convert_from_integer(
+            module,
+            *requirements.ty,
+            *requirements.i64_ty,
+            "convert_i64_u",
+            true,
+            false,
+        ))
+    }
+
+    fn gen_promote_f32(
+        module: &mut naga::Module,
+        requirements: f64_instance_gen::PromoteF32Requirements,
+    ) -> build::Result<f64_instance_gen::PromoteF32> {
+        Ok(gen_promote(module, *requirements.ty, *requirements.f32_ty))
+    }
+
+    fn gen_demote_f32(
+        module: &mut naga::Module,
+        requirements: f64_instance_gen::DemoteF32Requirements,
+    ) -> build::Result<f64_instance_gen::DemoteF32> {
+        Ok(gen_demote(module, *requirements.ty, *requirements.f32_ty))
+    }
+
+    fn gen_reinterpret_from_i64(
+        module: &mut naga::Module,
+        requirements: f64_instance_gen::ReinterpretFromI64Requirements,
+    ) -> build::Result<f64_instance_gen::ReinterpretFromI64> {
+        let f64_ty = *requirements.ty;
+        let (function_handle, value) = declare_function! {
+            module => fn f64_reinterpret_i64(value: *requirements.i64_ty) -> f64_ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        // Both are stored as their two words, low word first
+        let res = naga_expr!(&mut ctx => f64_ty((value[const 0]), (value[const 1])));
+        ctx.result(res);
+
+        Ok(function_handle)
+    }
+
+    fn gen_reinterpret_to_i64(
+        module: &mut naga::Module,
+        requirements: f64_instance_gen::ReinterpretToI64Requirements,
+    ) -> build::Result<f64_instance_gen::ReinterpretToI64> {
+        let i64_ty = *requirements.i64_ty;
+        let (function_handle, value) = declare_function! {
+            module => fn i64_reinterpret_f64(value: *requirements.ty) -> i64_ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        let res = naga_expr!(&mut ctx => i64_ty((value[const 0]), (value[const 1])));
+        ctx.result(res);
+
+        Ok(function_handle)
+    }
+
+    fn gen_trunc_i32_s(
+        module: &mut naga::Module,
+        requirements: f64_instance_gen::TruncI32SRequirements,
+    ) -> build::Result<f64_instance_gen::TruncI32S> {
+        Ok(gen_trunc_to_integer(
+            module,
+            *requirements.ty,
+            requirements.preamble,
+            *requirements.i32_ty,
+            "i32_trunc_f64_s",
+            false,
+            true,
+        ))
+    }
+
+    fn gen_trunc_i32_u(
+        module: &mut naga::Module,
+        requirements: f64_instance_gen::TruncI32URequirements,
+    ) -> build::Result<f64_instance_gen::TruncI32U> {
+        Ok(gen_trunc_to_integer(
+            module,
+            *requirements.ty,
+            requirements.preamble,
+            *requirements.i32_ty,
+            "i32_trunc_f64_u",
+            false,
+            false,
+        ))
+    }
+
+    fn gen_trunc_i64_s(
+        module: &mut naga::Module,
+        requirements: f64_instance_gen::TruncI64SRequirements,
+    ) -> build::Result<f64_instance_gen::TruncI64S> {
+        Ok(gen_trunc_to_integer(
+            module,
+            *requirements.ty,
+            requirements.preamble,
+            *requirements.i64_ty,
+            "i64_trunc_f64_s",
+            true,
+            true,
+        ))
+    }
+
+    fn gen_trunc_i64_u(
+        module: &mut naga::Module,
+        requirements: f64_instance_gen::TruncI64URequirements,
+    ) -> build::Result<f64_instance_gen::TruncI64U> {
+        Ok(gen_trunc_to_integer(
+            module,
+            *requirements.ty,
+            requirements.preamble,
+            *requirements.i64_ty,
+            "i64_trunc_f64_u",
+            true,
+            false,
+        ))
+    }
+
     impl_mono_using_frexp! {f64_instance_gen, abs}
     impl_mono_using_frexp! {f64_instance_gen, neg}
     impl_mono_using_frexp! {f64_instance_gen, ceil}
@@ -360,6 +932,8 @@ impl F64Gen for PolyfillF64 {
     impl_mono_using_frexp! {f64_instance_gen, nearest}
     impl_mono_using_frexp! {f64_instance_gen, sqrt}
 
+    impl_bool_binary_using_frexp! {f64_instance_gen, eq}
+    impl_bool_binary_using_frexp! {f64_instance_gen, ne}
     impl_bool_binary_using_frexp! {f64_instance_gen, lt}
     impl_bool_binary_using_frexp! {f64_instance_gen, le}
     impl_bool_binary_using_frexp! {f64_instance_gen, gt}
@@ -416,3 +990,493 @@ fn gen_write(
 
     Ok(function_handle)
 }
+
+/// fn(lhs: f64, rhs: f64) -> f64
+///
+/// Aligns the significand of the operand with the smaller magnitude to that of the larger, then adds or subtracts
+/// the significands depending on whether the signs match. Subtraction is addition with the sign of `rhs` flipped.
+fn gen_add_or_sub(
+    module: &mut naga::Module,
+    fp_options: &crate::FloatingPointOptions,
+    f64_ty: f64_instance_gen::Ty,
+    name: &str,
+    is_sub: bool,
+) -> naga::Handle<naga::Function> {
+    let (function_handle, lhs, rhs) = declare_function! {
+        module => fn {format!("f64_{}", name)}(lhs: f64_ty, rhs: f64_ty) -> f64_ty
+    };
+    let mut ctx = BlockContext::from((module, function_handle));
+
+    let lhs_frexp = FrexpParts::from_uvec2(&mut ctx, lhs);
+    let mut rhs_frexp = FrexpParts::from_uvec2(&mut ctx, rhs);
+    if is_sub {
+        rhs_frexp.sign = naga_expr!(&mut ctx => {rhs_frexp.sign} ^ U32(1));
+    }
+
+    // Order the operands by magnitude
+    let lhs_magnitude = Wide::new(
+        naga_expr!(&mut ctx => (lhs[const 1]) & U32(0x7FFFFFFF)),
+        naga_expr!(&mut ctx => lhs[const 0]),
+    );
+    let rhs_magnitude = Wide::new(
+        naga_expr!(&mut ctx => (rhs[const 1]) & U32(0x7FFFFFFF)),
+        naga_expr!(&mut ctx => rhs[const 0]),
+    );
+    let lhs_is_smaller = lhs_magnitude.gen_lt(rhs_magnitude, &mut ctx);
+    let larger = FrexpParts::select(&mut ctx, lhs_is_smaller, &rhs_frexp, &lhs_frexp);
+    let smaller = FrexpParts::select(&mut ctx, lhs_is_smaller, &lhs_frexp, &rhs_frexp);
+
+    // Give the significands 10 bits below them for rounding, with the leading bit of normal values at 62
+    let (larger_significand, larger_exponent) = larger.gen_significand(&mut ctx);
+    let (smaller_significand, smaller_exponent) = smaller.gen_significand(&mut ctx);
+    let ten = naga_expr!(&mut ctx => U32(10));
+    let larger_significand = larger_significand.gen_shl(ten, &mut ctx);
+    let smaller_significand = smaller_significand.gen_shl(ten, &mut ctx);
+    let alignment = naga_expr!(&mut ctx => larger_exponent - smaller_exponent);
+    let smaller_significand = smaller_significand.gen_shr_jam(alignment, &mut ctx);
+
+    // Adding may carry into bit 63, in which case the sum is shifted back down
+    let sum = larger_significand.gen_add(smaller_significand, &mut ctx);
+    let carried = naga_expr!(&mut ctx => {sum.high} >= U32(0x80000000));
+    let one = naga_expr!(&mut ctx => U32(1));
+    let shifted_sum = sum.gen_shr_jam(one, &mut ctx);
+    let sum = Wide::select(&mut ctx, carried, shifted_sum, sum);
+    let difference = larger_significand.gen_sub(smaller_significand, &mut ctx);
+
+    let same_sign = naga_expr!(&mut ctx => {larger.sign} == {smaller.sign});
+    let significand = Wide::select(&mut ctx, same_sign, sum, difference);
+    let exponent_offset = naga_expr!(&mut ctx => if (same_sign & carried) {I32(0)} else {I32(-1)});
+    let exponent = naga_expr!(&mut ctx => (bitcast<i32>(larger_exponent)) + exponent_offset);
+
+    // Exact cancellation gives positive zero
+    let is_zero = significand.gen_is_zero(&mut ctx);
+    let is_cancelled = naga_expr!(&mut ctx => (!same_sign) & is_zero);
+    let sign = naga_expr!(&mut ctx => if (is_cancelled) {U32(0)} else {{larger.sign}});
+    let res = gen_normalize_round_pack(&mut ctx, f64_ty, sign, exponent, significand);
+
+    // Special values. If either operand is infinite then the larger one is
+    let lhs_is_nan = lhs_frexp.gen_is_nan(&mut ctx);
+    let rhs_is_nan = rhs_frexp.gen_is_nan(&mut ctx);
+    let lhs_is_inf = lhs_frexp.gen_is_inf(&mut ctx);
+    let rhs_is_inf = rhs_frexp.gen_is_inf(&mut ctx);
+    let larger_is_inf = larger.gen_is_inf(&mut ctx);
+
+    let infinite = larger.gen_uvec2(&mut ctx, f64_ty);
+    let res = naga_expr!(&mut ctx => if (larger_is_inf) {infinite} else {res});
+    let nan = gen_nan_result(
+        &mut ctx, fp_options, f64_ty, lhs, lhs_is_nan, rhs, rhs_is_nan,
+    );
+    let is_nan = naga_expr!(&mut ctx =>
+        (lhs_is_nan | rhs_is_nan) | ((lhs_is_inf & rhs_is_inf) & (!same_sign))
+    );
+    let res = naga_expr!(&mut ctx => if (is_nan) {nan} else {res});
+    ctx.result(res);
+
+    function_handle
+}
+
+/// fn(lhs: f64, rhs: f64) -> f64
+///
+/// Multiplies the full significands into a 128 bit product, keeping the high 64 bits for rounding with any set bits
+/// below them collected into the lowest bit.
+fn gen_mul(
+    module: &mut naga::Module,
+    fp_options: &crate::FloatingPointOptions,
+    f64_ty: f64_instance_gen::Ty,
+) -> naga::Handle<naga::Function> {
+    let (function_handle, lhs, rhs) = declare_function! {
+        module => fn f64_mul(lhs: f64_ty, rhs: f64_ty) -> f64_ty
+    };
+    let mut ctx = BlockContext::from((module, function_handle));
+
+    let lhs_frexp = FrexpParts::from_uvec2(&mut ctx, lhs);
+    let rhs_frexp = FrexpParts::from_uvec2(&mut ctx, rhs);
+    let sign = naga_expr!(&mut ctx => {lhs_frexp.sign} ^ {rhs_frexp.sign});
+
+    // Multiply the significands with the leading bits at 62 and 63, so that the leading bit of the product's
+    // high 64 bits is at 61 or 62
+    let (lhs_significand, lhs_exponent) = lhs_frexp.gen_normalized_significand(&mut ctx);
+    let (rhs_significand, rhs_exponent) = rhs_frexp.gen_normalized_significand(&mut ctx);
+    let ten = naga_expr!(&mut ctx => U32(10));
+    let eleven = naga_expr!(&mut ctx => U32(11));
+    let lhs_significand = lhs_significand.gen_shl(ten, &mut ctx);
+    let rhs_significand = rhs_significand.gen_shl(eleven, &mut ctx);
+    let (product, product_low) = lhs_significand.gen_mul(rhs_significand, &mut ctx);
+
+    let low_is_zero = product_low.gen_is_zero(&mut ctx);
+    let jammed_low =
+        naga_expr!(&mut ctx => if (low_is_zero) {{product.low}} else {{product.low} | U32(1)});
+    let product = Wide::new(product.high, jammed_low);
+
+    let exponent = naga_expr!(&mut ctx => (lhs_exponent + rhs_exponent) - I32(0x3FF));
+    let is_unnormalized = naga_expr!(&mut ctx => {product.high} < U32(0x40000000));
+    let one = naga_expr!(&mut ctx => U32(1));
+    let shifted_product = product.gen_shl(one, &mut ctx);
+    let product = Wide::select(&mut ctx, is_unnormalized, shifted_product, product);
+    let exponent = naga_expr!(&mut ctx => if (is_unnormalized) {exponent - I32(1)} else {exponent});
+    let res = gen_round_pack(&mut ctx, f64_ty, sign, exponent, product);
+
+    // Special values
+    let lhs_is_nan = lhs_frexp.gen_is_nan(&mut ctx);
+    let rhs_is_nan = rhs_frexp.gen_is_nan(&mut ctx);
+    let lhs_is_inf = lhs_frexp.gen_is_inf(&mut ctx);
+    let rhs_is_inf = rhs_frexp.gen_is_inf(&mut ctx);
+    let lhs_is_zero = lhs_frexp.gen_is_zero(&mut ctx);
+    let rhs_is_zero = rhs_frexp.gen_is_zero(&mut ctx);
+
+    let zero = gen_f64_from_high_word(&mut ctx, f64_ty, sign, 0);
+    let res = naga_expr!(&mut ctx => if (lhs_is_zero | rhs_is_zero) {zero} else {res});
+    let infinite = gen_f64_from_high_word(&mut ctx, f64_ty, sign, 0x7FF00000);
+    let res = naga_expr!(&mut ctx => if (lhs_is_inf | rhs_is_inf) {infinite} else {res});
+    let nan = gen_nan_result(
+        &mut ctx, fp_options, f64_ty, lhs, lhs_is_nan, rhs, rhs_is_nan,
+    );
+    let is_nan = naga_expr!(&mut ctx =>
+        (lhs_is_nan | rhs_is_nan) | ((lhs_is_inf & rhs_is_zero) | (lhs_is_zero & rhs_is_inf))
+    );
+    let res = naga_expr!(&mut ctx => if (is_nan) {nan} else {res});
+    ctx.result(res);
+
+    function_handle
+}
+
+/// fn(lhs: f64, rhs: f64) -> f64
+///
+/// Divides the significands by long division, one quotient bit at a time, until the quotient has its leading bit
+/// at 62 and 10 bits below the kept bits for rounding. Any remainder is collected into the lowest bit.
+fn gen_div(
+    module: &mut naga::Module,
+    fp_options: &crate::FloatingPointOptions,
+    f64_ty: f64_instance_gen::Ty,
+) -> naga::Handle<naga::Function> {
+    let word_ty = module.types.insert_u32();
+    let (function_handle, lhs, rhs) = declare_function! {
+        module => fn f64_div(lhs: f64_ty, rhs: f64_ty) -> f64_ty
+    };
+    let mut ctx = BlockContext::from((module, function_handle));
+
+    let lhs_frexp = FrexpParts::from_uvec2(&mut ctx, lhs);
+    let rhs_frexp = FrexpParts::from_uvec2(&mut ctx, rhs);
+    let sign = naga_expr!(&mut ctx => {lhs_frexp.sign} ^ {rhs_frexp.sign});
+
+    // Make the dividend at least the divisor, so that the first quotient bit is set
+    let (lhs_significand, lhs_exponent) = lhs_frexp.gen_normalized_significand(&mut ctx);
+    let (rhs_significand, rhs_exponent) = rhs_frexp.gen_normalized_significand(&mut ctx);
+    let exponent = naga_expr!(&mut ctx => (lhs_exponent - rhs_exponent) + I32(0x3FE));
+    let lhs_is_smaller = lhs_significand.gen_lt(rhs_significand, &mut ctx);
+    let one = naga_expr!(&mut ctx => U32(1));
+    let doubled_lhs_significand = lhs_significand.gen_shl(one, &mut ctx);
+    let dividend = Wide::select(
+        &mut ctx,
+        lhs_is_smaller,
+        doubled_lhs_significand,
+        lhs_significand,
+    );
+    let exponent = naga_expr!(&mut ctx => if (lhs_is_smaller) {exponent - I32(1)} else {exponent});
+
+    let new_word = |ctx: &mut BlockContext<'_>, name: &str, value| {
+        let local = ctx.new_local(name, word_ty, None);
+        let local = ctx.local_expr(local);
+        ctx.store(local, value);
+        local
+    };
+    let zero = naga_expr!(&mut ctx => U32(0));
+    let remainder_high = new_word(&mut ctx, "remainder_high", dividend.high);
+    let remainder_low = new_word(&mut ctx, "remainder_low", dividend.low);
+    let quotient_high = new_word(&mut ctx, "quotient_high", zero);
+    let quotient_low = new_word(&mut ctx, "quotient_low", zero);
+
+    naga_expr!(&mut ctx => for i in (U32(0))..(U32(63)) |ctx| {
+        let bit = naga_expr!(&mut ctx => U32(62) - i);
+        let in_high_word = naga_expr!(&mut ctx => bit >= U32(32));
+        let shift = naga_expr!(&mut ctx => bit & U32(31));
+
+        // The remainder is always less than twice the divisor, so never overflows when doubled
+        let remainder = Wide::new(
+            naga_expr!(&mut ctx => Load(remainder_high)),
+            naga_expr!(&mut ctx => Load(remainder_low)),
+        );
+        let is_less = remainder.gen_lt(rhs_significand, &mut ctx);
+        let fits = naga_expr!(&mut ctx => !is_less);
+        let subtracted = remainder.gen_sub(rhs_significand, &mut ctx);
+        let remainder = Wide::select(&mut ctx, fits, subtracted, remainder);
+        let one = naga_expr!(&mut ctx => U32(1));
+        let remainder = remainder.gen_shl(one, &mut ctx);
+        ctx.store(remainder_high, remainder.high);
+        ctx.store(remainder_low, remainder.low);
+
+        let quotient_bit = naga_expr!(&mut ctx => if (fits) {(U32(1)) << shift} else {U32(0)});
+        let quotient_bit_high = naga_expr!(&mut ctx => if (in_high_word) {quotient_bit} else {U32(0)});
+        let quotient_bit_low = naga_expr!(&mut ctx => if (in_high_word) {U32(0)} else {quotient_bit});
+        let new_quotient_high = naga_expr!(&mut ctx => (Load(quotient_high)) | quotient_bit_high);
+        let new_quotient_low = naga_expr!(&mut ctx => (Load(quotient_low)) | quotient_bit_low);
+        ctx.store(quotient_high, new_quotient_high);
+        ctx.store(quotient_low, new_quotient_low);
+    });
+
+    let remainder = Wide::new(
+        naga_expr!(&mut ctx => Load(remainder_high)),
+        naga_expr!(&mut ctx => Load(remainder_low)),
+    );
+    let is_exact = remainder.gen_is_zero(&mut ctx);
+    let jam = naga_expr!(&mut ctx => if (is_exact) {U32(0)} else {U32(1)});
+    let quotient = Wide::new(
+        naga_expr!(&mut ctx => Load(quotient_high)),
+        naga_expr!(&mut ctx => (Load(quotient_low)) | jam),
+    );
+    let res = gen_round_pack(&mut ctx, f64_ty, sign, exponent, quotient);
+
+    // Special values
+    let lhs_is_nan = lhs_frexp.gen_is_nan(&mut ctx);
+    let rhs_is_nan = rhs_frexp.gen_is_nan(&mut ctx);
+    let lhs_is_inf = lhs_frexp.gen_is_inf(&mut ctx);
+    let rhs_is_inf = rhs_frexp.gen_is_inf(&mut ctx);
+    let lhs_is_zero = lhs_frexp.gen_is_zero(&mut ctx);
+    let rhs_is_zero = rhs_frexp.gen_is_zero(&mut ctx);
+
+    let zero = gen_f64_from_high_word(&mut ctx, f64_ty, sign, 0);
+    let res = naga_expr!(&mut ctx => if (lhs_is_zero | rhs_is_inf) {zero} else {res});
+    let infinite = gen_f64_from_high_word(&mut ctx, f64_ty, sign, 0x7FF00000);
+    let res = naga_expr!(&mut ctx => if (lhs_is_inf | rhs_is_zero) {infinite} else {res});
+    let nan = gen_nan_result(
+        &mut ctx, fp_options, f64_ty, lhs, lhs_is_nan, rhs, rhs_is_nan,
+    );
+    let is_nan = naga_expr!(&mut ctx =>
+        (lhs_is_nan | rhs_is_nan) | ((lhs_is_inf & rhs_is_inf) | (lhs_is_zero & rhs_is_zero))
+    );
+    let res = naga_expr!(&mut ctx => if (is_nan) {nan} else {res});
+    ctx.result(res);
+
+    function_handle
+}
+
+/// fn(value: i32 or i64) -> f64
+///
+/// Normalizes the magnitude of the integer as a significand, which is exact for 32 bit integers and rounds for
+/// 64 bit integers with more than 53 significant bits.
+fn gen_convert_from_integer(
+    module: &mut naga::Module,
+    f64_ty: f64_instance_gen::Ty,
+    int_ty: naga::Handle<naga::Type>,
+    name: &str,
+    is_64_bit: bool,
+    is_signed: bool,
+) -> naga::Handle<naga::Function> {
+    let (function_handle, value) = declare_function! {
+        module => fn {format!("f64_{}", name)}(value: int_ty) -> f64_ty
+    };
+    let mut ctx = BlockContext::from((module, function_handle));
+
+    let zero = naga_expr!(&mut ctx => U32(0));
+    let bits = if is_64_bit {
+        Wide::new(
+            naga_expr!(&mut ctx => value[const 1]),
+            naga_expr!(&mut ctx => value[const 0]),
+        )
+    } else {
+        Wide::new(zero, naga_expr!(&mut ctx => bitcast<u32>(value)))
+    };
+
+    let (sign, magnitude) = if is_signed {
+        let sign = if is_64_bit {
+            naga_expr!(&mut ctx => {bits.high} >> U32(31))
+        } else {
+            naga_expr!(&mut ctx => {bits.low} >> U32(31))
+        };
+        let is_negative = naga_expr!(&mut ctx => sign == U32(1));
+        let negated = if is_64_bit {
+            Wide::new(zero, zero).gen_sub(bits, &mut ctx)
+        } else {
+            Wide::new(zero, naga_expr!(&mut ctx => U32(0) - {bits.low}))
+        };
+        (sign, Wide::select(&mut ctx, is_negative, negated, bits))
+    } else {
+        (zero, bits)
+    };
+
+    // An integer has its leading bit at 62 when scaled by 2^62, which is 0x43C + 1 when biased. Magnitudes with
+    // bit 63 set are halved first
+    let top_bit_set = naga_expr!(&mut ctx => {magnitude.high} >= U32(0x80000000));
+    let one = naga_expr!(&mut ctx => U32(1));
+    let halved = magnitude.gen_shr_jam(one, &mut ctx);
+    let magnitude = Wide::select(&mut ctx, top_bit_set, halved, magnitude);
+    let exponent = naga_expr!(&mut ctx => if (top_bit_set) {I32(0x43D)} else {I32(0x43C)});
+
+    let res = gen_normalize_round_pack(&mut ctx, f64_ty, sign, exponent, magnitude);
+    ctx.result(res);
+
+    function_handle
+}
+
+/// fn(value: f32) -> f64
+///
+/// Every f32 is exactly representable as an f64, including subnormals, which become normal
+fn gen_promote(
+    module: &mut naga::Module,
+    f64_ty: f64_instance_gen::Ty,
+    f32_ty: naga::Handle<naga::Type>,
+) -> naga::Handle<naga::Function> {
+    let (function_handle, value) = declare_function! {
+        module => fn f64_promote_f32(value: f32_ty) -> f64_ty
+    };
+    let mut ctx = BlockContext::from((module, function_handle));
+
+    let bits = naga_expr!(&mut ctx => bitcast<u32>(value));
+    let sign = naga_expr!(&mut ctx => bits >> U32(31));
+    let exponent = naga_expr!(&mut ctx => (bits >> U32(23)) & U32(0xFF));
+    let fraction = naga_expr!(&mut ctx => bits & U32(0x7FFFFF));
+
+    // An f32 significand is scaled by 2^-149 for the smallest exponent of 1
+    let is_subnormal = naga_expr!(&mut ctx => exponent == U32(0));
+    let implicit_bit = naga_expr!(&mut ctx => if (is_subnormal) {U32(0)} else {U32(1 << 23)});
+    let exponent_offset =
+        naga_expr!(&mut ctx => if (is_subnormal) {I32(1)} else {bitcast<i32>(exponent)});
+    let zero = naga_expr!(&mut ctx => U32(0));
+    let significand = Wide::new(zero, naga_expr!(&mut ctx => fraction | implicit_bit));
+    let exponent_offset = naga_expr!(&mut ctx => exponent_offset + I32(0x43C - 150));
+    let finite = gen_normalize_round_pack(&mut ctx, f64_ty, sign, exponent_offset, significand);
+
+    // Infinities and NaNs keep their fraction, with NaNs made quiet
+    let is_special = naga_expr!(&mut ctx => exponent == U32(0xFF));
+    let is_nan = naga_expr!(&mut ctx => is_special & (fraction != U32(0)));
+    let quiet_bit = naga_expr!(&mut ctx => if (is_nan) {U32(1 << 19)} else {U32(0)});
+    let special_high = naga_expr!(&mut ctx =>
+        ((sign << U32(31)) | U32(0x7FF00000)) | ((fraction >> U32(3)) | quiet_bit)
+    );
+    let special = naga_expr!(&mut ctx => f64_ty((fraction << U32(29)), special_high));
+
+    let res = naga_expr!(&mut ctx => if (is_special) {special} else {finite});
+    ctx.result(res);
+
+    function_handle
+}
+
+/// fn(value: f64) -> f32
+///
+/// Keeps 30 bits of the fraction, the 23 that fit in an f32 and 7 more for rounding, then rounds to the nearest f32
+/// with ties going to even. Values too small to be normal become subnormal, and values too large become infinite.
+fn gen_demote(
+    module: &mut naga::Module,
+    f64_ty: f64_instance_gen::Ty,
+    f32_ty: naga::Handle<naga::Type>,
+) -> naga::Handle<naga::Function> {
+    let (function_handle, value) = declare_function! {
+        module => fn f32_demote_f64(value: f64_ty) -> f32_ty
+    };
+    let mut ctx = BlockContext::from((module, function_handle));
+
+    let frexp = FrexpParts::from_uvec2(&mut ctx, value);
+    let sign = frexp.sign;
+    let fraction = Wide::new(frexp.upper_magnitude, frexp.lower_magnitude);
+    let twenty_two = naga_expr!(&mut ctx => U32(22));
+    let fraction = fraction.gen_shr_jam(twenty_two, &mut ctx).low;
+
+    // As with f64s, the exponent is one less than the biased exponent as the leading bit at 30 carries into it
+    let significand = naga_expr!(&mut ctx => fraction | U32(0x40000000));
+    let exponent = naga_expr!(&mut ctx => (bitcast<i32>({frexp.exponent})) - I32(0x381));
+    let finite = gen_round_pack_bits(&mut ctx, sign, exponent, significand);
+
+    // Zeros, infinities and NaNs, with NaNs made quiet and keeping the top of their fraction
+    let is_zero = frexp.gen_is_zero(&mut ctx);
+    let is_inf = frexp.gen_is_inf(&mut ctx);
+    let is_nan = frexp.gen_is_nan(&mut ctx);
+    let zero = naga_expr!(&mut ctx => sign << U32(31));
+    let infinite = naga_expr!(&mut ctx => (sign << U32(31)) | U32(0x7F800000));
+    let nan_fraction = naga_expr!(&mut ctx =>
+        ({frexp.upper_magnitude} << U32(3)) | ({frexp.lower_magnitude} >> U32(29))
+    );
+    let nan = naga_expr!(&mut ctx => ((sign << U32(31)) | U32(0x7FC00000)) | nan_fraction);
+
+    let bits = naga_expr!(&mut ctx => if (is_inf) {infinite} else {finite});
+    let bits = naga_expr!(&mut ctx => if (is_zero) {zero} else {bits});
+    let bits = naga_expr!(&mut ctx => if (is_nan) {nan} else {bits});
+    let res = naga_expr!(&mut ctx => bitcast<f32>(bits));
+    ctx.result(res);
+
+    function_handle
+}
+
+/// fn(value: f64) -> i32 or i64
+///
+/// Shifts the significand so that only the integer part remains, trapping if the value is NaN or if that integer
+/// doesn't fit in the result type.
+fn gen_trunc_to_integer(
+    module: &mut naga::Module,
+    f64_ty: f64_instance_gen::Ty,
+    preamble: &PreambleObjects,
+    int_ty: naga::Handle<naga::Type>,
+    name: &str,
+    is_64_bit: bool,
+    is_signed: bool,
+) -> naga::Handle<naga::Function> {
+    let (function_handle, value) = declare_function! {
+        module => fn {name}(value: f64_ty) -> int_ty
+    };
+    let mut ctx = BlockContext::from((module, function_handle));
+
+    let frexp = FrexpParts::from_uvec2(&mut ctx, value);
+    let (significand, _) = frexp.gen_significand(&mut ctx);
+    let exponent = frexp.exponent;
+
+    // The bit at 52 of the significand has the value 2^(exponent - 1023)
+    let zero = naga_expr!(&mut ctx => U32(0));
+    let right_shift = naga_expr!(&mut ctx => (U32(1023 + 52) - exponent) & U32(63));
+    let left_shift = naga_expr!(&mut ctx => (exponent - U32(1023 + 52)) & U32(63));
+    let shifted_right = significand.gen_shr(right_shift, &mut ctx);
+    let shifted_left = significand.gen_shl(left_shift, &mut ctx);
+    let is_shifted_left = naga_expr!(&mut ctx => exponent > U32(1023 + 52));
+    let magnitude = Wide::select(&mut ctx, is_shifted_left, shifted_left, shifted_right);
+    let is_fractional = naga_expr!(&mut ctx => exponent < U32(1023));
+    let magnitude = Wide::select(&mut ctx, is_fractional, Wide::new(zero, zero), magnitude);
+
+    // Magnitudes of 2^64 and above, including infinities and NaNs, never fit
+    let is_too_large = naga_expr!(&mut ctx => exponent >= U32(1023 + 64));
+    let is_negative = naga_expr!(&mut ctx => {frexp.sign} == U32(1));
+    let is_zero = magnitude.gen_is_zero(&mut ctx);
+    let fits = match (is_64_bit, is_signed) {
+        (false, true) => naga_expr!(&mut ctx =>
+            ({magnitude.high} == U32(0))
+                & (({magnitude.low} < U32(0x80000000))
+                    | (is_negative & ({magnitude.low} == U32(0x80000000))))
+        ),
+        (false, false) => naga_expr!(&mut ctx =>
+            ({magnitude.high} == U32(0)) & ((!is_negative) | is_zero)
+        ),
+        (true, true) => naga_expr!(&mut ctx =>
+            ({magnitude.high} < U32(0x80000000))
+                | (is_negative & (({magnitude.high} == U32(0x80000000)) & ({magnitude.low} == U32(0))))
+        ),
+        (true, false) => naga_expr!(&mut ctx => (!is_negative) | is_zero),
+    };
+    let fits = naga_expr!(&mut ctx => (!is_too_large) & fits);
+
+    let is_nan = frexp.gen_is_nan(&mut ctx);
+    ctx.test(is_nan).then(|mut ctx| {
+        preamble.trap_values.emit_set_trap(
+            &mut ctx,
+            Trap::BadConversionToInteger,
+            preamble.trap_state,
+        );
+    });
+    let is_overflowing = naga_expr!(&mut ctx => (!is_nan) & (!fits));
+    ctx.test(is_overflowing).then(|mut ctx| {
+        preamble
+            .trap_values
+            .emit_set_trap(&mut ctx, Trap::IntegerOverflow, preamble.trap_state);
+    });
+
+    let integer = if is_signed {
+        let negated = Wide::new(zero, zero).gen_sub(magnitude, &mut ctx);
+        Wide::select(&mut ctx, is_negative, negated, magnitude)
+    } else {
+        magnitude
+    };
+    let res = if is_64_bit {
+        naga_expr!(&mut ctx => int_ty({integer.low}, {integer.high}))
+    } else {
+        naga_expr!(&mut ctx => bitcast<i32>({integer.low}))
+    };
+    ctx.result(res);
+
+    function_handle
+}
@@ -2,7 +2,8 @@ use crate::{
     build,
     std_objects::{preamble_objects_gen, WasmBoolInstance},
 };
-use naga_ext::{declare_function, naga_expr, BlockContext, ConstantsExt, TypesExt};
+use naga_ext::prelude::*;
+use wasmtime_environ::Trap;
 
 use super::{i64_instance_gen, I64Gen};
 
@@ -25,8 +26,8 @@ fn gen_boolean_mono(
     let t = naga_expr!(&mut ctx => Constant(wasm_bool.const_true));
     let f = naga_expr!(&mut ctx => Constant(wasm_bool.const_false));
 
-    let value_high = naga_expr!(&mut ctx => value[const 0]);
-    let value_low = naga_expr!(&mut ctx => value[const 1]);
+    let value_high = naga_expr!(&mut ctx => value[const 1]);
+    let value_low = naga_expr!(&mut ctx => value[const 0]);
     let cond = make(&mut ctx, value_high, value_low);
     let res = naga_expr!(&mut ctx => if (cond) {t} else {f});
     ctx.result(res);
@@ -55,10 +56,10 @@ fn gen_boolean_binary(
     let t = naga_expr!(&mut ctx => Constant(wasm_bool.const_true));
     let f = naga_expr!(&mut ctx => Constant(wasm_bool.const_false));
 
-    let lhs_high = naga_expr!(&mut ctx => lhs[const 0]);
-    let lhs_low = naga_expr!(&mut ctx => lhs[const 1]);
-    let rhs_high = naga_expr!(&mut ctx => rhs[const 0]);
-    let rhs_low = naga_expr!(&mut ctx => rhs[const 1]);
+    let lhs_high = naga_expr!(&mut ctx => lhs[const 1]);
+    let lhs_low = naga_expr!(&mut ctx => lhs[const 0]);
+    let rhs_high = naga_expr!(&mut ctx => rhs[const 1]);
+    let rhs_low = naga_expr!(&mut ctx => rhs[const 0]);
     let cond = make(&mut ctx, lhs_high, lhs_low, rhs_high, rhs_low);
     let res = naga_expr!(&mut ctx => if (cond) {t} else {f});
     ctx.result(res);
@@ -66,7 +67,205 @@ fn gen_boolean_binary(
     Ok(function_handle)
 }
 
-/// An implementation of i64s using a 2-vector of u32s
+/// Gives the two's complement negation of an i64 given as its high and low words, as `(high, low)`
+fn emit_negate(
+    ctx: &mut BlockContext<'_>,
+    high: naga::Handle<naga::Expression>,
+    low: naga::Handle<naga::Expression>,
+) -> (
+    naga::Handle<naga::Expression>,
+    naga::Handle<naga::Expression>,
+) {
+    let negated_low = naga_expr!(ctx => (~low) + U32(1));
+    let carry = naga_expr!(ctx => if (low == U32(0)) {U32(1)} else {U32(0)});
+    let negated_high = naga_expr!(ctx => (~high) + carry);
+    (negated_high, negated_low)
+}
+
+/// Gives whether an i64 given as its high word is negative
+fn emit_is_negative(
+    ctx: &mut BlockContext<'_>,
+    high: naga::Handle<naga::Expression>,
+) -> naga::Handle<naga::Expression> {
+    naga_expr!(ctx => (high >> U32(31)) == U32(1))
+}
+
+fn emit_math(
+    ctx: &mut BlockContext<'_>,
+    fun: naga::MathFunction,
+    arg: naga::Handle<naga::Expression>,
+) -> naga::Handle<naga::Expression> {
+    ctx.append_expr(naga::Expression::Math {
+        fun,
+        arg,
+        arg1: None,
+        arg2: None,
+        arg3: None,
+    })
+}
+
+/// fn(value: i64) -> i64
+///
+/// Counts bits across both words, where the count is at most 64 and so is held in the low word of the result.
+fn gen_bit_count(
+    module: &mut naga::Module,
+    i64_ty: i64_instance_gen::Ty,
+    name: &str,
+    make: impl FnOnce(
+        &mut BlockContext<'_>,
+        naga::Handle<naga::Expression>,
+        naga::Handle<naga::Expression>,
+    ) -> naga::Handle<naga::Expression>,
+) -> build::Result<naga::Handle<naga::Function>> {
+    let (function_handle, value) = declare_function! {
+        module => fn {format!("i64_{}", name)}(value: i64_ty) -> i64_ty
+    };
+    let mut ctx = BlockContext::from((module, function_handle));
+
+    let value_high = naga_expr!(&mut ctx => value[const 1]);
+    let value_low = naga_expr!(&mut ctx => value[const 0]);
+    let count = make(&mut ctx, value_high, value_low);
+    let res = naga_expr!(&mut ctx => i64_ty(count, U32(0)));
+    ctx.result(res);
+
+    Ok(function_handle)
+}
+
+/// fn(lhs: i64, rhs: i64) -> vec4<u32>
+///
+/// Unsigned long division, giving the low and high words of the quotient followed by the low and high words of the
+/// remainder. Each bit of the dividend is shifted into the remainder in turn, from the most significant, and the
+/// divisor is subtracted whenever it fits. Division by zero gives a quotient with every bit set and a remainder of
+/// `lhs`, so callers must trap before then.
+fn gen_long_division(
+    module: &mut naga::Module,
+    i64_ty: i64_instance_gen::Ty,
+    name: &str,
+) -> naga::Handle<naga::Function> {
+    let word_ty = module.types.insert_u32();
+    let res_ty = module.types.insert_anonymous(naga::TypeInner::Vector {
+        size: naga::VectorSize::Quad,
+        scalar: naga::Scalar::U32,
+    });
+    let (function_handle, lhs, rhs) = declare_function! {
+        module => fn {format!("i64_{}_long_division", name)}(lhs: i64_ty, rhs: i64_ty) -> res_ty
+    };
+    let mut ctx = BlockContext::from((module, function_handle));
+
+    let lhs_high = naga_expr!(&mut ctx => lhs[const 1]);
+    let lhs_low = naga_expr!(&mut ctx => lhs[const 0]);
+    let rhs_high = naga_expr!(&mut ctx => rhs[const 1]);
+    let rhs_low = naga_expr!(&mut ctx => rhs[const 0]);
+
+    let new_word = |ctx: &mut BlockContext<'_>, name: &str| {
+        let local = ctx.new_local(name, word_ty, None);
+        let local = ctx.local_expr(local);
+        let zero = naga_expr!(ctx => U32(0));
+        ctx.store(local, zero);
+        local
+    };
+    let quotient_high = new_word(&mut ctx, "quotient_high");
+    let quotient_low = new_word(&mut ctx, "quotient_low");
+    let remainder_high = new_word(&mut ctx, "remainder_high");
+    let remainder_low = new_word(&mut ctx, "remainder_low");
+
+    naga_expr!(&mut ctx => for i in (U32(0))..(U32(64)) |ctx| {
+        let bit = naga_expr!(&mut ctx => U32(63) - i);
+        let in_high_word = naga_expr!(&mut ctx => bit >= U32(32));
+        let shift = naga_expr!(&mut ctx => bit & U32(31));
+
+        // Shift the next bit of the dividend into the remainder. If the divisor is at least 2^63 then the
+        // remainder may overflow, but then the divisor always fits and the subtraction wraps back into range
+        let dividend_word = naga_expr!(&mut ctx => if (in_high_word) {lhs_high} else {lhs_low});
+        let next_bit = naga_expr!(&mut ctx => (dividend_word >> shift) & U32(1));
+        let old_high = naga_expr!(&mut ctx => Load(remainder_high));
+        let old_low = naga_expr!(&mut ctx => Load(remainder_low));
+        let overflowed = naga_expr!(&mut ctx => (old_high >> U32(31)) == U32(1));
+        let shifted_high = naga_expr!(&mut ctx => (old_high << U32(1)) | (old_low >> U32(31)));
+        let shifted_low = naga_expr!(&mut ctx => (old_low << U32(1)) | next_bit);
+
+        // Subtract the divisor if it fits
+        let fits = naga_expr!(&mut ctx =>
+            overflowed | ((shifted_high > rhs_high) | ((shifted_high == rhs_high) & (shifted_low >= rhs_low)))
+        );
+        let borrow = naga_expr!(&mut ctx => if (shifted_low < rhs_low) {U32(1)} else {U32(0)});
+        let subtracted_high = naga_expr!(&mut ctx => (shifted_high - rhs_high) - borrow);
+        let subtracted_low = naga_expr!(&mut ctx => shifted_low - rhs_low);
+        let new_high = naga_expr!(&mut ctx => if (fits) {subtracted_high} else {shifted_high});
+        let new_low = naga_expr!(&mut ctx => if (fits) {subtracted_low} else {shifted_low});
+        ctx.store(remainder_high, new_high);
+        ctx.store(remainder_low, new_low);
+
+        // Record the subtraction in the quotient
+        let quotient_bit = naga_expr!(&mut ctx => if (fits) {(U32(1)) << shift} else {U32(0)});
+        let quotient_bit_high = naga_expr!(&mut ctx => if (in_high_word) {quotient_bit} else {U32(0)});
+        let quotient_bit_low = naga_expr!(&mut ctx => if (in_high_word) {U32(0)} else {quotient_bit});
+        let new_quotient_high = naga_expr!(&mut ctx => (Load(quotient_high)) | quotient_bit_high);
+        let new_quotient_low = naga_expr!(&mut ctx => (Load(quotient_low)) | quotient_bit_low);
+        ctx.store(quotient_high, new_quotient_high);
+        ctx.store(quotient_low, new_quotient_low);
+    });
+
+    let res = naga_expr!(&mut ctx => res_ty(
+        (Load(quotient_low)),
+        (Load(quotient_high)),
+        (Load(remainder_low)),
+        (Load(remainder_high))
+    ));
+    ctx.result(res);
+
+    function_handle
+}
+
+/// fn(lhs: i64, rhs: i64) -> i64
+///
+/// Rotates across both words by first swapping the words if rotating by 32 or more, then shifting each word by the
+/// remaining amount and filling in the bits shifted out of the other word.
+fn gen_rotate(
+    module: &mut naga::Module,
+    i64_ty: i64_instance_gen::Ty,
+    name: &str,
+    rotate_left: bool,
+) -> naga::Handle<naga::Function> {
+    let (function_handle, lhs, rhs) = declare_function! {
+        module => fn {format!("i64_{}", name)}(lhs: i64_ty, rhs: i64_ty) -> i64_ty
+    };
+    let mut ctx = BlockContext::from((module, function_handle));
+
+    let lhs_high = naga_expr!(&mut ctx => lhs[const 1]);
+    let lhs_low = naga_expr!(&mut ctx => lhs[const 0]);
+
+    // A right rotation is a left rotation by the complement
+    let amount = naga_expr!(&mut ctx => rhs[const 0]);
+    let amount = if rotate_left {
+        naga_expr!(&mut ctx => amount & U32(63))
+    } else {
+        naga_expr!(&mut ctx => (U32(64) - (amount & U32(63))) & U32(63))
+    };
+
+    let swap_words = naga_expr!(&mut ctx => amount >= U32(32));
+    let high = naga_expr!(&mut ctx => if (swap_words) {lhs_low} else {lhs_high});
+    let low = naga_expr!(&mut ctx => if (swap_words) {lhs_high} else {lhs_low});
+
+    // Shifting a word by 32 is undefined, so rotations by a multiple of 32 are just the swap
+    let shift = naga_expr!(&mut ctx => amount & U32(31));
+    let carried_shift = naga_expr!(&mut ctx => (U32(32) - shift) & U32(31));
+    let is_aligned = naga_expr!(&mut ctx => shift == U32(0));
+    let carried_from_low =
+        naga_expr!(&mut ctx => if (is_aligned) {U32(0)} else {low >> carried_shift});
+    let carried_from_high =
+        naga_expr!(&mut ctx => if (is_aligned) {U32(0)} else {high >> carried_shift});
+    let res_high = naga_expr!(&mut ctx => (high << shift) | carried_from_low);
+    let res_low = naga_expr!(&mut ctx => (low << shift) | carried_from_high);
+
+    let res = naga_expr!(&mut ctx => i64_ty(res_low, res_high));
+    ctx.result(res);
+
+    function_handle
+}
+
+/// An implementation of i64s using a 2-vector of u32s, with the low word first so that values
+/// have the same layout as they do in memory and in the I/O buffers
 pub(crate) struct PolyfillI64;
 impl I64Gen for PolyfillI64 {
     fn gen_ty(
@@ -176,14 +375,14 @@ impl I64Gen for PolyfillI64 {
         };
         let mut ctx = BlockContext::from((module, function_handle));
 
-        let lhs_high = naga_expr!(&mut ctx => lhs[const 0]);
-        let lhs_low = naga_expr!(&mut ctx => lhs[const 1]);
-        let rhs_high = naga_expr!(&mut ctx => rhs[const 0]);
-        let rhs_low = naga_expr!(&mut ctx => rhs[const 1]);
+        let lhs_high = naga_expr!(&mut ctx => lhs[const 1]);
+        let lhs_low = naga_expr!(&mut ctx => lhs[const 0]);
+        let rhs_high = naga_expr!(&mut ctx => rhs[const 1]);
+        let rhs_low = naga_expr!(&mut ctx => rhs[const 0]);
         let carry_bit = naga_expr!(&mut ctx => if (lhs_low > (Constant(requirements.preamble.word_max) - rhs_low)) {U32(1)} else {U32(0)});
         let res_low = naga_expr!(&mut ctx => lhs_low + rhs_low);
         let res_high = naga_expr!(&mut ctx => lhs_high + rhs_high + carry_bit);
-        let res = naga_expr!(&mut ctx => i64_ty(res_high, res_low));
+        let res = naga_expr!(&mut ctx => i64_ty(res_low, res_high));
         ctx.result(res);
 
         Ok(function_handle)
@@ -199,10 +398,10 @@ impl I64Gen for PolyfillI64 {
         };
         let mut ctx = BlockContext::from((module, function_handle));
 
-        let lhs_high = naga_expr!(&mut ctx => lhs[const 0]);
-        let lhs_low = naga_expr!(&mut ctx => lhs[const 1]);
-        let rhs_high = naga_expr!(&mut ctx => rhs[const 0]);
-        let rhs_low = naga_expr!(&mut ctx => rhs[const 1]);
+        let lhs_high = naga_expr!(&mut ctx => lhs[const 1]);
+        let lhs_low = naga_expr!(&mut ctx => lhs[const 0]);
+        let rhs_high = naga_expr!(&mut ctx => rhs[const 1]);
+        let rhs_low = naga_expr!(&mut ctx => rhs[const 0]);
         let carry_condition = naga_expr!(&mut ctx => lhs_low < rhs_low);
         let res_low = naga_expr!(&mut ctx => if (carry_condition) {
             (Constant(requirements.preamble.word_max) - rhs_low) + lhs_low + U32(1)
@@ -210,7 +409,7 @@ impl I64Gen for PolyfillI64 {
             lhs_low - rhs_low
         });
         let res_high = naga_expr!(&mut ctx => lhs_high - rhs_high - if (carry_condition) {U32(1)} else {U32(0)});
-        let res = naga_expr!(&mut ctx => i64_ty(res_high, res_low));
+        let res = naga_expr!(&mut ctx => i64_ty(res_low, res_high));
         ctx.result(res);
 
         Ok(function_handle)
@@ -408,15 +607,105 @@ impl I64Gen for PolyfillI64 {
     super::impl_dud_integer_rmw! {i64_instance_gen, i64, atomic_rmw_32_xchg_u}
     super::impl_dud_integer_rmw! {i64_instance_gen, i64, atomic_rmw_32_cmpxchg_u}*/
 
-    super::impl_dud_inner_binexp! {i64_instance_gen, i64, clz }
-    super::impl_dud_inner_binexp! {i64_instance_gen, i64, ctz }
-    super::impl_dud_inner_binexp! {i64_instance_gen, i64, div_s }
-    super::impl_dud_inner_binexp! {i64_instance_gen, i64, div_u }
-    super::impl_dud_inner_binexp! {i64_instance_gen, i64, rem_s }
-    super::impl_dud_inner_binexp! {i64_instance_gen, i64, rem_u }
-    super::impl_dud_inner_binexp! {i64_instance_gen, i64, rotl }
-    super::impl_dud_inner_binexp! {i64_instance_gen, i64, rotr }
-    super::impl_dud_inner_binexp! {i64_instance_gen, i64, popcnt }
+    fn gen_clz(
+        module: &mut naga::Module,
+        requirements: i64_instance_gen::ClzRequirements,
+    ) -> build::Result<i64_instance_gen::Clz> {
+        gen_bit_count(module, *requirements.ty, "clz", |ctx, high, low| {
+            let high_count = emit_math(ctx, naga::MathFunction::CountLeadingZeros, high);
+            let low_count = emit_math(ctx, naga::MathFunction::CountLeadingZeros, low);
+            naga_expr!(ctx => if (high == U32(0)) {U32(32) + low_count} else {high_count})
+        })
+    }
+
+    fn gen_ctz(
+        module: &mut naga::Module,
+        requirements: i64_instance_gen::CtzRequirements,
+    ) -> build::Result<i64_instance_gen::Ctz> {
+        gen_bit_count(module, *requirements.ty, "ctz", |ctx, high, low| {
+            let high_count = emit_math(ctx, naga::MathFunction::CountTrailingZeros, high);
+            let low_count = emit_math(ctx, naga::MathFunction::CountTrailingZeros, low);
+            naga_expr!(ctx => if (low == U32(0)) {U32(32) + high_count} else {low_count})
+        })
+    }
+
+    fn gen_popcnt(
+        module: &mut naga::Module,
+        requirements: i64_instance_gen::PopcntRequirements,
+    ) -> build::Result<i64_instance_gen::Popcnt> {
+        gen_bit_count(module, *requirements.ty, "popcnt", |ctx, high, low| {
+            let high_count = emit_math(ctx, naga::MathFunction::CountOneBits, high);
+            let low_count = emit_math(ctx, naga::MathFunction::CountOneBits, low);
+            naga_expr!(ctx => high_count + low_count)
+        })
+    }
+
+    fn gen_div_s(
+        module: &mut naga::Module,
+        requirements: i64_instance_gen::DivSRequirements,
+    ) -> build::Result<i64_instance_gen::DivS> {
+        gen_signed_division(
+            module,
+            *requirements.ty,
+            requirements.preamble,
+            "div_s",
+            true,
+        )
+    }
+
+    fn gen_div_u(
+        module: &mut naga::Module,
+        requirements: i64_instance_gen::DivURequirements,
+    ) -> build::Result<i64_instance_gen::DivU> {
+        gen_unsigned_division(
+            module,
+            *requirements.ty,
+            requirements.preamble,
+            "div_u",
+            true,
+        )
+    }
+
+    fn gen_rem_s(
+        module: &mut naga::Module,
+        requirements: i64_instance_gen::RemSRequirements,
+    ) -> build::Result<i64_instance_gen::RemS> {
+        gen_signed_division(
+            module,
+            *requirements.ty,
+            requirements.preamble,
+            "rem_s",
+            false,
+        )
+    }
+
+    fn gen_rem_u(
+        module: &mut naga::Module,
+        requirements: i64_instance_gen::RemURequirements,
+    ) -> build::Result<i64_instance_gen::RemU> {
+        gen_unsigned_division(
+            module,
+            *requirements.ty,
+            requirements.preamble,
+            "rem_u",
+            false,
+        )
+    }
+
+    fn gen_rotl(
+        module: &mut naga::Module,
+        requirements: i64_instance_gen::RotlRequirements,
+    ) -> build::Result<i64_instance_gen::Rotl> {
+        Ok(gen_rotate(module, *requirements.ty, "rotl", true))
+    }
+
+    fn gen_rotr(
+        module: &mut naga::Module,
+        requirements: i64_instance_gen::RotrRequirements,
+    ) -> build::Result<i64_instance_gen::Rotr> {
+        Ok(gen_rotate(module, *requirements.ty, "rotr", false))
+    }
+
     super::impl_dud_inner_binexp! {i64_instance_gen, i64, and }
     super::impl_dud_inner_binexp! {i64_instance_gen, i64, or }
     super::impl_dud_inner_binexp! {i64_instance_gen, i64, xor }
@@ -433,10 +722,11 @@ impl I64Gen for PolyfillI64 {
         };
         let mut ctx = BlockContext::from((module, function_handle));
 
-        let low = naga_expr!(&mut ctx => value[const 1] as Sint);
-        let high = naga_expr!(&mut ctx => (low << U32(31)) >> U32(31));
+        // Sign extend within the low word, then fill the high word with the resulting sign bit
+        let low = naga_expr!(&mut ctx => value[const 0] as Sint);
         let low = naga_expr!(&mut ctx => (low << U32(24)) >> U32(24));
-        let res = naga_expr!(&mut ctx => (*requirements.ty)(high as Uint, low as Uint));
+        let high = naga_expr!(&mut ctx => low >> U32(31));
+        let res = naga_expr!(&mut ctx => (*requirements.ty)(low as Uint, high as Uint));
         ctx.result(res);
 
         Ok(function_handle)
@@ -451,10 +741,11 @@ impl I64Gen for PolyfillI64 {
         };
         let mut ctx = BlockContext::from((module, function_handle));
 
-        let low = naga_expr!(&mut ctx => value[const 1] as Sint);
-        let high = naga_expr!(&mut ctx => (low << U32(31)) >> U32(31));
+        // Sign extend within the low word, then fill the high word with the resulting sign bit
+        let low = naga_expr!(&mut ctx => value[const 0] as Sint);
         let low = naga_expr!(&mut ctx => (low << U32(16)) >> U32(16));
-        let res = naga_expr!(&mut ctx => (*requirements.ty)(high as Uint, low as Uint));
+        let high = naga_expr!(&mut ctx => low >> U32(31));
+        let res = naga_expr!(&mut ctx => (*requirements.ty)(low as Uint, high as Uint));
         ctx.result(res);
 
         Ok(function_handle)
@@ -465,13 +756,13 @@ impl I64Gen for PolyfillI64 {
         requirements: i64_instance_gen::Extend32SRequirements,
     ) -> build::Result<i64_instance_gen::Extend32S> {
         let (function_handle, value) = declare_function! {
-            module => fn i64_extend_16_s(value: *requirements.ty) -> *requirements.ty
+            module => fn i64_extend_32_s(value: *requirements.ty) -> *requirements.ty
         };
         let mut ctx = BlockContext::from((module, function_handle));
 
-        let low = naga_expr!(&mut ctx => value[const 1]);
-        let high = naga_expr!(&mut ctx => ((low as Sint) << U32(31)) >> U32(31));
-        let res = naga_expr!(&mut ctx => (*requirements.ty)(high as Uint, low));
+        let low = naga_expr!(&mut ctx => value[const 0]);
+        let high = naga_expr!(&mut ctx => (low as Sint) >> U32(31));
+        let res = naga_expr!(&mut ctx => (*requirements.ty)(low, high as Uint));
         ctx.result(res);
 
         Ok(function_handle)
@@ -528,3 +819,123 @@ fn gen_write(
 
     Ok(function_handle)
 }
+
+// fn(lhs: i64, rhs: i64) -> i64
+fn gen_unsigned_division(
+    module: &mut naga::Module,
+    i64_ty: i64_instance_gen::Ty,
+    preamble: &crate::std_objects::PreambleObjects,
+    name: &str,
+    is_quotient: bool,
+) -> build::Result<naga::Handle<naga::Function>> {
+    let long_division = gen_long_division(module, i64_ty, name);
+
+    let (function_handle, lhs, rhs) = declare_function! {
+        module => fn {format!("i64_{}", name)}(lhs: i64_ty, rhs: i64_ty) -> i64_ty
+    };
+    let mut ctx = BlockContext::from((module, function_handle));
+
+    // Div by 0 test
+    let is_0 = naga_expr!(&mut ctx => (rhs[const 1] == U32(0)) & (rhs[const 0] == U32(0)));
+    ctx.test(is_0).then(|mut ctx| {
+        preamble.trap_values.emit_set_trap(
+            &mut ctx,
+            Trap::IntegerDivisionByZero,
+            preamble.trap_state,
+        );
+        // Skip the long division entirely and bail out with a poison value
+        let poison = naga_expr!(&mut ctx => i64_ty(U32(0), U32(0)));
+        ctx.result(poison);
+    });
+
+    let division = ctx.call_get_return(long_division, vec![lhs, rhs]);
+    let res = if is_quotient {
+        naga_expr!(&mut ctx => i64_ty((division[const 0]), (division[const 1])))
+    } else {
+        naga_expr!(&mut ctx => i64_ty((division[const 2]), (division[const 3])))
+    };
+    ctx.result(res);
+
+    Ok(function_handle)
+}
+
+// fn(lhs: i64, rhs: i64) -> i64
+fn gen_signed_division(
+    module: &mut naga::Module,
+    i64_ty: i64_instance_gen::Ty,
+    preamble: &crate::std_objects::PreambleObjects,
+    name: &str,
+    is_quotient: bool,
+) -> build::Result<naga::Handle<naga::Function>> {
+    let long_division = gen_long_division(module, i64_ty, name);
+
+    let (function_handle, lhs, rhs) = declare_function! {
+        module => fn {format!("i64_{}", name)}(lhs: i64_ty, rhs: i64_ty) -> i64_ty
+    };
+    let mut ctx = BlockContext::from((module, function_handle));
+
+    let lhs_high = naga_expr!(&mut ctx => lhs[const 1]);
+    let lhs_low = naga_expr!(&mut ctx => lhs[const 0]);
+    let rhs_high = naga_expr!(&mut ctx => rhs[const 1]);
+    let rhs_low = naga_expr!(&mut ctx => rhs[const 0]);
+
+    // Div by 0 test
+    let is_0 = naga_expr!(&mut ctx => (rhs_high == U32(0)) & (rhs_low == U32(0)));
+    ctx.test(is_0).then(|mut ctx| {
+        preamble.trap_values.emit_set_trap(
+            &mut ctx,
+            Trap::IntegerDivisionByZero,
+            preamble.trap_state,
+        );
+        // Skip the long division entirely and bail out with a poison value
+        let poison = naga_expr!(&mut ctx => i64_ty(U32(0), U32(0)));
+        ctx.result(poison);
+    });
+
+    // Overflow test, only the quotient of `i64::MIN / -1` is unrepresentable
+    if is_quotient {
+        let is_overflowing = naga_expr!(&mut ctx =>
+            ((lhs_high == U32(0x80000000)) & (lhs_low == U32(0)))
+                & ((rhs_high == U32(0xffffffff)) & (rhs_low == U32(0xffffffff)))
+        );
+        ctx.test(is_overflowing).then(|mut ctx| {
+            preamble.trap_values.emit_set_trap(
+                &mut ctx,
+                Trap::IntegerOverflow,
+                preamble.trap_state,
+            );
+            let poison = naga_expr!(&mut ctx => i64_ty(U32(0), U32(0)));
+            ctx.result(poison);
+        });
+    }
+
+    // Divide the magnitudes, then fix up the sign. The quotient is negative if exactly one operand is, and the
+    // remainder takes the sign of the dividend
+    let lhs_is_negative = emit_is_negative(&mut ctx, lhs_high);
+    let rhs_is_negative = emit_is_negative(&mut ctx, rhs_high);
+    let (negated_lhs_high, negated_lhs_low) = emit_negate(&mut ctx, lhs_high, lhs_low);
+    let (negated_rhs_high, negated_rhs_low) = emit_negate(&mut ctx, rhs_high, rhs_low);
+    let abs_lhs = naga_expr!(&mut ctx => if (lhs_is_negative) {i64_ty(negated_lhs_low, negated_lhs_high)} else {lhs});
+    let abs_rhs = naga_expr!(&mut ctx => if (rhs_is_negative) {i64_ty(negated_rhs_low, negated_rhs_high)} else {rhs});
+
+    let division = ctx.call_get_return(long_division, vec![abs_lhs, abs_rhs]);
+    let (res_high, res_low, res_is_negative) = if is_quotient {
+        let res_high = naga_expr!(&mut ctx => division[const 1]);
+        let res_low = naga_expr!(&mut ctx => division[const 0]);
+        let res_is_negative = naga_expr!(&mut ctx => lhs_is_negative != rhs_is_negative);
+        (res_high, res_low, res_is_negative)
+    } else {
+        let res_high = naga_expr!(&mut ctx => division[const 3]);
+        let res_low = naga_expr!(&mut ctx => division[const 2]);
+        (res_high, res_low, lhs_is_negative)
+    };
+    let (negated_res_high, negated_res_low) = emit_negate(&mut ctx, res_high, res_low);
+    let res = naga_expr!(&mut ctx => if (res_is_negative) {
+        i64_ty(negated_res_low, negated_res_high)
+    } else {
+        i64_ty(res_low, res_high)
+    });
+    ctx.result(res);
+
+    Ok(function_handle)
+}
@@ -1,27 +1,190 @@
 use crate::build;
 use crate::std_objects::preamble_objects_gen;
 use crate::typed::V128;
-use naga_ext::{declare_function, naga_expr, BlockContext, ConstantsExt, ExpressionsExt, TypesExt};
+use naga_ext::prelude::*;
 
 use super::{v128_instance_gen, V128Gen};
 
+/// Builds a constant `u32` in the shape of the words being operated on. The scalar polyfill operates on one
+/// word at a time, but the same bit-twiddling can be reused by implementations which operate on whole vectors.
+pub(super) type WordConst<'a> =
+    &'a dyn Fn(&mut BlockContext<'_>, u32) -> naga::Handle<naga::Expression>;
+
+/// Adds the lanes packed within words without carrying between lanes. `high_bits` has the top bit of every lane set.
+pub(super) fn packed_add(
+    ctx: &mut BlockContext<'_>,
+    lhs: naga::Handle<naga::Expression>,
+    rhs: naga::Handle<naga::Expression>,
+    high_bits: u32,
+    word: WordConst<'_>,
+) -> naga::Handle<naga::Expression> {
+    let high = word(ctx, high_bits);
+    let low = word(ctx, !high_bits);
+    naga_expr!(ctx => (((lhs & low) + (rhs & low)) ^ ((lhs ^ rhs) & high)))
+}
+
+/// Subtracts the lanes packed within words without borrowing between lanes. `high_bits` has the top bit of every
+/// lane set.
+pub(super) fn packed_sub(
+    ctx: &mut BlockContext<'_>,
+    lhs: naga::Handle<naga::Expression>,
+    rhs: naga::Handle<naga::Expression>,
+    high_bits: u32,
+    word: WordConst<'_>,
+) -> naga::Handle<naga::Expression> {
+    let high = word(ctx, high_bits);
+    let low = word(ctx, !high_bits);
+    naga_expr!(ctx => (((lhs | high) - (rhs & low)) ^ ((lhs ^ (~rhs)) & high)))
+}
+
+/// Multiplies the two 16-bit lanes packed within words, discarding the upper half of each product.
+pub(super) fn packed_mul_16(
+    ctx: &mut BlockContext<'_>,
+    lhs: naga::Handle<naga::Expression>,
+    rhs: naga::Handle<naga::Expression>,
+    word: WordConst<'_>,
+) -> naga::Handle<naga::Expression> {
+    let mask = word(ctx, 0xFFFF);
+    let shift = word(ctx, 16);
+    let low = naga_expr!(ctx => ((lhs & mask) * (rhs & mask)) & mask);
+    let high = naga_expr!(ctx => ((lhs >> shift) * (rhs >> shift)) << shift);
+    naga_expr!(ctx => low | high)
+}
+
+/// Declares `fn(lhs: v128, rhs: v128) -> v128`, built from the result of operating on each pair of words
+fn gen_word_wise_binary(
+    module: &mut naga::Module,
+    v128_ty: naga::Handle<naga::Type>,
+    name: &str,
+    make: impl Fn(
+        &mut BlockContext<'_>,
+        naga::Handle<naga::Expression>,
+        naga::Handle<naga::Expression>,
+    ) -> naga::Handle<naga::Expression>,
+) -> build::Result<naga::Handle<naga::Function>> {
+    let (function_handle, lhs, rhs) = declare_function! {
+        module => fn {format!("v128_{}", name)}(lhs: v128_ty, rhs: v128_ty) -> v128_ty
+    };
+    let mut ctx = BlockContext::from((module, function_handle));
+
+    let mut components = Vec::new();
+    for i in 0..4 {
+        let lhs_word = naga_expr!(&mut ctx => lhs[const i]);
+        let rhs_word = naga_expr!(&mut ctx => rhs[const i]);
+        components.push(make(&mut ctx, lhs_word, rhs_word));
+    }
+    let res = ctx.append_expr(naga::Expression::Compose {
+        ty: v128_ty,
+        components,
+    });
+    ctx.result(res);
+
+    Ok(function_handle)
+}
+
+/// The `(low, high)` words of a 64-bit lane
+pub(super) type LaneWords = (
+    naga::Handle<naga::Expression>,
+    naga::Handle<naga::Expression>,
+);
+
+/// Declares `fn(lhs: v128, rhs: v128) -> v128`, built from the result of operating on each pair of 64-bit lanes
+fn gen_i64_lane_wise_binary(
+    module: &mut naga::Module,
+    v128_ty: naga::Handle<naga::Type>,
+    name: &str,
+    make: impl Fn(&mut BlockContext<'_>, LaneWords, LaneWords) -> LaneWords,
+) -> build::Result<naga::Handle<naga::Function>> {
+    let (function_handle, lhs, rhs) = declare_function! {
+        module => fn {format!("v128_{}", name)}(lhs: v128_ty, rhs: v128_ty) -> v128_ty
+    };
+    let mut ctx = BlockContext::from((module, function_handle));
+
+    let mut components = Vec::new();
+    for lane in 0..2 {
+        // Lanes are little-endian, so the low word comes first
+        let lhs_low = naga_expr!(&mut ctx => lhs[const lane * 2]);
+        let lhs_high = naga_expr!(&mut ctx => lhs[const lane * 2 + 1]);
+        let rhs_low = naga_expr!(&mut ctx => rhs[const lane * 2]);
+        let rhs_high = naga_expr!(&mut ctx => rhs[const lane * 2 + 1]);
+        let (low, high) = make(&mut ctx, (lhs_low, lhs_high), (rhs_low, rhs_high));
+        components.push(low);
+        components.push(high);
+    }
+    let res = ctx.append_expr(naga::Expression::Compose {
+        ty: v128_ty,
+        components,
+    });
+    ctx.result(res);
+
+    Ok(function_handle)
+}
+
+/// Declares `fn(lhs: v128, rhs: v128) -> v128` which calls an f32 function on each pair of lanes, so that lanes
+/// get the same subnormal and range handling as scalar f32s.
+fn gen_f32_lane_wise_binary(
+    module: &mut naga::Module,
+    v128_ty: naga::Handle<naga::Type>,
+    name: &str,
+    f32_fn: naga::Handle<naga::Function>,
+) -> build::Result<naga::Handle<naga::Function>> {
+    gen_word_wise_binary(module, v128_ty, name, |ctx, lhs, rhs| {
+        let lhs = naga_expr!(ctx => bitcast<f32>(lhs));
+        let rhs = naga_expr!(ctx => bitcast<f32>(rhs));
+        let res = ctx.call_get_return(f32_fn, vec![lhs, rhs]);
+        naga_expr!(ctx => bitcast<u32>(res))
+    })
+}
+
+macro_rules! impl_word_wise_binexp {
+    ($op_name:ident; $op:tt) => {
+        paste::paste! {
+            fn [< gen_ $op_name >](
+                module: &mut naga::Module,
+                requirements: v128_instance_gen::[< $op_name:camel Requirements >],
+            ) -> build::Result<v128_instance_gen::[< $op_name:camel >]> {
+                gen_word_wise_binary(module, *requirements.ty, stringify!($op_name), |ctx, lhs, rhs| {
+                    naga_expr!(ctx => lhs $op rhs)
+                })
+            }
+        }
+    };
+}
+
+macro_rules! impl_packed_binexp {
+    ($op_name:ident; $packed_fn:ident; $high_bits:expr) => {
+        paste::paste! {
+            fn [< gen_ $op_name >](
+                module: &mut naga::Module,
+                requirements: v128_instance_gen::[< $op_name:camel Requirements >],
+            ) -> build::Result<v128_instance_gen::[< $op_name:camel >]> {
+                gen_word_wise_binary(module, *requirements.ty, stringify!($op_name), |ctx, lhs, rhs| {
+                    $packed_fn(ctx, lhs, rhs, $high_bits, &|ctx, value| naga_expr!(ctx => U32(value)))
+                })
+            }
+        }
+    };
+}
+
+macro_rules! impl_f32_lane_wise_binexp {
+    ($op_name:ident; $f32_fn:ident) => {
+        paste::paste! {
+            fn [< gen_ $op_name >](
+                module: &mut naga::Module,
+                requirements: v128_instance_gen::[< $op_name:camel Requirements >],
+            ) -> build::Result<v128_instance_gen::[< $op_name:camel >]> {
+                gen_f32_lane_wise_binary(module, *requirements.ty, stringify!($op_name), requirements.f32.$f32_fn)
+            }
+        }
+    };
+}
+
 fn make_const_expr_impl(
     const_expressions: &mut naga::Arena<naga::Expression>,
     ty: naga::Handle<naga::Type>,
     value: V128,
 ) -> naga::Handle<naga::Expression> {
-    let bytes = value.to_le_bytes();
-    let components = bytes
-        .as_chunks::<4>()
-        .0
-        .iter()
-        .map(|bytes| {
-            let word = u32::from_le_bytes(*bytes);
-            let word = u32::try_from(word & 0xFFFFFFFF).expect("truncated word always fits in u32");
-            const_expressions.append_u32(word)
-        })
-        .collect();
-    const_expressions.append_compose(ty, components)
+    const_expressions.append_u128(ty, value.bits())
 }
 
 /// An implementation of v128s using a 4-vector of u32s. Calling this a Polyfill is a slight stretch
@@ -120,6 +283,102 @@ impl V128Gen for PolyfillV128 {
             "memory",
         )
     }
+
+    fn gen_not(
+        module: &mut naga::Module,
+        requirements: v128_instance_gen::NotRequirements,
+    ) -> build::Result<v128_instance_gen::Not> {
+        let v128_ty = *requirements.ty;
+        let (function_handle, value) = declare_function! {
+            module => fn v128_not(value: v128_ty) -> v128_ty
+        };
+        let mut ctx = BlockContext::from((module, function_handle));
+
+        let mut components = Vec::new();
+        for i in 0..4 {
+            components.push(naga_expr!(&mut ctx => ~(value[const i])));
+        }
+        let res = ctx.append_expr(naga::Expression::Compose {
+            ty: v128_ty,
+            components,
+        });
+        ctx.result(res);
+
+        Ok(function_handle)
+    }
+
+    impl_word_wise_binexp! { and; & }
+    impl_word_wise_binexp! { or; | }
+    impl_word_wise_binexp! { xor; ^ }
+
+    fn gen_and_not(
+        module: &mut naga::Module,
+        requirements: v128_instance_gen::AndNotRequirements,
+    ) -> build::Result<v128_instance_gen::AndNot> {
+        gen_word_wise_binary(
+            module,
+            *requirements.ty,
+            "and_not",
+            |ctx, lhs, rhs| naga_expr!(ctx => lhs & (~rhs)),
+        )
+    }
+
+    impl_packed_binexp! { i8x16_add; packed_add; 0x80808080 }
+    impl_packed_binexp! { i8x16_sub; packed_sub; 0x80808080 }
+    impl_packed_binexp! { i16x8_add; packed_add; 0x80008000 }
+    impl_packed_binexp! { i16x8_sub; packed_sub; 0x80008000 }
+
+    fn gen_i16x8_mul(
+        module: &mut naga::Module,
+        requirements: v128_instance_gen::I16x8MulRequirements,
+    ) -> build::Result<v128_instance_gen::I16x8Mul> {
+        gen_word_wise_binary(module, *requirements.ty, "i16x8_mul", |ctx, lhs, rhs| {
+            packed_mul_16(ctx, lhs, rhs, &|ctx, value| naga_expr!(ctx => U32(value)))
+        })
+    }
+
+    impl_word_wise_binexp! { i32x4_add; + }
+    impl_word_wise_binexp! { i32x4_sub; - }
+    impl_word_wise_binexp! { i32x4_mul; * }
+
+    fn gen_i64x2_add(
+        module: &mut naga::Module,
+        requirements: v128_instance_gen::I64x2AddRequirements,
+    ) -> build::Result<v128_instance_gen::I64x2Add> {
+        gen_i64_lane_wise_binary(
+            module,
+            *requirements.ty,
+            "i64x2_add",
+            |ctx, (lhs_low, lhs_high), (rhs_low, rhs_high)| {
+                let low = naga_expr!(ctx => lhs_low + rhs_low);
+                let carry = naga_expr!(ctx => if (low < lhs_low) {U32(1)} else {U32(0)});
+                let high = naga_expr!(ctx => lhs_high + (rhs_high + carry));
+                (low, high)
+            },
+        )
+    }
+
+    fn gen_i64x2_sub(
+        module: &mut naga::Module,
+        requirements: v128_instance_gen::I64x2SubRequirements,
+    ) -> build::Result<v128_instance_gen::I64x2Sub> {
+        gen_i64_lane_wise_binary(
+            module,
+            *requirements.ty,
+            "i64x2_sub",
+            |ctx, (lhs_low, lhs_high), (rhs_low, rhs_high)| {
+                let low = naga_expr!(ctx => lhs_low - rhs_low);
+                let borrow = naga_expr!(ctx => if (lhs_low < rhs_low) {U32(1)} else {U32(0)});
+                let high = naga_expr!(ctx => lhs_high - (rhs_high + borrow));
+                (low, high)
+            },
+        )
+    }
+
+    impl_f32_lane_wise_binexp! { f32x4_add; add }
+    impl_f32_lane_wise_binexp! { f32x4_sub; sub }
+    impl_f32_lane_wise_binexp! { f32x4_mul; mul }
+    impl_f32_lane_wise_binexp! { f32x4_div; div }
 }
 
 // fn<buffer>(word_address: u32) -> v128
@@ -44,3 +44,24 @@ pub fn u32_to_trap(trap: u32) -> Option<Trap> {
         _ => panic!("unsupported trap code: {:?}", trap),
     }
 }
+
+/// Gives a human readable reason for a trap, matching the messages given by wasmtime.
+pub fn trap_message(trap: Trap) -> &'static str {
+    match trap {
+        Trap::StackOverflow => "call stack exhausted",
+        Trap::MemoryOutOfBounds => "out of bounds memory access",
+        Trap::HeapMisaligned => "misaligned memory access",
+        Trap::TableOutOfBounds => "undefined element: out of bounds table access",
+        Trap::IndirectCallToNull => "uninitialized element",
+        Trap::BadSignature => "indirect call type mismatch",
+        Trap::IntegerOverflow => "integer overflow",
+        Trap::IntegerDivisionByZero => "integer divide by zero",
+        Trap::BadConversionToInteger => "invalid conversion to integer",
+        Trap::UnreachableCodeReached => "wasm `unreachable` instruction executed",
+        Trap::Interrupt => "interrupt",
+        Trap::AlwaysTrapAdapter => "degenerate component adapter called",
+        Trap::OutOfFuel => "all fuel consumed by WebAssembly",
+        Trap::AtomicWaitNonSharedMemory => "atomic wait on non-shared memory",
+        _ => panic!("unsupported trap code: {:?}", trap),
+    }
+}
@@ -152,6 +152,8 @@ impl Val {
         }
     }
 
+    /// The little-endian bytes of this value. This matches the layout used by the I/O buffers
+    /// of a generated shader, where 64-bit values span two words with the low word first.
     pub fn to_bytes(&self) -> Vec<u8> {
         match self {
             Val::I32(v) => WasmTyVal::to_bytes(v),
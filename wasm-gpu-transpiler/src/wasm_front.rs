@@ -1,7 +1,9 @@
 //! This module defines our interface to shader generation, i.e. how WASM should be specified when
 //! handed off to this package.
 
-use std::ops::Deref;
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::ops::{Deref, Range};
 use std::sync::Arc;
 
 use crate::typed::FuncRef;
@@ -50,6 +52,30 @@ impl_index!(pub struct GlobalImmutableIndex);
 impl_index!(pub struct ElementIndex);
 impl_index!(pub struct DataIndex);
 
+/// A hasher whose output only depends on what is written to it, unlike `DefaultHasher`, so that hashes can be saved
+/// and compared between runs of a program. This is 64-bit FNV-1a.
+#[derive(Debug, Clone)]
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum GlobalIndex {
     Mutable(GlobalMutableIndex),
@@ -67,17 +93,43 @@ pub struct FuncData<'a> {
     pub ty: FuncType,
     pub locals: Vec<(u32, ValType)>,
     pub operators: Vec<OperatorByProposal<'a>>,
+    /// The bytes of the module's binary that each of `operators` was read from, used for debug spans
+    pub operator_ranges: Vec<Range<usize>>,
+    /// A [`StableHasher`] hash of the bytes of the function's body, including its locals, used to tell cached modules
+    /// apart
+    pub body_hash: u64,
     pub module_data: Arc<FunctionModuleData>,
+    /// The name given to the function by the module's name section, if any
+    pub name: Option<String>,
+    /// The names given to the function's parameters and locals by the module's name section, by local index
+    pub local_names: HashMap<u32, String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct FuncAccessible {
     pub func_index_lookup: Vec<FuncRef>,
     pub global_index_lookup: Vec<GlobalIndex>,
     pub element_index_lookup: Vec<ElementIndex>,
+    /// The number of references in each element segment in `element_index_lookup`
+    pub element_size_lookup: Vec<u32>,
+    /// The position of each element segment in `element_index_lookup` within the store set, which selects its dropped flag
+    pub element_segment_lookup: Vec<u32>,
     pub table_index_lookup: Vec<TableIndex>,
+    /// The number of entries in each table in `table_index_lookup`
+    pub table_size_lookup: Vec<u32>,
+    /// The type of the entries of each table in `table_index_lookup`
+    pub table_type_lookup: Vec<ValType>,
     pub data_index_lookup: Vec<DataIndex>,
+    /// The number of bytes in each data segment in `data_index_lookup`
+    pub data_size_lookup: Vec<u32>,
+    /// The position of each data segment in `data_index_lookup` within the store set, which selects its dropped flag
+    pub data_segment_lookup: Vec<u32>,
     pub memory_index_lookup: Vec<MemoryIndex>,
+    /// The number of pages allocated for each memory in `memory_index_lookup`, which bounds `memory.grow`
+    pub memory_capacity_lookup: Vec<u32>,
+    /// The initial number of pages of each memory in `memory_index_lookup`. Only the memory at the start of the
+    /// memory buffer can grow, so every other memory keeps this size
+    pub memory_pages_lookup: Vec<u32>,
 }
 
 impl FuncAccessible {
@@ -86,9 +138,17 @@ impl FuncAccessible {
             func_index_lookup: Vec::new(),
             global_index_lookup: Vec::new(),
             element_index_lookup: Vec::new(),
+            element_size_lookup: Vec::new(),
+            element_segment_lookup: Vec::new(),
             table_index_lookup: Vec::new(),
+            table_size_lookup: Vec::new(),
+            table_type_lookup: Vec::new(),
             data_index_lookup: Vec::new(),
+            data_size_lookup: Vec::new(),
+            data_segment_lookup: Vec::new(),
             memory_index_lookup: Vec::new(),
+            memory_capacity_lookup: Vec::new(),
+            memory_pages_lookup: Vec::new(),
         }
     }
 }
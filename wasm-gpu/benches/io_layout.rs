@@ -0,0 +1,88 @@
+//! Compares the time taken to marshal arguments and results for a wide dispatch when each invocation's values are
+//! packed together against when each value is laid out in its own array.
+//! Run with `cargo bench -p wasm-gpu --bench io_layout`.
+use std::time::{Duration, Instant};
+
+use wasm_gpu::{imports, IoLayout, MappedStoreSetBuilder, Module, Tuneables};
+use wasm_gpu_test_lib::shared_backend;
+
+const INSTANCE_COUNT: usize = 65_536;
+const ITERATIONS: usize = 5;
+const LAYOUTS: [IoLayout; 2] = [IoLayout::Packed, IoLayout::StructOfArrays];
+
+/// A module with a single function that does very little work with many arguments and results.
+const WIDE_IO_WAT: &str = r#"
+(module
+    (func $f (export "f") (param i32 i64 f32 f64) (result f64 i32 i64)
+        (f64.add (f64.promote_f32 (local.get 2)) (local.get 3))
+        (i32.add (local.get 0) (i32.wrap_i64 (local.get 1)))
+        (i64.mul (local.get 1) (i64.extend_i32_s (local.get 0)))
+    )
+)
+"#;
+
+async fn time_call(module: &Module, io_layout: IoLayout) -> Duration {
+    let (memory_system, queue) = shared_backend();
+
+    let mut stores_builder = MappedStoreSetBuilder::new(
+        memory_system,
+        "bench_module",
+        Tuneables {
+            io_layout,
+            ..Default::default()
+        },
+    );
+    let instances = stores_builder
+        .instantiate_module(queue, module, imports! {})
+        .await
+        .expect("could not instantiate module");
+    let f = instances
+        .get_func("f")
+        .expect("module exports f")
+        .try_typed::<(i32, i64, f32, f64), (f64, i32, i64)>()
+        .expect("f has the signature (i32, i64, f32, f64) -> (f64, i32, i64)");
+    let store_source = stores_builder
+        .complete(queue)
+        .await
+        .expect("could not complete store builder");
+    let mut stores = store_source
+        .build(memory_system, queue, INSTANCE_COUNT)
+        .await
+        .expect("could not build stores");
+
+    let inputs = (0..INSTANCE_COUNT)
+        .map(|i| (i as i32, i as i64 * 3, i as f32 / 2.0, i as f64 * 1.5))
+        .collect();
+
+    let start = Instant::now();
+    f.call_all(memory_system, queue, &mut stores, inputs)
+        .await
+        .expect("could not allocate call buffers")
+        .await
+        .expect("could not read results buffers");
+    return start.elapsed();
+}
+
+fn main() {
+    let module = Module::new(
+        &wasm_gpu::WasmFeatures::default(),
+        WIDE_IO_WAT.as_bytes(),
+        "bench_module".to_owned(),
+    )
+    .expect("could not parse module");
+
+    for io_layout in LAYOUTS {
+        let mut timings = (0..ITERATIONS)
+            .map(|_| pollster::block_on(time_call(&module, io_layout)))
+            .collect::<Vec<_>>();
+        timings.sort();
+
+        println!(
+            "{} instances, io_layout = {:?}: median {:?}, min {:?}",
+            INSTANCE_COUNT,
+            io_layout,
+            timings[ITERATIONS / 2],
+            timings[0]
+        );
+    }
+}
@@ -0,0 +1,84 @@
+//! Compares the time taken to generate the shader for a module with many functions when function bodies are
+//! generated on a thread pool against when they are generated one after another.
+//! Run with `cargo bench -p wasm-gpu --features parallel`.
+use std::time::{Duration, Instant};
+
+use wasm_gpu::{imports, MappedStoreSetBuilder, Module, Tuneables};
+use wasm_gpu_test_lib::shared_backend;
+
+const FUNCTION_COUNT: usize = 400;
+const ITERATIONS: usize = 5;
+
+/// A module with many independent functions, each with enough control flow to take a while to lower.
+fn many_functions_wat() -> String {
+    let mut wat = String::from("(module\n");
+    for i in 0..FUNCTION_COUNT {
+        wat += &format!(
+            r#"
+            (func $f{i} (export "f{i}") (param i32 i32) (result i32)
+                (local i32)
+                (local.set 2 (i32.mul (local.get 0) (i32.const {i})))
+                (block
+                    (loop
+                        (br_if 1 (i32.ge_u (local.get 2) (local.get 1)))
+                        (local.set 2 (i32.add (local.get 2) (i32.xor (local.get 0) (i32.const 7))))
+                        (br 0)
+                    )
+                )
+                (i32.add (local.get 2) (i32.const {i}))
+            )
+            "#
+        );
+    }
+    wat += ")";
+    return wat;
+}
+
+async fn time_codegen(module: &Module, parallel_codegen: bool) -> Duration {
+    let (memory_system, queue) = shared_backend();
+
+    let mut stores_builder = MappedStoreSetBuilder::new(
+        memory_system,
+        "bench_module",
+        Tuneables {
+            parallel_codegen,
+            ..Default::default()
+        },
+    );
+    stores_builder
+        .instantiate_module(queue, module, imports! {})
+        .await
+        .expect("could not instantiate module");
+
+    let start = Instant::now();
+    stores_builder
+        .complete(queue)
+        .await
+        .expect("could not complete store builder");
+    return start.elapsed();
+}
+
+fn main() {
+    let wat = many_functions_wat();
+    let module = Module::new(
+        &wasm_gpu::WasmFeatures::default(),
+        wat.as_bytes(),
+        "bench_module".to_owned(),
+    )
+    .expect("could not parse module");
+
+    for parallel_codegen in [false, true] {
+        let mut timings = (0..ITERATIONS)
+            .map(|_| pollster::block_on(time_codegen(&module, parallel_codegen)))
+            .collect::<Vec<_>>();
+        timings.sort();
+
+        println!(
+            "{} functions, parallel_codegen = {}: median {:?}, min {:?}",
+            FUNCTION_COUNT,
+            parallel_codegen,
+            timings[ITERATIONS / 2],
+            timings[0]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! Compares the time taken to run a tight loop when the trap state is read on every iteration against when it is
+//! only read every few iterations.
+//! Run with `cargo bench -p wasm-gpu --bench trap_check_interval`.
+use std::time::{Duration, Instant};
+
+use wasm_gpu::{imports, MappedStoreSetBuilder, Module, Tuneables};
+use wasm_gpu_test_lib::shared_backend;
+
+const LOOP_ITERATIONS: i32 = 1_000_000;
+const INSTANCE_COUNT: usize = 256;
+const ITERATIONS: usize = 5;
+const INTERVALS: [u32; 4] = [1, 16, 256, 4096];
+
+/// A module with a single function that spends all of its time in a loop doing very little work.
+const TIGHT_LOOP_WAT: &str = r#"
+(module
+    (func $f (export "f") (param i32) (result i32)
+        (local i32)
+        (loop
+            (local.set 1 (i32.add (local.get 1) (i32.xor (local.get 0) (i32.const 7))))
+            (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+            (br_if 0 (local.get 0))
+        )
+        (local.get 1)
+    )
+)
+"#;
+
+async fn time_loop(module: &Module, trap_check_interval: u32) -> Duration {
+    let (memory_system, queue) = shared_backend();
+
+    let mut stores_builder = MappedStoreSetBuilder::new(
+        memory_system,
+        "bench_module",
+        Tuneables {
+            trap_check_interval,
+            ..Default::default()
+        },
+    );
+    let instances = stores_builder
+        .instantiate_module(queue, module, imports! {})
+        .await
+        .expect("could not instantiate module");
+    let f = instances
+        .get_func("f")
+        .expect("module exports f")
+        .try_typed::<i32, i32>()
+        .expect("f has the signature i32 -> i32");
+    let store_source = stores_builder
+        .complete(queue)
+        .await
+        .expect("could not complete store builder");
+    let mut stores = store_source
+        .build(memory_system, queue, INSTANCE_COUNT)
+        .await
+        .expect("could not build stores");
+
+    let start = Instant::now();
+    f.call_all(
+        memory_system,
+        queue,
+        &mut stores,
+        vec![LOOP_ITERATIONS; INSTANCE_COUNT],
+    )
+    .await
+    .expect("could not allocate call buffers")
+    .await
+    .expect("could not read results buffers");
+    return start.elapsed();
+}
+
+fn main() {
+    let module = Module::new(
+        &wasm_gpu::WasmFeatures::default(),
+        TIGHT_LOOP_WAT.as_bytes(),
+        "bench_module".to_owned(),
+    )
+    .expect("could not parse module");
+
+    for trap_check_interval in INTERVALS {
+        let mut timings = (0..ITERATIONS)
+            .map(|_| pollster::block_on(time_loop(&module, trap_check_interval)))
+            .collect::<Vec<_>>();
+        timings.sort();
+
+        println!(
+            "{} loop iterations, trap_check_interval = {}: median {:?}, min {:?}",
+            LOOP_ITERATIONS,
+            trap_check_interval,
+            timings[ITERATIONS / 2],
+            timings[0]
+        );
+    }
+}
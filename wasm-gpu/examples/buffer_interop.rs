@@ -0,0 +1,184 @@
+//! Reads the memory of every instance of a store set in a compute pass of our own, without copying the
+//! memory back to the host first.
+//! Run with `cargo run -p wasm-gpu --example buffer_interop`.
+use wasm_gpu::{imports, MappedStoreSetBuilder, Module, MEMORY_BINDING_INDEX};
+use wasm_gpu_test_lib::shared_backend;
+
+const INSTANCE_COUNT: usize = 8;
+
+/// Each instance writes the square of its input to the first word of its memory.
+const SQUARE_WAT: &str = r#"
+(module
+    (memory 1)
+    (func $f (export "square") (param i32)
+        (i32.store (i32.const 0) (i32.mul (local.get 0) (local.get 0)))
+    )
+)
+"#;
+
+/// Sums the first word of the memory of every instance, given the word offset of each.
+const SUM_WGSL: &str = r#"
+@group(0) @binding(0) var<storage, read> memory: array<u32>;
+@group(0) @binding(1) var<storage, read> offsets: array<u32>;
+@group(0) @binding(2) var<storage, read_write> total: u32;
+
+@compute @workgroup_size(1)
+fn main() {
+    var sum = 0u;
+    for (var i = 0u; i < arrayLength(&offsets); i++) {
+        sum += memory[offsets[i]];
+    }
+    total = sum;
+}
+"#;
+
+async fn run() -> u32 {
+    let (memory_system, queue) = shared_backend();
+
+    let module = Module::new(
+        &wasm_gpu::WasmFeatures::default(),
+        SQUARE_WAT.as_bytes(),
+        "interop_module".to_owned(),
+    )
+    .expect("could not parse module");
+    let mut stores_builder =
+        MappedStoreSetBuilder::new(memory_system, "interop_module", Default::default());
+    let instances = stores_builder
+        .instantiate_module(queue, &module, imports! {})
+        .await
+        .expect("could not instantiate module");
+    let square = instances
+        .get_typed_func::<i32, ()>("square")
+        .expect("module exports square");
+    let store_source = stores_builder
+        .complete(queue)
+        .await
+        .expect("could not complete store builder");
+    let mut stores = store_source
+        .build(memory_system, queue, INSTANCE_COUNT)
+        .await
+        .expect("could not build stores");
+
+    square
+        .call_all(memory_system, queue, &mut stores, 0..INSTANCE_COUNT as i32)
+        .await
+        .expect("could not allocate call buffers")
+        .await
+        .expect("could not read results buffers");
+
+    // The memories of the instances are interleaved, so find where each instance's first word is
+    let memory = stores
+        .binding_buffer(MEMORY_BINDING_INDEX)
+        .expect("memory is owned by the store set");
+    let offsets = (0..INSTANCE_COUNT)
+        .flat_map(|instance_index| {
+            let region = stores
+                .binding_instance_region(MEMORY_BINDING_INDEX, instance_index)
+                .expect("memory is owned by the store set");
+            u32::try_from(region.offset / 4).unwrap().to_le_bytes()
+        })
+        .collect::<Vec<_>>();
+
+    let device = queue.device();
+    let offsets_buffer = device
+        .create_buffer(&wgpu::BufferDescriptor {
+            label: Some("offsets"),
+            size: offsets.len() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: true,
+        })
+        .await
+        .expect("could not allocate offsets buffer");
+    offsets_buffer
+        .slice(..)
+        .get_mapped_range_mut()
+        .copy_from_slice(&offsets);
+    offsets_buffer.unmap();
+    let total_buffer = device
+        .create_buffer(&wgpu::BufferDescriptor {
+            label: Some("total"),
+            size: 4,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        })
+        .await
+        .expect("could not allocate total buffer");
+    let read_buffer = device
+        .create_buffer(&wgpu::BufferDescriptor {
+            label: Some("total_read"),
+            size: 4,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+        .await
+        .expect("could not allocate read buffer");
+
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("sum"),
+        source: wgpu::ShaderSource::Wgsl(SUM_WGSL.into()),
+    });
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("sum"),
+        layout: None,
+        module: &shader,
+        entry_point: "main",
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("sum"),
+        layout: &pipeline.get_bind_group_layout(0),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: memory.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: offsets_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: total_buffer.as_entire_binding(),
+            },
+        ],
+    });
+
+    // Submitted after the call on the same queue, so sees the memory written by the call
+    let mut encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(1, 1, 1);
+    }
+    encoder.copy_buffer_to_buffer(&total_buffer, 0, &read_buffer, 0, 4);
+    queue.submit([encoder.finish()]).await;
+
+    read_buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read)
+        .await
+        .expect("could not map read buffer");
+    let total = u32::from_le_bytes(
+        read_buffer.slice(..).get_mapped_range()[..]
+            .try_into()
+            .unwrap(),
+    );
+    read_buffer.unmap();
+
+    return total;
+}
+
+fn main() {
+    let total = pollster::block_on(run());
+
+    let expected: u32 = (0..INSTANCE_COUNT as u32).map(|i| i * i).sum();
+    assert_eq!(total, expected);
+    println!(
+        "sum of squares across {} instances: {}",
+        INSTANCE_COUNT, total
+    );
+}
@@ -0,0 +1,96 @@
+//! Records several calls on the same instances with a `SessionBuilder` and submits them to the GPU together,
+//! rather than waiting for each call to finish before the next is dispatched.
+//! Run with `cargo run -p wasm-gpu --example pipelined_calls`.
+use wasm_gpu::{imports, MappedStoreSetBuilder, Module, SessionBuilder};
+use wasm_gpu_test_lib::shared_backend;
+
+const INSTANCE_COUNT: usize = 16;
+const STEPS: usize = 4;
+
+/// Each instance keeps a counter in its memory, which every step doubles and adds one to.
+const COUNTER_WAT: &str = r#"
+(module
+    (memory 1)
+    (func $init (export "init") (param i32)
+        (i32.store (i32.const 0) (local.get 0))
+    )
+    (func $step (export "step")
+        (i32.store (i32.const 0)
+            (i32.add (i32.mul (i32.load (i32.const 0)) (i32.const 2)) (i32.const 1))
+        )
+    )
+    (func $read (export "read") (result i32)
+        (i32.load (i32.const 0))
+    )
+)
+"#;
+
+async fn run() -> Vec<i32> {
+    let (memory_system, queue) = shared_backend();
+
+    let module = Module::new(
+        &wasm_gpu::WasmFeatures::default(),
+        COUNTER_WAT.as_bytes(),
+        "counter_module".to_owned(),
+    )
+    .expect("could not parse module");
+    let mut stores_builder =
+        MappedStoreSetBuilder::new(memory_system, "counter_module", Default::default());
+    let instances = stores_builder
+        .instantiate_module(queue, &module, imports! {})
+        .await
+        .expect("could not instantiate module");
+    let init = instances
+        .get_typed_func::<i32, ()>("init")
+        .expect("module exports init");
+    let step = instances
+        .get_typed_func::<(), ()>("step")
+        .expect("module exports step");
+    let read = instances
+        .get_typed_func::<(), i32>("read")
+        .expect("module exports read");
+    let store_source = stores_builder
+        .complete(queue)
+        .await
+        .expect("could not complete store builder");
+    let mut stores = store_source
+        .build(memory_system, queue, INSTANCE_COUNT)
+        .await
+        .expect("could not build stores");
+
+    // Every call is submitted at once, and each sees the memory left by the calls before it
+    let mut session =
+        SessionBuilder::new(&mut stores).call_all_typed(&init, 0..INSTANCE_COUNT as i32);
+    for _ in 0..STEPS {
+        session = session.call_all_typed(&step, vec![(); INSTANCE_COUNT]);
+    }
+    let results = session
+        .run(memory_system, queue)
+        .await
+        .expect("could not allocate call buffers")
+        .await
+        .expect("could not read results buffers");
+    // The results of each call are given in the order that the calls were recorded
+    assert_eq!(results.len(), STEPS + 1);
+
+    // The state left by the last call of the session persists for later calls
+    return read
+        .call_all(memory_system, queue, &mut stores, vec![(); INSTANCE_COUNT])
+        .await
+        .expect("could not allocate call buffers")
+        .await
+        .expect("could not read results buffers")
+        .into_iter()
+        .map(|result| result.expect("read does not trap"))
+        .collect();
+}
+
+fn main() {
+    let counters = pollster::block_on(run());
+
+    for (start, counter) in counters.iter().enumerate() {
+        let expected = (0..STEPS).fold(start as i32, |value, _| value * 2 + 1);
+        assert_eq!(*counter, expected);
+    }
+    println!("counters after {} pipelined steps: {:?}", STEPS, counters);
+}
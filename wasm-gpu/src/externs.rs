@@ -5,7 +5,47 @@ use crate::instance::table::builder::AbstractTablePtr;
 use itertools::Itertools;
 use perfect_derive::perfect_derive;
 use wasm_types::WasmTyVec;
-use wasmparser::{FuncType, GlobalType, TableType};
+use wasmparser::{FuncType, GlobalType, MemoryType, TableType};
+
+/// The type of an object imported or exported by a module, as reported by
+/// [`Module::imports`](crate::Module::imports) and [`Module::exports`](crate::Module::exports).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExternType {
+    Func(FuncType),
+    Global(GlobalType),
+    Table(TableType),
+    Memory(MemoryType),
+}
+
+impl ExternType {
+    pub fn func(&self) -> Option<&FuncType> {
+        match self {
+            Self::Func(ty) => Some(ty),
+            _ => None,
+        }
+    }
+
+    pub fn global(&self) -> Option<&GlobalType> {
+        match self {
+            Self::Global(ty) => Some(ty),
+            _ => None,
+        }
+    }
+
+    pub fn table(&self) -> Option<&TableType> {
+        match self {
+            Self::Table(ty) => Some(ty),
+            _ => None,
+        }
+    }
+
+    pub fn memory(&self) -> Option<&MemoryType> {
+        match self {
+            Self::Memory(ty) => Some(ty),
+            _ => None,
+        }
+    }
+}
 
 #[perfect_derive(Clone)]
 pub struct NamedExtern {
@@ -1,4 +1,5 @@
 use wasm_gpu_funcgen::FuncAccessible;
+use wasmparser::ValType;
 
 use crate::instance::data::DataPtr;
 use crate::instance::element::ElementPtr;
@@ -33,21 +34,66 @@ impl FuncAccessiblePtrs {
                 .iter()
                 .map(|ptr| ptr.to_index())
                 .collect(),
+            element_size_lookup: self
+                .element_index_lookup
+                .iter()
+                .map(|ptr| {
+                    u32::try_from(*ptr.len())
+                        .expect("element segments are indexed by 32-bit values")
+                })
+                .collect(),
+            element_segment_lookup: self
+                .element_index_lookup
+                .iter()
+                .map(|ptr| ptr.segment_index())
+                .collect(),
             table_index_lookup: self
                 .table_index_lookup
                 .iter()
                 .map(|ptr| ptr.to_index())
                 .collect(),
+            table_size_lookup: self
+                .table_index_lookup
+                .iter()
+                .map(|ptr| u32::try_from(*ptr.len()).expect("tables are indexed by 32-bit values"))
+                .collect(),
+            table_type_lookup: self
+                .table_index_lookup
+                .iter()
+                .map(|ptr| ValType::Ref(ptr.ty().element_type))
+                .collect(),
             data_index_lookup: self
                 .data_index_lookup
                 .iter()
                 .map(|ptr| ptr.to_index())
                 .collect(),
+            data_size_lookup: self
+                .data_index_lookup
+                .iter()
+                .map(|ptr| {
+                    u32::try_from(*ptr.len()).expect("data segments are indexed by 32-bit values")
+                })
+                .collect(),
+            data_segment_lookup: self
+                .data_index_lookup
+                .iter()
+                .map(|ptr| ptr.segment_index())
+                .collect(),
             memory_index_lookup: self
                 .memory_index_lookup
                 .iter()
                 .map(|ptr| ptr.to_index())
                 .collect(),
+            memory_capacity_lookup: self
+                .memory_index_lookup
+                .iter()
+                .map(|ptr| ptr.capacity_pages())
+                .collect(),
+            memory_pages_lookup: self
+                .memory_index_lookup
+                .iter()
+                .map(|ptr| ptr.initial_pages())
+                .collect(),
         }
     }
 }
@@ -1,17 +1,18 @@
 use crate::externs::{Extern, NamedExtern};
 use crate::instance::func::{TypedFuncPtr, UntypedFuncPtr};
-use crate::instance::global::builder::AbstractGlobalPtr;
+use crate::instance::global::builder::{AbstractGlobalPtr, TypedGlobalPtr};
 use crate::instance::memory::builder::AbstractMemoryPtr;
 use crate::instance::table::builder::AbstractTablePtr;
 use crate::module::parsing::ModuleExport;
 use anyhow::{anyhow, Context};
 use std::collections::HashMap;
-use wasm_types::WasmTyVec;
+use wasm_types::{WasmTyVal, WasmTyVec};
 
 pub mod data;
 pub mod element;
 pub mod func;
 pub mod global;
+pub(crate) mod interleaving;
 pub mod memory;
 pub mod ptrs;
 pub mod table;
@@ -101,7 +102,8 @@ impl ModuleInstanceReferences {
         return Ok(typed);
     }
 
-    pub fn get_memory_export(&self, name: &str) -> anyhow::Result<AbstractMemoryPtr> {
+    /// Find an exported memory, which can be read and written by the host once a store set has been built.
+    pub fn get_memory(&self, name: &str) -> anyhow::Result<AbstractMemoryPtr> {
         self.get_export(name)
             .ok_or(anyhow!("no exported object with name {}", name))
             .and_then(|export| match export {
@@ -112,4 +114,24 @@ impl ModuleInstanceReferences {
                 )),
             })
     }
+
+    /// Find an exported global that doesn't track its type. Prefer get_typed_global if possible.
+    pub fn get_global(&self, name: &str) -> anyhow::Result<AbstractGlobalPtr> {
+        self.get_export(name)
+            .ok_or(anyhow!("no exported object with name {}", name))
+            .and_then(|export| match export {
+                Extern::Global(g) => Ok(g.clone()),
+                _ => Err(anyhow!("exported object named {} is not a global", name)),
+            })
+    }
+
+    /// Find an exported global that tracks its type, which can be read and written by the host
+    /// once a store set has been built.
+    pub fn get_typed_global<V: WasmTyVal>(&self, name: &str) -> anyhow::Result<TypedGlobalPtr<V>> {
+        let untyped = self
+            .get_global(name)
+            .context(format!("failed to find global export `{}`", name))?;
+
+        untyped.try_typed()
+    }
 }
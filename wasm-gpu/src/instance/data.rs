@@ -1,3 +1,5 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
 use wgpu::BufferAsyncError;
 use wgpu_async::async_queue::AsyncQueue;
 use wgpu_lazybuffers::{
@@ -5,6 +7,8 @@ use wgpu_lazybuffers::{
 };
 use wgpu_lazybuffers_macros::lazy_mappable;
 
+use wasm_gpu_funcgen::{DATA_DROPPED_FLAG_WORDS, MAX_DROPPABLE_DATA_SEGMENTS};
+
 use crate::capabilities::CapabilityStore;
 use crate::impl_immutable_ptr;
 
@@ -15,6 +19,10 @@ pub struct UnmappedDataInstance {
     datas: UnmappedLazyBuffer,
     cap_set: CapabilityStore,
     head: usize,
+    /// The number of segments added, used to give each segment a dropped flag
+    segments: usize,
+    /// A bit for each segment that has been dropped during instantiation, laid out as in the flags buffer
+    dropped: Vec<u32>,
 }
 
 impl UnmappedDataInstance {
@@ -35,6 +43,11 @@ impl UnmappedDataInstance {
     pub(crate) fn buffer(&self) -> &UnmappedLazyBuffer {
         &self.datas
     }
+
+    /// The dropped flags that every instance starts with, i.e. with every active segment dropped.
+    pub(crate) fn dropped_flags(&self) -> &[u32] {
+        &self.dropped
+    }
 }
 
 impl MappedDataInstance {
@@ -48,14 +61,22 @@ impl MappedDataInstance {
             }),
             cap_set: CapabilityStore::new(0),
             head: 0,
+            segments: 0,
+            dropped: vec![0; DATA_DROPPED_FLAG_WORDS as usize],
         }
     }
 
+    /// Used when taking a snapshot of a store, where some segments may have since been dropped
+    pub(crate) fn set_dropped_flags(&mut self, dropped: Vec<u32>) {
+        assert_eq!(dropped.len(), self.dropped.len());
+        self.dropped = dropped;
+    }
+
     /// Resizes the GPU buffers backing these elements by the specified amount.
     ///
     /// values_size is given in units of bytes, so an f64 is 8 bytes
     pub fn reserve(&mut self, values_size: usize) {
-        self.datas.resize_lazy(values_size);
+        self.datas.extend_lazy(values_size);
         self.cap_set = self.cap_set.resize_ref(self.datas.len())
     }
 
@@ -73,7 +94,17 @@ impl MappedDataInstance {
         self.datas
             .try_write_slice_locking(queue, start..end, data)
             .await?;
-        return Ok(DataPtr::new(start, self.cap_set.get_cap(), data.len()));
+        self.head = end;
+
+        let segment = self.segments;
+        self.segments += 1;
+
+        return Ok(DataPtr::new(
+            start,
+            self.cap_set.get_cap(),
+            data.len(),
+            segment,
+        ));
     }
 
     pub async fn try_get(
@@ -91,9 +122,63 @@ impl MappedDataInstance {
         return self.datas.try_read_slice_locking(queue, start..end).await;
     }
 
-    /// Calls `elem.drop` on the element pointed to. May or may not actually free the memory
-    pub async fn drop(&mut self, _ptr: &DataPtr) {
-        //TODO: Use this hint
+    /// Calls `data.drop` on the data pointed to, so that later `memory.init` instructions using it trap.
+    /// May or may not actually free the memory
+    pub async fn drop(&mut self, ptr: &DataPtr) {
+        // Segments without a dropped flag can't be referenced by `memory.init`, so dropping them has no visible effect
+        let segment = ptr.segment;
+        if segment < MAX_DROPPABLE_DATA_SEGMENTS as usize {
+            self.dropped[segment / 32] |= 1 << (segment % 32);
+        }
+        //TODO: Use this hint to free memory
+    }
+}
+
+/// The data or element segments dropped by each instance in a store set, which persist between invocations.
+#[derive(Debug)]
+pub struct SegmentDroppedFlags {
+    /// The number of flag words held for each instance
+    words: usize,
+    flags: Vec<AtomicU32>,
+}
+
+impl SegmentDroppedFlags {
+    pub(crate) fn new(initial: &[u32], instance_count: usize) -> Self {
+        Self {
+            words: initial.len(),
+            flags: (0..instance_count)
+                .flat_map(|_| initial.iter().map(|flag| AtomicU32::new(*flag)))
+                .collect(),
+        }
+    }
+
+    /// The number of instances that flags are held for.
+    pub(crate) fn instance_count(&self) -> usize {
+        self.flags.len() / self.words
+    }
+
+    /// Adds flags for `additional` more instances, each starting with the given flags.
+    pub(crate) fn grow(&mut self, initial: &[u32], additional: usize) {
+        self.flags
+            .extend((0..additional).flat_map(|_| initial.iter().map(|flag| AtomicU32::new(*flag))));
+    }
+
+    fn range(&self, instance_index: usize) -> std::ops::Range<usize> {
+        instance_index * self.words..(instance_index + 1) * self.words
+    }
+
+    /// The dropped flag words of the given instance.
+    pub(crate) fn get(&self, instance_index: usize) -> Vec<u32> {
+        self.flags[self.range(instance_index)]
+            .iter()
+            .map(|flag| flag.load(Ordering::Acquire))
+            .collect()
+    }
+
+    pub(crate) fn set(&self, instance_index: usize, flags: &[u32]) {
+        for (flag, value) in self.flags[self.range(instance_index)].iter().zip(flags) {
+            flag.store(*value, Ordering::Release)
+        }
     }
 }
 
@@ -101,6 +186,7 @@ impl_immutable_ptr!(
     pub struct DataPtr {
         data...
         len: usize, // In bytes
+        segment: usize, // The number of segments added before this one
     }
 );
 
@@ -108,4 +194,8 @@ impl DataPtr {
     pub fn to_index(&self) -> wasm_gpu_funcgen::DataIndex {
         wasm_gpu_funcgen::DataIndex::from(self.ptr)
     }
+
+    pub fn segment_index(&self) -> u32 {
+        u32::try_from(self.segment).expect("only 32-bit GPU word sizes are supported")
+    }
 }
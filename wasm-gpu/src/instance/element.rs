@@ -10,6 +10,8 @@ use wgpu_lazybuffers::{
 };
 use wgpu_lazybuffers_macros::lazy_mappable;
 
+use wasm_gpu_funcgen::{ELEMENT_DROPPED_FLAG_WORDS, MAX_DROPPABLE_ELEMENT_SEGMENTS};
+
 #[derive(Debug, Clone)]
 struct Meta {
     head: usize,
@@ -22,12 +24,21 @@ pub struct UnmappedElementInstance {
     #[map(MappedLazyBuffer)]
     references: UnmappedLazyBuffer,
     meta: Meta,
+    /// The number of segments added, used to give each segment a dropped flag
+    segments: usize,
+    /// A bit for each segment that has been dropped during instantiation, laid out as in the flags buffer
+    dropped: Vec<u32>,
 }
 
 impl UnmappedElementInstance {
     pub(crate) fn buffer(&self) -> &UnmappedLazyBuffer {
         &self.references
     }
+
+    /// The dropped flags that every instance starts with, i.e. with every active and declared segment dropped.
+    pub(crate) fn dropped_flags(&self) -> &[u32] {
+        &self.dropped
+    }
 }
 
 impl MappedElementInstance {
@@ -44,9 +55,17 @@ impl MappedElementInstance {
                 cap_set: CapabilityStore::new(0),
                 head: 0,
             },
+            segments: 0,
+            dropped: vec![0; ELEMENT_DROPPED_FLAG_WORDS as usize],
         }
     }
 
+    /// Used when taking a snapshot of a store, where some segments may have since been dropped
+    pub(crate) fn set_dropped_flags(&mut self, dropped: Vec<u32>) {
+        assert_eq!(dropped.len(), self.dropped.len());
+        self.dropped = dropped;
+    }
+
     /// Resizes the GPU buffers backing these elements by the specified amount.
     ///
     /// values_count is given in units of bytes, so an f64 is 8 bytes
@@ -83,11 +102,15 @@ impl MappedElementInstance {
 
         self.meta.head = end;
 
+        let segment = self.segments;
+        self.segments += 1;
+
         return Ok(ElementPtr::new(
             start,
             self.meta.cap_set.get_cap(),
             ty,
             element.len(),
+            segment,
         ));
     }
 
@@ -109,8 +132,14 @@ impl MappedElementInstance {
             .await;
     }
 
-    /// Calls `elem.drop` on the element pointed to. May or may not actually free the memory
-    pub async fn drop(&mut self, _ptr: &ElementPtr) {
+    /// Calls `elem.drop` on the element pointed to, so that later `table.init` instructions using it trap.
+    /// May or may not actually free the memory
+    pub async fn drop(&mut self, ptr: &ElementPtr) {
+        // Segments without a dropped flag can't be referenced by `table.init`, so dropping them has no visible effect
+        let segment = ptr.segment;
+        if segment < MAX_DROPPABLE_ELEMENT_SEGMENTS as usize {
+            self.dropped[segment / 32] |= 1 << (segment % 32);
+        }
         //TODO - use this optimisation hint
     }
 }
@@ -120,6 +149,7 @@ impl_immutable_ptr!(
         data...
         ty: RefType,
         len: usize,
+        segment: usize, // The number of segments added before this one
     }
 );
 
@@ -127,4 +157,8 @@ impl ElementPtr {
     pub fn to_index(&self) -> wasm_gpu_funcgen::ElementIndex {
         wasm_gpu_funcgen::ElementIndex::from(self.ptr)
     }
+
+    pub fn segment_index(&self) -> u32 {
+        u32::try_from(self.segment).expect("only 32-bit GPU word sizes are supported")
+    }
 }
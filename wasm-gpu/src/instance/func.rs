@@ -1,14 +1,16 @@
 use std::sync::Arc;
 
 use crate::capabilities::CapabilityStore;
-use crate::session::{OutputType, Session};
+use crate::session::{OutputType, Session, SessionArgs, StreamedOutputType};
 use crate::{impl_immutable_ptr, DeviceStoreSet};
 use futures::future::BoxFuture;
-use futures::FutureExt;
+use futures::stream::BoxStream;
+use futures::{FutureExt, StreamExt};
 use itertools::Itertools;
 use once_cell::sync::Lazy;
 use wasm_gpu_funcgen::{FuncAccessible, FuncData, FuncUnit};
 use wasm_types::{FuncRef, Val, WasmTyVec};
+use wasmparser::ValType;
 use wgpu::BufferAsyncError;
 use wgpu_async::{AsyncQueue, OutOfMemoryError};
 use wgpu_lazybuffers::MemorySystem;
@@ -86,6 +88,40 @@ impl FuncsInstance {
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum DynamicCallError {
+    #[error("invocation {invocation} was given arguments of types {given:?}, but the function takes {expected:?}")]
+    ArgumentTypeMismatch {
+        invocation: usize,
+        expected: Vec<ValType>,
+        given: Vec<ValType>,
+    },
+    #[error("could not allocate call buffers as gpu was out of space")]
+    OoM(#[from] OutOfMemoryError),
+}
+
+/// Describes the first difference between the types that a caller expects and the types of a function, if any.
+fn describe_mismatch(expected: &[ValType], actual: &[ValType]) -> Option<String> {
+    if expected.len() != actual.len() {
+        return Some(format!(
+            "expected {} values but the function has {}",
+            expected.len(),
+            actual.len()
+        ));
+    }
+
+    expected
+        .iter()
+        .zip(actual)
+        .position(|(expected, actual)| expected != actual)
+        .map(|i| {
+            format!(
+                "value {} was expected to be {:?} but the function has {:?}",
+                i, expected[i], actual[i]
+            )
+        })
+}
+
 impl_immutable_ptr!(
 pub struct UntypedFuncPtr {
     data...
@@ -102,16 +138,18 @@ impl UntypedFuncPtr {
     pub fn try_typed<Params: WasmTyVec, Results: WasmTyVec>(
         self,
     ) -> anyhow::Result<TypedFuncPtr<Params, Results>> {
-        if !Params::VAL_TYPES.eq(self.ty.params()) {
+        if let Some(mismatch) = describe_mismatch(Params::VAL_TYPES, self.ty.params()) {
             return Err(anyhow::anyhow!(
-                "function pointer parameters were not the correct type, expected {:?} but got {:?}",
+                "function pointer parameters were not the correct type, {}: expected {:?} but got {:?}",
+                mismatch,
                 Params::VAL_TYPES,
                 self.ty.params()
             ));
         }
-        if !Results::VAL_TYPES.eq(self.ty.results()) {
+        if let Some(mismatch) = describe_mismatch(Results::VAL_TYPES, self.ty.results()) {
             return Err(anyhow::anyhow!(
-                "function pointer results were not the correct type, expected {:?} but got {:?}",
+                "function pointer results were not the correct type, {}: expected {:?} but got {:?}",
+                mismatch,
                 Results::VAL_TYPES,
                 self.ty.results()
             ));
@@ -134,11 +172,78 @@ impl UntypedFuncPtr {
         stores: &'a mut DeviceStoreSet,
         args: impl IntoIterator<Item = Vec<Val>>,
     ) -> Result<BoxFuture<'a, OutputType>, OutOfMemoryError> {
-        let args = args.into_iter().collect();
+        let args = SessionArgs::PerInvocation(args.into_iter().collect());
 
         let session = Session::new(stores, self.clone(), args);
         return session.run(memory_system, queue).await;
     }
+
+    /// Like `call_all`, but checks the arguments of every invocation against the signature of this
+    /// function first, for hosts that only know the signatures of the functions they call at runtime.
+    ///
+    /// # Panics
+    /// This function panics if this function is not in the given store set
+    pub async fn call_all_dynamic<'a>(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        stores: &'a mut DeviceStoreSet,
+        args: Vec<Vec<Val>>,
+    ) -> Result<BoxFuture<'a, OutputType>, DynamicCallError> {
+        for (invocation, invocation_args) in args.iter().enumerate() {
+            let given = invocation_args.iter().map(Val::get_type).collect_vec();
+            if !given.eq(self.ty.params()) {
+                return Err(DynamicCallError::ArgumentTypeMismatch {
+                    invocation,
+                    expected: self.ty.params().to_vec(),
+                    given,
+                });
+            }
+        }
+
+        Ok(self.call_all(memory_system, queue, stores, args).await?)
+    }
+
+    /// Calls this function on the first `count` instances, giving every invocation the same arguments.
+    ///
+    /// # Panics
+    /// This function panics if:
+    ///  - this function is not in the given store set
+    ///  - the arguments given don't match the arguments that the function takes
+    pub async fn call_all_broadcast<'a>(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        stores: &'a mut DeviceStoreSet,
+        args: Vec<Val>,
+        count: usize,
+    ) -> Result<BoxFuture<'a, OutputType>, OutOfMemoryError> {
+        let args = SessionArgs::Broadcast { args, count };
+
+        let session = Session::new(stores, self.clone(), args);
+        return session.run(memory_system, queue).await;
+    }
+
+    /// Like `call_all`, but yields the index of each instance along with its result as soon as it has
+    /// been downloaded, rather than waiting for every result. Useful to reduce peak host memory usage
+    /// when calling a function on a very large number of instances.
+    ///
+    /// # Panics
+    /// This function panics if:
+    ///  - this function is not in the given store set
+    ///  - the arguments given don't match the arguments that the function takes
+    pub async fn call_all_streaming<'a>(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        stores: &'a mut DeviceStoreSet,
+        args: impl IntoIterator<Item = Vec<Val>>,
+    ) -> Result<BoxStream<'a, StreamedOutputType>, OutOfMemoryError> {
+        let args = SessionArgs::PerInvocation(args.into_iter().collect());
+
+        let session = Session::new(stores, self.clone(), args);
+        return session.run_streaming(memory_system, queue).await;
+    }
 }
 
 // Typed function pointers should have their types checked before construction
@@ -167,8 +272,83 @@ impl<Params: WasmTyVec, Results: WasmTyVec> TypedFuncPtr<Params, Results> {
         BoxFuture<'a, Result<Vec<Result<Results, wasmtime_environ::Trap>>, BufferAsyncError>>,
         OutOfMemoryError,
     > {
-        let args = args.into_iter().map(|v| v.to_val_vec()).collect();
+        let args = SessionArgs::PerInvocation(args.into_iter().map(|v| v.to_val_vec()).collect());
 
+        self.run_session(memory_system, queue, stores, args).await
+    }
+
+    /// Calls this function on the first `count` instances, giving every invocation the same arguments.
+    /// This avoids cloning the arguments for each invocation when only the invocation index differs
+    /// between them.
+    ///
+    /// # Panics
+    /// This function panics if:
+    ///  - this function is not in the given store set
+    pub async fn call_all_broadcast<'a>(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        stores: &'a mut DeviceStoreSet,
+        args: Params,
+        count: usize,
+    ) -> Result<
+        BoxFuture<'a, Result<Vec<Result<Results, wasmtime_environ::Trap>>, BufferAsyncError>>,
+        OutOfMemoryError,
+    > {
+        let args = SessionArgs::Broadcast {
+            args: args.to_val_vec(),
+            count,
+        };
+
+        self.run_session(memory_system, queue, stores, args).await
+    }
+
+    /// Like `call_all`, but yields the index of each instance along with its result as soon as it has
+    /// been downloaded, rather than waiting for every result.
+    ///
+    /// # Panics
+    /// This function panics if:
+    ///  - this function is not in the given store set
+    pub async fn call_all_streaming<'a>(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        stores: &'a mut DeviceStoreSet,
+        args: impl IntoIterator<Item = Params>,
+    ) -> Result<
+        BoxStream<'a, Result<(usize, Result<Results, wasmtime_environ::Trap>), BufferAsyncError>>,
+        OutOfMemoryError,
+    > {
+        let args = SessionArgs::PerInvocation(args.into_iter().map(|v| v.to_val_vec()).collect());
+
+        let entry_func = self.as_untyped();
+        let session = Session::new(stores, entry_func, args);
+
+        let gpu_stream = session.run_streaming(memory_system, queue).await?;
+        let typed_gpu_stream = gpu_stream
+            .map(|res| {
+                res.map(|(instance_index, ret)| {
+                    let ret = ret.map(|v| {
+                        Results::try_from_val_vec(&v).expect("type safety should ensure that casting function results to this typed pointer type always succeeds")
+                    });
+                    (instance_index, ret)
+                })
+            })
+            .boxed();
+
+        return Ok(typed_gpu_stream);
+    }
+
+    async fn run_session<'a>(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        stores: &'a mut DeviceStoreSet,
+        args: SessionArgs,
+    ) -> Result<
+        BoxFuture<'a, Result<Vec<Result<Results, wasmtime_environ::Trap>>, BufferAsyncError>>,
+        OutOfMemoryError,
+    > {
         let entry_func = self.as_untyped();
         let session = Session::new(stores, entry_func.clone(), args);
 
@@ -190,3 +370,43 @@ impl<Params: WasmTyVec, Results: WasmTyVec> TypedFuncPtr<Params, Results> {
         return Ok(typed_gpu_future);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::UntypedFuncPtr;
+    use crate::capabilities::CapabilityStore;
+    use wasmparser::{FuncType, ValType};
+
+    fn func_ptr(params: &[ValType], results: &[ValType]) -> UntypedFuncPtr {
+        let ty = FuncType::new(params.iter().copied(), results.iter().copied());
+        UntypedFuncPtr::new(0, CapabilityStore::new(1).get_cap(), ty)
+    }
+
+    #[test]
+    fn test_try_typed_accepts_matching_signature() {
+        let ptr = func_ptr(&[ValType::I32, ValType::F32], &[ValType::F32]);
+        assert!(ptr.try_typed::<(i32, f32), f32>().is_ok());
+    }
+
+    #[test]
+    fn test_try_typed_rejects_swapped_types() {
+        let ptr = func_ptr(&[ValType::I32, ValType::F32], &[ValType::F32]);
+        let err = ptr.try_typed::<(f32, i32), f32>().err().unwrap();
+        assert!(err.to_string().contains("value 0 was expected to be F32"));
+
+        let ptr = func_ptr(&[ValType::I32, ValType::F32], &[ValType::F32]);
+        assert!(ptr.try_typed::<(i32, f32), i32>().is_err());
+    }
+
+    #[test]
+    fn test_try_typed_rejects_wrong_arity() {
+        let ptr = func_ptr(&[ValType::I32, ValType::F32], &[ValType::F32]);
+        let err = ptr.try_typed::<(i32, f32, i32), f32>().err().unwrap();
+        assert!(err
+            .to_string()
+            .contains("expected 3 values but the function has 2"));
+
+        let ptr = func_ptr(&[ValType::I32, ValType::F32], &[ValType::F32]);
+        assert!(ptr.try_typed::<(i32, f32), ()>().is_err());
+    }
+}
@@ -4,6 +4,9 @@ use crate::instance::global::immutable::GlobalImmutablePtr;
 use crate::instance::global::instance::GlobalMutablePtr;
 use crate::instance::global::instance::UnmappedMutableGlobalsInstanceSet;
 use crate::instance::global::{impl_global_get, impl_global_push};
+use crate::instance::memory::instance::MemoryAccessError;
+use crate::DeviceStoreSet;
+use std::marker::PhantomData;
 use std::mem::size_of;
 use wasm_gpu_funcgen::GlobalIndex;
 use wasm_types::{ExternRef, FuncRef, Val, WasmTyVal, V128};
@@ -44,6 +47,42 @@ impl UnmappedMutableGlobalsInstanceBuilder {
         )
         .await;
     }
+
+    /// Creates a new builder holding the current state of the given instance in a set.
+    pub(crate) async fn try_from_instance(
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        existing: &UnmappedMutableGlobalsInstanceSet,
+        interleaved_index: usize,
+    ) -> Result<Self, OutOfMemoryError> {
+        let (mutable_values, head, cap_set) = existing
+            .take(memory_system, queue, interleaved_index)
+            .await?;
+        Ok(Self {
+            mutable_values,
+            head,
+            cap_set,
+        })
+    }
+
+    /// Adds `additional` instances to a set built from this builder, initialised with the state in this builder.
+    pub(crate) async fn try_grow(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        set: &mut UnmappedMutableGlobalsInstanceSet,
+        existing_count: usize,
+        additional: usize,
+    ) -> Result<(), OutOfMemoryError> {
+        set.try_grow(
+            memory_system,
+            queue,
+            &self.mutable_values,
+            existing_count,
+            additional,
+        )
+        .await
+    }
 }
 
 impl MappedMutableGlobalsInstanceBuilder {
@@ -208,6 +247,25 @@ impl AbstractGlobalPtr {
             AbstractGlobalPtr::Mutable(ptr) => GlobalIndex::Mutable(ptr.to_index()),
         }
     }
+
+    pub fn try_typed<V: WasmTyVal>(self) -> anyhow::Result<TypedGlobalPtr<V>> {
+        if !V::VAL_TYPE.eq(self.content_type()) {
+            return Err(anyhow::anyhow!(
+                "global pointer was not the correct type, expected {:?} but got {:?}",
+                V::VAL_TYPE,
+                self.content_type()
+            ));
+        }
+
+        Ok(TypedGlobalPtr {
+            ptr: self,
+            _phantom_data: PhantomData,
+        })
+    }
+
+    pub fn typed<V: WasmTyVal>(self) -> TypedGlobalPtr<V> {
+        self.try_typed().unwrap()
+    }
 }
 
 impl Clone for AbstractGlobalPtr {
@@ -218,3 +276,51 @@ impl Clone for AbstractGlobalPtr {
         }
     }
 }
+
+/// A global that has been checked to hold values of type `V`, which can be read and written by the host.
+#[perfect_derive::perfect_derive(Clone, Debug)]
+pub struct TypedGlobalPtr<V: WasmTyVal> {
+    ptr: AbstractGlobalPtr,
+    _phantom_data: PhantomData<fn(V) -> V>,
+}
+
+impl<V: WasmTyVal> TypedGlobalPtr<V> {
+    pub fn as_untyped(&self) -> AbstractGlobalPtr {
+        self.ptr.clone()
+    }
+
+    /// Reads the value of this global in one of the instances in a store set.
+    pub async fn read(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        stores: &DeviceStoreSet,
+        instance_index: usize,
+    ) -> Result<V, MemoryAccessError> {
+        stores
+            .read_global(memory_system, queue, instance_index, &self.ptr)
+            .await
+    }
+
+    /// Overwrites the value of this global in one of the instances in a store set.
+    ///
+    /// # Panics
+    /// Panics if this global is immutable.
+    pub async fn write(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        stores: &mut DeviceStoreSet,
+        instance_index: usize,
+        value: V,
+    ) -> Result<(), MemoryAccessError> {
+        match &self.ptr {
+            AbstractGlobalPtr::Immutable(_) => panic!("cannot write to an immutable global"),
+            AbstractGlobalPtr::Mutable(ptr) => {
+                stores
+                    .write_global(memory_system, queue, instance_index, ptr, value)
+                    .await
+            }
+        }
+    }
+}
@@ -1,14 +1,19 @@
 use crate::capabilities::CapabilityStore;
 use crate::impl_concrete_ptr;
 use crate::instance::global::builder::AbstractGlobalMutablePtr;
+use crate::instance::interleaving::{
+    try_grow_interleaved, try_read_interleaved, try_write_interleaved,
+};
+use crate::instance::memory::instance::MemoryAccessError;
+use std::ops::Range;
 use wgpu_async::{async_device::OutOfMemoryError, async_queue::AsyncQueue};
-use wgpu_lazybuffers::{MemorySystem, UnmappedLazyBuffer};
+use wgpu_lazybuffers::{LazilyMappable, MemorySystem, UnmappedLazyBuffer};
 use wgpu_lazybuffers_interleaving::{
     Interleaveable, InterleavedBufferConfig, MappedInterleavedBuffer, UnmappedInterleavedBuffer,
 };
 use wgpu_lazybuffers_macros::lazy_mappable;
 
-const STRIDE: u64 = 4; // 1 * u32
+pub(crate) const STRIDE: u64 = 4; // 1 * u32
 
 #[lazy_mappable(MappedMutableGlobalsInstanceSet)]
 pub struct UnmappedMutableGlobalsInstanceSet {
@@ -53,6 +58,84 @@ impl UnmappedMutableGlobalsInstanceSet {
         &self.mutables
     }
 
+    /// Adds `additional` instances to this set, initialised from `mutables_source`, while preserving the
+    /// state of the `existing_count` instances already in the set.
+    pub(crate) async fn try_grow(
+        &mut self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        mutables_source: &UnmappedLazyBuffer,
+        existing_count: usize,
+        additional: usize,
+    ) -> Result<(), OutOfMemoryError> {
+        self.mutables = try_grow_interleaved(
+            memory_system,
+            queue,
+            mutables_source,
+            &self.mutables,
+            existing_count,
+            additional,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Reads the data of each of the first `count` instances in this set.
+    pub(crate) async fn try_read_instances(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        count: usize,
+    ) -> Result<Vec<Vec<u8>>, MemoryAccessError> {
+        try_read_interleaved(memory_system, queue, &self.mutables, count).await
+    }
+
+    /// Overwrites the data of every instance in this set, one entry per instance.
+    pub(crate) async fn try_write_instances(
+        &self,
+        queue: &AsyncQueue,
+        instances: &[Vec<u8>],
+    ) -> Result<(), OutOfMemoryError> {
+        try_write_interleaved(queue, &self.mutables, instances).await
+    }
+
+    /// Reads a range of bytes from the mutable globals of the given instance.
+    pub(crate) async fn try_read_instance_slice(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        interleaved_index: usize,
+        range: Range<usize>,
+    ) -> Result<Vec<u8>, MemoryAccessError> {
+        let (buffer, _, _) = self.take(memory_system, queue, interleaved_index).await?;
+
+        let data = buffer
+            .map_lazy()
+            .try_read_slice_locking(queue, range)
+            .await?;
+
+        Ok(data)
+    }
+
+    /// Writes bytes to the mutable globals of the given instance, out of the first `count` instances in this set.
+    pub(crate) async fn try_write_instance_slice(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        count: usize,
+        interleaved_index: usize,
+        start: usize,
+        data: &[u8],
+    ) -> Result<(), MemoryAccessError> {
+        // Globals are small, so rewriting every instance is simpler than copying individual strides
+        let mut instances = self.try_read_instances(memory_system, queue, count).await?;
+        instances[interleaved_index][start..start + data.len()].copy_from_slice(data);
+        self.try_write_instances(queue, &instances).await?;
+
+        Ok(())
+    }
+
     /// Duplicates the data from a given instance into a new buffer
     pub(super) async fn take(
         &self,
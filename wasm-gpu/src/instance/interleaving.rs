@@ -0,0 +1,122 @@
+use crate::instance::memory::instance::MemoryAccessError;
+use wgpu_async::{async_device::OutOfMemoryError, async_queue::AsyncQueue};
+use wgpu_lazybuffers::{LazilyMappable, MemorySystem, UnmappedLazyBuffer};
+use wgpu_lazybuffers_interleaving::{
+    Interleaveable, InterleavedBufferConfig, UnmappedInterleavedBuffer,
+};
+
+/// Creates a new interleaved buffer with `additional` more repetitions of `source` than `existing`,
+/// where the first `existing_count` repetitions are copied from `existing` and the rest are
+/// initialised from `source`.
+pub(crate) async fn try_grow_interleaved<const STRIDE: u64>(
+    memory_system: &MemorySystem,
+    queue: &AsyncQueue,
+    source: &UnmappedLazyBuffer,
+    existing: &UnmappedInterleavedBuffer<STRIDE>,
+    existing_count: usize,
+    additional: usize,
+) -> Result<UnmappedInterleavedBuffer<STRIDE>, OutOfMemoryError> {
+    let cfg = InterleavedBufferConfig {
+        label: &format!("{}_instance_set", source.label()),
+        repetitions: existing_count + additional,
+        usages: wgpu::BufferUsages::STORAGE,
+        locking_size: None,
+        transfer_size: None,
+    };
+    let grown = source
+        .try_duplicate_interleave(memory_system, queue, &cfg)
+        .await?;
+
+    // Each stride of the source is repeated once per instance, so the strides of the existing instances
+    // are contiguous and can be copied in one go into the start of each stride of the grown buffer.
+    let strides = (source.len() as u64).div_ceil(STRIDE);
+    let existing_stride_bytes = STRIDE * existing_count as u64;
+    let grown_stride_bytes = STRIDE * (existing_count + additional) as u64;
+
+    let mut encoder = queue
+        .device()
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    for stride in 0..strides {
+        encoder.copy_buffer_to_buffer(
+            existing,
+            stride * existing_stride_bytes,
+            &grown,
+            stride * grown_stride_bytes,
+            existing_stride_bytes,
+        );
+    }
+    queue.submit([encoder.finish()]).await;
+
+    Ok(grown)
+}
+
+/// Reads the data of each of the first `count` repetitions in an interleaved buffer.
+pub(crate) async fn try_read_interleaved<const STRIDE: u64>(
+    memory_system: &MemorySystem,
+    queue: &AsyncQueue,
+    buffer: &UnmappedInterleavedBuffer<STRIDE>,
+    count: usize,
+) -> Result<Vec<Vec<u8>>, MemoryAccessError> {
+    let mut instances = Vec::new();
+    for interleaved_index in 0..count {
+        let instance = buffer
+            .try_uninterleave(memory_system, queue, interleaved_index)
+            .await?;
+        let data = instance
+            .map_lazy()
+            .try_read_slice_locking(queue, ..)
+            .await?;
+        instances.push(data);
+    }
+
+    Ok(instances)
+}
+
+/// Overwrites every repetition in an interleaved buffer with the given data, one entry per repetition.
+pub(crate) async fn try_write_interleaved<const STRIDE: u64>(
+    queue: &AsyncQueue,
+    buffer: &UnmappedInterleavedBuffer<STRIDE>,
+    instances: &[Vec<u8>],
+) -> Result<(), OutOfMemoryError> {
+    let stride = STRIDE as usize;
+    let instance_len = instances.first().map(Vec::len).unwrap_or(0);
+    if instance_len == 0 {
+        return Ok(());
+    }
+
+    let strides = instance_len.div_ceil(stride);
+    let mut data = vec![0u8; strides * stride * instances.len()];
+    for (interleaved_index, instance) in instances.iter().enumerate() {
+        assert_eq!(
+            instance.len(),
+            instance_len,
+            "every instance in an interleaved buffer has the same length"
+        );
+        for (i_stride, chunk) in instance.chunks(stride).enumerate() {
+            let start = (i_stride * instances.len() + interleaved_index) * stride;
+            data[start..start + chunk.len()].copy_from_slice(chunk);
+        }
+    }
+
+    let device = queue.device();
+    let staging = device
+        .create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{}_restore_staging_buffer", buffer.label())),
+            size: data.len() as u64,
+            usage: wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: true,
+        })
+        .await?;
+    staging
+        .slice(..)
+        .get_mapped_range_mut()
+        .copy_from_slice(&data);
+    staging.unmap();
+
+    let mut encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encoder.copy_buffer_to_buffer(&staging, 0, buffer, 0, data.len() as u64);
+    queue.submit([encoder.finish()]).await;
+
+    Ok(())
+}
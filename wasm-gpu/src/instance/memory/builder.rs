@@ -1,6 +1,8 @@
 use crate::capabilities::CapabilityStore;
 use crate::impl_abstract_ptr;
-use crate::instance::memory::instance::{MemoryPtr, UnmappedMemoryInstanceSet};
+use crate::instance::memory::instance::{MemoryAccessError, MemoryPtr, UnmappedMemoryInstanceSet};
+use crate::DeviceStoreSet;
+use std::ops::Range;
 use wasmparser::MemoryType;
 use wasmtime_environ::WASM_PAGE_SIZE;
 use wgpu::BufferAsyncError;
@@ -13,6 +15,9 @@ use wgpu_lazybuffers_macros::lazy_mappable;
 
 use super::wasm_limits_match;
 
+/// The most pages that a 32-bit memory can hold.
+const MAX_PAGES: u64 = 1 << 16;
+
 #[derive(Debug, Clone)]
 struct Meta {}
 
@@ -22,6 +27,10 @@ pub struct UnmappedMemoryInstanceSetBuilder {
     #[map(MappedLazyBuffer)]
     memory: UnmappedLazyBuffer,
     cap_set: CapabilityStore,
+    /// The number of pages allocated beyond the initial size of each memory, which `memory.grow` may use
+    reserved_pages: u32,
+    /// The current size of the first memory, which is the only memory addressable on the GPU
+    pages: u32,
 }
 
 impl UnmappedMemoryInstanceSetBuilder {
@@ -37,9 +46,40 @@ impl UnmappedMemoryInstanceSetBuilder {
             &self.memory,
             count,
             self.cap_set.clone(),
+            self.pages,
         )
         .await
     }
+
+    /// Creates a new builder holding the current state of the given instance in a set.
+    pub(crate) async fn try_from_instance(
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        existing: &UnmappedMemoryInstanceSet,
+        interleaved_index: usize,
+    ) -> Result<Self, OutOfMemoryError> {
+        let (memory, cap_set) = existing
+            .take(memory_system, queue, interleaved_index)
+            .await?;
+        Ok(Self {
+            memory,
+            cap_set,
+            reserved_pages: 0,
+            pages: existing.pages(interleaved_index),
+        })
+    }
+
+    /// Adds `additional` instances to a set built from this builder, initialised with the state in this builder.
+    pub(crate) async fn try_grow(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        set: &mut UnmappedMemoryInstanceSet,
+        additional: usize,
+    ) -> Result<(), OutOfMemoryError> {
+        set.try_grow(memory_system, queue, &self.memory, additional, self.pages)
+            .await
+    }
 }
 
 impl MappedMemoryInstanceSetBuilder {
@@ -52,6 +92,8 @@ impl MappedMemoryInstanceSetBuilder {
                 transfer_size: 4096,
             }),
             cap_set: CapabilityStore::new(0),
+            reserved_pages: 0,
+            pages: 0,
         }
     }
 
@@ -68,13 +110,28 @@ impl MappedMemoryInstanceSetBuilder {
         Ok(Self {
             memory: memory.map_lazy(),
             cap_set,
+            reserved_pages: 0,
+            pages: existing.pages(interleaved_index),
         })
     }
 
+    /// Allocates space for each memory added after this call to grow by the given number of pages, up to the memory's maximum.
+    pub fn reserve_pages(&mut self, pages: u32) {
+        self.reserved_pages = pages;
+    }
+
     pub fn add_memory(&mut self, plan: &MemoryType) -> AbstractMemoryPtr {
         let ptr = self.memory.len();
-        let len = usize::try_from(plan.initial * WASM_PAGE_SIZE as u64)
+        let capacity_pages = u64::min(
+            plan.initial + u64::from(self.reserved_pages),
+            plan.maximum.unwrap_or(MAX_PAGES),
+        );
+        let len = usize::try_from(capacity_pages * WASM_PAGE_SIZE as u64)
             .expect("memory must be expressable in RAM, but was too big");
+        if ptr == 0 {
+            self.pages =
+                u32::try_from(plan.initial).expect("32-bit memories have at most 65536 pages");
+        }
         self.memory.extend_lazy(len);
         self.cap_set = self.cap_set.resize_ref(self.memory.len());
         return AbstractMemoryPtr::new(ptr, self.cap_set.get_cap(), plan.clone(), len);
@@ -124,4 +181,53 @@ impl AbstractMemoryPtr {
     pub fn to_index(&self) -> wasm_gpu_funcgen::MemoryIndex {
         wasm_gpu_funcgen::MemoryIndex::from(self.ptr)
     }
+
+    /// The number of pages this memory has when it is instantiated.
+    pub fn initial_pages(&self) -> u32 {
+        u32::try_from(self.ty.initial).expect("32-bit memories have at most 65536 pages")
+    }
+
+    /// The number of pages allocated for this memory, which it may grow to.
+    pub fn capacity_pages(&self) -> u32 {
+        u32::try_from(self.len / WASM_PAGE_SIZE as usize)
+            .expect("32-bit memories have at most 65536 pages")
+    }
+
+    /// Reads a range of bytes from this memory in one of the instances in a store set.
+    /// See [`DeviceStoreSet::read_memory`].
+    pub async fn read(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        stores: &DeviceStoreSet,
+        instance_index: usize,
+        range: Range<u32>,
+    ) -> Result<Vec<u8>, MemoryAccessError> {
+        stores
+            .read_memory(memory_system, queue, instance_index, self.to_index(), range)
+            .await
+    }
+
+    /// Writes bytes to this memory in one of the instances in a store set, starting at the given offset.
+    /// See [`DeviceStoreSet::write_memory`].
+    pub async fn write(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        stores: &mut DeviceStoreSet,
+        instance_index: usize,
+        offset: u32,
+        data: &[u8],
+    ) -> Result<(), MemoryAccessError> {
+        stores
+            .write_memory(
+                memory_system,
+                queue,
+                instance_index,
+                self.to_index(),
+                offset,
+                data,
+            )
+            .await
+    }
 }
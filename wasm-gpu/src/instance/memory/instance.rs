@@ -1,12 +1,16 @@
 use std::ops::{Bound, Range, RangeBounds};
+use std::sync::atomic::{AtomicU32, Ordering};
 
 use crate::capabilities::CapabilityStore;
 use crate::impl_concrete_ptr;
+use crate::instance::interleaving::{
+    try_grow_interleaved, try_read_interleaved, try_write_interleaved,
+};
 use crate::instance::memory::builder::AbstractMemoryPtr;
 use wasm_gpu_funcgen::MEMORY_STRIDE_WORDS;
 use wgpu::BufferAsyncError;
 use wgpu_async::{async_device::OutOfMemoryError, async_queue::AsyncQueue};
-use wgpu_lazybuffers::{LockCollection, MemorySystem, UnmappedLazyBuffer};
+use wgpu_lazybuffers::{LazilyMappable, LockCollection, MemorySystem, UnmappedLazyBuffer};
 use wgpu_lazybuffers_interleaving::{
     Interleaveable, InterleavedBufferConfig, MappedInterleavedBuffer, UnmappedInterleavedBuffer,
 };
@@ -14,12 +18,22 @@ use wgpu_lazybuffers_macros::lazy_mappable;
 
 pub const MEMORY_STRIDE_BYTES: u64 = (MEMORY_STRIDE_WORDS * 4) as u64;
 
+#[derive(Debug, thiserror::Error)]
+pub enum MemoryAccessError {
+    #[error("could not copy memory as gpu was out of space")]
+    OoM(#[from] OutOfMemoryError),
+    #[error("could not map memory buffer")]
+    BufferAsync(#[from] BufferAsyncError),
+}
+
 #[lazy_mappable(MappedMemoryInstanceSet)]
 pub struct UnmappedMemoryInstanceSet {
     #[map(MappedInterleavedBuffer<MEMORY_STRIDE_BYTES>)]
     memory: UnmappedInterleavedBuffer<MEMORY_STRIDE_BYTES>,
     cap_set: CapabilityStore,
     instance_count: usize,
+    /// The current size of the first memory of each instance, updated after each invocation
+    pages: Vec<AtomicU32>,
 }
 
 impl UnmappedMemoryInstanceSet {
@@ -29,6 +43,7 @@ impl UnmappedMemoryInstanceSet {
         source: &UnmappedLazyBuffer,
         instance_count: usize,
         cap_set: CapabilityStore,
+        pages: u32,
     ) -> Result<Self, OutOfMemoryError> {
         let cfg = InterleavedBufferConfig {
             label: &format!("{}_instance_set", source.label()),
@@ -45,6 +60,7 @@ impl UnmappedMemoryInstanceSet {
             memory,
             cap_set,
             instance_count,
+            pages: (0..instance_count).map(|_| AtomicU32::new(pages)).collect(),
         })
     }
 
@@ -52,6 +68,59 @@ impl UnmappedMemoryInstanceSet {
         &self.memory
     }
 
+    /// Adds `additional` instances to this set, initialised from `source` and with the given initial
+    /// number of pages, while preserving the state of the existing instances.
+    pub(crate) async fn try_grow(
+        &mut self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        source: &UnmappedLazyBuffer,
+        additional: usize,
+        pages: u32,
+    ) -> Result<(), OutOfMemoryError> {
+        self.memory = try_grow_interleaved(
+            memory_system,
+            queue,
+            source,
+            &self.memory,
+            self.instance_count,
+            additional,
+        )
+        .await?;
+        self.instance_count += additional;
+        self.pages
+            .extend((0..additional).map(|_| AtomicU32::new(pages)));
+
+        Ok(())
+    }
+
+    /// The number of pages in the first memory of the given instance.
+    pub(crate) fn pages(&self, interleaved_index: usize) -> u32 {
+        self.pages[interleaved_index].load(Ordering::Acquire)
+    }
+
+    pub(crate) fn set_pages(&self, interleaved_index: usize, pages: u32) {
+        self.pages[interleaved_index].store(pages, Ordering::Release)
+    }
+
+    /// Reads the data of each of the instances in this set.
+    pub(crate) async fn try_read_instances(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+    ) -> Result<Vec<Vec<u8>>, MemoryAccessError> {
+        try_read_interleaved(memory_system, queue, &self.memory, self.instance_count).await
+    }
+
+    /// Overwrites the data of every instance in this set, one entry per instance.
+    pub(crate) async fn try_write_instances(
+        &self,
+        queue: &AsyncQueue,
+        instances: &[Vec<u8>],
+    ) -> Result<(), OutOfMemoryError> {
+        try_write_interleaved(queue, &self.memory, instances).await
+    }
+
     /// Duplicates the data from a given instance into a new buffer
     pub(super) async fn take(
         &self,
@@ -66,6 +135,94 @@ impl UnmappedMemoryInstanceSet {
 
         Ok((buffer, self.cap_set.clone()))
     }
+
+    /// Reads a range of bytes from the memories of the given instance, where the range is relative
+    /// to the start of the instance's concatenated memories.
+    pub(crate) async fn try_read_instance_slice(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        interleaved_index: usize,
+        range: Range<usize>,
+    ) -> Result<Vec<u8>, MemoryAccessError> {
+        let (buffer, _) = self.take(memory_system, queue, interleaved_index).await?;
+
+        let data = buffer
+            .map_lazy()
+            .try_read_slice_locking(queue, range)
+            .await?;
+
+        Ok(data)
+    }
+
+    /// Writes bytes to the memories of the given instance, starting at an offset relative
+    /// to the start of the instance's concatenated memories.
+    pub(crate) async fn try_write_instance_slice(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        interleaved_index: usize,
+        start: usize,
+        data: &[u8],
+    ) -> Result<(), MemoryAccessError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        // Instances are interleaved a stride at a time, so we write whole strides and need to
+        // read back the parts of the first and last strides that aren't being overwritten.
+        let stride = MEMORY_STRIDE_BYTES as usize;
+        let end = start + data.len();
+        let aligned_start = start / stride * stride;
+        let aligned_end = end.next_multiple_of(stride);
+
+        let aligned_data = if aligned_start == start && aligned_end == end {
+            data.to_vec()
+        } else {
+            let mut aligned_data = self
+                .try_read_instance_slice(
+                    memory_system,
+                    queue,
+                    interleaved_index,
+                    aligned_start..aligned_end,
+                )
+                .await?;
+            aligned_data[(start - aligned_start)..(end - aligned_start)].copy_from_slice(data);
+            aligned_data
+        };
+
+        let device = queue.device();
+        let staging = device
+            .create_buffer(&wgpu::BufferDescriptor {
+                label: Some("memory_write_staging_buffer"),
+                size: aligned_data.len() as u64,
+                usage: wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: true,
+            })
+            .await?;
+        staging
+            .slice(..)
+            .get_mapped_range_mut()
+            .copy_from_slice(&aligned_data);
+        staging.unmap();
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        for (i, stride_start) in (aligned_start..aligned_end).step_by(stride).enumerate() {
+            let interleaved_start =
+                (stride_start * self.instance_count) + (interleaved_index * stride);
+            encoder.copy_buffer_to_buffer(
+                &staging,
+                (i * stride) as u64,
+                &self.memory,
+                interleaved_start as u64,
+                MEMORY_STRIDE_BYTES,
+            );
+        }
+        queue.submit([encoder.finish()]).await;
+
+        Ok(())
+    }
 }
 
 impl MappedMemoryInstanceSet {
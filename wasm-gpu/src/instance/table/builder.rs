@@ -12,6 +12,9 @@ use wgpu_lazybuffers_macros::lazy_mappable;
 
 use super::instance::MappedTableInstanceSet;
 
+/// Every table entry is a function reference, stored as a single word.
+const TABLE_ENTRY_BYTES: usize = 4;
+
 #[derive(Debug, Clone)]
 struct Meta {}
 
@@ -39,6 +42,38 @@ impl UnmappedTableInstanceSetBuilder {
         )
         .await
     }
+
+    /// Creates a new builder holding the current state of the given instance in a set.
+    pub(crate) async fn try_from_instance(
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        existing: &UnmappedTableInstanceSet,
+        interleaved_index: usize,
+    ) -> Result<Self, OutOfMemoryError> {
+        let (tables, cap_set) = existing
+            .take(memory_system, queue, interleaved_index)
+            .await?;
+        Ok(Self { tables, cap_set })
+    }
+
+    /// Adds `additional` instances to a set built from this builder, initialised with the state in this builder.
+    pub(crate) async fn try_grow(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        set: &mut UnmappedTableInstanceSet,
+        existing_count: usize,
+        additional: usize,
+    ) -> Result<(), OutOfMemoryError> {
+        set.try_grow(
+            memory_system,
+            queue,
+            &self.tables,
+            existing_count,
+            additional,
+        )
+        .await
+    }
 }
 
 impl MappedTableInstanceSetBuilder {
@@ -70,13 +105,30 @@ impl MappedTableInstanceSetBuilder {
         })
     }
 
-    pub fn add_table(&mut self, plan: &TableType) -> AbstractTablePtr {
+    /// Adds a table with every entry initialized to the null reference.
+    pub async fn try_add_table(
+        &mut self,
+        queue: &AsyncQueue,
+        plan: &TableType,
+    ) -> Result<AbstractTablePtr, wgpu::BufferAsyncError> {
         let ptr = self.tables.len();
         let len = usize::try_from(plan.initial)
             .expect("table must be expressable in RAM, but was too big");
-        self.tables.extend_lazy(len);
+        let len_bytes = len * TABLE_ENTRY_BYTES;
+        self.tables.extend_lazy(len_bytes);
         self.cap_set = self.cap_set.resize_ref(self.tables.len());
-        return AbstractTablePtr::new(ptr, self.cap_set.get_cap(), plan.clone(), len);
+
+        let nulls = vec![0xFFu8; len_bytes];
+        self.tables
+            .try_write_slice_locking(queue, ptr..(ptr + len_bytes), &nulls)
+            .await?;
+
+        return Ok(AbstractTablePtr::new(
+            ptr,
+            self.cap_set.get_cap(),
+            plan.clone(),
+            len,
+        ));
     }
 
     pub async fn try_initialize(
@@ -84,15 +136,16 @@ impl MappedTableInstanceSetBuilder {
         queue: &AsyncQueue,
         ptr: &AbstractTablePtr,
         data: &[u8],
-        offset: usize,
+        offset_entries: usize,
     ) -> Result<(), wgpu::BufferAsyncError> {
         assert!(
             self.cap_set.check(&ptr.cap),
             "table pointer was not valid for this instance"
         );
 
+        let offset = offset_entries * TABLE_ENTRY_BYTES;
         assert!(
-            ptr.len >= offset + data.len(),
+            ptr.len * TABLE_ENTRY_BYTES >= offset + data.len(),
             "cannot slice memory larger than allocated memory space"
         );
 
@@ -119,7 +172,8 @@ impl AbstractTablePtr {
             && wasm_limits_match(self.ty.initial, self.ty.maximum, ty.initial, ty.maximum)
     }
 
+    /// The index of the first word of this table within the tables buffer.
     pub fn to_index(&self) -> wasm_gpu_funcgen::TableIndex {
-        wasm_gpu_funcgen::TableIndex::from(self.ptr)
+        wasm_gpu_funcgen::TableIndex::from(self.ptr / TABLE_ENTRY_BYTES)
     }
 }
@@ -1,5 +1,9 @@
 use crate::capabilities::CapabilityStore;
 use crate::impl_concrete_ptr;
+use crate::instance::interleaving::{
+    try_grow_interleaved, try_read_interleaved, try_write_interleaved,
+};
+use crate::instance::memory::instance::MemoryAccessError;
 use crate::instance::table::builder::AbstractTablePtr;
 use wgpu_async::{async_device::OutOfMemoryError, async_queue::AsyncQueue};
 use wgpu_lazybuffers::{MemorySystem, UnmappedLazyBuffer};
@@ -8,7 +12,7 @@ use wgpu_lazybuffers_interleaving::{
 };
 use wgpu_lazybuffers_macros::lazy_mappable;
 
-const STRIDE: u64 = 4; // FuncRef is 1 x u32
+pub(crate) const STRIDE: u64 = 4; // FuncRef is 1 x u32
 
 #[lazy_mappable(MappedTableInstanceSet)]
 pub struct UnmappedTableInstanceSet {
@@ -43,6 +47,48 @@ impl UnmappedTableInstanceSet {
         &self.tables
     }
 
+    /// Adds `additional` instances to this set, initialised from `source`, while preserving the state of
+    /// the `existing_count` instances already in the set.
+    pub(crate) async fn try_grow(
+        &mut self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        source: &UnmappedLazyBuffer,
+        existing_count: usize,
+        additional: usize,
+    ) -> Result<(), OutOfMemoryError> {
+        self.tables = try_grow_interleaved(
+            memory_system,
+            queue,
+            source,
+            &self.tables,
+            existing_count,
+            additional,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Reads the data of each of the first `count` instances in this set.
+    pub(crate) async fn try_read_instances(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        count: usize,
+    ) -> Result<Vec<Vec<u8>>, MemoryAccessError> {
+        try_read_interleaved(memory_system, queue, &self.tables, count).await
+    }
+
+    /// Overwrites the data of every instance in this set, one entry per instance.
+    pub(crate) async fn try_write_instances(
+        &self,
+        queue: &AsyncQueue,
+        instances: &[Vec<u8>],
+    ) -> Result<(), OutOfMemoryError> {
+        try_write_interleaved(queue, &self.tables, instances).await
+    }
+
     /// Duplicates the data from a given instance into a new buffer
     pub(super) async fn take(
         &self,
@@ -8,6 +8,7 @@ mod capabilities;
 mod externs;
 mod func;
 mod instance;
+mod limits;
 mod module;
 mod panic_on_any;
 mod session;
@@ -45,43 +46,55 @@ macro_rules! imports {
         ]
     };
 }
-pub use panic_on_any::PanicOnAny;
+pub use panic_on_any::{CollectErrors, PanicOnAny};
 
 // Configs
+pub use wasm_gpu_funcgen::BoundsCheckMode;
 pub use wasm_gpu_funcgen::FloatingPointOptions;
+pub use wasm_gpu_funcgen::HlslShaderModel;
+pub use wasm_gpu_funcgen::IoLayout;
+pub use wasm_gpu_funcgen::SpirvOptions;
+pub use wasm_gpu_funcgen::SpirvTargetEnv;
 pub use wasm_gpu_funcgen::Tuneables;
 pub use wasmparser::WasmFeatures;
 // Module
+pub use module::error::InstantiationError;
 pub use module::Module;
 // Externs
 pub use crate::externs::Extern;
+pub use crate::externs::ExternType;
 pub use crate::externs::NamedExtern;
 // Store
+pub use instance::memory::instance::MemoryAccessError;
+pub use session::SessionBuilder;
 pub use store_set::builder::MappedStoreSetBuilder; // Don't need to expose the unmapped version
-pub use store_set::DeviceStoreSet;
+pub use store_set::builder::StoreSetBuildError;
+pub use store_set::{DeviceStoreSet, InstanceBufferRegion, StoreSnapshot};
 // Instance
 pub use instance::ModuleInstanceReferences;
 // Ptr
+pub use instance::func::DynamicCallError;
 pub use instance::func::TypedFuncPtr;
 pub use instance::func::UntypedFuncPtr;
+pub use instance::global::builder::{AbstractGlobalPtr, TypedGlobalPtr};
+pub use instance::memory::builder::AbstractMemoryPtr;
+pub use wasm_gpu_funcgen::MemoryIndex;
 // Typing
 pub use typed::*;
+// Traps
+pub use wasm_gpu_funcgen::trap_message;
+// Bindings
+pub use wasm_gpu_funcgen::{
+    CONSTANTS_BINDING_INDEX, DATA_BINDING_INDEX, ELEMENTS_BINDING_INDEX, FLAGS_BINDING_INDEX,
+    IMMUTABLE_GLOBALS_BINDING_INDEX, INPUT_BINDING_INDEX, MEMORY_BINDING_INDEX,
+    MUTABLE_GLOBALS_BINDING_INDEX, OUTPUT_BINDING_INDEX, STACK_BINDING_INDEX, TABLES_BINDING_INDEX,
+};
+
+// Limits
+pub use limits::{UnsupportedLimit, UnsupportedLimitsError, WasmLimits, WasmLimitsBuilder};
 
 // Constants
-/// The limits required for evaluating wasm on the gpu.
+/// The limits required for evaluating wasm on the gpu. Use `WasmLimits::builder` for limits with higher occupancy.
 pub fn downlevel_wasm_defaults() -> wgpu::Limits {
-    let limits = wgpu::Limits {
-        max_bindings_per_bind_group: 11,
-        max_storage_buffers_per_shader_stage: 11,
-        max_compute_workgroup_size_x: 256,
-        max_compute_workgroup_size_y: 1,
-        max_compute_workgroup_size_z: 1,
-        max_compute_workgroups_per_dimension: 256,
-        max_compute_invocations_per_workgroup: 1, // Higher is *way* better
-        ..wgpu::Limits::downlevel_webgl2_defaults()
-    };
-
-    //assert!(limits.check_limits(&wgpu::Limits::downlevel_defaults()));
-
-    limits
+    WasmLimits::builder().build()
 }
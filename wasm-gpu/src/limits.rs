@@ -0,0 +1,152 @@
+//! Builds the `wgpu::Limits` to request a device with when evaluating wasm.
+
+/// The number of storage buffers bound by generated shaders, see `wasm_gpu_funcgen::BINDING_TUPLES`.
+const STORAGE_BUFFERS: u32 = 11;
+
+/// A single limit that was requested but isn't allowed by an adapter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedLimit {
+    /// The name of the field of `wgpu::Limits`
+    pub name: &'static str,
+    pub requested: u64,
+    pub allowed: u64,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("the adapter does not support the requested limits {unsupported:?}")]
+pub struct UnsupportedLimitsError {
+    pub unsupported: Vec<UnsupportedLimit>,
+}
+
+/// The limits that evaluating wasm depends on. Use `WasmLimits::builder` to construct limits with higher occupancy
+/// than `downlevel_wasm_defaults`, which every adapter can run.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WasmLimits {
+    invocations_per_workgroup: u32,
+    workgroups_per_dimension: u32,
+}
+
+impl WasmLimits {
+    pub fn builder() -> WasmLimitsBuilder {
+        WasmLimitsBuilder {
+            limits: Self::default(),
+        }
+    }
+
+    /// The limits to request a device with, regardless of whether any adapter supports them.
+    pub fn to_wgpu_limits(&self) -> wgpu::Limits {
+        wgpu::Limits {
+            max_bindings_per_bind_group: STORAGE_BUFFERS,
+            max_storage_buffers_per_shader_stage: STORAGE_BUFFERS,
+            // Workgroups are only ever one dimensional
+            max_compute_workgroup_size_x: u32::max(256, self.invocations_per_workgroup),
+            max_compute_workgroup_size_y: 1,
+            max_compute_workgroup_size_z: 1,
+            max_compute_workgroups_per_dimension: self.workgroups_per_dimension,
+            max_compute_invocations_per_workgroup: self.invocations_per_workgroup,
+            ..wgpu::Limits::downlevel_webgl2_defaults()
+        }
+    }
+}
+
+impl Default for WasmLimits {
+    fn default() -> Self {
+        Self {
+            invocations_per_workgroup: 1, // Higher is *way* better
+            workgroups_per_dimension: 256,
+        }
+    }
+}
+
+pub struct WasmLimitsBuilder {
+    limits: WasmLimits,
+}
+
+impl WasmLimitsBuilder {
+    /// The number of invocations in each workgroup, which should be at least `Tuneables::workgroup_size`.
+    pub fn invocations_per_workgroup(mut self, invocations_per_workgroup: u32) -> Self {
+        self.limits.invocations_per_workgroup = invocations_per_workgroup;
+        self
+    }
+
+    /// The number of workgroups that can be dispatched at once.
+    pub fn workgroups_per_dimension(mut self, workgroups_per_dimension: u32) -> Self {
+        self.limits.workgroups_per_dimension = workgroups_per_dimension;
+        self
+    }
+
+    /// Gives the limits to request a device with, without checking them against any adapter.
+    pub fn build(self) -> wgpu::Limits {
+        self.limits.to_wgpu_limits()
+    }
+
+    /// Gives the limits to request a device with from the given adapter, or describes the limits that the adapter
+    /// doesn't support.
+    pub fn build_for_adapter(
+        self,
+        adapter: &wgpu::Adapter,
+    ) -> Result<wgpu::Limits, UnsupportedLimitsError> {
+        self.check_against(&adapter.limits())
+    }
+
+    /// As with `build_for_adapter`, but checking against the limits that an adapter allows, as given by
+    /// `wgpu::Adapter::limits`.
+    pub fn check_against(
+        self,
+        allowed: &wgpu::Limits,
+    ) -> Result<wgpu::Limits, UnsupportedLimitsError> {
+        let limits = self.build();
+
+        let mut unsupported = Vec::new();
+        limits.check_limits_with_fail_fn(allowed, false, |name, requested, allowed| {
+            unsupported.push(UnsupportedLimit {
+                name,
+                requested,
+                allowed,
+            })
+        });
+        if !unsupported.is_empty() {
+            return Err(UnsupportedLimitsError { unsupported });
+        }
+
+        Ok(limits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WasmLimits;
+
+    #[test]
+    fn default_limits_use_one_invocation_per_workgroup() {
+        let limits = WasmLimits::builder().build();
+        assert_eq!(limits.max_compute_invocations_per_workgroup, 1);
+        assert_eq!(limits.max_compute_workgroup_size_x, 256);
+    }
+
+    #[test]
+    fn supported_limits_pass_check() {
+        let limits = WasmLimits::builder()
+            .invocations_per_workgroup(256)
+            .check_against(&wgpu::Limits {
+                max_storage_buffers_per_shader_stage: 16,
+                ..wgpu::Limits::default()
+            })
+            .unwrap();
+        assert_eq!(limits.max_compute_invocations_per_workgroup, 256);
+    }
+
+    #[test]
+    fn unsupported_limits_are_described() {
+        let err = WasmLimits::builder()
+            .invocations_per_workgroup(1024)
+            .check_against(&wgpu::Limits::downlevel_webgl2_defaults())
+            .unwrap_err();
+        let invocations = err
+            .unsupported
+            .iter()
+            .find(|limit| limit.name == "max_compute_invocations_per_workgroup")
+            .expect("invocations per workgroup should be unsupported");
+        assert_eq!(invocations.requested, 1024);
+    }
+}
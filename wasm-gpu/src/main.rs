@@ -171,7 +171,10 @@ async fn main() -> anyhow::Result<()> {
                 emulate_subnormals: true,
                 emulate_div_beyond_max: true,
                 emulate_f64: true,
+                canonicalize_nans: false,
+                round_to_nearest_even: false,
             },
+            ..Tuneables::default()
         },
     );
 
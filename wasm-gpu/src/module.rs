@@ -1,7 +1,7 @@
 pub mod error;
 pub mod parsing;
 
-use crate::externs::{Extern, NamedExtern};
+use crate::externs::{Extern, ExternType, NamedExtern};
 use crate::func::FuncAccessiblePtrs;
 use crate::instance::data::{DataPtr, MappedDataInstance};
 use crate::instance::element::{ElementPtr, MappedElementInstance};
@@ -10,6 +10,7 @@ use crate::instance::global::builder::{AbstractGlobalPtr, MappedMutableGlobalsIn
 use crate::instance::global::immutable::MappedImmutableGlobalsInstance;
 use crate::instance::memory::builder::{AbstractMemoryPtr, MappedMemoryInstanceSetBuilder};
 use crate::instance::table::builder::{AbstractTablePtr, MappedTableInstanceSetBuilder};
+use crate::module::error::InstantiationError;
 use crate::module::parsing::{
     ImportTypeRef, ModuleEnviron, ModuleExport, ParsedDataKind, ParsedElementKind, ParsedModuleUnit,
 };
@@ -17,10 +18,10 @@ use crate::store_set::builder::interpret_constexpr;
 use anyhow::{anyhow, Context, Error};
 use itertools::Itertools;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::slice::Iter;
 use std::sync::Arc;
-use wasm_gpu_funcgen::{FuncData, FunctionModuleData};
+use wasm_gpu_funcgen::{unlowered_proposal, BuildError, FuncData, FunctionModuleData};
 use wasm_types::{FuncRef, Val, ValTypeByteCount};
 use wasmparser::Validator;
 use wgpu::BufferAsyncError;
@@ -68,16 +69,63 @@ impl Module {
         return Ok(parsed);
     }
 
-    pub fn new<'a>(
+    /// Parses and validates a module given as either wasm or wat, then checks that everything it uses can
+    /// be lowered to the GPU.
+    fn parse_supported<'a>(
         features: &wasmparser::WasmFeatures,
         bytes: impl IntoIterator<Item = &'a u8>,
-        name: String,
-    ) -> Result<Self, Error> {
+    ) -> Result<ParsedModuleUnit, Error> {
         let wasm: Vec<_> = bytes.into_iter().map(|v| *v).collect();
         let wasm: Cow<'_, [u8]> = wat::parse_bytes(wasm.as_slice())?;
         let wasm = wasm.to_vec();
 
         let parsed = Self::parse(features, wasm)?;
+        Self::check_supported(&parsed)?;
+
+        return Ok(parsed);
+    }
+
+    fn check_supported(parsed: &ParsedModuleUnit) -> Result<(), BuildError> {
+        // All addressing on the GPU is 32-bit, so 64-bit memories would be silently truncated
+        let sections = parsed.borrow_sections();
+        let imported_memories = sections.imports.iter().filter_map(|(_, _, ty)| match ty {
+            ImportTypeRef::Memory(ty) => Some(ty),
+            _ => None,
+        });
+        if sections
+            .memories
+            .iter()
+            .chain(imported_memories)
+            .any(|memory| memory.memory64)
+        {
+            return Err(BuildError::UnsupportedProposal {
+                proposal: "memory64",
+            });
+        }
+
+        // Validation only checks the module against the features requested, so also check that every instruction
+        // can be lowered, rather than failing part way through building a store set
+        let unlowered_proposals: BTreeSet<_> = sections
+            .functions
+            .iter()
+            .flat_map(|func| func.operators.iter())
+            .filter_map(unlowered_proposal)
+            .collect();
+        if !unlowered_proposals.is_empty() {
+            return Err(BuildError::UnloweredProposals {
+                proposals: unlowered_proposals.into_iter().collect(),
+            });
+        }
+
+        return Ok(());
+    }
+
+    pub fn new<'a>(
+        features: &wasmparser::WasmFeatures,
+        bytes: impl IntoIterator<Item = &'a u8>,
+        name: String,
+    ) -> Result<Self, Error> {
+        let parsed = Self::parse_supported(features, bytes)?;
 
         return Ok(Self {
             parsed,
@@ -85,6 +133,18 @@ impl Module {
         });
     }
 
+    /// Checks that a module is valid and that everything it uses can be lowered to the GPU, without keeping
+    /// the parsed module. This catches the same errors as [`Module::new`] without the caller needing to
+    /// hold on to a module, so is useful for rejecting modules up front before any GPU work is done.
+    pub fn validate_only<'a>(
+        features: &wasmparser::WasmFeatures,
+        bytes: impl IntoIterator<Item = &'a u8>,
+    ) -> Result<(), Error> {
+        Self::parse_supported(features, bytes)?;
+
+        return Ok(());
+    }
+
     /// See 4.5.4 of WASM spec 2.0
     /// Performs 1-4
     pub fn typecheck_imports(
@@ -106,6 +166,17 @@ impl Module {
             })
             .collect();
 
+        let missing_imports = self
+            .imports()
+            .into_iter()
+            .filter(|(module, name, _)| {
+                !import_by_name.contains_key(&(module.clone(), name.clone()))
+            })
+            .collect_vec();
+        if !missing_imports.is_empty() {
+            return Err(InstantiationError::MissingImports(missing_imports).into());
+        }
+
         let mut validated_imports = ValidatedImports {
             functions: vec![],
             globals: vec![],
@@ -115,11 +186,9 @@ impl Module {
         for (module, name, required_import) in self.parsed.borrow_sections().imports.iter() {
             // Get provided
             let key = (module.to_string(), name.to_string());
-            let provided_import = import_by_name.get(&key).ok_or(anyhow!(
-                "missing import with module {} and name {}",
-                module,
-                name
-            ))?;
+            let provided_import = import_by_name
+                .get(&key)
+                .expect("missing imports are reported above");
 
             // Check type
             let matches = match (required_import, provided_import) {
@@ -289,7 +358,7 @@ impl Module {
 
         // Create tables first
         for table_plan in self.parsed.borrow_sections().tables.iter() {
-            let ptr = tables.add_table(&table_plan.ty);
+            let ptr = tables.try_add_table(queue, &table_plan.ty).await?;
             ptrs.push(ptr);
         }
 
@@ -303,11 +372,13 @@ impl Module {
         {
             match &element.kind {
                 ParsedElementKind::Active {
-                    table_index: Some(table_index),
+                    table_index,
                     offset_expr,
                 } => {
+                    // MVP element segments omit the table index, implicitly referring to table 0
+                    let table_index = table_index.unwrap_or(0);
                     let table_ptr = ptrs
-                        .get((*table_index) as usize)
+                        .get(table_index as usize)
                         .expect("table index out of range");
                     let v = interpret_constexpr(
                         queue,
@@ -333,7 +404,10 @@ impl Module {
                     // Then we can drop this element
                     elements.drop(element_ptr).await;
                 }
-                _ => {}
+                // Declarative segments are only there to allow `ref.func`, so act as if dropped
+                ParsedElementKind::Declared => elements.drop(element_ptr).await,
+                // Passive segments are kept for `table.init`
+                ParsedElementKind::Passive => {}
             }
         }
 
@@ -403,8 +477,6 @@ impl Module {
                     memory_index,
                     offset_expr,
                 } => {
-                    assert_eq!(*memory_index, 0);
-
                     let memory_ptr = ptrs
                         .get((*memory_index) as usize)
                         .expect("memory index out of range");
@@ -472,7 +544,11 @@ impl Module {
                     ty,
                     locals: func.locals.clone(),
                     operators: func.operators.clone(),
+                    operator_ranges: func.operator_ranges.clone(),
+                    body_hash: func.body_hash,
                     module_data: Arc::clone(&module_data),
+                    name: func.name.clone(),
+                    local_names: func.local_names.clone(),
                 }
             })
             .map(|data| functions.register_definition(data))
@@ -670,7 +746,355 @@ impl Module {
         }
     }
 
-    pub fn exports(&self) -> &HashMap<String, ModuleExport> {
+    pub(crate) fn export_refs(&self) -> &HashMap<String, ModuleExport> {
         &self.parsed.borrow_sections().exports
     }
+
+    fn import_type(&self, import: &ImportTypeRef) -> ExternType {
+        match import {
+            ImportTypeRef::Func(type_id) => {
+                ExternType::Func(self.parsed.borrow_sections().types[*type_id as usize].clone())
+            }
+            ImportTypeRef::Table(ty) => ExternType::Table(*ty),
+            ImportTypeRef::Memory(ty) => ExternType::Memory(*ty),
+            ImportTypeRef::Global(ty) => ExternType::Global(*ty),
+        }
+    }
+
+    /// Lists the objects that must be provided to instantiate this module, as `(module, name, type)`
+    /// in the order that they are declared.
+    pub fn imports(&self) -> Vec<(String, String, ExternType)> {
+        self.parsed
+            .borrow_sections()
+            .imports
+            .iter()
+            .map(|(module, name, ty)| (module.to_string(), name.to_string(), self.import_type(ty)))
+            .collect()
+    }
+
+    /// Lists the objects exported by this module, with their types, sorted by name.
+    pub fn exports(&self) -> Vec<(String, ExternType)> {
+        let sections = self.parsed.borrow_sections();
+
+        // Each index space contains the imported objects followed by the defined objects
+        let imported = sections
+            .imports
+            .iter()
+            .map(|(_, _, ty)| self.import_type(ty))
+            .collect_vec();
+        let funcs = imported
+            .iter()
+            .filter_map(|ty| ty.func().cloned())
+            .chain(
+                sections
+                    .functions
+                    .iter()
+                    .map(|func| sections.types[func.type_id as usize].clone()),
+            )
+            .collect_vec();
+        let tables = imported
+            .iter()
+            .filter_map(|ty| ty.table().cloned())
+            .chain(sections.tables.iter().map(|table| table.ty))
+            .collect_vec();
+        let memories = imported
+            .iter()
+            .filter_map(|ty| ty.memory().cloned())
+            .chain(sections.memories.iter().cloned())
+            .collect_vec();
+        let globals = imported
+            .iter()
+            .filter_map(|ty| ty.global().cloned())
+            .chain(sections.globals.iter().map(|global| global.ty))
+            .collect_vec();
+
+        sections
+            .exports
+            .iter()
+            .map(|(name, export)| {
+                let ty = match export {
+                    ModuleExport::Func(i) => ExternType::Func(funcs[*i].clone()),
+                    ModuleExport::Table(i) => ExternType::Table(tables[*i]),
+                    ModuleExport::Memory(i) => ExternType::Memory(memories[*i]),
+                    ModuleExport::Global(i) => ExternType::Global(globals[*i]),
+                };
+                (name.clone(), ty)
+            })
+            .sorted_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ExternType, InstantiationError};
+    use wasm_gpu_funcgen::BuildError;
+    use wasmparser::{FuncType, GlobalType, MemoryType, ValType};
+
+    #[test]
+    fn test_memory64_is_rejected() {
+        let features = wasmparser::WasmFeatures {
+            memory64: true,
+            ..Default::default()
+        };
+        let wat = r#"
+            (module
+                (memory i64 1)
+            )
+        "#;
+
+        let err = match crate::Module::new(&features, wat.as_bytes(), "test_module".to_owned()) {
+            Ok(_) => panic!("memory64 modules should be rejected"),
+            Err(err) => err,
+        };
+        assert!(matches!(
+            err.downcast_ref::<BuildError>(),
+            Some(BuildError::UnsupportedProposal {
+                proposal: "memory64"
+            })
+        ));
+    }
+
+    fn unlowered_proposals(features: &wasmparser::WasmFeatures, wat: &str) -> Vec<&'static str> {
+        let err = match crate::Module::new(features, wat.as_bytes(), "test_module".to_owned()) {
+            Ok(_) => panic!("modules with unlowered instructions should be rejected"),
+            Err(err) => err,
+        };
+        match err.downcast_ref::<BuildError>() {
+            Some(BuildError::UnloweredProposals { proposals }) => proposals.clone(),
+            _ => panic!("expected unlowered proposals but got {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_unlowered_simd_is_rejected() {
+        let wat = r#"
+            (module
+                (func (export "shuffle") (param v128 v128) (result v128)
+                    (i8x16.shuffle 0 1 2 3 4 5 6 7 16 17 18 19 20 21 22 23
+                        (local.get 0) (local.get 1))
+                )
+            )
+        "#;
+
+        assert_eq!(
+            unlowered_proposals(&wasmparser::WasmFeatures::default(), wat),
+            vec!["simd"]
+        );
+    }
+
+    #[test]
+    fn test_lowered_simd_is_accepted() {
+        let wat = r#"
+            (module
+                (func (export "add") (param v128 v128) (result v128)
+                    (i32x4.add (local.get 0) (local.get 1))
+                )
+            )
+        "#;
+
+        crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_gc_is_rejected() {
+        let features = wasmparser::WasmFeatures {
+            function_references: true,
+            gc: true,
+            ..Default::default()
+        };
+        let wat = r#"
+            (module
+                (func (export "is_small") (param i32) (result i32)
+                    (i31.get_s (ref.i31 (local.get 0)))
+                )
+            )
+        "#;
+
+        assert_eq!(unlowered_proposals(&features, wat), vec!["gc"]);
+    }
+
+    #[test]
+    fn test_validate_only_accepts_supported_module() {
+        let wat = r#"
+            (module
+                (memory 1)
+                (func (export "add") (param i32 i32) (result i32)
+                    (i32.add (local.get 0) (local.get 1))
+                )
+            )
+        "#;
+
+        crate::Module::validate_only(&wasmparser::WasmFeatures::default(), wat.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_validate_only_rejects_gc_module() {
+        let features = wasmparser::WasmFeatures {
+            function_references: true,
+            gc: true,
+            ..Default::default()
+        };
+        let wat = r#"
+            (module
+                (func (export "is_small") (param i32) (result i32)
+                    (i31.get_s (ref.i31 (local.get 0)))
+                )
+            )
+        "#;
+
+        let err = crate::Module::validate_only(&features, wat.as_bytes())
+            .expect_err("gc modules should be rejected");
+        assert!(matches!(
+            err.downcast_ref::<BuildError>(),
+            Some(BuildError::UnloweredProposals { proposals }) if proposals == &vec!["gc"]
+        ));
+    }
+
+    #[test]
+    fn test_validate_only_rejects_invalid_module() {
+        let wat = r#"
+            (module
+                (func (export "bad") (result i32)
+                    (i64.const 0)
+                )
+            )
+        "#;
+
+        assert!(
+            crate::Module::validate_only(&wasmparser::WasmFeatures::default(), wat.as_bytes())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_imports_and_exports_are_listed_with_types() {
+        let wat = r#"
+            (module
+                (import "env" "scale" (global $scale i32))
+                (import "env" "log" (func $log (param i64)))
+                (memory 1 2)
+                (global $counter (mut f32) (f32.const 0))
+                (func $add (param i32 i32) (result i32)
+                    (i32.add (local.get 0) (local.get 1))
+                )
+                (export "memory" (memory 0))
+                (export "counter" (global $counter))
+                (export "scale" (global $scale))
+                (export "add" (func $add))
+                (export "log" (func $log))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let i32_global = GlobalType {
+            content_type: ValType::I32,
+            mutable: false,
+        };
+        let log_ty = FuncType::new([ValType::I64], []);
+
+        assert_eq!(
+            module.imports(),
+            vec![
+                (
+                    "env".to_owned(),
+                    "scale".to_owned(),
+                    ExternType::Global(i32_global)
+                ),
+                (
+                    "env".to_owned(),
+                    "log".to_owned(),
+                    ExternType::Func(log_ty.clone())
+                ),
+            ]
+        );
+
+        assert_eq!(
+            module.exports(),
+            vec![
+                (
+                    "add".to_owned(),
+                    ExternType::Func(FuncType::new([ValType::I32, ValType::I32], [ValType::I32]))
+                ),
+                (
+                    "counter".to_owned(),
+                    ExternType::Global(GlobalType {
+                        content_type: ValType::F32,
+                        mutable: true,
+                    })
+                ),
+                ("log".to_owned(), ExternType::Func(log_ty)),
+                (
+                    "memory".to_owned(),
+                    ExternType::Memory(MemoryType {
+                        memory64: false,
+                        shared: false,
+                        initial: 1,
+                        maximum: Some(2),
+                    })
+                ),
+                ("scale".to_owned(), ExternType::Global(i32_global)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_imports_are_listed() {
+        let wat = r#"
+            (module
+                (import "env" "offset" (global $offset i32))
+                (import "env" "memory" (memory 1))
+                (func (export "load") (result i32)
+                    (i32.load (global.get $offset))
+                )
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let err = module
+            .typecheck_imports(&crate::imports! {})
+            .err()
+            .expect("missing imports should be rejected");
+        match err.downcast_ref::<InstantiationError>() {
+            Some(InstantiationError::MissingImports(missing)) => assert_eq!(
+                missing,
+                &vec![
+                    (
+                        "env".to_owned(),
+                        "offset".to_owned(),
+                        ExternType::Global(GlobalType {
+                            content_type: ValType::I32,
+                            mutable: false,
+                        })
+                    ),
+                    (
+                        "env".to_owned(),
+                        "memory".to_owned(),
+                        ExternType::Memory(MemoryType {
+                            memory64: false,
+                            shared: false,
+                            initial: 1,
+                            maximum: None,
+                        })
+                    ),
+                ]
+            ),
+            _ => panic!("expected missing imports error but got {err}"),
+        }
+    }
 }
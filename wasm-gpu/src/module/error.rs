@@ -1,3 +1,5 @@
+use crate::externs::ExternType;
+use itertools::Itertools;
 use thiserror::Error;
 use wasmparser::BinaryReaderError;
 
@@ -34,3 +36,14 @@ impl From<BinaryReaderError> for WasmError {
         }
     }
 }
+
+/// An error found when linking the imports provided to a module against the imports that it requires.
+#[derive(Error, Debug)]
+pub enum InstantiationError {
+    /// The module requires imports that weren't provided, listed as `(module, name, type)`.
+    #[error(
+        "missing imports: {}",
+        .0.iter().map(|(module, name, ty)| format!("{module}.{name} of type {ty:?}")).join(", ")
+    )]
+    MissingImports(Vec<(String, String, ExternType)>),
+}
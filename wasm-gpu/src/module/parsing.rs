@@ -1,12 +1,14 @@
 use crate::module::error::WasmError;
 use ouroboros::self_referencing;
 use std::collections::HashMap;
+use std::hash::Hasher;
 use std::ops::Range;
+use wasm_gpu_funcgen::StableHasher;
 use wasm_opcodes::OperatorByProposal;
 use wasmparser::{
     BinaryReaderError, DataKind, ElementKind, Encoding, ExternalKind, FuncType,
-    FuncValidatorAllocations, GlobalType, MemoryType, NameSectionReader, Operator, Parser, Payload,
-    RefType, Table, TableType, Type, TypeRef, ValType, Validator,
+    FuncValidatorAllocations, GlobalType, MemoryType, Name, NameSectionReader, Operator, Parser,
+    Payload, RefType, Table, TableType, Type, TypeRef, ValType, Validator,
 };
 
 type WasmResult<T> = Result<T, WasmError>;
@@ -37,6 +39,14 @@ pub struct ParsedFunc {
     pub type_id: u32,
     pub locals: Vec<(u32, ValType)>,
     pub operators: Vec<OperatorByProposal>,
+    /// The bytes of the module's binary that each of `operators` was read from
+    pub operator_ranges: Vec<Range<usize>>,
+    /// A [`StableHasher`] hash of the bytes of the function's body, including its locals
+    pub body_hash: u64,
+    /// The name given to the function by the name section, if any
+    pub name: Option<String>,
+    /// The names given to the function's parameters and locals by the name section, by local index
+    pub local_names: HashMap<u32, String>,
 }
 
 pub enum ParsedElementKind<'data> {
@@ -420,16 +430,34 @@ impl ModuleEnviron {
                 let mut func = ParsedFunc {
                     locals: vec![],
                     operators: vec![],
+                    operator_ranges: vec![],
+                    body_hash: 0,
                     type_id: type_id.clone(),
+                    name: None,
+                    local_names: HashMap::new(),
                 };
+                let mut reader = body.get_binary_reader();
+                let mut hasher = StableHasher::default();
+                hasher.write(reader.read_bytes(reader.bytes_remaining())?);
+                func.body_hash = hasher.finish();
+
                 for local in body.get_locals_reader()? {
                     func.locals.push(local?);
                 }
-                for operator in body.get_operators_reader()? {
-                    let op = OperatorByProposal::from_operator(operator?)?;
+                let mut offsets = Vec::new();
+                for operator in body.get_operators_reader()?.into_iter_with_offsets() {
+                    let (operator, offset) = operator?;
+                    let op = OperatorByProposal::from_operator(operator)?;
 
                     func.operators.push(op);
+                    offsets.push(offset);
                 }
+                // Each operator runs up to the start of the next, and the last runs to the end of the body
+                offsets.push(body.range().end);
+                func.operator_ranges = offsets
+                    .windows(2)
+                    .map(|window| window[0]..window[1])
+                    .collect();
 
                 result.functions.push(func);
             }
@@ -473,7 +501,9 @@ impl ModuleEnviron {
             Payload::CustomSection(s) => {
                 match s.name() {
                     "name" => {
-                        let _reader = NameSectionReader::new(s.data(), 0);
+                        // Names are only for debugging, so a malformed name section is ignored as the spec requires
+                        let reader = NameSectionReader::new(s.data(), s.data_offset());
+                        let _ = Self::translate_name_section(reader, result);
                     }
                     _ => {
                         return Err(WasmError::Unsupported(format!(
@@ -495,4 +525,55 @@ impl ModuleEnviron {
         }
         Ok(())
     }
+
+    /// Attaches the function and local names in the name section to the functions defined in the module. Names
+    /// are indexed by function index, which includes imported functions.
+    fn translate_name_section(
+        reader: NameSectionReader,
+        result: &mut ParsedModule,
+    ) -> Result<(), BinaryReaderError> {
+        let imported_functions = result
+            .imports
+            .iter()
+            .filter(|(_, _, ty)| matches!(ty, ImportTypeRef::Func(_)))
+            .count();
+        let defined_function = |index: u32| {
+            usize::try_from(index)
+                .ok()
+                .and_then(|index| index.checked_sub(imported_functions))
+        };
+
+        for subsection in reader {
+            match subsection? {
+                Name::Function(names) => {
+                    for naming in names {
+                        let naming = naming?;
+                        let func = defined_function(naming.index)
+                            .and_then(|index| result.functions.get_mut(index));
+                        if let Some(func) = func {
+                            func.name = Some(naming.name.to_owned());
+                        }
+                    }
+                }
+                Name::Local(names) => {
+                    for indirect_naming in names {
+                        let indirect_naming = indirect_naming?;
+                        let func = defined_function(indirect_naming.index)
+                            .and_then(|index| result.functions.get_mut(index));
+                        if let Some(func) = func {
+                            for naming in indirect_naming.names {
+                                let naming = naming?;
+                                func.local_names
+                                    .insert(naming.index, naming.name.to_owned());
+                            }
+                        }
+                    }
+                }
+                // Other names aren't used in generated modules
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
 }
@@ -21,3 +21,98 @@ impl<E: std::fmt::Debug, T> PanicOnAny for Vec<Result<T, E>> {
         self.into_iter().map(|r| r.expect(msg)).collect_vec()
     }
 }
+
+/// Utility for splitting vectors of Results into the values that succeeded and the errors, along with the index
+/// of each error, e.g. to report which instances trapped without giving up on the rest
+pub trait CollectErrors {
+    type Item;
+    type Error;
+
+    fn collect_errors(self) -> (Vec<Self::Item>, Vec<(usize, Self::Error)>);
+}
+
+impl<E, T> CollectErrors for Vec<Result<T, E>> {
+    type Item = T;
+    type Error = E;
+
+    fn collect_errors(self) -> (Vec<T>, Vec<(usize, E)>) {
+        let mut oks = Vec::new();
+        let mut errs = Vec::new();
+        for (i, r) in self.into_iter().enumerate() {
+            match r {
+                Ok(v) => oks.push(v),
+                Err(e) => errs.push((i, e)),
+            }
+        }
+
+        (oks, errs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CollectErrors;
+    use crate::unit_tests_lib::get_backend;
+    use crate::{imports, MappedStoreSetBuilder};
+    use wasmtime_environ::Trap;
+
+    #[tokio::test]
+    async fn test_collect_errors_partitions_trapped_instances() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+
+        let wat = r#"
+            (module
+                (func $f (param i32) (result i32)
+                    (if (i32.rem_u (local.get 0) (i32.const 2))
+                        (then (unreachable))
+                    )
+                    (i32.mul (local.get 0) (i32.const 10))
+                )
+                (export "f" (func $f))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instances = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let target = instances.get_typed_func::<i32, i32>("f").unwrap();
+
+        let store_source = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let mut stores = store_source
+            .build(&memory_system, &queue, 8)
+            .await
+            .expect("could not build stores");
+
+        let (oks, errs) = target
+            .call_all(&memory_system, &queue, &mut stores, 0..8)
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers")
+            .collect_errors();
+
+        assert_eq!(oks, vec![0, 20, 40, 60]);
+        assert_eq!(
+            errs,
+            vec![
+                (1, Trap::UnreachableCodeReached),
+                (3, Trap::UnreachableCodeReached),
+                (5, Trap::UnreachableCodeReached),
+                (7, Trap::UnreachableCodeReached),
+            ]
+        );
+    }
+}
@@ -1,28 +1,35 @@
-use crate::instance::func::UntypedFuncPtr;
-use crate::store_set::StoreSet;
+use crate::instance::func::{TypedFuncPtr, UntypedFuncPtr};
+use crate::store_set::UnmappedStoreSetData;
 use crate::DeviceStoreSet;
 use futures::future::join_all;
-use futures::{future::BoxFuture, FutureExt};
+use futures::stream::{BoxStream, FuturesUnordered};
+use futures::{future::BoxFuture, FutureExt, StreamExt};
+use std::ops::Range;
 use wasm_gpu_funcgen::{
-    u32_to_trap, CONSTANTS_BINDING_INDEX, CONSTANTS_LEN_BYTES, FLAGS_LEN_BYTES,
-    IO_ARGUMENT_ALIGNMENT_WORDS, IO_INVOCATION_ALIGNMENT_WORDS, STACK_LEN_BYTES,
-    TOTAL_INVOCATIONS_CONSTANT_INDEX, TRAP_FLAG_INDEX,
+    u32_to_trap, IoLayout, PackedIoLayout, Tuneables, CONSTANTS_BINDING_INDEX, CONSTANTS_LEN_BYTES,
+    DATA_DROPPED_FLAG_INDEX, DATA_DROPPED_FLAG_WORDS, ELEMENT_DROPPED_FLAG_INDEX,
+    ELEMENT_DROPPED_FLAG_WORDS, FLAGS_LEN_BYTES, MEMORY_PAGES_FLAG_INDEX, PACKED_IO_BINDING_INDEX,
+    STACK_LEN_BYTES, TOTAL_INVOCATIONS_CONSTANT_INDEX, TRAP_FLAG_INDEX,
 };
 use wasm_gpu_funcgen::{
     DATA_BINDING_INDEX, ELEMENTS_BINDING_INDEX, FLAGS_BINDING_INDEX,
     IMMUTABLE_GLOBALS_BINDING_INDEX, INPUT_BINDING_INDEX, MEMORY_BINDING_INDEX,
     MUTABLE_GLOBALS_BINDING_INDEX, OUTPUT_BINDING_INDEX, STACK_BINDING_INDEX, TABLES_BINDING_INDEX,
 };
-use wasm_types::{Val, ValTypeByteCount};
+use wasm_types::{Val, ValTypeByteCount, WasmTyVec};
 use wasmparser::ValType;
 use wgpu::{BufferAsyncError, BufferUsages};
-use wgpu_async::{AsyncBuffer, AsyncDevice, AsyncQueue, OutOfMemoryError};
-use wgpu_lazybuffers::{
-    LazilyMappable, LockCollection, MemoryBlockConfig, MemorySystem, UnmappedLazyBuffer,
-};
+use wgpu_async::{AsyncBuffer, AsyncDevice, AsyncQueue, OutOfMemoryError, WgpuFuture};
+use wgpu_lazybuffers::{LazilyMappable, MemoryBlockConfig, MemorySystem, UnmappedLazyBuffer};
 
 pub(crate) type OutputType =
     Result<Vec<Result<Vec<Val>, wasmtime_environ::Trap>>, BufferAsyncError>;
+/// The instance index and result of a single invocation, yielded as soon as it has been read
+pub(crate) type StreamedOutputType =
+    Result<(usize, Result<Vec<Val>, wasmtime_environ::Trap>), BufferAsyncError>;
+/// The results of every invocation of each call in a pipeline, in the order that the calls were recorded
+pub(crate) type PipelinedOutputType =
+    Result<Vec<Vec<Result<Vec<Val>, wasmtime_environ::Trap>>>, BufferAsyncError>;
 
 pub struct Bindings<'a> {
     pub data: &'a wgpu::Buffer,
@@ -38,6 +45,9 @@ pub struct Bindings<'a> {
     pub stack: &'a wgpu::Buffer,
     pub constants: &'a wgpu::Buffer,
 
+    /// If true, `flags`, `input`, `output`, `stack` and `constants` are all the same buffer and are bound once
+    pub packed_io: bool,
+
     /// When a buffer is empty (has size 0) we need to bind something else instead. This holds ownership of those buffers
     empty_bindings: elsa::FrozenVec<Box<wgpu::Buffer>>,
 }
@@ -87,16 +97,20 @@ impl<'a> Bindings<'a> {
         );
         self.conditionally_attach(&mut entries, device, self.memory, MEMORY_BINDING_INDEX);
         self.conditionally_attach(&mut entries, device, self.table, TABLES_BINDING_INDEX);
-        self.conditionally_attach(&mut entries, device, self.flags, FLAGS_BINDING_INDEX);
-        self.conditionally_attach(&mut entries, device, self.input, INPUT_BINDING_INDEX);
-        self.conditionally_attach(&mut entries, device, self.output, OUTPUT_BINDING_INDEX);
-        self.conditionally_attach(&mut entries, device, self.stack, STACK_BINDING_INDEX);
-        self.conditionally_attach(
-            &mut entries,
-            device,
-            self.constants,
-            CONSTANTS_BINDING_INDEX,
-        );
+        if self.packed_io {
+            self.conditionally_attach(&mut entries, device, self.input, PACKED_IO_BINDING_INDEX);
+        } else {
+            self.conditionally_attach(&mut entries, device, self.flags, FLAGS_BINDING_INDEX);
+            self.conditionally_attach(&mut entries, device, self.input, INPUT_BINDING_INDEX);
+            self.conditionally_attach(&mut entries, device, self.output, OUTPUT_BINDING_INDEX);
+            self.conditionally_attach(&mut entries, device, self.stack, STACK_BINDING_INDEX);
+            self.conditionally_attach(
+                &mut entries,
+                device,
+                self.constants,
+                CONSTANTS_BINDING_INDEX,
+            );
+        }
 
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: None,
@@ -106,17 +120,48 @@ impl<'a> Bindings<'a> {
     }
 }
 
+/// The buffers created for each dispatch, which are either bound separately or, on devices with too few
+/// storage buffer bindings, packed into a single buffer laid out as described by a `PackedIoLayout`.
+enum IoBuffers {
+    Separate {
+        input: AsyncBuffer,
+        constants: AsyncBuffer,
+        stack: AsyncBuffer,
+    },
+    Packed {
+        buffer: AsyncBuffer,
+        layout: PackedIoLayout,
+    },
+}
+
+/// The arguments given to the invocations of a session
+pub(crate) enum SessionArgs {
+    /// A distinct set of arguments for each invocation
+    PerInvocation(Vec<Vec<Val>>),
+    /// The same set of arguments for every one of `count` invocations
+    Broadcast { args: Vec<Val>, count: usize },
+}
+
+impl SessionArgs {
+    fn len(&self) -> usize {
+        match self {
+            SessionArgs::PerInvocation(args) => args.len(),
+            SessionArgs::Broadcast { count, .. } => *count,
+        }
+    }
+}
+
 pub struct Session<'a> {
     stores: &'a mut DeviceStoreSet,
     entry_func: UntypedFuncPtr,
-    args: Vec<Vec<Val>>,
+    args: SessionArgs,
 }
 
 impl<'a> Session<'a> {
-    pub fn new(
+    pub(crate) fn new(
         stores: &'a mut DeviceStoreSet,
         entry_func: UntypedFuncPtr,
-        args: Vec<Vec<Val>>,
+        args: SessionArgs,
     ) -> Self {
         Self {
             stores,
@@ -125,24 +170,98 @@ impl<'a> Session<'a> {
         }
     }
 
-    async fn make_inputs(
-        args: &[Vec<Val>],
-        device: &AsyncDevice,
-        label: &str,
-    ) -> Result<AsyncBuffer, OutOfMemoryError> {
+    fn encode_input(input: &Val, tuneables: &Tuneables, data: &mut Vec<u8>) {
+        data.append(&mut Val::to_bytes(input));
+
+        while data.len() % (tuneables.io_argument_alignment_words * 4) as usize != 0 {
+            data.push(0u8)
+        }
+    }
+
+    fn encode_invocation_inputs(input_set: &[Val], tuneables: &Tuneables, data: &mut Vec<u8>) {
+        for input in input_set {
+            Self::encode_input(input, tuneables, data);
+        }
+        while data.len() % (tuneables.io_invocation_alignment_words * 4) as usize != 0 {
+            data.push(0u8)
+        }
+    }
+
+    /// The input bytes of each of the given invocations, laid out as given by `Tuneables::io_layout`.
+    fn encode_inputs(
+        args: &SessionArgs,
+        instances: Range<usize>,
+        tuneables: &Tuneables,
+    ) -> Vec<u8> {
+        match tuneables.io_layout {
+            IoLayout::Packed => Self::encode_packed_inputs(args, instances, tuneables),
+            IoLayout::StructOfArrays => {
+                Self::encode_struct_of_arrays_inputs(args, instances, tuneables)
+            }
+        }
+    }
+
+    /// The input bytes of each of the given invocations, laid out one after another.
+    fn encode_packed_inputs(
+        args: &SessionArgs,
+        instances: Range<usize>,
+        tuneables: &Tuneables,
+    ) -> Vec<u8> {
         let mut data = Vec::new();
-        for input_set in args {
-            for input in input_set {
-                data.append(&mut Val::to_bytes(input));
+        match args {
+            SessionArgs::PerInvocation(args) => {
+                for input_set in &args[instances] {
+                    Self::encode_invocation_inputs(input_set, tuneables, &mut data);
+                }
+            }
+            SessionArgs::Broadcast { args, .. } => {
+                // Every invocation has the same inputs, so encode them once and repeat the bytes
+                Self::encode_invocation_inputs(args, tuneables, &mut data);
+                data = data.repeat(instances.len());
+            }
+        }
+
+        return data;
+    }
 
-                while data.len() % (IO_ARGUMENT_ALIGNMENT_WORDS * 4) as usize != 0 {
-                    data.push(0u8)
+    /// The first input of each of the given invocations, followed by the second input of each, and so on.
+    fn encode_struct_of_arrays_inputs(
+        args: &SessionArgs,
+        instances: Range<usize>,
+        tuneables: &Tuneables,
+    ) -> Vec<u8> {
+        let mut data = Vec::new();
+        match args {
+            SessionArgs::PerInvocation(args) => {
+                let input_sets = &args[instances];
+                let input_count = input_sets.first().map_or(0, Vec::len);
+                for i_input in 0..input_count {
+                    for input_set in input_sets {
+                        Self::encode_input(&input_set[i_input], tuneables, &mut data);
+                    }
                 }
             }
-            while data.len() % (IO_INVOCATION_ALIGNMENT_WORDS * 4) as usize != 0 {
-                data.push(0u8)
+            SessionArgs::Broadcast { args, .. } => {
+                // Every invocation has the same inputs, so each input is encoded once and its bytes repeated
+                for input in args {
+                    let mut input_data = Vec::new();
+                    Self::encode_input(input, tuneables, &mut input_data);
+                    data.append(&mut input_data.repeat(instances.len()));
+                }
             }
         }
+
+        return data;
+    }
+
+    async fn make_inputs(
+        args: &SessionArgs,
+        instances: Range<usize>,
+        tuneables: &Tuneables,
+        device: &AsyncDevice,
+        label: &str,
+    ) -> Result<AsyncBuffer, OutOfMemoryError> {
+        let mut data = Self::encode_inputs(args, instances, tuneables);
         // Pad out
         while data.len() < 128 {
             data.push(0u8)
@@ -167,24 +286,33 @@ impl<'a> Session<'a> {
         Ok(input_buffer)
     }
 
-    fn output_instance_len<'b>(output_tys: impl IntoIterator<Item = &'b ValType>) -> u64 {
-        let output_length: u64 = output_tys
+    /// The number of bytes taken by the inputs or outputs of a single invocation
+    fn io_instance_len<'b>(
+        io_tys: impl IntoIterator<Item = &'b ValType>,
+        tuneables: &Tuneables,
+    ) -> u64 {
+        let output_length: u64 = io_tys
             .into_iter()
             .map(|res| {
                 let bs = u64::from(res.byte_count());
-                bs.next_multiple_of(u64::from(IO_ARGUMENT_ALIGNMENT_WORDS * 4))
+                bs.next_multiple_of(u64::from(tuneables.io_argument_alignment_words * 4))
             })
             .sum();
-        output_length.next_multiple_of(u64::from(IO_INVOCATION_ALIGNMENT_WORDS * 4))
+        match tuneables.io_layout {
+            IoLayout::Packed => output_length
+                .next_multiple_of(u64::from(tuneables.io_invocation_alignment_words * 4)),
+            IoLayout::StructOfArrays => output_length,
+        }
     }
 
     async fn make_output<'b>(
         instances_count: usize,
         output_tys: impl IntoIterator<Item = &'b ValType>,
+        tuneables: &Tuneables,
         memory_system: &MemorySystem,
         label: &str,
     ) -> Result<UnmappedLazyBuffer, OutOfMemoryError> {
-        let output_length = Self::output_instance_len(output_tys);
+        let output_length = Self::io_instance_len(output_tys, tuneables);
         let output_length =
             instances_count * usize::try_from(output_length).expect("that's a big type");
         let output_length = usize::max(output_length, 128);
@@ -201,19 +329,56 @@ impl<'a> Session<'a> {
             .await
     }
 
+    /// Without disjoint memory, every invocation shares the memory of the first instance.
+    pub(crate) fn memory_instance_index(tuneables: &Tuneables, instance_index: usize) -> usize {
+        if tuneables.disjoint_memory {
+            instance_index
+        } else {
+            0
+        }
+    }
+
+    /// The flags of each invocation before it is run, holding no trap, the current size of its memory
+    /// and the data and element segments it has dropped.
+    fn encode_flags(
+        owned: &UnmappedStoreSetData,
+        tuneables: &Tuneables,
+        instances: Range<usize>,
+    ) -> Vec<u8> {
+        let flags_words = (FLAGS_LEN_BYTES / 4) as usize;
+        let mut data = Vec::new();
+        for instance_index in instances {
+            let mut flags = vec![0u32; flags_words];
+            flags[MEMORY_PAGES_FLAG_INDEX as usize] = owned
+                .memories
+                .pages(Self::memory_instance_index(tuneables, instance_index));
+            let data_dropped_flags = DATA_DROPPED_FLAG_INDEX as usize
+                ..(DATA_DROPPED_FLAG_INDEX + DATA_DROPPED_FLAG_WORDS) as usize;
+            flags[data_dropped_flags].copy_from_slice(&owned.data_dropped.get(instance_index));
+            let element_dropped_flags = ELEMENT_DROPPED_FLAG_INDEX as usize
+                ..(ELEMENT_DROPPED_FLAG_INDEX + ELEMENT_DROPPED_FLAG_WORDS) as usize;
+            flags[element_dropped_flags]
+                .copy_from_slice(&owned.element_dropped.get(instance_index));
+            for flag in flags {
+                data.extend_from_slice(&u32::to_le_bytes(flag));
+            }
+        }
+
+        return data;
+    }
+
     async fn make_flags(
-        instances_count: usize,
+        flags: &[u8],
         memory_system: &MemorySystem,
         label: &str,
     ) -> Result<UnmappedLazyBuffer, OutOfMemoryError> {
-        let flags_length = instances_count
-            * usize::try_from(FLAGS_LEN_BYTES).expect("that's a very big wasm module");
+        let flags_length = flags.len();
 
         memory_system
             .try_create_device_memory_block(&MemoryBlockConfig {
                 usages: BufferUsages::STORAGE,
                 size: flags_length,
-                initial_data: None,
+                initial_data: Some(flags),
                 locking_size: flags_length,
                 transfer_size: 4096,
                 label,
@@ -236,140 +401,135 @@ impl<'a> Session<'a> {
             .await
     }
 
-    async fn make_constants(
+    async fn make_packed_io<'b>(
+        args: &SessionArgs,
+        instances: Range<usize>,
+        flags: &[u8],
+        input_tys: impl IntoIterator<Item = &'b ValType>,
+        output_tys: impl IntoIterator<Item = &'b ValType>,
+        tuneables: &Tuneables,
         device: &AsyncDevice,
         label: &str,
         count: u32,
-    ) -> Result<AsyncBuffer, OutOfMemoryError> {
+    ) -> Result<(AsyncBuffer, PackedIoLayout), OutOfMemoryError> {
+        let input_words = Self::io_instance_len(input_tys, tuneables) / 4;
+        let output_words = Self::io_instance_len(output_tys, tuneables) / 4;
+        let layout = PackedIoLayout::new(
+            count,
+            u32::try_from(input_words).expect("that's a big type"),
+            u32::try_from(output_words).expect("that's a big type"),
+        );
+
+        let mut data = vec![0u8; layout.len_words as usize * 4];
+
+        let count_loc =
+            (layout.constants_offset_words + TOTAL_INVOCATIONS_CONSTANT_INDEX) as usize * 4;
+        data[count_loc..count_loc + 4].copy_from_slice(&u32::to_le_bytes(count));
+
+        let flags_loc = layout.flags_offset_words as usize * 4;
+        data[flags_loc..flags_loc + flags.len()].copy_from_slice(flags);
+
+        let inputs = Self::encode_inputs(args, instances, tuneables);
+        let input_loc = layout.input_offset_words as usize * 4;
+        data[input_loc..input_loc + inputs.len()].copy_from_slice(&inputs);
+
         let buffer = device
             .create_buffer(&wgpu::BufferDescriptor {
                 label: Some(label),
-                size: CONSTANTS_LEN_BYTES as u64,
-                usage: BufferUsages::STORAGE,
+                size: data.len() as u64,
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
                 mapped_at_creation: true,
             })
             .await?;
 
-        // Write values
-        let count_loc = TOTAL_INVOCATIONS_CONSTANT_INDEX as u64 * 4;
         buffer
-            .slice(count_loc..count_loc + 4)
+            .slice(..)
             .get_mapped_range_mut()
-            .copy_from_slice(&u32::to_le_bytes(count));
+            .copy_from_slice(&data);
 
         buffer.unmap();
 
-        return Ok(buffer);
+        return Ok((buffer, layout));
     }
 
-    async fn extract_output(
-        ret_ty: Vec<ValType>,
-        len: usize,
-        flags: UnmappedLazyBuffer,
-        output: UnmappedLazyBuffer,
+    /// Copies the flags and outputs of a packed dispatch into the buffers that results are read from.
+    fn unpack_io(
         queue: &AsyncQueue,
-    ) -> OutputType {
-        let mut results = Vec::new();
-
-        let flags = flags.map_lazy();
-        let output = output.map_lazy();
-
-        let mut flags_lock_collection = LockCollection::empty();
-        let mut output_lock_collection = LockCollection::empty();
-
-        flags.lock_reading(.., &mut flags_lock_collection).await;
-        output.lock_reading(.., &mut output_lock_collection).await;
-
-        let flags_len = usize::try_from(FLAGS_LEN_BYTES).expect("flags len is set at compile time");
-        let output_len = usize::try_from(Self::output_instance_len(&ret_ty))
-            .expect("instances output must fit in memory");
-
-        for i in 0..len {
-            let flags_offset = flags_len * i;
-            let mut output_offset = output_len * i;
-
-            // Extract trap flag
-            let trap_flag_offset = usize::try_from(TRAP_FLAG_INDEX)
-                .expect("trap flag is set at compile time to be small")
-                + flags_offset;
-            let trap_bytes = &flags
-                .try_read_slice_with_locks(
-                    &queue,
-                    trap_flag_offset..trap_flag_offset + 4,
-                    &flags_lock_collection,
-                )
-                .await?;
-            let trap_bytes =
-                <[u8; 4]>::try_from(trap_bytes.as_slice()).expect("there are 4 bytes to a u32");
-            let trap_word = u32::from_le_bytes(trap_bytes);
+        packed: &wgpu::Buffer,
+        layout: &PackedIoLayout,
+        flags: &wgpu::Buffer,
+        output: &wgpu::Buffer,
+    ) -> WgpuFuture<()> {
+        let mut encoder = queue
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
-            let trap = u32_to_trap(trap_word);
+        Self::encode_unpack_io(&mut encoder, packed, layout, flags, output);
 
-            if let Some(trap) = trap {
-                results.push(Err(trap));
-                continue;
-            }
+        queue.submit([encoder.finish()])
+    }
 
-            let mut result_values = Vec::new();
-            for ty in &ret_ty {
-                let byte_count = ty.byte_count() as usize;
-                let result_bytes = &output
-                    .try_read_slice_with_locks(
-                        &queue,
-                        output_offset..output_offset + byte_count,
-                        &output_lock_collection,
-                    )
-                    .await?;
+    fn encode_unpack_io(
+        encoder: &mut wgpu::CommandEncoder,
+        packed: &wgpu::Buffer,
+        layout: &PackedIoLayout,
+        flags: &wgpu::Buffer,
+        output: &wgpu::Buffer,
+    ) {
+        let flags_len = u64::from(layout.input_offset_words - layout.flags_offset_words) * 4;
+        if flags_len > 0 {
+            let flags_loc = u64::from(layout.flags_offset_words) * 4;
+            encoder.copy_buffer_to_buffer(packed, flags_loc, flags, 0, flags_len);
+        }
 
-                output_offset +=
-                    byte_count.next_multiple_of(IO_ARGUMENT_ALIGNMENT_WORDS as usize * 4);
+        let output_len = u64::from(layout.stack_offset_words - layout.output_offset_words) * 4;
+        if output_len > 0 {
+            let output_loc = u64::from(layout.output_offset_words) * 4;
+            encoder.copy_buffer_to_buffer(packed, output_loc, output, 0, output_len);
+        }
+    }
 
-                let ret = ty.try_from_bytes(result_bytes).expect(&format!(
-                    "returned value was not a valid {:?}, with bytes {:?}",
-                    ty, result_bytes
-                ));
+    async fn make_constants(
+        device: &AsyncDevice,
+        label: &str,
+        count: u32,
+    ) -> Result<AsyncBuffer, OutOfMemoryError> {
+        let buffer = device
+            .create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: CONSTANTS_LEN_BYTES as u64,
+                usage: BufferUsages::STORAGE,
+                mapped_at_creation: true,
+            })
+            .await?;
 
-                result_values.push(ret);
-            }
-            results.push(Ok(result_values));
-        }
+        // Write values
+        let count_loc = TOTAL_INVOCATIONS_CONSTANT_INDEX as u64 * 4;
+        buffer
+            .slice(count_loc..count_loc + 4)
+            .get_mapped_range_mut()
+            .copy_from_slice(&u32::to_le_bytes(count));
 
-        return Ok(results);
+        buffer.unmap();
+
+        return Ok(buffer);
     }
 
-    pub async fn run(
-        self,
+    /// Creates the buffers for every batch of invocations of a function on the instances of a store set.
+    async fn prepare_batches(
+        stores: &DeviceStoreSet,
+        entry_func: &UntypedFuncPtr,
+        args: &SessionArgs,
         memory_system: &MemorySystem,
         queue: &AsyncQueue,
-    ) -> Result<BoxFuture<'a, OutputType>, OutOfMemoryError> {
-        let Self {
-            stores,
-            entry_func,
-            args,
-        } = self;
-
-        let StoreSet {
-            label,
-            functions: _,
-            elements,
-            datas,
-            immutable_globals,
-            shader_module,
-            owned,
-            tuneables: _,
-        } = stores;
-
-        let owned_queue = queue.clone();
-        let ret_ty: Vec<_> = entry_func
-            .ty()
-            .results()
-            .iter()
-            .map(ValType::clone)
-            .collect();
+    ) -> Result<Vec<PreparedBatch>, OutOfMemoryError> {
+        let label = &stores.label;
+        let owned = &stores.owned;
+        let tuneables = stores.tuneables;
 
         // Dispatch and be ready to parse results
         let total_invocation_count =
-            f32::ceil(args.len() as f32 / wasm_gpu_funcgen::WORKGROUP_SIZE as f32) as u32;
+            f32::ceil(args.len() as f32 / tuneables.workgroup_size as f32) as u32;
 
         let max_invocations = queue
             .device()
@@ -380,113 +540,1279 @@ impl<'a> Session<'a> {
             "device must be able to invoke compute shaders"
         );
 
-        let mut invocations = Vec::new();
+        let mut batches = Vec::new();
         for i_invocation in (0..total_invocation_count).step_by(max_invocations as usize) {
             let dispatch_count = u32::min(total_invocation_count - i_invocation, max_invocations);
 
-            let args_start = i_invocation as usize * wasm_gpu_funcgen::WORKGROUP_SIZE as usize;
-            let args_count = dispatch_count as usize * wasm_gpu_funcgen::WORKGROUP_SIZE as usize;
+            let args_start = i_invocation as usize * tuneables.workgroup_size as usize;
+            let args_count = dispatch_count as usize * tuneables.workgroup_size as usize;
             let args_end = args_start + args_count;
             let args_end = usize::min(args_end, args.len());
             let args_count = args_end - args_start;
-            let args = &args[args_start..args_end];
 
-            let input =
-                Self::make_inputs(args, queue.device(), &format!("{}_input_buffer", label)).await?;
             let output = Self::make_output(
                 args_count,
                 entry_func.ty().results(),
+                &tuneables,
                 memory_system,
                 &format!("{}_output_buffer", label),
             )
             .await?;
+            let initial_flags = Self::encode_flags(owned, &tuneables, args_start..args_end);
             let flags = Self::make_flags(
-                args_count,
+                &initial_flags,
                 memory_system,
                 &format!("{}_flags_buffer", label),
             )
             .await?;
-            let constants = Self::make_constants(
-                queue.device(),
-                &format!("{}_constants_buffer", label),
-                u32::try_from(args_count).map_err(|source| OutOfMemoryError {
-                    source: Box::new(source),
-                })?,
-            )
-            .await?;
+            let count = u32::try_from(args_count).map_err(|source| OutOfMemoryError {
+                source: Box::new(source),
+            })?;
 
-            let stack = Self::make_stack(
-                STACK_LEN_BYTES.into(),
-                queue.device(),
-                &format!("{}_stack_buffer", label),
-            )
-            .await?;
+            let io = if tuneables.pack_io_bindings {
+                let (buffer, layout) = Self::make_packed_io(
+                    args,
+                    args_start..args_end,
+                    &initial_flags,
+                    entry_func.ty().params(),
+                    entry_func.ty().results(),
+                    &tuneables,
+                    queue.device(),
+                    &format!("{}_packed_io_buffer", label),
+                    count,
+                )
+                .await?;
+                IoBuffers::Packed { buffer, layout }
+            } else {
+                let input = Self::make_inputs(
+                    args,
+                    args_start..args_end,
+                    &tuneables,
+                    queue.device(),
+                    &format!("{}_input_buffer", label),
+                )
+                .await?;
+                let constants = Self::make_constants(
+                    queue.device(),
+                    &format!("{}_constants_buffer", label),
+                    count,
+                )
+                .await?;
+                let stack = Self::make_stack(
+                    STACK_LEN_BYTES.into(),
+                    queue.device(),
+                    &format!("{}_stack_buffer", label),
+                )
+                .await?;
+                IoBuffers::Separate {
+                    input,
+                    constants,
+                    stack,
+                }
+            };
 
-            invocations.push((
-                input,
+            batches.push(PreparedBatch {
+                io,
                 flags,
-                constants,
                 output,
-                stack,
                 dispatch_count,
-                args_count,
-            ));
+                instances: args_start..args_end,
+            });
         }
 
-        let future = (async move {
-            // Since we've gone to the effort of creating state buffers for each invocation, we might as well run all invocations at once.
-            let mut futures = Vec::new();
-            for (input, flags, constants, output, stack, dispatch_count, args_count) in invocations
-            {
-                let bindings = Bindings {
-                    data: datas.buffer(),
-                    element: elements.buffer(),
-                    immutable_globals: immutable_globals.buffer(),
-                    mutable_globals: owned.mutable_globals.buffer(),
-                    memory: owned.memories.buffer(),
-                    table: owned.tables.buffer(),
-                    flags: &flags,
-                    input: &input,
-                    output: &output,
-                    stack: &stack,
-                    constants: &constants,
-                    empty_bindings: elsa::FrozenVec::new(),
-                };
-
-                let queue_ref = &owned_queue;
-                let ret_ty = ret_ty.clone();
-                let future = shader_module
+        return Ok(batches);
+    }
+
+    /// Creates the buffers for every batch of invocations, returning futures that each dispatch a batch
+    /// and complete once it has finished executing, along with a reader for the results of the batches.
+    async fn dispatch(
+        self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+    ) -> Result<(Vec<BoxFuture<'a, CompletedBatch>>, ResultsReader<'a>), OutOfMemoryError> {
+        let Self {
+            stores,
+            entry_func,
+            args,
+        } = self;
+        let stores: &'a DeviceStoreSet = stores;
+
+        let prepared =
+            Self::prepare_batches(stores, &entry_func, &args, memory_system, queue).await?;
+
+        // Since we've gone to the effort of creating state buffers for each invocation, we might as well run all invocations at once.
+        let mut batches = Vec::new();
+        for batch in prepared {
+            let func_ref = entry_func.to_func_ref();
+            let queue = queue.clone();
+
+            let batch = async move {
+                stores
+                    .shader_module
                     .run_pipeline_for_fn(
-                        &owned_queue,
-                        entry_func.to_func_ref(),
-                        bindings,
-                        dispatch_count,
+                        &queue,
+                        func_ref,
+                        batch.bindings(stores),
+                        batch.dispatch_count,
                         1,
                         1,
                     )
-                    .then(move |_| {
-                        Self::extract_output(ret_ty, args_count, flags, output, queue_ref)
-                    });
+                    .await;
 
-                futures.push(future.boxed());
-            }
+                if let IoBuffers::Packed { buffer, layout } = &batch.io {
+                    Self::unpack_io(&queue, buffer, layout, &batch.flags, &batch.output).await;
+                }
+
+                batch.completed()
+            };
+
+            batches.push(batch.boxed());
+        }
+
+        let reader = ResultsReader::new(stores, &entry_func, queue);
 
-            join_all(futures)
-                .await
-                .into_iter()
-                .fold(Ok(Vec::new()), |lhs, rhs| {
-                    // Join in results, preserving joint error state
-                    lhs.and_then(|mut res| match rhs {
-                        Err(e) => Err(e),
-                        Ok(mut vals) => {
-                            res.append(&mut vals);
-                            Ok(res)
-                        }
-                    })
+        return Ok((batches, reader));
+    }
+
+    pub async fn run(
+        self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+    ) -> Result<BoxFuture<'a, OutputType>, OutOfMemoryError> {
+        let (batches, reader) = self.dispatch(memory_system, queue).await?;
+
+        let future = (async move {
+            let reader = &reader;
+            join_all(
+                batches
+                    .into_iter()
+                    .map(|batch| batch.then(|batch| reader.read_batch(batch))),
+            )
+            .await
+            .into_iter()
+            .fold(Ok(Vec::new()), |lhs, rhs| {
+                // Join in results, preserving joint error state
+                lhs.and_then(|mut res| match rhs {
+                    Err(e) => Err(e),
+                    Ok(mut vals) => {
+                        res.append(&mut vals);
+                        Ok(res)
+                    }
                 })
+            })
         })
         .boxed();
 
         return Ok(future);
     }
+
+    /// Like `run`, but yields the result of each invocation, along with its instance index, as soon as
+    /// it has been downloaded rather than collecting every result before returning. Results are yielded
+    /// in the order that their batches finish executing.
+    pub async fn run_streaming(
+        self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+    ) -> Result<BoxStream<'a, StreamedOutputType>, OutOfMemoryError> {
+        let (batches, reader) = self.dispatch(memory_system, queue).await?;
+
+        let stream = batches
+            .into_iter()
+            .collect::<FuturesUnordered<_>>()
+            .flat_map(move |batch| reader.clone().stream_batch(batch))
+            .boxed();
+
+        return Ok(stream);
+    }
+}
+
+/// Records a sequence of calls on the instances of a store set, which are then submitted to the gpu together
+/// rather than waiting for each call to finish before dispatching the next. Each call sees the state left
+/// by the calls before it, as though the calls had been made one after another.
+pub struct SessionBuilder<'a> {
+    stores: &'a mut DeviceStoreSet,
+    calls: Vec<(UntypedFuncPtr, SessionArgs)>,
+}
+
+impl<'a> SessionBuilder<'a> {
+    pub fn new(stores: &'a mut DeviceStoreSet) -> Self {
+        Self {
+            stores,
+            calls: Vec::new(),
+        }
+    }
+
+    /// Records a call of a function on the first `args.len()` instances, after any calls already recorded.
+    ///
+    /// # Panics
+    /// The session panics when run if:
+    ///  - this function is not in the store set
+    ///  - the arguments given don't match the arguments that the function takes
+    pub fn call_all(
+        mut self,
+        func: &UntypedFuncPtr,
+        args: impl IntoIterator<Item = Vec<Val>>,
+    ) -> Self {
+        let args = SessionArgs::PerInvocation(args.into_iter().collect());
+        self.calls.push((func.clone(), args));
+        self
+    }
+
+    /// As with `call_all`, but for a function with known types.
+    ///
+    /// # Panics
+    /// The session panics when run if this function is not in the store set
+    pub fn call_all_typed<Params: WasmTyVec, Results: WasmTyVec>(
+        self,
+        func: &TypedFuncPtr<Params, Results>,
+        args: impl IntoIterator<Item = Params>,
+    ) -> Self {
+        self.call_all(
+            &func.as_untyped(),
+            args.into_iter().map(|args| args.to_val_vec()),
+        )
+    }
+
+    /// Submits every recorded call to the gpu in a single submission, returning a future which gives
+    /// the results of each call once they have all finished executing.
+    pub async fn run(
+        self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+    ) -> Result<BoxFuture<'a, PipelinedOutputType>, OutOfMemoryError> {
+        let Self { stores, calls } = self;
+        let stores: &'a DeviceStoreSet = stores;
+
+        let mut prepared = Vec::new();
+        for (func, args) in &calls {
+            prepared
+                .push(Session::prepare_batches(stores, func, args, memory_system, queue).await?);
+        }
+
+        // wgpu places barriers between the passes of an encoder that use the same buffers, so each
+        // call sees the writes of the calls before it.
+        let device = queue.device();
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        for (i_call, (func, _)) in calls.iter().enumerate() {
+            for (i_batch, batch) in prepared[i_call].iter().enumerate() {
+                // Every call splits its invocations into batches in the same way, so batches with the same
+                // index start at the same instance. The latest flags of each instance are left by the most recent
+                // call that ran it, which is the previous call unless that call ran fewer instances.
+                let earlier = prepared[..i_call]
+                    .iter()
+                    .rev()
+                    .filter_map(|earlier| earlier.get(i_batch));
+                batch.encode_copy_flags_from(&mut encoder, earlier);
+
+                stores.shader_module.encode_pipeline_for_fn(
+                    device,
+                    &mut encoder,
+                    func.to_func_ref(),
+                    batch.bindings(stores),
+                    batch.dispatch_count,
+                    1,
+                    1,
+                );
+
+                if let IoBuffers::Packed { buffer, layout } = &batch.io {
+                    Session::encode_unpack_io(
+                        &mut encoder,
+                        buffer,
+                        layout,
+                        &batch.flags,
+                        &batch.output,
+                    );
+                }
+            }
+        }
+        let submission = queue.submit([encoder.finish()]);
+
+        let queue = queue.clone();
+        let future: BoxFuture<'a, PipelinedOutputType> = (async move {
+            submission.await;
+
+            // Results are decoded in call order, so that the state left by the last call persists
+            let mut results = Vec::new();
+            for ((func, _), batches) in calls.iter().zip(prepared) {
+                let reader = ResultsReader::new(stores, func, &queue);
+                let mut call_results = Vec::new();
+                for batch in batches {
+                    call_results.append(&mut reader.read_batch(batch.completed()).await?);
+                }
+                results.push(call_results);
+            }
+
+            Ok(results)
+        })
+        .boxed();
+
+        return Ok(future);
+    }
+}
+
+/// The buffers of a batch of invocations which are ready to be dispatched
+struct PreparedBatch {
+    io: IoBuffers,
+    flags: UnmappedLazyBuffer,
+    output: UnmappedLazyBuffer,
+    dispatch_count: u32,
+    instances: Range<usize>,
+}
+
+impl PreparedBatch {
+    fn bindings<'b>(&'b self, stores: &'b DeviceStoreSet) -> Bindings<'b> {
+        match &self.io {
+            IoBuffers::Separate {
+                input,
+                constants,
+                stack,
+            } => Bindings {
+                data: stores.datas.buffer(),
+                element: stores.elements.buffer(),
+                immutable_globals: stores.immutable_globals.buffer(),
+                mutable_globals: stores.owned.mutable_globals.buffer(),
+                memory: stores.owned.memories.buffer(),
+                table: stores.owned.tables.buffer(),
+                flags: &self.flags,
+                input,
+                output: &self.output,
+                stack,
+                constants,
+                packed_io: false,
+                empty_bindings: elsa::FrozenVec::new(),
+            },
+            IoBuffers::Packed { buffer, .. } => Bindings {
+                data: stores.datas.buffer(),
+                element: stores.elements.buffer(),
+                immutable_globals: stores.immutable_globals.buffer(),
+                mutable_globals: stores.owned.mutable_globals.buffer(),
+                memory: stores.owned.memories.buffer(),
+                table: stores.owned.tables.buffer(),
+                flags: buffer,
+                input: buffer,
+                output: buffer,
+                stack: buffer,
+                constants: buffer,
+                packed_io: true,
+                empty_bindings: elsa::FrozenVec::new(),
+            },
+        }
+    }
+
+    /// Overwrites the initial flags of this batch with the flags left by batches of earlier calls, given from
+    /// the most recent, so that memory growth and dropped data segments carry over between the calls of a
+    /// pipeline. Each instance's flags are copied from the first batch that ran it, as one copy per batch. The
+    /// trap flag is copied too, but the shader never reads it and overwrites it once each invocation finishes,
+    /// so a trap in one call isn't reported by every call after it.
+    fn encode_copy_flags_from<'b>(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        earlier: impl IntoIterator<Item = &'b PreparedBatch>,
+    ) {
+        let destination: &wgpu::Buffer = match &self.io {
+            IoBuffers::Separate { .. } => &self.flags,
+            IoBuffers::Packed { buffer, .. } => buffer,
+        };
+        let flags_loc = match &self.io {
+            IoBuffers::Separate { .. } => 0,
+            IoBuffers::Packed { layout, .. } => u64::from(layout.flags_offset_words) * 4,
+        };
+
+        let mut copied = 0;
+        for earlier in earlier {
+            debug_assert_eq!(self.instances.start, earlier.instances.start);
+
+            let instances = usize::min(self.instances.len(), earlier.instances.len());
+            if instances > copied {
+                let offset = copied as u64 * u64::from(FLAGS_LEN_BYTES);
+                let len = (instances - copied) as u64 * u64::from(FLAGS_LEN_BYTES);
+                encoder.copy_buffer_to_buffer(
+                    &earlier.flags,
+                    offset,
+                    destination,
+                    flags_loc + offset,
+                    len,
+                );
+                copied = instances;
+            }
+
+            if copied == self.instances.len() {
+                break;
+            }
+        }
+    }
+
+    fn completed(self) -> CompletedBatch {
+        CompletedBatch {
+            instances: self.instances,
+            flags: self.flags,
+            output: self.output,
+        }
+    }
+}
+
+/// The flags and outputs of a batch of invocations which have finished executing
+struct CompletedBatch {
+    instances: Range<usize>,
+    flags: UnmappedLazyBuffer,
+    output: UnmappedLazyBuffer,
+}
+
+/// Decodes the flags and outputs of completed invocations, updating the parts of the store set
+/// which persist between calls.
+#[derive(Clone)]
+struct ResultsReader<'a> {
+    ret_ty: Vec<ValType>,
+    tuneables: Tuneables,
+    owned: &'a UnmappedStoreSetData,
+    queue: AsyncQueue,
+}
+
+impl<'a> ResultsReader<'a> {
+    fn new(stores: &'a DeviceStoreSet, entry_func: &UntypedFuncPtr, queue: &AsyncQueue) -> Self {
+        Self {
+            ret_ty: entry_func.ty().results().to_vec(),
+            tuneables: stores.tuneables,
+            owned: &stores.owned,
+            queue: queue.clone(),
+        }
+    }
+
+    fn flags_len(&self) -> usize {
+        usize::try_from(FLAGS_LEN_BYTES).expect("flags len is set at compile time")
+    }
+
+    fn output_len(&self) -> usize {
+        usize::try_from(Session::io_instance_len(&self.ret_ty, &self.tuneables))
+            .expect("instances output must fit in memory")
+    }
+
+    /// The byte ranges within the output buffer of a batch of `batch_len` invocations that hold each of the
+    /// results of the `i`th invocation of the batch.
+    fn output_value_ranges(&self, i: usize, batch_len: usize) -> Vec<Range<usize>> {
+        let argument_alignment = self.tuneables.io_argument_alignment_words as usize * 4;
+        let output_len = self.output_len();
+
+        let mut value_offset = 0;
+        let mut ranges = Vec::new();
+        for ty in &self.ret_ty {
+            let byte_count = ty.byte_count() as usize;
+            let value_len = byte_count.next_multiple_of(argument_alignment);
+            let start = match self.tuneables.io_layout {
+                IoLayout::Packed => output_len * i + value_offset,
+                IoLayout::StructOfArrays => value_offset * batch_len + value_len * i,
+            };
+            ranges.push(start..start + byte_count);
+
+            value_offset += value_len;
+        }
+
+        return ranges;
+    }
+
+    /// Decodes the result of a single invocation from its flags and the bytes of each of its results.
+    fn decode(
+        &self,
+        instance_index: usize,
+        flags: &[u8],
+        results: &[impl AsRef<[u8]>],
+    ) -> Result<Vec<Val>, wasmtime_environ::Trap> {
+        let flag = |index: u32| {
+            let offset = index as usize * 4;
+            let bytes = <[u8; 4]>::try_from(&flags[offset..offset + 4])
+                .expect("there are 4 bytes to a u32");
+            u32::from_le_bytes(bytes)
+        };
+
+        // Memory growth persists, even if the invocation trapped
+        self.owned.memories.set_pages(
+            Session::memory_instance_index(&self.tuneables, instance_index),
+            flag(MEMORY_PAGES_FLAG_INDEX),
+        );
+
+        // As do dropped data and element segments
+        let data_dropped = (DATA_DROPPED_FLAG_INDEX
+            ..DATA_DROPPED_FLAG_INDEX + DATA_DROPPED_FLAG_WORDS)
+            .map(flag)
+            .collect::<Vec<_>>();
+        self.owned.data_dropped.set(instance_index, &data_dropped);
+        let element_dropped = (ELEMENT_DROPPED_FLAG_INDEX
+            ..ELEMENT_DROPPED_FLAG_INDEX + ELEMENT_DROPPED_FLAG_WORDS)
+            .map(flag)
+            .collect::<Vec<_>>();
+        self.owned
+            .element_dropped
+            .set(instance_index, &element_dropped);
+
+        // And the trap, so that it can be queried without holding onto the results
+        let trap_flag = flag(TRAP_FLAG_INDEX);
+        self.owned.traps.set(instance_index, trap_flag);
+
+        if let Some(trap) = u32_to_trap(trap_flag) {
+            return Err(trap);
+        }
+
+        let mut result_values = Vec::new();
+        for (ty, result_bytes) in self.ret_ty.iter().zip(results) {
+            let result_bytes = result_bytes.as_ref();
+            let ret = ty.try_from_bytes(result_bytes).expect(&format!(
+                "returned value was not a valid {:?}, with bytes {:?}",
+                ty, result_bytes
+            ));
+
+            result_values.push(ret);
+        }
+
+        return Ok(result_values);
+    }
+
+    /// Downloads the flags and outputs of a whole batch, then decodes every result.
+    async fn read_batch(&self, batch: CompletedBatch) -> OutputType {
+        let flags = batch
+            .flags
+            .map_lazy()
+            .try_read_slice_locking(&self.queue, ..)
+            .await?;
+        let output = batch
+            .output
+            .map_lazy()
+            .try_read_slice_locking(&self.queue, ..)
+            .await?;
+
+        let flags_len = self.flags_len();
+        let batch_len = batch.instances.len();
+
+        let results = batch
+            .instances
+            .enumerate()
+            .map(|(i, instance_index)| {
+                let results = self
+                    .output_value_ranges(i, batch_len)
+                    .into_iter()
+                    .map(|range| &output[range])
+                    .collect::<Vec<_>>();
+                self.decode(
+                    instance_index,
+                    &flags[flags_len * i..flags_len * (i + 1)],
+                    &results,
+                )
+            })
+            .collect();
+
+        return Ok(results);
+    }
+
+    /// Downloads and decodes the result of each invocation of a batch in turn, so that results can be
+    /// processed as they arrive without holding the whole batch in host memory.
+    fn stream_batch(self, batch: CompletedBatch) -> BoxStream<'a, StreamedOutputType> {
+        let batch_len = batch.instances.len();
+        let state = (
+            self,
+            batch.flags.map_lazy(),
+            batch.output.map_lazy(),
+            batch
+                .instances
+                .enumerate()
+                .map(move |(i, instance_index)| (i, instance_index, batch_len)),
+        );
+
+        futures::stream::unfold(state, |(reader, flags, output, mut instances)| async move {
+            let (i, instance_index, batch_len) = instances.next()?;
+
+            let flags_len = reader.flags_len();
+            let result: StreamedOutputType = async {
+                let flags_bytes = flags
+                    .try_read_slice_locking(&reader.queue, flags_len * i..flags_len * (i + 1))
+                    .await?;
+                let mut results = Vec::new();
+                for range in reader.output_value_ranges(i, batch_len) {
+                    results.push(output.try_read_slice_locking(&reader.queue, range).await?);
+                }
+
+                Ok((
+                    instance_index,
+                    reader.decode(instance_index, &flags_bytes, &results),
+                ))
+            }
+            .await;
+
+            Some((result, (reader, flags, output, instances)))
+        })
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Session, SessionArgs, SessionBuilder};
+    use crate::unit_tests_lib::{get_backend, get_limited_backend};
+    use crate::{imports, MappedStoreSetBuilder};
+    use futures::StreamExt;
+    use wasm_gpu_funcgen::{IoLayout, Tuneables};
+    use wasm_types::Val;
+    use wasmparser::ValType;
+
+    /// A host structure laid out the same as a single invocation's results with 16-byte invocation alignment
+    #[repr(C, align(16))]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct AlignedResults {
+        v1: i32,
+        v2: f32,
+    }
+
+    fn aligned_tuneables() -> Tuneables {
+        Tuneables {
+            io_invocation_alignment_words: 4,
+            ..Tuneables::default()
+        }
+    }
+
+    #[test]
+    fn test_output_instance_len_matches_aligned_host_layout() {
+        let tuneables = aligned_tuneables();
+
+        let len = Session::io_instance_len(&[ValType::I32, ValType::F32], &tuneables);
+
+        assert_eq!(len, std::mem::size_of::<AlignedResults>() as u64);
+        assert_eq!(len, 16);
+    }
+
+    #[tokio::test]
+    async fn test_results_decode_with_16_byte_invocation_alignment() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", aligned_tuneables());
+
+        let wat = r#"
+            (module
+                (func $f (param i32) (result i32 f32)
+                    (local.get 0)
+                    (i32.const 3)
+                    (i32.mul)
+                    (local.get 0)
+                    (f32.convert_i32_s)
+                )
+                (export "f" (func $f))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instances = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let target = instances
+            .get_func("f")
+            .unwrap()
+            .try_typed::<i32, (i32, f32)>()
+            .unwrap();
+
+        let store_source = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let mut stores = store_source
+            .build(&memory_system, &queue, 8)
+            .await
+            .expect("could not build stores");
+
+        let inputs: Vec<i32> = (0..8).collect();
+        let results = target
+            .call_all(&memory_system, &queue, &mut stores, inputs.clone())
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+
+        assert_eq!(results.len(), inputs.len());
+        for (input, result) in inputs.into_iter().zip(results) {
+            let (v1, v2) = result.expect("function does not trap");
+            assert_eq!(
+                AlignedResults { v1, v2 },
+                AlignedResults {
+                    v1: input * 3,
+                    v2: input as f32
+                }
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_packed_bindings_run_with_8_storage_buffers() {
+        let (memory_system, queue) = get_limited_backend();
+        assert!(queue.device().limits().max_storage_buffers_per_shader_stage <= 8);
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Tuneables::default());
+
+        let wat = r#"
+            (module
+                (func $f (param i32 i32) (result i32)
+                    (local.get 0)
+                    (local.get 1)
+                    (i32.sub)
+                )
+                (func $trap (param i32) (result i32)
+                    (unreachable)
+                )
+                (export "f" (func $f))
+                (export "trap" (func $trap))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instances = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let f = instances
+            .get_func("f")
+            .unwrap()
+            .try_typed::<(i32, i32), i32>()
+            .unwrap();
+        let trap = instances
+            .get_func("trap")
+            .unwrap()
+            .try_typed::<i32, i32>()
+            .unwrap();
+
+        let completed = stores_builder
+            .complete(&queue)
+            .await
+            .expect("module should build by packing bindings");
+        let bound_globals = completed
+            .get_module()
+            .module
+            .global_variables
+            .iter()
+            .filter(|(_, global)| global.binding.is_some())
+            .count();
+        assert!(bound_globals <= 8);
+
+        let mut stores = completed
+            .build(&memory_system, &queue, 8)
+            .await
+            .expect("could not build stores");
+
+        let inputs: Vec<(i32, i32)> = (0..8).map(|i| (i * 5, i)).collect();
+        let results = f
+            .call_all(&memory_system, &queue, &mut stores, inputs.clone())
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(results.len(), inputs.len());
+        for ((lhs, rhs), result) in inputs.into_iter().zip(results) {
+            assert_eq!(result.expect("function does not trap"), lhs - rhs);
+        }
+
+        let results = trap
+            .call_all(&memory_system, &queue, &mut stores, vec![0; 8])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(results.len(), 8);
+        for result in results {
+            assert!(
+                result.is_err(),
+                "trap flags should be read back from the packed buffer"
+            );
+        }
+    }
+    #[tokio::test]
+    async fn test_trap_message_read_back_per_instance() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            r#"
+            (module
+                (func $f (param i32 i32) (result i32)
+                    (local.get 0)
+                    (local.get 1)
+                    (i32.div_s)
+                )
+                (export "f" (func $f))
+            )
+            "#
+            .as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instances = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let f = instances
+            .get_func("f")
+            .unwrap()
+            .try_typed::<(i32, i32), i32>()
+            .unwrap();
+
+        let store_source = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let mut stores = store_source
+            .build(&memory_system, &queue, 4)
+            .await
+            .expect("could not build stores");
+
+        let inputs = vec![(8, 2), (8, 4), (8, 8), (8, 0)];
+        let results = f
+            .call_all(&memory_system, &queue, &mut stores, inputs)
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+
+        assert_eq!(results[0], Ok(4));
+        assert_eq!(results[1], Ok(2));
+        assert_eq!(results[2], Ok(1));
+        let trap = results[3].expect_err("division by zero traps");
+        assert_eq!(
+            format!("instance 3 trapped: {}", crate::trap_message(trap)),
+            "instance 3 trapped: integer divide by zero"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_memory_grow_into_reserved_pages() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+        stores_builder.reserve_memory_pages(2);
+
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            r#"
+            (module
+                (memory 1 8)
+                (func $grow (param i32) (result i32 i32)
+                    (local $old i32)
+                    (local $last_page i32)
+                    (local.set $old (memory.grow (local.get 0)))
+
+                    ;; Write to, then read from, the start of the last page
+                    (local.set $last_page
+                        (i32.mul
+                            (i32.sub (memory.size) (i32.const 1))
+                            (i32.const 65536)
+                        )
+                    )
+                    (i32.store (local.get $last_page) (i32.const 42))
+
+                    (local.get $old)
+                    (i32.load (local.get $last_page))
+                )
+                (func $size (result i32)
+                    (memory.size)
+                )
+                (export "grow" (func $grow))
+                (export "size" (func $size))
+            )
+            "#
+            .as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instances = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let grow = instances
+            .get_func("grow")
+            .unwrap()
+            .try_typed::<i32, (i32, i32)>()
+            .unwrap();
+        let size = instances
+            .get_func("size")
+            .unwrap()
+            .try_typed::<(), i32>()
+            .unwrap();
+
+        let store_source = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let mut stores = store_source
+            .build(&memory_system, &queue, 4)
+            .await
+            .expect("could not build stores");
+
+        // Only 2 pages were reserved, so growing by 3 fails
+        let results = grow
+            .call_all(&memory_system, &queue, &mut stores, vec![0, 1, 2, 3])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(results[0], Ok((1, 42)));
+        assert_eq!(results[1], Ok((1, 42)));
+        assert_eq!(results[2], Ok((1, 42)));
+        assert_eq!(results[3], Ok((-1, 42)));
+
+        // The new size persists between calls
+        let results = size
+            .call_all(&memory_system, &queue, &mut stores, vec![(); 4])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(results, vec![Ok(1), Ok(2), Ok(3), Ok(1)]);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_arguments_reach_every_invocation() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Tuneables::default());
+
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            r#"
+            (module
+                (func $f (param i32 i32) (result i32)
+                    (local.get 0)
+                    (local.get 1)
+                    (i32.mul)
+                )
+                (export "f" (func $f))
+            )
+            "#
+            .as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instances = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let target = instances
+            .get_func("f")
+            .unwrap()
+            .try_typed::<(i32, i32), i32>()
+            .unwrap();
+
+        let store_source = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let mut stores = store_source
+            .build(&memory_system, &queue, 1024)
+            .await
+            .expect("could not build stores");
+
+        let results = target
+            .call_all_broadcast(&memory_system, &queue, &mut stores, (6, 7), 1024)
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+
+        assert_eq!(results.len(), 1024);
+        for result in results {
+            assert_eq!(result, Ok(42));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streamed_results_cover_every_instance() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Tuneables::default());
+
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            r#"
+            (module
+                (func $f (param i32) (result i32)
+                    (if (i32.eq (local.get 0) (i32.const 3))
+                        (then unreachable)
+                    )
+                    (i32.mul (local.get 0) (i32.const 2))
+                )
+                (export "f" (func $f))
+            )
+            "#
+            .as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instances = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let target = instances
+            .get_func("f")
+            .unwrap()
+            .try_typed::<i32, i32>()
+            .unwrap();
+
+        let store_source = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let mut stores = store_source
+            .build(&memory_system, &queue, 300)
+            .await
+            .expect("could not build stores");
+
+        let mut results = target
+            .call_all_streaming(&memory_system, &queue, &mut stores, 0..300)
+            .await
+            .expect("could not allocate call buffers")
+            .map(|result| result.expect("could not read results buffers"))
+            .collect::<Vec<_>>()
+            .await;
+
+        results.sort_by_key(|(instance_index, _)| *instance_index);
+        assert_eq!(results.len(), 300);
+        for (expected_index, (instance_index, result)) in results.into_iter().enumerate() {
+            assert_eq!(instance_index, expected_index);
+            if instance_index == 3 {
+                assert_eq!(result, Err(wasmtime_environ::Trap::UnreachableCodeReached));
+            } else {
+                assert_eq!(result, Ok(instance_index as i32 * 2));
+            }
+        }
+    }
+
+    #[test]
+    fn test_broadcast_inputs_match_per_invocation_inputs() {
+        let tuneables = aligned_tuneables();
+        let args = vec![Val::I32(3), Val::F64(1.5)];
+
+        let broadcast = Session::encode_inputs(
+            &SessionArgs::Broadcast {
+                args: args.clone(),
+                count: 5,
+            },
+            1..4,
+            &tuneables,
+        );
+        let per_invocation =
+            Session::encode_inputs(&SessionArgs::PerInvocation(vec![args; 5]), 1..4, &tuneables);
+
+        assert_eq!(broadcast, per_invocation);
+        assert_eq!(broadcast.len(), 3 * 16);
+    }
+
+    fn struct_of_arrays_tuneables() -> Tuneables {
+        Tuneables {
+            io_layout: IoLayout::StructOfArrays,
+            ..Tuneables::default()
+        }
+    }
+
+    #[test]
+    fn test_struct_of_arrays_inputs_are_laid_out_by_value() {
+        let tuneables = struct_of_arrays_tuneables();
+        let args = SessionArgs::PerInvocation(vec![
+            vec![Val::I32(1), Val::F64(1.5)],
+            vec![Val::I32(2), Val::F64(2.5)],
+        ]);
+
+        let data = Session::encode_inputs(&args, 0..2, &tuneables);
+
+        let expected = [
+            1i32.to_le_bytes().as_slice(),
+            2i32.to_le_bytes().as_slice(),
+            1.5f64.to_le_bytes().as_slice(),
+            2.5f64.to_le_bytes().as_slice(),
+        ]
+        .concat();
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn test_struct_of_arrays_ignores_invocation_alignment() {
+        let tuneables = Tuneables {
+            io_invocation_alignment_words: 4,
+            ..struct_of_arrays_tuneables()
+        };
+
+        let len = Session::io_instance_len(&[ValType::I32, ValType::F32], &tuneables);
+
+        assert_eq!(len, 8);
+    }
+
+    #[test]
+    fn test_struct_of_arrays_broadcast_inputs_match_per_invocation_inputs() {
+        let tuneables = struct_of_arrays_tuneables();
+        let args = vec![Val::I32(3), Val::F64(1.5)];
+
+        let broadcast = Session::encode_inputs(
+            &SessionArgs::Broadcast {
+                args: args.clone(),
+                count: 5,
+            },
+            1..4,
+            &tuneables,
+        );
+        let per_invocation =
+            Session::encode_inputs(&SessionArgs::PerInvocation(vec![args; 5]), 1..4, &tuneables);
+
+        assert_eq!(broadcast, per_invocation);
+        assert_eq!(broadcast.len(), 3 * 12);
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_calls_run_in_order() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Tuneables::default());
+        stores_builder.reserve_memory_pages(2);
+
+        let wat = r#"
+            (module
+                (memory 1 8)
+                (func $init (param i32)
+                    (i32.store (i32.const 0) (local.get 0))
+                )
+                (func $step
+                    (i32.store (i32.const 0)
+                        (i32.add (i32.mul (i32.load (i32.const 0)) (i32.const 2)) (i32.const 1))
+                    )
+                    (drop (memory.grow (i32.const 1)))
+                )
+                (func $read (result i32 i32)
+                    (i32.load (i32.const 0))
+                    (memory.size)
+                )
+                (export "init" (func $init))
+                (export "step" (func $step))
+                (export "read" (func $read))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instances = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let init = instances.get_typed_func::<i32, ()>("init").unwrap();
+        let step = instances.get_typed_func::<(), ()>("step").unwrap();
+        let read = instances.get_typed_func::<(), (i32, i32)>("read").unwrap();
+
+        let store_source = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let mut stores = store_source
+            .build(&memory_system, &queue, 4)
+            .await
+            .expect("could not build stores");
+
+        let results = SessionBuilder::new(&mut stores)
+            .call_all_typed(&init, 0..4)
+            .call_all_typed(&step, vec![(); 4])
+            .call_all_typed(&step, vec![(); 4])
+            .call_all_typed(&read, vec![(); 4])
+            .run(&memory_system, &queue)
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0], vec![Ok(vec![]); 4]);
+        let expected = (0..4)
+            .map(|i| Ok(vec![Val::I32(i * 4 + 3), Val::I32(3)]))
+            .collect::<Vec<_>>();
+        assert_eq!(results[3], expected);
+
+        // The state left by the last call persists for later calls
+        let results = read
+            .call_all(&memory_system, &queue, &mut stores, vec![(); 4])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        let expected = (0..4).map(|i| Ok((i * 4 + 3, 3))).collect::<Vec<_>>();
+        assert_eq!(results, expected);
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_trap_does_not_carry_over() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Tuneables::default());
+
+        let wat = r#"
+            (module
+                (func $div (param i32) (result i32)
+                    (i32.div_s (i32.const 12) (local.get 0))
+                )
+                (export "div" (func $div))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instances = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let div = instances.get_typed_func::<i32, i32>("div").unwrap();
+
+        let store_source = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let mut stores = store_source
+            .build(&memory_system, &queue, 4)
+            .await
+            .expect("could not build stores");
+
+        let results = SessionBuilder::new(&mut stores)
+            .call_all_typed(&div, vec![0; 4])
+            .call_all_typed(&div, vec![3; 4])
+            .run(&memory_system, &queue)
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            results[0],
+            vec![Err(wasmtime_environ::Trap::IntegerDivisionByZero); 4]
+        );
+        assert_eq!(results[1], vec![Ok(vec![Val::I32(4)]); 4]);
+    }
 }
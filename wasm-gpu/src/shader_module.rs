@@ -2,7 +2,9 @@ use std::borrow::Cow;
 
 use elsa::sync::FrozenMap;
 use itertools::Itertools;
-use wasm_gpu_funcgen::{get_entry_name, AssembledModule, BINDING_TUPLES};
+use wasm_gpu_funcgen::{
+    get_entry_name, AssembledModule, Tuneables, BINDING_TUPLES, PACKED_BINDING_TUPLES,
+};
 use wasm_types::FuncRef;
 use wgpu::{BindGroupLayoutDescriptor, ShaderModule};
 use wgpu_async::{AsyncQueue, WgpuFuture};
@@ -26,10 +28,11 @@ impl WasmShaderModule {
         #[cfg(debug_assertions)]
         device.push_error_scope(wgpu::ErrorFilter::Validation);
 
-        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        let descriptor = wgpu::ShaderModuleDescriptor {
             label: None,
             source: wgpu::ShaderSource::Naga(Cow::Owned(module.clone())),
-        });
+        };
+        let shader_module = device.create_shader_module(descriptor);
 
         // Clearly this might be slow in debug, but we assume there will be no issues in realease so drop the performance hit
         #[cfg(debug_assertions)]
@@ -48,12 +51,21 @@ impl WasmShaderModule {
         return shader_module;
     }
 
-    pub(crate) fn make(device: &wgpu::Device, assembled: &AssembledModule) -> Self {
+    pub(crate) fn make(
+        device: &wgpu::Device,
+        assembled: &AssembledModule,
+        tuneables: &Tuneables,
+    ) -> Self {
         let shader = Self::make_shader_module(device, assembled);
 
-        let binding_entries = BINDING_TUPLES
-            .clone()
-            .into_iter()
+        let binding_tuples: &[(u32, bool)] = if tuneables.pack_io_bindings {
+            &PACKED_BINDING_TUPLES
+        } else {
+            &BINDING_TUPLES
+        };
+        let binding_entries = binding_tuples
+            .iter()
+            .copied()
             .sorted_by_key(|(binding, _)| *binding)
             .map(|(binding, read_only)| wgpu::BindGroupLayoutEntry {
                 binding,
@@ -107,14 +119,39 @@ impl WasmShaderModule {
         dispatch_y: u32,
         dispatch_z: u32,
     ) -> WgpuFuture<()> {
-        let device = queue.device();
+        let mut encoder = queue
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        self.encode_pipeline_for_fn(
+            queue.device(),
+            &mut encoder,
+            func,
+            bindings,
+            dispatch_x,
+            dispatch_y,
+            dispatch_z,
+        );
+
+        queue.submit([encoder.finish()])
+    }
+
+    /// Records a dispatch of the entry point for a function into an encoder, so that several dispatches
+    /// can be submitted together.
+    pub(crate) fn encode_pipeline_for_fn(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        func: FuncRef,
+        bindings: Bindings,
+        dispatch_x: u32,
+        dispatch_y: u32,
+        dispatch_z: u32,
+    ) {
         let name = get_entry_name(func);
 
         self.ensure_pipeline_exists(device, &name);
 
-        let mut encoder =
-            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-
         {
             let bind_group = bindings.attach(device, &self.bind_group_layout);
 
@@ -131,7 +168,5 @@ impl WasmShaderModule {
 
             compute.dispatch_workgroups(dispatch_x, dispatch_y, dispatch_z);
         }
-
-        queue.submit([encoder.finish()])
     }
 }
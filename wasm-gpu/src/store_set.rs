@@ -1,20 +1,33 @@
 pub mod builder;
 
-use wasm_gpu_funcgen::Tuneables;
+use std::mem::size_of;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU32, Ordering};
+use wasm_gpu_funcgen::{
+    u32_to_trap, MemoryIndex, Tuneables, DATA_BINDING_INDEX, ELEMENTS_BINDING_INDEX,
+    IMMUTABLE_GLOBALS_BINDING_INDEX, MEMORY_BINDING_INDEX, MUTABLE_GLOBALS_BINDING_INDEX,
+    TABLES_BINDING_INDEX,
+};
+use wasm_types::WasmTyVal;
 use wgpu_async::{AsyncQueue, OutOfMemoryError};
-use wgpu_lazybuffers::MemorySystem;
+use wgpu_lazybuffers::{LazilyMappable, MemorySystem};
 use wgpu_lazybuffers_macros::lazy_mappable;
 
-use crate::instance::data::UnmappedDataInstance;
+use crate::instance::data::{SegmentDroppedFlags, UnmappedDataInstance};
 use crate::instance::element::UnmappedElementInstance;
 use crate::instance::func::FuncsInstance;
+use crate::instance::global::builder::{AbstractGlobalMutablePtr, AbstractGlobalPtr};
 use crate::instance::global::immutable::UnmappedImmutableGlobalsInstance;
 use crate::instance::global::instance::{
     MappedMutableGlobalsInstanceSet, UnmappedMutableGlobalsInstanceSet,
 };
-use crate::instance::memory::instance::{MappedMemoryInstanceSet, UnmappedMemoryInstanceSet};
+use crate::instance::memory::instance::{
+    MappedMemoryInstanceSet, MemoryAccessError, UnmappedMemoryInstanceSet, MEMORY_STRIDE_BYTES,
+};
 use crate::instance::table::instance::{MappedTableInstanceSet, UnmappedTableInstanceSet};
+use crate::session::Session;
 use crate::shader_module::WasmShaderModule;
+use crate::store_set::builder::InstanceSources;
 use crate::MappedStoreSetBuilder;
 use std::sync::Arc;
 
@@ -26,6 +39,39 @@ pub struct UnmappedStoreSetData {
     pub memories: UnmappedMemoryInstanceSet,
     #[map(MappedMutableGlobalsInstanceSet)]
     pub mutable_globals: UnmappedMutableGlobalsInstanceSet,
+    pub data_dropped: SegmentDroppedFlags,
+    pub element_dropped: SegmentDroppedFlags,
+    pub traps: TrapFlags,
+}
+
+/// The trap flag left by the most recent call of each instance in a store set, where 0 means that
+/// the call completed without trapping.
+#[derive(Debug)]
+pub struct TrapFlags {
+    flags: Vec<AtomicU32>,
+}
+
+impl TrapFlags {
+    pub(crate) fn new(instance_count: usize) -> Self {
+        Self {
+            flags: (0..instance_count).map(|_| AtomicU32::new(0)).collect(),
+        }
+    }
+
+    /// Adds flags for `additional` more instances, none of which have trapped.
+    pub(crate) fn grow(&mut self, additional: usize) {
+        self.flags
+            .extend((0..additional).map(|_| AtomicU32::new(0)));
+    }
+
+    /// The trap that ended the most recent call of the given instance, if any.
+    pub(crate) fn get(&self, instance_index: usize) -> Option<wasmtime_environ::Trap> {
+        u32_to_trap(self.flags[instance_index].load(Ordering::Acquire))
+    }
+
+    pub(crate) fn set(&self, instance_index: usize, flag: u32) {
+        self.flags[instance_index].store(flag, Ordering::Release)
+    }
 }
 
 /// All of the state for a collection of active WASM state machines
@@ -39,6 +85,9 @@ pub struct StoreSet<O> {
 
     pub shader_module: Arc<WasmShaderModule>,
 
+    /// The state that new instances are initialised with
+    pub(crate) sources: Arc<InstanceSources>,
+
     pub owned: O,
 
     pub tuneables: Tuneables,
@@ -47,6 +96,41 @@ pub struct StoreSet<O> {
 pub type DeviceStoreSet = StoreSet<UnmappedStoreSetData>;
 pub type HostStoreSet = StoreSet<MappedStoreSetData>;
 
+/// A copy of the mutable state of every instance in a store set, taken with
+/// [`DeviceStoreSet::snapshot_state`] and written back with [`DeviceStoreSet::restore`].
+///
+/// The stack is not included, since it only lives for the duration of a single call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StoreSnapshot {
+    instance_count: usize,
+    tables: Vec<Vec<u8>>,
+    memories: Vec<Vec<u8>>,
+    memory_pages: Vec<u32>,
+    mutable_globals: Vec<Vec<u8>>,
+    data_dropped: Vec<Vec<u32>>,
+    element_dropped: Vec<Vec<u32>>,
+}
+
+impl StoreSnapshot {
+    /// The number of instances in the store set that this snapshot was taken from.
+    pub fn instance_count(&self) -> usize {
+        self.instance_count
+    }
+}
+
+/// Where the bytes of a single instance are found within one of the buffers of a store set, as given by
+/// [`DeviceStoreSet::binding_instance_region`].
+///
+/// The bytes of the instance are split into chunks of `chunk_bytes` bytes, with chunk `n` starting at
+/// `offset + n * stride_bytes`. When `chunk_bytes == stride_bytes` the bytes of the instance are contiguous.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstanceBufferRegion {
+    pub offset: u64,
+    pub chunk_bytes: u64,
+    pub stride_bytes: u64,
+}
+
 impl DeviceStoreSet {
     /// Use current module state to form a new store set builder, with all values initialised to the
     /// parts contained in this. This is similar to the [Wizer](https://github.com/bytecodealliance/wizer)
@@ -65,4 +149,1050 @@ impl DeviceStoreSet {
     ) -> Result<MappedStoreSetBuilder, OutOfMemoryError> {
         MappedStoreSetBuilder::snapshot(memory_system, queue, &self, store_index).await
     }
+
+    /// The number of instances in this set, which is the number of invocations that a call can run at once.
+    pub fn instance_count(&self) -> usize {
+        self.owned.data_dropped.instance_count()
+    }
+
+    /// The trap that ended the most recent call of the given instance, or `None` if the call completed
+    /// cleanly or the instance hasn't been called yet. This is the same trap as given in the results of the call.
+    pub fn trap_state(&self, instance_index: usize) -> Option<wasmtime_environ::Trap> {
+        self.owned.traps.get(instance_index)
+    }
+
+    /// The buffer bound at the given binding index when functions in this set are called, for use in a
+    /// user's own wgpu pipelines without a round-trip through host memory. Only the buffers owned by the
+    /// store set are available, so this gives `None` for the input, output, stack, flags and constants
+    /// bindings, which are created for each call.
+    ///
+    /// The buffer is replaced when the set is grown, so shouldn't be held across calls to
+    /// [`DeviceStoreSet::grow`]. No synchronisation is performed beyond the ordering of submissions on a
+    /// queue: work reading the buffer sees the results of calls submitted to the same queue before it, and
+    /// anything written to the buffer is seen by calls submitted after it. Writes must keep to the layout
+    /// given by [`DeviceStoreSet::binding_instance_region`].
+    pub fn binding_buffer(&self, binding_index: u32) -> Option<&wgpu::Buffer> {
+        let buffer: &wgpu::Buffer = match binding_index {
+            MEMORY_BINDING_INDEX => self.owned.memories.buffer(),
+            MUTABLE_GLOBALS_BINDING_INDEX => self.owned.mutable_globals.buffer(),
+            IMMUTABLE_GLOBALS_BINDING_INDEX => self.immutable_globals.buffer(),
+            TABLES_BINDING_INDEX => self.owned.tables.buffer(),
+            DATA_BINDING_INDEX => self.datas.buffer(),
+            ELEMENTS_BINDING_INDEX => self.elements.buffer(),
+            _ => return None,
+        };
+
+        Some(buffer)
+    }
+
+    /// Where the bytes of the given instance are found within the buffer given by
+    /// [`DeviceStoreSet::binding_buffer`] for the same binding index.
+    ///
+    /// With disjoint memory the memories, tables and mutable globals of the instances are interleaved a
+    /// chunk at a time. Otherwise, and for the buffers that are never written to by calls, every instance
+    /// shares the whole buffer.
+    pub fn binding_instance_region(
+        &self,
+        binding_index: u32,
+        instance_index: usize,
+    ) -> Option<InstanceBufferRegion> {
+        assert!(
+            instance_index < self.instance_count(),
+            "instance index {} out of range for store set with {} instances",
+            instance_index,
+            self.instance_count()
+        );
+
+        let chunk_bytes = match binding_index {
+            MEMORY_BINDING_INDEX => MEMORY_STRIDE_BYTES,
+            MUTABLE_GLOBALS_BINDING_INDEX => crate::instance::global::instance::STRIDE,
+            TABLES_BINDING_INDEX => crate::instance::table::instance::STRIDE,
+            _ => {
+                let size = self.binding_buffer(binding_index)?.size();
+                return Some(InstanceBufferRegion {
+                    offset: 0,
+                    chunk_bytes: size,
+                    stride_bytes: size,
+                });
+            }
+        };
+
+        let interleaved_index = if self.tuneables.disjoint_memory {
+            instance_index
+        } else {
+            0
+        };
+        Some(InstanceBufferRegion {
+            offset: interleaved_index as u64 * chunk_bytes,
+            chunk_bytes,
+            stride_bytes: self.duplication_count() as u64 * chunk_bytes,
+        })
+    }
+
+    /// Adds `additional` instances to this set, initialised in the same way as the instances created when
+    /// this set was built. The state of the existing instances is preserved, so this can be used to
+    /// increase parallelism when more work is discovered part of the way through a run.
+    ///
+    /// Without disjoint memory every instance shares the tables, memory and globals of the first instance,
+    /// so new instances share the existing state.
+    pub async fn grow(
+        &mut self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        additional: usize,
+    ) -> Result<(), OutOfMemoryError> {
+        let existing_count = self.instance_count();
+
+        if self.tuneables.disjoint_memory {
+            self.sources
+                .tables
+                .try_grow(
+                    memory_system,
+                    queue,
+                    &mut self.owned.tables,
+                    existing_count,
+                    additional,
+                )
+                .await?;
+            self.sources
+                .memories
+                .try_grow(memory_system, queue, &mut self.owned.memories, additional)
+                .await?;
+            self.sources
+                .mutable_globals
+                .try_grow(
+                    memory_system,
+                    queue,
+                    &mut self.owned.mutable_globals,
+                    existing_count,
+                    additional,
+                )
+                .await?;
+        }
+
+        self.owned
+            .data_dropped
+            .grow(&self.sources.data_dropped, additional);
+        self.owned
+            .element_dropped
+            .grow(&self.sources.element_dropped, additional);
+        self.owned.traps.grow(additional);
+
+        Ok(())
+    }
+
+    /// The number of copies of the tables, memories and mutable globals held by this set.
+    fn duplication_count(&self) -> usize {
+        if self.tuneables.disjoint_memory {
+            self.instance_count()
+        } else {
+            1
+        }
+    }
+
+    /// Downloads the tables, memories, mutable globals and dropped data segments of every instance in
+    /// this set, so that they can later be written back with [`DeviceStoreSet::restore`], e.g. to roll
+    /// back a long-running simulation to a checkpoint.
+    pub async fn snapshot_state(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+    ) -> Result<StoreSnapshot, MemoryAccessError> {
+        let duplication_count = self.duplication_count();
+
+        let tables = self
+            .owned
+            .tables
+            .try_read_instances(memory_system, queue, duplication_count)
+            .await?;
+        let memories = self
+            .owned
+            .memories
+            .try_read_instances(memory_system, queue)
+            .await?;
+        let memory_pages = (0..duplication_count)
+            .map(|i| self.owned.memories.pages(i))
+            .collect();
+        let mutable_globals = self
+            .owned
+            .mutable_globals
+            .try_read_instances(memory_system, queue, duplication_count)
+            .await?;
+        let data_dropped = (0..self.instance_count())
+            .map(|i| self.owned.data_dropped.get(i))
+            .collect();
+        let element_dropped = (0..self.instance_count())
+            .map(|i| self.owned.element_dropped.get(i))
+            .collect();
+
+        Ok(StoreSnapshot {
+            instance_count: self.instance_count(),
+            tables,
+            memories,
+            memory_pages,
+            mutable_globals,
+            data_dropped,
+            element_dropped,
+        })
+    }
+
+    /// Overwrites the state of every instance in this set with the state captured in a snapshot.
+    ///
+    /// # Panics
+    /// Panics if the snapshot was taken from a store set with a different number of instances or
+    /// different memory tuneables. Snapshots should only be restored into the store set that they
+    /// were taken from, or one built from the same modules.
+    pub async fn restore(
+        &mut self,
+        queue: &AsyncQueue,
+        snapshot: &StoreSnapshot,
+    ) -> Result<(), OutOfMemoryError> {
+        assert_eq!(
+            snapshot.instance_count,
+            self.instance_count(),
+            "snapshot was taken from a store set with a different number of instances"
+        );
+        assert_eq!(
+            snapshot.memories.len(),
+            self.duplication_count(),
+            "snapshot was taken from a store set with different memory tuneables"
+        );
+
+        self.owned
+            .tables
+            .try_write_instances(queue, &snapshot.tables)
+            .await?;
+        self.owned
+            .memories
+            .try_write_instances(queue, &snapshot.memories)
+            .await?;
+        for (i, pages) in snapshot.memory_pages.iter().enumerate() {
+            self.owned.memories.set_pages(i, *pages);
+        }
+        self.owned
+            .mutable_globals
+            .try_write_instances(queue, &snapshot.mutable_globals)
+            .await?;
+        for (i, flags) in snapshot.data_dropped.iter().enumerate() {
+            self.owned.data_dropped.set(i, flags);
+        }
+        for (i, flags) in snapshot.element_dropped.iter().enumerate() {
+            self.owned.element_dropped.set(i, flags);
+        }
+
+        Ok(())
+    }
+
+    /// Reads a range of bytes from a linear memory of one of the instances in this set, e.g. to
+    /// retrieve the output of a module that writes its results to memory rather than returning them.
+    /// The range is relative to the start of the memory, and the memory index can be obtained
+    /// from a memory pointer.
+    ///
+    /// Without disjoint memory, every instance shares the memory of the first instance.
+    ///
+    /// # Panics
+    /// Panics if the range lies outside of the memories of the instance.
+    pub async fn read_memory(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        instance_index: usize,
+        memory: MemoryIndex,
+        range: Range<u32>,
+    ) -> Result<Vec<u8>, MemoryAccessError> {
+        let interleaved_index = Session::memory_instance_index(&self.tuneables, instance_index);
+        let start = *memory as usize + range.start as usize;
+        let end = *memory as usize + range.end as usize;
+
+        self.owned
+            .memories
+            .try_read_instance_slice(memory_system, queue, interleaved_index, start..end)
+            .await
+    }
+
+    /// Writes bytes to a linear memory of one of the instances in this set, starting at the given
+    /// offset within the memory, e.g. to provide input to a module that reads its arguments from memory.
+    ///
+    /// Without disjoint memory, every instance shares the memory of the first instance, so
+    /// writing to any instance writes to all of them.
+    ///
+    /// # Panics
+    /// Panics if the data does not fit within the memories of the instance.
+    pub async fn write_memory(
+        &mut self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        instance_index: usize,
+        memory: MemoryIndex,
+        offset: u32,
+        data: &[u8],
+    ) -> Result<(), MemoryAccessError> {
+        let interleaved_index = Session::memory_instance_index(&self.tuneables, instance_index);
+        let start = *memory as usize + offset as usize;
+
+        self.owned
+            .memories
+            .try_write_instance_slice(memory_system, queue, interleaved_index, start, data)
+            .await
+    }
+
+    /// Reads the value of a global of one of the instances in this set, e.g. to retrieve state that
+    /// a module keeps in an exported global.
+    ///
+    /// Without disjoint memory, every instance shares the mutable globals of the first instance.
+    ///
+    /// # Panics
+    /// Panics if the global does not hold values of type `V`.
+    pub async fn read_global<V: WasmTyVal>(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        instance_index: usize,
+        global: &AbstractGlobalPtr,
+    ) -> Result<V, MemoryAccessError> {
+        assert!(
+            global.content_type().eq(&V::VAL_TYPE),
+            "global does not hold values of type {}",
+            std::any::type_name::<V>()
+        );
+
+        let bytes = match global {
+            AbstractGlobalPtr::Immutable(ptr) => {
+                // Immutable globals are shared by every instance
+                let mut immutables = self
+                    .immutable_globals
+                    .as_ref()
+                    .try_duplicate(queue)
+                    .await?
+                    .map_lazy();
+                return Ok(immutables.try_get_typed::<V>(queue, ptr).await?);
+            }
+            AbstractGlobalPtr::Mutable(ptr) => {
+                let interleaved_index =
+                    Session::memory_instance_index(&self.tuneables, instance_index);
+                let start = ptr.to_index().as_usize();
+                let end = start + size_of::<V>();
+
+                self.owned
+                    .mutable_globals
+                    .try_read_instance_slice(memory_system, queue, interleaved_index, start..end)
+                    .await?
+            }
+        };
+
+        Ok(V::try_from_bytes(&bytes).expect(
+            format!(
+                "could not parse memory - invalid state for {}: {:?}",
+                std::any::type_name::<V>(),
+                bytes
+            )
+            .as_str(),
+        ))
+    }
+
+    /// Overwrites the value of a mutable global of one of the instances in this set, e.g. to
+    /// provide input to a module that reads its arguments from an exported global.
+    ///
+    /// Without disjoint memory, every instance shares the mutable globals of the first instance, so
+    /// writing to any instance writes to all of them.
+    ///
+    /// # Panics
+    /// Panics if the global does not hold values of type `V`.
+    pub async fn write_global<V: WasmTyVal>(
+        &mut self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        instance_index: usize,
+        global: &AbstractGlobalMutablePtr,
+        value: V,
+    ) -> Result<(), MemoryAccessError> {
+        assert!(
+            global.content_type().eq(&V::VAL_TYPE),
+            "global does not hold values of type {}",
+            std::any::type_name::<V>()
+        );
+
+        let interleaved_index = Session::memory_instance_index(&self.tuneables, instance_index);
+
+        self.owned
+            .mutable_globals
+            .try_write_instance_slice(
+                memory_system,
+                queue,
+                self.duplication_count(),
+                interleaved_index,
+                global.to_index().as_usize(),
+                &value.to_bytes(),
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::instance::memory::instance::MEMORY_STRIDE_BYTES;
+    use crate::unit_tests_lib::get_backend;
+    use crate::{imports, DynamicCallError, MappedStoreSetBuilder};
+    use wasm_types::Val;
+
+    #[tokio::test]
+    async fn test_memory_written_from_host_is_read_back_after_call() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+
+        let wat = r#"
+            (module
+                (memory 1)
+                (func $f (param i32)
+                    (i32.store (i32.const 21)
+                        (i32.add (i32.load (i32.const 3)) (local.get 0))
+                    )
+                )
+                (export "memory" (memory 0))
+                (export "f" (func $f))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instances = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let memory = instances
+            .get_memory("memory")
+            .expect("memory is exported")
+            .to_index();
+        let target = instances
+            .get_func("f")
+            .unwrap()
+            .try_typed::<i32, ()>()
+            .unwrap();
+
+        let store_source = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let mut stores = store_source
+            .build(&memory_system, &queue, 4)
+            .await
+            .expect("could not build stores");
+
+        for instance_index in 0..4 {
+            let value = 100 * instance_index as i32;
+            stores
+                .write_memory(
+                    &memory_system,
+                    &queue,
+                    instance_index,
+                    memory,
+                    3,
+                    &value.to_le_bytes(),
+                )
+                .await
+                .expect("could not write memory");
+        }
+
+        let inputs: Vec<i32> = (0..4).collect();
+        target
+            .call_all(&memory_system, &queue, &mut stores, inputs.clone())
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+
+        for (instance_index, input) in inputs.into_iter().enumerate() {
+            let bytes = stores
+                .read_memory(&memory_system, &queue, instance_index, memory, 21..25)
+                .await
+                .expect("could not read memory");
+            let value = i32::from_le_bytes(bytes.try_into().unwrap());
+            assert_eq!(value, 100 * instance_index as i32 + input);
+
+            // Neighbouring bytes are left untouched by the unaligned write
+            let bytes = stores
+                .read_memory(&memory_system, &queue, instance_index, memory, 0..3)
+                .await
+                .expect("could not read memory");
+            assert_eq!(bytes, vec![0, 0, 0]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_grown_instances_start_fresh_and_existing_instances_keep_state() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+
+        let wat = r#"
+            (module
+                (memory 1)
+                (data (i32.const 0) "\05")
+                (func $f (param i32) (result i32)
+                    (i32.store (i32.const 0)
+                        (i32.add (i32.load (i32.const 0)) (local.get 0))
+                    )
+                    (i32.load (i32.const 0))
+                )
+                (export "f" (func $f))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instances = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let target = instances
+            .get_func("f")
+            .unwrap()
+            .try_typed::<i32, i32>()
+            .unwrap();
+
+        let store_source = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let mut stores = store_source
+            .build(&memory_system, &queue, 2)
+            .await
+            .expect("could not build stores");
+
+        let results = target
+            .call_all(&memory_system, &queue, &mut stores, vec![1; 2])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(results, vec![Ok(6); 2]);
+
+        stores
+            .grow(&memory_system, &queue, 6)
+            .await
+            .expect("could not grow stores");
+        assert_eq!(stores.instance_count(), 8);
+
+        let results = target
+            .call_all(&memory_system, &queue, &mut stores, vec![1; 8])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(
+            results,
+            vec![Ok(7), Ok(7), Ok(6), Ok(6), Ok(6), Ok(6), Ok(6), Ok(6)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restoring_snapshot_reverts_state() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+
+        let wat = r#"
+            (module
+                (memory 1)
+                (data (i32.const 0) "\05")
+                (func $f (param i32) (result i32)
+                    (i32.store (i32.const 0)
+                        (i32.add (i32.load (i32.const 0)) (local.get 0))
+                    )
+                    (i32.load (i32.const 0))
+                )
+                (export "f" (func $f))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instances = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let target = instances
+            .get_func("f")
+            .unwrap()
+            .try_typed::<i32, i32>()
+            .unwrap();
+
+        let store_source = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let mut stores = store_source
+            .build(&memory_system, &queue, 4)
+            .await
+            .expect("could not build stores");
+
+        let results = target
+            .call_all(&memory_system, &queue, &mut stores, vec![1, 2, 3, 4])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(results, vec![Ok(6), Ok(7), Ok(8), Ok(9)]);
+
+        let snapshot = stores
+            .snapshot_state(&memory_system, &queue)
+            .await
+            .expect("could not take snapshot");
+        assert_eq!(snapshot.instance_count(), 4);
+
+        let results = target
+            .call_all(&memory_system, &queue, &mut stores, vec![10; 4])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(results, vec![Ok(16), Ok(17), Ok(18), Ok(19)]);
+
+        stores
+            .restore(&queue, &snapshot)
+            .await
+            .expect("could not restore snapshot");
+        let restored = stores
+            .snapshot_state(&memory_system, &queue)
+            .await
+            .expect("could not take snapshot");
+        assert_eq!(restored, snapshot);
+
+        let results = target
+            .call_all(&memory_system, &queue, &mut stores, vec![10; 4])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(results, vec![Ok(16), Ok(17), Ok(18), Ok(19)]);
+    }
+
+    #[tokio::test]
+    async fn test_exported_memory_and_globals_are_accessible_from_host() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+
+        let wat = r#"
+            (module
+                (memory (export "m") 1)
+                (global $g (export "g") (mut i32) (i32.const 0))
+                (global $k (export "k") i32 (i32.const 7))
+                (func $f (param i32) (result i32)
+                    (global.set $g (i32.add (global.get $g) (local.get 0)))
+                    (i32.add (i32.load (i32.const 8)) (global.get $g))
+                )
+                (export "f" (func $f))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instances = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let memory = instances.get_memory("m").expect("memory is exported");
+        let global = instances
+            .get_typed_global::<i32>("g")
+            .expect("global is exported");
+        let constant = instances
+            .get_typed_global::<i32>("k")
+            .expect("global is exported");
+        assert!(instances.get_typed_global::<i64>("g").is_err());
+        assert!(instances.get_global("m").is_err());
+        let target = instances
+            .get_func("f")
+            .unwrap()
+            .try_typed::<i32, i32>()
+            .unwrap();
+
+        let store_source = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let mut stores = store_source
+            .build(&memory_system, &queue, 4)
+            .await
+            .expect("could not build stores");
+
+        for instance_index in 0..4 {
+            let value = 100 * instance_index as i32;
+            memory
+                .write(
+                    &memory_system,
+                    &queue,
+                    &mut stores,
+                    instance_index,
+                    8,
+                    &value.to_le_bytes(),
+                )
+                .await
+                .expect("could not write memory");
+            global
+                .write(
+                    &memory_system,
+                    &queue,
+                    &mut stores,
+                    instance_index,
+                    10 * instance_index as i32,
+                )
+                .await
+                .expect("could not write global");
+        }
+
+        let inputs: Vec<i32> = (0..4).collect();
+        let results = target
+            .call_all(&memory_system, &queue, &mut stores, inputs.clone())
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(results, vec![Ok(0), Ok(111), Ok(222), Ok(333)]);
+
+        for (instance_index, input) in inputs.into_iter().enumerate() {
+            let value = global
+                .read(&memory_system, &queue, &stores, instance_index)
+                .await
+                .expect("could not read global");
+            assert_eq!(value, 11 * input);
+
+            let value = constant
+                .read(&memory_system, &queue, &stores, instance_index)
+                .await
+                .expect("could not read global");
+            assert_eq!(value, 7);
+
+            let bytes = memory
+                .read(&memory_system, &queue, &stores, instance_index, 8..12)
+                .await
+                .expect("could not read memory");
+            assert_eq!(bytes, (100 * input).to_le_bytes());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dynamic_call_round_trips_mixed_values() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+
+        let wat = r#"
+            (module
+                (func $f (param i32 f32) (result f32 i32)
+                    (f32.add (local.get 1) (f32.convert_i32_s (local.get 0)))
+                    (i32.add (local.get 0) (i32.const 1))
+                )
+                (export "f" (func $f))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instances = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let target = instances.get_func("f").unwrap();
+
+        let store_source = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let mut stores = store_source
+            .build(&memory_system, &queue, 2)
+            .await
+            .expect("could not build stores");
+
+        // Mismatched types and arities are rejected before anything is run
+        let swapped = vec![
+            vec![Val::I32(1), Val::F32(0.5)],
+            vec![Val::F32(0.5), Val::I32(1)],
+        ];
+        let err = target
+            .call_all_dynamic(&memory_system, &queue, &mut stores, swapped)
+            .await
+            .err()
+            .expect("swapped arguments should be rejected");
+        assert!(matches!(
+            err,
+            DynamicCallError::ArgumentTypeMismatch { invocation: 1, .. }
+        ));
+        let short = vec![vec![Val::I32(1)]];
+        let err = target
+            .call_all_dynamic(&memory_system, &queue, &mut stores, short)
+            .await
+            .err()
+            .expect("missing arguments should be rejected");
+        assert!(matches!(
+            err,
+            DynamicCallError::ArgumentTypeMismatch { invocation: 0, .. }
+        ));
+
+        let args = vec![
+            vec![Val::I32(1), Val::F32(0.5)],
+            vec![Val::I32(-3), Val::F32(2.25)],
+        ];
+        let results = target
+            .call_all_dynamic(&memory_system, &queue, &mut stores, args)
+            .await
+            .expect("arguments match the signature")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(
+            results,
+            vec![
+                Ok(vec![Val::F32(1.5), Val::I32(2)]),
+                Ok(vec![Val::F32(-0.75), Val::I32(-2)]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trap_state_reports_trap_of_last_call() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+
+        let wat = r#"
+            (module
+                (func $f (param i32) (result i32)
+                    (if (i32.eq (local.get 0) (i32.const 2))
+                        (then (unreachable))
+                    )
+                    (local.get 0)
+                )
+                (export "f" (func $f))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instances = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let target = instances.get_typed_func::<i32, i32>("f").unwrap();
+
+        let store_source = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let mut stores = store_source
+            .build(&memory_system, &queue, 4)
+            .await
+            .expect("could not build stores");
+
+        for instance_index in 0..4 {
+            assert_eq!(stores.trap_state(instance_index), None);
+        }
+
+        let results = target
+            .call_all(&memory_system, &queue, &mut stores, 0..4)
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(
+            results,
+            vec![
+                Ok(0),
+                Ok(1),
+                Err(wasmtime_environ::Trap::UnreachableCodeReached),
+                Ok(3)
+            ]
+        );
+        for instance_index in 0..4 {
+            let expected =
+                (instance_index == 2).then_some(wasmtime_environ::Trap::UnreachableCodeReached);
+            assert_eq!(stores.trap_state(instance_index), expected);
+        }
+
+        // A clean call clears the trap
+        target
+            .call_all(&memory_system, &queue, &mut stores, vec![0; 4])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        for instance_index in 0..4 {
+            assert_eq!(stores.trap_state(instance_index), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_halts_only_the_trapping_instance() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+
+        let wat = r#"
+            (module
+                (global $g (export "g") (mut i32) (i32.const 0))
+                (func $f (param i32) (result i32)
+                    (if (result i32) (local.get 0)
+                        (then
+                            unreachable
+                            (global.set $g (i32.const 1))
+                            (i32.const 1)
+                        )
+                        (else
+                            (global.set $g (i32.const 2))
+                            (i32.const 7)
+                        )
+                    )
+                )
+                (export "f" (func $f))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instances = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let target = instances.get_typed_func::<i32, i32>("f").unwrap();
+        let global = instances
+            .get_typed_global::<i32>("g")
+            .expect("global is exported");
+
+        let store_source = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let mut stores = store_source
+            .build(&memory_system, &queue, 2)
+            .await
+            .expect("could not build stores");
+
+        let results = target
+            .call_all(&memory_system, &queue, &mut stores, vec![0, 1])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(
+            results,
+            vec![Ok(7), Err(wasmtime_environ::Trap::UnreachableCodeReached)]
+        );
+
+        // Nothing after the unreachable instruction was executed
+        for (instance_index, expected) in [(0, 2), (1, 0)] {
+            let value = global
+                .read(&memory_system, &queue, &stores, instance_index)
+                .await
+                .expect("could not read global");
+            assert_eq!(value, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_binding_buffers_describe_interleaved_instances() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+
+        let wat = r#"
+            (module
+                (memory 1)
+                (global $k i32 (i32.const 7))
+                (func $f (param i32)
+                    (i32.store (i32.const 0) (local.get 0))
+                )
+                (export "f" (func $f))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let store_source = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let stores = store_source
+            .build(&memory_system, &queue, 4)
+            .await
+            .expect("could not build stores");
+
+        // Per-call buffers aren't owned by the store set
+        assert!(stores.binding_buffer(crate::OUTPUT_BINDING_INDEX).is_none());
+        assert!(stores
+            .binding_instance_region(crate::OUTPUT_BINDING_INDEX, 0)
+            .is_none());
+
+        let memory = stores
+            .binding_buffer(crate::MEMORY_BINDING_INDEX)
+            .expect("memory is owned by the store set");
+        assert!(memory.size() >= 4 * 65536);
+        for instance_index in 0..4 {
+            let region = stores
+                .binding_instance_region(crate::MEMORY_BINDING_INDEX, instance_index)
+                .unwrap();
+            assert_eq!(
+                region,
+                crate::InstanceBufferRegion {
+                    offset: instance_index as u64 * MEMORY_STRIDE_BYTES,
+                    chunk_bytes: MEMORY_STRIDE_BYTES,
+                    stride_bytes: 4 * MEMORY_STRIDE_BYTES,
+                }
+            );
+        }
+
+        // Immutable globals are shared by every instance
+        let immutable_globals = stores
+            .binding_buffer(crate::IMMUTABLE_GLOBALS_BINDING_INDEX)
+            .unwrap();
+        let region = stores
+            .binding_instance_region(crate::IMMUTABLE_GLOBALS_BINDING_INDEX, 3)
+            .unwrap();
+        assert_eq!(region.offset, 0);
+        assert_eq!(region.chunk_bytes, immutable_globals.size());
+        assert_eq!(region.stride_bytes, immutable_globals.size());
+    }
 }
@@ -1,6 +1,6 @@
 use crate::externs::NamedExtern;
 use crate::func::FuncAccessiblePtrs;
-use crate::instance::data::{MappedDataInstance, UnmappedDataInstance};
+use crate::instance::data::{MappedDataInstance, SegmentDroppedFlags, UnmappedDataInstance};
 use crate::instance::element::{MappedElementInstance, UnmappedElementInstance};
 use crate::instance::func::{FuncsInstance, UntypedFuncPtr};
 use crate::instance::global::builder::{
@@ -17,13 +17,15 @@ use crate::instance::table::builder::{
 };
 use crate::instance::ModuleInstanceReferences;
 use crate::shader_module::WasmShaderModule;
-use crate::store_set::UnmappedStoreSetData;
+use crate::store_set::{TrapFlags, UnmappedStoreSetData};
 use crate::{DeviceStoreSet, Module, Tuneables};
+use anyhow::Context;
 use perfect_derive::perfect_derive;
 use std::sync::Arc;
-use wasm_gpu_funcgen::{AssembledModule, BuildError};
+use wasm_gpu_funcgen::{AssembledModule, BuildError, BINDING_TUPLES};
 use wasm_types::{ExternRef, FuncRef, Val, V128};
 use wasmparser::{HeapType, Operator};
+use wasmtime_environ::Trap;
 use wgpu::BufferAsyncError;
 use wgpu_async::async_device::OutOfMemoryError;
 use wgpu_async::async_queue::AsyncQueue;
@@ -94,12 +96,27 @@ pub(crate) async fn interpret_constexpr<'data>(
     return Ok(res);
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum StoreSetBuildError {
+    #[error("could not allocate store set as gpu was out of space")]
+    OoM(#[from] OutOfMemoryError),
+    #[error("could not read the results of a start function")]
+    BufferAsync(#[from] BufferAsyncError),
+    #[error("start function trapped: {0}")]
+    StartTrapped(Trap),
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum BuilderCompleteError {
     #[error("could not map memory as gpu was out of space")]
     OoM(DelayedOutOfMemoryError<MappedStoreSetBuilder>),
     #[error("could not build SPIR-V module")]
     BuildError(BuildError),
+    #[error("workgroup size {workgroup_size} is larger than the device supports, which is {max_workgroup_size}")]
+    WorkgroupSizeExceeded {
+        workgroup_size: u32,
+        max_workgroup_size: u32,
+    },
 }
 
 /// Acts like a traditional OOP factory where we initialise modules into this before
@@ -124,6 +141,8 @@ pub struct UnmappedStoreSetBuilder {
     immutable_globals: UnmappedImmutableGlobalsInstance,
 
     functions: FuncsInstance,
+    /// The start functions of the instantiated modules, in instantiation order, which are run when building
+    start_fns: Vec<UntypedFuncPtr>,
     tuneables: Tuneables,
 }
 
@@ -140,10 +159,17 @@ impl MappedStoreSetBuilder {
             datas: MappedDataInstance::new(memory_system, label),
 
             functions: FuncsInstance::new(),
+            start_fns: Vec::new(),
             tuneables,
         }
     }
 
+    /// Allocates space for each memory instantiated after this call to grow by the given number of pages
+    /// using `memory.grow`, up to the memory's maximum. Growing beyond the reserved space fails.
+    pub fn reserve_memory_pages(&mut self, pages: u32) {
+        self.memories.reserve_pages(pages);
+    }
+
     pub(crate) async fn snapshot(
         memory_system: &MemorySystem,
         queue: &AsyncQueue,
@@ -159,22 +185,30 @@ impl MappedStoreSetBuilder {
             datas,
             immutable_globals,
             shader_module: _,
+            sources: _,
             owned,
             tuneables,
         } = src;
 
         let functions = functions.as_ref().clone();
-        let elements = elements.as_ref().try_duplicate(queue).await?;
+        let mut elements = elements.as_ref().try_duplicate(queue).await?.map_lazy();
         let immutable_globals = immutable_globals.as_ref().try_duplicate(queue).await?;
-        let datas = datas.as_ref().try_duplicate(queue).await?;
+        let mut datas = datas.as_ref().try_duplicate(queue).await?.map_lazy();
 
         // Things that need to be un-interleaved
         let UnmappedStoreSetData {
             tables,
             memories,
             mutable_globals,
+            data_dropped,
+            element_dropped,
+            traps: _,
         } = owned;
 
+        // Segments dropped by the store being snapshotted stay dropped
+        datas.set_dropped_flags(data_dropped.get(store_index));
+        elements.set_dropped_flags(element_dropped.get(store_index));
+
         let tables =
             MappedTableInstanceSetBuilder::from_existing(memory_system, queue, tables, store_index)
                 .await?;
@@ -196,9 +230,11 @@ impl MappedStoreSetBuilder {
         Ok(Self {
             label: label.clone(),
             functions,
-            elements: elements.map_lazy(),
+            elements,
             immutable_globals: immutable_globals.map_lazy(),
-            datas: datas.map_lazy(),
+            datas,
+            // Start functions have already been run on the snapshotted instance
+            start_fns: Vec::new(),
             tuneables: tuneables.clone(),
             tables,
             memories,
@@ -290,9 +326,10 @@ impl MappedStoreSetBuilder {
         };
         module.try_initialize_function_bodies(&mut self.functions, &function_accessible_ptrs)?;
 
-        // Final setup, consisting of the Start function, must be performed in the build step if it
-        // calls any host functions
+        // Final setup, consisting of the Start function, is performed in the build step once the shader
+        // has been assembled
         let start_fn = module.start_fn(&func_ptrs);
+        self.start_fns.extend(start_fn.clone());
 
         // Lock vectors to be immutable
         let func_ptrs = func_ptrs.into_iter().collect();
@@ -300,7 +337,7 @@ impl MappedStoreSetBuilder {
         let memory_ptrs = memory_ptrs.into_iter().collect();
         let global_ptrs = global_ptrs.into_iter().collect();
         let exports = module
-            .exports()
+            .export_refs()
             .into_iter()
             .map(|(k, v)| (k.clone(), v.clone()))
             .collect();
@@ -314,10 +351,78 @@ impl MappedStoreSetBuilder {
         ));
     }
 
+    /// Instantiates each module in turn, providing the exports of every module instantiated so far as imports
+    /// to the next, under the name given with each module. Since all of the modules share this builder, calls
+    /// from one module to a function imported from another are lowered as direct calls within a single shader.
+    pub async fn link_modules(
+        &mut self,
+        queue: &AsyncQueue,
+        modules: &[(&str, &Module)],
+        imports: Vec<NamedExtern>,
+    ) -> anyhow::Result<Vec<ModuleInstanceReferences>> {
+        let mut imports = imports;
+        let mut instances = Vec::new();
+        for (name, module) in modules {
+            let instance = self
+                .instantiate_module(queue, module, imports.clone())
+                .await
+                .with_context(|| format!("failed to link module {}", name))?;
+
+            imports.append(&mut instance.get_named_exports(name));
+            instances.push(instance);
+        }
+
+        return Ok(instances);
+    }
+
     /// Takes this builder and makes it immutable, allowing instances to be created from it
     pub async fn complete(
         self,
         queue: &AsyncQueue,
+    ) -> Result<CompletedBuilder, BuilderCompleteError> {
+        self.complete_with(queue, |functions, tuneables| {
+            AssembledModule::assemble(functions, tuneables)
+        })
+        .await
+    }
+
+    /// As with `complete`, but reuses a module saved with `AssembledModule::serialize` rather than assembling the
+    /// module again, which is slow for modules that use many polyfills. The cached module must have been assembled
+    /// from the same wasm modules, instantiated in the same order.
+    ///
+    /// If the cached module is rejected, e.g. because it was saved by a different version of this crate, with
+    /// different tuneables or from different wasm, the module is assembled as normal and the reason is given by
+    /// `CompletedBuilder::cache_rejection`.
+    #[cfg(feature = "cache")]
+    pub async fn complete_cached(
+        self,
+        queue: &AsyncQueue,
+        cached: Option<&[u8]>,
+    ) -> Result<CompletedBuilder, BuilderCompleteError> {
+        let mut cache_rejection = None;
+        let mut completed = self
+            .complete_with(queue, |functions, tuneables| {
+                let Some(bytes) = cached else {
+                    return AssembledModule::assemble(functions, tuneables);
+                };
+                AssembledModule::deserialize(bytes, functions, tuneables).or_else(|err| {
+                    cache_rejection = Some(err);
+                    AssembledModule::assemble(functions, tuneables)
+                })
+            })
+            .await?;
+        completed.cache_rejection = cache_rejection;
+
+        Ok(completed)
+    }
+
+    async fn complete_with(
+        self,
+        queue: &AsyncQueue,
+        assemble: impl FnOnce(
+            &wasm_gpu_funcgen::FuncsInstance,
+            &Tuneables,
+        ) -> Result<AssembledModule, BuildError>,
     ) -> Result<CompletedBuilder, BuilderCompleteError> {
         let UnmappedStoreSetBuilder {
             label,
@@ -330,30 +435,104 @@ impl MappedStoreSetBuilder {
             immutable_globals,
 
             functions,
+            start_fns,
             tuneables,
         } = self
             .try_unmap(queue)
             .await
             .map_err(BuilderCompleteError::OoM)?;
 
+        // Devices which can't provide a storage buffer per binding need some of them packing together
+        let mut tuneables = tuneables;
+        let max_storage_buffers = queue.device().limits().max_storage_buffers_per_shader_stage;
+        if max_storage_buffers < BINDING_TUPLES.len() as u32 {
+            tuneables.pack_io_bindings = true;
+        }
+
+        let limits = queue.device().limits();
+        let max_workgroup_size = u32::min(
+            limits.max_compute_workgroup_size_x,
+            limits.max_compute_invocations_per_workgroup,
+        );
+        if tuneables.workgroup_size > max_workgroup_size {
+            return Err(BuilderCompleteError::WorkgroupSizeExceeded {
+                workgroup_size: tuneables.workgroup_size,
+                max_workgroup_size,
+            });
+        }
+
         let assembleable_functions = functions.assembleable();
-        let assembled_module = AssembledModule::assemble(&assembleable_functions, &tuneables)
+        let assembled_module = assemble(&assembleable_functions, &tuneables)
             .map_err(BuilderCompleteError::BuildError)?;
 
-        let shader_module = WasmShaderModule::make(queue.device(), &assembled_module);
+        let shader_module = WasmShaderModule::make(queue.device(), &assembled_module, &tuneables);
 
         Ok(CompletedBuilder {
             label,
-            tables,
-            memories,
-            mutable_globals,
+            sources: Arc::new(InstanceSources {
+                tables,
+                memories,
+                mutable_globals,
+                data_dropped: datas.dropped_flags().to_vec(),
+                element_dropped: elements.dropped_flags().to_vec(),
+            }),
             elements: Arc::new(elements),
             immutable_globals: Arc::new(immutable_globals),
             datas: Arc::new(datas),
             functions: Arc::new(functions),
             shader_module: Arc::new(shader_module),
             assembled_module,
+            start_fns,
             tuneables,
+            #[cfg(feature = "cache")]
+            cache_rejection: None,
+        })
+    }
+}
+
+/// The initial state of the tables, memories and mutable globals of every instance. Built store sets
+/// keep hold of this so that they can add more instances after being built.
+#[derive(Debug)]
+pub(crate) struct InstanceSources {
+    pub(crate) tables: UnmappedTableInstanceSetBuilder,
+    pub(crate) memories: UnmappedMemoryInstanceSetBuilder,
+    pub(crate) mutable_globals: UnmappedMutableGlobalsInstanceBuilder,
+    pub(crate) data_dropped: Vec<u32>,
+    pub(crate) element_dropped: Vec<u32>,
+}
+
+impl InstanceSources {
+    /// Takes the current state of the given instance in a store set, to initialise new instances with.
+    async fn try_from_instance(
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        owned: &UnmappedStoreSetData,
+        instance_index: usize,
+    ) -> Result<Self, OutOfMemoryError> {
+        Ok(Self {
+            tables: UnmappedTableInstanceSetBuilder::try_from_instance(
+                memory_system,
+                queue,
+                &owned.tables,
+                instance_index,
+            )
+            .await?,
+            memories: UnmappedMemoryInstanceSetBuilder::try_from_instance(
+                memory_system,
+                queue,
+                &owned.memories,
+                instance_index,
+            )
+            .await?,
+            mutable_globals: UnmappedMutableGlobalsInstanceBuilder::try_from_instance(
+                memory_system,
+                queue,
+                &owned.mutable_globals,
+                instance_index,
+            )
+            .await?,
+            data_dropped: owned.data_dropped.get(instance_index),
+            element_dropped: owned.element_dropped.get(instance_index),
         })
     }
 }
@@ -362,9 +541,7 @@ pub struct CompletedBuilder {
     /// Used for debugging
     label: String,
 
-    tables: UnmappedTableInstanceSetBuilder,
-    memories: UnmappedMemoryInstanceSetBuilder,
-    mutable_globals: UnmappedMutableGlobalsInstanceBuilder,
+    sources: Arc<InstanceSources>,
     immutable_globals: Arc<UnmappedImmutableGlobalsInstance>,
     elements: Arc<UnmappedElementInstance>,
     datas: Arc<UnmappedDataInstance>,
@@ -375,7 +552,11 @@ pub struct CompletedBuilder {
     /// hoisting this is for optimisation reasons.
     shader_module: Arc<WasmShaderModule>,
     assembled_module: AssembledModule,
+    start_fns: Vec<UntypedFuncPtr>,
     tuneables: Tuneables,
+    /// Why the module given to `complete_cached` wasn't used, if it wasn't
+    #[cfg(feature = "cache")]
+    cache_rejection: Option<BuildError>,
 }
 
 impl CompletedBuilder {
@@ -383,16 +564,70 @@ impl CompletedBuilder {
         &self.assembled_module
     }
 
+    /// If this builder was completed with `complete_cached` but the cached module was rejected, gives the reason. The
+    /// module was assembled again instead.
+    #[cfg(feature = "cache")]
+    pub fn cache_rejection(&self) -> Option<&BuildError> {
+        self.cache_rejection.as_ref()
+    }
+
     /// Takes the instructions provided to this builder and produces a collection of stores which can
     /// be used to evaluate instructions. We take all of the initialisation that we did that can be shared and
     /// spin it into several instances. This shouldn't involve moving any data to the device, instead data
     /// that has already been provided to the device should be cloned and specialised as needed for a
     /// collection of instances.
+    ///
+    /// The start functions of the instantiated modules are run before this returns, so every instance begins
+    /// in the state left by the start functions.
     pub async fn build(
         &self,
         memory_system: &MemorySystem,
         queue: &AsyncQueue,
         count: usize,
+    ) -> Result<DeviceStoreSet, StoreSetBuildError> {
+        let sources = self.started_sources(memory_system, queue).await?;
+
+        Ok(self
+            .build_from_sources(memory_system, queue, sources, count)
+            .await?)
+    }
+
+    /// Runs the start functions on a single instance, then takes its state as the initial state of every
+    /// instance. Since start functions take no arguments, this is the same as running them on each instance
+    /// but runs them only once, even when instances share memory.
+    async fn started_sources(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+    ) -> Result<Arc<InstanceSources>, StoreSetBuildError> {
+        if self.start_fns.is_empty() {
+            return Ok(self.sources.clone());
+        }
+
+        let mut stores = self
+            .build_from_sources(memory_system, queue, self.sources.clone(), 1)
+            .await?;
+        for start_fn in &self.start_fns {
+            let results = start_fn
+                .call_all_broadcast(memory_system, queue, &mut stores, vec![], 1)
+                .await?
+                .await?;
+            if let Some(trap) = results.into_iter().find_map(Result::err) {
+                return Err(StoreSetBuildError::StartTrapped(trap));
+            }
+        }
+
+        let sources =
+            InstanceSources::try_from_instance(memory_system, queue, &stores.owned, 0).await?;
+        Ok(Arc::new(sources))
+    }
+
+    async fn build_from_sources(
+        &self,
+        memory_system: &MemorySystem,
+        queue: &AsyncQueue,
+        sources: Arc<InstanceSources>,
+        count: usize,
     ) -> Result<DeviceStoreSet, OutOfMemoryError> {
         let duplication_count = if self.tuneables.disjoint_memory {
             count
@@ -400,21 +635,25 @@ impl CompletedBuilder {
             1
         };
 
-        let tables = self
+        let tables = sources
             .tables
             .try_build(memory_system, queue, duplication_count)
             .await?;
 
-        let memories = self
+        let memories = sources
             .memories
             .try_build(memory_system, queue, duplication_count)
             .await?;
 
-        let mutable_globals = self
+        let mutable_globals = sources
             .mutable_globals
             .try_build(memory_system, queue, duplication_count)
             .await?;
 
+        let data_dropped = SegmentDroppedFlags::new(&sources.data_dropped, count);
+        let element_dropped = SegmentDroppedFlags::new(&sources.element_dropped, count);
+        let traps = TrapFlags::new(count);
+
         Ok(DeviceStoreSet {
             label: format!("{}_built", self.label),
 
@@ -423,10 +662,14 @@ impl CompletedBuilder {
             datas: self.datas.clone(),
             immutable_globals: self.immutable_globals.clone(),
             shader_module: self.shader_module.clone(),
+            sources,
             owned: UnmappedStoreSetData {
                 tables,
                 memories,
                 mutable_globals,
+                data_dropped,
+                element_dropped,
+                traps,
             },
             tuneables: self.tuneables,
         })
@@ -435,10 +678,13 @@ impl CompletedBuilder {
 
 #[cfg(test)]
 mod tests {
+    use super::StoreSetBuildError;
     use crate::unit_tests_lib::{gen_test_memory_string, get_backend};
     use crate::{block_test, imports, MappedStoreSetBuilder};
     use anyhow::anyhow;
     use std::sync::Arc;
+    #[cfg(feature = "cache")]
+    use wasm_gpu_funcgen::BuildError;
     macro_rules! data_tests {
         ($($value:expr),* $(,)?) => {
         $(
@@ -491,4 +737,892 @@ mod tests {
 
         assert_eq!(buffers.try_read_all(&queue).await.unwrap(), expected_data)
     }
+
+    #[tokio::test]
+    async fn test_identical_functions_share_body() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+
+        let wat = r#"
+            (module
+                (func $f1 (param i32) (result i32)
+                    (local.get 0)
+                    (i32.const 7)
+                    (i32.mul)
+                )
+                (func $f2 (param i32) (result i32)
+                    (local.get 0)
+                    (i32.const 7)
+                    (i32.mul)
+                )
+                (func $f3 (param i32) (result i32)
+                    (local.get 0)
+                    (i32.const 7)
+                    (i32.mul)
+                )
+                (export "f1" (func $f1))
+                (export "f2" (func $f2))
+                (export "f3" (func $f3))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let _instance = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+
+        let set = match stores_builder.complete(&queue).await {
+            Ok(set) => set,
+            Err(e) => panic!("{:#?}", e),
+        };
+
+        let naga_module = &set.get_module().module;
+        let base_function_count = naga_module
+            .functions
+            .iter()
+            .filter(|(_, function)| {
+                function
+                    .name
+                    .as_ref()
+                    .is_some_and(|name| name.ends_with("_base_impl"))
+            })
+            .count();
+        assert_eq!(base_function_count, 1);
+        assert_eq!(naga_module.entry_points.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_extend_then_wrap_is_elided() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+
+        let wat = r#"
+            (module
+                (func $f (param i32) (result i32)
+                    (local.get 0)
+                    (i64.extend_i32_u)
+                    (i32.wrap_i64)
+                    (i64.extend_i32_s)
+                    (i32.wrap_i64)
+                )
+                (export "f" (func $f))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instance = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let f = instance
+            .get_func("f")
+            .unwrap()
+            .try_typed::<i32, i32>()
+            .unwrap();
+
+        let completed = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+
+        // No i64 should have been constructed within the function body
+        let naga_module = &completed.get_module().module;
+        let is_i64 = |ty: naga::Handle<naga::Type>| {
+            matches!(
+                naga_module.types[ty].inner,
+                naga::TypeInner::Vector {
+                    size: naga::VectorSize::Bi,
+                    scalar: naga::Scalar::U32,
+                }
+            )
+        };
+        let (_, function) = naga_module
+            .functions
+            .iter()
+            .find(|(_, function)| {
+                function
+                    .name
+                    .as_ref()
+                    .is_some_and(|name| name.ends_with("_base_impl"))
+            })
+            .expect("function was lowered");
+        assert!(function
+            .local_variables
+            .iter()
+            .all(|(_, local)| !is_i64(local.ty)));
+        assert!(function
+            .expressions
+            .iter()
+            .all(|(_, expression)| match expression {
+                naga::Expression::Compose { ty, .. } | naga::Expression::ZeroValue(ty) =>
+                    !is_i64(*ty),
+                _ => true,
+            }));
+
+        let mut stores = completed
+            .build(&memory_system, &queue, 4)
+            .await
+            .expect("could not build stores");
+        let inputs = vec![0, 1, -1, i32::MIN];
+        let results = f
+            .call_all(&memory_system, &queue, &mut stores, inputs.clone())
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(results.len(), inputs.len());
+        for (input, result) in inputs.into_iter().zip(results) {
+            assert_eq!(result.expect("function does not trap"), input);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_linked_modules_share_shader() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+
+        let core = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            r#"
+            (module
+                (func $add (param i32 i32) (result i32)
+                    (local.get 0)
+                    (local.get 1)
+                    (i32.add)
+                )
+                (export "add" (func $add))
+            )
+            "#
+            .as_bytes(),
+            "core".to_owned(),
+        )
+        .unwrap();
+        let plugin = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            r#"
+            (module
+                (import "core" "add" (func $add (param i32 i32) (result i32)))
+                (func $add_one (param i32) (result i32)
+                    (local.get 0)
+                    (i32.const 1)
+                    (call $add)
+                )
+                (export "plugin_add" (func $add))
+                (export "add_one" (func $add_one))
+            )
+            "#
+            .as_bytes(),
+            "plugin".to_owned(),
+        )
+        .unwrap();
+
+        let instances = stores_builder
+            .link_modules(&queue, &[("core", &core), ("plugin", &plugin)], imports! {})
+            .await
+            .expect("could not link modules");
+        assert_eq!(instances.len(), 2);
+
+        let core_add = instances[0].get_func("add").unwrap();
+        let plugin_add = instances[1].get_func("plugin_add").unwrap();
+        assert_eq!(core_add.to_func_ref(), plugin_add.to_func_ref());
+        let plugin_add = plugin_add.try_typed::<(i32, i32), i32>().unwrap();
+        let add_one = instances[1]
+            .get_func("add_one")
+            .unwrap()
+            .try_typed::<i32, i32>()
+            .unwrap();
+
+        let completed = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete linked modules");
+        let mut stores = completed
+            .build(&memory_system, &queue, 4)
+            .await
+            .expect("could not build stores");
+
+        let results = plugin_add
+            .call_all(&memory_system, &queue, &mut stores, vec![(3, 4); 4])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(results.len(), 4);
+        for result in results {
+            assert_eq!(result.expect("function does not trap"), 7);
+        }
+
+        let inputs = vec![0, 1, -1, 41];
+        let results = add_one
+            .call_all(&memory_system, &queue, &mut stores, inputs.clone())
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(results.len(), inputs.len());
+        for (input, result) in inputs.into_iter().zip(results) {
+            assert_eq!(result.expect("function does not trap"), input + 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_start_function_runs_once_before_calls() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+
+        // The start function accumulates, so running it more than once per instance would be visible
+        let wat = r#"
+            (module
+                (memory 1)
+                (data (i32.const 8) "\01")
+                (func $init
+                    (i32.store (i32.const 8)
+                        (i32.add (i32.load (i32.const 8)) (i32.const 41))
+                    )
+                )
+                (func $read (result i32)
+                    (i32.load (i32.const 8))
+                )
+                (start $init)
+                (export "read" (func $read))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instance = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let read = instance
+            .get_func("read")
+            .unwrap()
+            .try_typed::<(), i32>()
+            .unwrap();
+
+        let completed = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let mut stores = completed
+            .build(&memory_system, &queue, 4)
+            .await
+            .expect("could not build stores");
+
+        let results = read
+            .call_all(&memory_system, &queue, &mut stores, vec![(); 4])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(results, vec![Ok(42); 4]);
+
+        // Instances added later also start in the state left by the start function
+        stores
+            .grow(&memory_system, &queue, 2)
+            .await
+            .expect("could not grow stores");
+        let results = read
+            .call_all(&memory_system, &queue, &mut stores, vec![(); 6])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(results, vec![Ok(42); 6]);
+    }
+
+    #[tokio::test]
+    async fn test_trapping_start_function_fails_build() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+
+        let wat = r#"
+            (module
+                (func $init
+                    (unreachable)
+                )
+                (start $init)
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+
+        let completed = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let err = completed
+            .build(&memory_system, &queue, 4)
+            .await
+            .err()
+            .expect("a trapping start function should fail the build");
+        assert!(matches!(
+            err,
+            StoreSetBuildError::StartTrapped(wasmtime_environ::Trap::UnreachableCodeReached)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_function_stats_report_sizes_and_callees() {
+        let (memory_system, queue) = get_backend();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+
+        let wat = r#"
+            (module
+                (func $small (param i32) (result i32)
+                    (local.get 0)
+                )
+                (func $big (param i32) (result i32)
+                    (i32.mul
+                        (i32.add (call $small (local.get 0)) (i32.const 3))
+                        (i32.sub (local.get 0) (i32.const 7))
+                    )
+                )
+                (export "small" (func $small))
+                (export "big" (func $big))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let instance = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let function_index = |name: &str| {
+            instance
+                .get_func(name)
+                .unwrap()
+                .to_func_ref()
+                .as_u32()
+                .unwrap() as usize
+        };
+        let small = function_index("small");
+        let big = function_index("big");
+
+        let completed = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let stats = completed.get_module().function_stats();
+
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[small].function_index, small);
+        assert_eq!(stats[big].function_index, big);
+        assert!(stats[small].callees.is_empty());
+        assert_eq!(stats[big].callees, vec![small]);
+        assert!(stats[big].expression_count > stats[small].expression_count);
+        assert!(
+            stats[big].estimated_spirv_instructions > stats[small].estimated_spirv_instructions
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unused_functions_are_pruned() {
+        let (memory_system, queue) = get_backend();
+
+        let wat = r#"
+            (module
+                (func $f (param i32) (result i32)
+                    (i32.add (local.get 0) (i32.const 5))
+                )
+                (export "add_5" (func $f))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let mut function_counts = Vec::new();
+        for prune_unused in [true, false] {
+            let mut stores_builder = MappedStoreSetBuilder::new(
+                &memory_system,
+                "test_module",
+                crate::Tuneables {
+                    prune_unused,
+                    ..Default::default()
+                },
+            );
+            stores_builder
+                .instantiate_module(&queue, &module, imports! {})
+                .await
+                .expect("could not instantiate all modules");
+            let completed = stores_builder
+                .complete(&queue)
+                .await
+                .expect("could not complete store builder");
+
+            let assembled = completed.get_module();
+            if prune_unused {
+                assert!(!assembled.generate_wgsl_source().unwrap().contains("f32_div"));
+            }
+            function_counts.push(assembled.module.functions.len());
+        }
+
+        let [pruned, unpruned]: [usize; 2] = function_counts.try_into().unwrap();
+        assert!(pruned < unpruned);
+    }
+
+    #[tokio::test]
+    async fn test_identical_functions_are_merged() {
+        let (memory_system, queue) = get_backend();
+
+        let wat = r#"
+            (module
+                (memory 1)
+                (func $f (param i32) (result i32)
+                    (i32.add (i32.load (local.get 0)) (i32.load offset=4 (local.get 0)))
+                )
+                (func $g (param i32) (result f32)
+                    (f32.add (f32.load offset=8 (local.get 0)) (f32.load offset=12 (local.get 0)))
+                )
+                (export "f" (func $f))
+                (export "g" (func $g))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let mut function_counts = Vec::new();
+        for deduplicate_functions in [true, false] {
+            let mut stores_builder = MappedStoreSetBuilder::new(
+                &memory_system,
+                "test_module",
+                crate::Tuneables {
+                    deduplicate_functions,
+                    prune_unused: false,
+                    ..Default::default()
+                },
+            );
+            let instance = stores_builder
+                .instantiate_module(&queue, &module, imports! {})
+                .await
+                .expect("could not instantiate all modules");
+            let f = instance
+                .get_func("f")
+                .unwrap()
+                .try_typed::<i32, i32>()
+                .unwrap();
+            let completed = stores_builder
+                .complete(&queue)
+                .await
+                .expect("could not complete store builder");
+
+            let functions = &completed.get_module().module.functions;
+            if deduplicate_functions {
+                // No two functions are left with the same body
+                let bodies = functions
+                    .iter()
+                    .map(|(_, function)| {
+                        let mut function = function.clone();
+                        function.name = None;
+                        format!("{:?}", function)
+                    })
+                    .collect::<std::collections::HashSet<_>>();
+                assert_eq!(bodies.len(), functions.len());
+            }
+            function_counts.push(functions.len());
+
+            // Merging doesn't change behaviour
+            let mut stores = completed
+                .build(&memory_system, &queue, 4)
+                .await
+                .expect("could not build stores");
+            let results = f
+                .call_all(&memory_system, &queue, &mut stores, vec![0; 4])
+                .await
+                .expect("could not allocate call buffers")
+                .await
+                .expect("could not read results buffers");
+            assert_eq!(results, vec![Ok(0); 4]);
+        }
+
+        let [merged, unmerged]: [usize; 2] = function_counts.try_into().unwrap();
+        assert!(merged <= unmerged);
+    }
+
+    #[cfg(feature = "cache")]
+    async fn complete_scaling_module(
+        memory_system: &wgpu_lazybuffers::MemorySystem,
+        queue: &wgpu_async::async_queue::AsyncQueue,
+        factor: i32,
+        tuneables: crate::Tuneables,
+        cached: Option<&[u8]>,
+    ) -> (super::CompletedBuilder, crate::TypedFuncPtr<i32, i32>) {
+        let wat = format!(
+            r#"
+            (module
+                (func $f (param i32) (result i32)
+                    (i32.mul (local.get 0) (i32.const {}))
+                )
+                (export "scale" (func $f))
+            )
+        "#,
+            factor
+        );
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(memory_system, "test_module", tuneables);
+        let instance = stores_builder
+            .instantiate_module(queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let scale = instance
+            .get_func("scale")
+            .unwrap()
+            .try_typed::<i32, i32>()
+            .unwrap();
+        let completed = stores_builder
+            .complete_cached(queue, cached)
+            .await
+            .expect("could not complete store builder");
+
+        (completed, scale)
+    }
+
+    #[cfg(feature = "cache")]
+    #[tokio::test]
+    async fn test_cached_module_is_reused() {
+        let (memory_system, queue) = get_backend();
+
+        let (completed, _) =
+            complete_scaling_module(&memory_system, &queue, 3, Default::default(), None).await;
+        assert!(completed.cache_rejection().is_none());
+        let cached = completed.get_module().serialize();
+
+        let (completed, triple) =
+            complete_scaling_module(&memory_system, &queue, 3, Default::default(), Some(&cached))
+                .await;
+        assert!(completed.cache_rejection().is_none());
+        // Modules loaded from a cache don't know the wasm functions that they were assembled from, so this shows
+        // that the cached module was used
+        assert!(completed.get_module().function_stats().is_empty());
+
+        let mut stores = completed
+            .build(&memory_system, &queue, 4)
+            .await
+            .expect("could not build stores");
+        let results = triple
+            .call_all(&memory_system, &queue, &mut stores, vec![1, 2, 3, 4])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(results, vec![Ok(3), Ok(6), Ok(9), Ok(12)]);
+    }
+
+    #[cfg(feature = "cache")]
+    #[tokio::test]
+    async fn test_mismatched_cached_module_is_rejected() {
+        let (memory_system, queue) = get_backend();
+
+        let (completed, _) =
+            complete_scaling_module(&memory_system, &queue, 3, Default::default(), None).await;
+        let cached = completed.get_module().serialize();
+
+        // Caches are rejected if the tuneables differ
+        let other_tuneables = crate::Tuneables {
+            workgroup_size: 64,
+            ..Default::default()
+        };
+        let (completed, _) =
+            complete_scaling_module(&memory_system, &queue, 3, other_tuneables, Some(&cached))
+                .await;
+        assert!(matches!(
+            completed.cache_rejection(),
+            Some(BuildError::InvalidCache { .. })
+        ));
+
+        // Caches are rejected if they are truncated
+        let (completed, _) = complete_scaling_module(
+            &memory_system,
+            &queue,
+            3,
+            Default::default(),
+            Some(&cached[..8]),
+        )
+        .await;
+        assert!(matches!(
+            completed.cache_rejection(),
+            Some(BuildError::InvalidCache { .. })
+        ));
+
+        // Caches are rejected if they were assembled from different wasm, and the module is assembled from the given
+        // wasm instead
+        let (completed, quadruple) =
+            complete_scaling_module(&memory_system, &queue, 4, Default::default(), Some(&cached))
+                .await;
+        assert!(matches!(
+            completed.cache_rejection(),
+            Some(BuildError::InvalidCache { .. })
+        ));
+        assert!(!completed.get_module().function_stats().is_empty());
+
+        let mut stores = completed
+            .build(&memory_system, &queue, 4)
+            .await
+            .expect("could not build stores");
+        let results = quadruple
+            .call_all(&memory_system, &queue, &mut stores, vec![1, 2, 3, 4])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(results, vec![Ok(4), Ok(8), Ok(12), Ok(16)]);
+    }
+
+    #[tokio::test]
+    async fn test_parallel_codegen_matches_sequential_codegen() {
+        let (memory_system, queue) = get_backend();
+
+        // Each function appends its own constants, which must be merged back into the module
+        let wat = r#"
+            (module
+                (func $scale (param i64) (result i64)
+                    (i64.add (local.get 0) (i64.const 3000000000))
+                )
+                (func $offset (param i64) (result i64)
+                    (i64.add (call $scale (local.get 0)) (i64.const 17))
+                )
+                (func $f (param i64) (result i64)
+                    (i64.add (call $offset (local.get 0)) (i64.const 5))
+                )
+                (export "f" (func $f))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let mut all_results = Vec::new();
+        for parallel_codegen in [true, false] {
+            let mut stores_builder = MappedStoreSetBuilder::new(
+                &memory_system,
+                "test_module",
+                crate::Tuneables {
+                    parallel_codegen,
+                    ..Default::default()
+                },
+            );
+            let instance = stores_builder
+                .instantiate_module(&queue, &module, imports! {})
+                .await
+                .expect("could not instantiate all modules");
+            let f = instance
+                .get_func("f")
+                .unwrap()
+                .try_typed::<i64, i64>()
+                .unwrap();
+            let completed = stores_builder
+                .complete(&queue)
+                .await
+                .expect("could not complete store builder");
+            let mut stores = completed
+                .build(&memory_system, &queue, 4)
+                .await
+                .expect("could not build stores");
+            let results = f
+                .call_all(&memory_system, &queue, &mut stores, vec![0, 1, 2, 3])
+                .await
+                .expect("could not allocate call buffers")
+                .await
+                .expect("could not read results buffers");
+            all_results.push(results);
+        }
+
+        let expected = (0..4i64)
+            .map(|i| Ok(i + 3000000000 + 22))
+            .collect::<Vec<_>>();
+        assert_eq!(all_results, vec![expected.clone(), expected]);
+    }
+
+    #[tokio::test]
+    async fn test_f64_is_emulated_without_float64_capability() {
+        let (memory_system, queue) = get_backend();
+
+        let wat = r#"
+            (module
+                (func $f (param f64 f64) (result f64)
+                    (f64.add (local.get 0) (local.get 1))
+                )
+                (export "add" (func $f))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures::default(),
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        // Native f64 is requested, but the device is declared not to support it
+        let mut stores_builder = MappedStoreSetBuilder::new(
+            &memory_system,
+            "test_module",
+            crate::Tuneables {
+                fp_options: crate::FloatingPointOptions {
+                    emulate_f64: false,
+                    ..Default::default()
+                },
+                capabilities: Some(naga::valid::Capabilities::empty()),
+                ..Default::default()
+            },
+        );
+        let instance = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let add = instance
+            .get_func("add")
+            .unwrap()
+            .try_typed::<(f64, f64), f64>()
+            .unwrap();
+        let completed = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+
+        // The polyfill is made of 32-bit words, so no 64-bit float types are declared
+        let uses_native_f64 = completed
+            .get_module()
+            .module
+            .types
+            .iter()
+            .any(|(_, ty)| ty.inner == naga::TypeInner::Scalar(naga::Scalar::F64));
+        assert!(!uses_native_f64);
+
+        let mut stores = completed
+            .build(&memory_system, &queue, 2)
+            .await
+            .expect("could not build stores");
+        let results = add
+            .call_all(
+                &memory_system,
+                &queue,
+                &mut stores,
+                vec![(1.5, 2.25), (-0.1, 1e300)],
+            )
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+        assert_eq!(results, vec![Ok(3.75), Ok(-0.1 + 1e300)]);
+    }
+
+    #[tokio::test]
+    async fn test_multiple_memories_are_separate() {
+        let (memory_system, queue) = get_backend();
+
+        let wat = r#"
+            (module
+                (memory $a 1)
+                (memory $b 2)
+                (data (memory $a) (i32.const 0) "\01")
+                (data (memory $b) (i32.const 0) "\02")
+                (func $f (param i32) (result i32 i32 i32 i32)
+                    (i32.store $b (i32.const 4) (local.get 0))
+                    (i32.load $a (i32.const 0))
+                    (i32.load $a (i32.const 4))
+                    (i32.add (i32.load $b (i32.const 0)) (i32.load $b (i32.const 4)))
+                    (memory.size $b)
+                )
+                (export "f" (func $f))
+            )
+        "#;
+        let module = crate::Module::new(
+            &wasmparser::WasmFeatures {
+                multi_memory: true,
+                ..Default::default()
+            },
+            wat.as_bytes(),
+            "test_module".to_owned(),
+        )
+        .unwrap();
+
+        let mut stores_builder =
+            MappedStoreSetBuilder::new(&memory_system, "test_module", Default::default());
+        let instance = stores_builder
+            .instantiate_module(&queue, &module, imports! {})
+            .await
+            .expect("could not instantiate all modules");
+        let f = instance
+            .get_func("f")
+            .unwrap()
+            .try_typed::<i32, (i32, i32, i32, i32)>()
+            .unwrap();
+        let completed = stores_builder
+            .complete(&queue)
+            .await
+            .expect("could not complete store builder");
+        let mut stores = completed
+            .build(&memory_system, &queue, 2)
+            .await
+            .expect("could not build stores");
+
+        let results = f
+            .call_all(&memory_system, &queue, &mut stores, vec![5, -1])
+            .await
+            .expect("could not allocate call buffers")
+            .await
+            .expect("could not read results buffers");
+
+        // Writing to the second memory leaves the first untouched
+        assert_eq!(results, vec![Ok((1, 0, 7, 2)), Ok((1, 0, 1, 2))]);
+    }
 }
@@ -8,7 +8,7 @@ use rand::{RngCore, SeedableRng};
 use wgpu_async::async_queue::AsyncQueue;
 use wgpu_lazybuffers::{BufferRingConfig, MemorySystem};
 
-async fn new_backend() -> (MemorySystem, AsyncQueue) {
+async fn new_backend(max_storage_buffers: Option<u32>) -> (MemorySystem, AsyncQueue) {
     let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
         backends: wgpu::Backends::all(),
         dx12_shader_compiler: wgpu::Dx12Compiler::Fxc,
@@ -23,12 +23,20 @@ async fn new_backend() -> (MemorySystem, AsyncQueue) {
         .await
         .expect("could not aquire a test adapter");
 
+    let mut limits = adapter.limits();
+    if let Some(max_storage_buffers) = max_storage_buffers {
+        limits.max_storage_buffers_per_shader_stage = u32::min(
+            limits.max_storage_buffers_per_shader_stage,
+            max_storage_buffers,
+        );
+    }
+
     let (device, queue) = adapter
         .request_device(
             &wgpu::DeviceDescriptor {
                 label: None,
                 features: adapter.features(),
-                limits: adapter.limits(),
+                limits,
             },
             None,
         )
@@ -54,12 +62,18 @@ struct WgpuState {
 
 static GPU_STATE: OnceCell<WgpuState> = OnceCell::new();
 fn gpu<'a>() -> &'a WgpuState {
-    GPU_STATE.get_or_init(WgpuState::new)
+    GPU_STATE.get_or_init(|| WgpuState::new(None))
+}
+
+// Many mobile and WebGL adapters only provide 8 storage buffers per shader stage
+static LIMITED_GPU_STATE: OnceCell<WgpuState> = OnceCell::new();
+fn limited_gpu<'a>() -> &'a WgpuState {
+    LIMITED_GPU_STATE.get_or_init(|| WgpuState::new(Some(8)))
 }
 
 impl WgpuState {
-    fn new() -> Self {
-        let (memory_system, queue) = pollster::block_on(new_backend());
+    fn new(max_storage_buffers: Option<u32>) -> Self {
+        let (memory_system, queue) = pollster::block_on(new_backend(max_storage_buffers));
         Self {
             memory_system,
             queue,
@@ -71,6 +85,11 @@ pub fn get_backend<'a>() -> (&'a MemorySystem, &'a AsyncQueue) {
     (&gpu().memory_system, &gpu().queue)
 }
 
+/// A backend which can bind at most 8 storage buffers per shader stage
+pub fn get_limited_backend<'a>() -> (&'a MemorySystem, &'a AsyncQueue) {
+    (&limited_gpu().memory_system, &limited_gpu().queue)
+}
+
 #[macro_export]
 macro_rules! block_test {
     ($value:expr, $name:ident) => {
@@ -1,6 +1,11 @@
 //! A collection of hand-written programs and tests that they evaluate to the expected result.
 //! Uses Wasmtime as a reference implementation
-use wasm_gpu_test_lib::{test_parity, test_parity_set};
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use wasm_gpu_test_lib::{
+    call_all_in_turn, test_parity, test_parity_set, test_parity_set_with_tuneables,
+    test_parity_with_tuneables,
+};
 
 macro_rules! do_test {
     ($test_name:ident( $($input:expr),* $(,)? )) => {
@@ -150,6 +155,68 @@ async fn pass_return_f64() {
     .await
 }
 
+#[tokio::test]
+async fn i64_sign_to_f64() {
+    test_parity_set::<(i64,), f64>(
+        r#"
+        (module
+            (func $f (param i64) (result f64)
+                (if (result f64) (i64.lt_s (local.get 0) (i64.const 0))
+                    (then (f64.const -1.5))
+                    (else (f64.const 2.25))
+                )
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        // Values which differ in only one of their two words
+        vec![(0,), (1,), (4294967296,), (-1,), (-4294967296,)],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn swap_i64_and_f64() {
+    test_parity::<(i64, f64), (f64, i64)>(
+        r#"
+        (module
+            (func $f (param i64 f64) (result f64 i64)
+                (local.get 1)
+                (local.get 0)
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        (0x0123456789ABCDEF, -2.000000000001f64),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn swap_i64_and_f64_with_aligned_io() {
+    test_parity_with_tuneables::<(i64, f64), (f64, i64)>(
+        r#"
+        (module
+            (func $f (param i64 f64) (result f64 i64)
+                (local.get 1)
+                (local.get 0)
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        (-0x0123456789ABCDEF, 1e300f64),
+        wasm_gpu::Tuneables {
+            io_argument_alignment_words: 4,
+            io_invocation_alignment_words: 4,
+            ..Default::default()
+        },
+    )
+    .await
+}
+
 #[tokio::test]
 async fn pass_through_local_return_i32() {
     test_parity::<i32, i32>(
@@ -303,6 +370,25 @@ async fn add_5_i64() {
     .await
 }
 
+#[tokio::test]
+async fn add_carries_between_words_i64() {
+    test_parity::<i64, i64>(
+        r#"
+        (module
+            (func $f (param i64) (result i64)
+                (local.get 0)
+                (i64.const 1)
+                (i64.add)
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        4294967295,
+    )
+    .await
+}
+
 #[tokio::test]
 async fn add_5_f32() {
     test_parity::<f32, f32>(
@@ -322,6 +408,123 @@ async fn add_5_f32() {
     .await
 }
 
+/// NaN payloads are allowed to differ, so we check NaN-ness within wasm rather than comparing bits
+async fn f32_add_is_nan(lhs: &str, rhs: &str) {
+    test_parity::<(), i32>(
+        &format!(
+            r#"
+            (module
+                (func $f (result i32)
+                    (local f32)
+                    (f32.const {})
+                    (f32.const {})
+                    (f32.add)
+                    (local.tee 0)
+                    (local.get 0)
+                    (f32.ne)
+                )
+                (export "foi" (func $f))
+            )
+            "#,
+            lhs, rhs
+        ),
+        "foi",
+        (),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn add_nan_to_one_f32() {
+    f32_add_is_nan("nan", "1.0").await
+}
+
+#[tokio::test]
+async fn add_one_to_nan_f32() {
+    f32_add_is_nan("1.0", "nan").await
+}
+
+#[tokio::test]
+async fn add_nan_to_nan_f32() {
+    f32_add_is_nan("nan", "nan").await
+}
+
+async fn f32_mul(lhs: f32, rhs: f32) {
+    test_parity::<(f32, f32), f32>(
+        r#"
+        (module
+            (func $f (param f32 f32) (result f32)
+                (local.get 0)
+                (local.get 1)
+                (f32.mul)
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        (lhs, rhs),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn mul_overflow_to_inf_f32() {
+    f32_mul(3.4e38, 10.0).await
+}
+
+#[tokio::test]
+async fn mul_overflow_to_neg_inf_f32() {
+    f32_mul(-3.4e38, 10.0).await
+}
+
+#[tokio::test]
+async fn mul_both_large_overflow_to_inf_f32() {
+    f32_mul(1e20, 1e20).await
+}
+
+#[tokio::test]
+async fn mul_normal_f32() {
+    f32_mul(1.5e10, -2.25e-3).await
+}
+
+const IDENTICAL_FUNCTIONS_MODULE: &str = r#"
+    (module
+        (func $f1 (param i32) (result i32)
+            (local.get 0)
+            (i32.const 7)
+            (i32.mul)
+        )
+        (func $f2 (param i32) (result i32)
+            (local.get 0)
+            (i32.const 7)
+            (i32.mul)
+        )
+        (func $f3 (param i32) (result i32)
+            (local.get 0)
+            (i32.const 7)
+            (i32.mul)
+        )
+        (export "f1" (func $f1))
+        (export "f2" (func $f2))
+        (export "f3" (func $f3))
+    )
+    "#;
+
+#[tokio::test]
+async fn identical_functions_f1() {
+    test_parity::<i32, i32>(IDENTICAL_FUNCTIONS_MODULE, "f1", 6).await
+}
+
+#[tokio::test]
+async fn identical_functions_f2() {
+    test_parity::<i32, i32>(IDENTICAL_FUNCTIONS_MODULE, "f2", -11).await
+}
+
+#[tokio::test]
+async fn identical_functions_f3() {
+    test_parity::<i32, i32>(IDENTICAL_FUNCTIONS_MODULE, "f3", 123456).await
+}
+
 /*#[tokio::test]
 async fn add_5_f64() {
     test_parity::<f64, f64>(
@@ -468,6 +671,64 @@ do_test!(nested_blocks_br_if(1));
 do_test!(nested_blocks_br_if(2));
 do_test!(nested_blocks_br_if(3));
 
+async fn nested_blocks_br_table(input: i32) {
+    test_parity::<i32, i32>(
+        r#"
+        (module
+            (func $f (param i32) (result i32)
+                (local i32)
+                (block
+                    (block
+                        (block
+                            (block
+                                (block
+                                    local.get 0
+                                    br_table 0 1 2 3 4
+                                )
+                                i32.const 10
+                                local.set 1
+                                br 3
+                            )
+                            i32.const 20
+                            local.set 1
+                            br 2
+                        )
+                        i32.const 30
+                        local.set 1
+                        br 1
+                    )
+                    i32.const 40
+                    local.set 1
+                    br 0
+                )
+                ;; Default target
+                local.get 1
+                i32.eqz
+                (if
+                    (then
+                        i32.const 50
+                        local.set 1
+                    )
+                )
+                local.get 1
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        input,
+    )
+    .await
+}
+
+do_test!(nested_blocks_br_table(0));
+do_test!(nested_blocks_br_table(1));
+do_test!(nested_blocks_br_table(2));
+do_test!(nested_blocks_br_table(3));
+do_test!(nested_blocks_br_table(4));
+do_test!(nested_blocks_br_table(5));
+do_test!(nested_blocks_br_table(100));
+
 async fn nested_if(input: i32) {
     test_parity::<i32, f32>(
         r#"
@@ -709,6 +970,131 @@ do_test!(write_then_read_memory_back(3));*/
 do_test!(write_then_read_memory_back(4));
 do_test!(write_then_read_memory_back(8));
 
+async fn memory_copy_overlapping(dst: i32, src: i32) {
+    test_parity_set::<(i32, i32), i32>(
+        &format!(
+            r#"
+            (module
+                (memory (data "\01\02\03\04\05\06\07\08\09\0a\0b\0c"))
+                (func $f (param $len i32) (param $read i32) (result i32)
+                    (i32.const {})
+                    (i32.const {})
+                    (local.get $len)
+                    (memory.copy)
+                    (local.get $read)
+                    (i32.load)
+                )
+                (export "foi" (func $f))
+            )
+            "#,
+            dst, src
+        ),
+        "foi",
+        (0..=9)
+            .flat_map(|len| [(len, 0), (len, 4), (len, 8)])
+            .collect(),
+    )
+    .await
+}
+
+// Forward copies read ahead of where they write, backward copies read behind
+do_test!(memory_copy_overlapping(0, 3));
+do_test!(memory_copy_overlapping(3, 0));
+do_test!(memory_copy_overlapping(1, 2));
+do_test!(memory_copy_overlapping(2, 1));
+
+async fn memory_fill_sub_range(read: i32) {
+    test_parity_set::<(i32, i32), i32>(
+        &format!(
+            r#"
+            (module
+                (memory (data "\01\02\03\04\05\06\07\08\09\0a\0b\0c"))
+                (func $f (param $address i32) (param $len i32) (result i32)
+                    (local.get $address)
+                    (i32.const 0xAB)
+                    (local.get $len)
+                    (memory.fill)
+                    (i32.const {})
+                    (i32.load)
+                )
+                (export "foi" (func $f))
+            )
+            "#,
+            read
+        ),
+        "foi",
+        (0..4)
+            .flat_map(|address| (0..=6).map(move |len| (address, len)))
+            .collect(),
+    )
+    .await
+}
+
+do_test!(memory_fill_sub_range(0));
+do_test!(memory_fill_sub_range(4));
+do_test!(memory_fill_sub_range(8));
+
+async fn memory_init_from_passive_segment(read: i32) {
+    test_parity_set::<(i32, i32, i32), i32>(
+        &format!(
+            r#"
+            (module
+                (memory 1)
+                (data $passive "\01\02\03\04\05\06\07\08")
+                (func $f (param $dst i32) (param $src i32) (param $len i32) (result i32)
+                    (local.get $dst)
+                    (local.get $src)
+                    (local.get $len)
+                    (memory.init $passive)
+                    (i32.const {})
+                    (i32.load)
+                )
+                (export "foi" (func $f))
+            )
+            "#,
+            read
+        ),
+        "foi",
+        // Includes sources which run off the end of the segment, which trap
+        (0..3)
+            .flat_map(|dst| {
+                (0..4).flat_map(move |src| [(dst, src, 0), (dst, src, 4), (dst, src, 6)])
+            })
+            .collect(),
+    )
+    .await
+}
+
+do_test!(memory_init_from_passive_segment(0));
+do_test!(memory_init_from_passive_segment(4));
+do_test!(memory_init_from_passive_segment(8));
+
+#[tokio::test]
+async fn memory_init_after_data_drop() {
+    // Dropped segments act as if they are empty, so only empty initializations succeed
+    test_parity_set::<i32, i32>(
+        r#"
+            (module
+                (memory 1)
+                (data $passive "\01\02\03\04")
+                (func $f (param $len i32) (result i32)
+                    (data.drop $passive)
+                    (i32.const 0)
+                    (i32.const 0)
+                    (local.get $len)
+                    (memory.init $passive)
+                    (i32.const 0)
+                    (i32.load)
+                )
+                (export "foi" (func $f))
+            )
+        "#,
+        "foi",
+        vec![0, 1, 4],
+    )
+    .await
+}
+
 #[tokio::test]
 async fn trap_out_of_loop() {
     test_parity::<(), ()>(
@@ -755,115 +1141,2377 @@ async fn double_trap_only_gives_first() {
     .await
 }
 
-async fn mandelbrot(locs: Vec<(f32, f32)>) {
-    test_parity_set::<_, f32>(
+/// A loop that traps on its 101st iteration, where the trap state is only read every `trap_check_interval`
+/// iterations
+async fn trap_out_of_loop_with_interval(trap_check_interval: u32) {
+    test_parity_with_tuneables::<(), ()>(
         r#"
-            (module
-                (func $f (param $x_0 f32) (param $y_0 f32) (param $max_iterations i32) (result f32)
-                    (local $a f32)
-                    (local $b f32)
-                    (local $iterations i32)
-
-                    ;; a = 0.0
-                    f32.const 0.0
-                    local.set $a
-                    ;; b = 0.0
-                    f32.const 0.0
-                    local.set $b
-
-                    ;; iterations = -1
-                    i32.const -1
-                    local.set $iterations
-
-                    (loop $inner
-                        ;; a_new = a * a - b * b + x0
-                        local.get $a
-                        local.get $a
-                        f32.mul
+        (module
+            (func $f
+                (local i32)
+                (local.set 0 (i32.const 100))
+                loop $LOOP
+                    (i32.div_s (i32.const 5) (local.get 0))
+                    drop
 
-                        local.get $b
-                        local.get $b
-                        f32.mul
+                    (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
 
-                        f32.sub
+                    br $LOOP
+                end
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        (),
+        wasm_gpu::Tuneables {
+            trap_check_interval,
+            ..wasm_gpu::Tuneables::default()
+        },
+    )
+    .await
+}
 
-                        local.get $x_0
-                        f32.add
+#[tokio::test]
+async fn trap_out_of_loop_checking_every_16() {
+    trap_out_of_loop_with_interval(16).await
+}
 
-                        ;; b_new = 2.0 * a * b + y0
-                        f32.const 2.0
-                        local.get $a
-                        f32.mul
-                        local.get $b
-                        f32.mul
+#[tokio::test]
+async fn trap_out_of_loop_checking_every_1000() {
+    trap_out_of_loop_with_interval(1000).await
+}
 
-                        local.get $y_0
-                        f32.add
+#[tokio::test]
+async fn call_helper_function() {
+    test_parity_set::<(i32, i32), i32>(
+        r#"
+        (module
+            (func $add (param i32 i32) (result i32)
+                (local.get 0)
+                (local.get 1)
+                (i32.add)
+            )
+            (func $f (param i32 i32) (result i32)
+                (local.get 0)
+                (local.get 1)
+                (call $add)
+                (i32.const 1)
+                (i32.add)
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        (-64..64).map(|i| (i, i * 3)).collect(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn call_trapping_helper_function() {
+    test_parity_set::<(i32, i32), i32>(
+        r#"
+        (module
+            (func $div (param i32 i32) (result i32)
+                (local.get 0)
+                (local.get 1)
+                (i32.div_s)
+            )
+            (func $f (param i32 i32) (result i32)
+                (local.get 0)
+                (local.get 1)
+                (call $div)
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        (-8..8).map(|i| (12, i)).collect(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn call_indirect_selected_at_runtime() {
+    // Entry 2 has the wrong signature, entry 3 is null and entry 4 is out of bounds
+    test_parity_set::<(i32, i32, i32), i32>(
+        r#"
+        (module
+            (type $binop (func (param i32 i32) (result i32)))
+            (table 4 funcref)
+            (elem (i32.const 0) $add $sub $neg)
+            (func $add (type $binop)
+                (local.get 0)
+                (local.get 1)
+                (i32.add)
+            )
+            (func $sub (type $binop)
+                (local.get 0)
+                (local.get 1)
+                (i32.sub)
+            )
+            (func $neg (param i32) (result i32)
+                (i32.const 0)
+                (local.get 0)
+                (i32.sub)
+            )
+            (func $f (param i32 i32 i32) (result i32)
+                (local.get 1)
+                (local.get 2)
+                (local.get 0)
+                (call_indirect (type $binop))
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        (0..5).flat_map(|i| [(i, 7, 3), (i, -2, 5)]).collect(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn call_indirect_from_dispatcher_of_same_type() {
+    // The dispatcher is in its own table at entry 2, so selecting it recurses until the stack is exhausted
+    test_parity_set::<(i32, i32, i32), i32>(
+        r#"
+        (module
+            (type $select (func (param i32 i32 i32) (result i32)))
+            (table 3 funcref)
+            (elem (i32.const 0) $add $sub $dispatch)
+            (func $add (type $select)
+                (local.get 1)
+                (local.get 2)
+                (i32.add)
+            )
+            (func $sub (type $select)
+                (local.get 1)
+                (local.get 2)
+                (i32.sub)
+            )
+            (func $dispatch (type $select)
+                (local.get 0)
+                (local.get 1)
+                (local.get 2)
+                (local.get 0)
+                (call_indirect (type $select))
+            )
+            (export "foi" (func $dispatch))
+        )
+        "#,
+        "foi",
+        (0..4).flat_map(|i| [(i, 7, 3), (i, -2, 5)]).collect(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn table_get_set_round_trip() {
+    // Entry 3 of $a is out of bounds, and the funcref read from $a is called through $b
+    test_parity_set::<i32, i32>(
+        r#"
+        (module
+            (type $nullary (func (result i32)))
+            (table $a 3 funcref)
+            (table $b 2 funcref)
+            (elem (table $a) (i32.const 0) func $one $two $three)
+            (func $one (type $nullary)
+                (i32.const 1)
+            )
+            (func $two (type $nullary)
+                (i32.const 2)
+            )
+            (func $three (type $nullary)
+                (i32.const 3)
+            )
+            (func $f (param $i i32) (result i32)
+                (table.set $b (i32.const 1) (table.get $a (local.get $i)))
+                (call_indirect $b (type $nullary) (i32.const 1))
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        (0..4).collect(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn active_element_segment_populates_table() {
+    // Entries 0, 1 and 4 are null and entry 5 is out of bounds
+    test_parity_set::<i32, i32>(
+        r#"
+        (module
+            (type $unary (func (param i32) (result i32)))
+            (table 5 funcref)
+            (elem (i32.const 2) func $double $square)
+            (func $double (type $unary)
+                (i32.add (local.get 0) (local.get 0))
+            )
+            (func $square (type $unary)
+                (i32.mul (local.get 0) (local.get 0))
+            )
+            (func $f (param $i i32) (result i32)
+                (call_indirect (type $unary) (i32.const 7) (local.get $i))
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        (0..6).collect(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn table_init_copies_passive_segment() {
+    // The last two references of the segment are copied to the start of the table, leaving entries 2 and 3 null
+    test_parity_set::<i32, i32>(
+        r#"
+        (module
+            (type $nullary (func (result i32)))
+            (table 4 funcref)
+            (elem $passive func $one $two $three)
+            (func $one (type $nullary)
+                (i32.const 1)
+            )
+            (func $two (type $nullary)
+                (i32.const 2)
+            )
+            (func $three (type $nullary)
+                (i32.const 3)
+            )
+            (func $f (param $i i32) (result i32)
+                (table.init $passive (i32.const 0) (i32.const 1) (i32.const 2))
+                (call_indirect (type $nullary) (local.get $i))
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        (0..5).collect(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn table_init_out_of_bounds_traps() {
+    // Inputs give the destination of a two reference copy, so only destinations 0 to 2 fit within the table
+    test_parity_set::<i32, i32>(
+        r#"
+        (module
+            (type $nullary (func (result i32)))
+            (table 4 funcref)
+            (elem $passive func $one $two)
+            (func $one (type $nullary)
+                (i32.const 1)
+            )
+            (func $two (type $nullary)
+                (i32.const 2)
+            )
+            (func $f (param $i i32) (result i32)
+                (table.init $passive (local.get $i) (i32.const 0) (i32.const 2))
+                (call_indirect (type $nullary) (local.get $i))
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        vec![0, 1, 2, 3, 4, -1],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn elem_drop_empties_passive_segment() {
+    // Once dropped, only empty copies from the segment succeed
+    test_parity_set::<i32, i32>(
+        r#"
+        (module
+            (type $nullary (func (result i32)))
+            (table 2 funcref)
+            (elem $passive func $one $two)
+            (func $one (type $nullary)
+                (i32.const 1)
+            )
+            (func $two (type $nullary)
+                (i32.const 2)
+            )
+            (func $f (param $length i32) (result i32)
+                (elem.drop $passive)
+                (table.init $passive (i32.const 0) (i32.const 0) (local.get $length))
+                (local.get $length)
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        (0..3).collect(),
+    )
+    .await
+}
+
+/// Values at and around the edges of every integer range that a saturating conversion might clamp to
+fn trunc_sat_f32_inputs() -> Vec<f32> {
+    vec![
+        0.0,
+        -0.0,
+        1.5,
+        -1.5,
+        0.75,
+        -0.75,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        f32::NAN,
+        -f32::NAN,
+        2147483520.0,
+        2147483648.0,
+        -2147483648.0,
+        -2147483904.0,
+        4294967040.0,
+        4294967296.0,
+        9223371487098961920.0,
+        9223372036854775808.0,
+        -9223372036854775808.0,
+        -9223373136366403584.0,
+        18446742974197923840.0,
+        18446744073709551616.0,
+        f32::MAX,
+        f32::MIN,
+    ]
+}
+
+#[tokio::test]
+async fn i32_trunc_sat_f32_s() {
+    test_parity_set::<f32, i32>(
+        r#"
+        (module
+            (func $f (param f32) (result i32)
+                (local.get 0)
+                (i32.trunc_sat_f32_s)
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        trunc_sat_f32_inputs(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn i32_trunc_sat_f32_u() {
+    test_parity_set::<f32, i32>(
+        r#"
+        (module
+            (func $f (param f32) (result i32)
+                (local.get 0)
+                (i32.trunc_sat_f32_u)
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        trunc_sat_f32_inputs(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn i64_trunc_sat_f32_s() {
+    test_parity_set::<f32, i64>(
+        r#"
+        (module
+            (func $f (param f32) (result i64)
+                (local.get 0)
+                (i64.trunc_sat_f32_s)
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        trunc_sat_f32_inputs(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn i64_trunc_sat_f32_u() {
+    test_parity_set::<f32, i64>(
+        r#"
+        (module
+            (func $f (param f32) (result i64)
+                (local.get 0)
+                (i64.trunc_sat_f32_u)
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        trunc_sat_f32_inputs(),
+    )
+    .await
+}
+
+/// Words chosen so that packed lanes of every width carry and borrow into their neighbours
+const V128_INT_LHS: &str = "i32x4 0xFFFFFFFF 0x7FFF80FF 0x00010203 0x80000001";
+const V128_INT_RHS: &str = "i32x4 0x00000001 0x00018001 0xFFFFFFFF 0x7FFFFFFF";
+const V128_F32_LHS: &str = "f32x4 1.5 -2.0 1e-40 3.0e38";
+const V128_F32_RHS: &str = "f32x4 0.25 4.0 1e-40 10.0";
+
+async fn v128_binop(instruction: &str, lhs: &str, rhs: &str, native_v128: bool) {
+    test_parity_with_tuneables::<(), (i32, i32, i32, i32)>(
+        &format!(
+            r#"
+            (module
+                (func $f (result i32 i32 i32 i32)
+                    (local $v v128)
+                    (local.set $v ({instruction} (v128.const {lhs}) (v128.const {rhs})))
+                    (i32x4.extract_lane 0 (local.get $v))
+                    (i32x4.extract_lane 1 (local.get $v))
+                    (i32x4.extract_lane 2 (local.get $v))
+                    (i32x4.extract_lane 3 (local.get $v))
+                )
+                (export "foi" (func $f))
+            )
+            "#
+        ),
+        "foi",
+        (),
+        wasm_gpu::Tuneables {
+            native_v128,
+            ..wasm_gpu::Tuneables::default()
+        },
+    )
+    .await
+}
+
+/// Runs each vector operation with both the scalar polyfill and native vectors
+macro_rules! v128_binop_tests {
+    ($($test_name:ident => $instruction:literal ($lhs:ident, $rhs:ident)),* $(,)?) => {
+        paste::paste! {
+            $(
+                #[tokio::test]
+                async fn [< $test_name _polyfill >]() {
+                    v128_binop($instruction, $lhs, $rhs, false).await
+                }
+                #[tokio::test]
+                async fn [< $test_name _native >]() {
+                    v128_binop($instruction, $lhs, $rhs, true).await
+                }
+            )*
+        }
+    };
+}
+
+v128_binop_tests! {
+    v128_and => "v128.and" (V128_INT_LHS, V128_INT_RHS),
+    v128_and_not => "v128.andnot" (V128_INT_LHS, V128_INT_RHS),
+    v128_or => "v128.or" (V128_INT_LHS, V128_INT_RHS),
+    v128_xor => "v128.xor" (V128_INT_LHS, V128_INT_RHS),
+    i8x16_add => "i8x16.add" (V128_INT_LHS, V128_INT_RHS),
+    i8x16_sub => "i8x16.sub" (V128_INT_LHS, V128_INT_RHS),
+    i16x8_add => "i16x8.add" (V128_INT_LHS, V128_INT_RHS),
+    i16x8_sub => "i16x8.sub" (V128_INT_LHS, V128_INT_RHS),
+    i16x8_mul => "i16x8.mul" (V128_INT_LHS, V128_INT_RHS),
+    i32x4_add => "i32x4.add" (V128_INT_LHS, V128_INT_RHS),
+    i32x4_sub => "i32x4.sub" (V128_INT_LHS, V128_INT_RHS),
+    i32x4_mul => "i32x4.mul" (V128_INT_LHS, V128_INT_RHS),
+    i64x2_add => "i64x2.add" (V128_INT_LHS, V128_INT_RHS),
+    i64x2_sub => "i64x2.sub" (V128_INT_LHS, V128_INT_RHS),
+    f32x4_add => "f32x4.add" (V128_F32_LHS, V128_F32_RHS),
+    f32x4_sub => "f32x4.sub" (V128_F32_LHS, V128_F32_RHS),
+    f32x4_mul => "f32x4.mul" (V128_F32_LHS, V128_F32_RHS),
+    f32x4_div => "f32x4.div" (V128_F32_LHS, V128_F32_RHS),
+}
+
+async fn v128_not(native_v128: bool) {
+    test_parity_with_tuneables::<(), (i32, i32, i32, i32)>(
+        &format!(
+            r#"
+            (module
+                (func $f (result i32 i32 i32 i32)
+                    (local $v v128)
+                    (local.set $v (v128.not (v128.const {V128_INT_LHS})))
+                    (i32x4.extract_lane 0 (local.get $v))
+                    (i32x4.extract_lane 1 (local.get $v))
+                    (i32x4.extract_lane 2 (local.get $v))
+                    (i32x4.extract_lane 3 (local.get $v))
+                )
+                (export "foi" (func $f))
+            )
+            "#
+        ),
+        "foi",
+        (),
+        wasm_gpu::Tuneables {
+            native_v128,
+            ..wasm_gpu::Tuneables::default()
+        },
+    )
+    .await
+}
+do_test!(v128_not(false));
+do_test!(v128_not(true));
+
+#[tokio::test]
+async fn v128_load_replace_lane_store() {
+    test_parity_set::<(i32, i32), (i32, i32, i32, i32)>(
+        r#"
+            (module
+                (memory 1)
+                (data (i32.const 0) "\01\00\00\00\02\00\00\00\03\00\00\00\04\00\00\00\05\00\00\00")
+                (func $f (param $address i32) (param $lane_value i32) (result i32 i32 i32 i32)
+                    (v128.store offset=32
+                        (i32.const 0)
+                        (i32x4.replace_lane 2
+                            (v128.load (local.get $address))
+                            (local.get $lane_value)
+                        )
+                    )
+                    (i32.load (i32.const 32))
+                    (i32.load (i32.const 36))
+                    (i32.load (i32.const 40))
+                    (i32.load (i32.const 44))
+                )
+                (export "foi" (func $f))
+            )
+        "#,
+        "foi",
+        vec![(0, 99), (4, -1), (1, 0x7FFFFFFF), (65520, 7)],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn i32x4_splat() {
+    test_parity_set::<i32, (i32, i32, i32, i32)>(
+        r#"
+            (module
+                (func $f (param $value i32) (result i32 i32 i32 i32)
+                    (local $v v128)
+                    (local.set $v (i32x4.add (i32x4.splat (local.get $value)) (v128.const i32x4 0 1 2 3)))
+                    (i32x4.extract_lane 0 (local.get $v))
+                    (i32x4.extract_lane 1 (local.get $v))
+                    (i32x4.extract_lane 2 (local.get $v))
+                    (i32x4.extract_lane 3 (local.get $v))
+                )
+                (export "foi" (func $f))
+            )
+        "#,
+        "foi",
+        vec![0, 1, -1, 0x7FFFFFFF],
+    )
+    .await
+}
+
+/// Runs an atomic read-modify-write instruction on a counter in memory that is shared by every invocation
+async fn shared_counter_rmw(instruction: &str, initial: i32, operand: i32) -> (Vec<i32>, i32) {
+    const INVOCATIONS: usize = 16;
+
+    let results = call_all_in_turn::<i32>(
+        &format!(
+            r#"
+            (module
+                (memory 1)
+                (data (i32.const 4) "\{initial:02x}\{:02x}\{:02x}\{:02x}")
+                (func $update (result i32)
+                    ({instruction} offset=4 (i32.const 0) (i32.const {operand}))
+                )
+                (func $read (result i32)
+                    (i32.load (i32.const 4))
+                )
+                (export "update" (func $update))
+                (export "read" (func $read))
+            )
+            "#,
+            (initial >> 8) & 0xFF,
+            (initial >> 16) & 0xFF,
+            (initial >> 24) & 0xFF,
+            initial = initial & 0xFF,
+        ),
+        &["update", "read"],
+        INVOCATIONS,
+        wasm_gpu::WasmFeatures {
+            threads: true,
+            ..wasm_gpu::WasmFeatures::default()
+        },
+        wasm_gpu::Tuneables {
+            disjoint_memory: false,
+            ..wasm_gpu::Tuneables::default()
+        },
+    )
+    .await;
+
+    let [updates, reads]: [Vec<_>; 2] = results.try_into().unwrap();
+    let previous_values = updates.into_iter().map(Result::unwrap).collect();
+
+    // Every invocation reads the same final value
+    let reads: Vec<_> = reads.into_iter().map(Result::unwrap).collect();
+    assert!(reads.iter().all(|read| *read == reads[0]), "{:?}", reads);
+
+    (previous_values, reads[0])
+}
+
+#[tokio::test]
+async fn atomic_rmw_add_shared_counter() {
+    let (mut previous_values, final_value) = shared_counter_rmw("i32.atomic.rmw.add", 0, 1).await;
+
+    // Every invocation saw a distinct count
+    previous_values.sort();
+    assert_eq!(previous_values, (0..16).collect::<Vec<_>>());
+    assert_eq!(final_value, 16);
+}
+
+#[tokio::test]
+async fn atomic_rmw_sub_shared_counter() {
+    let (mut previous_values, final_value) = shared_counter_rmw("i32.atomic.rmw.sub", 100, 3).await;
+
+    previous_values.sort();
+    assert_eq!(
+        previous_values,
+        (0..16).rev().map(|i| 100 - 3 * i).collect::<Vec<_>>()
+    );
+    assert_eq!(final_value, 100 - 3 * 16);
+}
+
+#[tokio::test]
+async fn atomic_rmw_or_shared_counter() {
+    let (_, final_value) = shared_counter_rmw("i32.atomic.rmw.or", 0x100, 0x0F).await;
+    assert_eq!(final_value, 0x10F);
+}
+
+#[tokio::test]
+async fn atomic_rmw_xchg_shared_counter() {
+    let (previous_values, final_value) = shared_counter_rmw("i32.atomic.rmw.xchg", 5, 9).await;
+
+    // Only the first invocation to exchange saw the initial value
+    assert_eq!(
+        previous_values.iter().filter(|value| **value == 5).count(),
+        1
+    );
+    assert_eq!(
+        previous_values.iter().filter(|value| **value == 9).count(),
+        15
+    );
+    assert_eq!(final_value, 9);
+}
+
+#[tokio::test]
+async fn atomic_cmpxchg_spinlock_acquire() {
+    // The lock is never released, and invocations aren't guaranteed to make progress independently on a GPU, so
+    // each invocation only spins a bounded number of times before giving up
+    let results = call_all_in_turn::<i32>(
+        r#"
+            (module
+                (memory 1)
+                (func $acquire (result i32)
+                    (local $attempts i32)
+                    (loop $spin
+                        (if (i32.eqz (i32.atomic.rmw.cmpxchg (i32.const 8) (i32.const 0) (i32.const 1)))
+                            (then (return (i32.const 1)))
+                        )
+                        (local.set $attempts (i32.add (local.get $attempts) (i32.const 1)))
+                        (br_if $spin (i32.lt_u (local.get $attempts) (i32.const 64)))
+                    )
+                    (i32.const 0)
+                )
+                (func $read (result i32)
+                    (i32.load (i32.const 8))
+                )
+                (export "acquire" (func $acquire))
+                (export "read" (func $read))
+            )
+        "#,
+        &["acquire", "read"],
+        2,
+        wasm_gpu::WasmFeatures {
+            threads: true,
+            ..wasm_gpu::WasmFeatures::default()
+        },
+        wasm_gpu::Tuneables {
+            disjoint_memory: false,
+            ..wasm_gpu::Tuneables::default()
+        },
+    )
+    .await;
+
+    let [acquired, reads]: [Vec<_>; 2] = results.try_into().unwrap();
+    let mut acquired: Vec<_> = acquired.into_iter().map(Result::unwrap).collect();
+
+    // Exactly one invocation holds the lock
+    acquired.sort();
+    assert_eq!(acquired, vec![0, 1]);
+    for read in reads {
+        assert_eq!(read.unwrap(), 1);
+    }
+}
+
+#[tokio::test]
+async fn nonblocking_atomic_wait_and_notify() {
+    let results = call_all_in_turn::<i32>(
+        r#"
+            (module
+                (memory 1 1 shared)
+                (data (i32.const 8) "\07\00\00\00\00\00\00\00")
+                (func $wait32_equal (result i32)
+                    (memory.atomic.wait32 (i32.const 8) (i32.const 7) (i64.const 0))
+                )
+                (func $wait32_not_equal (result i32)
+                    (memory.atomic.wait32 (i32.const 8) (i32.const 3) (i64.const 0))
+                )
+                (func $wait64_equal (result i32)
+                    (memory.atomic.wait64 (i32.const 8) (i64.const 7) (i64.const -1))
+                )
+                (func $wait64_not_equal (result i32)
+                    (memory.atomic.wait64 (i32.const 8) (i64.const 0x100000007) (i64.const -1))
+                )
+                (func $notify (result i32)
+                    (memory.atomic.notify (i32.const 8) (i32.const 1))
+                )
+                (export "wait32_equal" (func $wait32_equal))
+                (export "wait32_not_equal" (func $wait32_not_equal))
+                (export "wait64_equal" (func $wait64_equal))
+                (export "wait64_not_equal" (func $wait64_not_equal))
+                (export "notify" (func $notify))
+            )
+        "#,
+        &[
+            "wait32_equal",
+            "wait32_not_equal",
+            "wait64_equal",
+            "wait64_not_equal",
+            "notify",
+        ],
+        4,
+        wasm_gpu::WasmFeatures {
+            threads: true,
+            ..wasm_gpu::WasmFeatures::default()
+        },
+        wasm_gpu::Tuneables {
+            disjoint_memory: false,
+            permit_nonblocking_atomics: true,
+            ..wasm_gpu::Tuneables::default()
+        },
+    )
+    .await;
+
+    // Waits never block, so report "timed-out" (2) if the value was expected and "not-equal" (1) otherwise, and
+    // notifies never wake anything
+    let expected = [2, 1, 2, 1, 0];
+    for (got, expected) in results.into_iter().zip(expected) {
+        for got in got {
+            assert_eq!(got.unwrap(), expected);
+        }
+    }
+}
+
+#[tokio::test]
+async fn canonicalize_nans_from_arithmetic() {
+    let results = call_all_in_turn::<i32>(
+        r#"
+            (module
+                (memory 1)
+                ;; A positive signalling NaN, a negative signalling NaN with a payload, and 1.0
+                (data (i32.const 0) "\01\00\a0\7f\05\00\a0\ff\00\00\80\3f")
+                (func $bits (param $value f32) (result i32)
+                    (f32.store (i32.const 16) (local.get $value))
+                    (i32.load (i32.const 16))
+                )
+                (func $add (result i32)
+                    (call $bits (f32.add (f32.load (i32.const 0)) (f32.load (i32.const 8))))
+                )
+                (func $sub (result i32)
+                    (call $bits (f32.sub (f32.load (i32.const 8)) (f32.load (i32.const 4))))
+                )
+                (func $mul (result i32)
+                    (call $bits (f32.mul (f32.load (i32.const 4)) (f32.load (i32.const 8))))
+                )
+                (func $div (result i32)
+                    (call $bits (f32.div (f32.load (i32.const 0)) (f32.load (i32.const 4))))
+                )
+                (func $zero_div_zero (result i32)
+                    (call $bits (f32.div (f32.const 0) (f32.const -0)))
+                )
+                (export "add" (func $add))
+                (export "sub" (func $sub))
+                (export "mul" (func $mul))
+                (export "div" (func $div))
+                (export "zero_div_zero" (func $zero_div_zero))
+            )
+        "#,
+        &["add", "sub", "mul", "div", "zero_div_zero"],
+        4,
+        wasm_gpu::WasmFeatures::default(),
+        wasm_gpu::Tuneables {
+            fp_options: wasm_gpu::FloatingPointOptions {
+                canonicalize_nans: true,
+                ..wasm_gpu::FloatingPointOptions::default()
+            },
+            ..wasm_gpu::Tuneables::default()
+        },
+    )
+    .await;
+
+    for got in results.into_iter().flatten() {
+        assert_eq!(got.unwrap() as u32, 0x7fc00000);
+    }
+}
+
+/// Gives the bits of the NaNs produced by f64 arithmetic on signalling NaNs and by `0 / -0`, for each of `add`,
+/// `sub`, `mul`, `div` and `zero_div_zero`
+async fn f64_nans_from_arithmetic(canonicalize_nans: bool) -> Vec<u64> {
+    let results = call_all_in_turn::<i64>(
+        r#"
+            (module
+                (memory 1)
+                ;; A positive signalling NaN, a negative signalling NaN with a payload, and 1.0
+                (data (i32.const 0) "\01\00\00\00\00\00\f0\7f\05\00\00\00\00\00\f0\ff\00\00\00\00\00\00\f0\3f")
+                (func $add (result i64)
+                    (i64.reinterpret_f64 (f64.add (f64.load (i32.const 0)) (f64.load (i32.const 16))))
+                )
+                (func $sub (result i64)
+                    (i64.reinterpret_f64 (f64.sub (f64.load (i32.const 16)) (f64.load (i32.const 8))))
+                )
+                (func $mul (result i64)
+                    (i64.reinterpret_f64 (f64.mul (f64.load (i32.const 8)) (f64.load (i32.const 16))))
+                )
+                (func $div (result i64)
+                    (i64.reinterpret_f64 (f64.div (f64.load (i32.const 0)) (f64.load (i32.const 8))))
+                )
+                (func $zero_div_zero (result i64)
+                    (i64.reinterpret_f64 (f64.div (f64.const 0) (f64.const -0)))
+                )
+                (export "add" (func $add))
+                (export "sub" (func $sub))
+                (export "mul" (func $mul))
+                (export "div" (func $div))
+                (export "zero_div_zero" (func $zero_div_zero))
+            )
+        "#,
+        &["add", "sub", "mul", "div", "zero_div_zero"],
+        4,
+        wasm_gpu::WasmFeatures::default(),
+        wasm_gpu::Tuneables {
+            fp_options: wasm_gpu::FloatingPointOptions {
+                canonicalize_nans,
+                ..wasm_gpu::FloatingPointOptions::default()
+            },
+            ..wasm_gpu::Tuneables::default()
+        },
+    )
+    .await;
+
+    results
+        .into_iter()
+        .map(|invocations| {
+            let bits: Vec<_> = invocations
+                .into_iter()
+                .map(|got| got.unwrap() as u64)
+                .collect();
+            assert!(bits.iter().all(|got| *got == bits[0]), "{:x?}", bits);
+            bits[0]
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn f64_canonicalize_nans_from_arithmetic() {
+    let nans = f64_nans_from_arithmetic(true).await;
+    assert_eq!(nans, vec![0x7ff8000000000000; 5]);
+}
+
+#[tokio::test]
+async fn f64_propagate_nans_from_arithmetic() {
+    // NaN operands are made quiet and propagated, with the left hand side taking priority
+    let nans = f64_nans_from_arithmetic(false).await;
+    assert_eq!(
+        nans,
+        vec![
+            0x7ff8000000000001,
+            0xfff8000000000005,
+            0xfff8000000000005,
+            0x7ff8000000000001,
+            0x7ff8000000000000,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn f32_copysign() {
+    // Compare bit patterns, since NaNs are never equal
+    test_parity_set::<(f32, f32), i32>(
+        r#"
+            (module
+                (memory 1)
+                (func $f (param $lhs f32) (param $rhs f32) (result i32)
+                    (f32.store (i32.const 0) (f32.copysign (local.get $lhs) (local.get $rhs)))
+                    (i32.load (i32.const 0))
+                )
+                (export "foi" (func $f))
+            )
+        "#,
+        "foi",
+        vec![
+            (0.0, -1.0),
+            (-0.0, 1.0),
+            (0.0, -0.0),
+            (-0.0, 0.0),
+            (1.5, -0.0),
+            (-2.0, 0.0),
+            (f32::NAN, -1.0),
+            (-f32::NAN, 1.0),
+            (3.0, f32::NAN),
+            (3.0, -f32::NAN),
+            (f32::INFINITY, -0.0),
+        ],
+    )
+    .await
+}
+
+/// Writes to addresses in different strides of disjoint memory, which must neither alias each other nor the memory
+/// of any other instance
+#[tokio::test]
+async fn disjoint_memory_strides_are_isolated() {
+    test_parity_set::<(i32, i32), (i32, i32, i32)>(
+        r#"
+            (module
+                (memory 1)
+                (func $f (param $a i32) (param $b i32) (result i32 i32 i32)
+                    (i32.store (i32.const 4) (local.get $a))
+                    (i32.store (i32.const 16) (local.get $b))
+                    (i32.store (i32.const 260) (i32.add (local.get $a) (local.get $b)))
+                    (i32.load (i32.const 4))
+                    (i32.load (i32.const 16))
+                    (i32.load (i32.const 260))
+                )
+                (export "foi" (func $f))
+            )
+        "#,
+        "foi",
+        (0..16).map(|i| (i, 100 * i)).collect(),
+    )
+    .await
+}
+
+/// Accesses which run off the end of each instance's memory must trap, without modifying that memory or the memory
+/// of any other instance
+async fn checked_out_of_bounds_accesses_trap(bounds_checks: wasm_gpu::BoundsCheckMode) {
+    const INVOCATIONS: usize = 8;
+
+    let results = call_all_in_turn::<i32>(
+        r#"
+            (module
+                (memory 1)
+                (func $mark (result i32)
+                    (i32.store (i32.const 65532) (i32.const 0x55))
+                    (i32.const 0)
+                )
+                (func $store_past_end (result i32)
+                    (i32.store (i32.const 65534) (i32.const -1))
+                    (i32.const 0)
+                )
+                (func $store_wrapped (result i32)
+                    (i32.store offset=8 (i32.const -4) (i32.const -1))
+                    (i32.const 0)
+                )
+                (func $load_past_end (result i32)
+                    (i32x4.extract_lane 0 (v128.load (i32.const 65530)))
+                )
+                (func $read (result i32)
+                    (i32.add (i32.load (i32.const 65532)) (i32.load (i32.const 4)))
+                )
+                (export "mark" (func $mark))
+                (export "store_past_end" (func $store_past_end))
+                (export "store_wrapped" (func $store_wrapped))
+                (export "load_past_end" (func $load_past_end))
+                (export "read" (func $read))
+            )
+        "#,
+        &[
+            "mark",
+            "store_past_end",
+            "store_wrapped",
+            "load_past_end",
+            "read",
+        ],
+        INVOCATIONS,
+        wasm_gpu::WasmFeatures::default(),
+        wasm_gpu::Tuneables {
+            bounds_checks,
+            ..wasm_gpu::Tuneables::default()
+        },
+    )
+    .await;
+
+    let [marks, stores_past_end, stores_wrapped, loads_past_end, reads]: [Vec<_>; 5] =
+        results.try_into().unwrap();
+    for mark in marks {
+        assert_eq!(mark.unwrap(), 0);
+    }
+    for trapping in [stores_past_end, stores_wrapped, loads_past_end] {
+        for result in trapping {
+            assert_eq!(result.unwrap_err(), wasmtime::Trap::MemoryOutOfBounds);
+        }
+    }
+    for read in reads {
+        assert_eq!(read.unwrap(), 0x55);
+    }
+}
+
+#[tokio::test]
+async fn restrict_out_of_bounds_accesses_trap() {
+    checked_out_of_bounds_accesses_trap(wasm_gpu::BoundsCheckMode::Restrict).await
+}
+
+#[tokio::test]
+async fn read_zero_skip_write_out_of_bounds_accesses_trap() {
+    checked_out_of_bounds_accesses_trap(wasm_gpu::BoundsCheckMode::ReadZeroSkipWrite).await
+}
+
+/// Invocations which don't fill a whole workgroup must still be run when the workgroup size isn't the default
+#[tokio::test]
+async fn small_workgroup_size_runs_every_invocation() {
+    const INVOCATIONS: usize = 100;
+
+    let results = call_all_in_turn::<i32>(
+        r#"
+            (module
+                (memory 1)
+                (func $increment (result i32)
+                    (i32.store (i32.const 0) (i32.add (i32.load (i32.const 0)) (i32.const 1)))
+                    (i32.load (i32.const 0))
+                )
+                (export "increment" (func $increment))
+            )
+        "#,
+        &["increment", "increment"],
+        INVOCATIONS,
+        wasm_gpu::WasmFeatures::default(),
+        wasm_gpu::Tuneables {
+            workgroup_size: 16,
+            ..wasm_gpu::Tuneables::default()
+        },
+    )
+    .await;
+
+    let [first, second]: [Vec<_>; 2] = results.try_into().unwrap();
+    assert_eq!(first.len(), INVOCATIONS);
+    for result in first {
+        assert_eq!(result.unwrap(), 1);
+    }
+    for result in second {
+        assert_eq!(result.unwrap(), 2);
+    }
+}
+
+async fn mandelbrot(locs: Vec<(f32, f32)>) {
+    test_parity_set::<_, f32>(
+        r#"
+            (module
+                (func $f (param $x_0 f32) (param $y_0 f32) (param $max_iterations i32) (result f32)
+                    (local $a f32)
+                    (local $b f32)
+                    (local $iterations i32)
+
+                    ;; a = 0.0
+                    f32.const 0.0
+                    local.set $a
+                    ;; b = 0.0
+                    f32.const 0.0
+                    local.set $b
+
+                    ;; iterations = -1
+                    i32.const -1
+                    local.set $iterations
+
+                    (loop $inner
+                        ;; a_new = a * a - b * b + x0
+                        local.get $a
+                        local.get $a
+                        f32.mul
+
+                        local.get $b
+                        local.get $b
+                        f32.mul
+
+                        f32.sub
+
+                        local.get $x_0
+                        f32.add
+
+                        ;; b_new = 2.0 * a * b + y0
+                        f32.const 2.0
+                        local.get $a
+                        f32.mul
+                        local.get $b
+                        f32.mul
+
+                        local.get $y_0
+                        f32.add
+
+                        ;; a = a_new; b = b_new
+                        local.set $b
+                        local.set $a
+
+                        ;; iterations += 1
+                        local.get $iterations
+                        i32.const 1
+                        i32.add
+                        local.set $iterations
+
+                        ;; loop while iterations < max_iterations && a * a + b * b <= 4.0
+                        local.get $iterations
+                        local.get $max_iterations
+                        i32.lt_s
+
+                        local.get $a
+                        local.get $a
+                        f32.mul
+
+                        local.get $b
+                        local.get $b
+                        f32.mul
+
+                        f32.add
+
+                        f32.const 4.0
+
+                        f32.le
+
+                        i32.and
+
+                        br_if $inner
+                    )
+
+                    local.get $iterations
+                    f32.convert_i32_s
+                    local.get $max_iterations
+                    f32.convert_i32_s
+                    f32.div
+                )
+                (export "foi" (func $f))
+            )
+            "#,
+        "foi",
+        locs.into_iter().map(|(x, y)| (x, y, 1024)).collect(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn mandelbrot_grid() {
+    const SIZE: i32 = 1024;
+
+    let locs = (-SIZE..SIZE)
+        .flat_map(|x| {
+            let x = x as f32 / SIZE as f32;
+            let x = x / 2.0;
+            (-SIZE..SIZE).map(move |y| {
+                let y = y as f32 / SIZE as f32;
+                let y = y / 2.0;
+
+                (x, y)
+            })
+        })
+        .collect();
+    mandelbrot(locs).await
+}
+
+/// Runs a binary i64 instruction over every pair of the given operands
+async fn i64_binary_op(op: &str, lhs_values: &[i64], rhs_values: &[i64]) {
+    test_parity_set::<(i64, i64), i64>(
+        &format!(
+            r#"
+            (module
+                (func $f (param i64 i64) (result i64)
+                    (local.get 0)
+                    (local.get 1)
+                    (i64.{})
+                )
+                (export "foi" (func $f))
+            )
+            "#,
+            op
+        ),
+        "foi",
+        lhs_values
+            .iter()
+            .flat_map(|lhs| rhs_values.iter().map(move |rhs| (*lhs, *rhs)))
+            .collect(),
+    )
+    .await
+}
+
+/// Values held in just the low word, just the high word and across both. Includes the operands of the divide by zero
+/// and `i64::MIN / -1` traps
+const I64_OPERANDS: [i64; 16] = [
+    0,
+    1,
+    -1,
+    2,
+    -2,
+    7,
+    -7,
+    4294967295,
+    4294967296,
+    -4294967296,
+    12345678901234,
+    -98765432109876,
+    i64::MAX,
+    i64::MAX - 1,
+    i64::MIN,
+    i64::MIN + 1,
+];
+
+#[tokio::test]
+async fn i64_div_s() {
+    i64_binary_op("div_s", &I64_OPERANDS, &I64_OPERANDS).await
+}
+
+#[tokio::test]
+async fn i64_div_u() {
+    i64_binary_op("div_u", &I64_OPERANDS, &I64_OPERANDS).await
+}
+
+#[tokio::test]
+async fn i64_rem_s() {
+    i64_binary_op("rem_s", &I64_OPERANDS, &I64_OPERANDS).await
+}
+
+#[tokio::test]
+async fn i64_rem_u() {
+    i64_binary_op("rem_u", &I64_OPERANDS, &I64_OPERANDS).await
+}
+
+/// Rotations by less than a word, by whole words and by more than the width, which wraps
+const I64_ROTATE_AMOUNTS: [i64; 10] = [0, 1, 4, 31, 32, 33, 63, 64, 65, -1];
+
+#[tokio::test]
+async fn i64_rotl() {
+    i64_binary_op("rotl", &I64_OPERANDS, &I64_ROTATE_AMOUNTS).await
+}
+
+#[tokio::test]
+async fn i64_rotr() {
+    i64_binary_op("rotr", &I64_OPERANDS, &I64_ROTATE_AMOUNTS).await
+}
+
+/// Runs a unary i64 instruction over each of the given operands
+async fn i64_unary_op(op: &str, values: &[i64]) {
+    test_parity_set::<i64, i64>(
+        &format!(
+            r#"
+            (module
+                (func $f (param i64) (result i64)
+                    (local.get 0)
+                    (i64.{})
+                )
+                (export "foi" (func $f))
+            )
+            "#,
+            op
+        ),
+        "foi",
+        values.to_vec(),
+    )
+    .await
+}
+
+/// Bits only in the low word, only in the high word, and spanning the boundary between the two
+const I64_BIT_COUNT_OPERANDS: [i64; 14] = [
+    0,
+    1,
+    0x7FFF_FFFF,
+    0x8000_0000,
+    0xFFFF_FFFF,
+    0x1_0000_0000,
+    0x8000_0000_0000,
+    0x7FFF_FFFF_0000_0000,
+    i64::MIN,
+    0x1_8000_0000,
+    0x0000_00FF_FF00_0000,
+    0x0123_4567_89AB_CDEF,
+    i64::MAX,
+    -1,
+];
+
+#[tokio::test]
+async fn i64_clz() {
+    i64_unary_op("clz", &I64_BIT_COUNT_OPERANDS).await
+}
+
+#[tokio::test]
+async fn i64_ctz() {
+    i64_unary_op("ctz", &I64_BIT_COUNT_OPERANDS).await
+}
+
+#[tokio::test]
+async fn i64_popcnt() {
+    i64_unary_op("popcnt", &I64_BIT_COUNT_OPERANDS).await
+}
+
+/// Runs a binary f64 instruction over every pair of the given operands, skipping any pair for which `host_op`
+/// gives a NaN since NaNs never compare equal
+async fn f64_binary_op(op: &str, host_op: fn(f64, f64) -> f64, values: &[f64]) {
+    test_parity_set::<(f64, f64), f64>(
+        &format!(
+            r#"
+            (module
+                (func $f (param f64 f64) (result f64)
+                    (local.get 0)
+                    (local.get 1)
+                    (f64.{})
+                )
+                (export "foi" (func $f))
+            )
+            "#,
+            op
+        ),
+        "foi",
+        values
+            .iter()
+            .flat_map(|lhs| values.iter().map(move |rhs| (*lhs, *rhs)))
+            .filter(|(lhs, rhs)| !host_op(*lhs, *rhs).is_nan())
+            .collect(),
+    )
+    .await
+}
+
+/// Zeros, infinities, subnormals and values at the edges of the finite range, along with values that don't have
+/// exact binary representations so that results need rounding
+const F64_OPERANDS: [f64; 20] = [
+    0.0,
+    -0.0,
+    1.0,
+    -1.0,
+    1.5,
+    0.1,
+    -0.3,
+    3.0,
+    123456.789,
+    -9.87654321e-7,
+    1e300,
+    -1e300,
+    1e-300,
+    f64::MIN_POSITIVE,
+    2.225073858507201e-308,
+    5e-324,
+    -1.5e-320,
+    f64::MAX,
+    f64::INFINITY,
+    f64::NEG_INFINITY,
+];
+
+#[tokio::test]
+async fn f64_add() {
+    f64_binary_op("add", |lhs, rhs| lhs + rhs, &F64_OPERANDS).await
+}
+
+#[tokio::test]
+async fn f64_sub() {
+    f64_binary_op("sub", |lhs, rhs| lhs - rhs, &F64_OPERANDS).await
+}
+
+#[tokio::test]
+async fn f64_mul() {
+    f64_binary_op("mul", |lhs, rhs| lhs * rhs, &F64_OPERANDS).await
+}
+
+#[tokio::test]
+async fn f64_div() {
+    f64_binary_op("div", |lhs, rhs| lhs / rhs, &F64_OPERANDS).await
+}
+
+/// Runs a binary f64 comparison over every pair of the given operands and NaN
+async fn f64_compare_op(op: &str) {
+    let values = F64_OPERANDS
+        .iter()
+        .copied()
+        .chain([f64::NAN])
+        .collect::<Vec<_>>();
+    test_parity_set::<(f64, f64), i32>(
+        &format!(
+            r#"
+            (module
+                (func $f (param f64 f64) (result i32)
+                    (local.get 0)
+                    (local.get 1)
+                    (f64.{})
+                )
+                (export "foi" (func $f))
+            )
+            "#,
+            op
+        ),
+        "foi",
+        values
+            .iter()
+            .flat_map(|lhs| values.iter().map(move |rhs| (*lhs, *rhs)))
+            .collect(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn f64_eq() {
+    f64_compare_op("eq").await
+}
+
+#[tokio::test]
+async fn f64_ne() {
+    f64_compare_op("ne").await
+}
+
+#[tokio::test]
+async fn f64_lt() {
+    f64_compare_op("lt").await
+}
+
+#[tokio::test]
+async fn f64_le() {
+    f64_compare_op("le").await
+}
+
+#[tokio::test]
+async fn f64_gt() {
+    f64_compare_op("gt").await
+}
+
+#[tokio::test]
+async fn f64_ge() {
+    f64_compare_op("ge").await
+}
+
+#[tokio::test]
+async fn f64_sort_four() {
+    test_parity_set::<(f64, f64, f64, f64), (f64, f64, f64, f64)>(
+        r#"
+        (module
+            (func $sort (param f64 f64 f64 f64) (result f64 f64 f64 f64)
+                (local f64)
+                ;; A sorting network, where each step swaps a pair of values if they are out of order
+                (if (f64.lt (local.get 1) (local.get 0))
+                    (then (local.set 4 (local.get 0)) (local.set 0 (local.get 1)) (local.set 1 (local.get 4))))
+                (if (f64.lt (local.get 3) (local.get 2))
+                    (then (local.set 4 (local.get 2)) (local.set 2 (local.get 3)) (local.set 3 (local.get 4))))
+                (if (f64.lt (local.get 2) (local.get 0))
+                    (then (local.set 4 (local.get 0)) (local.set 0 (local.get 2)) (local.set 2 (local.get 4))))
+                (if (f64.lt (local.get 3) (local.get 1))
+                    (then (local.set 4 (local.get 1)) (local.set 1 (local.get 3)) (local.set 3 (local.get 4))))
+                (if (f64.lt (local.get 2) (local.get 1))
+                    (then (local.set 4 (local.get 1)) (local.set 1 (local.get 2)) (local.set 2 (local.get 4))))
+                (local.get 0)
+                (local.get 1)
+                (local.get 2)
+                (local.get 3)
+            )
+            (export "sort" (func $sort))
+        )
+        "#,
+        "sort",
+        F64_OPERANDS
+            .windows(4)
+            .map(|window| (window[3], window[1], window[0], window[2]))
+            .chain([
+                (-0.0, 0.0, -0.0, 0.0),
+                (1.0, 1.0 + f64::EPSILON, 1.0 - f64::EPSILON, -1.0),
+                (f64::INFINITY, -5e-324, 5e-324, f64::NEG_INFINITY),
+            ])
+            .collect(),
+    )
+    .await
+}
+
+/// A module exporting a function that applies a single conversion instruction to its argument
+fn conversion_wat(instruction: &str, input_ty: &str, output_ty: &str) -> String {
+    format!(
+        r#"
+        (module
+            (func $f (param {input_ty}) (result {output_ty})
+                (local.get 0)
+                ({instruction})
+            )
+            (export "foi" (func $f))
+        )
+        "#
+    )
+}
+
+#[tokio::test]
+async fn f64_promote_f32() {
+    test_parity_set::<f32, f64>(
+        &conversion_wat("f64.promote_f32", "f32", "f64"),
+        "foi",
+        vec![
+            0.0,
+            -0.0,
+            1.0,
+            -0.1,
+            3.5e-40,
+            -f32::from_bits(1),
+            f32::MIN_POSITIVE,
+            f32::MAX,
+            f32::MIN,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+        ],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn f32_demote_f64() {
+    test_parity_set::<f64, f32>(
+        &conversion_wat("f32.demote_f64", "f64", "f32"),
+        "foi",
+        F64_OPERANDS
+            .iter()
+            .copied()
+            .chain([
+                // Halfway between two f32s, rounding to the even one in each direction
+                f64::from_bits(0x3FF0_0000_1000_0000),
+                f64::from_bits(0x3FF0_0000_3000_0000),
+                // Halfway between the largest f32 and the next power of two, so rounds up to infinity
+                f64::from_bits(0x47EF_FFFF_F000_0000),
+                f64::from(f32::MAX),
+                // Subnormal f32s
+                1e-40,
+                -7e-46,
+                // Too small to be an f32
+                1e-50,
+            ])
+            .collect(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn f64_convert_i32_s() {
+    test_parity_set::<i32, f64>(
+        &conversion_wat("f64.convert_i32_s", "i32", "f64"),
+        "foi",
+        vec![0, 1, -1, 7, -123456789, i32::MAX, i32::MIN],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn f64_convert_i32_u() {
+    test_parity_set::<i32, f64>(
+        &conversion_wat("f64.convert_i32_u", "i32", "f64"),
+        "foi",
+        vec![0, 1, -1, 7, -123456789, i32::MAX, i32::MIN],
+    )
+    .await
+}
+
+/// Includes values with more than 53 significant bits, which must be rounded
+const CONVERTED_I64S: [i64; 10] = [
+    0,
+    1,
+    -1,
+    0x1_0000_0000,
+    -123456789012,
+    (1 << 53) + 1,
+    (1 << 54) + 2,
+    0x0123_4567_89AB_CDEF,
+    i64::MAX,
+    i64::MIN,
+];
+
+#[tokio::test]
+async fn f64_convert_i64_s() {
+    test_parity_set::<i64, f64>(
+        &conversion_wat("f64.convert_i64_s", "i64", "f64"),
+        "foi",
+        CONVERTED_I64S.to_vec(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn f64_convert_i64_u() {
+    test_parity_set::<i64, f64>(
+        &conversion_wat("f64.convert_i64_u", "i64", "f64"),
+        "foi",
+        CONVERTED_I64S.to_vec(),
+    )
+    .await
+}
+
+/// Values within, at the edges of and beyond the ranges of each integer type, along with NaN
+const TRUNCATED_F64S: [f64; 20] = [
+    0.0,
+    -0.0,
+    0.75,
+    -0.75,
+    1.5,
+    -1.5,
+    123456.789,
+    -123456.789,
+    2147483647.9,
+    2147483648.0,
+    -2147483648.9,
+    -2147483649.0,
+    4294967295.5,
+    4294967296.0,
+    9223372036854774784.0,
+    9223372036854775808.0,
+    -9223372036854775808.0,
+    18446744073709549568.0,
+    f64::INFINITY,
+    f64::NAN,
+];
+
+#[tokio::test]
+async fn i32_trunc_f64_s() {
+    test_parity_set::<f64, i32>(
+        &conversion_wat("i32.trunc_f64_s", "f64", "i32"),
+        "foi",
+        TRUNCATED_F64S.to_vec(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn i32_trunc_f64_u() {
+    test_parity_set::<f64, i32>(
+        &conversion_wat("i32.trunc_f64_u", "f64", "i32"),
+        "foi",
+        TRUNCATED_F64S.to_vec(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn i64_trunc_f64_s() {
+    test_parity_set::<f64, i64>(
+        &conversion_wat("i64.trunc_f64_s", "f64", "i64"),
+        "foi",
+        TRUNCATED_F64S.to_vec(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn i64_trunc_f64_u() {
+    test_parity_set::<f64, i64>(
+        &conversion_wat("i64.trunc_f64_u", "f64", "i64"),
+        "foi",
+        TRUNCATED_F64S.to_vec(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn i32_reinterpret_f32() {
+    test_parity_set::<f32, i32>(
+        &conversion_wat("i32.reinterpret_f32", "f32", "i32"),
+        "foi",
+        vec![0.0, -0.0, 1.0, -2.5, 0.1, f32::MAX, f32::INFINITY],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn f32_reinterpret_i32() {
+    test_parity_set::<i32, f32>(
+        &conversion_wat("f32.reinterpret_i32", "i32", "f32"),
+        "foi",
+        vec![
+            0,
+            0x3F80_0000,
+            0xC020_0000u32 as i32,
+            0x7F80_0000,
+            0x3DCC_CCCD,
+        ],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn f32_reinterpret_round_trip() {
+    test_parity_set::<i32, i32>(
+        r#"
+        (module
+            (func $f (param i32) (result i32)
+                (i32.reinterpret_f32 (f32.reinterpret_i32 (local.get 0)))
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        vec![0, 1, -1, 0x3F80_0000, 0x4049_0FDB, i32::MAX, i32::MIN],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn i64_reinterpret_f64() {
+    test_parity_set::<f64, i64>(
+        &conversion_wat("i64.reinterpret_f64", "f64", "i64"),
+        "foi",
+        F64_OPERANDS.to_vec(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn f64_reinterpret_i64() {
+    test_parity_set::<i64, f64>(
+        &conversion_wat("f64.reinterpret_i64", "i64", "f64"),
+        "foi",
+        vec![
+            0,
+            0x3FF0_0000_0000_0000,
+            0xC004_0000_0000_0000u64 as i64,
+            0x7FF0_0000_0000_0000,
+            0x0000_0000_0000_0001,
+            0x4009_21FB_5444_2D18,
+        ],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn f64_reinterpret_round_trip() {
+    test_parity_set::<i64, i64>(
+        r#"
+        (module
+            (func $f (param i64) (result i64)
+                (i64.reinterpret_f64 (f64.reinterpret_i64 (local.get 0)))
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        vec![0, 1, -1, 0x3FF0_0000_0000_0000, i64::MAX, i64::MIN],
+    )
+    .await
+}
+
+/// Runs a binary i32 instruction over every pair of the given operands
+async fn i32_binary_op(op: &str, lhs_values: &[i32], rhs_values: &[i32]) {
+    test_parity_set::<(i32, i32), i32>(
+        &format!(
+            r#"
+            (module
+                (func $f (param i32 i32) (result i32)
+                    (local.get 0)
+                    (local.get 1)
+                    (i32.{})
+                )
+                (export "foi" (func $f))
+            )
+            "#,
+            op
+        ),
+        "foi",
+        lhs_values
+            .iter()
+            .flat_map(|lhs| rhs_values.iter().map(move |rhs| (*lhs, *rhs)))
+            .collect(),
+    )
+    .await
+}
+
+/// Includes the operands of the divide by zero and `i32::MIN / -1` traps
+const I32_DIVISION_OPERANDS: [i32; 11] = [
+    0,
+    1,
+    -1,
+    2,
+    -2,
+    7,
+    -7,
+    i32::MAX,
+    i32::MAX - 1,
+    i32::MIN,
+    i32::MIN + 1,
+];
+
+#[tokio::test]
+async fn i32_div_s() {
+    i32_binary_op("div_s", &I32_DIVISION_OPERANDS, &I32_DIVISION_OPERANDS).await
+}
+
+#[tokio::test]
+async fn i32_div_u() {
+    i32_binary_op("div_u", &I32_DIVISION_OPERANDS, &I32_DIVISION_OPERANDS).await
+}
+
+#[tokio::test]
+async fn i32_rem_s() {
+    i32_binary_op("rem_s", &I32_DIVISION_OPERANDS, &I32_DIVISION_OPERANDS).await
+}
+
+#[tokio::test]
+async fn i32_rem_u() {
+    i32_binary_op("rem_u", &I32_DIVISION_OPERANDS, &I32_DIVISION_OPERANDS).await
+}
+
+#[tokio::test]
+async fn i32_div_by_zero_traps() {
+    i32_binary_op("div_u", &[0, 1, i32::MIN], &[0]).await
+}
+
+#[tokio::test]
+async fn i32_signed_division_overflow_traps() {
+    i32_binary_op("div_s", &[i32::MIN], &[-1]).await
+}
+
+/// Includes the largest floats that fit in each integer, the smallest that don't, and NaN and `3e38` which must trap
+const TRUNCATED_F32S: [f32; 20] = [
+    0.0,
+    -0.0,
+    0.75,
+    -0.75,
+    1.5,
+    -1.5,
+    123456.79,
+    -123456.79,
+    2147483520.0,
+    2147483648.0,
+    -2147483648.0,
+    -2147483904.0,
+    4294967040.0,
+    4294967296.0,
+    9223371487098961920.0,
+    9223372036854775808.0,
+    -9223372036854775808.0,
+    18446742974197923840.0,
+    3e38,
+    f32::NAN,
+];
+
+#[tokio::test]
+async fn i32_trunc_f32_s() {
+    test_parity_set::<f32, i32>(
+        &conversion_wat("i32.trunc_f32_s", "f32", "i32"),
+        "foi",
+        TRUNCATED_F32S.to_vec(),
+    )
+    .await
+}
 
-                        ;; a = a_new; b = b_new
-                        local.set $b
-                        local.set $a
+#[tokio::test]
+async fn i32_trunc_f32_u() {
+    test_parity_set::<f32, i32>(
+        &conversion_wat("i32.trunc_f32_u", "f32", "i32"),
+        "foi",
+        TRUNCATED_F32S.to_vec(),
+    )
+    .await
+}
 
-                        ;; iterations += 1
-                        local.get $iterations
-                        i32.const 1
-                        i32.add
-                        local.set $iterations
+#[tokio::test]
+async fn i64_trunc_f32_s() {
+    test_parity_set::<f32, i64>(
+        &conversion_wat("i64.trunc_f32_s", "f32", "i64"),
+        "foi",
+        TRUNCATED_F32S.to_vec(),
+    )
+    .await
+}
 
-                        ;; loop while iterations < max_iterations && a * a + b * b <= 4.0
-                        local.get $iterations
-                        local.get $max_iterations
-                        i32.lt_s
+#[tokio::test]
+async fn i64_trunc_f32_u() {
+    test_parity_set::<f32, i64>(
+        &conversion_wat("i64.trunc_f32_u", "f32", "i64"),
+        "foi",
+        TRUNCATED_F32S.to_vec(),
+    )
+    .await
+}
 
-                        local.get $a
-                        local.get $a
-                        f32.mul
+#[tokio::test]
+async fn multi_value_if_takes_both_arms() {
+    test_parity_set::<i32, (i32, i32)>(
+        r#"
+            (module
+                (func $f (param i32) (result i32 i32)
+                    (if (result i32 i32) (local.get 0)
+                        (then
+                            (i32.const 1)
+                            (i32.const 2)
+                        )
+                        (else
+                            (i32.const 3)
+                            (i32.const 4)
+                        )
+                    )
+                )
+                (export "foi" (func $f))
+            )
+        "#,
+        "foi",
+        vec![0, 1, -1, 7],
+    )
+    .await
+}
 
-                        local.get $b
-                        local.get $b
-                        f32.mul
+#[tokio::test]
+async fn multi_value_if_with_params() {
+    test_parity_set::<(i32, i32), (i32, i32)>(
+        r#"
+            (module
+                (func $f (param i32 i32) (result i32 i32)
+                    (local.get 0)
+                    (local.get 1)
+                    (if (param i32 i32) (result i32 i32) (i32.lt_s (local.get 0) (local.get 1))
+                        (then
+                            (local.set 0)
+                            (local.get 0)
+                        )
+                        (else
+                            (i32.add)
+                            (i32.const 10)
+                        )
+                    )
+                )
+                (export "foi" (func $f))
+            )
+        "#,
+        "foi",
+        vec![(1, 2), (2, 1), (5, 5), (-3, 8)],
+    )
+    .await
+}
 
-                        f32.add
+#[tokio::test]
+async fn multi_value_if_without_else_passes_params_through() {
+    test_parity_set::<(i32, i32), (i32, i32)>(
+        r#"
+            (module
+                (func $f (param i32 i32) (result i32 i32)
+                    (local.get 0)
+                    (local.get 1)
+                    (if (param i32 i32) (result i32 i32) (i32.eqz (local.get 0))
+                        (then
+                            (drop)
+                            (drop)
+                            (i32.const 42)
+                            (i32.const 43)
+                        )
+                    )
+                )
+                (export "foi" (func $f))
+            )
+        "#,
+        "foi",
+        vec![(0, 1), (1, 2), (-1, 0)],
+    )
+    .await
+}
 
-                        f32.const 4.0
+#[tokio::test]
+async fn multi_value_if_arm_branches_out_early() {
+    test_parity_set::<i32, (i32, i32)>(
+        r#"
+            (module
+                (func $f (param i32) (result i32 i32)
+                    (if (result i32 i32) (i32.gt_s (local.get 0) (i32.const 10))
+                        (then
+                            (i32.const 5)
+                            (i32.const 6)
+                            (br_if 0 (i32.gt_s (local.get 0) (i32.const 20)))
+                            (drop)
+                            (drop)
+                            (i32.const 7)
+                            (i32.const 8)
+                        )
+                        (else
+                            (local.get 0)
+                            (i32.const 9)
+                        )
+                    )
+                )
+                (export "foi" (func $f))
+            )
+        "#,
+        "foi",
+        vec![0, 11, 21, -5],
+    )
+    .await
+}
 
-                        f32.le
+#[tokio::test]
+async fn local_tee_within_multi_value_if() {
+    test_parity_set::<i32, (i32, i32)>(
+        r#"
+            (module
+                (func $f (param i32) (result i32 i32)
+                    (local i32)
+                    (if (result i32 i32) (local.get 0)
+                        (then
+                            (local.tee 1 (i32.add (local.get 0) (i32.const 1)))
+                            (local.get 1)
+                        )
+                        (else
+                            (local.tee 1 (i32.const 100))
+                            (i32.add (local.get 1) (i32.const 1))
+                        )
+                    )
+                )
+                (export "foi" (func $f))
+            )
+        "#,
+        "foi",
+        vec![0, 1, 41],
+    )
+    .await
+}
 
-                        i32.and
+#[tokio::test]
+async fn block_with_params() {
+    test_parity_set::<(i32, i32), i32>(
+        r#"
+            (module
+                (func $f (param i32 i32) (result i32)
+                    (local.get 0)
+                    (local.get 1)
+                    (block (param i32 i32) (result i32)
+                        (i32.sub)
+                    )
+                )
+                (export "foi" (func $f))
+            )
+        "#,
+        "foi",
+        vec![(5, 3), (3, 5), (0, 0)],
+    )
+    .await
+}
 
-                        br_if $inner
+#[tokio::test]
+async fn loop_with_params() {
+    test_parity_set::<i32, i32>(
+        r#"
+            (module
+                (func $f (param i32) (result i32)
+                    ;; Sums the numbers from the parameter down to 1
+                    (i32.const 0)
+                    (local.get 0)
+                    (loop (param i32 i32) (result i32)
+                        (local.set 0)
+                        (i32.add (local.get 0))
+                        (local.tee 0 (i32.sub (local.get 0) (i32.const 1)))
+                        (br_if 0 (i32.gt_s (local.get 0) (i32.const 0)))
+                        (drop)
+                    )
+                )
+                (export "foi" (func $f))
+            )
+        "#,
+        "foi",
+        vec![0, 1, 4, 10],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn ref_is_null() {
+    test_parity_set::<i32, (i32, i32, i32)>(
+        r#"
+            (module
+                (func $g)
+                (func $f (param i32) (result i32 i32 i32)
+                    (ref.is_null (ref.func $g))
+                    (ref.is_null (ref.null extern))
+                    (ref.is_null
+                        (if (result funcref) (local.get 0)
+                            (then (ref.func $g))
+                            (else (ref.null func))
+                        )
                     )
+                )
+                (export "g" (func $g))
+                (export "foi" (func $f))
+            )
+        "#,
+        "foi",
+        vec![0, 1],
+    )
+    .await
+}
 
-                    local.get $iterations
-                    f32.convert_i32_s
-                    local.get $max_iterations
-                    f32.convert_i32_s
-                    f32.div
+#[tokio::test]
+async fn typed_select_between_funcrefs() {
+    test_parity_set::<i32, i32>(
+        r#"
+            (module
+                (type $t (func (result i32)))
+                (table 1 funcref)
+                (func $a (type $t) (i32.const 10))
+                (func $b (type $t) (i32.const 20))
+                (func $f (param i32) (result i32)
+                    (table.set 0 (i32.const 0)
+                        (select (result funcref) (ref.func $a) (ref.func $b) (local.get 0))
+                    )
+                    (call_indirect (type $t) (i32.const 0))
+                )
+                (export "a" (func $a))
+                (export "b" (func $b))
+                (export "foi" (func $f))
+            )
+        "#,
+        "foi",
+        vec![0, 1, -1],
+    )
+    .await
+}
+
+/// Signed zeros, infinities, subnormals and values at the edges of the normal range
+const F32_EDGE_OPERANDS: [f32; 12] = [
+    0.0,
+    -0.0,
+    1.0,
+    -1.5,
+    0.1,
+    f32::MIN_POSITIVE,
+    1.0e-40,
+    -1.4e-45,
+    f32::MAX,
+    -f32::MAX,
+    f32::INFINITY,
+    f32::NEG_INFINITY,
+];
+
+/// Pairs of non-NaN operands with random bits from a fixed seed. Half of the pairs only differ in their lower
+/// fraction bits, so that subtraction cancels most of the significand
+fn random_f32_operand_pairs(count: usize) -> Vec<(f32, f32)> {
+    let mut rng = StdRng::seed_from_u64(0xF32);
+    let mut pairs = Vec::new();
+    while pairs.len() < count {
+        let lhs = f32::from_bits(rng.next_u32());
+        let rhs = if rng.next_u32() % 2 == 0 {
+            f32::from_bits(rng.next_u32())
+        } else {
+            f32::from_bits(lhs.to_bits() ^ (rng.next_u32() & 0x000F_FFFF))
+        };
+        if !lhs.is_nan() && !rhs.is_nan() {
+            pairs.push((lhs, rhs));
+        }
+    }
+    pairs
+}
+
+/// Runs a binary f32 instruction with `Tuneables::deterministic_f32` and compares the bits of the result with
+/// wasmtime, which uses the host's f32 instructions. Pairs for which `host_op` gives a NaN are skipped, since the
+/// payload of a NaN is only guaranteed to be canonical rather than to match the host's.
+async fn deterministic_f32_binary_op(op: &str, host_op: fn(f32, f32) -> f32) {
+    let edge_pairs = F32_EDGE_OPERANDS
+        .iter()
+        .flat_map(|lhs| F32_EDGE_OPERANDS.iter().map(move |rhs| (*lhs, *rhs)));
+    test_parity_set_with_tuneables::<(f32, f32), i32>(
+        &format!(
+            r#"
+            (module
+                (func $f (param f32 f32) (result i32)
+                    (i32.reinterpret_f32 (f32.{} (local.get 0) (local.get 1)))
                 )
                 (export "foi" (func $f))
             )
             "#,
+            op
+        ),
         "foi",
-        locs.into_iter().map(|(x, y)| (x, y, 1024)).collect(),
+        edge_pairs
+            .chain(random_f32_operand_pairs(1024))
+            .filter(|(lhs, rhs)| !host_op(*lhs, *rhs).is_nan())
+            .collect(),
+        wasm_gpu::Tuneables::deterministic_f32(),
     )
     .await
 }
 
 #[tokio::test]
-async fn mandelbrot_grid() {
-    const SIZE: i32 = 1024;
+async fn deterministic_f32_add() {
+    deterministic_f32_binary_op("add", |lhs, rhs| lhs + rhs).await
+}
 
-    let locs = (-SIZE..SIZE)
-        .flat_map(|x| {
-            let x = x as f32 / SIZE as f32;
-            let x = x / 2.0;
-            (-SIZE..SIZE).map(move |y| {
-                let y = y as f32 / SIZE as f32;
-                let y = y / 2.0;
+#[tokio::test]
+async fn deterministic_f32_sub() {
+    deterministic_f32_binary_op("sub", |lhs, rhs| lhs - rhs).await
+}
 
-                (x, y)
-            })
+#[tokio::test]
+async fn deterministic_f32_mul() {
+    deterministic_f32_binary_op("mul", |lhs, rhs| lhs * rhs).await
+}
+
+#[tokio::test]
+async fn deterministic_f32_div() {
+    deterministic_f32_binary_op("div", |lhs, rhs| lhs / rhs).await
+}
+
+#[tokio::test]
+async fn deterministic_f32_nans_are_canonical() {
+    let results = call_all_in_turn::<i32>(
+        r#"
+            (module
+                (func $inf_sub_inf (result i32)
+                    (i32.reinterpret_f32 (f32.sub (f32.const inf) (f32.const inf)))
+                )
+                (func $zero_mul_inf (result i32)
+                    (i32.reinterpret_f32 (f32.mul (f32.const -0) (f32.const inf)))
+                )
+                (func $zero_div_zero (result i32)
+                    (i32.reinterpret_f32 (f32.div (f32.const 0) (f32.const -0)))
+                )
+                (func $nan_add (result i32)
+                    (i32.reinterpret_f32 (f32.add (f32.const -nan:0x200001) (f32.const 1)))
+                )
+                (export "inf_sub_inf" (func $inf_sub_inf))
+                (export "zero_mul_inf" (func $zero_mul_inf))
+                (export "zero_div_zero" (func $zero_div_zero))
+                (export "nan_add" (func $nan_add))
+            )
+        "#,
+        &["inf_sub_inf", "zero_mul_inf", "zero_div_zero", "nan_add"],
+        4,
+        wasm_gpu::WasmFeatures::default(),
+        wasm_gpu::Tuneables::deterministic_f32(),
+    )
+    .await;
+
+    for got in results.into_iter().flatten() {
+        assert_eq!(got.unwrap() as u32, 0x7fc00000);
+    }
+}
+
+/// Runs a function with mixed width arguments and results over many instances with the given I/O layout
+async fn mixed_io_with_layout(io_layout: wasm_gpu::IoLayout) {
+    let mut rng = StdRng::seed_from_u64(0x10);
+    let inputs = (0..1024)
+        .map(|_| {
+            (
+                rng.next_u32() as i32,
+                rng.next_u64() as i64,
+                rng.next_u32() as i32,
+            )
         })
         .collect();
-    mandelbrot(locs).await
+    test_parity_set_with_tuneables::<(i32, i64, i32), (i64, i32, i64)>(
+        r#"
+        (module
+            (func $f (param i32 i64 i32) (result i64 i32 i64)
+                (i64.add (i64.extend_i32_s (local.get 0)) (local.get 1))
+                (i32.sub (local.get 2) (local.get 0))
+                (i64.mul (local.get 1) (i64.extend_i32_u (local.get 2)))
+            )
+            (export "foi" (func $f))
+        )
+        "#,
+        "foi",
+        inputs,
+        wasm_gpu::Tuneables {
+            io_layout,
+            ..wasm_gpu::Tuneables::default()
+        },
+    )
+    .await
+}
+
+#[tokio::test]
+async fn mixed_io_packed_layout() {
+    mixed_io_with_layout(wasm_gpu::IoLayout::Packed).await
+}
+
+#[tokio::test]
+async fn mixed_io_struct_of_arrays_layout() {
+    mixed_io_with_layout(wasm_gpu::IoLayout::StructOfArrays).await
+}
+
+/// Values around the sign bits of each of the widths that can be sign extended from
+const SIGN_EXTENSION_OPERANDS: [i64; 12] = [
+    0,
+    1,
+    0x7F,
+    0x80,
+    0xFF,
+    0x7FFF,
+    0x8000,
+    0xFFFF,
+    0x7FFF_FFFF,
+    0x8000_0000,
+    0xFFFF_FFFF,
+    0x1234_5678_8000_0080,
+];
+
+async fn i32_sign_extension(op: &str) {
+    test_parity_set::<(i32,), i32>(
+        &format!(
+            r#"
+            (module
+                (func $f (param i32) (result i32)
+                    (i32.{} (local.get 0))
+                )
+                (export "foi" (func $f))
+            )
+            "#,
+            op
+        ),
+        "foi",
+        SIGN_EXTENSION_OPERANDS
+            .iter()
+            .map(|value| (*value as i32,))
+            .collect(),
+    )
+    .await
+}
+
+/// Checks the low and high words of the result separately, so that a missing sign in the high word is reported
+/// alongside the low word that should have produced it
+async fn i64_sign_extension(op: &str) {
+    test_parity_set::<(i64,), (i32, i32)>(
+        &format!(
+            r#"
+            (module
+                (func $f (param i64) (result i32 i32)
+                    (local $res i64)
+                    (local.set $res (i64.{} (local.get 0)))
+                    (i32.wrap_i64 (local.get $res))
+                    (i32.wrap_i64 (i64.shr_u (local.get $res) (i64.const 32)))
+                )
+                (export "foi" (func $f))
+            )
+            "#,
+            op
+        ),
+        "foi",
+        SIGN_EXTENSION_OPERANDS
+            .iter()
+            .map(|value| (*value,))
+            .collect(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn i32_extend8_s() {
+    i32_sign_extension("extend8_s").await
+}
+
+#[tokio::test]
+async fn i32_extend16_s() {
+    i32_sign_extension("extend16_s").await
+}
+
+#[tokio::test]
+async fn i64_extend8_s() {
+    i64_sign_extension("extend8_s").await
+}
+
+#[tokio::test]
+async fn i64_extend16_s() {
+    i64_sign_extension("extend16_s").await
+}
+
+#[tokio::test]
+async fn i64_extend32_s() {
+    i64_sign_extension("extend32_s").await
 }